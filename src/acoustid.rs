@@ -0,0 +1,376 @@
+//! Song identification with Chromaprint fingerprints and the AcoustID service.
+//!
+//! An open alternative to Shazam/songrec: segments of the recording are
+//! fingerprinted with `fpcalc` (part of Chromaprint) and looked up at
+//! <https://acoustid.org>. Matches carry the MusicBrainz recording ID.
+//!
+//! AcoustID needs an application API key, taken from `ACOUSTID_API_KEY` or
+//! `api_key` in `acoustid.toml` (working directory, `~/.config/autorec/` or
+//! `/etc/autorec/`). Keys are free at <https://acoustid.org/new-application>.
+
+use std::path::PathBuf;
+use std::process::Command;
+
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use crate::album_identifier::{generate_default_timestamps, merge_consecutive_matches, IdentifiedSong};
+use crate::error::{Error, Result};
+use crate::http;
+use crate::progress::{ProgressSink, STAGE_IDENTIFY};
+use crate::rate_limiter::RateLimiter;
+use crate::wavfile::{extract_wav_segment, read_wav_header};
+
+const LOOKUP_URL: &str = "https://api.acoustid.org/v2/lookup";
+
+const USER_AGENT: &str = "HiFiBerryAutoRec/0.1 (https://github.com/hifiberry/autorec)";
+
+/// Length of the fingerprinted segments (seconds)
+pub const SEGMENT_SECONDS: f64 = 60.0;
+
+/// Matches with a lower score are ignored
+pub const MIN_SCORE: f64 = 0.5;
+
+/// A Chromaprint fingerprint as computed by `fpcalc`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Fingerprint {
+    pub duration: f64,
+    pub fingerprint: String,
+}
+
+/// AcoustID allows 3 requests per second
+pub fn create_rate_limiter() -> RateLimiter {
+    RateLimiter::shared("AcoustID", std::time::Duration::from_millis(340))
+}
+
+/// Find the AcoustID API key (environment, then acoustid.toml)
+pub fn load_api_key() -> Option<String> {
+    if let Ok(key) = std::env::var("ACOUSTID_API_KEY") {
+        if !key.trim().is_empty() {
+            return Some(key.trim().to_string());
+        }
+    }
+
+    let mut paths = vec![PathBuf::from("acoustid.toml")];
+    if let Some(home) = std::env::var_os("HOME") {
+        paths.push(PathBuf::from(home).join(".config/autorec/acoustid.toml"));
+    }
+    paths.push(PathBuf::from("/etc/autorec/acoustid.toml"));
+
+    paths.iter().find_map(|path| {
+        let table = std::fs::read_to_string(path).ok()?.parse::<toml::Table>().ok()?;
+        table.get("api_key")?.as_str().map(|k| k.to_string())
+    })
+}
+
+/// Parse the output of `fpcalc -json`
+pub fn parse_fpcalc_output(output: &str) -> Result<Fingerprint> {
+    let fingerprint: Fingerprint = serde_json::from_str(output)?;
+    if fingerprint.fingerprint.is_empty() {
+        return Err(Error::Decode("fpcalc returned an empty fingerprint".to_string()));
+    }
+    Ok(fingerprint)
+}
+
+/// Fingerprint an audio file with `fpcalc`
+pub fn fingerprint_file(path: &str) -> Result<Fingerprint> {
+    let output = Command::new("fpcalc")
+        .arg("-json")
+        .arg("-length")
+        .arg(format!("{}", SEGMENT_SECONDS as u32))
+        .arg(path)
+        .output()
+        .map_err(|e| Error::Backend(format!("Error running fpcalc (is chromaprint installed?): {}", e)))?;
+
+    if !output.status.success() {
+        return Err(Error::Decode(format!(
+            "fpcalc failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    parse_fpcalc_output(&String::from_utf8_lossy(&output.stdout))
+}
+
+#[derive(Debug, Deserialize)]
+struct LookupResponse {
+    status: String,
+    #[serde(default)]
+    error: Option<LookupError>,
+    #[serde(default)]
+    results: Vec<LookupResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LookupError {
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct LookupResult {
+    score: f64,
+    #[serde(default)]
+    recordings: Vec<Recording>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Recording {
+    id: String,
+    title: Option<String>,
+    #[serde(default)]
+    artists: Vec<Artist>,
+    #[serde(default)]
+    releasegroups: Vec<ReleaseGroup>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Artist {
+    name: String,
+    #[serde(default)]
+    joinphrase: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReleaseGroup {
+    title: Option<String>,
+    #[serde(rename = "type")]
+    kind: Option<String>,
+}
+
+/// Parse an AcoustID lookup response into the best matching song
+///
+/// Returns `Ok(None)` if nothing matched with at least [`MIN_SCORE`].
+pub fn parse_lookup_response(json: &str) -> Result<Option<IdentifiedSong>> {
+    let response: LookupResponse = serde_json::from_str(json)?;
+    if response.status != "ok" {
+        let message = response.error.map(|e| e.message).unwrap_or(response.status);
+        return Err(Error::Lookup(format!("AcoustID: {}", message)));
+    }
+
+    let best = response
+        .results
+        .iter()
+        .filter(|r| r.score >= MIN_SCORE)
+        .flat_map(|r| r.recordings.iter().map(move |rec| (r.score, rec)))
+        .filter(|(_, rec)| rec.title.is_some() && !rec.artists.is_empty())
+        .max_by(|a, b| a.0.total_cmp(&b.0));
+
+    Ok(best.map(|(_, recording)| {
+        let artist = recording
+            .artists
+            .iter()
+            .map(|a| format!("{}{}", a.name, a.joinphrase.as_deref().unwrap_or("")))
+            .collect::<String>();
+        // Prefer the album the recording appeared on over singles and compilations
+        let album = recording
+            .releasegroups
+            .iter()
+            .find(|g| g.kind.as_deref() == Some("Album"))
+            .or_else(|| recording.releasegroups.first())
+            .and_then(|g| g.title.clone());
+
+        IdentifiedSong {
+            timestamp: 0.0, // Will be set by caller
+            title: recording.title.clone().unwrap_or_default(),
+            artist,
+            album,
+            match_offsets: Vec::new(),
+            song_offset: None,
+            recording_id: Some(recording.id.clone()),
+            confidence: None,
+        }
+    }))
+}
+
+/// Look up a fingerprint at AcoustID
+pub fn lookup(fingerprint: &Fingerprint, api_key: &str) -> Result<Option<IdentifiedSong>> {
+    let duration = format!("{}", fingerprint.duration.round() as u64);
+    let response = http::call("AcoustID", || {
+        http::agent().post(LOOKUP_URL)
+            .set("User-Agent", USER_AGENT)
+            .send_form(&[
+                ("client", api_key),
+                ("format", "json"),
+                ("meta", "recordings releasegroups"),
+                ("duration", &duration),
+                ("fingerprint", &fingerprint.fingerprint),
+            ])
+            .map_err(Box::new)
+    });
+
+    // AcoustID reports errors (e.g. an invalid key) with a JSON body
+    let body = match response {
+        Ok(r) => r.into_string()?,
+        Err(e) => match *e {
+            ureq::Error::Status(_, r) => r.into_string()?,
+            e => return Err(e.into()),
+        },
+    };
+    parse_lookup_response(&body)
+}
+
+/// Fingerprint and look up `duration` seconds of a WAV file from `start`
+pub fn identify_segment(
+    wav_path: &str,
+    start: f64,
+    duration: f64,
+    api_key: &str,
+    rate_limiter: &mut RateLimiter,
+) -> Result<Option<IdentifiedSong>> {
+    let temp_file = std::env::temp_dir().join(format!(
+        "acoustid_segment_{}_{}.wav",
+        std::process::id(),
+        (start * 1000.0) as u64
+    ));
+    let temp_str = temp_file.to_string_lossy().to_string();
+
+    extract_wav_segment(wav_path, &temp_str, start, duration).map_err(Error::Decode)?;
+    let fingerprint = fingerprint_file(&temp_str);
+    let _ = std::fs::remove_file(&temp_file);
+    let fingerprint = fingerprint?;
+
+    rate_limiter.wait_if_needed();
+    match lookup(&fingerprint, api_key) {
+        Ok(song) => {
+            rate_limiter.report_success();
+            Ok(song.map(|mut s| {
+                s.timestamp = start;
+                s
+            }))
+        }
+        Err(e) => {
+            rate_limiter.report_failure();
+            Err(e)
+        }
+    }
+}
+
+/// Identify songs in a WAV file with AcoustID
+///
+/// Same interface as [`crate::album_identifier::identify_songs`]: returns the
+/// deduplicated songs and a log that is available even on error.
+pub fn identify_songs(
+    wav_path: &str,
+    timestamps: Option<Vec<f64>>,
+    progress: &mut dyn ProgressSink,
+) -> (std::result::Result<Vec<IdentifiedSong>, String>, String) {
+    let mut log = String::new();
+    let note = |log: &mut String, msg: String| {
+        info!("{}", msg);
+        log.push_str(&msg);
+        log.push('\n');
+    };
+
+    let api_key = match load_api_key() {
+        Some(key) => key,
+        None => {
+            let msg = "No AcoustID API key (set ACOUSTID_API_KEY or api_key in acoustid.toml)".to_string();
+            log.push_str(&msg);
+            log.push('\n');
+            return (Err(msg), log);
+        }
+    };
+
+    let timestamps = match timestamps {
+        Some(ts) => ts,
+        None => {
+            let header = std::fs::File::open(wav_path)
+                .map_err(|e| format!("Failed to open WAV file: {}", e))
+                .and_then(|f| read_wav_header(&mut std::io::BufReader::new(f)));
+            match header {
+                Ok(h) => {
+                    let frame_size = (h.bits_per_sample / 8) as f64 * h.num_channels as f64;
+                    let duration = h.data_size as f64 / (h.sample_rate as f64 * frame_size);
+                    generate_default_timestamps(duration, 60.0, 120.0)
+                }
+                Err(e) => {
+                    log.push_str(&e);
+                    log.push('\n');
+                    return (Err(e), log);
+                }
+            }
+        }
+    };
+
+    note(&mut log, format!("Identifying songs in: {} (AcoustID)", wav_path));
+    note(&mut log, format!("Using {} segment(s) of {:.0}s", timestamps.len(), SEGMENT_SECONDS));
+
+    let mut rate_limiter = create_rate_limiter();
+    let mut songs = Vec::new();
+    for (i, &timestamp) in timestamps.iter().enumerate() {
+        let msg = format!("Identifying segment at {}:{:02}...", (timestamp / 60.0) as u32, (timestamp % 60.0) as u32);
+        progress.progress(i as f32 * 100.0 / timestamps.len() as f32, STAGE_IDENTIFY, &msg);
+        note(&mut log, msg);
+        match identify_segment(wav_path, timestamp, SEGMENT_SECONDS, &api_key, &mut rate_limiter) {
+            Ok(Some(song)) => {
+                note(&mut log, format!("  Found: {} - {}", song.artist, song.title));
+                songs.push(song);
+            }
+            Ok(None) => note(&mut log, "  No match found".to_string()),
+            Err(e @ Error::Backend(_)) => {
+                // fpcalc is missing, no point in trying further segments
+                let msg = e.to_string();
+                log.push_str(&msg);
+                log.push('\n');
+                return (Err(msg), log);
+            }
+            Err(e) => {
+                let msg = format!("  Error: {}", e);
+                warn!("{}", e);
+                log.push_str(&msg);
+                log.push('\n');
+            }
+        }
+    }
+
+    progress.finish(STAGE_IDENTIFY);
+    (Ok(merge_consecutive_matches(&songs)), log)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_fpcalc_output() {
+        let fp = parse_fpcalc_output(r#"{"duration": 59.98, "fingerprint": "AQADtEmUaEkSRZEGAA"}"#).unwrap();
+        assert_eq!(fp.duration, 59.98);
+        assert_eq!(fp.fingerprint, "AQADtEmUaEkSRZEGAA");
+
+        assert!(parse_fpcalc_output(r#"{"duration": 1.0, "fingerprint": ""}"#).is_err());
+        assert!(matches!(parse_fpcalc_output("ERROR: bad file"), Err(Error::Json(_))));
+    }
+
+    #[test]
+    fn test_parse_lookup_response() {
+        let json = r#"{
+            "status": "ok",
+            "results": [
+                {"id": "low", "score": 0.3, "recordings": [
+                    {"id": "r0", "title": "Wrong", "artists": [{"name": "Nobody"}]}
+                ]},
+                {"id": "a1", "score": 0.92, "recordings": [
+                    {"id": "8f3471b5-7e6a-48da-86a9-c1c07a0f47ae", "title": "Midnight in a Perfect World",
+                     "artists": [{"name": "DJ Shadow", "joinphrase": " feat. "}, {"name": "Someone"}],
+                     "releasegroups": [
+                        {"title": "Midnight in a Perfect World", "type": "Single"},
+                        {"title": "Endtroducing.....", "type": "Album"}
+                     ]}
+                ]}
+            ]
+        }"#;
+        let song = parse_lookup_response(json).unwrap().unwrap();
+        assert_eq!(song.title, "Midnight in a Perfect World");
+        assert_eq!(song.artist, "DJ Shadow feat. Someone");
+        assert_eq!(song.album.as_deref(), Some("Endtroducing....."));
+        assert_eq!(song.recording_id.as_deref(), Some("8f3471b5-7e6a-48da-86a9-c1c07a0f47ae"));
+
+        // Only low scores or results without recordings
+        let json = r#"{"status": "ok", "results": [{"id": "x", "score": 0.95}, {"id": "y", "score": 0.2, "recordings": []}]}"#;
+        assert!(parse_lookup_response(json).unwrap().is_none());
+
+        let json = r#"{"status": "error", "error": {"code": 4, "message": "invalid API key"}}"#;
+        let err = parse_lookup_response(json).unwrap_err();
+        assert!(matches!(err, Error::Lookup(_)));
+        assert!(err.to_string().contains("invalid API key"));
+    }
+}
@@ -22,10 +22,11 @@
 use std::collections::{HashMap, HashSet};
 use std::error::Error;
 
+use tracing::info;
+
 use crate::album_identifier::IdentifiedSong;
 use crate::discogs::{self, DiscogsRelease, DiscogsSide};
 use crate::musicbrainz::{self, ExpectedTrack};
-use crate::rate_limiter::RateLimiter;
 
 // ── Input / output types ─────────────────────────────────────────────────────
 
@@ -80,14 +81,13 @@ pub fn find_album_for_files(
 
     // ── Step 1: Pool all identified songs ────────────────────────────────
     let pooled = pool_songs(files);
-    println!("Pooled {} unique song(s) from {} file(s)", pooled.len(), files.len());
+    info!("Pooled {} unique song(s) from {} file(s)", pooled.len(), files.len());
     for song in &pooled {
-        println!("  {} - {}", song.artist, song.title);
+        info!("  {} - {}", song.artist, song.title);
     }
-    println!();
 
     if pooled.is_empty() {
-        println!("No songs identified across any file");
+        info!("No songs identified across any file");
         return Ok(None);
     }
 
@@ -100,10 +100,10 @@ pub fn find_album_for_files(
     let mut discogs_release: Option<DiscogsRelease> = None;
 
     if !no_discogs {
-        println!("Searching Discogs with all songs (avg side duration {:.0}s)...", avg_duration);
+        info!("Searching Discogs with all songs (avg side duration {:.0}s)...", avg_duration);
         match discogs::find_album_by_songs(&pooled, avg_duration, true, verbose)? {
             Some(release) => {
-                println!("Discogs: found {} - {} ({} sides)",
+                info!("Discogs: found {} - {} ({} sides)",
                          release.artist, release.title, release.sides.len());
                 for side in &release.sides {
                     let dur_str = if side.total_duration > 0.0 {
@@ -111,14 +111,12 @@ pub fn find_album_for_files(
                     } else {
                         "no durations".to_string()
                     };
-                    println!("  Side {}: {} tracks ({})", side.label, side.tracks.len(), dur_str);
+                    info!("  Side {}: {} tracks ({})", side.label, side.tracks.len(), dur_str);
                 }
-                println!();
                 discogs_release = Some(release);
             }
             None => {
-                println!("Discogs: no match found");
-                println!();
+                info!("Discogs: no match found");
             }
         }
     }
@@ -135,13 +133,12 @@ pub fn find_album_for_files(
             let mut mb_tracks_by_side: HashMap<char, Vec<ExpectedTrack>> = HashMap::new();
 
             if needs_enrichment && !no_musicbrainz {
-                println!("Some sides have no duration data, enriching from MusicBrainz...");
+                info!("Some sides have no duration data, enriching from MusicBrainz...");
                 if let Some(enriched) = enrich_from_musicbrainz(
                     &release.artist, &release.title, release, verbose,
                 )? {
                     mb_tracks_by_side = enriched;
                 }
-                println!();
             }
 
             // Build results
@@ -198,7 +195,7 @@ pub fn find_album_for_files(
 
     // ── Step 4: Fallback to MusicBrainz ──────────────────────────────────
     if !no_musicbrainz {
-        println!("Trying MusicBrainz with all songs...");
+        info!("Trying MusicBrainz with all songs...");
         if let Some(result) = find_via_musicbrainz(files, &pooled, avg_duration, verbose)? {
             return Ok(Some(result));
         }
@@ -259,25 +256,24 @@ fn assign_files_to_sides<'a>(
     }
 
     if verbose {
-        println!("Assignment score matrix:");
-        print!("  {:>40}", "");
+        info!("Assignment score matrix:");
+        let mut header = format!("  {:>40}", "");
         for side in &release.sides {
-            print!("  Side {} ", side.label);
+            header.push_str(&format!("  Side {} ", side.label));
         }
-        println!();
+        info!("{}", header);
         for (fi, file) in files.iter().enumerate() {
             let name = std::path::Path::new(&file.path)
                 .file_name()
                 .and_then(|n| n.to_str())
                 .unwrap_or(&file.path);
             let short = if name.len() > 40 { &name[..40] } else { name };
-            print!("  {:>40}", short);
+            let mut row = format!("  {:>40}", short);
             for si in 0..n_sides {
-                print!("  {:>6.1}", scores[fi][si]);
+                row.push_str(&format!("  {:>6.1}", scores[fi][si]));
             }
-            println!();
+            info!("{}", row);
         }
-        println!();
     }
 
     // Greedy assignment: pick highest score, assign, remove both from pool
@@ -312,14 +308,13 @@ fn assign_files_to_sides<'a>(
             .file_name()
             .and_then(|n| n.to_str())
             .unwrap_or(&files[best_fi].path);
-        println!("  {} → Side {} (score {:.1})", name, side.label, best_score);
+        info!("  {} → Side {} (score {:.1})", name, side.label, best_score);
 
         assigned_files.insert(best_fi);
         assigned_sides.insert(best_si);
         assignments.push((best_fi, side));
     }
 
-    println!();
     assignments
 }
 
@@ -389,14 +384,11 @@ fn enrich_from_musicbrainz(
     discogs_release: &DiscogsRelease,
     verbose: bool,
 ) -> Result<Option<HashMap<char, Vec<ExpectedTrack>>>, Box<dyn Error>> {
-    let mut rl = RateLimiter::from_millis("MusicBrainz", 1100);
-
     let results = musicbrainz::search_release(artist, album_title, 10)?;
-    rl.wait_if_needed();
 
     if results.is_empty() {
         if verbose {
-            println!("  MusicBrainz: no releases found for enrichment");
+            info!("  MusicBrainz: no releases found for enrichment");
         }
         return Ok(None);
     }
@@ -413,9 +405,8 @@ fn enrich_from_musicbrainz(
     for result in candidates.iter().take(5) {
         let mb_sides = match musicbrainz::fetch_release_sides(&result.release_id) {
             Ok(s) => s,
-            Err(_) => { rl.wait_if_needed(); continue; }
+            Err(_) => continue,
         };
-        rl.wait_if_needed();
 
         // Flatten all MB tracks into one pool
         let all_mb_tracks: Vec<&ExpectedTrack> = mb_sides.iter()
@@ -496,7 +487,7 @@ fn enrich_from_musicbrainz(
                 if verbose {
                     let dur: f64 = side_tracks.iter().map(|t| t.length_seconds).sum();
                     let matched = side_tracks.iter().filter(|t| t.length_seconds > 0.0).count();
-                    println!("  MusicBrainz: Side {} — {}/{} tracks matched ({:.0}s)",
+                    info!("  MusicBrainz: Side {} — {}/{} tracks matched ({:.0}s)",
                              discogs_side.label, matched, side_tracks.len(), dur);
                 }
                 result_map.insert(discogs_side.label, side_tracks);
@@ -507,12 +498,12 @@ fn enrich_from_musicbrainz(
         let match_fraction = total_matched as f64 / total_discogs_tracks.max(1) as f64;
         if total_matched > 0 && match_fraction >= 0.5 {
             if verbose {
-                println!("  MusicBrainz: enriched {} side(s), matched {}/{} tracks from release {}",
+                info!("  MusicBrainz: enriched {} side(s), matched {}/{} tracks from release {}",
                          result_map.len(), total_matched, total_discogs_tracks, result.release_id);
             }
             return Ok(Some(result_map));
         } else if verbose {
-            println!("  MusicBrainz: only matched {}/{} tracks ({:.0}%) from release {} — skipping",
+            info!("  MusicBrainz: only matched {}/{} tracks ({:.0}%) from release {} — skipping",
                      total_matched, total_discogs_tracks, match_fraction * 100.0, result.release_id);
         }
     }
@@ -576,7 +567,7 @@ fn find_via_musicbrainz(
     // Try vinyl first, then all
     for vinyl_only in [true, false] {
         let label = if vinyl_only { "MusicBrainz (vinyl)" } else { "MusicBrainz (all)" };
-        println!("Trying {}...", label);
+        info!("Trying {}...", label);
 
         // Use the first file's duration as a rough guide for the search
         let avg_duration = files.iter().map(|f| f.music_duration).sum::<f64>() / files.len() as f64;
@@ -585,10 +576,10 @@ fn find_via_musicbrainz(
             pooled_songs, avg_duration, vinyl_only, verbose,
         )? {
             Some(r) => r,
-            None => { println!("{}: no match", label); continue; }
+            None => { info!("{}: no match", label); continue; }
         };
 
-        println!("{}: found {} - {}", label, best.artist, best.title);
+        info!("{}: found {} - {}", label, best.artist, best.title);
 
         let sides = musicbrainz::fetch_release_sides(&best.release_id)?;
         if sides.is_empty() {
@@ -629,7 +620,7 @@ fn find_via_musicbrainz(
 
             let name = std::path::Path::new(&files[*fi].path)
                 .file_name().and_then(|n| n.to_str()).unwrap_or(&files[*fi].path);
-            println!("  {} → Medium {} (score {:.1})", name, mb_side.position, score);
+            info!("  {} → Medium {} (score {:.1})", name, mb_side.position, score);
 
             results[*fi] = Some(FileSideResult {
                 path: files[*fi].path.clone(),
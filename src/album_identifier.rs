@@ -1,18 +1,106 @@
 use std::process::Command;
 use std::path::Path;
 use std::time::Duration;
-use std::thread;
 use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
 use crate::wavfile::{extract_wav_segment, read_wav_header};
+use crate::progress::{ProgressSink, STAGE_IDENTIFY};
 use crate::songrec_cache;
+use crate::http;
+use crate::fingerprint_db::FingerprintDb;
 use crate::rate_limiter::RateLimiter;
 
+/// Length of the clips sent to songrec (seconds)
+pub const CLIP_SECONDS: f64 = 30.0;
+
+/// The shared songrec (Shazam) rate limiter: one clip every 5 seconds
+pub fn songrec_rate_limiter() -> RateLimiter {
+    RateLimiter::shared("songrec", Duration::from_secs(5))
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IdentifiedSong {
     pub timestamp: f64,
     pub title: String,
     pub artist: String,
     pub album: Option<String>,
+    /// Recording positions (seconds) of every clip that matched this song,
+    /// in ascending order. Filled in by `identify_songs` when merging runs.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub match_offsets: Vec<f64>,
+    /// Position within the song (seconds) at which the clip matched, as
+    /// reported by Shazam. `timestamp - song_offset` estimates the song start.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub song_offset: Option<f64>,
+    /// MusicBrainz recording ID, if the identification service provides one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recording_id: Option<String>,
+    /// Share of the clips of a track region that agreed on this song (0–1),
+    /// set by `identify_songs_in_regions`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f64>,
+}
+
+impl IdentifiedSong {
+    /// Estimated start of the song in the recording, if Shazam reported an offset
+    pub fn estimated_start(&self) -> Option<f64> {
+        self.song_offset.map(|offset| (self.timestamp - offset).max(0.0))
+    }
+
+    /// Recording position of the last clip that matched this song
+    pub fn last_match(&self) -> f64 {
+        self.match_offsets.last().copied().unwrap_or(self.timestamp)
+    }
+
+    /// Weight of the song in votes: its confidence, 1 if unknown
+    pub fn weight(&self) -> f64 {
+        self.confidence.unwrap_or(1.0)
+    }
+
+    fn same_song(&self, other: &IdentifiedSong) -> bool {
+        self.artist.eq_ignore_ascii_case(&other.artist) && self.title.eq_ignore_ascii_case(&other.title)
+    }
+}
+
+/// Time window in which the boundary between two identified songs must lie
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoundaryWindow {
+    /// Index of the song that ends at this boundary
+    pub song_index: usize,
+    pub start: f64,
+    pub end: f64,
+    /// Start of the next song estimated from its Shazam match offset
+    pub expected: Option<f64>,
+}
+
+/// Derive boundary search windows from consecutive, differently identified songs.
+///
+/// The boundary between songs A and B lies after the start of the last clip
+/// that matched A and before the end of the first clip that matched B. If
+/// Shazam reported where in B the first clip matched, the window is narrowed
+/// to `±tolerance` seconds around the estimated start of B. Songs must be
+/// deduplicated (as returned by `identify_songs`).
+pub fn boundary_windows(songs: &[IdentifiedSong], clip_seconds: f64, tolerance: f64) -> Vec<BoundaryWindow> {
+    let mut windows = Vec::new();
+
+    for (i, pair) in songs.windows(2).enumerate() {
+        let (a, b) = (&pair[0], &pair[1]);
+        let mut start = a.last_match();
+        let mut end = b.timestamp + clip_seconds;
+        if end <= start {
+            continue;
+        }
+
+        let expected = b.estimated_start().filter(|&t| t >= start && t <= end);
+        if let Some(t) = expected {
+            start = start.max(t - tolerance);
+            end = end.min(t + tolerance);
+        }
+
+        windows.push(BoundaryWindow { song_index: i, start, end, expected });
+    }
+
+    windows
 }
 
 /// Result from song identification including log
@@ -22,14 +110,18 @@ pub struct IdentificationResult {
 }
 
 /// Identify songs at specific timestamps in a WAV file using songrec
-pub fn identify_songs_at_timestamps(wav_path: &str, timestamps: &[f64]) -> Result<IdentificationResult, String> {
+pub fn identify_songs_at_timestamps(
+    wav_path: &str,
+    timestamps: &[f64],
+    progress: &mut dyn ProgressSink,
+) -> Result<IdentificationResult, String> {
     let path = Path::new(wav_path);
     if !path.exists() {
         return Err(format!("WAV file not found: {}", wav_path));
     }
 
     let mut identified_songs = Vec::new();
-    let mut rate_limiter = RateLimiter::from_secs("songrec", 5);
+    let mut rate_limiter = songrec_rate_limiter();
     let mut log = String::new();
 
     // Load songrec cache
@@ -37,46 +129,60 @@ pub fn identify_songs_at_timestamps(wav_path: &str, timestamps: &[f64]) -> Resul
     let cache_size = cache.len();
     if cache_size > 0 {
         let msg = format!("Loaded songrec cache with {} entries", cache_size);
-        println!("{}", msg);
+        info!("{}", msg.trim_start());
         log.push_str(&msg);
         log.push('\n');
     }
+    let mut fpdb = FingerprintDb::open_default();
+    if let Some(db) = fpdb.as_ref().filter(|db| !db.is_empty()) {
+        log_line(&mut log, format!("Loaded fingerprint database with {} entries", db.len()));
+    }
 
-    for &timestamp in timestamps {
+    for (i, &timestamp) in timestamps.iter().enumerate() {
         let msg = format!("Identifying song at {}...", format_timestamp(timestamp));
-        println!("{}", msg);
+        progress.progress(i as f32 * 100.0 / timestamps.len() as f32, STAGE_IDENTIFY, &msg);
+        info!("{}", msg.trim_start());
         log.push_str(&msg);
         log.push('\n');
         
         // Extract 30-second segment using native WAV extraction
         let temp_file = format!("/tmp/songrec_segment_{}.wav", timestamp as u32);
         
-        if let Err(e) = extract_wav_segment(wav_path, &temp_file, timestamp, 30.0) {
+        if let Err(e) = extract_wav_segment(wav_path, &temp_file, timestamp, CLIP_SECONDS) {
             let msg = format!("  Error extracting segment: {}", e);
-            eprintln!("{}", msg);
+            warn!("{}", msg.trim_start());
             log.push_str(&msg);
             log.push('\n');
             continue;
         }
+
+        // Songs digitized before are found without a network call
+        if let Some(song_data) = fpdb.as_ref().and_then(|db| db.identify_clip(&temp_file, timestamp)) {
+            log_line(&mut log, format!("  Found in fingerprint database: {} - {}", song_data.artist, song_data.title));
+            identified_songs.push(song_data);
+            let _ = std::fs::remove_file(&temp_file);
+            continue;
+        }
         
         // Check cache before calling songrec
         let cache_key = songrec_cache::cache_key(&temp_file);
         if let Some(ref key) = cache_key {
             if let Some(cached_json) = cache.get(key) {
                 let msg = "  Cache hit, skipping songrec API call";
-                println!("{}", msg);
+                info!("{}", msg.trim_start());
                 log.push_str(msg);
                 log.push('\n');
                 if let Ok(mut song_data) = parse_songrec_output(cached_json) {
                     song_data.timestamp = timestamp;
                     let msg = format!("  Found: {} - {}", song_data.artist, song_data.title);
-                    println!("{}", msg);
+                    info!("{}", msg.trim_start());
                     log.push_str(&msg);
                     log.push('\n');
+                    remember_clip(&mut fpdb, &temp_file, &song_data);
                     identified_songs.push(song_data);
                 } else {
                     let msg = "  Cached result: no match";
-                    println!("{}", msg);
+                    info!("{}", msg.trim_start());
                     log.push_str(msg);
                     log.push('\n');
                 }
@@ -89,10 +195,7 @@ pub fn identify_songs_at_timestamps(wav_path: &str, timestamps: &[f64]) -> Resul
         rate_limiter.wait_if_needed();
         
         // Run songrec on the extracted segment
-        let output = Command::new("songrec")
-            .arg("audio-file-to-recognized-song")
-            .arg(&temp_file)
-            .output();
+        let output = run_songrec(&temp_file);
 
         match output {
             Ok(result) if result.status.success() => {
@@ -108,14 +211,15 @@ pub fn identify_songs_at_timestamps(wav_path: &str, timestamps: &[f64]) -> Resul
                 if let Ok(mut song_data) = parse_songrec_output(&stdout) {
                     song_data.timestamp = timestamp;
                     let msg = format!("  Found: {} - {}", song_data.artist, song_data.title);
-                    println!("{}", msg);
+                    info!("{}", msg.trim_start());
                     log.push_str(&msg);
                     log.push('\n');
                     rate_limiter.report_success();
+                    remember_clip(&mut fpdb, &temp_file, &song_data);
                     identified_songs.push(song_data);
                 } else {
                     let msg = "  No match found";
-                    println!("{}", msg);
+                    info!("{}", msg.trim_start());
                     log.push_str(msg);
                     log.push('\n');
                     rate_limiter.report_success();
@@ -124,65 +228,20 @@ pub fn identify_songs_at_timestamps(wav_path: &str, timestamps: &[f64]) -> Resul
             Ok(result) => {
                 let stderr = String::from_utf8_lossy(&result.stderr);
                 let msg = format!("  songrec failed: {}", stderr);
-                eprintln!("{}", msg);
+                warn!("{}", msg.trim_start());
                 log.push_str(&msg);
                 log.push('\n');
                 
-                // Check if it's a decode error (rate limiting issue)
+                // A decode error means Shazam is limiting the rate
                 if stderr.contains("Decode") || stderr.contains("expected value") {
-                    let msg = "  Retrying after 30s wait...";
-                    println!("{}", msg);
-                    log.push_str(msg);
-                    log.push('\n');
-                    thread::sleep(Duration::from_secs(30));
-                    
-                    let retry_output = Command::new("songrec")
-                        .arg("audio-file-to-recognized-song")
-                        .arg(&temp_file)
-                        .output();
-                    
-                    match retry_output {
-                        Ok(retry_result) if retry_result.status.success() => {
-                            let stdout = String::from_utf8_lossy(&retry_result.stdout).to_string();
-                            
-                            // Store in cache
-                            if let Some(ref key) = cache_key {
-                                songrec_cache::append_to_cache(key, &stdout);
-                                cache.insert(key.clone(), stdout.clone());
-                            }
-                            
-                            if let Ok(mut song_data) = parse_songrec_output(&stdout) {
-                                song_data.timestamp = timestamp;
-                                let msg = format!("  Retry succeeded: {} - {}", song_data.artist, song_data.title);
-                                println!("{}", msg);
-                                log.push_str(&msg);
-                                log.push('\n');
-                                // Still increase rate limit since original request failed
-                                rate_limiter.report_failure();
-                                identified_songs.push(song_data);
-                            } else {
-                                let msg = "  Retry: no match found";
-                                println!("{}", msg);
-                                log.push_str(msg);
-                                log.push('\n');
-                                rate_limiter.report_failure();  // Original request failed
-                            }
-                        }
-                        _ => {
-                            let msg = "  Retry also failed, increasing rate limit";
-                            eprintln!("{}", msg);
-                            log.push_str(msg);
-                            log.push('\n');
-                            rate_limiter.report_failure();
-                        }
-                    }
+                    rate_limiter.report_failure();
                 } else {
                     rate_limiter.report_success();
                 }
             }
             Err(e) => {
                 let msg = format!("  Error running songrec: {}", e);
-                eprintln!("{}", msg);
+                warn!("{}", msg.trim_start());
                 log.push_str(&msg);
                 log.push('\n');
                 rate_limiter.report_success();
@@ -192,10 +251,59 @@ pub fn identify_songs_at_timestamps(wav_path: &str, timestamps: &[f64]) -> Resul
         // Clean up temp file (after potential retry)
         let _ = std::fs::remove_file(&temp_file);
     }
+    progress.finish(STAGE_IDENTIFY);
 
     Ok(IdentificationResult { songs: identified_songs, log })
 }
 
+/// Store the fingerprint of an identified clip for later recordings
+fn remember_clip(fpdb: &mut Option<FingerprintDb>, clip_path: &str, song: &IdentifiedSong) {
+    if let Some(db) = fpdb {
+        if let Err(e) = db.remember_clip(clip_path, song) {
+            warn!("Could not store fingerprint: {}", e);
+        }
+    }
+}
+
+/// Run songrec on `clip`, with the timeout and retries of the metadata requests
+fn run_songrec(clip: &str) -> std::io::Result<std::process::Output> {
+    http::run("Shazam", || {
+        let mut command = Command::new("songrec");
+        command.arg("audio-file-to-recognized-song").arg(clip);
+        command
+    })
+}
+
+/// Identify a single short WAV clip with songrec
+///
+/// Uses the songrec cache and the caller's rate limiter. Returns `Ok(None)` if
+/// songrec ran but did not recognize the clip.
+pub fn identify_wav_clip(wav_path: &str, rate_limiter: &mut RateLimiter) -> Result<Option<IdentifiedSong>, String> {
+    let cache_key = songrec_cache::cache_key(wav_path);
+    if let Some(ref key) = cache_key {
+        if let Some(cached_json) = songrec_cache::load_cache().get(key) {
+            return Ok(parse_songrec_output(cached_json).ok());
+        }
+    }
+
+    rate_limiter.wait_if_needed();
+
+    let result = run_songrec(wav_path).map_err(|e| format!("Error running songrec: {}", e))?;
+
+    if !result.status.success() {
+        rate_limiter.report_failure();
+        return Err(format!("songrec failed: {}", String::from_utf8_lossy(&result.stderr).trim()));
+    }
+
+    let stdout = String::from_utf8_lossy(&result.stdout).to_string();
+    if let Some(ref key) = cache_key {
+        songrec_cache::append_to_cache(key, &stdout);
+    }
+    rate_limiter.report_success();
+
+    Ok(parse_songrec_output(&stdout).ok())
+}
+
 /// Generate default timestamps with configurable first timestamp and interval
 pub fn generate_default_timestamps(duration_seconds: f64, first_seconds: f64, interval_seconds: f64) -> Vec<f64> {
     let mut timestamps = vec![first_seconds]; // Start at first_seconds
@@ -247,14 +355,32 @@ fn parse_songrec_output(json_str: &str) -> Result<IdentifiedSong, String> {
         .and_then(|text| text.as_str())
         .map(|s| s.to_string());
     
+    // Position of the clip within the matched song
+    let song_offset = json.get("matches")
+        .and_then(|m| m.as_array())
+        .and_then(|arr| arr.first())
+        .and_then(|m| m.get("offset"))
+        .and_then(|o| o.as_f64());
+    
     Ok(IdentifiedSong {
         timestamp: 0.0, // Will be set by caller
         title,
         artist,
         album,
+        match_offsets: Vec::new(),
+        song_offset,
+        recording_id: None,
+        confidence: None,
     })
 }
 
+/// Log a message and add it to the log text
+fn log_line(log: &mut String, msg: String) {
+    info!("{}", msg.trim_start());
+    log.push_str(&msg);
+    log.push('\n');
+}
+
 /// Format timestamp as MM:SS
 fn format_timestamp(seconds: f64) -> String {
     let mins = (seconds / 60.0) as u32;
@@ -262,9 +388,42 @@ fn format_timestamp(seconds: f64) -> String {
     format!("{}:{:02}", mins, secs)
 }
 
+/// Deduplicate consecutive identical songs (same artist + title).
+///
+/// Keeps the first occurrence's timestamp and song offset for each run and
+/// collects the timestamps of all matches of the run in `match_offsets`.
+pub fn merge_consecutive_matches(songs: &[IdentifiedSong]) -> Vec<IdentifiedSong> {
+    let mut deduped: Vec<IdentifiedSong> = Vec::new();
+    for song in songs {
+        if let Some(prev) = deduped.last_mut() {
+            if prev.same_song(song) {
+                if song.match_offsets.is_empty() {
+                    prev.match_offsets.push(song.timestamp);
+                } else {
+                    prev.match_offsets.extend_from_slice(&song.match_offsets);
+                }
+                if song.confidence > prev.confidence {
+                    prev.confidence = song.confidence;
+                }
+                continue;
+            }
+        }
+        let mut first = song.clone();
+        if first.match_offsets.is_empty() {
+            first.match_offsets = vec![song.timestamp];
+        }
+        deduped.push(first);
+    }
+    deduped
+}
+
 /// Main function to identify songs in a WAV file using Shazam/songrec
 /// Returns (Result<Vec<IdentifiedSong>>, log_string) - log is always available even on error
-pub fn identify_songs(wav_path: &str, timestamps: Option<Vec<f64>>) -> (Result<Vec<IdentifiedSong>, String>, String) {
+pub fn identify_songs(
+    wav_path: &str,
+    timestamps: Option<Vec<f64>>,
+    progress: &mut dyn ProgressSink,
+) -> (Result<Vec<IdentifiedSong>, String>, String) {
     let mut log = String::new();
     
     // Get WAV duration if timestamps not provided
@@ -308,18 +467,17 @@ pub fn identify_songs(wav_path: &str, timestamps: Option<Vec<f64>>) -> (Result<V
     };
     
     let msg = format!("Identifying songs in: {}", wav_path);
-    println!("{}", msg);
+    info!("{}", msg.trim_start());
     log.push_str(&msg);
     log.push('\n');
     let msg = format!("Using {} timestamp(s)", timestamps.len());
-    println!("{}", msg);
+    info!("{}", msg.trim_start());
     log.push_str(&msg);
     log.push('\n');
     log.push('\n');
-    println!();
     
     // Identify songs at each timestamp
-    let id_result = match identify_songs_at_timestamps(wav_path, &timestamps) {
+    let id_result = match identify_songs_at_timestamps(wav_path, &timestamps, progress) {
         Ok(r) => r,
         Err(e) => {
             let msg = format!("Error: {}", e);
@@ -339,30 +497,256 @@ pub fn identify_songs(wav_path: &str, timestamps: Option<Vec<f64>>) -> (Result<V
         return (Err(msg), log);
     }
 
-    // Deduplicate consecutive identical songs (same artist + title).
-    // Keep the first occurrence's timestamp for each run.
-    let mut deduped: Vec<IdentifiedSong> = Vec::new();
-    for song in &songs {
-        let dominated = deduped.last().map_or(false, |prev| {
-            prev.artist.eq_ignore_ascii_case(&song.artist)
-                && prev.title.eq_ignore_ascii_case(&song.title)
-        });
-        if !dominated {
-            deduped.push(song.clone());
-        }
-    }
+    let deduped = merge_consecutive_matches(&songs);
     
     let msg = format!("\nFound {} song(s) ({} unique)", songs.len(), deduped.len());
-    println!("{}", msg);
+    info!("{}", msg.trim_start());
     log.push_str(&msg);
     log.push('\n');
     
     for song in &deduped {
         let msg = format!("  {} - {}", song.artist, song.title);
-        println!("{}", msg);
+        info!("{}", msg.trim_start());
         log.push_str(&msg);
         log.push('\n');
     }
     
     (Ok(deduped), log)
 }
+
+// ── Adaptive identification ─────────────────────────────────────────────────
+
+/// A match is consistent with its region if the song start estimated from
+/// the Shazam offset lies within this many seconds of the region start
+const REGION_START_TOLERANCE: f64 = 20.0;
+
+/// Track regions between preliminary boundaries inside the music
+pub fn track_regions(music_start: f64, boundaries: &[f64], music_end: f64) -> Vec<(f64, f64)> {
+    let mut edges = vec![music_start];
+    edges.extend(boundaries.iter().copied().filter(|&b| b > music_start && b < music_end));
+    edges.push(music_end);
+    edges.windows(2).map(|w| (w[0], w[1])).collect()
+}
+
+/// Clip positions for a region: centered in the middle first, then a
+/// quarter and three quarters in for retries (fewer if the region is short)
+pub fn region_clip_positions(start: f64, end: f64) -> Vec<f64> {
+    let last = (end - CLIP_SECONDS).max(start);
+    let mut positions: Vec<f64> = Vec::new();
+    for fraction in [0.5, 0.25, 0.75] {
+        let position = (start + (end - start) * fraction - CLIP_SECONDS / 2.0).clamp(start, last);
+        if positions.iter().all(|p| (p - position).abs() >= CLIP_SECONDS / 2.0) {
+            positions.push(position);
+        }
+    }
+    positions
+}
+
+/// Whether a clip match needs a second opinion: no match, or a song that
+/// started far from the region start according to its Shazam offset
+fn is_ambiguous(clip: &Option<IdentifiedSong>, region_start: f64) -> bool {
+    match clip {
+        None => true,
+        Some(song) => song.estimated_start().is_some_and(|start| (start - region_start).abs() > REGION_START_TOLERANCE),
+    }
+}
+
+/// Vote among the clips of one region.
+///
+/// The song matched by most clips wins (the earlier one on a tie); its
+/// confidence is the share of all clips that matched it, so clips without
+/// a match count against it.
+pub fn vote_region(clips: &[Option<IdentifiedSong>]) -> Option<IdentifiedSong> {
+    let matches: Vec<&IdentifiedSong> = clips.iter().flatten().collect();
+    let (winner, votes) = matches.iter()
+        .map(|song| (*song, matches.iter().filter(|other| other.same_song(song)).count()))
+        .fold(None, |best: Option<(&IdentifiedSong, usize)>, (song, votes)| match best {
+            Some((_, best_votes)) if best_votes >= votes => best,
+            _ => Some((song, votes)),
+        })?;
+    let mut song = winner.clone();
+    song.match_offsets = matches.iter().filter(|other| other.same_song(winner)).map(|s| s.timestamp).collect();
+    song.match_offsets.sort_by(f64::total_cmp);
+    song.confidence = Some(votes as f64 / clips.len() as f64);
+    Some(song)
+}
+
+/// Identify the clip of `wav_path` that starts at `position`, from the
+/// fingerprint database if it is known there
+fn identify_clip_at(
+    wav_path: &str,
+    position: f64,
+    rate_limiter: &mut RateLimiter,
+    fpdb: &mut Option<FingerprintDb>,
+) -> Result<Option<IdentifiedSong>, String> {
+    let temp_file = format!("/tmp/songrec_segment_{}.wav", position as u32);
+    extract_wav_segment(wav_path, &temp_file, position, CLIP_SECONDS)
+        .map_err(|e| format!("Error extracting segment: {}", e))?;
+    if let Some(song) = fpdb.as_ref().and_then(|db| db.identify_clip(&temp_file, position)) {
+        let _ = std::fs::remove_file(&temp_file);
+        return Ok(Some(song));
+    }
+    let result = identify_wav_clip(&temp_file, rate_limiter);
+    if let Ok(Some(song)) = &result {
+        remember_clip(fpdb, &temp_file, song);
+    }
+    let _ = std::fs::remove_file(&temp_file);
+    Ok(result?.map(|mut song| {
+        song.timestamp = position;
+        song
+    }))
+}
+
+/// Identify one song per track region instead of at fixed timestamps.
+///
+/// Each region (e.g. from [`track_regions`] over preliminary boundaries) is
+/// sampled in the middle. Ambiguous results are retried at other offsets and
+/// the clips vote; every song carries the confidence of its vote. Returns
+/// the songs and the log like [`identify_songs`].
+pub fn identify_songs_in_regions(
+    wav_path: &str,
+    regions: &[(f64, f64)],
+    progress: &mut dyn ProgressSink,
+) -> (Result<Vec<IdentifiedSong>, String>, String) {
+    let mut log = String::new();
+    log_line(&mut log, format!("Identifying songs in: {}", wav_path));
+    log_line(&mut log, format!("Sampling {} track region(s)\n", regions.len()));
+
+    let mut rate_limiter = songrec_rate_limiter();
+    let mut fpdb = FingerprintDb::open_default();
+    let mut songs = Vec::new();
+    for (i, &(start, end)) in regions.iter().enumerate() {
+        let msg = format!("Region {} ({} - {}):", i + 1, format_timestamp(start), format_timestamp(end));
+        progress.progress(i as f32 * 100.0 / regions.len() as f32, STAGE_IDENTIFY, &msg);
+        log_line(&mut log, msg);
+
+        let mut clips: Vec<Option<IdentifiedSong>> = Vec::new();
+        for position in region_clip_positions(start, end) {
+            if let Some(winner) = vote_region(&clips) {
+                // Stop once the clips agree and the last one was clear
+                let last_clear = clips.last().is_some_and(|c| !is_ambiguous(c, start));
+                if last_clear && winner.confidence.is_some_and(|c| c > 0.5) {
+                    break;
+                }
+            }
+            match identify_clip_at(wav_path, position, &mut rate_limiter, &mut fpdb) {
+                Ok(clip) => {
+                    match &clip {
+                        Some(song) => log_line(&mut log, format!("  {}: {} - {}", format_timestamp(position), song.artist, song.title)),
+                        None => log_line(&mut log, format!("  {}: no match", format_timestamp(position))),
+                    }
+                    clips.push(clip);
+                }
+                Err(e) => log_line(&mut log, format!("  {}: {}", format_timestamp(position), e)),
+            }
+        }
+
+        match vote_region(&clips) {
+            Some(song) => {
+                log_line(&mut log, format!("  → {} - {} (confidence {:.0}%)",
+                                      song.artist, song.title, song.weight() * 100.0));
+                songs.push(song);
+            }
+            None => log_line(&mut log, "  → not identified".to_string()),
+        }
+    }
+    progress.finish(STAGE_IDENTIFY);
+
+    if songs.is_empty() {
+        let msg = "No songs could be identified".to_string();
+        log.push_str(&msg);
+        log.push('\n');
+        return (Err(msg), log);
+    }
+    let deduped = merge_consecutive_matches(&songs);
+    log_line(&mut log, format!("\nFound {} song(s) in {} region(s)", deduped.len(), regions.len()));
+    (Ok(deduped), log)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn song(timestamp: f64, title: &str, song_offset: Option<f64>) -> IdentifiedSong {
+        IdentifiedSong {
+            timestamp,
+            title: title.to_string(),
+            artist: "Artist".to_string(),
+            album: None,
+            match_offsets: Vec::new(),
+            song_offset,
+            recording_id: None,
+            confidence: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_songrec_offset() {
+        let json = r#"{"matches":[{"offset":42.5,"timeskew":0.0}],"track":{"title":"T","subtitle":"A"}}"#;
+        let parsed = parse_songrec_output(json).unwrap();
+        assert_eq!(parsed.song_offset, Some(42.5));
+
+        let json = r#"{"matches":[],"track":{"title":"T","subtitle":"A"}}"#;
+        assert_eq!(parse_songrec_output(json).unwrap().song_offset, None);
+    }
+
+    #[test]
+    fn test_merge_collects_match_offsets() {
+        let songs = vec![
+            song(60.0, "One", Some(50.0)),
+            song(180.0, "one", Some(170.0)),
+            song(300.0, "Two", None),
+            song(420.0, "One", None),
+        ];
+        let merged = merge_consecutive_matches(&songs);
+        assert_eq!(merged.len(), 3);
+        assert_eq!(merged[0].match_offsets, vec![60.0, 180.0]);
+        assert_eq!(merged[0].song_offset, Some(50.0));
+        assert_eq!(merged[0].last_match(), 180.0);
+        assert_eq!(merged[1].match_offsets, vec![300.0]);
+    }
+
+    #[test]
+    fn test_boundary_windows() {
+        let merged = merge_consecutive_matches(&[
+            song(60.0, "One", None),
+            song(180.0, "One", None),
+            song(300.0, "Two", Some(70.0)),
+            song(420.0, "Three", None),
+        ]);
+        let windows = boundary_windows(&merged, CLIP_SECONDS, 10.0);
+        assert_eq!(windows.len(), 2);
+
+        // Song two started ~230s, inside [180, 330]: narrowed to ±10s
+        assert_eq!(windows[0].song_index, 0);
+        assert_eq!(windows[0].expected, Some(230.0));
+        assert_eq!((windows[0].start, windows[0].end), (220.0, 240.0));
+
+        // No offset for song three: the whole gap between the matches
+        assert_eq!(windows[1].expected, None);
+        assert_eq!((windows[1].start, windows[1].end), (300.0, 450.0));
+    }
+
+    #[test]
+    fn test_adaptive_sampling_and_vote() {
+        assert_eq!(track_regions(2.0, &[1.0, 200.0, 500.0], 400.0), vec![(2.0, 200.0), (200.0, 400.0)]);
+        assert_eq!(region_clip_positions(0.0, 200.0), vec![85.0, 35.0, 135.0]);
+        // Too short for three separate clips
+        assert_eq!(region_clip_positions(100.0, 140.0), vec![105.0]);
+
+        let clips = vec![Some(song(85.0, "One", None)), None, Some(song(135.0, "one", None))];
+        let voted = vote_region(&clips).unwrap();
+        assert_eq!(voted.match_offsets, vec![85.0, 135.0]);
+        assert!((voted.weight() - 2.0 / 3.0).abs() < 1e-9);
+
+        // A tie goes to the first clip
+        let tie = vote_region(&[Some(song(85.0, "One", None)), Some(song(35.0, "Two", None))]).unwrap();
+        assert_eq!((tie.title.as_str(), tie.confidence), ("One", Some(0.5)));
+        assert!(vote_region(&[None, None]).is_none());
+
+        // The song started 80s before the region: ask again
+        assert!(is_ambiguous(&Some(song(185.0, "One", Some(165.0))), 100.0));
+        assert!(!is_ambiguous(&Some(song(185.0, "One", Some(80.0))), 100.0));
+        assert!(is_ambiguous(&None, 100.0));
+    }
+}
@@ -1,7 +1,34 @@
-//! Audio analysis utilities for RMS computation and signal level estimation.
+//! Audio analysis utilities for RMS computation, signal level estimation,
+//! stereo channel balance and per-channel levels of multichannel recordings.
 
+use std::fs::File;
+use std::io::{BufReader, Read, Seek};
+use std::thread::{self, JoinHandle};
+
+use crate::downmix::Downmix;
+use crate::error::{Error, Result};
+use crate::progress::{NoProgress, ProgressSink, STAGE_QUALITY, STAGE_RMS};
+use crate::wavfile::{read_channel_names, read_wav_header, WavHeader, UNKNOWN_DATA_SIZE};
 use crate::SampleFormat;
 
+/// Bytes read per call when streaming a file
+pub const READ_BUFFER_BYTES: usize = 1 << 20;
+
+/// Level difference between the channels above which the balance report warns, in dB
+pub const BALANCE_WARNING_DB: f64 = 1.0;
+
+/// Below this L/R correlation the balance report warns about the azimuth
+pub const CORRELATION_WARNING: f64 = 0.3;
+
+/// DC offset (fraction of full scale) above which the quality report warns
+pub const DC_OFFSET_WARNING: f64 = 0.001;
+
+/// Shortest run of identical frames that counts as a dropout, in seconds
+pub const MIN_DROPOUT_SECONDS: f64 = 0.002;
+
+/// Windows of the quality report's level statistics, in milliseconds
+const QUALITY_WINDOW_MS: u32 = 100;
+
 /// Compute RMS in dB for a chunk of audio samples.
 ///
 /// # Arguments
@@ -18,10 +45,7 @@ pub fn compute_rms_db(audio: &[Vec<i32>], format: SampleFormat) -> f32 {
         return -80.0;
     }
     
-    let max_value = match format {
-        SampleFormat::S16 => 32768.0_f32,
-        SampleFormat::S32 => 2147483648.0_f32,
-    };
+    let max_value = format.max_value() as f32;
     
     let mut sum_squares = 0.0_f64;
     for i in 0..num_samples {
@@ -42,6 +66,735 @@ pub fn compute_rms_db(audio: &[Vec<i32>], format: SampleFormat) -> f32 {
     }
 }
 
+/// Splits interleaved PCM that arrives in arbitrary pieces into whole
+/// frames; a frame split between two pieces is kept until it is complete
+#[derive(Debug, Default)]
+pub struct FrameReassembler {
+    frame_bytes: usize,
+    partial_frame: Vec<u8>,
+}
+
+impl FrameReassembler {
+    pub fn new(format: SampleFormat, channels: usize) -> Self {
+        FrameReassembler { frame_bytes: channels.max(1) * format.bytes_per_sample(), partial_frame: Vec::new() }
+    }
+
+    /// Call `add_frame` for each frame completed by `data`
+    pub fn process(&mut self, mut data: &[u8], mut add_frame: impl FnMut(&[u8])) {
+        // Complete a frame left over from the previous call
+        if !self.partial_frame.is_empty() {
+            let take = (self.frame_bytes - self.partial_frame.len()).min(data.len());
+            self.partial_frame.extend_from_slice(&data[..take]);
+            data = &data[take..];
+            if self.partial_frame.len() < self.frame_bytes {
+                return;
+            }
+            add_frame(&self.partial_frame);
+            self.partial_frame.clear();
+        }
+
+        let mut frames = data.chunks_exact(self.frame_bytes);
+        for frame in &mut frames {
+            add_frame(frame);
+        }
+        self.partial_frame.extend_from_slice(frames.remainder());
+    }
+}
+
+/// The sample data of a WAV file, read piece by piece into an analyzer
+pub struct WavData {
+    pub header: WavHeader,
+    pub format: SampleFormat,
+    reader: BufReader<File>,
+    start: u64,
+    end: u64,
+}
+
+impl WavData {
+    /// Open `path` and read its header
+    pub fn open(path: &str) -> Result<Self> {
+        let file = File::open(path)?;
+        let file_len = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+        let header = read_wav_header(&mut reader).map_err(Error::Decode)?;
+        let format = header
+            .sample_format()
+            .ok_or_else(|| Error::Decode(format!("Unsupported bit depth: {}", header.bits_per_sample)))?;
+
+        // An unfinished recording may have no valid data size yet
+        let start = reader.stream_position()?;
+        let end = match header.data_size {
+            0 | UNKNOWN_DATA_SIZE => file_len,
+            size => (start + size).min(file_len),
+        };
+        Ok(WavData { header, format, reader, start, end })
+    }
+
+    pub fn channels(&self) -> usize {
+        self.header.num_channels as usize
+    }
+
+    /// Bytes per frame (one sample of each channel)
+    pub fn frame_bytes(&self) -> usize {
+        (self.channels() * self.format.bytes_per_sample()).max(1)
+    }
+
+    /// Number of whole frames in the file
+    pub fn frames(&self) -> u64 {
+        (self.end - self.start) / self.frame_bytes() as u64
+    }
+
+    /// Pass the sample data to `chunk` in pieces of whole frames, at most
+    /// [`READ_BUFFER_BYTES`] each, together with the percentage read so far.
+    /// An error of `chunk` ends the reading.
+    ///
+    /// With `use_mmap` the file is memory-mapped instead of read through a
+    /// buffer, which avoids copying multi-GB recordings through user space.
+    pub fn for_each_data_chunk(mut self, use_mmap: bool, mut chunk: impl FnMut(&[u8], f32) -> Result<()>) -> Result<()> {
+        let frame_bytes = self.frame_bytes();
+        let piece = (READ_BUFFER_BYTES / frame_bytes).max(1) * frame_bytes;
+        let total = self.frames() * frame_bytes as u64;
+        let percent = |done: u64| done as f32 * 100.0 / total.max(1) as f32;
+
+        if use_mmap {
+            let file = self.reader.into_inner();
+            // SAFETY: the file is only read; recordings that are still being
+            // written are skipped by the callers.
+            let mmap = unsafe { memmap2::Mmap::map(&file)? };
+            #[cfg(unix)]
+            let _ = mmap.advise(memmap2::Advice::Sequential);
+            let data = &mmap[self.start as usize..(self.start + total) as usize];
+            let mut done = 0;
+            for piece in data.chunks(piece) {
+                done += piece.len() as u64;
+                chunk(piece, percent(done))?;
+            }
+        } else {
+            let mut buffer = vec![0u8; piece];
+            let mut done = 0;
+            while done < total {
+                let want = ((total - done) as usize).min(buffer.len());
+                self.reader.read_exact(&mut buffer[..want])?;
+                done += want as u64;
+                chunk(&buffer[..want], percent(done))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Streaming RMS over fixed-size windows of interleaved little-endian PCM.
+///
+/// Produces the same values as calling [`compute_rms_db`] on consecutive
+/// windows, but works directly on the raw bytes so no per-channel buffers are
+/// allocated. Data may be passed in arbitrary pieces, even split mid-frame.
+pub struct RmsAnalyzer {
+    format: SampleFormat,
+    channels: usize,
+    /// Average only these channels instead of all
+    selected: Vec<usize>,
+    window_frames: usize,
+    max_value: f32,
+    sum_squares: f64,
+    frames_in_window: usize,
+    reassembler: FrameReassembler,
+    values: Vec<f32>,
+}
+
+impl RmsAnalyzer {
+    pub fn new(format: SampleFormat, channels: usize, window_frames: usize) -> Self {
+        let max_value = format.max_value() as f32;
+        RmsAnalyzer {
+            format,
+            channels: channels.max(1),
+            selected: Vec::new(),
+            window_frames: window_frames.max(1),
+            max_value,
+            sum_squares: 0.0,
+            frames_in_window: 0,
+            reassembler: FrameReassembler::new(format, channels),
+            values: Vec::new(),
+        }
+    }
+
+    /// Average only `channels` (0-based), e.g. the mid channel of a mid/side
+    /// recording or one deck of a multichannel capture
+    pub fn only_channels(mut self, channels: &[usize]) -> Self {
+        self.selected = channels.iter().map(|&c| c.min(self.channels - 1)).collect();
+        self
+    }
+
+    /// Feed interleaved PCM bytes
+    pub fn process_bytes(&mut self, data: &[u8]) {
+        let mut reassembler = std::mem::take(&mut self.reassembler);
+        reassembler.process(data, |frame| self.add_frame(frame));
+        self.reassembler = reassembler;
+    }
+
+    fn add_frame(&mut self, frame: &[u8]) {
+        let bytes = self.format.bytes_per_sample();
+        let mono_sample = if self.selected.is_empty() {
+            let mut sample_sum = 0.0_f32;
+            for b in frame.chunks_exact(bytes) {
+                sample_sum += self.format.decode_sample(b) as f32 / self.max_value;
+            }
+            sample_sum / self.channels as f32
+        } else {
+            let mut sample_sum = 0.0_f32;
+            for &channel in &self.selected {
+                sample_sum += self.format.decode_sample(&frame[channel * bytes..(channel + 1) * bytes]) as f32 / self.max_value;
+            }
+            sample_sum / self.selected.len() as f32
+        };
+        self.sum_squares += (mono_sample * mono_sample) as f64;
+        self.frames_in_window += 1;
+
+        if self.frames_in_window == self.window_frames {
+            self.finish_window();
+        }
+    }
+
+    fn finish_window(&mut self) {
+        let rms = (self.sum_squares / self.frames_in_window as f64).sqrt() as f32;
+        self.values.push(if rms > 0.0 { 20.0 * rms.log10() } else { -80.0 });
+        self.sum_squares = 0.0;
+        self.frames_in_window = 0;
+    }
+
+    /// RMS values (dB) of all completed windows
+    pub fn values(&self) -> &[f32] {
+        &self.values
+    }
+
+    /// Close the last, possibly shorter, window and return all RMS values (dB)
+    pub fn finish(mut self) -> Vec<f32> {
+        if self.frames_in_window > 0 {
+            self.finish_window();
+        }
+        self.values
+    }
+}
+
+/// Compute RMS values (dB) in windows of `window_ms` over a whole WAV file.
+///
+/// With `use_mmap` the file is memory-mapped instead of read through a
+/// buffer, which avoids copying multi-GB recordings through user space.
+/// Progress is reported to `progress` as the stage [`STAGE_RMS`].
+///
+/// The `channels` given (0-based) are averaged, all if it is empty. Without a
+/// selection only the mid channel of mid/side recordings (see
+/// [`crate::downmix`]) is used.
+pub fn wav_rms(path: &str, window_ms: u32, use_mmap: bool, channels: &[usize], progress: &mut dyn ProgressSink) -> Result<Vec<f32>> {
+    let wav = WavData::open(path)?;
+    let window_frames = (wav.header.sample_rate as f64 * window_ms as f64 / 1000.0) as usize;
+    check_channels(channels, wav.header.num_channels)?;
+    let mut analyzer = RmsAnalyzer::new(wav.format, wav.channels(), window_frames);
+    if !channels.is_empty() {
+        analyzer = analyzer.only_channels(channels);
+    } else if Downmix::from_channel_names(&read_channel_names(path).unwrap_or_default()) == Downmix::MidSide {
+        analyzer = analyzer.only_channels(&[0]);
+    }
+
+    wav.for_each_data_chunk(use_mmap, |data, percent| {
+        analyzer.process_bytes(data);
+        progress.progress(percent, STAGE_RMS, "");
+        Ok(())
+    })?;
+    progress.finish(STAGE_RMS);
+
+    Ok(analyzer.finish())
+}
+
+/// Parse a 1-based channel list like "3,4" into 0-based channel indices
+pub fn parse_channel_list(s: &str) -> Option<Vec<usize>> {
+    s.split(',')
+        .map(|c| c.trim().parse::<usize>().ok().filter(|&c| c > 0).map(|c| c - 1))
+        .collect()
+}
+
+/// Error for selected channels that the file doesn't have
+fn check_channels(channels: &[usize], num_channels: u16) -> Result<()> {
+    match channels.iter().find(|&&c| c >= num_channels as usize) {
+        Some(c) => Err(Error::Decode(format!("Channel {} selected, but the file has {} channel(s)", c + 1, num_channels))),
+        None => Ok(()),
+    }
+}
+
+/// [`wav_rms`] of the next file of a batch, computed on a background thread
+/// while the current file waits for song identification and album lookup
+pub struct RmsPrefetch {
+    window_ms: u32,
+    use_mmap: bool,
+    channels: Vec<usize>,
+    pending: Option<(String, JoinHandle<Result<Vec<f32>>>)>,
+}
+
+impl RmsPrefetch {
+    pub fn new(window_ms: u32, use_mmap: bool, channels: &[usize]) -> Self {
+        RmsPrefetch { window_ms, use_mmap, channels: channels.to_vec(), pending: None }
+    }
+
+    /// Start computing the RMS of `path`. A computation for another file
+    /// that was not taken is abandoned.
+    pub fn start(&mut self, path: &str) {
+        if self.pending.as_ref().is_some_and(|(p, _)| p == path) {
+            return;
+        }
+        let (owned, window_ms, use_mmap, channels) = (path.to_string(), self.window_ms, self.use_mmap, self.channels.clone());
+        let handle = thread::spawn(move || wav_rms(&owned, window_ms, use_mmap, &channels, &mut NoProgress));
+        self.pending = Some((path.to_string(), handle));
+    }
+
+    /// The RMS of `path` if it was started; waits until it is done
+    pub fn take(&mut self, path: &str) -> Option<Result<Vec<f32>>> {
+        match self.pending.take() {
+            Some((p, handle)) if p == path => {
+                Some(handle.join().unwrap_or_else(|_| Err(Error::Decode(format!("RMS computation of {} failed", path)))))
+            }
+            other => {
+                self.pending = other;
+                None
+            }
+        }
+    }
+}
+
+/// Long-term level comparison of the two channels of a stereo recording.
+///
+/// A level difference points at a cartridge that is not level (azimuth) or
+/// a wrong balance; a low correlation between the channels at high
+/// crosstalk or a twisted azimuth; a negative one at swapped wiring.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChannelBalance {
+    /// RMS of the left channel in dBFS
+    pub left_db: f64,
+    /// RMS of the right channel in dBFS
+    pub right_db: f64,
+    /// Correlation of left and right (-1.0 to 1.0)
+    pub correlation: f64,
+    /// Level of the side signal (L-R) below the mid signal (L+R) in dB
+    pub side_below_mid_db: f64,
+}
+
+impl ChannelBalance {
+    /// Right minus left level in dB
+    pub fn difference_db(&self) -> f64 {
+        self.right_db - self.left_db
+    }
+
+    /// Which channel is louder, e.g. "right channel is 2.3 dB hotter"
+    pub fn describe_difference(&self) -> String {
+        let difference = self.difference_db();
+        if difference.abs() < 0.05 {
+            "channels are balanced".to_string()
+        } else {
+            let side = if difference > 0.0 { "right" } else { "left" };
+            format!("{} channel is {:.1} dB hotter", side, difference.abs())
+        }
+    }
+
+    /// Problems worth fixing at the turntable
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if self.difference_db().abs() >= BALANCE_WARNING_DB {
+            warnings.push(format!("{} - check the cartridge alignment and balance", self.describe_difference()));
+        }
+        if self.correlation < 0.0 {
+            warnings.push("channels are out of phase - check the cartridge wiring".to_string());
+        } else if self.correlation < CORRELATION_WARNING {
+            warnings.push(format!("low L/R correlation ({:.2}) - check the azimuth", self.correlation));
+        }
+        warnings
+    }
+
+    /// Text for the recording's info file
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        text.push_str("Channel Balance:\n");
+        text.push_str("----------------\n");
+        text.push_str(&format!("Left:  {:.1} dBFS RMS\n", self.left_db));
+        text.push_str(&format!("Right: {:.1} dBFS RMS\n", self.right_db));
+        text.push_str(&format!("Balance: {}\n", self.describe_difference()));
+        text.push_str(&format!("L/R correlation: {:.2}\n", self.correlation));
+        text.push_str(&format!("Side below mid: {:.1} dB\n", self.side_below_mid_db));
+        for warning in self.warnings() {
+            text.push_str(&format!("Warning: {}\n", warning));
+        }
+        text
+    }
+}
+
+/// Streaming accumulator for [`ChannelBalance`] over interleaved PCM.
+///
+/// The first two channels are compared, or the pair given to
+/// [`pair`](Self::pair).
+pub struct BalanceAnalyzer {
+    format: SampleFormat,
+    channels: usize,
+    /// Indices of the left and right channel
+    left: usize,
+    right: usize,
+    sum_left: f64,
+    sum_right: f64,
+    sum_product: f64,
+    frames: u64,
+    reassembler: FrameReassembler,
+}
+
+impl BalanceAnalyzer {
+    pub fn new(format: SampleFormat, channels: usize) -> Self {
+        BalanceAnalyzer {
+            format,
+            channels: channels.max(1),
+            left: 0,
+            right: 1,
+            sum_left: 0.0,
+            sum_right: 0.0,
+            sum_product: 0.0,
+            frames: 0,
+            reassembler: FrameReassembler::new(format, channels),
+        }
+    }
+
+    /// Compare channels `left` and `right` (0-based) of a multichannel stream
+    pub fn pair(mut self, left: usize, right: usize) -> Self {
+        self.left = left.min(self.channels - 1);
+        self.right = right.min(self.channels - 1);
+        self
+    }
+
+    /// Feed interleaved PCM bytes
+    pub fn process_bytes(&mut self, data: &[u8]) {
+        let mut reassembler = std::mem::take(&mut self.reassembler);
+        reassembler.process(data, |frame| self.add_frame(frame));
+        self.reassembler = reassembler;
+    }
+
+    fn add_frame(&mut self, frame: &[u8]) {
+        self.frames += 1;
+        if self.channels < 2 {
+            return;
+        }
+        let size = self.format.bytes_per_sample();
+        let left = self.format.decode_sample(&frame[self.left * size..(self.left + 1) * size]) as f64;
+        let right = self.format.decode_sample(&frame[self.right * size..(self.right + 1) * size]) as f64;
+        self.sum_left += left * left;
+        self.sum_right += right * right;
+        self.sum_product += left * right;
+    }
+
+    /// The balance over all data; None for mono audio or no data
+    pub fn finish(self) -> Option<ChannelBalance> {
+        if self.channels < 2 || self.frames == 0 {
+            return None;
+        }
+        let max_value = self.format.max_value();
+        let frames = self.frames as f64;
+        let db = |energy: f64| {
+            let rms = (energy / frames).sqrt() / max_value;
+            if rms > 0.0 { 20.0 * rms.log10() } else { -80.0 }
+        };
+        let correlation = if self.sum_left > 0.0 && self.sum_right > 0.0 {
+            self.sum_product / (self.sum_left * self.sum_right).sqrt()
+        } else {
+            0.0
+        };
+        // (L+R)^2 and (L-R)^2 summed over all frames
+        let mid = self.sum_left + self.sum_right + 2.0 * self.sum_product;
+        let side = self.sum_left + self.sum_right - 2.0 * self.sum_product;
+        let side_below_mid_db = if side > 0.0 && mid > 0.0 {
+            10.0 * (mid / side).log10()
+        } else if side > 0.0 {
+            -80.0
+        } else {
+            80.0
+        };
+        Some(ChannelBalance {
+            left_db: db(self.sum_left),
+            right_db: db(self.sum_right),
+            correlation,
+            side_below_mid_db,
+        })
+    }
+}
+
+/// Compare the channel levels of a whole WAV file; None for mono files.
+///
+/// In files with more than two channels only a pair selected with
+/// `channels` (0-based) is compared, as the channels may come from
+/// different decks; their levels are in the [`QualityReport`].
+pub fn wav_channel_balance(path: &str, channels: &[usize]) -> Result<Option<ChannelBalance>> {
+    let wav = WavData::open(path)?;
+    check_channels(channels, wav.header.num_channels)?;
+    let mut analyzer = match channels {
+        [] if wav.channels() <= 2 => BalanceAnalyzer::new(wav.format, wav.channels()),
+        &[left, right] => BalanceAnalyzer::new(wav.format, wav.channels()).pair(left, right),
+        _ => return Ok(None),
+    };
+    wav.for_each_data_chunk(false, |data, _| {
+        analyzer.process_bytes(data);
+        Ok(())
+    })?;
+    Ok(analyzer.finish())
+}
+
+/// A stretch where the signal stopped: identical frames (usually zeros)
+/// between moving audio, as left by a buffer underrun
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Dropout {
+    pub start_seconds: f64,
+    pub duration_seconds: f64,
+}
+
+impl Dropout {
+    /// e.g. "5.0 ms at 2:03.50"
+    pub fn describe(&self) -> String {
+        format!("{:.1} ms at {}", self.duration_seconds * 1000.0, format_position(self.start_seconds))
+    }
+}
+
+/// Finds [`Dropout`]s in a stream of frames, while recording or afterwards.
+///
+/// Analog sources always carry some noise, so even silence never repeats
+/// the same frame for [`MIN_DROPOUT_SECONDS`]. Runs of identical frames at
+/// the start (before the first change) and at the end are the stream
+/// starting and stopping, not dropouts.
+pub struct DropoutDetector {
+    channels: usize,
+    sample_rate: u32,
+    previous_frame: Vec<i32>,
+    frames: u64,
+    /// First frame of the current run of identical frames
+    run_start: u64,
+    min_frames: u64,
+    dropouts: Vec<Dropout>,
+}
+
+impl DropoutDetector {
+    pub fn new(channels: usize, sample_rate: u32) -> Self {
+        DropoutDetector {
+            channels: channels.max(1),
+            sample_rate,
+            previous_frame: Vec::new(),
+            frames: 0,
+            run_start: 0,
+            min_frames: ((sample_rate as f64 * MIN_DROPOUT_SECONDS) as u64).max(2),
+            dropouts: Vec::new(),
+        }
+    }
+
+    /// Add one frame (one sample per channel)
+    pub fn add_frame(&mut self, frame: &[i32]) {
+        if frame != self.previous_frame.as_slice() {
+            let run = self.frames - self.run_start;
+            if self.run_start > 0 && run >= self.min_frames {
+                self.dropouts.push(Dropout {
+                    start_seconds: self.run_start as f64 / self.sample_rate as f64,
+                    duration_seconds: run as f64 / self.sample_rate as f64,
+                });
+            }
+            self.run_start = self.frames;
+            self.previous_frame.clear();
+            self.previous_frame.extend_from_slice(frame);
+        }
+        self.frames += 1;
+    }
+
+    /// Add interleaved samples; returns the dropouts that ended in them
+    pub fn process_samples(&mut self, samples: &[i32]) -> &[Dropout] {
+        let known = self.dropouts.len();
+        for frame in samples.chunks_exact(self.channels) {
+            self.add_frame(frame);
+        }
+        &self.dropouts[known..]
+    }
+
+    /// Dropouts found so far
+    pub fn dropouts(&self) -> &[Dropout] {
+        &self.dropouts
+    }
+
+    pub fn finish(self) -> Vec<Dropout> {
+        self.dropouts
+    }
+}
+
+/// Short name of channel `index` of `channels`: "L" and "R" for stereo, else "Ch 1", "Ch 2", ...
+pub fn channel_label(index: usize, channels: usize) -> String {
+    match (channels, index) {
+        (2, 0) => "L".to_string(),
+        (2, 1) => "R".to_string(),
+        _ => format!("Ch {}", index + 1),
+    }
+}
+
+/// DC offset per channel, e.g. "L +0.004% (-88 dBFS), R -0.002% (-94 dBFS)"
+pub fn format_dc_offset(dc_offset: &[f64]) -> String {
+    let offsets: Vec<String> = dc_offset.iter().enumerate().map(|(i, dc)| {
+        let db = if *dc != 0.0 { 20.0 * dc.abs().log10() } else { -120.0 };
+        format!("{} {:+.3}% ({:.0} dBFS)", channel_label(i, dc_offset.len()), dc * 100.0, db)
+    }).collect();
+    offsets.join(", ")
+}
+
+/// RMS level per channel, e.g. "Ch 1 -18.2 dBFS, Ch 2 -18.5 dBFS, Ch 3 -80.0 dBFS"
+pub fn format_channel_levels(levels_db: &[f64]) -> String {
+    let levels: Vec<String> = levels_db.iter().enumerate()
+        .map(|(i, db)| format!("{} {:.1} dBFS", channel_label(i, levels_db.len()), db))
+        .collect();
+    levels.join(", ")
+}
+
+/// Levels, DC offset and dropouts of a whole recording, for comparing
+/// cartridges, preamps and ADC settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct QualityReport {
+    /// Level of the quietest passages (groove noise) in dBFS
+    pub noise_floor_db: f64,
+    /// Typical music level in dBFS
+    pub music_level_db: f64,
+    /// Mean of each channel as a fraction of full scale
+    pub dc_offset: Vec<f64>,
+    /// RMS of each channel over the whole recording in dBFS
+    pub channel_levels_db: Vec<f64>,
+    pub dropouts: Vec<Dropout>,
+}
+
+fn format_position(seconds: f64) -> String {
+    format!("{}:{:05.2}", (seconds / 60.0) as u64, seconds % 60.0)
+}
+
+impl QualityReport {
+    /// Music level above the noise floor in dB
+    pub fn snr_db(&self) -> f64 {
+        self.music_level_db - self.noise_floor_db
+    }
+
+    /// Problems worth fixing before the next recording
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if self.dc_offset.iter().any(|dc| dc.abs() >= DC_OFFSET_WARNING) {
+            warnings.push("DC offset - check the ADC or record with --remove-dc".to_string());
+        }
+        if !self.dropouts.is_empty() {
+            warnings.push(format!("{} dropout(s) - the recording should be redone", self.dropouts.len()));
+        }
+        warnings
+    }
+
+    /// Text for the recording's info file
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        text.push_str("Recording Quality:\n");
+        text.push_str("------------------\n");
+        text.push_str(&format!("Noise floor: {:.1} dBFS\n", self.noise_floor_db));
+        text.push_str(&format!("Music level: {:.1} dBFS\n", self.music_level_db));
+        text.push_str(&format!("Estimated SNR: {:.1} dB\n", self.snr_db()));
+        text.push_str(&format!("DC offset: {}\n", format_dc_offset(&self.dc_offset)));
+        // Stereo levels are compared in the channel balance
+        if self.channel_levels_db.len() > 2 {
+            text.push_str(&format!("Channel levels: {}\n", format_channel_levels(&self.channel_levels_db)));
+        }
+        if self.dropouts.is_empty() {
+            text.push_str("Dropouts: none\n");
+        } else {
+            text.push_str(&format!("Dropouts: {}\n", self.dropouts.len()));
+            for dropout in &self.dropouts {
+                text.push_str(&format!("  {}\n", dropout.describe()));
+            }
+        }
+        for warning in self.warnings() {
+            text.push_str(&format!("Warning: {}\n", warning));
+        }
+        text
+    }
+}
+
+/// Streaming accumulator for [`QualityReport`] over interleaved PCM
+pub struct QualityAnalyzer {
+    format: SampleFormat,
+    rms: RmsAnalyzer,
+    sums: Vec<f64>,
+    sum_squares: Vec<f64>,
+    frames: u64,
+    reassembler: FrameReassembler,
+    frame: Vec<i32>,
+    dropouts: DropoutDetector,
+}
+
+impl QualityAnalyzer {
+    pub fn new(format: SampleFormat, channels: usize, sample_rate: u32) -> Self {
+        let channels = channels.max(1);
+        let window_frames = (sample_rate as u64 * QUALITY_WINDOW_MS as u64 / 1000) as usize;
+        QualityAnalyzer {
+            format,
+            rms: RmsAnalyzer::new(format, channels, window_frames),
+            sums: vec![0.0; channels],
+            sum_squares: vec![0.0; channels],
+            frames: 0,
+            reassembler: FrameReassembler::new(format, channels),
+            frame: Vec::with_capacity(channels),
+            dropouts: DropoutDetector::new(channels, sample_rate),
+        }
+    }
+
+    /// Feed interleaved PCM bytes
+    pub fn process_bytes(&mut self, data: &[u8]) {
+        self.rms.process_bytes(data);
+        let mut reassembler = std::mem::take(&mut self.reassembler);
+        reassembler.process(data, |frame| self.add_frame(frame));
+        self.reassembler = reassembler;
+    }
+
+    fn add_frame(&mut self, frame: &[u8]) {
+        let size = self.format.bytes_per_sample();
+        self.frame.clear();
+        self.frame.extend(frame.chunks_exact(size).map(|sample| self.format.decode_sample(sample)));
+        for ((sum, square), &sample) in self.sums.iter_mut().zip(&mut self.sum_squares).zip(&self.frame) {
+            *sum += sample as f64;
+            *square += sample as f64 * sample as f64;
+        }
+        self.dropouts.add_frame(&self.frame);
+        self.frames += 1;
+    }
+
+    /// The report over all data; a run of identical frames at the end is
+    /// the recording stopping and not counted
+    pub fn finish(self) -> QualityReport {
+        let levels = smooth_rms(&self.rms.finish(), 5);
+        let (noise_floor_db, music_level_db) = if levels.is_empty() {
+            (-80.0, -80.0)
+        } else {
+            (estimate_noise_floor(&levels) as f64, estimate_music_level(&levels) as f64)
+        };
+        let frames = self.frames.max(1) as f64;
+        let max_value = self.format.max_value();
+        QualityReport {
+            noise_floor_db,
+            music_level_db,
+            dc_offset: self.sums.iter().map(|sum| sum / frames / max_value).collect(),
+            channel_levels_db: self.sum_squares.iter().map(|square| {
+                let rms = (square / frames).sqrt() / max_value;
+                if rms > 0.0 { 20.0 * rms.log10() } else { -80.0 }
+            }).collect(),
+            dropouts: self.dropouts.finish(),
+        }
+    }
+}
+
+/// Measure the quality of a whole WAV file
+pub fn quality_report(path: &str, progress: &mut dyn ProgressSink) -> Result<QualityReport> {
+    let wav = WavData::open(path)?;
+    let mut analyzer = QualityAnalyzer::new(wav.format, wav.channels(), wav.header.sample_rate);
+    wav.for_each_data_chunk(false, |data, percent| {
+        analyzer.process_bytes(data);
+        progress.progress(percent, STAGE_QUALITY, "");
+        Ok(())
+    })?;
+    progress.finish(STAGE_QUALITY);
+    Ok(analyzer.finish())
+}
+
 /// Apply a moving average smoothing filter in the linear domain.
 ///
 /// Converts dB to linear, applies moving average, then converts back to dB.
@@ -114,3 +867,191 @@ pub fn estimate_music_level(smoothed: &[f32]) -> f32 {
         sorted[p60.min(sorted.len() - 1)]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::JsonLinesProgress;
+    use crate::wavfile::write_wav_samples;
+
+    fn interleave_s16(channels: &[Vec<i32>]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for i in 0..channels[0].len() {
+            for ch in channels {
+                bytes.extend_from_slice(&(ch[i] as i16).to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    fn test_signal(frames: usize) -> Vec<Vec<i32>> {
+        let left = (0..frames).map(|i| ((i as f64 * 0.05).sin() * 12000.0) as i32).collect();
+        let right = (0..frames).map(|i| ((i as f64 * 0.013).cos() * 3000.0) as i32).collect();
+        vec![left, right]
+    }
+
+    #[test]
+    fn test_rms_analyzer_matches_compute_rms_db() {
+        let audio = test_signal(1050);
+        let bytes = interleave_s16(&audio);
+
+        let expected: Vec<f32> = (0..audio[0].len())
+            .step_by(100)
+            .map(|start| {
+                let end = (start + 100).min(audio[0].len());
+                let window: Vec<Vec<i32>> = audio.iter().map(|ch| ch[start..end].to_vec()).collect();
+                compute_rms_db(&window, SampleFormat::S16)
+            })
+            .collect();
+
+        // Odd piece sizes split frames and samples across calls
+        let mut analyzer = RmsAnalyzer::new(SampleFormat::S16, 2, 100);
+        for piece in bytes.chunks(333) {
+            analyzer.process_bytes(piece);
+        }
+        assert_eq!(analyzer.values().len(), 10);
+        assert_eq!(analyzer.finish(), expected);
+    }
+
+    #[test]
+    fn test_wav_rms_mid_side() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ms.wav").to_string_lossy().to_string();
+        // Silent mid, loud side: only the mid channel counts
+        let mut writer = crate::wavfile::WavWriter::new(&path, 1000, 2, SampleFormat::S16).unwrap();
+        writer.write_samples(&[0, 16384].repeat(100)).unwrap();
+        writer.set_ixml(crate::wavfile::WavIxml {
+            track_names: Downmix::MidSide.channel_names(2),
+            ..Default::default()
+        });
+        writer.finalize().unwrap();
+        assert_eq!(wav_rms(&path, 100, false, &[], &mut NoProgress).unwrap(), vec![-80.0]);
+        // An explicit selection wins
+        assert!(wav_rms(&path, 100, false, &[1], &mut NoProgress).unwrap()[0] > -7.0);
+    }
+
+    #[test]
+    fn test_wav_rms_read_and_mmap() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rms.wav").to_string_lossy().to_string();
+        let audio = test_signal(4800);
+        write_wav_samples(&path, &audio, 48000, 16).unwrap();
+
+        let mut progress = JsonLinesProgress::new(Vec::new());
+        let read = wav_rms(&path, 20, false, &[], &mut progress).unwrap();
+        let mapped = wav_rms(&path, 20, true, &[], &mut crate::progress::NoProgress).unwrap();
+        assert_eq!(read.len(), 5);
+        assert_eq!(read, mapped);
+        let updates = String::from_utf8(progress.into_inner()).unwrap();
+        let last: serde_json::Value = serde_json::from_str(updates.lines().last().unwrap()).unwrap();
+        assert_eq!(last["stage"], "rms");
+        assert_eq!(last["percent"], 100.0);
+
+        let window: Vec<Vec<i32>> = audio.iter().map(|ch| ch[..960].to_vec()).collect();
+        assert_eq!(read[0], compute_rms_db(&window, SampleFormat::S16));
+
+        // Computed in the background, handed out only for the file it was started for
+        let mut prefetch = RmsPrefetch::new(20, false, &[]);
+        prefetch.start(&path);
+        assert!(prefetch.take("other.wav").is_none());
+        assert_eq!(prefetch.take(&path).unwrap().unwrap(), read);
+        assert!(prefetch.take(&path).is_none());
+    }
+
+    #[test]
+    fn test_multichannel_analysis() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("quad.wav").to_string_lossy().to_string();
+        let stereo_path = dir.path().join("stereo.wav").to_string_lossy().to_string();
+        // Two decks: one silent, the other playing on channels 3 and 4
+        let audio = test_signal(4800);
+        let silence = vec![0; 4800];
+        write_wav_samples(&path, &[silence.clone(), silence, audio[0].clone(), audio[1].clone()], 48000, 16).unwrap();
+        write_wav_samples(&stereo_path, &audio, 48000, 16).unwrap();
+
+        assert_eq!(parse_channel_list("3, 4"), Some(vec![2, 3]));
+        assert_eq!(parse_channel_list("0,1"), None);
+        let deck = wav_rms(&path, 20, false, &[2, 3], &mut NoProgress).unwrap();
+        assert_eq!(deck, wav_rms(&stereo_path, 20, false, &[], &mut NoProgress).unwrap());
+        assert!(wav_rms(&path, 20, false, &[4], &mut NoProgress).is_err());
+
+        // Balance only for a selected pair
+        assert!(wav_channel_balance(&path, &[]).unwrap().is_none());
+        let balance = wav_channel_balance(&path, &[2, 3]).unwrap().unwrap();
+        assert!(balance.left_db > balance.right_db + 10.0, "{:?}", balance);
+
+        let report = quality_report(&path, &mut NoProgress).unwrap();
+        assert_eq!(report.channel_levels_db.len(), 4);
+        assert_eq!(report.channel_levels_db[0], -80.0);
+        assert!(report.channel_levels_db[2] > -40.0);
+        assert!(report.to_text().contains("Channel levels: Ch 1 -80.0 dBFS, Ch 2 -80.0 dBFS, Ch 3"));
+    }
+
+    #[test]
+    fn test_quality_report() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("quality.wav").to_string_lossy().to_string();
+        // 1 s of groove noise, then 3 s of music with a 5 ms dropout at 2.5 s;
+        // the left channel has a DC offset of 330 (1% of full scale)
+        let mut state = 12345u32;
+        let mut noise = move || {
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            (state >> 16) as i32 % 60 - 30
+        };
+        let mut left = Vec::new();
+        let mut right = Vec::new();
+        for i in 0..4 * 48000 {
+            let music = if i >= 48000 { ((i as f64 * 0.05).sin() * 8000.0) as i32 } else { 0 };
+            let dropout = (120000..120240).contains(&i);
+            left.push(if dropout { 0 } else { music + noise() + 330 });
+            right.push(if dropout { 0 } else { music + noise() });
+        }
+        write_wav_samples(&path, &[left, right], 48000, 16).unwrap();
+
+        let report = quality_report(&path, &mut crate::progress::NoProgress).unwrap();
+        assert!((report.dc_offset[0] - 0.01).abs() < 0.0005, "{:?}", report.dc_offset);
+        assert!(report.dc_offset[1].abs() < 0.0005);
+        assert_eq!(report.dropouts.len(), 1);
+        assert!((report.dropouts[0].start_seconds - 2.5).abs() < 0.001);
+        assert!((report.dropouts[0].duration_seconds - 0.005).abs() < 0.0001);
+        assert!(report.snr_db() > 30.0, "{:?}", report);
+        let text = report.to_text();
+        assert!(text.contains("Dropouts: 1\n  5.0 ms at 0:02.50"), "{}", text);
+        assert!(text.contains("DC offset: L +1.0"), "{}", text);
+        assert_eq!(report.warnings().len(), 2);
+    }
+
+    #[test]
+    fn test_channel_balance() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("balance.wav").to_string_lossy().to_string();
+        // Right channel 2.3 dB louder than the left, same signal
+        let left: Vec<i32> = (0..48000).map(|i| ((i as f64 * 0.05).sin() * 8000.0) as i32).collect();
+        let gain = 10.0_f64.powf(2.3 / 20.0);
+        let right: Vec<i32> = left.iter().map(|&s| (s as f64 * gain) as i32).collect();
+        write_wav_samples(&path, &[left.clone(), right], 48000, 16).unwrap();
+
+        let balance = wav_channel_balance(&path, &[]).unwrap().unwrap();
+        assert!((balance.difference_db() - 2.3).abs() < 0.05, "{:?}", balance);
+        assert!(balance.correlation > 0.99);
+        assert_eq!(balance.describe_difference(), "right channel is 2.3 dB hotter");
+        assert_eq!(balance.warnings().len(), 1);
+        assert!(balance.to_text().contains("Warning: right channel is 2.3 dB hotter"));
+
+        // Inverted channel
+        let inverted: Vec<i32> = left.iter().map(|&s| -s).collect();
+        let bytes = interleave_s16(&[left.clone(), inverted]);
+        let mut analyzer = BalanceAnalyzer::new(SampleFormat::S16, 2);
+        for piece in bytes.chunks(333) {
+            analyzer.process_bytes(piece);
+        }
+        let balance = analyzer.finish().unwrap();
+        assert!(balance.correlation < -0.99);
+        assert!(balance.warnings()[0].contains("out of phase"));
+
+        // Mono has no balance
+        let mut analyzer = BalanceAnalyzer::new(SampleFormat::S16, 1);
+        analyzer.process_bytes(&interleave_s16(&[left]));
+        assert!(analyzer.finish().is_none());
+    }
+}
@@ -1,14 +1,17 @@
+use crate::error::{Error, Result};
+use crate::resampler::Resampler;
 use crate::vu_meter::SampleFormat;
+use std::collections::VecDeque;
 use std::io::Read;
 use std::process::{Child, Command, Stdio};
 use std::fs::File;
 use std::path::Path;
 use std::time::{Duration, Instant};
 use std::sync::{Arc, Mutex};
-use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread::{self, JoinHandle};
 use symphonia::core::audio::{AudioBufferRef, Signal};
 use symphonia::core::codecs::{Decoder, DecoderOptions};
+use symphonia::core::errors::Error as SymphoniaError;
 use symphonia::core::formats::{FormatOptions, FormatReader};
 use symphonia::core::io::MediaSourceStream;
 use symphonia::core::meta::MetadataOptions;
@@ -16,15 +19,21 @@ use symphonia::core::probe::Hint;
 use pipewire as pw;
 use pw::spa::param::audio::{AudioFormat, AudioInfoRaw};
 use pw::spa::pod::Pod;
+use tracing::{error, warn};
 
 /// Parse an audio source address in the format "backend:device"
-/// Examples: "pipewire:input1", "pwpipe:input1", "alsa:hw:0,0", "file:/path/to/audio.wav"
+/// Examples: "pipewire:input1", "pwpipe:input1", "alsa:hw:0,0", "file:/path/to/audio.wav",
+/// "jack:system:capture_1,system:capture_2", "pulse:alsa_input.usb-0d8c.analog-stereo",
+/// "net:rtp://0.0.0.0:5004", "stdin:", "raw:/path/to/fifo"
 /// If no backend is specified, tries to auto-detect
-pub fn parse_audio_address(address: &str) -> Result<(String, String), String> {
+pub fn parse_audio_address(address: &str) -> Result<(String, String)> {
     // First check for ALSA-style addresses without explicit backend
     if address.starts_with("hw:") || address.starts_with("plughw:") || address == "default" {
         return Ok(("alsa".to_string(), address.to_string()));
     }
+    if address == "-" || address == "stdin" {
+        return Ok(("raw".to_string(), "-".to_string()));
+    }
     
     // Look for backend prefix
     if let Some(colon_pos) = address.find(':') {
@@ -35,7 +44,13 @@ pub fn parse_audio_address(address: &str) -> Result<(String, String), String> {
             "pipewire" | "pw" => Ok(("pipewire".to_string(), device.to_string())),
             "pwpipe" => Ok(("pwpipe".to_string(), device.to_string())),
             "alsa" => Ok(("alsa".to_string(), device.to_string())),
+            "jack" => Ok(("jack".to_string(), device.to_string())),
+            "pulse" | "pa" => Ok(("pulse".to_string(), device.to_string())),
             "file" => Ok(("file".to_string(), device.to_string())),
+            "net" => Ok(("net".to_string(), device.to_string())),
+            "stdin" => Ok(("raw".to_string(), "-".to_string())),
+            "raw" if device.is_empty() => Ok(("raw".to_string(), "-".to_string())),
+            "raw" => Ok(("raw".to_string(), device.to_string())),
             _ => {
                 // Unknown backend, default to PipeWire for compatibility
                 Ok(("pipewire".to_string(), address.to_string()))
@@ -60,7 +75,7 @@ pub fn create_input_stream(
     rate: u32,
     channels: usize,
     format: SampleFormat,
-) -> Result<Box<dyn AudioInputStream>, String> {
+) -> Result<Box<dyn AudioInputStream>> {
     let (backend, device) = parse_audio_address(address)?;
     
     match backend.as_str() {
@@ -73,9 +88,46 @@ pub fn create_input_stream(
         "alsa" => Ok(Box::new(AlsaInputStream::new(
             device, rate, channels, format,
         ))),
+        "pulse" => Ok(Box::new(PulseInputStream::new(
+            device, rate, channels, format,
+        ))),
+        #[cfg(feature = "jack")]
+        "jack" => Ok(Box::new(crate::jack_stream::JackInputStream::new(
+            &device, rate, channels, format,
+        )?)),
+        #[cfg(not(feature = "jack"))]
+        "jack" => Err(Error::Config("JACK support is not enabled (build with --features jack)".to_string())),
         "file" => FileInputStream::new(device, rate, channels, format)
             .map(|s| Box::new(s) as Box<dyn AudioInputStream>),
-        _ => Err(format!("Unsupported backend: {}", backend)),
+        "net" => Ok(Box::new(crate::net_stream::NetInputStream::new(
+            &device, rate, channels, format,
+        )?)),
+        "raw" => Ok(Box::new(RawInputStream::new(
+            device, rate, channels, format,
+        ))),
+        _ => Err(Error::Config(format!("Unsupported backend: {}", backend))),
+    }
+}
+
+/// Create an input stream that passes on only the device channels in
+/// `channel_map` (0-based, in this order), e.g. `[2, 3]` for inputs 3 and 4
+/// of a multi-input interface; an empty map passes on all of them.
+///
+/// The device is opened with `channels` channels, or with as many as the
+/// highest mapped channel needs.
+pub fn create_mapped_input_stream(
+    address: &str,
+    rate: u32,
+    channels: usize,
+    format: SampleFormat,
+    channel_map: &[usize],
+) -> Result<Box<dyn AudioInputStream>> {
+    let device_channels = channel_map.iter().map(|&c| c + 1).fold(channels, usize::max);
+    let stream = create_input_stream(address, rate, device_channels, format)?;
+    if channel_map.is_empty() {
+        Ok(stream)
+    } else {
+        Ok(Box::new(ChannelMapStream::new(stream, channel_map.to_vec())?))
     }
 }
 
@@ -108,13 +160,50 @@ pub trait AudioInputStream: AudioStream {
     fn read_chunk(&mut self, frames: usize) -> Option<Vec<Vec<i32>>>;
     
     /// Start the audio input stream
-    fn start(&mut self) -> Result<(), String>;
+    fn start(&mut self) -> Result<()>;
     
     /// Stop the audio input stream
     fn stop(&mut self);
     
     /// Check if the stream is active
     fn is_active(&self) -> bool;
+    
+    /// Whether the input has ended (end of a file) rather than being lost
+    fn at_end(&self) -> bool {
+        false
+    }
+}
+
+/// Trait for audio output streams that play audio data
+pub trait AudioOutputStream: AudioStream {
+    /// Queue a chunk of audio for playback
+    /// The chunk is a vector of channels, where each channel is a vector of samples
+    fn write_chunk(&mut self, chunk: &[Vec<i32>]) -> Result<()>;
+    
+    /// Number of queued frames that have not been played yet
+    fn pending_frames(&self) -> usize;
+    
+    /// Start the audio output stream
+    fn start(&mut self) -> Result<()>;
+    
+    /// Stop the audio output stream, dropping queued audio
+    fn stop(&mut self);
+    
+    /// Check if the stream is active
+    fn is_active(&self) -> bool;
+    
+    /// Wait until all queued audio has been played.
+    /// Returns false if it was not played within `timeout`.
+    fn drain(&mut self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        while self.pending_frames() > 0 {
+            if !self.is_active() || Instant::now() >= deadline {
+                return false;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        true
+    }
 }
 
 /// Native PipeWire audio input stream using the Rust pipewire crate
@@ -126,12 +215,13 @@ pub struct PipeWireInputStream {
     active: bool,
     buffer: Arc<Mutex<Vec<Vec<i32>>>>,
     thread_handle: Option<JoinHandle<()>>,
-    quit_flag: Arc<AtomicBool>,
+    /// Tells the mainloop of the capture thread to quit
+    quit_sender: Option<pw::channel::Sender<()>>,
 }
 
 impl PipeWireInputStream {
     /// Create a new native PipeWire input stream
-    pub fn new(target: String, rate: u32, channels: usize, format: SampleFormat) -> Result<Self, String> {
+    pub fn new(target: String, rate: u32, channels: usize, format: SampleFormat) -> Result<Self> {
         Ok(PipeWireInputStream {
             target,
             rate,
@@ -140,9 +230,39 @@ impl PipeWireInputStream {
             active: false,
             buffer: Arc::new(Mutex::new(Vec::new())),
             thread_handle: None,
-            quit_flag: Arc::new(AtomicBool::new(false)),
+            quit_sender: None,
         })
     }
+
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// Switch to another capture target (e.g. after a device was replugged).
+    ///
+    /// A running stream is stopped and started again on the new target.
+    pub fn set_target(&mut self, target: String) -> Result<()> {
+        let was_active = self.active;
+        self.stop();
+        self.target = target;
+        if was_active {
+            self.start()?;
+        }
+        Ok(())
+    }
+
+    /// Change rate, channels and sample format, restarting a running stream
+    pub fn reconfigure(&mut self, rate: u32, channels: usize, format: SampleFormat) -> Result<()> {
+        let was_active = self.active;
+        self.stop();
+        self.rate = rate;
+        self.channels = channels;
+        self.format = format;
+        if was_active {
+            self.start()?;
+        }
+        Ok(())
+    }
 }
 
 impl AudioStream for PipeWireInputStream {
@@ -193,7 +313,7 @@ impl AudioInputStream for PipeWireInputStream {
         Some(result)
     }
     
-    fn start(&mut self) -> Result<(), String> {
+    fn start(&mut self) -> Result<()> {
         if self.active {
             return Ok(());
         }
@@ -205,9 +325,9 @@ impl AudioInputStream for PipeWireInputStream {
         let target = self.target.clone();
         let target_for_linking = self.target.clone();
         
-        // Reset quit flag
-        self.quit_flag.store(false, Ordering::Relaxed);
-        let _quit_flag_thread = self.quit_flag.clone();
+        // The receiver is attached to the mainloop inside the thread, as the
+        // mainloop itself cannot be shared across threads
+        let (quit_sender, quit_receiver) = pw::channel::channel::<()>();
         
         // Spawn thread to run PipeWire mainloop
         let thread_handle = thread::spawn(move || {
@@ -217,15 +337,20 @@ impl AudioInputStream for PipeWireInputStream {
             let main_loop = match pw::main_loop::MainLoop::new(None) {
                 Ok(ml) => ml,
                 Err(e) => {
-                    eprintln!("Failed to create main loop: {:?}", e);
+                    error!("Failed to create main loop: {:?}", e);
                     return;
                 }
             };
             
+            let _quit = quit_receiver.attach(main_loop.loop_(), {
+                let main_loop = main_loop.clone();
+                move |_| main_loop.quit()
+            });
+            
             let context = match pw::context::Context::new(&main_loop) {
                 Ok(ctx) => ctx,
                 Err(e) => {
-                    eprintln!("Failed to create context: {:?}", e);
+                    error!("Failed to create context: {:?}", e);
                     return;
                 }
             };
@@ -233,7 +358,7 @@ impl AudioInputStream for PipeWireInputStream {
             let core = match context.connect(None) {
                 Ok(c) => c,
                 Err(e) => {
-                    eprintln!("Failed to connect to PipeWire: {:?}", e);
+                    error!("Failed to connect to PipeWire: {:?}", e);
                     return;
                 }
             };
@@ -241,6 +366,8 @@ impl AudioInputStream for PipeWireInputStream {
             // Create audio format info
             let audio_format = match format {
                 SampleFormat::S16 => AudioFormat::S16LE,
+                SampleFormat::S24 => AudioFormat::S24LE,
+                SampleFormat::S24In32 => AudioFormat::S24_32LE,
                 SampleFormat::S32 => AudioFormat::S32LE,
             };
             
@@ -261,7 +388,7 @@ impl AudioInputStream for PipeWireInputStream {
             ) {
                 Ok(s) => s,
                 Err(e) => {
-                    eprintln!("Failed to create stream: {:?}", e);
+                    error!("Failed to create stream: {:?}", e);
                     return;
                 }
             };
@@ -287,26 +414,10 @@ impl AudioInputStream for PipeWireInputStream {
                                 for frame in 0..num_frames {
                                     for ch in 0..channels {
                                         let offset = frame * frame_size + ch * bytes_per_sample;
-                                        let sample = match format {
-                                            SampleFormat::S16 => {
-                                                if offset + 2 <= samples_slice.len() {
-                                                    i16::from_le_bytes([samples_slice[offset], samples_slice[offset + 1]]) as i32
-                                                } else {
-                                                    0
-                                                }
-                                            }
-                                            SampleFormat::S32 => {
-                                                if offset + 4 <= samples_slice.len() {
-                                                    i32::from_le_bytes([
-                                                        samples_slice[offset],
-                                                        samples_slice[offset + 1],
-                                                        samples_slice[offset + 2],
-                                                        samples_slice[offset + 3],
-                                                    ])
-                                                } else {
-                                                    0
-                                                }
-                                            }
+                                        let sample = if offset + bytes_per_sample <= samples_slice.len() {
+                                            format.decode_sample(&samples_slice[offset..offset + bytes_per_sample])
+                                        } else {
+                                            0
                                         };
                                         channel_samples[ch].push(sample);
                                     }
@@ -328,7 +439,7 @@ impl AudioInputStream for PipeWireInputStream {
                 .register();
             
             if _listener.is_err() {
-                eprintln!("Failed to register listener");
+                error!("Failed to register listener");
                 return;
             }
             
@@ -344,7 +455,7 @@ impl AudioInputStream for PipeWireInputStream {
             ) {
                 Ok((cursor, _)) => cursor.into_inner(),
                 Err(e) => {
-                    eprintln!("Failed to serialize audio info: {:?}", e);
+                    error!("Failed to serialize audio info: {:?}", e);
                     return;
                 }
             };
@@ -368,19 +479,17 @@ impl AudioInputStream for PipeWireInputStream {
                 stream_flags,
                 &mut params,
             ) {
-                eprintln!("Failed to connect stream: {:?}", e);
+                error!("Failed to connect stream: {:?}", e);
                 return;
             }
             
-            // Run the main loop (blocks until quit() is called)
-            // Note: Currently we cannot gracefully stop the mainloop because:
-            // 1. iterate() doesn't properly trigger audio callbacks
-            // 2. MainLoop uses Rc and cannot be shared across threads
-            // The thread will be killed when the process exits
+            // Run the main loop until stop() sends the quit message
             main_loop.run();
+            let _ = stream.disconnect();
         });
         
         self.thread_handle = Some(thread_handle);
+        self.quit_sender = Some(quit_sender);
         self.active = true;
         
         // If we have a specific target, manually connect using pw-link
@@ -404,8 +513,9 @@ impl AudioInputStream for PipeWireInputStream {
             }
             
             if !ports_ready {
-                eprintln!("Warning: autorecord ports not ready after 3 seconds");
-                return Err("Stream ports not ready".to_string());
+                warn!("autorecord ports not ready after 3 seconds");
+                self.stop();
+                return Err(Error::Backend("Stream ports not ready".to_string()));
             }
             
             // Connect left channel
@@ -416,9 +526,13 @@ impl AudioInputStream for PipeWireInputStream {
             
             if let Ok(output) = output_l {
                 if !output.status.success() {
-                    eprintln!("Error: Failed to link {}:capture_FL -> autorecord:input_FL", target_for_linking);
-                    eprintln!("  {}", String::from_utf8_lossy(&output.stderr));
-                    return Err("Failed to link audio source".to_string());
+                    error!(
+                        "Failed to link {}:capture_FL -> autorecord:input_FL: {}",
+                        target_for_linking,
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    );
+                    self.stop();
+                    return Err(Error::Backend("Failed to link audio source".to_string()));
                 }
             }
             
@@ -430,9 +544,13 @@ impl AudioInputStream for PipeWireInputStream {
             
             if let Ok(output) = output_r {
                 if !output.status.success() {
-                    eprintln!("Error: Failed to link {}:capture_FR -> autorecord:input_FR", target_for_linking);
-                    eprintln!("  {}", String::from_utf8_lossy(&output.stderr));
-                    return Err("Failed to link audio source".to_string());
+                    error!(
+                        "Failed to link {}:capture_FR -> autorecord:input_FR: {}",
+                        target_for_linking,
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    );
+                    self.stop();
+                    return Err(Error::Backend("Failed to link audio source".to_string()));
                 }
             }
         } else {
@@ -446,8 +564,11 @@ impl AudioInputStream for PipeWireInputStream {
     fn stop(&mut self) {
         self.active = false;
         
-        // Signal the thread to quit
-        self.quit_flag.store(true, Ordering::Relaxed);
+        // Signal the mainloop to quit; this fails if the thread already
+        // ended on an error, which is fine
+        if let Some(sender) = self.quit_sender.take() {
+            let _ = sender.send(());
+        }
         
         // Wait for thread to finish
         if let Some(handle) = self.thread_handle.take() {
@@ -462,6 +583,276 @@ impl AudioInputStream for PipeWireInputStream {
     }
 }
 
+impl Drop for PipeWireInputStream {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Native PipeWire audio output (playback) stream.
+///
+/// Audio written with `write_chunk` is queued and played from the PipeWire
+/// mainloop thread; silence is played while the queue is empty. The stream
+/// goes to `target` (a node name) or, if empty, to the default sink.
+pub struct PipeWireOutputStream {
+    target: String,
+    rate: u32,
+    channels: usize,
+    format: SampleFormat,
+    active: bool,
+    /// Interleaved little-endian samples waiting to be played
+    queue: Arc<Mutex<VecDeque<u8>>>,
+    thread_handle: Option<JoinHandle<()>>,
+    /// Tells the mainloop of the playback thread to quit
+    quit_sender: Option<pw::channel::Sender<()>>,
+}
+
+impl PipeWireOutputStream {
+    /// Create a new native PipeWire output stream
+    pub fn new(target: String, rate: u32, channels: usize, format: SampleFormat) -> Result<Self> {
+        if channels == 0 {
+            return Err(Error::Config("Output stream needs at least one channel".to_string()));
+        }
+        Ok(PipeWireOutputStream {
+            target,
+            rate,
+            channels,
+            format,
+            active: false,
+            queue: Arc::new(Mutex::new(VecDeque::new())),
+            thread_handle: None,
+            quit_sender: None,
+        })
+    }
+
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// Change rate, channels and sample format, restarting a running stream
+    pub fn reconfigure(&mut self, rate: u32, channels: usize, format: SampleFormat) -> Result<()> {
+        let was_active = self.active;
+        self.stop();
+        self.rate = rate;
+        self.channels = channels;
+        self.format = format;
+        if was_active {
+            self.start()?;
+        }
+        Ok(())
+    }
+}
+
+impl AudioStream for PipeWireOutputStream {
+    fn sample_rate(&self) -> u32 {
+        self.rate
+    }
+    
+    fn channels(&self) -> usize {
+        self.channels
+    }
+    
+    fn sample_format(&self) -> SampleFormat {
+        self.format
+    }
+}
+
+impl AudioOutputStream for PipeWireOutputStream {
+    fn write_chunk(&mut self, chunk: &[Vec<i32>]) -> Result<()> {
+        if chunk.len() != self.channels {
+            return Err(Error::Decode(format!(
+                "Output stream expects {} channels, got {}",
+                self.channels,
+                chunk.len()
+            )));
+        }
+        let frames = chunk.iter().map(|ch| ch.len()).min().unwrap_or(0);
+        let mut bytes = Vec::with_capacity(frames * self.bytes_per_frame());
+        for frame in 0..frames {
+            for channel in chunk {
+                self.format.encode_sample(channel[frame], &mut bytes);
+            }
+        }
+        self.queue.lock().unwrap().extend(bytes);
+        Ok(())
+    }
+    
+    fn pending_frames(&self) -> usize {
+        self.queue.lock().unwrap().len() / self.bytes_per_frame()
+    }
+    
+    fn start(&mut self) -> Result<()> {
+        if self.active {
+            return Ok(());
+        }
+        
+        let queue = self.queue.clone();
+        let rate = self.rate;
+        let channels = self.channels;
+        let format = self.format;
+        let target = self.target.clone();
+        let frame_size = self.bytes_per_frame();
+        
+        let (quit_sender, quit_receiver) = pw::channel::channel::<()>();
+        
+        let thread_handle = thread::spawn(move || {
+            pw::init();
+            
+            let main_loop = match pw::main_loop::MainLoop::new(None) {
+                Ok(ml) => ml,
+                Err(e) => {
+                    error!("Failed to create main loop: {:?}", e);
+                    return;
+                }
+            };
+            
+            let _quit = quit_receiver.attach(main_loop.loop_(), {
+                let main_loop = main_loop.clone();
+                move |_| main_loop.quit()
+            });
+            
+            let context = match pw::context::Context::new(&main_loop) {
+                Ok(ctx) => ctx,
+                Err(e) => {
+                    error!("Failed to create context: {:?}", e);
+                    return;
+                }
+            };
+            
+            let core = match context.connect(None) {
+                Ok(c) => c,
+                Err(e) => {
+                    error!("Failed to connect to PipeWire: {:?}", e);
+                    return;
+                }
+            };
+            
+            let audio_format = match format {
+                SampleFormat::S16 => AudioFormat::S16LE,
+                SampleFormat::S24 => AudioFormat::S24LE,
+                SampleFormat::S24In32 => AudioFormat::S24_32LE,
+                SampleFormat::S32 => AudioFormat::S32LE,
+            };
+            
+            let mut audio_info = AudioInfoRaw::new();
+            audio_info.set_format(audio_format);
+            audio_info.set_rate(rate);
+            audio_info.set_channels(channels as u32);
+            
+            let mut properties = pw::properties::properties! {
+                *pw::keys::MEDIA_TYPE => "Audio",
+                *pw::keys::MEDIA_CATEGORY => "Playback",
+                *pw::keys::MEDIA_ROLE => "Music",
+            };
+            if !target.is_empty() {
+                properties.insert(*pw::keys::TARGET_OBJECT, target.as_str());
+            }
+            
+            let stream = match pw::stream::Stream::new(&core, "autorec-playback", properties) {
+                Ok(s) => s,
+                Err(e) => {
+                    error!("Failed to create stream: {:?}", e);
+                    return;
+                }
+            };
+            
+            // Fill each buffer from the queue, padding with silence
+            let _listener = stream
+                .add_local_listener_with_user_data(())
+                .process(move |stream, _user_data| {
+                    if let Some(mut buffer_data) = stream.dequeue_buffer() {
+                        let datas = buffer_data.datas_mut();
+                        if let Some(data) = datas.first_mut() {
+                            let mut size = 0;
+                            if let Some(slice) = data.data() {
+                                size = slice.len() / frame_size * frame_size;
+                                let mut queue = queue.lock().unwrap();
+                                let available = queue.len().min(size);
+                                for (dst, src) in slice[..available].iter_mut().zip(queue.drain(..available)) {
+                                    *dst = src;
+                                }
+                                slice[available..size].fill(0);
+                            }
+                            let chunk = data.chunk_mut();
+                            *chunk.offset_mut() = 0;
+                            *chunk.stride_mut() = frame_size as i32;
+                            *chunk.size_mut() = size as u32;
+                        }
+                    }
+                })
+                .register();
+            
+            if _listener.is_err() {
+                error!("Failed to register listener");
+                return;
+            }
+            
+            let obj = pw::spa::pod::Object {
+                type_: pw::spa::utils::SpaTypes::ObjectParamFormat.as_raw(),
+                id: pw::spa::param::ParamType::EnumFormat.as_raw(),
+                properties: audio_info.into(),
+            };
+            let values: Vec<u8> = match pw::spa::pod::serialize::PodSerializer::serialize(
+                std::io::Cursor::new(Vec::new()),
+                &pw::spa::pod::Value::Object(obj),
+            ) {
+                Ok((cursor, _)) => cursor.into_inner(),
+                Err(e) => {
+                    error!("Failed to serialize audio info: {:?}", e);
+                    return;
+                }
+            };
+            
+            let mut params = [Pod::from_bytes(&values).unwrap()];
+            
+            // The session manager links the stream to target.object or the default sink
+            if let Err(e) = stream.connect(
+                pw::spa::utils::Direction::Output,
+                None,
+                pw::stream::StreamFlags::AUTOCONNECT
+                    | pw::stream::StreamFlags::MAP_BUFFERS
+                    | pw::stream::StreamFlags::RT_PROCESS,
+                &mut params,
+            ) {
+                error!("Failed to connect stream: {:?}", e);
+                return;
+            }
+            
+            main_loop.run();
+            let _ = stream.disconnect();
+        });
+        
+        self.thread_handle = Some(thread_handle);
+        self.quit_sender = Some(quit_sender);
+        self.active = true;
+        Ok(())
+    }
+    
+    fn stop(&mut self) {
+        self.active = false;
+        
+        if let Some(sender) = self.quit_sender.take() {
+            let _ = sender.send(());
+        }
+        
+        if let Some(handle) = self.thread_handle.take() {
+            let _ = handle.join();
+        }
+        
+        self.queue.lock().unwrap().clear();
+    }
+    
+    fn is_active(&self) -> bool {
+        self.active
+    }
+}
+
+impl Drop for PipeWireOutputStream {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
 /// PipeWire-based audio input stream using pw-record subprocess (legacy)
 pub struct PwPipeInputStream {
     target: String,
@@ -513,16 +904,7 @@ impl AudioInputStream for PwPipeInputStream {
         }
         
         // Convert bytes to samples
-        let samples: Vec<i32> = match format {
-            SampleFormat::S16 => buffer
-                .chunks_exact(2)
-                .map(|chunk| i16::from_le_bytes([chunk[0], chunk[1]]) as i32)
-                .collect(),
-            SampleFormat::S32 => buffer
-                .chunks_exact(4)
-                .map(|chunk| i32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
-                .collect(),
-        };
+        let samples = format.decode_samples(&buffer);
         
         // Reshape into channels
         let mut audio = vec![Vec::new(); channels];
@@ -533,7 +915,7 @@ impl AudioInputStream for PwPipeInputStream {
         Some(audio)
     }
     
-    fn start(&mut self) -> Result<(), String> {
+    fn start(&mut self) -> Result<()> {
         let process = Command::new("pw-record")
             .arg("--target")
             .arg(&self.target)
@@ -547,7 +929,7 @@ impl AudioInputStream for PwPipeInputStream {
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .spawn()
-            .map_err(|e| format!("Failed to start pw-record: {}", e))?;
+            .map_err(|e| Error::Backend(format!("Failed to start pw-record: {}", e)))?;
         
         self.process = Some(process);
         Ok(())
@@ -622,16 +1004,7 @@ impl AudioInputStream for AlsaInputStream {
         }
         
         // Convert bytes to samples
-        let samples: Vec<i32> = match format {
-            SampleFormat::S16 => buffer
-                .chunks_exact(2)
-                .map(|chunk| i16::from_le_bytes([chunk[0], chunk[1]]) as i32)
-                .collect(),
-            SampleFormat::S32 => buffer
-                .chunks_exact(4)
-                .map(|chunk| i32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
-                .collect(),
-        };
+        let samples = format.decode_samples(&buffer);
         
         // Reshape into channels
         let mut audio = vec![Vec::new(); channels];
@@ -642,10 +1015,12 @@ impl AudioInputStream for AlsaInputStream {
         Some(audio)
     }
     
-    fn start(&mut self) -> Result<(), String> {
+    fn start(&mut self) -> Result<()> {
         // Format the ALSA format string
         let alsa_format = match self.format {
             SampleFormat::S16 => "S16_LE",
+            SampleFormat::S24 => "S24_3LE",
+            SampleFormat::S24In32 => "S24_LE",
             SampleFormat::S32 => "S32_LE",
         };
         
@@ -664,7 +1039,7 @@ impl AudioInputStream for AlsaInputStream {
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .spawn()
-            .map_err(|e| format!("Failed to start arecord: {}", e))?;
+            .map_err(|e| Error::Backend(format!("Failed to start arecord: {}", e)))?;
         
         self.process = Some(process);
         Ok(())
@@ -688,8 +1063,230 @@ impl Drop for AlsaInputStream {
     }
 }
 
+/// PulseAudio input stream using the parec subprocess
+///
+/// For systems that run PulseAudio instead of PipeWire. The device is a Pulse
+/// source name as shown by `pactl list short sources`; an empty name or
+/// "default" records from the default source.
+pub struct PulseInputStream {
+    device: String,
+    rate: u32,
+    channels: usize,
+    format: SampleFormat,
+    process: Option<Child>,
+}
+
+impl PulseInputStream {
+    /// Create a new PulseAudio input stream
+    pub fn new(device: String, rate: u32, channels: usize, format: SampleFormat) -> Self {
+        PulseInputStream {
+            device,
+            rate,
+            channels,
+            format,
+            process: None,
+        }
+    }
+    
+    /// Arguments for parec
+    fn parec_args(&self) -> Vec<String> {
+        let pulse_format = match self.format {
+            SampleFormat::S16 => "s16le",
+            SampleFormat::S24 => "s24le",
+            SampleFormat::S24In32 => "s24-32le",
+            SampleFormat::S32 => "s32le",
+        };
+        let mut args = vec![
+            "--raw".to_string(),
+            format!("--rate={}", self.rate),
+            format!("--channels={}", self.channels),
+            format!("--format={}", pulse_format),
+            "--client-name=autorec".to_string(),
+        ];
+        if !self.device.is_empty() && self.device != "default" {
+            args.push(format!("--device={}", self.device));
+        }
+        args
+    }
+}
+
+impl AudioStream for PulseInputStream {
+    fn sample_rate(&self) -> u32 {
+        self.rate
+    }
+    
+    fn channels(&self) -> usize {
+        self.channels
+    }
+    
+    fn sample_format(&self) -> SampleFormat {
+        self.format
+    }
+}
+
+impl AudioInputStream for PulseInputStream {
+    fn read_chunk(&mut self, frames: usize) -> Option<Vec<Vec<i32>>> {
+        let chunk_size = frames * self.bytes_per_frame();
+        let format = self.format;
+        let channels = self.channels;
+        
+        let process = self.process.as_mut()?;
+        let stdout = process.stdout.as_mut()?;
+        let mut buffer = vec![0u8; chunk_size];
+        
+        if stdout.read_exact(&mut buffer).is_err() {
+            return None;
+        }
+        
+        // Convert bytes to samples
+        let samples = format.decode_samples(&buffer);
+        
+        // Reshape into channels
+        let mut audio = vec![Vec::new(); channels];
+        for (i, sample) in samples.iter().enumerate() {
+            audio[i % channels].push(*sample);
+        }
+        
+        Some(audio)
+    }
+    
+    fn start(&mut self) -> Result<()> {
+        let process = Command::new("parec")
+            .args(self.parec_args())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| Error::Backend(format!("Failed to start parec: {}", e)))?;
+        
+        self.process = Some(process);
+        Ok(())
+    }
+    
+    fn stop(&mut self) {
+        if let Some(mut process) = self.process.take() {
+            let _ = process.kill();
+            let _ = process.wait();
+        }
+    }
+    
+    fn is_active(&self) -> bool {
+        self.process.is_some()
+    }
+}
+
+impl Drop for PulseInputStream {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Raw PCM input from stdin or a file/FIFO ("stdin:", "raw:-", "raw:/path")
+///
+/// Reads interleaved little-endian PCM in the configured rate, channel count
+/// and format, so any capture tool can feed the recorder, e.g.
+/// `pw-cat --record --raw -`, `sox -d -t raw -` or `snapclient --player file`.
+/// The end of the input ends the capture; a read error counts as a lost source.
+pub struct RawInputStream {
+    path: String,
+    rate: u32,
+    channels: usize,
+    format: SampleFormat,
+    reader: Option<Box<dyn Read + Send>>,
+    /// The writer closed the input
+    ended: bool,
+}
+
+impl RawInputStream {
+    /// Create a raw input stream; "-" reads from stdin
+    pub fn new(path: String, rate: u32, channels: usize, format: SampleFormat) -> Self {
+        RawInputStream {
+            path,
+            rate,
+            channels,
+            format,
+            reader: None,
+            ended: false,
+        }
+    }
+}
+
+impl AudioStream for RawInputStream {
+    fn sample_rate(&self) -> u32 {
+        self.rate
+    }
+    
+    fn channels(&self) -> usize {
+        self.channels
+    }
+    
+    fn sample_format(&self) -> SampleFormat {
+        self.format
+    }
+}
+
+impl AudioInputStream for RawInputStream {
+    fn read_chunk(&mut self, frames: usize) -> Option<Vec<Vec<i32>>> {
+        let chunk_size = frames * self.bytes_per_frame();
+        let format = self.format;
+        let channels = self.channels;
+        
+        let reader = self.reader.as_mut()?;
+        let mut buffer = vec![0u8; chunk_size];
+        let mut filled = 0;
+        while filled < chunk_size {
+            match reader.read(&mut buffer[filled..]) {
+                Ok(0) => {
+                    self.ended = true;
+                    break;
+                }
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(_) => return None,
+            }
+        }
+        
+        // The last chunk has the complete frames before the end of the input
+        buffer.truncate(filled - filled % self.bytes_per_frame());
+        if buffer.is_empty() {
+            return None;
+        }
+        
+        let mut audio = vec![Vec::with_capacity(frames); channels];
+        for (i, sample) in format.decode_samples(&buffer).into_iter().enumerate() {
+            audio[i % channels].push(sample);
+        }
+        
+        Some(audio)
+    }
+    
+    fn start(&mut self) -> Result<()> {
+        let reader: Box<dyn Read + Send> = if self.path == "-" {
+            Box::new(std::io::stdin())
+        } else {
+            // Opening a FIFO blocks until the writer has opened it
+            Box::new(File::open(&self.path)
+                .map_err(|e| Error::Backend(format!("Failed to open {}: {}", self.path, e)))?)
+        };
+        self.reader = Some(reader);
+        self.ended = false;
+        Ok(())
+    }
+    
+    fn stop(&mut self) {
+        self.reader = None;
+    }
+    
+    fn is_active(&self) -> bool {
+        self.reader.is_some()
+    }
+    
+    fn at_end(&self) -> bool {
+        self.ended
+    }
+}
+
 /// File-based audio input stream for WAV, MP3, and FLAC files
-/// Maintains correct timing by controlling playback speed
+/// Maintains correct timing by controlling playback speed; the stream ends with the file
 pub struct FileInputStream {
     file_path: String,
     rate: u32,
@@ -699,17 +1296,24 @@ pub struct FileInputStream {
     decoder: Option<Box<dyn Decoder>>,
     track_id: Option<u32>,
     active: bool,
+    /// The whole file has been read
+    ended: bool,
     start_time: Option<Instant>,
     frames_read: u64,
     buffer: Vec<Vec<i32>>,  // Buffered samples organized by channel
+    /// Converts the file to the stream rate if they differ
+    resampler: Option<Resampler>,
 }
 
 impl FileInputStream {
     /// Create a new file input stream
-    pub fn new(file_path: String, rate: u32, channels: usize, format: SampleFormat) -> Result<Self, String> {
+    pub fn new(file_path: String, rate: u32, channels: usize, format: SampleFormat) -> Result<Self> {
         // Verify file exists
         if !Path::new(&file_path).exists() {
-            return Err(format!("File not found: {}", file_path));
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("File not found: {}", file_path),
+            )));
         }
         
         Ok(FileInputStream {
@@ -721,40 +1325,44 @@ impl FileInputStream {
             decoder: None,
             track_id: None,
             active: false,
+            ended: false,
             start_time: None,
             frames_read: 0,
             buffer: Vec::new(),
+            resampler: None,
         })
     }
     
     /// Refill the internal buffer by decoding more audio
-    fn refill_buffer(&mut self) -> Result<(), String> {
+    fn refill_buffer(&mut self) -> Result<()> {
         // Read the next packet
         let packet = {
             let format_reader = self.format_reader.as_mut()
-                .ok_or("Format reader not initialized")?;
+                .ok_or_else(|| Error::Decode("Format reader not initialized".to_string()))?;
             match format_reader.next_packet() {
                 Ok(packet) => packet,
-                Err(_) => {
-                    // End of stream - loop back to the beginning
-                    let _ = format_reader; // Release the borrow
-                    self.stop();
-                    self.start()?;
-                    return Ok(());
+                Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                    self.ended = true;
+                    return Err(Error::Decode("End of file".to_string()));
                 }
+                Err(e) => return Err(Error::Decode(format!("Read error: {}", e))),
             }
         };
         
         // Decode the packet and extract sample data immediately
         let (num_channels, channel_data) = {
             let decoder = self.decoder.as_mut()
-                .ok_or("Decoder not initialized")?;
+                .ok_or_else(|| Error::Decode("Decoder not initialized".to_string()))?;
             let decoded = decoder.decode(&packet)
-                .map_err(|e| format!("Decode error: {}", e))?;
+                .map_err(|e| Error::Decode(format!("Decode error: {}", e)))?;
             
             // Extract data from AudioBufferRef before it goes out of scope
             extract_audio_samples(&decoded, self.channels)
         };
+        let channel_data = match self.resampler.as_mut() {
+            Some(resampler) => resampler.process(&channel_data)?,
+            None => channel_data,
+        };
         
         // Now append to our buffer with no borrowing conflicts
         if self.buffer.is_empty() {
@@ -902,9 +1510,14 @@ impl AudioInputStream for FileInputStream {
         }
         
         // Ensure we have enough data in the buffer
+        let mut frames = frames;
         while self.buffer.is_empty() || self.buffer[0].len() < frames {
-            if let Err(_) = self.refill_buffer() {
-                return None;
+            if self.refill_buffer().is_err() {
+                // The rest of the file is returned as a shorter chunk
+                match self.buffer.first().map(Vec::len) {
+                    Some(rest) if self.ended && rest > 0 => frames = rest,
+                    _ => return None,
+                }
             }
         }
         
@@ -932,14 +1545,14 @@ impl AudioInputStream for FileInputStream {
         Some(result)
     }
     
-    fn start(&mut self) -> Result<(), String> {
+    fn start(&mut self) -> Result<()> {
         if self.active {
             return Ok(());
         }
         
         // Open the file
         let file = File::open(&self.file_path)
-            .map_err(|e| format!("Failed to open file: {}", e))?;
+            .map_err(|e| Error::Io(std::io::Error::new(e.kind(), format!("Failed to open file: {}", e))))?;
         
         // Create a media source stream
         let mss = MediaSourceStream::new(Box::new(file), Default::default());
@@ -953,7 +1566,7 @@ impl AudioInputStream for FileInputStream {
         // Probe the media source
         let probed = symphonia::default::get_probe()
             .format(&hint, mss, &FormatOptions::default(), &MetadataOptions::default())
-            .map_err(|e| format!("Failed to probe file: {}", e))?;
+            .map_err(|e| Error::Decode(format!("Failed to probe file: {}", e)))?;
         
         let format_reader = probed.format;
         
@@ -961,23 +1574,30 @@ impl AudioInputStream for FileInputStream {
         let track = format_reader.tracks()
             .iter()
             .find(|t| t.codec_params.codec != symphonia::core::codecs::CODEC_TYPE_NULL)
-            .ok_or("No audio tracks found")?;
+            .ok_or_else(|| Error::Decode("No audio tracks found".to_string()))?;
         
         let track_id = track.id;
         
-        // Get the actual sample rate from the file (we'll use our requested rate for output)
-        let _file_rate = track.codec_params.sample_rate
-            .ok_or("Sample rate not specified in file")?;
+        // Files at another rate are resampled to the requested rate
+        let file_rate = track.codec_params.sample_rate
+            .ok_or_else(|| Error::Decode("Sample rate not specified in file".to_string()))?;
+        self.resampler = if file_rate != self.rate {
+            let file_channels = track.codec_params.channels.map(|c| c.count()).unwrap_or(self.channels);
+            Some(Resampler::new(file_rate, self.rate, file_channels.min(self.channels))?)
+        } else {
+            None
+        };
         
         // Create a decoder
         let decoder = symphonia::default::get_codecs()
             .make(&track.codec_params, &DecoderOptions::default())
-            .map_err(|e| format!("Failed to create decoder: {}", e))?;
+            .map_err(|e| Error::Decode(format!("Failed to create decoder: {}", e)))?;
         
         self.format_reader = Some(format_reader);
         self.decoder = Some(decoder);
         self.track_id = Some(track_id);
         self.active = true;
+        self.ended = false;
         self.start_time = Some(Instant::now());
         self.frames_read = 0;
         self.buffer.clear();
@@ -993,11 +1613,16 @@ impl AudioInputStream for FileInputStream {
         self.start_time = None;
         self.frames_read = 0;
         self.buffer.clear();
+        self.resampler = None;
     }
     
     fn is_active(&self) -> bool {
         self.active
     }
+    
+    fn at_end(&self) -> bool {
+        self.ended
+    }
 }
 
 impl Drop for FileInputStream {
@@ -1026,7 +1651,7 @@ impl AudioInputStream for Box<dyn AudioInputStream> {
         (**self).read_chunk(frames)
     }
     
-    fn start(&mut self) -> Result<(), String> {
+    fn start(&mut self) -> Result<()> {
         (**self).start()
     }
     
@@ -1037,6 +1662,78 @@ impl AudioInputStream for Box<dyn AudioInputStream> {
     fn is_active(&self) -> bool {
         (**self).is_active()
     }
+    
+    fn at_end(&self) -> bool {
+        (**self).at_end()
+    }
+}
+
+/// Input stream with selected channels of another stream, for any backend
+pub struct ChannelMapStream {
+    inner: Box<dyn AudioInputStream>,
+    /// Channel of the inner stream for each channel of this one
+    map: Vec<usize>,
+}
+
+impl ChannelMapStream {
+    /// Pass on channels `map` (0-based) of `inner`; a channel may be used twice
+    pub fn new(inner: Box<dyn AudioInputStream>, map: Vec<usize>) -> Result<Self> {
+        if map.is_empty() {
+            return Err(Error::Config("Empty channel map".to_string()));
+        }
+        if let Some(&channel) = map.iter().find(|&&c| c >= inner.channels()) {
+            return Err(Error::Config(format!(
+                "Channel {} is mapped, but the source has {} channels", channel + 1, inner.channels()
+            )));
+        }
+        Ok(ChannelMapStream { inner, map })
+    }
+}
+
+impl AudioStream for ChannelMapStream {
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+    
+    fn channels(&self) -> usize {
+        self.map.len()
+    }
+    
+    fn sample_format(&self) -> SampleFormat {
+        self.inner.sample_format()
+    }
+}
+
+impl AudioInputStream for ChannelMapStream {
+    fn read_chunk(&mut self, frames: usize) -> Option<Vec<Vec<i32>>> {
+        let mut chunk = self.inner.read_chunk(frames)?;
+        let mut mapped = Vec::with_capacity(self.map.len());
+        for (i, &channel) in self.map.iter().enumerate() {
+            // The last use of a channel takes it instead of copying it
+            if self.map[i + 1..].contains(&channel) {
+                mapped.push(chunk.get(channel).cloned().unwrap_or_default());
+            } else {
+                mapped.push(chunk.get_mut(channel).map(std::mem::take).unwrap_or_default());
+            }
+        }
+        Some(mapped)
+    }
+    
+    fn start(&mut self) -> Result<()> {
+        self.inner.start()
+    }
+    
+    fn stop(&mut self) {
+        self.inner.stop()
+    }
+    
+    fn is_active(&self) -> bool {
+        self.inner.is_active()
+    }
+    
+    fn at_end(&self) -> bool {
+        self.inner.at_end()
+    }
 }
 
 #[cfg(test)]
@@ -1093,6 +1790,48 @@ mod tests {
         assert!(matches!(stream_s32.sample_format(), SampleFormat::S32));
     }
 
+    #[test]
+    fn test_pipewire_stream_restart() {
+        // Without a PipeWire daemon the capture thread ends on its own;
+        // with one, stop() has to quit the mainloop. Both must not hang.
+        let mut stream = PipeWireInputStream::new(String::new(), 48000, 2, SampleFormat::S16)
+            .expect("Failed to create PipeWireInputStream");
+        for _ in 0..2 {
+            stream.start().unwrap();
+            assert!(stream.is_active());
+            stream.stop();
+            assert!(!stream.is_active());
+        }
+        
+        stream.reconfigure(96000, 2, SampleFormat::S24).unwrap();
+        assert_eq!(stream.sample_rate(), 96000);
+        assert_eq!(stream.bytes_per_frame(), 6);
+        assert!(!stream.is_active());
+        stream.set_target("alsa_input.usb".to_string()).unwrap();
+        assert_eq!(stream.target(), "alsa_input.usb");
+    }
+
+    #[test]
+    fn test_pipewire_output_stream_queue() {
+        let mut stream = PipeWireOutputStream::new(String::new(), 44100, 2, SampleFormat::S24)
+            .expect("Failed to create PipeWireOutputStream");
+        assert_eq!(stream.bytes_per_frame(), 6);
+        stream.write_chunk(&[vec![1, -1, 8388607], vec![2, -2, -8388608]]).unwrap();
+        assert_eq!(stream.pending_frames(), 3);
+        assert_eq!(
+            stream.queue.lock().unwrap().iter().take(6).copied().collect::<Vec<u8>>(),
+            vec![1, 0, 0, 2, 0, 0]
+        );
+        assert!(stream.write_chunk(&[vec![0]]).is_err());
+        
+        // Not started: nothing is played
+        assert!(!stream.drain(Duration::from_millis(20)));
+        stream.start().unwrap();
+        stream.stop();
+        assert!(!stream.is_active());
+        assert_eq!(stream.pending_frames(), 0);
+    }
+
     #[test]
     fn test_alsa_stream_creation() {
         let stream = AlsaInputStream::new(
@@ -1160,6 +1899,94 @@ mod tests {
         assert_eq!(device, "unknown:device");
     }
     
+    #[test]
+    fn test_parse_audio_address_jack() {
+        let (backend, device) = parse_audio_address("jack:system:capture_1,system:capture_2").unwrap();
+        assert_eq!(backend, "jack");
+        assert_eq!(device, "system:capture_1,system:capture_2");
+        
+        let (backend, device) = parse_audio_address("net:rtp://0.0.0.0:5004").unwrap();
+        assert_eq!(backend, "net");
+        assert_eq!(device, "rtp://0.0.0.0:5004");
+    }
+    
+    #[test]
+    fn test_raw_input_stream() {
+        for address in ["stdin:", "stdin", "-", "raw:", "raw:-"] {
+            assert_eq!(parse_audio_address(address).unwrap(), ("raw".to_string(), "-".to_string()));
+        }
+        
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("capture.raw");
+        // Two stereo S16 frames and half a frame
+        std::fs::write(&path, [1u8, 0, 2, 0, 0xff, 0xff, 4, 0, 9]).unwrap();
+        let address = format!("raw:{}", path.display());
+        let mut stream = create_input_stream(&address, 48000, 2, SampleFormat::S16).unwrap();
+        assert!(!stream.is_active());
+        stream.start().unwrap();
+        assert_eq!(stream.read_chunk(2), Some(vec![vec![1, -1], vec![2, 4]]));
+        assert!(!stream.at_end());
+        // The half frame at the end is dropped and the input has ended
+        assert_eq!(stream.read_chunk(1), None);
+        assert!(stream.at_end());
+        
+        // A short last chunk is returned
+        stream.start().unwrap();
+        assert_eq!(stream.read_chunk(4), Some(vec![vec![1, -1], vec![2, 4]]));
+        assert!(stream.at_end());
+        assert_eq!(stream.read_chunk(4), None);
+        
+        let mut missing = RawInputStream::new("/nonexistent/fifo".to_string(), 48000, 2, SampleFormat::S16);
+        assert!(missing.start().is_err());
+    }
+    
+    #[test]
+    fn test_channel_map_stream() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("capture4.raw");
+        // Two 4-channel S16 frames
+        let samples: [i16; 8] = [1, 2, 3, 4, 5, 6, 7, 8];
+        let bytes: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+        std::fs::write(&path, bytes).unwrap();
+        let address = format!("raw:{}", path.display());
+        
+        // Opened with 4 channels although 2 were asked for
+        let mut stream = create_mapped_input_stream(&address, 48000, 2, SampleFormat::S16, &[3, 2]).unwrap();
+        assert_eq!(stream.channels(), 2);
+        assert_eq!(stream.bytes_per_frame(), 4);
+        stream.start().unwrap();
+        assert_eq!(stream.read_chunk(2), Some(vec![vec![4, 8], vec![3, 7]]));
+        
+        let mut stream = create_mapped_input_stream(&address, 48000, 4, SampleFormat::S16, &[0, 0]).unwrap();
+        stream.start().unwrap();
+        assert_eq!(stream.read_chunk(2), Some(vec![vec![1, 5], vec![1, 5]]));
+        
+        let inner = create_input_stream(&address, 48000, 2, SampleFormat::S16).unwrap();
+        assert!(ChannelMapStream::new(inner, vec![2]).is_err());
+    }
+    
+    #[test]
+    fn test_pulse_input_stream() {
+        let (backend, device) = parse_audio_address("pulse:alsa_input.usb.analog-stereo").unwrap();
+        assert_eq!(backend, "pulse");
+        
+        let stream = PulseInputStream::new(device, 48000, 2, SampleFormat::S16);
+        let args = stream.parec_args();
+        assert!(args.contains(&"--rate=48000".to_string()));
+        assert!(args.contains(&"--format=s16le".to_string()));
+        assert_eq!(args.last().unwrap(), "--device=alsa_input.usb.analog-stereo");
+        
+        let default = PulseInputStream::new("default".to_string(), 96000, 2, SampleFormat::S32);
+        assert!(!default.parec_args().iter().any(|a| a.starts_with("--device")));
+        
+        let sources = discovery::parse_pactl_sources(
+            "1\talsa_input.usb.analog-stereo\tmodule-alsa-card.c\ts16le 2ch 48000Hz\tSUSPENDED\n",
+        );
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].url, "pulse:alsa_input.usb.analog-stereo");
+        assert_eq!(sources[0].description.as_deref(), Some("s16le 2ch 48000Hz (SUSPENDED)"));
+    }
+    
     #[test]
     fn test_parse_audio_address_file() {
         // Test file path detection
@@ -1214,7 +2041,7 @@ mod tests {
     }
     
     // Helper function to create test audio files
-    fn create_test_audio_file(path: &str, format: &str, duration_secs: f64, sample_rate: u32, freq: f64) -> Result<(), String> {
+    fn create_test_audio_file(path: &str, format: &str, duration_secs: f64, sample_rate: u32, freq: f64) -> std::result::Result<(), String> {
         use std::process::Command;
         
         // Generate a sine wave using sox
@@ -1391,6 +2218,36 @@ mod tests {
         fs::remove_file(test_file).ok();
     }
     
+    #[test]
+    fn test_file_input_stream_resampled() {
+        use std::fs;
+        
+        // 44.1 kHz file read into a 96 kHz pipeline
+        let test_file = "/tmp/test_autorec_resample.wav";
+        if let Err(e) = create_test_audio_file(test_file, "wav", 0.5, 44100, 1000.0) {
+            eprintln!("Skipping test_file_input_stream_resampled: {}", e);
+            return;
+        }
+        
+        let mut stream = FileInputStream::new(
+            test_file.to_string(),
+            96000,
+            2,
+            SampleFormat::S32,
+        ).unwrap();
+        stream.start().unwrap();
+        
+        let chunk = stream.read_chunk(9600).unwrap(); // 0.1 seconds
+        assert_eq!(chunk[0].len(), 9600);
+        
+        // 1 kHz at 96 kHz: about 200 zero crossings in 0.1 s
+        let crossings = chunk[0].windows(2).filter(|w| (w[0] < 0) != (w[1] < 0)).count();
+        assert!((190..=210).contains(&crossings), "{} zero crossings", crossings);
+        
+        stream.stop();
+        fs::remove_file(test_file).ok();
+    }
+    
     #[test]
     fn test_file_input_stream_timing() {
         use std::fs;
@@ -1441,7 +2298,8 @@ mod tests {
         
         assert!(result.is_err());
         if let Err(e) = result {
-            assert!(e.contains("File not found"));
+            assert!(matches!(e, Error::Io(ref io) if io.kind() == std::io::ErrorKind::NotFound));
+            assert!(e.to_string().contains("File not found"));
         }
     }
     
@@ -1581,6 +2439,39 @@ pub mod discovery {
         sources
     }
     
+    /// Parse `pactl list short sources` output (id, name, driver, spec, state)
+    pub fn parse_pactl_sources(output: &str) -> Vec<AudioSource> {
+        output
+            .lines()
+            .filter_map(|line| {
+                let fields: Vec<&str> = line.split('\t').collect();
+                let name = fields.get(1)?.trim();
+                if name.is_empty() {
+                    return None;
+                }
+                let description = fields.get(3).map(|spec| match fields.get(4) {
+                    Some(state) => format!("{} ({})", spec.trim(), state.trim()),
+                    None => spec.trim().to_string(),
+                });
+                Some(AudioSource {
+                    backend: "pulse".to_string(),
+                    url: format!("pulse:{}", name),
+                    description,
+                })
+            })
+            .collect()
+    }
+    
+    /// Discover PulseAudio sources
+    pub fn discover_pulse_sources() -> Vec<AudioSource> {
+        match Command::new("pactl").args(["list", "short", "sources"]).output() {
+            Ok(output) if output.status.success() => {
+                parse_pactl_sources(&String::from_utf8_lossy(&output.stdout))
+            }
+            _ => Vec::new(),
+        }
+    }
+    
     /// Discover audio files in the current directory
     pub fn discover_file_sources() -> Vec<AudioSource> {
         use std::fs;
@@ -1620,6 +2511,7 @@ pub mod discovery {
         
         all_sources.extend(discover_pipewire_sources());
         all_sources.extend(discover_alsa_sources());
+        all_sources.extend(discover_pulse_sources());
         all_sources.extend(discover_file_sources());
         
         all_sources
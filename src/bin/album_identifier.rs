@@ -1,4 +1,5 @@
 use autorec::identify_songs;
+use autorec::progress::NoProgress;
 use std::env;
 use std::process;
 
@@ -23,6 +24,7 @@ fn print_usage() {
 }
 
 fn main() {
+    autorec::logging::init_cli();
     let args: Vec<String> = env::args().collect();
 
     if args.len() < 2 {
@@ -109,7 +111,7 @@ fn main() {
     };
 
     // Run song identification
-    let (result, _log) = identify_songs(&wav_file, timestamps);
+    let (result, _log) = identify_songs(&wav_file, timestamps, &mut NoProgress);
     match result {
         Ok(songs) => {
             println!();
@@ -0,0 +1,223 @@
+//! Query the catalog of recordings.
+//!
+//! `autorecord`, `autorecd` and `cue_creator` write every recording, its
+//! levels, the identified release and the track boundaries into the catalog
+//! (see `autorec::catalog`). This tool lists and searches it, and prints the
+//! shell completions and man pages of the programs for installing them (see
+//! `autorec::completions`).
+//!
+//! Usage:
+//!     autorec list [--tracks] [--catalog FILE]
+//!     autorec search [--tracks] [--catalog FILE] TEXT
+//!     autorec completions SHELL
+//!     autorec man [PROGRAM] [--output DIR]
+
+use std::env;
+use std::path::PathBuf;
+use std::process;
+
+use autorec::catalog::{self, Catalog, CatalogEntry};
+use autorec::completions::{self, Shell, SHELL_NAMES};
+use autorec::cuefile::format_cue_time;
+
+fn print_usage() {
+    println!("List and search the catalog of recordings, install completions and man pages");
+    println!();
+    println!("Usage: autorec <COMMAND> [OPTIONS]");
+    println!();
+    println!("Commands:");
+    println!("  list                     All recordings, oldest first");
+    println!("  search <TEXT>            Recordings whose artist, album, release, file name");
+    println!("                             or a track title contains TEXT");
+    println!("  completions <SHELL>      Completions of the AutoRec programs ({})", SHELL_NAMES.join(", "));
+    println!("  man [PROGRAM]            Man page of PROGRAM (default: autorec)");
+    println!();
+    println!("Options:");
+    println!("  --tracks                 Also show the tracks of each recording");
+    println!("  --catalog <FILE>         Catalog to read (default: ~/.state/autorec/catalog.db)");
+    println!("  --output <DIR>           With man: write all man pages to DIR");
+    println!("  --help                   Show this help message");
+    println!();
+    println!("Examples:");
+    println!("  autorec list");
+    println!("  autorec search \"pink floyd\" --tracks");
+    println!("  autorec completions bash > /usr/share/bash-completion/completions/autorec");
+    println!("  autorec man --output /usr/local/share/man/man1");
+}
+
+/// Print the completions for `shell`
+fn print_completions(shell: Option<&str>) -> i32 {
+    match shell.and_then(Shell::from_str) {
+        Some(shell) => {
+            print!("{}", completions::completions(shell));
+            0
+        }
+        None => {
+            eprintln!("Error: Shell must be one of: {}", SHELL_NAMES.join(", "));
+            1
+        }
+    }
+}
+
+/// Print the man page of `program`, or write all of them to `output`
+fn print_man_page(program: Option<&str>, output: Option<&PathBuf>) -> i32 {
+    if let Some(dir) = output {
+        return match completions::write_man_pages(dir) {
+            Ok(written) => {
+                for path in written {
+                    println!("{}", path);
+                }
+                0
+            }
+            Err(e) => {
+                eprintln!("Error: {}: {}", dir.display(), e);
+                1
+            }
+        };
+    }
+    let program = program.unwrap_or("autorec");
+    match completions::man_page(program) {
+        Some(page) => {
+            print!("{}", page);
+            0
+        }
+        None => {
+            let programs: Vec<&str> = completions::MAN_PAGES.iter().map(|(name, _)| *name).collect();
+            eprintln!("Error: No man page for {} (available: {})", program, programs.join(", "));
+            1
+        }
+    }
+}
+
+fn format_minutes(seconds: f64) -> String {
+    format!("{}:{:02}", (seconds / 60.0) as u32, (seconds % 60.0) as u32)
+}
+
+fn print_entry(entry: &CatalogEntry, tracks: bool) {
+    let started = entry.started.as_deref().map_or("", |s| s.get(..16).unwrap_or(s));
+    let duration = entry.duration.map(format_minutes).unwrap_or_default();
+    let album = match (&entry.artist, &entry.album) {
+        (Some(artist), Some(album)) => {
+            let mut album = format!("{} - {}", artist, album);
+            if let Some(year) = entry.year {
+                album.push_str(&format!(" ({})", year));
+            }
+            if let Some(side) = entry.side {
+                album.push_str(&format!(", side {}", side));
+            }
+            album
+        }
+        _ => "(not identified)".to_string(),
+    };
+    println!("{:16}  {:>6}  {}", started, duration, album);
+    println!("    {}", entry.path);
+
+    let mut levels = Vec::new();
+    if let Some(level) = entry.average_level_db {
+        levels.push(format!("level {:.1} dBFS", level));
+    }
+    if let Some(peak) = entry.true_peak_db {
+        levels.push(format!("true peak {:.1} dBTP", peak));
+    }
+    if entry.overs > 0 {
+        levels.push(format!("{} overs", entry.overs));
+    }
+    if entry.dropouts > 0 {
+        levels.push(format!("{} dropouts", entry.dropouts));
+    }
+    if !entry.tracks.is_empty() {
+        levels.push(format!("{} tracks", entry.tracks.len()));
+    }
+    if !levels.is_empty() {
+        println!("    {}", levels.join(", "));
+    }
+    if tracks {
+        for track in &entry.tracks {
+            println!("      {:02}  {}  {}", track.number, format_cue_time(track.start), track.title.as_deref().unwrap_or(""));
+        }
+    }
+}
+
+fn main() {
+    autorec::logging::init_cli();
+    let args: Vec<String> = env::args().collect();
+
+    let mut command: Option<String> = None;
+    let mut text: Option<String> = None;
+    let mut show_tracks = false;
+    let mut catalog_file: Option<PathBuf> = catalog::catalog_path();
+    let mut output: Option<PathBuf> = None;
+
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--tracks" => show_tracks = true,
+            "--catalog" => {
+                if i + 1 < args.len() {
+                    catalog_file = Some(PathBuf::from(&args[i + 1]));
+                    i += 1;
+                }
+            }
+            "--output" => {
+                if i + 1 < args.len() {
+                    output = Some(PathBuf::from(&args[i + 1]));
+                    i += 1;
+                }
+            }
+            "--help" | "-h" => {
+                print_usage();
+                process::exit(0);
+            }
+            arg if !arg.starts_with("--") && command.is_none() => command = Some(arg.to_string()),
+            arg if !arg.starts_with("--") && text.is_none() => text = Some(arg.to_string()),
+            _ => {
+                eprintln!("Unknown option: {}", args[i]);
+                print_usage();
+                process::exit(1);
+            }
+        }
+        i += 1;
+    }
+
+    // Neither needs the catalog
+    match command.as_deref() {
+        Some("completions") => process::exit(print_completions(text.as_deref())),
+        Some("man") => process::exit(print_man_page(text.as_deref(), output.as_ref())),
+        _ => {}
+    }
+
+    let catalog = match catalog_file.map(|path| Catalog::open(&path)) {
+        Some(Ok(catalog)) => catalog,
+        Some(Err(e)) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+        None => {
+            eprintln!("Error: No catalog (HOME is not set, use --catalog)");
+            process::exit(1);
+        }
+    };
+
+    let entries = match (command.as_deref(), text) {
+        (Some("list"), None) => catalog.list(),
+        (Some("search"), Some(text)) => catalog.search(&text),
+        _ => {
+            print_usage();
+            process::exit(1);
+        }
+    };
+    match entries {
+        Ok(entries) if entries.is_empty() => println!("No recordings found"),
+        Ok(entries) => {
+            for entry in &entries {
+                print_entry(entry, show_tracks);
+            }
+            println!();
+            println!("{} recording(s)", entries.len());
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+    }
+}
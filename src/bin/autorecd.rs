@@ -0,0 +1,789 @@
+//! Headless recorder with an HTTP/JSON control API.
+//!
+//! Records like `autorecord` (automatic start/stop on signal) but without a
+//! terminal, so it can run as a service and be controlled from the HiFiBerry
+//! web UI or Home Assistant. See `autorec::control_api` for the endpoints.
+//!
+//! Usage:
+//!     autorecd [FILENAME] [--listen ADDR] [--source SOURCE] ...
+
+use autorec::catalog::{self, Capture};
+use autorec::config::{profile_arg, ResolvedSource};
+use autorec::detection_strategies::{self, StrategyParams, UNGUIDED_STRATEGY_NAMES};
+use autorec::audio_analysis::parse_channel_list;
+use autorec::downmix::{Downmix, DOWNMIX_NAMES};
+use autorec::events::DaemonEvent;
+use autorec::control_api::{self, ApiCommand, CueStatus, DaemonState, IdentifiedAlbum, SharedState, DEFAULT_LISTEN_ADDRESS};
+use autorec::filename_template::FilenameTemplate;
+use autorec::listening_copy::ListeningCopySettings;
+use autorec::locked_groove::LockedGrooveDetector;
+use autorec::logging::{self, LogSettings, Sink};
+use autorec::mqtt::{self, MqttSettings};
+use autorec::preview::{PreviewFormat, PreviewSettings, DEFAULT_PREVIEW_BITRATE};
+use autorec::schedule::Schedule;
+use autorec::session::Session;
+use autorec::status_display::{open_oled, StatusDisplay, StatusEvent, StatusFrame};
+use autorec::{create_mapped_input_stream, parse_audio_address, process_audio_chunk, validate_and_select_target, AudioRecorder, Config, ReconnectPolicy, SampleFormat, VUMeter};
+use chrono::Local;
+use std::env;
+use std::process;
+use std::sync::mpsc::{channel, Sender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
+
+fn print_usage() {
+    println!("Headless audio recorder controlled through an HTTP/JSON API");
+    println!();
+    println!("Usage: autorecd [FILENAME] [OPTIONS]");
+    println!();
+    println!("Arguments:");
+    println!("  FILENAME                 Base filename for recordings (default: recording)");
+    println!();
+    println!("Options:");
+    println!("  --listen <ADDR>          Address of the control API (default: {})", DEFAULT_LISTEN_ADDRESS);
+    println!("                             Use 0.0.0.0:<PORT> to allow remote clients");
+    println!("  --profile <NAME>         Use the settings of [profile.<NAME>] from the config");
+    println!("  --source <SOURCE>        Audio source address or named source");
+    println!("                             (default: saved default or auto-detected PipeWire source)");
+    println!("  --rate <RATE>            Sample rate (default: 96000)");
+    println!("  --channels <CHANNELS>    Number of channels (default: 2)");
+    println!("  --channel-map <LIST>     Record only these input channels, e.g. 3,4");
+    println!("  --format <FORMAT>        Sample format: s16, s24, s24_32, s32 (default: s32)");
+    println!("  --off-threshold <THRESH> Threshold for on/off detection in dB (default: -60)");
+    println!("  --silence-duration <SEC> Duration of silence before recording stops (default: 10)");
+    println!("  --min-length <SEC>       Minimum recording length in seconds (default: 600)");
+    println!("  --stop-on-locked-groove  Stop the recording when the stylus circles in the locked");
+    println!("                           run-out groove");
+    println!("  --preview <FORMAT>       Also write a low-bitrate preview: mp3, opus (needs ffmpeg)");
+    println!("  --disarmed               Start disarmed; recording begins after POST /start");
+    println!("  --start-at <TIME>        Arm at TIME: HH:MM, \"YYYY-MM-DD HH:MM\" or a delay like +30m");
+    println!("  --stop-at <TIME>         Disarm and exit at TIME; HH:MM and +2h count from the start time");
+    println!("  --schedule <RULE>        Arm during a weekly window, e.g. \"mon-fri 07:00-09:00\"");
+    println!("                             (repeatable; replaces the schedule from the config)");
+    println!("  --reconnect-timeout <SEC> Wait this long for a lost audio source (default: 0 = forever)");
+    println!("  --no-reconnect           Exit when the audio source is lost");
+    println!("  --no-generate-cue        Do not create CUE files after each recording");
+    println!("  --pause-strategy <NAME>  Detect song boundaries while recording and report them on");
+    println!("                             /events: {}", UNGUIDED_STRATEGY_NAMES.join(", "));
+    println!("  --downmix <MODE>         Channels of the recordings: {} (default: off)", DOWNMIX_NAMES.join(", "));
+    println!("  --log-level <FILTER>     Log filter, e.g. \"debug\" or \"info,autorec::boundary=debug\"");
+    println!("                             (default: level in [log] or info)");
+    println!("  --log-format <FORMAT>    Log format: text, json (default: text)");
+    println!("  --log-dir <DIR>          Write logs to files in DIR instead of stderr");
+    println!("  --help                   Show this help message");
+    println!();
+    println!("Defaults for all options are read from /etc/autorec/config.toml,");
+    println!("$XDG_CONFIG_HOME/autorec/config.toml, ~/.state/autorec/defaults.toml and");
+    println!("AUTOREC_* environment variables (later ones override earlier ones).");
+    println!();
+    println!("API:");
+    println!("  GET  /status /levels /recordings /metrics (Prometheus)");
+    println!("  POST /start /stop /split /pause /resume /shutdown");
+    println!("  POST /cue  {{\"file\": \"recording.1.wav\"}}");
+    println!("  GET  /events (WebSocket: levels, state, recordings, song boundaries, albums)");
+    println!();
+    println!("GPIO (with the gpio feature and lines set in [gpio]):");
+    println!("  Button: short press starts/stops, holding it splits the recording");
+    println!("  LEDs: armed, recording (blinks while paused), clipping");
+    println!();
+    println!("MQTT (with broker set in [mqtt]):");
+    println!("  Publishes <prefix>/state /status /album /levels /availability");
+    println!("  Subscribes <prefix>/command: start, stop, split, pause, resume");
+    println!();
+    println!("Examples:");
+    println!("  autorecd /data/vinyl/side --source vinyl");
+    println!("  autorecd /data/radio/show --source alsa:hw:1,0 --schedule \"sat 20:00-22:00\"");
+    println!("  curl -X POST http://127.0.0.1:8780/stop");
+}
+
+/// Run cue_creator for `file` in the background and record the result
+fn spawn_cue_creator(file: String, state: SharedState) {
+    state.lock().unwrap().cue_status.insert(file.clone(), CueStatus::Running);
+    thread::spawn(move || {
+        info!("Generating CUE file for {}", file);
+        let status = match process::Command::new("cue_creator").arg(&file).output() {
+            Ok(result) if result.status.success() => CueStatus::Done,
+            Ok(result) => CueStatus::Failed(String::from_utf8_lossy(&result.stderr).trim().to_string()),
+            Err(e) => CueStatus::Failed(format!("Error running cue_creator: {}", e)),
+        };
+        if let CueStatus::Failed(e) = &status {
+            warn!("CUE generation for {} failed: {}", file, e);
+        }
+        // cue_creator may have renamed the recording, the session is still there
+        let album = match (&status, Session::load_for(&file)) {
+            (CueStatus::Done, Ok(Session { artist: Some(artist), album: Some(album), year, release, .. })) => {
+                Some(IdentifiedAlbum { file: file.clone(), artist, album, year, release })
+            }
+            _ => None,
+        };
+        let mut state = state.lock().unwrap();
+        if let Some(album) = album {
+            state.events.publish(&DaemonEvent::Identified(album.clone()));
+            state.album = Some(album);
+        }
+        state.cue_status.insert(file, status);
+    });
+}
+
+/// Serve the button and LEDs of the `[gpio]` section
+#[cfg(feature = "gpio")]
+fn start_gpio(config: &Config, state: &SharedState, commands: &Sender<ApiCommand>) {
+    use autorec::gpio::{self, GpioSettings};
+
+    let result = GpioSettings::from_config(&config.gpio.clone().unwrap_or_default())
+        .and_then(|settings| settings.map_or(Ok(()), |s| gpio::start(s, Arc::clone(state), commands.clone())));
+    if let Err(e) = result {
+        error!("{}", e);
+        process::exit(1);
+    }
+}
+
+#[cfg(not(feature = "gpio"))]
+fn start_gpio(config: &Config, _state: &SharedState, _commands: &Sender<ApiCommand>) {
+    if config.gpio.is_some() {
+        warn!("Ignoring [gpio]: GPIO support is not enabled (build with --features gpio)");
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    let saved_config = Config::load().unwrap_or_else(|e| {
+        eprintln!("Warning: Ignoring config: {}", e);
+        Config::new()
+    });
+    let saved_config = match profile_arg(&args) {
+        Some(name) => saved_config.with_profile(name).unwrap_or_else(|e| {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }),
+        None => saved_config,
+    };
+
+    let mut record_file = "recording".to_string();
+    let mut listen = DEFAULT_LISTEN_ADDRESS.to_string();
+    let mut source: Option<String> = saved_config.source.clone();
+    let mut rate = saved_config.rate.unwrap_or(96000);
+    let mut channels = saved_config.channels.unwrap_or(2);
+    let mut format = SampleFormat::from_str(&saved_config.format.clone().unwrap_or_else(|| "s32".to_string()))
+        .unwrap_or(SampleFormat::S32);
+    let mut off_threshold = saved_config.off_threshold.unwrap_or(-60.0);
+    let mut silence_duration = saved_config.silence_duration.unwrap_or(10.0);
+    let mut min_length = saved_config.min_length.unwrap_or(600.0);
+    let mut stop_on_locked_groove = saved_config.stop_on_locked_groove.unwrap_or(false);
+    let mut preview_format = saved_config.preview.as_deref().and_then(PreviewFormat::from_str);
+    let preview_bitrate = saved_config.preview_bitrate.unwrap_or(DEFAULT_PREVIEW_BITRATE);
+    let mut armed = true;
+    let mut generate_cue = true;
+    let mut reconnect = ReconnectPolicy::default();
+    let mut start_at: Option<String> = None;
+    let mut stop_at: Option<String> = None;
+    let mut schedule_rules: Option<Vec<String>> = None;
+    let mut log_config = saved_config.log.clone().unwrap_or_default();
+    let mut pause_strategy = saved_config.pause_strategy.as_deref().map(str::to_lowercase);
+    let mut downmix_name = saved_config.downmix.clone();
+    let mut channel_map_name = saved_config.channel_map.clone();
+
+    let mut rate_given = false;
+    let mut channels_given = false;
+    let mut channel_map_given = false;
+    let mut format_given = false;
+    let mut off_threshold_given = false;
+
+    let mut i = 1;
+    let mut positional_args = Vec::new();
+    while i < args.len() {
+        match args[i].as_str() {
+            "--listen" => {
+                if i + 1 < args.len() {
+                    listen = args[i + 1].clone();
+                    i += 1;
+                }
+            }
+            "--profile" => {
+                // Already applied to the defaults above
+                i += 1;
+            }
+            "--source" => {
+                if i + 1 < args.len() {
+                    source = Some(args[i + 1].clone());
+                    i += 1;
+                }
+            }
+            "--rate" => {
+                if i + 1 < args.len() {
+                    rate = args[i + 1].parse().unwrap_or(96000);
+                    rate_given = true;
+                    i += 1;
+                }
+            }
+            "--channels" => {
+                if i + 1 < args.len() {
+                    channels = args[i + 1].parse().unwrap_or(2);
+                    channels_given = true;
+                    i += 1;
+                }
+            }
+            "--channel-map" => {
+                if i + 1 < args.len() {
+                    channel_map_name = Some(args[i + 1].clone());
+                    channel_map_given = true;
+                    i += 1;
+                }
+            }
+            "--format" => {
+                if i + 1 < args.len() {
+                    format = match SampleFormat::from_str(&args[i + 1]) {
+                        Ok(f) => f,
+                        Err(e) => {
+                            eprintln!("Error: {}", e);
+                            process::exit(1);
+                        }
+                    };
+                    format_given = true;
+                    i += 1;
+                }
+            }
+            "--off-threshold" => {
+                if i + 1 < args.len() {
+                    off_threshold = args[i + 1].parse().unwrap_or(-60.0);
+                    off_threshold_given = true;
+                    i += 1;
+                }
+            }
+            "--silence-duration" => {
+                if i + 1 < args.len() {
+                    silence_duration = args[i + 1].parse().unwrap_or(10.0);
+                    i += 1;
+                }
+            }
+            "--min-length" => {
+                if i + 1 < args.len() {
+                    min_length = args[i + 1].parse().unwrap_or(600.0);
+                    i += 1;
+                }
+            }
+            "--stop-on-locked-groove" => {
+                stop_on_locked_groove = true;
+            }
+            "--preview" => {
+                if i + 1 < args.len() {
+                    preview_format = match PreviewFormat::from_str(&args[i + 1]) {
+                        Some(f) => Some(f),
+                        None => {
+                            eprintln!("Error: Unknown preview format '{}' (use mp3 or opus)", args[i + 1]);
+                            process::exit(1);
+                        }
+                    };
+                    i += 1;
+                }
+            }
+            "--disarmed" => {
+                armed = false;
+            }
+            "--start-at" => {
+                if i + 1 < args.len() {
+                    start_at = Some(args[i + 1].clone());
+                    i += 1;
+                }
+            }
+            "--stop-at" => {
+                if i + 1 < args.len() {
+                    stop_at = Some(args[i + 1].clone());
+                    i += 1;
+                }
+            }
+            "--schedule" => {
+                if i + 1 < args.len() {
+                    schedule_rules.get_or_insert_with(Vec::new).push(args[i + 1].clone());
+                    i += 1;
+                }
+            }
+            "--no-reconnect" => {
+                reconnect.enabled = false;
+            }
+            "--reconnect-timeout" => {
+                if i + 1 < args.len() {
+                    let seconds: f64 = args[i + 1].parse().unwrap_or(0.0);
+                    reconnect.timeout = (seconds > 0.0).then(|| Duration::from_secs_f64(seconds));
+                    i += 1;
+                }
+            }
+            "--no-generate-cue" => {
+                generate_cue = false;
+            }
+            "--pause-strategy" => {
+                if i + 1 < args.len() {
+                    pause_strategy = Some(args[i + 1].to_lowercase());
+                    i += 1;
+                }
+            }
+            "--downmix" => {
+                if i + 1 < args.len() {
+                    downmix_name = Some(args[i + 1].clone());
+                    i += 1;
+                }
+            }
+            "--log-level" => {
+                if i + 1 < args.len() {
+                    log_config.level = Some(args[i + 1].clone());
+                    i += 1;
+                }
+            }
+            "--log-format" => {
+                if i + 1 < args.len() {
+                    log_config.format = Some(args[i + 1].clone());
+                    i += 1;
+                }
+            }
+            "--log-dir" => {
+                if i + 1 < args.len() {
+                    log_config.dir = Some(args[i + 1].clone());
+                    i += 1;
+                }
+            }
+            "--help" | "-h" => {
+                print_usage();
+                process::exit(0);
+            }
+            arg if !arg.starts_with("--") => {
+                positional_args.push(arg.to_string());
+            }
+            _ => {
+                eprintln!("Unknown option: {}", args[i]);
+                print_usage();
+                process::exit(1);
+            }
+        }
+        i += 1;
+    }
+
+    let log_settings = LogSettings::from_config(&log_config).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    });
+    if let Err(e) = logging::init(&log_settings, Sink::Service { name: "autorecd".to_string() }) {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    }
+
+    if !positional_args.is_empty() {
+        record_file = positional_args[0].clone();
+    }
+
+    let schedule_rules = schedule_rules.or_else(|| saved_config.schedule.clone()).unwrap_or_default();
+    let schedule = match Schedule::parse(start_at.as_deref(), stop_at.as_deref(), &schedule_rules, Local::now().naive_local()) {
+        Ok(schedule) => schedule,
+        Err(e) => {
+            error!("{}", e);
+            process::exit(1);
+        }
+    };
+    // The schedule decides the initial state; the API can still override it
+    // until the next scheduled change
+    let mut scheduled_armed = (!schedule.is_empty()).then(|| schedule.is_active(Local::now().naive_local()));
+    if let Some(active) = scheduled_armed {
+        armed = active;
+    }
+
+    // Resolve a named source alias; explicit command-line options take precedence
+    let resolved = source.as_deref().map(|s| saved_config.resolve_source(s)).transpose().unwrap_or_else(|e| {
+        error!("{}", e);
+        process::exit(1);
+    });
+    if let Some(ResolvedSource { alias, format: alias_format, .. }) = resolved.flatten() {
+        info!("Using source alias '{}' -> {}", source.as_deref().unwrap_or(""), alias.address);
+        if !rate_given {
+            rate = alias.rate.unwrap_or(rate);
+        }
+        if !channels_given {
+            channels = alias.channels.unwrap_or(channels);
+        }
+        if !channel_map_given && alias.channel_map.is_some() {
+            channel_map_name = alias.channel_map.clone();
+        }
+        if !format_given {
+            format = alias_format.unwrap_or(format);
+        }
+        if !off_threshold_given {
+            off_threshold = alias.off_threshold.unwrap_or(off_threshold);
+        }
+        source = Some(alias.address);
+    }
+
+    // Determine the audio source address
+    let source_address = match source {
+        Some(src) => {
+            let (backend, device) = match parse_audio_address(&src) {
+                Ok(result) => result,
+                Err(e) => {
+                    error!("Invalid audio source '{}': {}", src, e);
+                    process::exit(1);
+                }
+            };
+            if backend == "pipewire" {
+                let (validated_target, error_code) = validate_and_select_target(Some(&device), true);
+                if error_code != 0 {
+                    process::exit(error_code);
+                }
+                format!("pipewire:{}", validated_target.unwrap())
+            } else {
+                src
+            }
+        }
+        None => {
+            let (selected_target, error_code) = validate_and_select_target(None, true);
+            if error_code != 0 {
+                process::exit(error_code);
+            }
+            format!("pipewire:{}", selected_target.unwrap())
+        }
+    };
+
+    let output_config = saved_config.output.clone().unwrap_or_default();
+    let filename_template = match FilenameTemplate::for_recordings(&output_config) {
+        Ok(template) => template,
+        Err(e) => {
+            error!("{}", e);
+            process::exit(1);
+        }
+    };
+    // With a channel map, --channels is the number of channels of the device
+    let channel_map = match channel_map_name.as_deref().map(|list| (list, parse_channel_list(list))) {
+        None => Vec::new(),
+        Some((_, Some(map))) => map,
+        Some((list, None)) => {
+            error!("Invalid channel map '{}' (use e.g. 3,4)", list);
+            process::exit(1);
+        }
+    };
+    let device_channels = channels;
+    if !channel_map.is_empty() {
+        channels = channel_map.len();
+    }
+    let downmix = match downmix_name.as_deref().map(|name| (name, Downmix::from_str(name))) {
+        None => Downmix::Off,
+        Some((_, Some(downmix))) => downmix,
+        Some((name, None)) => {
+            error!("Unknown downmix '{}' (use {})", name, DOWNMIX_NAMES.join(", "));
+            process::exit(1);
+        }
+    };
+    if let Err(e) = downmix.validate(channels) {
+        error!("{}", e);
+        process::exit(1);
+    }
+    // The daemon knows no track lengths for the guided strategy
+    if let Some(name) = pause_strategy.as_deref().filter(|name| !UNGUIDED_STRATEGY_NAMES.contains(name)) {
+        error!("Unknown pause strategy '{}' (use {})", name, UNGUIDED_STRATEGY_NAMES.join(", "));
+        process::exit(1);
+    }
+    let mut recorder = AudioRecorder::with_filename_template(record_file.clone(), filename_template.clone(), rate, channels, format, min_length);
+    recorder.set_downmix(downmix);
+    if let Some(format) = preview_format {
+        recorder.set_preview(Some(PreviewSettings { format, bitrate_kbps: preview_bitrate }));
+    }
+    match ListeningCopySettings::from_config(&output_config) {
+        Ok(settings) => recorder.set_listening_copy(settings),
+        Err(e) => {
+            error!("{}", e);
+            process::exit(1);
+        }
+    }
+
+    let stream = match create_mapped_input_stream(&source_address, rate, device_channels, format, &channel_map) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Failed to create audio stream: {}", e);
+            process::exit(1);
+        }
+    };
+    let mut meter = VUMeter::new(stream, 0.2, 90.0, 0.0, off_threshold, silence_duration);
+    if let Err(e) = meter.start() {
+        error!("Failed to start recording: {}", e);
+        process::exit(1);
+    }
+
+    // Optional live song boundary detection
+    let mut pause_detector = match pause_strategy {
+        Some(name) => match detection_strategies::create(&name, &StrategyParams::new(rate)) {
+            Ok(detector) => {
+                info!("Song boundary detection: {}", detector.name());
+                Some(detector)
+            }
+            Err(e) => {
+                error!("{}", e);
+                process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    let state: SharedState = Arc::new(Mutex::new(DaemonState::new(&source_address, armed)));
+    let (command_sender, commands) = channel();
+    match MqttSettings::from_config(&saved_config.mqtt.clone().unwrap_or_default()) {
+        Ok(Some(settings)) => {
+            info!("Publishing to MQTT broker {}:{} ({}/...)", settings.host, settings.port, settings.topic_prefix);
+            mqtt::start(settings, Arc::clone(&state), command_sender.clone());
+        }
+        Ok(None) => {}
+        Err(e) => {
+            error!("{}", e);
+            process::exit(1);
+        }
+    }
+    start_gpio(&saved_config, &state, &command_sender);
+    if let Err(e) = control_api::serve(&listen, Arc::clone(&state), command_sender) {
+        error!("{}", e);
+        process::exit(1);
+    }
+
+    info!("Recording from {} to {} ({})", source_address, record_file, filename_template.as_str());
+    info!("Control API listening on http://{}/ ({})", listen, if armed { "armed" } else { "disarmed" });
+    info!("Web UI on http://{}/ui/", listen);
+
+    // Front panel display of a recording appliance
+    let mut panel: Option<Box<dyn StatusDisplay>> = match saved_config.oled.clone().filter(|c| c.enabled == Some(true)) {
+        Some(config) => match open_oled(&config) {
+            Ok(display) => Some(display),
+            Err(e) => {
+                error!("{}", e);
+                process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    let mut known_recordings = 0;
+    let mut source_lost: Option<Instant> = None;
+    let mut pause_file: Option<String> = None;
+    let mut locked_groove = stop_on_locked_groove.then(|| LockedGrooveDetector::new(rate, off_threshold as f32));
+    let mut locked_groove_file: Option<String> = None;
+    // After a locked groove nothing is recorded until the signal goes off
+    let mut locked_groove_stopped = false;
+
+    loop {
+        // Apply API commands
+        let mut shutdown = false;
+        loop {
+            match commands.try_recv() {
+                Ok(ApiCommand::Arm) => {
+                    info!("Armed");
+                    armed = true;
+                }
+                Ok(ApiCommand::Disarm) => {
+                    info!("Disarmed");
+                    armed = false;
+                }
+                Ok(ApiCommand::Split) => {
+                    if recorder.split_recording() {
+                        info!("Splitting recording");
+                    }
+                }
+                Ok(ApiCommand::Pause) => {
+                    info!("Paused");
+                    recorder.pause();
+                }
+                Ok(ApiCommand::Resume) => {
+                    info!("Resumed");
+                    recorder.resume();
+                }
+                Ok(ApiCommand::GenerateCue(file)) => spawn_cue_creator(file, Arc::clone(&state)),
+                Ok(ApiCommand::Shutdown) => shutdown = true,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    shutdown = true;
+                    break;
+                }
+            }
+        }
+        if shutdown {
+            info!("Shutting down...");
+            break;
+        }
+
+        if !schedule.is_empty() {
+            let now = Local::now().naive_local();
+            if schedule.is_finished(now) {
+                info!("Scheduled stop time reached");
+                break;
+            }
+            let active = schedule.is_active(now);
+            if scheduled_armed != Some(active) {
+                info!("{}", if active { "Armed by schedule" } else { "Disarmed by schedule" });
+                armed = active;
+                scheduled_armed = Some(active);
+            }
+        }
+
+        // Wait for a lost source to come back, still answering API commands
+        if let Some(lost_since) = source_lost {
+            if reconnect.expired(lost_since) {
+                warn!("Audio source did not come back");
+                break;
+            }
+            thread::sleep(reconnect.retry_interval);
+            if meter.try_reconnect() {
+                info!("Audio source is back");
+                if let Some(panel) = &mut panel {
+                    panel.event(&StatusEvent::SourceBack { seconds: lost_since.elapsed().as_secs_f64() });
+                }
+                let mut state = state.lock().unwrap();
+                state.source_connected = true;
+                state.events.publish(&DaemonEvent::SourceBack { seconds: lost_since.elapsed().as_secs_f64() });
+                state.publish_state();
+                source_lost = None;
+            }
+            continue;
+        }
+
+        let (metrics, audio_data) = match process_audio_chunk(&mut meter) {
+            Some(chunk) => chunk,
+            None if reconnect.enabled && !meter.source_ended() => {
+                recorder.stop_recording();
+                warn!("Audio source lost, waiting for it to come back...");
+                if let Some(panel) = &mut panel {
+                    panel.event(&StatusEvent::SourceLost);
+                }
+                let mut state = state.lock().unwrap();
+                state.source_connected = false;
+                state.recording = false;
+                if let Some(file) = state.current_file.take() {
+                    state.events.publish(&DaemonEvent::RecordingStopped { file });
+                }
+                state.events.publish(&DaemonEvent::SourceLost);
+                state.publish_state();
+                source_lost = Some(Instant::now());
+                continue;
+            }
+            None => {
+                info!("Audio stream ended");
+                break;
+            }
+        };
+
+        // While disarmed, audio is discarded and a running recording is closed
+        let any_channel_on = metrics.iter().any(|m| m.is_on);
+        if !any_channel_on {
+            locked_groove_stopped = false;
+        }
+        recorder.write_audio(&audio_data, armed && any_channel_on && !locked_groove_stopped);
+        let true_peak = metrics.iter().map(|m| m.true_peak_db).fold(f64::MIN, f64::max);
+        recorder.note_true_peak(true_peak);
+        recorder.note_overs(&metrics.iter().map(|m| m.overs).collect::<Vec<_>>());
+
+        let stats = recorder.get_recording_stats();
+        let new_recordings: Vec<String> = stats.iter().skip(known_recordings).map(|s| s.filename.clone()).collect();
+        if !new_recordings.is_empty() {
+            let captures: Vec<Capture> = stats.iter().skip(known_recordings).map(Capture::from_stats).collect();
+            catalog::update(|catalog| captures.iter().try_for_each(|c| catalog.add_capture(c)));
+        }
+        known_recordings = stats.len();
+
+        if let Some(detector) = &mut locked_groove {
+            let file = recorder.current_filename();
+            if file != locked_groove_file {
+                locked_groove_file = file;
+                detector.reset();
+            }
+            if recorder.is_recording() && !recorder.is_paused() {
+                if let Some(groove) = detector.feed_audio(&audio_data, format) {
+                    info!("Locked groove since {:.0}s ({:.2}s per revolution), stopping the recording",
+                          groove.start, groove.period);
+                    recorder.stop_recording();
+                    locked_groove_stopped = true;
+                }
+            }
+        }
+
+        let current_file = recorder.current_filename();
+        let mut song_boundary = None;
+        if let Some(detector) = &mut pause_detector {
+            // Count songs per recording file
+            if current_file != pause_file {
+                pause_file = current_file.clone();
+                detector.reset();
+            }
+            if recorder.is_recording() && !recorder.is_paused() && detector.feed_audio(&audio_data, format).is_some() {
+                song_boundary = Some(detector.song_number());
+            }
+        }
+
+        {
+            let mut state = state.lock().unwrap();
+            let previous_state = state.state_name();
+            state.armed = armed;
+            state.recording = recorder.is_recording();
+            state.paused = recorder.is_paused();
+            if state.current_file != current_file {
+                if let Some(file) = state.current_file.take() {
+                    state.events.publish(&DaemonEvent::RecordingStopped { file });
+                }
+                if let Some(file) = current_file.clone() {
+                    state.events.publish(&DaemonEvent::RecordingStarted { file });
+                }
+                state.current_file = current_file;
+            }
+            if state.state_name() != previous_state {
+                state.publish_state();
+            }
+            if let (Some(song), Some(file)) = (song_boundary, state.current_file.clone()) {
+                info!("Song boundary in {}: song {}", file, song);
+                state.events.publish(&DaemonEvent::SongBoundary { file, song });
+                if let Some(panel) = &mut panel {
+                    panel.event(&StatusEvent::SongBoundary { song });
+                }
+            }
+            state.events.publish(&DaemonEvent::Levels { channels: metrics.clone() });
+            state.levels = metrics;
+            state.recordings = stats;
+            state.writer = recorder.writer_stats();
+            if let Some(e) = recorder.take_error() {
+                state.last_error = Some(e.to_string());
+            }
+            if let Some(panel) = &mut panel {
+                let album = state.album.as_ref().map(|a| format!("{} - {}", a.artist, a.album));
+                let frame = StatusFrame {
+                    recording: state.recording,
+                    paused: state.paused,
+                    file: state.current_file.as_deref(),
+                    waiting_for_schedule: !armed && !schedule.is_empty(),
+                    song: album.as_deref(),
+                    levels: &state.levels,
+                    ..Default::default()
+                };
+                panel.update(&frame).ok();
+            }
+        }
+
+        for file in new_recordings {
+            info!("Finished recording {}", file);
+            if generate_cue {
+                spawn_cue_creator(file, Arc::clone(&state));
+            }
+        }
+    }
+
+    meter.stop();
+    recorder.close();
+
+    let stats = recorder.get_recording_stats();
+    if stats.len() > known_recordings {
+        catalog::update(|catalog| {
+            stats.iter().skip(known_recordings).try_for_each(|s| catalog.add_capture(&Capture::from_stats(s)))
+        });
+    }
+    if generate_cue {
+        for stat in stats.iter().skip(known_recordings) {
+            let output = process::Command::new("cue_creator").arg(&stat.filename).output();
+            if !matches!(output, Ok(ref result) if result.status.success()) {
+                error!("Failed to generate CUE file for {}", stat.filename);
+            }
+        }
+    }
+    for stat in &stats {
+        info!("Recorded {} ({:.1}s)", stat.filename, stat.duration);
+    }
+}
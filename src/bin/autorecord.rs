@@ -1,8 +1,38 @@
-use autorec::{create_input_stream, display_vu_meter, list_targets, parse_audio_address, process_audio_chunk, validate_and_select_target, AudioRecorder, Config, SampleFormat, VUMeter};
+use autorec::audio_analysis::{format_dc_offset, parse_channel_list};
+use autorec::catalog::{self, Capture};
+use autorec::config::{profile_arg, ResolvedSource, DEFAULT_CALIBRATION_KEY};
+use autorec::decibel::{Calibration, LevelScale, LevelUnit};
+use autorec::detection_strategies::{self, StrategyParams, STRATEGY_NAMES};
+use autorec::display::LevelHistory;
+use autorec::downmix::{Downmix, DOWNMIX_NAMES};
+use autorec::filters::dc::{DcBlocker, DcOffsetMeter};
+use autorec::filters::gain::{self, Gain, GainClipMeter};
+use autorec::filters::limiter::{self, Limiter};
+use autorec::filters::riaa::RiaaFilter;
+use autorec::filters::subsonic::{self, SubsonicFilter};
+use autorec::filters::FilterChain;
+use autorec::filename_template::{FilenameTemplate, DEFAULT_RECORDING_TEMPLATE};
+use autorec::listening_copy::ListeningCopySettings;
+use autorec::locked_groove::LockedGrooveDetector;
+use autorec::logging::{self, LogBuffer, LogSettings, Sink};
+use autorec::noise_floor::{NoiseFloorCalibration, CALIBRATION_SECONDS};
+use autorec::preview::{PreviewFormat, PreviewSettings, DEFAULT_PREVIEW_BITRATE};
+use autorec::recorder::RecordingStats;
+use autorec::schedule::Schedule;
+use autorec::side_groups::{self, SideGrouping};
+use autorec::status_display::{open_oled, DisplayKind, JsonDisplay, NullDisplay, StatusDisplay, StatusEvent, StatusFrame, TerminalDisplay, TextDisplay, DISPLAY_NAMES};
+use autorec::{create_mapped_input_stream, list_targets, list_targets_filtered, parse_audio_address, process_audio_chunk, validate_and_select_target, AudioRecorder, Config, ReconnectPolicy, SampleFormat, TargetFilter, VUMeter};
+use autorec::song_detect::{SnapshotBuffer, SongDetectEvent, SongDetector, MIN_SNAPSHOT_SECONDS, SNAPSHOT_SECONDS};
+use chrono::Local;
+use regex::Regex;
+use std::collections::BTreeMap;
 use std::env;
+use std::path::Path;
 use std::process;
+use std::str::FromStr;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
 use crossterm::{
     event::{poll, read, Event, KeyCode, KeyEvent},
     terminal::{disable_raw_mode, enable_raw_mode},
@@ -18,55 +48,232 @@ fn print_usage() {
     println!();
     println!("Options:");
     println!("  --list-targets           List available PipeWire recording targets and exit");
+    println!("    --json                 Print the target list as JSON (IDs, rates, channels)");
+    println!("    --media-class <CLASS>  Only list targets whose media class contains CLASS");
+    println!("    --name-regex <REGEX>   Only list targets whose name/description matches REGEX");
+    println!("    --active-only          Only list targets that are currently running");
     println!("  --show-defaults          Show default configuration values and exit");
     println!("  --show-saved-defaults    Show saved default configuration from file and exit");
     println!("  --save-defaults          Save current command-line options as defaults");
+    println!("                             (with --profile in that profile)");
+    println!("  --profile <NAME>         Use the settings of [profile.<NAME>] from the config");
+    println!("  --calibrate              Listen to {:.0} s of silence (or a silent groove), measure the", CALIBRATION_SECONDS);
+    println!("                             noise floor and suggest --off-threshold and --silence-duration;");
+    println!("                             with --save-defaults they are saved");
     println!("  --source <SOURCE>        Audio source address:");
     println!("                             pipewire:device or pw:device");
+    println!("                             pipewire:re:<regex> (first matching device)");
     println!("                             alsa:hw:0,0 or alsa:default");
+    println!("                             pulse:<source> or pulse:default (PulseAudio, uses parec)");
+    println!("                             jack:system:capture_1,system:capture_2 (needs --features jack)");
+    println!("                             net:udp://:5004, net:rtp://:5004 or net:tcp://:5004");
+    println!("                               (PCM sent over the network, see net_stream)");
+    println!("                             stdin: or raw:/path/to/fifo (raw PCM in --rate,");
+    println!("                               --channels and --format, e.g. from pw-cat or sox)");
     println!("                             file:path/to/audio.wav");
     println!("                             /path/to/audio.mp3 (auto-detects as file)");
     println!("                             Auto-detects backend if not specified");
     println!("                             (default: auto-detect PipeWire source)");
     println!("  --rate <RATE>            Sample rate (default: 96000)");
     println!("  --channels <CHANNELS>    Number of channels (default: 2)");
-    println!("  --format <FORMAT>        Sample format: s16, s32 (default: s32)");
+    println!("  --channel-map <LIST>     Record only these input channels, e.g. 3,4 on a");
+    println!("                           multi-input interface (the source is opened with");
+    println!("                           --channels or as many as the map needs)");
+    println!("  --format <FORMAT>        Sample format: s16, s24, s24_32, s32 (default: s32)");
     println!("  --interval <INTERVAL>    Update interval in seconds (default: 0.2)");
     println!("  --db-range <RANGE>       dB range to display (default: 90)");
     println!("  --max-db <MAX>           Maximum dB (default: 0)");
     println!("  --off-threshold <THRESH> Threshold for on/off detection in dB (default: -60)");
     println!("  --silence-duration <SEC> Duration of silence before recording stops (default: 10)");
     println!("  --min-length <SEC>       Minimum recording length in seconds (default: 600)");
+    println!("  --stop-on-locked-groove  Stop the recording when the stylus circles in the locked");
+    println!("                           run-out groove, whose ticks never fall below the");
+    println!("                           off-threshold; it starts again after silence");
+    println!("  --no-stop-on-locked-groove");
+    println!("                           Wait for silence at the end of a side (default)");
     println!("  --duration <SEC>         Maximum recording duration in seconds (0=unlimited)");
+    println!("  --start-at <TIME>        Only record from TIME: HH:MM, \"YYYY-MM-DD HH:MM\" or a delay like +30m");
+    println!("  --stop-at <TIME>         Stop and exit at TIME; HH:MM and +2h count from the start time");
+    println!("  --schedule <RULE>        Only record during a weekly window, e.g. \"mon-fri 07:00-09:00\"");
+    println!("                             (repeatable; windows may cross midnight)");
     println!("  --detect-interval <SEC>  Song detection interval in seconds (default: 180, 0=off)");
     println!("  --no-shazam              Disable song detection");
-    println!("  --no-vumeter             Disable VU meter display (simple text output)");
+    println!("  --pause-strategy <NAME>  Detect song boundaries while recording:");
+    println!("                             absolute   - level below a fixed threshold");
+    println!("                             relative   - level drop below the recent average");
+    println!("                             energy     - energy below a fraction of the recent maximum");
+    println!("                             transition - quiet period followed by a rise");
+    println!("                             guided     - quietest point near expected boundaries");
+    println!("                           Song identification restarts at every boundary");
+    println!("    --pause-threshold <DB> absolute: threshold in dBFS (default: -50)");
+    println!("    --pause-drop <DB>      relative: drop below the average (default: 15)");
+    println!("    --pause-ratio <R>      energy: fraction of the maximum (default: 0.01)");
+    println!("    --pause-percentile <P> transition: quiet percentile 0-1 (default: 0.2)");
+    println!("    --pause-rise <DB>      transition: rise after the pause (default: 10)");
+    println!("    --pause-window <SEC>   History window, or search window for guided");
+    println!("    --pause-min <MS>       Minimum pause duration in ms");
+    println!("    --pause-sensitivity <DB> Shift the threshold; positive finds more pauses");
+    println!("    --pause-tracks <LIST>  guided: expected track lengths, e.g. 3:45,4:10,5:02");
+    println!("  --reconnect-timeout <SEC> Wait this long for a lost audio source to come back");
+    println!("                             (default: 0 = forever); the current file is closed");
+    println!("                             and recording resumes into a new one");
+    println!("  --no-reconnect           Exit when the audio source is lost");
+    println!("  --preview <FORMAT>       Also write a low-bitrate preview: mp3, opus (needs ffmpeg)");
+    println!("  --preview-bitrate <KBPS> Preview bitrate in kbit/s (default: 128)");
+    println!("  --flac                   Also write a FLAC listening copy, 44.1 kHz/16 bit unless");
+    println!("                             set in the [output] section (needs ffmpeg)");
+    println!("  --no-flac                Write the WAV archive only (default)");
+    println!("  --name <TEMPLATE>        Names of the recordings, relative to the directory of");
+    println!("                             the output file (default: \"{}\")", DEFAULT_RECORDING_TEMPLATE);
+    println!("                             e.g. \"{{date}}/{{base}} {{time}}\"");
+    println!("  --sparkline              Show the last 60s of levels under each bar");
+    println!("  --remove-dc              Remove a DC offset of the ADC (one-pole DC blocker)");
+    println!("  --no-remove-dc           Record the DC offset as is (default)");
+    println!("  --riaa                   Apply RIAA correction (source is a flat, non-phono preamp)");
+    println!("  --no-riaa                Record the source as is (default)");
+    println!("  --subsonic-filter        High-pass filter against rumble and warp subsonics");
+    println!("  --no-subsonic-filter     Record without the subsonic filter (default)");
+    println!("  --subsonic-corner <HZ>   Corner frequency of the subsonic filter (default: 20)");
+    println!("  --gain <DB>              Software trim before metering and recording, e.g. 6 for a");
+    println!("                           quiet source (default: 0; warns when it clips)");
+    println!("  --limiter                Soft-knee peak limiter instead of hard clipping (radio, tapes)");
+    println!("  --no-limiter             Record without the limiter (default)");
+    println!("  --limiter-ceiling <DB>   Highest peak the limiter lets through in dBFS (default: -1)");
+    println!("  --downmix <MODE>         Channels of the recordings:");
+    println!("                             off  - as captured (default)");
+    println!("                             mono - average of the channels, for mono records");
+    println!("                             ms   - mid (L+R)/2 and side (L-R)/2");
+    println!("  --units <UNIT>           Level display unit: dbfs, dbu, dbv (default: dbfs)");
+    println!("  --calibration <DBU>      Analog level in dBu that reads 0 dBFS on this source");
+    println!("                             (stored per source with --save-defaults)");
+    println!("  --display <MODE>         What to show while recording:");
+    println!("                             vu   - VU meters (default)");
+    println!("                             text - a line when the recording state changes");
+    println!("                             json - levels and events as JSON lines on stdout");
+    println!("                             oled - the front panel display set in [oled]");
+    println!("                             none - nothing");
+    println!("  --no-vumeter             Same as --display text");
     println!("  --no-keyboard            Disable keyboard shortcuts (no raw mode)");
+    println!("                             Keys: m = mark a new track (saved to FILE.marks),");
+    println!("                             n = close the file and continue in the next one,");
+    println!("                             p = pause/resume without closing the file,");
+    println!("                             q/ESC = quit");
     println!("  --no-generate-cue        Disable automatic CUE file generation after recording");
+    println!("  --group-sides            Treat recordings started soon after the previous one at a");
+    println!("                             similar level as sides of one album: move them to");
+    println!("                             FILENAME-N/FILENAME.A.wav, .B.wav, ... with a session.toml");
+    println!("  --side-gap <MIN>         Longest break between two sides (default: {})",
+             side_groups::DEFAULT_MAX_GAP_SECONDS / 60.0);
     println!("  --help                   Show this help message");
     println!();
     println!("Configuration:");
     println!("  Defaults can be saved to ~/.state/autorec/defaults.toml using --save-defaults.");
-    println!("  Saved defaults override built-in defaults, and command-line options override both.");
+    println!("  Settings are read from /etc/autorec/config.toml, $XDG_CONFIG_HOME/autorec/config.toml,");
+    println!("  the saved defaults and AUTOREC_* environment variables, each overriding the one before.");
+    println!("  Command-line options override all of them.");
     println!();
     println!("Examples:");
     println!("  record vinyl --source pipewire:riaa.monitor");
     println!("  record tape --source alsa:hw:1,0 --rate 48000");
     println!("  record test --source /path/to/source.flac");
     println!("  record --source alsa:hw:1,0 --rate 48000 --save-defaults  # Save as defaults");
+    println!("  record --source vinyl --calibrate --save-defaults  # Measure and save the off threshold");
+    println!("  record side_a --profile vinyl            # Settings from [profile.vinyl]");
+    println!("  record side_a --source vinyl             # Named source from [sources.vinyl]");
+    println!("  record radio --source alsa:hw:1,0 --start-at 20:00 --stop-at +2h");
+}
+
+/// Parse the value of a numeric option, exiting on invalid input
+fn parse_option_value<T: FromStr>(option: &str, value: &str) -> T {
+    match value.parse() {
+        Ok(v) => v,
+        Err(_) => {
+            eprintln!("Error: Invalid {} value '{}'", option, value);
+            process::exit(1);
+        }
+    }
+}
+
+/// Save the command-line options as defaults, or into a profile, and exit
+fn save_defaults_and_exit(cmdline_config: &Config, profile: Option<&str>) -> ! {
+    // Merge command-line config with the saved defaults only, so that
+    // system, user and environment settings are not copied into the file
+    let mut config_to_save = Config::load_saved().unwrap_or_else(|_| Config::new());
+    match profile {
+        Some(name) => {
+            let profile_config = Config { profiles: Some(BTreeMap::from([(name.to_string(), cmdline_config.clone())])), ..Config::new() };
+            config_to_save.merge(&profile_config);
+        }
+        None => config_to_save.merge(cmdline_config),
+    }
+
+    match config_to_save.save() {
+        Ok(_) => {
+            if let Ok(config_path) = Config::get_config_path() {
+                println!("Defaults saved to {:?}", config_path);
+                println!();
+                config_to_save.print("Saved configuration");
+            }
+            process::exit(0);
+        }
+        Err(e) => {
+            eprintln!("Error saving defaults: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+fn parse_downmix(name: &str) -> Downmix {
+    Downmix::from_str(name).unwrap_or_else(|| {
+        eprintln!("Error: Unknown downmix '{}' (use {})", name, DOWNMIX_NAMES.join(", "));
+        process::exit(1);
+    })
+}
+
+fn parse_channel_map(list: &str) -> Vec<usize> {
+    parse_channel_list(list).unwrap_or_else(|| {
+        eprintln!("Error: Invalid channel map '{}' (use e.g. 3,4)", list);
+        process::exit(1);
+    })
+}
+
+fn parse_display(name: &str) -> DisplayKind {
+    DisplayKind::from_str(name).unwrap_or_else(|| {
+        eprintln!("Error: Unknown display '{}' (use {})", name, DISPLAY_NAMES.join(", "));
+        process::exit(1);
+    })
 }
 
 fn main() {
     let args: Vec<String> = env::args().collect();
 
     // Load saved defaults from config file if available
-    let saved_config = Config::load().unwrap_or_else(|_| Config::new());
+    let saved_config = Config::load().unwrap_or_else(|e| {
+        eprintln!("Warning: Ignoring config: {}", e);
+        Config::new()
+    });
+
+    // The values of a profile override the defaults
+    let profile = profile_arg(&args).map(str::to_string);
+    let saved_config = match profile.as_deref() {
+        Some(name) => match saved_config.with_profile(name) {
+            Ok(config) => config,
+            // --save-defaults creates a new profile
+            Err(_) if args.iter().any(|a| a == "--save-defaults") => saved_config,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        },
+        None => saved_config,
+    };
 
     // Built-in default values
     let builtin_defaults = Config {
         source: None,
         rate: Some(96000),
         channels: Some(2),
+        channel_map: None,
         format: Some("s32".to_string()),
         interval: Some(0.2),
         db_range: Some(90.0),
@@ -74,8 +281,39 @@ fn main() {
         off_threshold: Some(-60.0),
         silence_duration: Some(10.0),
         min_length: Some(600.0),
+        stop_on_locked_groove: Some(false),
         no_vumeter: Some(false),
+        display: None,
         no_keyboard: Some(false),
+        sparkline: Some(false),
+        remove_dc: Some(false),
+        riaa: Some(false),
+        subsonic_filter: Some(false),
+        subsonic_corner: Some(subsonic::DEFAULT_CORNER_HZ),
+        gain: Some(0.0),
+        limiter: Some(false),
+        limiter_ceiling: Some(limiter::DEFAULT_CEILING_DB),
+        downmix: None,
+        detect_interval: Some(180.0),
+        no_shazam: Some(false),
+        pause_strategy: None,
+        preview: None,
+        preview_bitrate: Some(DEFAULT_PREVIEW_BITRATE),
+        level_unit: Some("dbfs".to_string()),
+        calibration: None,
+        sources: None,
+        schedule: None,
+        profiles: None,
+        cue_creator: None,
+        splitter: None,
+        discogs: None,
+        http: None,
+        output: None,
+        organize: None,
+        mqtt: None,
+        log: None,
+        oled: None,
+        gpio: None,
     };
 
     // Start with built-in defaults, then apply saved config
@@ -87,6 +325,7 @@ fn main() {
     let mut source: Option<String> = effective_config.source.clone();
     let mut rate = effective_config.rate.unwrap_or(96000);
     let mut channels = effective_config.channels.unwrap_or(2);
+    let mut channel_map = effective_config.channel_map.as_deref().map(parse_channel_map).unwrap_or_default();
     let mut format = SampleFormat::from_str(&effective_config.format.clone().unwrap_or_else(|| "s32".to_string()))
         .unwrap_or(SampleFormat::S32);
     let mut interval = effective_config.interval.unwrap_or(0.2);
@@ -95,14 +334,47 @@ fn main() {
     let mut off_threshold = effective_config.off_threshold.unwrap_or(-60.0);
     let mut silence_duration = effective_config.silence_duration.unwrap_or(10.0);
     let mut min_length = effective_config.min_length.unwrap_or(600.0);
+    let mut stop_on_locked_groove = effective_config.stop_on_locked_groove.unwrap_or(false);
     let mut no_vumeter = effective_config.no_vumeter.unwrap_or(false);
+    let mut display_kind = effective_config.display.as_deref().map(parse_display);
     let mut no_keyboard = effective_config.no_keyboard.unwrap_or(false);
+    let mut show_sparkline = effective_config.sparkline.unwrap_or(false);
+    let mut remove_dc = effective_config.remove_dc.unwrap_or(false);
+    let mut riaa = effective_config.riaa.unwrap_or(false);
+    let mut subsonic_filter = effective_config.subsonic_filter.unwrap_or(false);
+    let mut subsonic_corner = effective_config.subsonic_corner.unwrap_or(subsonic::DEFAULT_CORNER_HZ);
+    let mut gain_db = effective_config.gain.unwrap_or(0.0);
+    let mut limiter = effective_config.limiter.unwrap_or(false);
+    let mut limiter_ceiling = effective_config.limiter_ceiling.unwrap_or(limiter::DEFAULT_CEILING_DB);
+    let mut downmix = effective_config.downmix.as_deref().map(parse_downmix).unwrap_or_default();
+    let mut preview_format = effective_config.preview.as_deref().and_then(PreviewFormat::from_str);
+    let mut preview_bitrate = effective_config.preview_bitrate.unwrap_or(DEFAULT_PREVIEW_BITRATE);
+    let mut output_config = effective_config.output.clone().unwrap_or_default();
+    let mut detect_interval = effective_config.detect_interval.unwrap_or(180.0);
+    let mut no_shazam = effective_config.no_shazam.unwrap_or(false);
+    let mut level_unit = effective_config.level_unit.as_deref()
+        .and_then(LevelUnit::from_str)
+        .unwrap_or(LevelUnit::Dbfs);
+    let mut calibration_override: Option<f64> = None;
     let mut duration: Option<f64> = None;
     let mut generate_cue = true;  // Generate CUE files by default
+    let mut group_sides = false;
+    let mut side_grouping = SideGrouping::default();
+    let mut pause_strategy = effective_config.pause_strategy.as_deref().map(str::to_lowercase);
+    let mut pause_params = StrategyParams::new(0);
+    let mut reconnect = ReconnectPolicy::default();
+    let mut start_at: Option<String> = None;
+    let mut stop_at: Option<String> = None;
 
     // Track which options were explicitly set on command line
     let mut cmdline_config = Config::new();
     let mut save_defaults = false;
+    let mut calibrate = false;
+
+    // Target listing options (applied after all arguments are parsed)
+    let mut list_targets_mode = false;
+    let mut list_json = false;
+    let mut target_filter = TargetFilter::new();
 
     let mut i = 1;
     let mut positional_args = Vec::new();
@@ -110,7 +382,31 @@ fn main() {
     while i < args.len() {
         match args[i].as_str() {
             "--list-targets" => {
-                process::exit(list_targets());
+                list_targets_mode = true;
+            }
+            "--json" => {
+                list_json = true;
+            }
+            "--active-only" => {
+                target_filter.active_only = true;
+            }
+            "--media-class" => {
+                if i + 1 < args.len() {
+                    target_filter.media_class = Some(args[i + 1].clone());
+                    i += 1;
+                }
+            }
+            "--name-regex" => {
+                if i + 1 < args.len() {
+                    match Regex::new(&args[i + 1]) {
+                        Ok(re) => target_filter.name_regex = Some(re),
+                        Err(e) => {
+                            eprintln!("Error: Invalid --name-regex '{}': {}", args[i + 1], e);
+                            process::exit(1);
+                        }
+                    }
+                    i += 1;
+                }
             }
             "--show-defaults" => {
                 println!("Built-in default settings:");
@@ -136,6 +432,15 @@ fn main() {
                 println!("  Min recording:      600 seconds (10 minutes)");
                 println!("  VU meter:           enabled");
                 println!("  Keyboard shortcuts: enabled");
+                println!("  Level sparkline:    disabled");
+                println!("  RIAA correction:    disabled");
+                println!("  Subsonic filter:    disabled");
+                println!("  Subsonic corner:    20 Hz");
+                println!("  Song detection:     every 180 seconds");
+                println!("  Shazam:             enabled");
+                println!("  Preview:            disabled");
+                println!("  Reconnect:          wait forever for a lost source");
+                println!("  Schedule:           none (record whenever there is a signal)");
                 process::exit(0);
             }
             "--show-saved-defaults" => {
@@ -156,6 +461,11 @@ fn main() {
             "--save-defaults" => {
                 save_defaults = true;
             }
+            "--calibrate" => calibrate = true,
+            "--profile" => {
+                // Already applied to the defaults above
+                i += 1;
+            }
             "--source" | "--target" => {
                 if i + 1 < args.len() {
                     source = Some(args[i + 1].clone());
@@ -177,6 +487,13 @@ fn main() {
                     i += 1;
                 }
             }
+            "--channel-map" => {
+                if i + 1 < args.len() {
+                    channel_map = parse_channel_map(&args[i + 1]);
+                    cmdline_config.channel_map = Some(args[i + 1].clone());
+                    i += 1;
+                }
+            }
             "--format" => {
                 if i + 1 < args.len() {
                     format = SampleFormat::from_str(&args[i + 1]).unwrap_or(SampleFormat::S32);
@@ -226,16 +543,272 @@ fn main() {
                     i += 1;
                 }
             }
+            "--stop-on-locked-groove" => {
+                stop_on_locked_groove = true;
+                cmdline_config.stop_on_locked_groove = Some(true);
+            }
+            "--no-stop-on-locked-groove" => {
+                stop_on_locked_groove = false;
+                cmdline_config.stop_on_locked_groove = Some(false);
+            }
             "--no-vumeter" => {
                 no_vumeter = true;
+                display_kind = Some(DisplayKind::Text);
                 cmdline_config.no_vumeter = Some(true);
             }
+            "--display" => {
+                if i + 1 < args.len() {
+                    display_kind = Some(parse_display(&args[i + 1]));
+                    cmdline_config.display = Some(args[i + 1].clone());
+                    i += 1;
+                }
+            }
             "--no-keyboard" => {
                 no_keyboard = true;
                 cmdline_config.no_keyboard = Some(true);
             }
+            "--preview" => {
+                if i + 1 < args.len() {
+                    match PreviewFormat::from_str(&args[i + 1]) {
+                        Some(f) => {
+                            preview_format = Some(f);
+                            cmdline_config.preview = Some(f.as_str().to_string());
+                        }
+                        None => {
+                            eprintln!("Error: Unknown preview format '{}' (use mp3 or opus)", args[i + 1]);
+                            process::exit(1);
+                        }
+                    }
+                    i += 1;
+                }
+            }
+            "--preview-bitrate" => {
+                if i + 1 < args.len() {
+                    preview_bitrate = args[i + 1].parse().unwrap_or(DEFAULT_PREVIEW_BITRATE);
+                    cmdline_config.preview_bitrate = Some(preview_bitrate);
+                    i += 1;
+                }
+            }
+            "--flac" | "--no-flac" => {
+                let flac = args[i] == "--flac";
+                output_config.flac = Some(flac);
+                cmdline_config.output.get_or_insert_with(Default::default).flac = Some(flac);
+            }
+            "--name" => {
+                if i + 1 < args.len() {
+                    output_config.filename_template = Some(args[i + 1].clone());
+                    cmdline_config.output.get_or_insert_with(Default::default).filename_template = Some(args[i + 1].clone());
+                    i += 1;
+                }
+            }
+            "--sparkline" => {
+                show_sparkline = true;
+                cmdline_config.sparkline = Some(true);
+            }
+            "--remove-dc" => {
+                remove_dc = true;
+                cmdline_config.remove_dc = Some(true);
+            }
+            "--no-remove-dc" => {
+                remove_dc = false;
+                cmdline_config.remove_dc = Some(false);
+            }
+            "--riaa" => {
+                riaa = true;
+                cmdline_config.riaa = Some(true);
+            }
+            "--no-riaa" => {
+                riaa = false;
+                cmdline_config.riaa = Some(false);
+            }
+            "--subsonic-filter" => {
+                subsonic_filter = true;
+                cmdline_config.subsonic_filter = Some(true);
+            }
+            "--no-subsonic-filter" => {
+                subsonic_filter = false;
+                cmdline_config.subsonic_filter = Some(false);
+            }
+            "--subsonic-corner" => {
+                if i + 1 < args.len() {
+                    match args[i + 1].parse::<f64>() {
+                        Ok(hz) if hz > 0.0 => {
+                            subsonic_corner = hz;
+                            cmdline_config.subsonic_corner = Some(hz);
+                        }
+                        _ => {
+                            eprintln!("Error: Invalid subsonic corner frequency: {}", args[i + 1]);
+                            process::exit(1);
+                        }
+                    }
+                    i += 1;
+                }
+            }
+            "--gain" => {
+                if i + 1 < args.len() {
+                    match args[i + 1].parse::<f64>() {
+                        Ok(db) if db.abs() <= gain::MAX_GAIN_DB => {
+                            gain_db = db;
+                            cmdline_config.gain = Some(db);
+                        }
+                        _ => {
+                            eprintln!("Error: Invalid gain (dB, -{0} to {0}): {1}", gain::MAX_GAIN_DB, args[i + 1]);
+                            process::exit(1);
+                        }
+                    }
+                    i += 1;
+                }
+            }
+            "--limiter" => {
+                limiter = true;
+                cmdline_config.limiter = Some(true);
+            }
+            "--no-limiter" => {
+                limiter = false;
+                cmdline_config.limiter = Some(false);
+            }
+            "--limiter-ceiling" => {
+                if i + 1 < args.len() {
+                    match args[i + 1].parse::<f64>() {
+                        Ok(db) if db <= 0.0 => {
+                            limiter_ceiling = db;
+                            cmdline_config.limiter_ceiling = Some(db);
+                        }
+                        _ => {
+                            eprintln!("Error: Invalid limiter ceiling (dBFS, at most 0): {}", args[i + 1]);
+                            process::exit(1);
+                        }
+                    }
+                    i += 1;
+                }
+            }
+            "--downmix" => {
+                if i + 1 < args.len() {
+                    downmix = parse_downmix(&args[i + 1]);
+                    cmdline_config.downmix = Some(downmix.as_str().to_string());
+                    i += 1;
+                }
+            }
+            "--detect-interval" => {
+                if i + 1 < args.len() {
+                    detect_interval = args[i + 1].parse().unwrap_or(180.0);
+                    cmdline_config.detect_interval = Some(detect_interval);
+                    i += 1;
+                }
+            }
+            "--no-shazam" => {
+                no_shazam = true;
+                cmdline_config.no_shazam = Some(true);
+            }
+            "--pause-strategy" => {
+                if i + 1 < args.len() {
+                    let name = args[i + 1].to_lowercase();
+                    if !STRATEGY_NAMES.contains(&name.as_str()) {
+                        eprintln!("Error: Unknown pause strategy '{}' (use {})", args[i + 1], STRATEGY_NAMES.join(", "));
+                        process::exit(1);
+                    }
+                    pause_strategy = Some(name.clone());
+                    cmdline_config.pause_strategy = Some(name);
+                    i += 1;
+                }
+            }
+            "--pause-threshold" | "--pause-drop" | "--pause-ratio" | "--pause-percentile" | "--pause-rise"
+            | "--pause-window" | "--pause-sensitivity" => {
+                if i + 1 < args.len() {
+                    let value: f32 = parse_option_value(&args[i], &args[i + 1]);
+                    match args[i].as_str() {
+                        "--pause-threshold" => pause_params.threshold_db = Some(value),
+                        "--pause-drop" => pause_params.drop_db = Some(value),
+                        "--pause-ratio" => pause_params.ratio = Some(value),
+                        "--pause-percentile" => pause_params.percentile = Some(value),
+                        "--pause-rise" => pause_params.rise_db = Some(value),
+                        "--pause-window" => pause_params.window_seconds = Some(value),
+                        _ => pause_params.sensitivity_db = value,
+                    }
+                    i += 1;
+                }
+            }
+            "--pause-min" => {
+                if i + 1 < args.len() {
+                    pause_params.min_pause_ms = Some(parse_option_value(&args[i], &args[i + 1]));
+                    i += 1;
+                }
+            }
+            "--pause-tracks" => {
+                if i + 1 < args.len() {
+                    match detection_strategies::parse_track_durations(&args[i + 1]) {
+                        Some(durations) => pause_params.set_track_durations(&durations),
+                        None => {
+                            eprintln!("Error: Invalid --pause-tracks '{}' (use e.g. 3:45,4:10)", args[i + 1]);
+                            process::exit(1);
+                        }
+                    }
+                    i += 1;
+                }
+            }
+            "--units" => {
+                if i + 1 < args.len() {
+                    match LevelUnit::from_str(&args[i + 1]) {
+                        Some(unit) => {
+                            level_unit = unit;
+                            cmdline_config.level_unit = Some(args[i + 1].to_lowercase());
+                        }
+                        None => {
+                            eprintln!("Error: Unknown level unit '{}' (use dbfs, dbu or dbv)", args[i + 1]);
+                            process::exit(1);
+                        }
+                    }
+                    i += 1;
+                }
+            }
+            "--calibration" => {
+                if i + 1 < args.len() {
+                    match args[i + 1].parse::<f64>() {
+                        Ok(v) => calibration_override = Some(v),
+                        Err(_) => {
+                            eprintln!("Error: Invalid --calibration value '{}'", args[i + 1]);
+                            process::exit(1);
+                        }
+                    }
+                    i += 1;
+                }
+            }
+            "--start-at" => {
+                if i + 1 < args.len() {
+                    start_at = Some(args[i + 1].clone());
+                    i += 1;
+                }
+            }
+            "--stop-at" => {
+                if i + 1 < args.len() {
+                    stop_at = Some(args[i + 1].clone());
+                    i += 1;
+                }
+            }
+            "--schedule" => {
+                if i + 1 < args.len() {
+                    cmdline_config.schedule.get_or_insert_with(Vec::new).push(args[i + 1].clone());
+                    i += 1;
+                }
+            }
+            "--group-sides" => group_sides = true,
+            "--side-gap" => {
+                if i + 1 < args.len() {
+                    let minutes: f64 = parse_option_value("--side-gap", &args[i + 1]);
+                    side_grouping.max_gap_seconds = minutes * 60.0;
+                    i += 1;
+                }
+            }
             "--generate-cue" => generate_cue = true,
             "--no-generate-cue" => generate_cue = false,
+            "--no-reconnect" => reconnect.enabled = false,
+            "--reconnect-timeout" => {
+                if i + 1 < args.len() {
+                    let seconds: f64 = parse_option_value("--reconnect-timeout", &args[i + 1]);
+                    reconnect.timeout = (seconds > 0.0).then(|| Duration::from_secs_f64(seconds));
+                    i += 1;
+                }
+            }
             "--duration" => {
                 if i + 1 < args.len() {
                     let dur_value: f64 = args[i + 1].parse().unwrap_or(60.0);
@@ -267,26 +840,68 @@ fn main() {
         i += 1;
     }
 
-    // Save defaults if requested
-    if save_defaults {
-        // Merge command-line config with saved config
-        let mut config_to_save = saved_config.clone();
-        config_to_save.merge(&cmdline_config);
-        
-        match config_to_save.save() {
-            Ok(_) => {
-                if let Ok(config_path) = Config::get_config_path() {
-                    println!("Defaults saved to {:?}", config_path);
-                    println!();
-                    config_to_save.print("Saved configuration");
-                }
-                process::exit(0);
+    if list_targets_mode {
+        process::exit(list_targets_filtered(&target_filter, list_json));
+    }
+
+    // Save defaults if requested; with --calibrate after measuring, and with
+    // --calibration once the source address it is saved under is known
+    if save_defaults && !calibrate && calibration_override.is_none() {
+        save_defaults_and_exit(&cmdline_config, profile.as_deref());
+    }
+
+    // With the VU meter on, log lines are shown below it so they don't
+    // scroll the display away
+    let log_buffer = LogBuffer::new();
+    let log_settings = LogSettings::from_config(&effective_config.log.clone().unwrap_or_default()).unwrap_or_else(|e| {
+        eprintln!("Warning: Ignoring [log] config: {}", e);
+        LogSettings::default()
+    });
+    let display_kind = display_kind.unwrap_or(if no_vumeter { DisplayKind::Text } else { DisplayKind::Vu });
+    let log_sink = if display_kind == DisplayKind::Vu { Sink::Buffer(log_buffer.clone()) } else { Sink::Terminal };
+    let _ = logging::init(&log_settings, log_sink);
+
+    let schedule_rules = cmdline_config.schedule.clone().or_else(|| effective_config.schedule.clone()).unwrap_or_default();
+    let schedule = match Schedule::parse(start_at.as_deref(), stop_at.as_deref(), &schedule_rules, Local::now().naive_local()) {
+        Ok(schedule) => schedule,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+    };
+
+    // Resolve a named source alias; explicit command-line options take precedence
+    let mut source_alias_name: Option<String> = None;
+    let resolved = source.as_deref().map(|s| effective_config.resolve_source(s)).transpose().unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    });
+    if let Some(ResolvedSource { alias, format: alias_format, channel_map: alias_channel_map }) = resolved.flatten() {
+        println!("Using source alias '{}' -> {}", source.as_deref().unwrap_or(""), alias.address);
+        if cmdline_config.rate.is_none() {
+            if let Some(r) = alias.rate {
+                rate = r;
             }
-            Err(e) => {
-                eprintln!("Error saving defaults: {}", e);
-                process::exit(1);
+        }
+        if cmdline_config.channels.is_none() {
+            if let Some(c) = alias.channels {
+                channels = c;
+            }
+        }
+        if cmdline_config.channel_map.is_none() {
+            if let Some(map) = alias_channel_map {
+                channel_map = map;
+            }
+        }
+        if cmdline_config.format.is_none() {
+            format = alias_format.unwrap_or(format);
+        }
+        if cmdline_config.off_threshold.is_none() {
+            if let Some(t) = alias.off_threshold {
+                off_threshold = t;
             }
         }
+        source_alias_name = source.replace(alias.address);
     }
 
     // Get filename from positional args
@@ -295,7 +910,7 @@ fn main() {
     }
 
     // Determine the audio source address
-    let source_address = if let Some(src) = source {
+    let source_address = if let Some(src) = source.clone() {
         // Parse to determine backend
         let (backend, device) = match parse_audio_address(&src) {
             Ok(result) => result,
@@ -337,11 +952,79 @@ fn main() {
 
     println!("Using {} backend with device: {}", backend, device);
 
+    // Calibration is stored under the resolved address, which it is looked
+    // up by (see below); without a source it becomes the default
+    if let Some(full_scale_dbu) = calibration_override {
+        let key = if source.is_some() { source_address.as_str() } else { DEFAULT_CALIBRATION_KEY };
+        cmdline_config.set_calibration(key, full_scale_dbu);
+        if save_defaults && !calibrate {
+            save_defaults_and_exit(&cmdline_config, profile.as_deref());
+        }
+    }
+
+    // With a channel map, --channels is the number of channels of the device
+    let device_channels = channels;
+    if !channel_map.is_empty() {
+        channels = channel_map.len();
+        let inputs: Vec<String> = channel_map.iter().map(|c| (c + 1).to_string()).collect();
+        println!("Recording input channel(s) {}", inputs.join(", "));
+    }
+
     // Create recorder
-    let mut recorder = AudioRecorder::new(record_file.clone(), rate, channels, format, min_length);
+    let filename_template = match FilenameTemplate::for_recordings(&output_config) {
+        Ok(template) => template,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+    };
+    let mut recorder = AudioRecorder::with_filename_template(record_file.clone(), filename_template, rate, channels, format, min_length);
+    if downmix != Downmix::Off {
+        if let Err(e) = downmix.validate(channels) {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+        recorder.set_downmix(downmix);
+        println!("Downmix of the recordings: {}", downmix.as_str());
+    }
+    if let Some(format) = preview_format {
+        recorder.set_preview(Some(PreviewSettings { format, bitrate_kbps: preview_bitrate }));
+        println!("Writing {} preview at {} kbit/s next to each recording", format.as_str(), preview_bitrate);
+    }
+    match ListeningCopySettings::from_config(&output_config) {
+        Ok(Some(settings)) => {
+            recorder.set_listening_copy(Some(settings));
+            println!(
+                "Writing a FLAC copy at {} Hz/{} bit next to each recording",
+                settings.sample_rate, settings.bits_per_sample
+            );
+        }
+        Ok(None) => {}
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+    }
+
+    // Determine how levels are displayed
+    let calibration = match calibration_override {
+        Some(full_scale_dbu) => Some(Calibration::new(full_scale_dbu)),
+        None => {
+            let names: Vec<&str> = [Some(source_address.as_str()), source_alias_name.as_deref()]
+                .into_iter().flatten().collect();
+            effective_config.calibration_for_any(&names)
+        }
+    };
+    let level_scale = match LevelScale::new(level_unit, calibration.as_ref()) {
+        Ok(scale) => scale,
+        Err(e) => {
+            eprintln!("Error: {}. Use --calibration <DBU> to set one.", e);
+            process::exit(1);
+        }
+    };
 
     // Create audio stream
-    let stream = match create_input_stream(&source_address, rate, channels, format) {
+    let stream = match create_mapped_input_stream(&source_address, rate, device_channels, format, &channel_map) {
         Ok(s) => s,
         Err(e) => {
             eprintln!("Failed to create audio stream: {}", e);
@@ -358,6 +1041,108 @@ fn main() {
         off_threshold,
         silence_duration,
     );
+    let mut filters = FilterChain::new(channels, format);
+    // First, so that the RIAA bass boost does not amplify the offset
+    let dc_meters: Vec<DcOffsetMeter> = if remove_dc { (0..channels).map(|_| DcOffsetMeter::new()).collect() } else { Vec::new() };
+    if remove_dc {
+        filters.add_per_channel(|channel| Box::new(DcBlocker::new(rate).with_meter(dc_meters[channel].clone())));
+        println!("Removing DC offset");
+    }
+    // Before the other filters, so that they work on the recorded level
+    let gain_meter = GainClipMeter::new();
+    if gain_db != 0.0 {
+        filters.add(|| Box::new(Gain::new(gain_db).with_meter(gain_meter.clone())));
+        println!("Applying {:+.1} dB input gain", gain_db);
+    }
+    if riaa {
+        filters.add(|| Box::new(RiaaFilter::new(rate)));
+        println!("Applying RIAA correction");
+    }
+    // After the RIAA bass boost, which raises the rumble as well
+    if subsonic_filter {
+        filters.add(|| Box::new(SubsonicFilter::new(rate, subsonic_corner)));
+        println!("Applying subsonic filter ({} Hz)", subsonic_corner);
+    }
+    // Last, so that nothing after it raises the peaks again
+    if limiter {
+        filters.add(|| Box::new(Limiter::new(rate, limiter_ceiling)));
+        println!("Applying peak limiter (ceiling {:.1} dBFS)", limiter_ceiling);
+    }
+    meter.set_filters(filters);
+
+    if calibrate {
+        if let Err(e) = meter.start() {
+            eprintln!("Failed to start recording: {}", e);
+            process::exit(1);
+        }
+        println!("Calibrating: keep the source silent (or the needle in a silent groove) for {:.0} seconds...", CALIBRATION_SECONDS);
+        let mut calibration = NoiseFloorCalibration::new(interval);
+        while calibration.seconds() < CALIBRATION_SECONDS {
+            match process_audio_chunk(&mut meter) {
+                Some((metrics, _)) => calibration.add(&metrics),
+                None => break,
+            }
+        }
+        meter.stop();
+        let Some(result) = calibration.result() else {
+            eprintln!("Error: No audio received from {}", source_address);
+            process::exit(1);
+        };
+        println!();
+        println!("Noise floor:        {:.1} dBFS", result.noise_floor_db);
+        println!("Noise peaks:        {:.1} dBFS ({} louder chunks)", result.noise_peak_db, result.clicks);
+        println!("Off threshold:      {} dB", result.off_threshold);
+        println!("Silence duration:   {} seconds", result.silence_duration);
+        if let Some(warning) = &result.warning {
+            println!();
+            println!("Warning: {}", warning);
+        }
+        if let Some(name) = source_alias_name.as_deref() {
+            if effective_config.source_alias(name).is_some_and(|alias| alias.off_threshold.is_some()) {
+                println!();
+                println!("Note: [sources.{}] sets its own off_threshold, which takes precedence over the defaults.", name);
+                println!("Set off_threshold = {} there.", result.off_threshold);
+            }
+        }
+        cmdline_config.off_threshold = Some(result.off_threshold);
+        cmdline_config.silence_duration = Some(result.silence_duration);
+        if save_defaults {
+            println!();
+            save_defaults_and_exit(&cmdline_config, profile.as_deref());
+        }
+        println!();
+        println!("Use --off-threshold {} --silence-duration {}, or add --save-defaults to save them.",
+                 result.off_threshold, result.silence_duration);
+        process::exit(0);
+    }
+
+    // Optional live song boundary detection
+    let mut pause_detector = match pause_strategy {
+        Some(ref name) => {
+            pause_params.sample_rate = rate;
+            match detection_strategies::create(name, &pause_params) {
+                Ok(detector) => {
+                    println!("Song boundary detection: {}", detector.name());
+                    Some(detector)
+                }
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    process::exit(1);
+                }
+            }
+        }
+        None => None,
+    };
+    let mut pause_file: Option<String> = None;
+    let mut locked_groove = stop_on_locked_groove.then(|| LockedGrooveDetector::new(rate, off_threshold as f32));
+    if locked_groove.is_some() {
+        println!("Locked-groove stop: enabled");
+    }
+    let mut locked_groove_file: Option<String> = None;
+    // After a locked groove nothing is recorded until the signal goes off
+    let mut locked_groove_stopped = false;
+    let mut gain_clips_reported = 0;
+    let mut gain_warned: Option<Instant> = None;
 
     // Start recording
     if let Err(e) = meter.start() {
@@ -368,19 +1153,62 @@ fn main() {
     // Wait a moment for process to start
     thread::sleep(Duration::from_millis(100));
 
-    if no_keyboard {
-        println!("Recording started. Press Ctrl+C to stop.");
+    let level_history = if show_sparkline {
+        Some(LevelHistory::new(60.0, interval))
     } else {
-        println!("Recording started. Press ESC or 'q' to quit.");
+        None
+    };
+    let mut display: Box<dyn StatusDisplay> = match display_kind {
+        DisplayKind::Vu => Box::new(TerminalDisplay::new(db_range, max_db, level_scale, level_history, Some(log_buffer.clone()))),
+        DisplayKind::Text => Box::new(TextDisplay::new()),
+        DisplayKind::Json => Box::new(JsonDisplay::stdout()),
+        DisplayKind::Oled => open_oled(&effective_config.oled.clone().unwrap_or_default()).unwrap_or_else(|e| {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }),
+        DisplayKind::None => Box::new(NullDisplay),
+    };
+
+    // stdout only carries JSON lines for the json display, messages go to stderr then
+    macro_rules! message {
+        ($($arg:tt)*) => {
+            if display_kind == DisplayKind::Json { eprintln!($($arg)*) } else { println!($($arg)*) }
+        };
+    }
+    if display_kind != DisplayKind::Json {
+        if no_keyboard {
+            println!("Recording started. Press Ctrl+C to stop.");
+        } else {
+            println!("Recording started. Press 'm' to mark a new track, 'n' to start a new file,");
+            println!("'p' to pause/resume, ESC or 'q' to quit.");
+        }
+    }
+    if !no_keyboard {
         // Enable raw mode for keyboard input
         enable_raw_mode().ok();
     }
-    println!("Waiting for signal...");
-    println!();
+    if display_kind == DisplayKind::Vu {
+        println!("Waiting for signal...");
+        println!();
+    }
 
     // Track start time for duration limit
     let start_time = std::time::Instant::now();
 
+    // Song detection runs on its own thread so it never stalls the capture loop
+    let song_detector = if !no_shazam && detect_interval > 0.0 {
+        Some(SongDetector::new(rate, format))
+    } else {
+        None
+    };
+    let mut snapshot = SnapshotBuffer::new(rate, channels, SNAPSHOT_SECONDS);
+    let mut recorded_seconds = 0.0;
+    let mut next_detection = detect_interval.min(60.0);
+    let mut detect_file: Option<String> = None;
+    let mut current_song: Option<String> = None;
+    let mut source_lost: Option<Instant> = None;
+    let mut scheduled_on: Option<bool> = None;
+
     // Main loop
     loop {
         // Check for keyboard input (non-blocking) if keyboard mode is enabled
@@ -389,9 +1217,25 @@ fn main() {
                 match code {
                     KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('Q') => {
                         disable_raw_mode().ok();
-                        println!("\nExiting...");
+                        message!("\nExiting...");
                         break;
                     }
+                    KeyCode::Char('n') | KeyCode::Char('N') => {
+                        // Manual side change: continue in the next file
+                        recorder.split_recording();
+                    }
+                    KeyCode::Char('p') | KeyCode::Char('P') => {
+                        // Pause while the record is turned over, keeping the file open
+                        if recorder.is_paused() {
+                            recorder.resume();
+                        } else {
+                            recorder.pause();
+                        }
+                    }
+                    KeyCode::Char('m') | KeyCode::Char('M') => {
+                        // A new track starts here; used by cue_creator
+                        recorder.add_mark();
+                    }
                     _ => {}
                 }
             }
@@ -404,9 +1248,47 @@ fn main() {
                 if !no_keyboard {
                     disable_raw_mode().ok();
                 }
-                println!("\nDuration limit reached. Exiting...");
+                message!("\nDuration limit reached. Exiting...");
+                break;
+            }
+        }
+
+        // Outside the scheduled windows audio is discarded and a running
+        // recording is closed
+        let schedule_active = if schedule.is_empty() {
+            true
+        } else {
+            let now = Local::now().naive_local();
+            if schedule.is_finished(now) {
+                if !no_keyboard {
+                    disable_raw_mode().ok();
+                }
+                message!("\nScheduled stop time reached. Exiting...");
+                break;
+            }
+            let active = schedule.is_active(now);
+            if scheduled_on != Some(active) {
+                info!("{}", if active { "Scheduled recording window started" } else { "Waiting for the scheduled recording window" });
+            }
+            scheduled_on = Some(active);
+            active
+        };
+
+        // Wait for a lost source to come back
+        if let Some(lost_since) = source_lost {
+            if reconnect.expired(lost_since) {
+                if !no_keyboard {
+                    disable_raw_mode().ok();
+                }
+                message!("\nAudio source did not come back. Exiting...");
                 break;
             }
+            thread::sleep(reconnect.retry_interval);
+            if meter.try_reconnect() {
+                display.event(&StatusEvent::SourceBack { seconds: lost_since.elapsed().as_secs_f64() });
+                source_lost = None;
+            }
+            continue;
         }
 
         // Read and process audio data once
@@ -414,55 +1296,204 @@ fn main() {
             Some((metrics, audio_data)) => {
                 let any_channel_on = metrics.iter().any(|m| m.is_on);
                 let is_recording = recorder.is_recording();
+                let is_paused = recorder.is_paused();
+
+                if !any_channel_on {
+                    locked_groove_stopped = false;
+                }
 
                 // Write the actual audio data to the recorder
-                recorder.write_audio(&audio_data, any_channel_on);
+                recorder.write_audio(&audio_data, schedule_active && any_channel_on && !locked_groove_stopped);
+                let true_peak = metrics.iter().map(|m| m.true_peak_db).fold(f64::MIN, f64::max);
+                recorder.note_true_peak(true_peak);
+                recorder.note_overs(&metrics.iter().map(|m| m.overs).collect::<Vec<_>>());
 
-                if !no_vumeter {
-                    // Build status lines
-                    let mut status_parts: Vec<String> = Vec::new();
+                // Without the limiter, peaks the gain pushes over full scale are clipped
+                let clipped = gain_meter.clipped();
+                if !limiter && clipped > gain_clips_reported
+                    && gain_warned.is_none_or(|t| t.elapsed().as_secs_f64() >= gain::CLIP_WARNING_SECONDS)
+                {
+                    warn!("Input gain {:+.1} dB clipped {} sample(s) - lower --gain or use --limiter", gain_db, clipped - gain_clips_reported);
+                    gain_clips_reported = clipped;
+                    gain_warned = Some(Instant::now());
+                }
 
-                    // Recording status
-                    if is_recording {
-                        if let Some(filename) = recorder.current_filename() {
-                            status_parts.push(format!("[RECORDING to {}]", filename));
-                        } else {
-                            status_parts.push("[RECORDING]".to_string());
+                let mut song_boundary = false;
+                if let Some(ref mut detector) = pause_detector {
+                    // Count songs per recording file
+                    let filename = recorder.current_filename();
+                    if filename != pause_file {
+                        pause_file = filename;
+                        detector.reset();
+                    }
+                    if recorder.is_recording() && !is_paused && detector.feed_audio(&audio_data, format).is_some() {
+                        song_boundary = true;
+                        display.event(&StatusEvent::SongBoundary { song: detector.song_number() });
+                    }
+                }
+
+                if let Some(ref mut detector) = locked_groove {
+                    let filename = recorder.current_filename();
+                    if filename != locked_groove_file {
+                        locked_groove_file = filename;
+                        detector.reset();
+                    }
+                    if recorder.is_recording() && !is_paused {
+                        if let Some(groove) = detector.feed_audio(&audio_data, format) {
+                            info!("Locked groove since {:.0}s ({:.2}s per revolution), stopping the recording",
+                                  groove.start, groove.period);
+                            recorder.stop_recording();
+                            locked_groove_stopped = true;
                         }
                     }
+                }
 
-                    let rec_status = if status_parts.is_empty() {
-                        None
-                    } else {
-                        Some(status_parts.join("  "))
-                    };
-                    display_vu_meter(&metrics, db_range, max_db, rec_status.as_deref()).ok();
+                if let Some(ref detector) = song_detector {
+                    // Restart detection timing for every new recording file
+                    let filename = recorder.current_filename();
+                    if filename != detect_file {
+                        detect_file = filename;
+                        snapshot.clear();
+                        recorded_seconds = 0.0;
+                        next_detection = detect_interval.min(60.0);
+                        current_song = None;
+                    }
+
+                    if song_boundary {
+                        // Identify the new song as soon as enough of it is buffered
+                        snapshot.clear();
+                        next_detection = recorded_seconds + MIN_SNAPSHOT_SECONDS;
+                        current_song = None;
+                    }
+
+                    if recorder.is_recording() && !is_paused {
+                        snapshot.push(&audio_data);
+                        recorded_seconds += audio_data.first().map_or(0, |c| c.len()) as f64 / rate as f64;
+
+                        if recorded_seconds >= next_detection && snapshot.seconds() >= MIN_SNAPSHOT_SECONDS {
+                            // If the worker is still busy, try again with the next chunk
+                            let position = recorded_seconds - snapshot.seconds();
+                            if detector.submit(snapshot.snapshot(), position) {
+                                next_detection = recorded_seconds + detect_interval;
+                            }
+                        }
+                    }
+
+                    while let Some(event) = detector.try_recv() {
+                        if let SongDetectEvent::Identified { song, .. } = event {
+                            current_song = Some(format!("{} - {}", song.artist, song.title));
+                            display.event(&StatusEvent::Identified { artist: song.artist, title: song.title });
+                        }
+                    }
                 }
+
+                let filename = recorder.current_filename();
+                let frame = StatusFrame {
+                    recording: is_recording,
+                    paused: is_paused,
+                    file: filename.as_deref(),
+                    waiting_for_schedule: !schedule_active,
+                    detector: pause_detector.as_ref().and_then(|d| d.status_line()),
+                    song: current_song.as_deref(),
+                    levels: &metrics,
+                };
+                display.update(&frame).ok();
+            }
+            None if reconnect.enabled && !meter.source_ended() => {
+                // Close the file so it stays usable even if the source never returns
+                recorder.stop_recording();
+                display.event(&StatusEvent::SourceLost);
+                source_lost = Some(Instant::now());
             }
             None => {
                 if !no_keyboard {
                     disable_raw_mode().ok();
                 }
-                println!("\nRecording stopped.");
+                message!("\nRecording stopped.");
                 break;
             }
         }
     }
 
+    log_buffer.release();
+    recorder.close();
+
+    let dc_offset: Option<Vec<f64>> = dc_meters.iter().map(DcOffsetMeter::offset).collect();
+    if let Some(dc_offset) = dc_offset.filter(|o| !o.is_empty()) {
+        message!("\nDC offset removed: {}", format_dc_offset(&dc_offset));
+    }
+    if gain_meter.clipped() > 0 {
+        let outcome = if limiter { "caught by the limiter" } else { "clipped" };
+        message!("\nInput gain {:+.1} dB pushed {} sample(s) over full scale ({})", gain_db, gain_meter.clipped(), outcome);
+    }
+
+    // Recording statistics
+    let stats = recorder.get_recording_stats();
+    if !stats.is_empty() {
+        catalog::update(|catalog| stats.iter().try_for_each(|s| catalog.add_capture(&Capture::from_stats(s))));
+        message!("\nRecordings:");
+        for stat in &stats {
+            match stat.max_true_peak_db {
+                Some(tp) => {
+                    let over = if tp > 0.0 { "  (inter-sample overs!)" } else { "" };
+                    message!("  {}  {:.1}s  true peak {:.1} dBTP{}", stat.filename, stat.duration, tp, over);
+                }
+                None => message!("  {}  {:.1}s", stat.filename, stat.duration),
+            }
+            if let Some(preview) = &stat.preview_file {
+                message!("    preview: {}", preview);
+            }
+            if let Some(flac) = &stat.flac_file {
+                message!("    FLAC copy: {}", flac);
+            }
+        }
+    }
+
+    // Files, or album directories with several sides, to create CUE files for
+    let mut cue_targets = recorder.get_recorded_files();
+    if group_sides {
+        for group in side_grouping.group(&stats) {
+            let sides: Vec<&RecordingStats> = group.iter().map(|&i| &stats[i]).collect();
+            match side_groups::organize_album(&sides, &record_file) {
+                Ok(moved) => {
+                    message!("\nSides of one album:");
+                    for (side, path) in sides.iter().zip(&moved) {
+                        message!("  {} -> {}", side.filename, path);
+                    }
+                    catalog::update(|catalog| {
+                        for (side, path) in sides.iter().zip(&moved) {
+                            // The FLAC copy and the preview moved along with the WAV file
+                            for old in std::iter::once(&side.filename).chain(&side.flac_file).chain(&side.preview_file) {
+                                let extension = Path::new(old).extension().unwrap_or_default();
+                                catalog.move_file(old, &Path::new(path).with_extension(extension).to_string_lossy())?;
+                            }
+                        }
+                        Ok(())
+                    });
+                    cue_targets.retain(|f| !sides.iter().any(|s| s.filename == *f));
+                    if let Some(dir) = Path::new(&moved[0]).parent() {
+                        cue_targets.push(dir.to_string_lossy().to_string());
+                    }
+                }
+                Err(e) => eprintln!("\nError grouping sides: {}", e),
+            }
+        }
+    }
+
     // Generate CUE files if requested
     if generate_cue {
-        let recorded_files = recorder.get_recorded_files();
+        let recorded_files = cue_targets;
         if !recorded_files.is_empty() {
-            println!("\nGenerating CUE files for {} recording(s)...", recorded_files.len());
+            message!("\nGenerating CUE files for {} recording(s)...", recorded_files.len());
             for file in &recorded_files {
-                println!("  Processing: {}", file);
+                message!("  Processing: {}", file);
                 let output = process::Command::new("cue_creator")
                     .arg(file)
                     .output();
                 
                 match output {
                     Ok(result) if result.status.success() => {
-                        println!("    ✓ CUE file generated");
+                        message!("    ✓ CUE file generated");
                     }
                     Ok(result) => {
                         eprintln!("    ✗ Failed to generate CUE file");
@@ -476,9 +1507,7 @@ fn main() {
                 }
             }
         } else {
-            println!("\nNo recordings were created, skipping CUE generation.");
+            message!("\nNo recordings were created, skipping CUE generation.");
         }
     }
-
-    recorder.close();
 }
\ No newline at end of file
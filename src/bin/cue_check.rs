@@ -0,0 +1,170 @@
+//! Check an existing CUE sheet against its WAV file.
+//!
+//! Verifies that the INDEX times are in order and inside the file and, if
+//! the sheet names a MusicBrainz release (REM MUSICBRAINZ_RELEASE_ID),
+//! compares the track lengths with the lengths listed for the release.
+//! Exits with status 1 if a sheet has errors.
+//!
+//! Usage:
+//!     cue_check [--cue FILE] [--threshold SEC] [--no-musicbrainz] file.wav ...
+
+use std::env;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::process;
+
+use autorec::cue_check::{self, Severity, DEFAULT_THRESHOLD_SECONDS};
+use autorec::cuefile::{self, CueSheet};
+use autorec::musicbrainz;
+use autorec::wavfile::read_wav_header;
+
+fn print_usage() {
+    println!("Check CUE sheets against their WAV files and the MusicBrainz track lengths");
+    println!();
+    println!("Usage: cue_check [OPTIONS] <FILE.wav> [FILE2.wav ...]");
+    println!();
+    println!("Options:");
+    println!("  --cue <FILE>             CUE sheet to check (only with a single WAV file)");
+    println!("                             (default: <name>.cue or <name>.guess.cue)");
+    println!("  --threshold <SEC>        Report tracks whose length differs from the");
+    println!("                             release by more than this (default: 5)");
+    println!("  --no-musicbrainz         Only check the INDEX times, don't compare");
+    println!("                             track lengths");
+    println!("  --help                   Show this help message");
+    println!();
+    println!("Examples:");
+    println!("  cue_check side_a.1.wav side_b.2.wav");
+    println!("  cue_check --cue edited.cue --threshold 2 side_a.1.wav");
+}
+
+/// Length of the audio in a WAV file in seconds
+fn wav_duration(wav_file: &str) -> Result<f64, String> {
+    let file = File::open(wav_file).map_err(|e| format!("Failed to open {}: {}", wav_file, e))?;
+    let header = read_wav_header(&mut BufReader::new(file))?;
+    let frame_size = header.num_channels as f64 * (header.bits_per_sample / 8) as f64;
+    Ok(header.data_size as f64 / (header.sample_rate as f64 * frame_size))
+}
+
+/// Track lengths of the sheet's MusicBrainz release for the side that fits
+/// the file best; None if the sheet has no release ID
+fn expected_lengths(sheet: &CueSheet, file_duration: f64) -> Result<Option<Vec<f64>>, String> {
+    let release_id = match &sheet.release.musicbrainz_release_id {
+        Some(id) => id,
+        None => return Ok(None),
+    };
+    let sides = musicbrainz::fetch_release_sides(release_id)
+        .map_err(|e| format!("Failed to fetch release {}: {}", release_id, e))?;
+    let titles: Vec<String> = sheet.tracks.iter().filter_map(|t| t.title.clone()).collect();
+    let tracks = musicbrainz::find_best_side(&sides, file_duration, &titles)
+        .ok_or_else(|| format!("No side of release {} matches the file", release_id))?;
+    Ok(Some(tracks.iter().map(|t| t.length_seconds).collect()))
+}
+
+fn main() {
+    autorec::logging::init_cli();
+    let args: Vec<String> = env::args().collect();
+
+    let mut cue_override: Option<PathBuf> = None;
+    let mut threshold = DEFAULT_THRESHOLD_SECONDS;
+    let mut use_musicbrainz = true;
+    let mut wav_files: Vec<String> = Vec::new();
+
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--cue" => {
+                if i + 1 < args.len() {
+                    cue_override = Some(PathBuf::from(&args[i + 1]));
+                    i += 1;
+                }
+            }
+            "--threshold" => {
+                if i + 1 < args.len() {
+                    threshold = match args[i + 1].parse::<f64>() {
+                        Ok(t) if t > 0.0 => t,
+                        _ => {
+                            eprintln!("Error: Invalid threshold '{}'", args[i + 1]);
+                            process::exit(1);
+                        }
+                    };
+                    i += 1;
+                }
+            }
+            "--no-musicbrainz" => use_musicbrainz = false,
+            "--help" | "-h" => {
+                print_usage();
+                process::exit(0);
+            }
+            arg if !arg.starts_with("--") => wav_files.push(arg.to_string()),
+            _ => {
+                eprintln!("Unknown option: {}", args[i]);
+                print_usage();
+                process::exit(1);
+            }
+        }
+        i += 1;
+    }
+
+    if wav_files.is_empty() {
+        print_usage();
+        process::exit(1);
+    }
+    if cue_override.is_some() && wav_files.len() > 1 {
+        eprintln!("Error: --cue can only be used with a single WAV file");
+        process::exit(1);
+    }
+
+    let mut failed = 0;
+
+    for wav_file in &wav_files {
+        println!("File: {}", wav_file);
+
+        let cue_path = match cue_override.clone().or_else(|| cuefile::find_cue_file(wav_file)) {
+            Some(p) => p,
+            None => {
+                eprintln!("  No CUE file found (run cue_creator first)");
+                failed += 1;
+                continue;
+            }
+        };
+        let (sheet, file_duration) = match cuefile::read_cue_file(&cue_path)
+            .and_then(|sheet| wav_duration(wav_file).map(|duration| (sheet, duration)))
+        {
+            Ok(result) => result,
+            Err(e) => {
+                eprintln!("  Error: {}", e);
+                failed += 1;
+                continue;
+            }
+        };
+        println!("  CUE: {} ({} tracks, file {})", cue_path.display(), sheet.tracks.len(),
+                 cuefile::format_cue_time(file_duration));
+
+        let mut findings = cue_check::check_structure(&sheet, file_duration);
+        if use_musicbrainz {
+            match expected_lengths(&sheet, file_duration) {
+                Ok(Some(expected)) => {
+                    findings.extend(cue_check::compare_durations(&sheet, file_duration, &expected, threshold))
+                }
+                Ok(None) => println!("  No MusicBrainz release in the CUE sheet, track lengths not compared"),
+                Err(e) => eprintln!("  Warning: {}", e),
+            }
+        }
+
+        if findings.is_empty() {
+            println!("  OK");
+        }
+        for finding in &findings {
+            println!("  {}", finding);
+        }
+        if findings.iter().any(|f| f.severity == Severity::Error) {
+            failed += 1;
+        }
+        println!();
+    }
+
+    if failed > 0 {
+        process::exit(1);
+    }
+}
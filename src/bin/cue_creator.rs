@@ -1,29 +1,47 @@
 //! Offline song boundary finder - finds song boundaries in WAV files without external metadata.
 //!
-//! Three-pass algorithm for vinyl recordings:
-//!   Pass 1: Compute RMS in small windows across the entire file
-//!   Pass 2: Detect groove-in (start of music) and groove-out (end of music)
-//!   Pass 3: Find "valleys" (local minima) that represent song boundaries
-//!           within the music region only
-//!
-//! Vinyl recording characteristics:
-//!   - Groove-in: 0.5-5s of quiet groove noise before music starts
-//!   - Groove-out: can be minutes of quiet at the end after music stops
-//!   - Song boundaries: brief energy dips (not true silence) between tracks
-//!   - No absolute silence: groove noise is always present
+//! The three-pass boundary algorithm lives in [`autorec::boundary`]; this
+//! tool adds song identification, album lookup, CUE generation and renaming.
 
-use autorec::SampleFormat;
 use autorec::musicbrainz;
-use autorec::cuefile::{self, Valley};
+use autorec::boundary::{self, BoundaryAnalysis, Medium, MEDIUM_NAMES};
+use autorec::cuefile::{self, CueEncoding, CueText, DetectionMode};
 use autorec::wavfile;
+use autorec::catalog::{self, CatalogTrack, Identification};
+use autorec::duplicates;
+use autorec::filename_template::{self, FilenameTemplate, TemplateValues};
+use autorec::rename::RenamePlan;
+use autorec::report::{self, FileReport};
+use autorec::tagging::ReleaseTags;
 use autorec::audio_analysis;
+use autorec::loudness;
+use autorec::acoustid;
 use autorec::album_identifier;
-use autorec::lookup::{self, DiscogsBackend, MusicBrainzBackend, AlbumIdentifier, FileForAssignment, FileSideResult};
+use autorec::lookup::{self, AlbumIdentifier, FileForAssignment, FileSideResult, ReleaseReference};
+use autorec::metadata::{self, RecordingMetadata};
+use autorec::tracklist::{self, Tracklist};
+use autorec::config::Config;
+use autorec::progress::{self, ProgressSink};
+use autorec::session::{self, Session};
+use autorec::spectrogram;
+use autorec::speed_check::{self, PlaybackSpeed};
+use autorec::watch::{self, WatchFolder};
+use autorec::audio_stream::{AudioOutputStream, PipeWireOutputStream};
 use std::env;
 use std::fs::{File, self};
-use std::io::{BufReader, Read};
+use std::io::{self, BufReader, Write};
 use std::path::{Path, PathBuf};
 use std::process::{self, Command};
+use std::time::Duration;
+
+/// Seconds played on each side of a boundary by --review
+const REVIEW_CONTEXT_SECONDS: f64 = 2.5;
+
+/// Interval between checks of the watched directory with --watch
+const WATCH_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Base name of renamed recordings unless the config sets `rename_template`
+const DEFAULT_RENAME_TEMPLATE: &str = "{artist} - {album}";
 
 /// Check if a file is currently open by another process (e.g. being recorded to)
 fn is_file_in_use(path: &str) -> bool {
@@ -44,430 +62,272 @@ fn format_timestamp(seconds: f64) -> String {
     format!("{:02}:{:05.2}", mins, secs)
 }
 
-/// Sanitize a string for use in filenames.
-/// - Keeps original case, spaces, hyphens, parentheses
-/// - Removes characters that cause filesystem problems: / \ : * ? " < > |
-/// - Collapses multiple spaces
-/// - Trims leading/trailing whitespace
-fn sanitize_for_filename(s: &str) -> String {
-    let s: String = s.chars()
-        .map(|c| match c {
-            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => ' ',
-            _ => c,
-        })
-        .collect();
-    // Collapse multiple spaces
-    let mut result = String::new();
-    let mut last_was_space = false;
-    for c in s.chars() {
-        if c == ' ' {
-            if !last_was_space {
-                result.push(c);
-            }
-            last_was_space = true;
-        } else {
-            result.push(c);
-            last_was_space = false;
-        }
+/// Play the audio around `position` and wait until it has been played
+fn play_around(wav_file: &str, position: f64, output: &mut Option<PipeWireOutputStream>) -> Result<(), String> {
+    let (header, samples) = wavfile::read_wav_segment(
+        wav_file, position - REVIEW_CONTEXT_SECONDS, 2.0 * REVIEW_CONTEXT_SECONDS)?;
+    let format = header.sample_format()
+        .ok_or_else(|| format!("Unsupported bit depth: {}", header.bits_per_sample))?;
+    if output.is_none() {
+        let mut stream = PipeWireOutputStream::new(
+            String::new(), header.sample_rate, header.num_channels as usize, format)
+            .map_err(|e| e.to_string())?;
+        stream.start().map_err(|e| e.to_string())?;
+        *output = Some(stream);
     }
-    result.trim().to_string()
+    let stream = output.as_mut().unwrap();
+    stream.write_chunk(&samples).map_err(|e| e.to_string())?;
+    let frames = samples.first().map_or(0, |ch| ch.len());
+    stream.drain(Duration::from_secs_f64(frames as f64 / header.sample_rate as f64 + 2.0));
+    Ok(())
 }
 
-/// Rename a WAV file and all its associated files (.cue, .identify.txt, etc.)
-/// based on the identified artist and album title.
-/// Preserves the side number (e.g. .1, .2) from the original filename.
-fn rename_recording(wav_file: &str, artist: &str, album_title: &str) {
-    let base = cuefile::wav_base_path(wav_file);
-    let base_str = base.to_string_lossy().to_string();
-
-    // Extract side number from the base name
-    // e.g. "dj_shadow_endtroducing.4" -> side_number = Some("4")
-    let base_filename = base.file_name().and_then(|n| n.to_str()).unwrap_or("");
-    let side_suffix = base_filename.rsplit('.').next()
-        .and_then(|s| s.parse::<u32>().ok())
-        .map(|n| format!(".{}", n))
-        .unwrap_or_default();
-
-    // Build new base name: Artist - Album Title
-    let artist_clean = sanitize_for_filename(artist);
-    let album_clean = sanitize_for_filename(album_title);
-    let new_base_name = format!("{} - {}", artist_clean, album_clean);
-
-    let dir = Path::new(wav_file).parent().unwrap_or(Path::new("."));
-    let new_stem = format!("{}{}", new_base_name, side_suffix);
-
-    // Check if already named correctly
-    let old_stem = base_filename;
-    if old_stem == new_stem {
-        println!("File already named correctly: {}", wav_file);
-        return;
-    }
-
+/// Play each detected boundary and let the user keep, replay or drop it
+fn review_boundaries(wav_file: &str, valleys: &mut Vec<cuefile::Valley>) {
     println!();
-    println!("Renaming: {} -> {}", old_stem, new_stem);
-
-    // Find and rename all associated files
-    let extensions = [".wav", ".cue", ".guess.cue", ".cue.txt", ".guess.cue.txt", ".identify.txt"];
-    let mut renamed_cue: Option<PathBuf> = None;
-    let mut new_wav_filename = String::new();
-
-    for ext in &extensions {
-        let old_path = PathBuf::from(format!("{}{}", base_str, ext));
-        if old_path.exists() {
-            let new_path = dir.join(format!("{}{}", new_stem, ext));
-            if new_path.exists() {
-                eprintln!("  Warning: Cannot rename {} -> {}: target exists",
-                         old_path.file_name().unwrap_or_default().to_string_lossy(),
-                         new_path.file_name().unwrap_or_default().to_string_lossy());
-                continue;
-            }
-            match fs::rename(&old_path, &new_path) {
-                Ok(()) => {
-                    println!("  {} -> {}",
-                             old_path.file_name().unwrap_or_default().to_string_lossy(),
-                             new_path.file_name().unwrap_or_default().to_string_lossy());
-                    // Track CUE files so we can update the FILE reference inside
-                    if *ext == ".cue" || *ext == ".guess.cue" {
-                        renamed_cue = Some(new_path.clone());
-                        new_wav_filename = format!("{}.wav", new_stem);
-                    }
-                }
-                Err(e) => {
-                    eprintln!("  Warning: Failed to rename {}: {}",
-                             old_path.file_name().unwrap_or_default().to_string_lossy(), e);
-                }
+    println!("Boundary Review:");
+    println!("----------------");
+    println!("  Playing {:.1}s around each boundary.", 2.0 * REVIEW_CONTEXT_SECONDS);
+    println!("  Enter: keep, r: play again, d: drop boundary, q: keep all remaining");
+    
+    let mut output: Option<PipeWireOutputStream> = None;
+    let stdin = io::stdin();
+    let mut i = 0;
+    'boundaries: while i < valleys.len() {
+        let valley = &valleys[i];
+        let position = valley.position_seconds;
+        println!("  Boundary {}/{} at {} (prominence {:.1} dB)",
+                 i + 1, valleys.len(), format_timestamp(position), valley.prominence_db);
+        loop {
+            if let Err(e) = play_around(wav_file, position, &mut output) {
+                eprintln!("  Playback failed: {}", e);
+                break 'boundaries;
             }
-        }
-    }
-
-    // Update FILE reference inside the CUE file
-    if let Some(cue_path) = renamed_cue {
-        if let Ok(content) = fs::read_to_string(&cue_path) {
-            let updated = content.lines().map(|line| {
-                if line.starts_with("FILE \"") && line.ends_with("\" WAVE") {
-                    format!("FILE \"{}\" WAVE", new_wav_filename)
-                } else {
-                    line.to_string()
-                }
-            }).collect::<Vec<_>>().join("\n");
-            if let Err(e) = fs::write(&cue_path, &updated) {
-                eprintln!("  Warning: Failed to update CUE file content: {}", e);
+            print!("  [Enter/r/d/q] ");
+            let _ = io::stdout().flush();
+            let mut line = String::new();
+            if stdin.read_line(&mut line).unwrap_or(0) == 0 {
+                break 'boundaries;
             }
-        }
-    }
-}
-
-/// Detect the groove-in point (where music starts).
-/// Scans from the start for a sustained rise above the midpoint between
-/// noise floor and music level.
-fn detect_groove_in(
-    smoothed: &[f32],
-    timestamps: &[f64],
-    noise_floor_db: f32,
-    music_level_db: f32,
-    chunk_duration: f64,
-    verbose: bool,
-) -> f64 {
-    if smoothed.is_empty() {
-        return 0.0;
-    }
-    
-    let threshold = (noise_floor_db + music_level_db) / 2.0;
-    let sustain_chunks = (2.0 / chunk_duration).max(1.0) as usize;
-    
-    for i in 0..smoothed.len().saturating_sub(sustain_chunks) {
-        if smoothed[i] > threshold {
-            let sustained = smoothed[i..i + sustain_chunks].iter().all(|&v| v > threshold);
-            if sustained {
-                // Walk back to find where the rise started
-                let mut start = i;
-                while start > 0 && smoothed[start - 1] < smoothed[start] {
-                    start -= 1;
-                }
-                let groove_in = timestamps[start];
-                if verbose {
-                    println!("  Groove-in detected at {} (threshold: {:.1} dB)",
-                             format_timestamp(groove_in), threshold);
+            match line.trim() {
+                "r" => continue,
+                "d" => {
+                    println!("  Dropped boundary at {}", format_timestamp(position));
+                    valleys.remove(i);
+                    continue 'boundaries;
                 }
-                return groove_in;
+                "q" => break 'boundaries,
+                _ => break,
             }
         }
+        i += 1;
     }
     
-    if verbose {
-        println!("  No groove-in detected, using file start");
+    if let Some(mut stream) = output {
+        stream.stop();
     }
-    0.0
 }
 
-/// Detect the groove-out point (where music ends).
-/// Scans from the end backwards for the last sustained music region,
-/// then finds where the final drop occurs.
-fn detect_groove_out(
-    smoothed: &[f32],
-    timestamps: &[f64],
-    noise_floor_db: f32,
-    music_level_db: f32,
-    file_duration: f64,
-    chunk_duration: f64,
-    verbose: bool,
-) -> f64 {
-    if smoothed.is_empty() {
-        return file_duration;
+/// Warn when the side was clearly played at the wrong speed.
+///
+/// Each detected song is compared with its length on the release; if the
+/// song count differs, only the whole side can be compared. With
+/// `fix_speed` a resampled copy that plays at the right speed is written
+/// next to the recording.
+fn check_playback_speed(wav_file: &str, valleys: &[cuefile::Valley], groove: (f64, f64),
+                        tracks: &[musicbrainz::ExpectedTrack], fix_speed: bool) {
+    let boundaries: Vec<f64> = valleys.iter().map(|v| v.position_seconds).collect();
+    let songs = album_identifier::track_regions(groove.0, &boundaries, groove.1);
+    let (measured, expected): (Vec<f64>, Vec<f64>) = if songs.len() == tracks.len() {
+        songs.iter().map(|(start, end)| end - start).zip(tracks.iter().map(|t| t.length_seconds)).unzip()
+    } else {
+        (vec![groove.1 - groove.0], vec![tracks.iter().map(|t| t.length_seconds).sum()])
+    };
+    let Some(check) = speed_check::check_durations(&measured, &expected) else { return };
+    if !check.speed.is_wrong() {
+        return;
     }
-    
-    let threshold = (noise_floor_db + music_level_db) / 2.0;
-    let sustain_chunks = (5.0 / chunk_duration) as usize;
-    let len = smoothed.len();
-    
-    // Scan from end backwards to find the last region with sustained music
-    for i in (sustain_chunks..len).rev() {
-        let window_start = i.saturating_sub(sustain_chunks);
-        let above_count = smoothed[window_start..=i].iter().filter(|&&v| v > threshold).count();
-        
-        if above_count > sustain_chunks / 2 {
-            // Found last music region. Walk forward to find the drop-off.
-            for j in i..len {
-                if smoothed[j] < threshold {
-                    // Check that it stays below for at least 10s
-                    let check_end = (j + (10.0 / chunk_duration) as usize).min(len);
-                    let stays_below = smoothed[j..check_end].iter().all(|&v| v < threshold);
-                    if stays_below {
-                        let groove_out = timestamps[j];
-                        if verbose {
-                            println!("  Groove-out detected at {} (threshold: {:.1} dB, {:.1}s before end)",
-                                     format_timestamp(groove_out), threshold, file_duration - groove_out);
-                        }
-                        return groove_out;
-                    }
-                }
-            }
-            break;
-        }
+    if check.compared > 1 {
+        println!("Warning: Songs are {:.0}% of their release length (median of {}) - {}",
+                 check.ratio * 100.0, check.compared, check.speed.describe());
+    } else {
+        println!("Warning: Music is {:.0}% of the release length - {}",
+                 check.ratio * 100.0, check.speed.describe());
     }
-    
-    if verbose {
-        println!("  No groove-out detected, using file end");
+    if !fix_speed {
+        println!("  Use --fix-speed to write a speed-corrected copy");
+        return;
+    }
+    let output = speed_check::corrected_path(wav_file);
+    let output = output.to_string_lossy();
+    match speed_check::correct_wav_speed(wav_file, &output, check.speed) {
+        Ok(()) => println!("  Speed-corrected copy written: {} (run cue_creator on it)", output),
+        Err(e) => eprintln!("  Speed correction failed: {}", e),
     }
-    file_duration
 }
 
-/// Find song boundaries within the music region.
+/// Speed drift of the deck from the boundaries of a guided detection.
 ///
-/// Algorithm:
-///   1. Short smoothing (3s) for precise boundary location
-///   2. Long smoothing (30s) for local reference level
-///   3. Find local minima in the short-smoothed curve
-///   4. Measure prominence = depth below long-smoothed reference
-///   5. Measure left/right context levels (15s on each side)
-///   6. Require music-level audio on BOTH sides of the valley
-///   7. Score by minimum of left-dip and right-dip, scaled by prominence
-fn find_song_boundaries(
-    rms_values: &[f32],
-    timestamps: &[f64],
-    smoothed_short: &[f32],
-    music_start_idx: usize,
-    music_end_idx: usize,
-    min_prominence_db: f32,
-    min_song_duration_seconds: f64,
-    chunk_duration: f64,
-    noise_floor_db: f32,
-    _music_level_db: f32,
-    verbose: bool,
-) -> Vec<Valley> {
-    let len = music_end_idx.min(rms_values.len());
-    if len <= music_start_idx + 10 {
-        return Vec::new();
-    }
-    
-    // Long smoothing for reference level (30 seconds)
-    let long_window = (30.0 / chunk_duration) as usize;
-    let long_smoothed = audio_analysis::smooth_rms(rms_values, long_window.max(3));
-    
-    // Context window: 15 seconds on each side
-    let context_chunks = (15.0 / chunk_duration) as usize;
-    
-    let mut valleys = Vec::new();
-    
-    // Search radius: 5 seconds for local minimum detection
-    let search_radius = (5.0 / chunk_duration) as usize;
-    
-    for i in (music_start_idx + search_radius)..(len.saturating_sub(search_radius)) {
-        let current = smoothed_short[i];
-        
-        // Check if this is a local minimum
-        let range_start = i.saturating_sub(search_radius);
-        let range_end = (i + search_radius).min(len - 1);
-        let mut is_minimum = true;
-        for j in range_start..=range_end {
-            if j != i && smoothed_short[j] < current {
-                is_minimum = false;
-                break;
-            }
-        }
-        if !is_minimum {
-            continue;
-        }
-        
-        // Prominence against long-term reference
-        let local_ref = long_smoothed[i];
-        let prominence = local_ref - current;
-        if prominence < min_prominence_db {
-            continue;
-        }
-        
-        // Measure left context (audio before the valley)
-        let left_start = if i > context_chunks + search_radius {
-            i - context_chunks - search_radius
-        } else {
-            music_start_idx
-        };
-        let left_end = i.saturating_sub(search_radius / 2);
-        let left_level = if left_end > left_start {
-            smoothed_short[left_start..left_end].iter().sum::<f32>() / (left_end - left_start) as f32
-        } else {
-            local_ref
-        };
-        
-        // Measure right context (audio after the valley)
-        let right_start = (i + search_radius / 2).min(len - 1);
-        let right_end = (i + context_chunks + search_radius).min(len);
-        let right_level = if right_end > right_start {
-            smoothed_short[right_start..right_end].iter().sum::<f32>() / (right_end - right_start) as f32
-        } else {
-            local_ref
-        };
-        
-        // Dip from both sides
-        let left_dip = left_level - current;
-        let right_dip = right_level - current;
-        let min_dip = left_dip.min(right_dip);
-        
-        // Reject if one side is also quiet (within a quiet passage, not between songs)
-        if min_dip < min_prominence_db * 0.5 {
+/// Each boundary still in `valleys` after the review is compared with the
+/// start of the track it was aligned to, and the end of the music with the
+/// length of the side.
+fn estimate_speed_drift(aligned: &[(usize, cuefile::Valley)], valleys: &[cuefile::Valley],
+                        tracks: &[musicbrainz::ExpectedTrack],
+                        groove_in: f64, music_duration: f64) -> Option<speed_check::SpeedDrift> {
+    let side_start = tracks.first()?.expected_start;
+    let mut measured = Vec::new();
+    let mut expected = Vec::new();
+    for (track, valley) in aligned {
+        if !valleys.iter().any(|v| v.position_seconds == valley.position_seconds) {
             continue;
         }
-        
-        // Valley width
-        let half_prom_threshold = current + prominence / 2.0;
-        let mut w_start = i;
-        let mut w_end = i;
-        while w_start > music_start_idx && smoothed_short[w_start - 1] < half_prom_threshold {
-            w_start -= 1;
+        measured.push(valley.position_seconds - groove_in);
+        expected.push(tracks.get(*track)?.expected_start - side_start);
+    }
+    measured.push(music_duration);
+    expected.push(tracks.iter().map(|t| t.length_seconds).sum());
+    speed_check::estimate_drift(&measured, &expected)
+}
+
+/// Path of a copy of the recording that plays at the right speed, written
+/// unless it exists already; None if the correction fails
+fn speed_corrected_copy(wav_file: &str, speed: PlaybackSpeed) -> Option<String> {
+    if speed_check::is_speed_corrected(Path::new(wav_file)) {
+        return Some(wav_file.to_string());
+    }
+    let output = speed_check::corrected_path(wav_file).to_string_lossy().to_string();
+    if Path::new(&output).exists() {
+        println!("Using speed-corrected copy {}", output);
+        return Some(output);
+    }
+    println!("Correcting {} ({})...", wav_file, speed.describe());
+    match speed_check::correct_wav_speed(wav_file, &output, speed) {
+        Ok(()) => {
+            println!("  Speed-corrected copy written: {}", output);
+            Some(output)
         }
-        while w_end < len - 1 && smoothed_short[w_end + 1] < half_prom_threshold {
-            w_end += 1;
+        Err(e) => {
+            eprintln!("Error: Speed correction of '{}' failed: {}", wav_file, e);
+            None
         }
-        let width = (w_end - w_start) as f64 * chunk_duration;
-        
-        // Score: emphasise the minimum dip (both sides must have music)
-        let score = (min_dip as f64) * (1.0 + prominence as f64 * 0.1) * (1.0 + width.sqrt());
-        
-        valleys.push(Valley {
-            position_seconds: timestamps[i],
-            depth_db: current,
-            prominence_db: prominence,
-            left_level_db: left_level,
-            right_level_db: right_level,
-            width_seconds: width,
-            score,
-        });
     }
-    
-    // Remove valleys too close together (min song duration), keep highest score
-    let mut filtered = Vec::new();
-    valleys.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
-    
-    for valley in &valleys {
-        let too_close = filtered.iter().any(|existing: &Valley| {
-            (existing.position_seconds - valley.position_seconds).abs() < min_song_duration_seconds
-        });
-        if !too_close {
-            filtered.push(valley.clone());
+}
+
+/// Warn about earlier sessions that recorded the release; true if there are any
+fn already_digitized(library_dir: Option<&Path>, wav_file: &str, release: &ReleaseTags) -> bool {
+    let earlier = duplicates::find_digitizations(library_dir, wav_file, release);
+    for digitization in &earlier {
+        match digitization.recorded {
+            Some(date) => println!("Warning: You already recorded this album on {} ({})",
+                                   date.format("%Y-%m-%d"), digitization.dir.display()),
+            None => println!("Warning: You already recorded this album ({})", digitization.dir.display()),
         }
     }
-    
-    filtered.sort_by(|a, b| a.position_seconds.partial_cmp(&b.position_seconds).unwrap());
-    
-    if verbose && !filtered.is_empty() {
-        println!("  Valley candidates before score filtering:");
-        for v in &filtered {
-            println!("    {} depth={:.1}dB prom={:.1}dB L={:.1}dB R={:.1}dB w={:.1}s score={:.1}",
-                     format_timestamp(v.position_seconds),
-                     v.depth_db, v.prominence_db,
-                     v.left_level_db, v.right_level_db,
-                     v.width_seconds, v.score);
-        }
+    if !earlier.is_empty() {
+        println!("Skipping {}; use --allow-duplicate to process it anyway", wav_file);
     }
-    
-    // Adaptive score threshold: find the largest gap in the sorted scores.
-    // Real song boundaries cluster at high scores, false positives at low scores.
-    // The gap between these clusters is the natural threshold.
-    if filtered.len() > 1 {
-        let mut scores: Vec<f64> = filtered.iter().map(|v| v.score).collect();
-        scores.sort_by(|a, b| a.partial_cmp(b).unwrap());
-        
-        // Find the largest relative gap between consecutive sorted scores
-        let mut best_gap_ratio = 0.0_f64;
-        let mut best_gap_idx = 0;
-        
-        for i in 0..scores.len() - 1 {
-            let lower = scores[i];
-            let upper = scores[i + 1];
-            // Use ratio: a gap from 30 to 75 (2.5x) is more significant than 200 to 300 (1.5x)
-            if lower > 0.0 {
-                let ratio = upper / lower;
-                if ratio > best_gap_ratio {
-                    best_gap_ratio = ratio;
-                    best_gap_idx = i;
-                }
-            }
+    !earlier.is_empty()
+}
+
+/// Update the session manifest (session.toml) next to a WAV file
+fn update_session(wav_file: &str, update: impl FnOnce(&mut Session)) {
+    let path = session::session_path(wav_file);
+    let mut session = match Session::load_for(wav_file) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("Warning: Cannot read session file {}: {}", path.display(), e);
+            return;
         }
-        
-        // Only apply gap filtering if the gap is significant (> 1.5x difference)
-        if best_gap_ratio > 1.5 {
-            let threshold = scores[best_gap_idx];
-            if verbose {
-                println!("  Score gap: {:.1} → {:.1} (ratio {:.1}x), threshold={:.1}",
-                         scores[best_gap_idx], scores[best_gap_idx + 1],
-                         best_gap_ratio, threshold);
-            }
-            filtered.retain(|v| v.score > threshold);
-        } else if verbose {
-            println!("  No significant score gap found (max ratio: {:.1}x)", best_gap_ratio);
+    };
+    update(&mut session);
+    if let Err(e) = session.save(&path) {
+        eprintln!("Warning: Failed to write session file {}: {}", path.display(), e);
+    }
+}
+
+/// Release and side known before identification: from the `.meta.toml` of
+/// the file, or else from the session manifest (unless `reidentify`)
+fn known_release(wav_file: &str, reidentify: bool) -> Option<FileSideResult> {
+    match RecordingMetadata::load_for(wav_file) {
+        Some(Ok(metadata)) => return Some(metadata.side_result(wav_file)),
+        Some(Err(e)) => eprintln!("Warning: Ignoring metadata file: {}", e),
+        None => {}
+    }
+    if reidentify {
+        return None;
+    }
+    Session::load_for(wav_file).ok().and_then(|s| s.side_result(wav_file))
+}
+
+/// Songs stored in the session manifest by an earlier run
+fn session_songs(wav_file: &str) -> Option<Vec<album_identifier::IdentifiedSong>> {
+    Session::load_for(wav_file).ok()?.songs(wav_file).map(|songs| songs.to_vec())
+}
+
+/// Rename a WAV file and all its associated files (.cue, .identify.txt, etc.)
+/// after the identified release, using the rename template.
+/// Without a {side} or {side_letter} placeholder in the template the side
+/// number of the original filename (e.g. .1, .2) is kept.
+/// Nothing is renamed if one of the new names is taken.
+/// Returns the new path of the WAV file if it was renamed.
+fn rename_recording(wav_file: &str, artist: &str, album_title: &str, side_label: Option<char>,
+                    year: Option<u32>, template: &FilenameTemplate) -> Option<String> {
+    let base = cuefile::wav_base_path(wav_file);
+    let base_filename = base.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    let file_side = filename_template::side_number(base_filename);
+
+    let values = TemplateValues {
+        artist: Some(artist.to_string()),
+        album: Some(album_title.to_string()),
+        album_artist: Some(artist.to_string()),
+        side: side_label.map(|c| c as u32 - 'A' as u32 + 1).or(file_side),
+        year,
+        date: fs::metadata(wav_file)
+            .and_then(|m| m.modified())
+            .ok()
+            .map(|t| chrono::DateTime::<chrono::Local>::from(t).naive_local()),
+        ..Default::default()
+    };
+    let mut new_name = template.render(&values);
+    if let (false, Some(side)) = (template.has_side(), file_side) {
+        new_name.push_str(&format!(".{}", side));
+    }
+
+    let dir = Path::new(wav_file).parent().unwrap_or(Path::new(""));
+    let plan = match RenamePlan::new(wav_file, &dir.join(&new_name)) {
+        Ok(plan) => plan,
+        Err(e) => {
+            eprintln!("Warning: Not renaming {}: {}", wav_file, e);
+            return None;
         }
-        
-        // Key insight for vinyl: real song boundaries drop WELL BELOW the noise
-        // floor. During a true inter-song gap, the stylus is in an unmodulated
-        // groove, producing a signal significantly quieter than the estimated
-        // noise floor (which is biased upward by including some musical bleed).
-        // Empirically, real boundaries are 7-16 dB below noise floor, while
-        // false positives (quiet passages within songs) are at or barely below it.
-        // Requiring 5 dB below noise floor cleanly separates them.
-        let depth_threshold = noise_floor_db - 5.0;
-        let before_depth = filtered.len();
-        filtered.retain(|v| v.depth_db <= depth_threshold);
-        if verbose {
-            println!("  Depth filter: valleys must reach {:.1} dB (noise floor {:.1} dB minus 5 dB margin)",
-                     depth_threshold, noise_floor_db);
-            if filtered.len() < before_depth {
-                println!("    Removed {} valleys that didn't reach deep enough below noise floor",
-                         before_depth - filtered.len());
+    };
+    if plan.is_noop() {
+        println!("File already named correctly: {}", wav_file);
+        return None;
+    }
+
+    println!();
+    println!("Renaming: {} -> {}", base_filename, new_name);
+    match plan.execute() {
+        Ok(()) => {
+            for (from, to) in &plan.moves {
+                println!("  {} -> {}",
+                         from.file_name().unwrap_or_default().to_string_lossy(),
+                         to.strip_prefix(dir).unwrap_or(to).display());
             }
+            catalog::update(|catalog| {
+                for (from, to) in &plan.moves {
+                    catalog.move_file(&from.to_string_lossy(), &to.to_string_lossy())?;
+                }
+                Ok(())
+            });
+            Some(plan.new_wav.to_string_lossy().to_string())
         }
-    }
-    
-    if verbose && !filtered.is_empty() {
-        println!("  Final boundaries:");
-        for v in &filtered {
-            println!("    {} depth={:.1}dB prom={:.1}dB score={:.1}",
-                     format_timestamp(v.position_seconds),
-                     v.depth_db, v.prominence_db, v.score);
+        Err(e) => {
+            eprintln!("Warning: Rename failed, files left unchanged: {}", e);
+            None
         }
-        println!();
     }
-    
-    filtered
 }
 
 /// Determine the dominant (most frequent) artist from a set of identified songs.
@@ -476,13 +336,13 @@ fn dominant_artist(songs: &[album_identifier::IdentifiedSong]) -> String {
     if songs.is_empty() {
         return "Unknown".to_string();
     }
-    let mut counts: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
+    let mut counts: std::collections::HashMap<String, f64> = std::collections::HashMap::new();
     for song in songs {
-        *counts.entry(song.artist.to_lowercase()).or_default() += 1;
+        *counts.entry(song.artist.to_lowercase()).or_default() += song.weight();
     }
-    // Find the artist with the most songs, return the original-case version
+    // Find the artist with the most (confidently identified) songs, return the original-case version
     let best_lower = counts.into_iter()
-        .max_by_key(|(_, c)| *c)
+        .max_by(|a, b| a.1.total_cmp(&b.1))
         .map(|(a, _)| a)
         .unwrap_or_default();
     // Return original-case from first matching song
@@ -503,7 +363,7 @@ fn collect_wav_files(directory: &str, recursive: bool) -> Vec<PathBuf> {
                     let path = entry.path();
                     if path.is_dir() {
                         visit_dirs(&path, wav_files);
-                    } else if path.extension().and_then(|s| s.to_str()) == Some("wav") {
+                    } else if watch::is_recording(&path) {
                         wav_files.push(path);
                     }
                 }
@@ -515,7 +375,7 @@ fn collect_wav_files(directory: &str, recursive: bool) -> Vec<PathBuf> {
         if let Ok(entries) = fs::read_dir(directory) {
             for entry in entries.flatten() {
                 let path = entry.path();
-                if path.extension().and_then(|s| s.to_str()) == Some("wav") {
+                if watch::is_recording(&path) {
                     wav_files.push(path);
                 }
             }
@@ -526,100 +386,134 @@ fn collect_wav_files(directory: &str, recursive: bool) -> Vec<PathBuf> {
     wav_files
 }
 
-/// Guided boundary detection using expected track positions from MusicBrainz.
-/// Searches for valleys within a window around each expected boundary.
-fn find_guided_boundaries(
-    smoothed: &[f32],
-    timestamps: &[f64],
-    expected_tracks: &[musicbrainz::ExpectedTrack],
-    music_start: f64,
-    search_window_seconds: f64,
-    verbose: bool,
-) -> Vec<Valley> {
-    if expected_tracks.len() < 2 {
-        return Vec::new();
-    }
-    
-    let mut boundaries = Vec::new();
-    
-    // For each expected boundary between tracks
-    for i in 1..expected_tracks.len() {
-        let expected_pos = music_start + expected_tracks[i].expected_start;
-        let window_start = expected_pos - search_window_seconds;
-        let window_end = expected_pos + search_window_seconds;
-        
-        // Find the minimum RMS within the search window
-        let mut min_rms = f32::MAX;
-        let mut min_pos = expected_pos;
-        let mut min_idx = 0;
-        
-        for (j, &ts) in timestamps.iter().enumerate() {
-            if ts >= window_start && ts <= window_end && j < smoothed.len() {
-                if smoothed[j] < min_rms {
-                    min_rms = smoothed[j];
-                    min_pos = ts;
-                    min_idx = j;
-                }
-            }
-        }
-        
-        if min_rms < f32::MAX {
-            // Calculate prominence from surrounding context
-            let context_window = 75; // ~15 seconds at 200ms chunks
-            let left_start = min_idx.saturating_sub(context_window);
-            let left_end = min_idx;
-            let right_start = min_idx + 1;
-            let right_end = (min_idx + context_window).min(smoothed.len());
-            
-            let left_avg = if left_end > left_start {
-                smoothed[left_start..left_end].iter().sum::<f32>() / (left_end - left_start) as f32
-            } else {
-                min_rms
-            };
-            
-            let right_avg = if right_end > right_start {
-                smoothed[right_start..right_end].iter().sum::<f32>() / (right_end - right_start) as f32
-            } else {
-                min_rms
-            };
-            
-            let prominence = (left_avg.max(right_avg) - min_rms).max(0.0);
-            
-            if verbose {
-                println!("  Track {} boundary: expected={:.1}s, found={:.1}s (offset={:.1}s), depth={:.1}dB, prom={:.1}dB",
-                         i + 1, expected_tracks[i].expected_start, min_pos - music_start,
-                         min_pos - expected_pos, min_rms, prominence);
-            }
-            
-            boundaries.push(Valley {
-                position_seconds: min_pos,
-                depth_db: min_rms,
-                prominence_db: prominence,
-                width_seconds: 0.0,
-                left_level_db: left_avg,
-                right_level_db: right_avg,
-                score: (prominence * 10.0) as f64,
-            });
-        }
+/// Identify the songs of a file with Shazam or, if requested, AcoustID.
+/// With track `regions` (--adaptive-identify), Shazam samples each region
+/// instead of fixed timestamps.
+fn identify_songs(
+    wav_file: &str,
+    use_acoustid: bool,
+    regions: Option<&[(f64, f64)]>,
+    progress: &mut dyn ProgressSink,
+) -> (Result<Vec<album_identifier::IdentifiedSong>, String>, String) {
+    if use_acoustid {
+        acoustid::identify_songs(wav_file, None, progress)
+    } else if let Some(regions) = regions {
+        album_identifier::identify_songs_in_regions(wav_file, regions, progress)
+    } else {
+        album_identifier::identify_songs(wav_file, None, progress)
     }
-    
-    boundaries
+}
+
+/// Track regions between preliminary boundaries, sampled by --adaptive-identify
+fn preliminary_regions(analysis: &BoundaryAnalysis, min_prominence_db: f32, min_song_duration: f64) -> Vec<(f64, f64)> {
+    let valleys = boundary::find_song_boundaries(analysis, min_prominence_db, min_song_duration, false);
+    let boundaries: Vec<f64> = valleys.iter().map(|v| v.position_seconds).collect();
+    album_identifier::track_regions(analysis.groove_in, &boundaries, analysis.groove_out)
 }
 
 fn main() {
+    autorec::logging::init_cli();
     let args: Vec<String> = env::args().collect();
     
     let verbose = args.iter().any(|a| a == "--verbose" || a == "-v");
     let dump = args.iter().any(|a| a == "--dump");
     let no_lookup = args.iter().any(|a| a == "--no-lookup");
     let no_shazam = args.iter().any(|a| a == "--no-shazam") || no_lookup;
+    let full_config = Config::load()
+        .unwrap_or_else(|e| {
+            eprintln!("Warning: Ignoring config file: {}", e);
+            Config::default()
+        });
+    let config = full_config.cue_creator.clone().unwrap_or_default();
+    let library_dir = full_config.organize.as_ref().and_then(|o| o.library_dir.as_ref()).map(PathBuf::from);
+    let use_acoustid = args.iter().any(|a| a == "--use-acoustid") || config.use_acoustid == Some(true);
     let no_musicbrainz = args.iter().any(|a| a == "--no-musicbrainz") || no_lookup;
     let no_discogs = args.iter().any(|a| a == "--no-discogs") || no_lookup;
     let identify_only = args.iter().any(|a| a == "--identify-only");
     let no_cue = args.iter().any(|a| a == "--no-cue") || identify_only;
-    let no_rename = args.iter().any(|a| a == "--no-rename") || identify_only;
-    let rename = !no_rename;
+    let rename = !identify_only
+        && (args.iter().any(|a| a == "--rename")
+            || (!args.iter().any(|a| a == "--no-rename") && config.rename != Some(false)));
+    let spectrogram = !identify_only
+        && (args.iter().any(|a| a == "--spectrogram") || config.spectrogram == Some(true));
+    let write_report = !identify_only
+        && (args.iter().any(|a| a == "--report") || config.report == Some(true));
+    let wav_markers = !no_cue
+        && (args.iter().any(|a| a == "--wav-markers")
+            || (!args.iter().any(|a| a == "--no-wav-markers") && config.wav_markers != Some(false)));
+    let rename_template = match FilenameTemplate::parse(
+        args.iter()
+            .position(|a| a == "--rename-template")
+            .and_then(|i| args.get(i + 1))
+            .or(config.rename_template.as_ref())
+            .map_or(DEFAULT_RENAME_TEMPLATE, |t| t.as_str()),
+    ) {
+        Ok(template) => template,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+    };
+    let cue_encoding_name = args.iter()
+        .position(|a| a == "--cue-encoding")
+        .and_then(|i| args.get(i + 1))
+        .or(config.cue_encoding.as_ref());
+    let cue_text = CueText {
+        encoding: match cue_encoding_name {
+            Some(name) => CueEncoding::from_str(name).unwrap_or_else(|| {
+                eprintln!("Error: Unknown CUE encoding '{}' (use utf8 or latin1)", name);
+                process::exit(1);
+            }),
+            None => CueEncoding::Utf8,
+        },
+        transliterate: args.iter().any(|a| a == "--cue-transliterate") || config.cue_transliterate == Some(true),
+    };
     let recursive = args.iter().any(|a| a == "--recursive" || a == "-r");
+    let seed_from_matches = args.iter().any(|a| a == "--seed-from-matches");
+    let use_mmap = args.iter().any(|a| a == "--mmap");
+    let reidentify = args.iter().any(|a| a == "--reidentify");
+    let check_duplicates = !args.iter().any(|a| a == "--allow-duplicate") && config.check_duplicates != Some(false);
+    let review = args.iter().any(|a| a == "--review");
+    let fix_speed = args.iter().any(|a| a == "--fix-speed");
+    let adaptive_identify = args.iter().any(|a| a == "--adaptive-identify");
+    
+    // Release named on the command line: no identification or album search
+    let release = args.iter()
+        .position(|a| a == "--release")
+        .and_then(|i| args.get(i + 1))
+        .map(|s| ReleaseReference::parse(s).unwrap_or_else(|| {
+            eprintln!("Error: '{}' is not a Discogs or MusicBrainz release URL or ID", s);
+            process::exit(1);
+        }));
+    
+    // Track lengths typed in by the user: no identification or lookup either
+    let tracklist_path = args.iter()
+        .position(|a| a == "--tracklist")
+        .and_then(|i| args.get(i + 1));
+    let tracklist = tracklist_path.map(|path| match Tracklist::load(Path::new(path)) {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("Error: Cannot read track list: {}", e);
+            process::exit(1);
+        }
+    });
+    if tracklist.is_some() && release.is_some() {
+        eprintln!("Error: --tracklist and --release cannot be combined");
+        process::exit(1);
+    }
+    
+    let progress_mode = args.iter()
+        .position(|a| a == "--progress")
+        .and_then(|i| args.get(i + 1))
+        .map(|s| s.as_str())
+        .unwrap_or("none");
+    let mut progress = match progress::create(progress_mode) {
+        Some(p) => p,
+        None => {
+            eprintln!("Error: Unknown progress mode '{}' (use {})", progress_mode, progress::PROGRESS_NAMES.join(", "));
+            process::exit(1);
+        }
+    };
     
     let directory = args.iter()
         .position(|a| a == "--directory" || a == "-d")
@@ -630,36 +524,151 @@ fn main() {
         .position(|a| a == "--min-prominence")
         .and_then(|i| args.get(i + 1))
         .and_then(|v| v.parse::<f32>().ok())
+        .or(config.min_prominence)
         .unwrap_or(3.0);
     
+    let medium_name = args.iter()
+        .position(|a| a == "--medium")
+        .and_then(|i| args.get(i + 1))
+        .or(config.medium.as_ref());
+    let medium = match medium_name {
+        Some(name) => Medium::from_str(name).unwrap_or_else(|| {
+            eprintln!("Error: Unknown medium '{}' (use {})", name, MEDIUM_NAMES.join(", "));
+            process::exit(1);
+        }),
+        None => Medium::Vinyl,
+    };
+    
+    // Speed the record was captured at, if it differs from the medium's own
+    let played_at = args.iter()
+        .position(|a| a == "--played-at")
+        .and_then(|i| args.get(i + 1))
+        .map(|v| {
+            let Some(nominal) = medium.nominal_rpm() else {
+                eprintln!("Error: --played-at needs --medium 78 (use --fix-speed for 33 1/3 and 45 RPM records)");
+                process::exit(1);
+            };
+            match v.parse::<f64>().map(|rpm| PlaybackSpeed::from_rpm(nominal, rpm)) {
+                Ok(PlaybackSpeed::Unknown) | Err(_) => {
+                    eprintln!("Error: Cannot correct a {} RPM record played at '{}' RPM", nominal, v);
+                    process::exit(1);
+                }
+                Ok(speed) => speed,
+            }
+        })
+        .filter(|speed| speed.is_wrong());
+    
     let min_song_duration = args.iter()
         .position(|a| a == "--min-song")
         .and_then(|i| args.get(i + 1))
         .and_then(|v| v.parse::<f64>().ok())
-        .unwrap_or(30.0);
+        .or(config.min_song)
+        .unwrap_or(medium.default_min_song_seconds());
     
     let smooth_window_secs = args.iter()
         .position(|a| a == "--smooth-window")
         .and_then(|i| args.get(i + 1))
         .and_then(|v| v.parse::<f64>().ok())
+        .or(config.smooth_window)
         .unwrap_or(3.0);
     
     let chunk_ms = args.iter()
         .position(|a| a == "--chunk-ms")
         .and_then(|i| args.get(i + 1))
         .and_then(|v| v.parse::<u32>().ok())
+        .or(config.chunk_ms)
         .unwrap_or(200);
     
-    let option_flags = ["--min-prominence", "--min-song", "--smooth-window", "--chunk-ms", "--directory", "-d"];
+    // Channels of a multichannel recording to analyze (0-based), all if empty
+    let analysis_channels = args.iter()
+        .position(|a| a == "--channels")
+        .and_then(|i| args.get(i + 1))
+        .map(|v| audio_analysis::parse_channel_list(v).unwrap_or_else(|| {
+            eprintln!("Error: Invalid channel list '{}' (use e.g. 3,4)", v);
+            process::exit(1);
+        }))
+        .unwrap_or_default();
+    
+    // Album lookup backends, in the configured order
+    let mut backend_names: Vec<String> = config.lookup_backends.clone()
+        .unwrap_or_else(|| lookup::BACKEND_NAMES.iter().map(|n| n.to_string()).collect());
+    backend_names.retain(|n| {
+        let n = n.to_lowercase();
+        !(no_discogs && n == "discogs" || no_musicbrainz && n.starts_with("musicbrainz"))
+    });
+    let backends = match lookup::backends_by_name(&backend_names) {
+        Ok(b) => b,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+    };
+    let backends: Vec<&dyn AlbumIdentifier> = backends.iter().map(|b| b.as_ref()).collect();
+    
+    let watch_dir = args.iter()
+        .position(|a| a == "--watch")
+        .and_then(|i| args.get(i + 1))
+        .map(|s| s.as_str());
+    
+    if let Some(dir) = watch_dir {
+        if tracklist.is_some() {
+            eprintln!("Error: --tracklist describes one recording and cannot be used with --watch");
+            process::exit(1);
+        }
+        let mut watcher = match WatchFolder::new(Path::new(dir), Duration::from_secs(watch::DEFAULT_SETTLE_SECONDS)) {
+            Ok(w) => w,
+            Err(e) => {
+                eprintln!("Error: Cannot watch {}: {}", dir, e);
+                process::exit(1);
+            }
+        };
+        println!("Watching {} for new recordings (Ctrl+C to stop)", dir);
+        loop {
+            let ready = match watcher.poll() {
+                Ok(files) => files,
+                Err(e) => {
+                    eprintln!("Error: Watching {} failed: {}", dir, e);
+                    process::exit(1);
+                }
+            };
+            for path in ready {
+                let Some(wav_file) = path.to_str() else { continue };
+                if !no_cue && cuefile::has_cue_file(wav_file) {
+                    continue;
+                }
+                if is_file_in_use(wav_file) {
+                    watcher.requeue(path.clone());
+                    continue;
+                }
+                let corrected = match played_at.map(|speed| speed_corrected_copy(wav_file, speed)) {
+                    Some(None) => continue,
+                    corrected => corrected.flatten(),
+                };
+                let wav_file = corrected.as_deref().unwrap_or(wav_file);
+                println!();
+                println!("{}", "=".repeat(60));
+                let override_result = if release.is_some() { None } else { known_release(wav_file, reidentify) };
+                process_file(wav_file, verbose, dump, spectrogram, write_report, min_prominence, min_song_duration,
+                             smooth_window_secs, chunk_ms, medium, no_shazam, use_acoustid, no_musicbrainz, &backends,
+                             no_cue, wav_markers, cue_text, rename.then_some(&rename_template), identify_only, seed_from_matches, use_mmap, &analysis_channels, reidentify, false, fix_speed, override_result.as_ref(),
+                             release.as_ref(), adaptive_identify, check_duplicates, library_dir.as_deref(), None, progress.as_mut());
+            }
+            std::thread::sleep(WATCH_POLL_INTERVAL);
+        }
+    }
+    
+    let option_flags = ["--min-prominence", "--min-song", "--smooth-window", "--chunk-ms", "--channels", "--medium", "--played-at", "--directory", "-d", "--progress", "--release", "--tracklist", "--cue-encoding", "--rename-template"];
     
     // Collect file arguments or process directory
     let mut wav_files_owned: Vec<PathBuf> = Vec::new();
     let mut is_directory_mode = false;
+    let mut report_dir: Option<PathBuf> = None;
     
     if let Some(dir) = directory {
         // Explicit directory mode with --directory flag
         wav_files_owned = collect_wav_files(dir, recursive);
         is_directory_mode = true;
+        report_dir = Some(PathBuf::from(dir));
         if wav_files_owned.is_empty() {
             eprintln!("No WAV files found in directory: {}", dir);
             process::exit(1);
@@ -689,6 +698,7 @@ fn main() {
                 // Automatically treat as directory mode
                 wav_files_owned = collect_wav_files(file_args[0], recursive);
                 is_directory_mode = true;
+                report_dir = Some(PathBuf::from(file_args[0]));
                 if wav_files_owned.is_empty() {
                     eprintln!("No WAV files found in directory: {}", file_args[0]);
                     process::exit(1);
@@ -715,29 +725,74 @@ fn main() {
         println!("Usage: cue_creator [OPTIONS] <FILE1.wav> [FILE2.wav ...]");
         println!("       cue_creator [OPTIONS] <DIRECTORY>");
         println!("       cue_creator [OPTIONS] --directory <DIR>");
+        println!("       cue_creator [OPTIONS] --watch <DIR>");
         println!();
         println!("Options:");
         println!("  --verbose, -v            Show detailed analysis");
         println!("  --directory <DIR>, -d    Process all WAV files in directory");
         println!("  --recursive, -r          Process subdirectories recursively");
+        println!("  --watch <DIR>            Wait for new WAV files in DIR and process each one once");
+        println!("                           it has stopped growing for {} s", watch::DEFAULT_SETTLE_SECONDS);
         println!("  --dump                   Dump RMS curve (tab-separated, for plotting)");
+        println!("  --spectrogram            Write <name>.spectrogram.png with the RMS curve and the");
+        println!("                           detected boundaries");
+        println!("  --report                 Write <name>.report.html with levels, RMS curve, boundaries");
+        println!("                           and tracks (and report.html for a directory)");
         println!("  --identify-only          Only identify album/side, skip CUE generation and rename");
         println!("  --no-lookup              Skip all metadata lookup (Shazam + album lookup)");
         println!("  --no-shazam              Skip Shazam song identification");
+        println!("  --use-acoustid           Identify songs with AcoustID instead of Shazam");
+        println!("                           (needs fpcalc and ACOUSTID_API_KEY or acoustid.toml)");
+        println!("  --adaptive-identify      Sample the middle of each preliminary track instead of");
+        println!("                           every 2 minutes; retry unclear tracks and vote");
         println!("  --no-discogs             Skip Discogs album lookup");
         println!("  --no-musicbrainz         Skip MusicBrainz album lookup");
         println!("  --no-cue                 Don't generate CUE files");
+        println!("  --no-wav-markers         Don't write the track starts into the WAV file as cue markers");
+        println!("  --cue-encoding <CHARSET> Character set of CUE files: utf8 (default) or latin1");
+        println!("  --cue-transliterate      Write only ASCII into CUE files (\"Björk\" -> \"Bjoerk\")");
+        println!("  --rename                 Rename files using identified artist/album (default)");
         println!("  --no-rename              Don't rename files using identified artist/album");
+        println!("  --rename-template <T>    Name of renamed files, e.g. \"{{artist}} - {{album}} (Side {{side_letter}})\"");
+        println!("                           (default: \"{{artist}} - {{album}}\" plus the side number)");
+        println!("  --reidentify             Identify again instead of using session.toml");
+        println!("  --allow-duplicate        Process a recording even if the release was recorded");
+        println!("                           before (in the library_dir of [organize] or next to it)");
+        println!("  --release <URL|ID>       Use this Discogs or MusicBrainz release; skips song");
+        println!("                           identification and album search");
+        println!("  --tracklist <FILE>       Place boundaries by the track lengths in FILE (\"M:SS Title\"");
+        println!("                           per line) or by the tracks of a CUE sheet; no online lookup");
+        println!("  --review                 Play 5s around each boundary and confirm or drop it");
+        println!("  --fix-speed              Write a resampled copy (name.speed.wav) when a 45 RPM record");
+        println!("                           was played at 33 1/3 RPM or vice versa, or a 78 at 45");
+        println!("  --seed-from-matches      Search boundaries between songs identified by Shazam");
+        println!("                           (when no release with matching durations is found)");
+        println!("  --mmap                   Memory-map the WAV file for the RMS pass (faster on large files)");
+        println!("  --progress <MODE>        Report progress of long steps on stderr:");
+        println!("                             none (default), bar or json (one JSON object per line)");
         println!("  --min-prominence <DB>    Minimum valley depth below local average (default: 3.0)");
-        println!("  --min-song <SEC>         Minimum song duration in seconds (default: 30)");
+        println!("  --min-song <SEC>         Minimum song duration in seconds (default: 30, 15 for 78)");
         println!("  --smooth-window <SEC>    Smoothing window in seconds (default: 3.0)");
         println!("  --chunk-ms <MS>          RMS window size in milliseconds (default: 200)");
+        println!("  --channels <LIST>        Analyze only these channels of a multichannel recording,");
+        println!("                           e.g. 3,4 for the second deck (default: all)");
+        println!("  --medium <MEDIUM>        Kind of recording (default: vinyl):");
+        println!("                             vinyl - groove noise, boundaries are dips below it");
+        println!("                             tape  - leader tape and hiss, boundaries are pauses");
+        println!("                                     near the hiss level");
+        println!("                             78    - shellac: loud surface noise, boundaries only fall");
+        println!("                                     back to it; shorter songs, wider guided search");
+        println!("  --played-at <RPM>        Speed a 78 was captured at (45 on turntables without 78);");
+        println!("                           a corrected copy (name.speed.wav) is written and analyzed");
         println!();
         println!("Examples:");
         println!("  cue_creator --verbose side_a.wav side_b.wav");
         println!("  cue_creator /music/at33ptg");
         println!("  cue_creator --directory /music/at33ptg");
         println!("  cue_creator --recursive /music");
+        println!("  cue_creator --watch /music/incoming");
+        println!("  cue_creator --release https://www.discogs.com/release/1873013 animals.1.wav");
+        println!("  cue_creator --medium 78 --played-at 45 shellac.wav");
         println!();
         println!("Directory Mode:");
         println!("  - Automatically activated when argument is a directory");
@@ -745,6 +800,26 @@ fn main() {
         println!("  - Use --recursive to include subdirectories");
         println!("  - Skips files that already have .cue or .guess.cue files");
         println!("  - Creates .cue files with detected boundaries and track info");
+        println!();
+        println!("Track marks:");
+        println!("  If autorecord wrote a .marks file for the recording ('m' key), boundaries");
+        println!("  are placed at the marks instead of being detected. Boundaries saved by the");
+        println!("  review in the autorecd web UI (.boundaries file) are used exactly as saved.");
+        println!();
+        println!("Metadata file:");
+        println!("  A <name>.meta.toml next to <name>.wav gives artist, album, release URL and");
+        println!("  track list by hand; song identification and album lookup are skipped.");
+        println!();
+        println!("Session file:");
+        println!("  Identified songs, the release and the side of each file are stored in");
+        println!("  session.toml next to the WAV files. Later runs (and track_splitter) use it");
+        println!("  instead of identifying the songs again.");
+        println!();
+        println!("Config file:");
+        println!("  The [cue_creator] section of the autorecord config file sets defaults for");
+        println!("  min_prominence, min_song, smooth_window, chunk_ms, medium, use_acoustid, rename,");
+        println!("  rename_template (\"{{artist}} - {{album}}\") and lookup_backends");
+        println!("  (in order of preference: {}).", lookup::BACKEND_NAMES.join(", "));
         process::exit(1);
     }
     
@@ -772,6 +847,25 @@ fn main() {
         wav_files
     };
     
+    // Analyze speed-corrected copies; originals and copies of a directory
+    // both lead to the same copy
+    let corrected_owned: Vec<String> = match played_at {
+        Some(speed) => {
+            let mut seen = std::collections::HashSet::new();
+            files_to_process.iter()
+                .filter_map(|f| speed_corrected_copy(f, speed))
+                .filter(|f| seen.insert(f.clone()))
+                .filter(|f| !is_directory_mode || no_cue || identify_only || !cuefile::has_cue_file(f))
+                .collect()
+        }
+        None => Vec::new(),
+    };
+    let files_to_process: Vec<&str> = if played_at.is_some() {
+        corrected_owned.iter().map(String::as_str).collect()
+    } else {
+        files_to_process
+    };
+    
     if files_to_process.is_empty() {
         println!("No files to process (all files already have .cue files)");
         process::exit(0);
@@ -785,10 +879,38 @@ fn main() {
     let mut album_overrides: std::collections::HashMap<String, FileSideResult> =
         std::collections::HashMap::new();
 
-    if files_to_process.len() > 1 && !no_shazam && (!no_discogs || !no_musicbrainz) {
+    if let Some(ref tracklist) = tracklist {
+        if files_to_process.len() != 1 {
+            eprintln!("Error: --tracklist describes one recording, but {} files were given", files_to_process.len());
+            process::exit(1);
+        }
+        let source = tracklist_path.map(String::as_str).unwrap_or_default();
+        album_overrides.insert(files_to_process[0].to_string(), tracklist.side_result(files_to_process[0], source));
+    }
+
+    // Release and side given by hand or assigned by an earlier run
+    for wav_file in files_to_process.iter().filter(|_| release.is_none() && tracklist.is_none()) {
+        if let Some(result) = known_release(wav_file, reidentify) {
+            album_overrides.insert(wav_file.to_string(), result);
+        }
+    }
+    if !album_overrides.is_empty() {
+        let from_metadata = album_overrides.values().filter(|r| r.backend == metadata::METADATA_BACKEND).count();
+        let from_tracklist = album_overrides.values().filter(|r| r.backend == tracklist::TRACKLIST_BACKEND).count();
+        if from_metadata > 0 {
+            println!("Using .meta.toml files for {} file(s)", from_metadata);
+        }
+        if album_overrides.len() > from_metadata + from_tracklist {
+            println!("Using {} for {} file(s)", session::SESSION_FILE_NAME, album_overrides.len() - from_metadata - from_tracklist);
+        }
+        println!();
+    }
+
+    let unassigned = files_to_process.iter().filter(|f| !album_overrides.contains_key(**f)).count();
+    if unassigned > 1 && !no_shazam && !backends.is_empty() && release.is_none() {
         println!("Multi-file album identification");
         println!("================================");
-        println!("Pre-identifying {} files...\n", files_to_process.len());
+        println!("Pre-identifying {} files...\n", unassigned);
 
         // Step 1: Identify songs per file
         struct PreIdentified {
@@ -797,8 +919,11 @@ fn main() {
             duration: f64,
         }
         let mut pre_identified: Vec<PreIdentified> = Vec::new();
+        let unassigned_files: Vec<&str> = files_to_process.iter()
+            .filter(|f| !album_overrides.contains_key(**f)).copied().collect();
+        let mut rms_prefetch = audio_analysis::RmsPrefetch::new(chunk_ms, use_mmap, &analysis_channels);
 
-        for wav_file in &files_to_process {
+        for (i, &wav_file) in unassigned_files.iter().enumerate() {
             let name = Path::new(wav_file)
                 .file_name().and_then(|n| n.to_str()).unwrap_or(wav_file);
 
@@ -816,12 +941,39 @@ fn main() {
                 Err(_) => continue,
             };
 
-            let (result, _log) = album_identifier::identify_songs(wav_file, None);
-            let songs = match result {
-                Ok(s) => s,
-                Err(e) => {
-                    println!("  {}: identification failed: {}", name, e);
-                    continue;
+            let stored = if reidentify { None } else { session_songs(wav_file) };
+            let songs = match stored {
+                Some(s) => s,
+                None => {
+                    let regions = if adaptive_identify {
+                        // The next file's RMS is computed while this one is identified
+                        let rms = rms_prefetch.take(wav_file);
+                        if let Some(next) = unassigned_files.get(i + 1) {
+                            rms_prefetch.start(next);
+                        }
+                        let rms = rms.unwrap_or_else(|| audio_analysis::wav_rms(wav_file, chunk_ms, use_mmap, &analysis_channels, progress.as_mut()));
+                        rms.ok().map(|rms| {
+                            let analysis = BoundaryAnalysis::for_medium(rms, chunk_ms, smooth_window_secs, file_duration, medium, false);
+                            preliminary_regions(&analysis, min_prominence, min_song_duration)
+                        })
+                    } else {
+                        None
+                    };
+                    let (result, _log) = identify_songs(wav_file, use_acoustid, regions.as_deref(), progress.as_mut());
+                    match result {
+                        Ok(s) => {
+                            update_session(wav_file, |session| {
+                                let side = session.side_mut(wav_file);
+                                side.duration = file_duration;
+                                side.songs = s.clone();
+                            });
+                            s
+                        }
+                        Err(e) => {
+                            println!("  {}: identification failed: {}", name, e);
+                            continue;
+                        }
+                    }
                 }
             };
 
@@ -867,17 +1019,7 @@ fn main() {
         }
         println!();
 
-        // Step 3: Build backends
-        let discogs_backend = DiscogsBackend;
-        let mb_vinyl = MusicBrainzBackend { vinyl_only: true };
-        let mb_all = MusicBrainzBackend { vinyl_only: false };
-
-        let mut backends: Vec<&dyn AlbumIdentifier> = Vec::new();
-        if !no_discogs { backends.push(&discogs_backend); }
-        if !no_musicbrainz { backends.push(&mb_vinyl); }
-        if !no_musicbrainz { backends.push(&mb_all); }
-
-        // Step 4: For each artist group, iteratively find albums and assign
+        // Step 3: For each artist group, iteratively find albums and assign
         // Minimum score to consider a file "matched" to an album side.
         const MIN_MATCH_SCORE: f64 = 25.0;
 
@@ -984,6 +1126,12 @@ fn main() {
                     println!("  {} → Side {} (score {:.1})",
                              name, r.side_label, r.score);
                     album_overrides.insert(r.path.clone(), r.clone());
+                    update_session(&r.path, |session| {
+                        if !session.assign(r) {
+                            eprintln!("  Warning: {} already belongs to another release, not updated",
+                                      session::SESSION_FILE_NAME);
+                        }
+                    });
                 }
                 println!();
 
@@ -1025,17 +1173,33 @@ fn main() {
         println!("{}", "=".repeat(60));
     }
 
-    for wav_file in &files_to_process {
+    // Pass 1 of the next file runs while this one is identified and looked up
+    let mut rms_prefetch = audio_analysis::RmsPrefetch::new(chunk_ms, use_mmap, &analysis_channels);
+    let mut reports: Vec<FileReport> = Vec::new();
+    for (i, wav_file) in files_to_process.iter().enumerate() {
         if files_to_process.len() > 1 {
             println!();
             println!("{}", "=".repeat(60));
         }
 
         let override_result = album_overrides.get(*wav_file);
+        let rms = rms_prefetch.take(wav_file);
+        if let Some(next) = files_to_process.get(i + 1) {
+            rms_prefetch.start(next);
+        }
+
+        let file_report = process_file(wav_file, verbose, dump, spectrogram, write_report, min_prominence, min_song_duration,
+                     smooth_window_secs, chunk_ms, medium, no_shazam, use_acoustid, no_musicbrainz, &backends,
+                     no_cue, wav_markers, cue_text, rename.then_some(&rename_template), identify_only, seed_from_matches, use_mmap, &analysis_channels, reidentify, review, fix_speed, override_result,
+                     release.as_ref(), adaptive_identify, check_duplicates, library_dir.as_deref(), rms, progress.as_mut());
+        reports.extend(file_report);
+    }
 
-        process_file(wav_file, verbose, dump, min_prominence, min_song_duration,
-                     smooth_window_secs, chunk_ms, no_shazam, no_musicbrainz, no_discogs,
-                     no_cue, rename, identify_only, override_result);
+    if let Some(dir) = report_dir.filter(|_| !reports.is_empty()) {
+        match report::write_session_report(&dir, &reports) {
+            Ok(path) => println!("Session report created: {}", path.display()),
+            Err(e) => eprintln!("Warning: Failed to write session report: {}", e),
+        }
     }
 }
 
@@ -1043,26 +1207,44 @@ fn process_file(
     wav_file: &str,
     verbose: bool,
     dump: bool,
+    spectrogram: bool,
+    write_report: bool,
     min_prominence_db: f32,
     min_song_duration: f64,
     smooth_window_secs: f64,
     chunk_ms: u32,
+    medium: Medium,
     no_shazam: bool,
+    use_acoustid: bool,
     no_musicbrainz: bool,
-    no_discogs: bool,
+    backends: &[&dyn AlbumIdentifier],
     no_cue: bool,
-    rename: bool,
+    wav_markers: bool,
+    cue_text: CueText,
+    rename_template: Option<&FilenameTemplate>,
     identify_only: bool,
+    seed_from_matches: bool,
+    use_mmap: bool,
+    analysis_channels: &[usize],
+    reidentify: bool,
+    review: bool,
+    fix_speed: bool,
     album_override: Option<&FileSideResult>,
-) {
+    release: Option<&ReleaseReference>,
+    adaptive_identify: bool,
+    check_duplicates: bool,
+    library_dir: Option<&Path>,
+    precomputed_rms: Option<autorec::error::Result<Vec<f32>>>,
+    progress: &mut dyn ProgressSink,
+) -> Option<FileReport> {
     if !Path::new(wav_file).exists() {
         eprintln!("Error: File not found: {}", wav_file);
-        return;
+        return None;
     }
 
     if is_file_in_use(wav_file) {
         println!("Skipping '{}': file is currently in use by another process", wav_file);
-        return;
+        return None;
     }
     
     println!("Song Boundary Finder");
@@ -1083,7 +1265,7 @@ fn process_file(
         Ok(f) => f,
         Err(e) => {
             eprintln!("Error: Cannot open file '{}': {}", wav_file, e);
-            return;
+            return None;
         }
     };
     let mut reader = BufReader::new(file);
@@ -1091,7 +1273,7 @@ fn process_file(
         Ok(h) => h,
         Err(e) => {
             eprintln!("Warning: Skipping invalid WAV file '{}': {}", wav_file, e);
-            return;
+            return None;
         }
     };
     
@@ -1102,123 +1284,144 @@ fn process_file(
     println!("WAV: {}Hz, {}ch, {}bit, duration: {} ({:.1}s)",
              header.sample_rate, header.num_channels, header.bits_per_sample,
              format_timestamp(file_duration), file_duration);
+    if !analysis_channels.is_empty() {
+        let names: Vec<String> = analysis_channels.iter().map(|c| (c + 1).to_string()).collect();
+        println!("Analyzing channel(s) {} of {}", names.join(", "), header.num_channels);
+    }
     println!();
     
-    let format = match header.bits_per_sample {
-        16 => SampleFormat::S16,
-        32 => SampleFormat::S32,
-        _ => {
-            eprintln!("Error: Unsupported bit depth: {}", header.bits_per_sample);
-            return;
-        }
-    };
-    
-    // ==== Pass 1: Compute RMS for entire file ====
-    let chunk_samples = (header.sample_rate as f64 * chunk_ms as f64 / 1000.0) as usize;
-    let chunk_bytes = chunk_samples * header.num_channels as usize * bytes_per_sample;
-    let chunk_duration = chunk_ms as f64 / 1000.0;
+    if header.sample_format().is_none() {
+        eprintln!("Error: Unsupported bit depth: {}", header.bits_per_sample);
+        return None;
+    }
     
-    let mut rms_values: Vec<f32> = Vec::new();
-    let mut timestamps: Vec<f64> = Vec::new();
-    let mut position = 0.0_f64;
+    // A release given up front is checked before any analysis
+    let known_release = release.map(|reference| ReleaseTags::from_release_info(&reference.url(), None))
+        .or_else(|| album_override.map(|ovr| ReleaseTags::from_release_info(&ovr.release_info, ovr.year)));
+    if check_duplicates && known_release.as_ref().is_some_and(|tags| already_digitized(library_dir, wav_file, tags)) {
+        return None;
+    }
     
+    // ==== Pass 1: Compute RMS for entire file ====
     if verbose {
-        println!("Pass 1: Computing RMS ({}ms windows)...", chunk_ms);
+        println!("Pass 1: Computing RMS ({}ms windows{}{})...", chunk_ms,
+                 if use_mmap { ", memory-mapped" } else { "" },
+                 if precomputed_rms.is_some() { ", in the background" } else { "" });
     }
     
-    loop {
-        let mut buffer = vec![0u8; chunk_bytes];
-        let bytes_read = reader.read(&mut buffer).unwrap_or(0);
-        if bytes_read == 0 { break; }
-        
-        let samples_in_chunk = bytes_read / (header.num_channels as usize * bytes_per_sample);
-        if samples_in_chunk == 0 { break; }
-        
-        let mut audio_data: Vec<Vec<i32>> =
-            vec![Vec::with_capacity(samples_in_chunk); header.num_channels as usize];
-        
-        for i in 0..samples_in_chunk {
-            for ch in 0..header.num_channels as usize {
-                let off = (i * header.num_channels as usize + ch) * bytes_per_sample;
-                if off + bytes_per_sample > bytes_read { break; }
-                let sample = match format {
-                    SampleFormat::S16 => {
-                        i16::from_le_bytes([buffer[off], buffer[off + 1]]) as i32
-                    }
-                    SampleFormat::S32 => {
-                        i32::from_le_bytes([buffer[off], buffer[off+1], buffer[off+2], buffer[off+3]])
-                    }
-                };
-                audio_data[ch].push(sample);
-            }
+    let rms_values = precomputed_rms.unwrap_or_else(|| audio_analysis::wav_rms(wav_file, chunk_ms, use_mmap, analysis_channels, progress));
+    let rms_values = match rms_values {
+        Ok(values) => values,
+        Err(e) => {
+            eprintln!("Error: Cannot analyze '{}': {}", wav_file, e);
+            return None;
         }
-        
-        rms_values.push(audio_analysis::compute_rms_db(&audio_data, format));
-        timestamps.push(position);
-        position += chunk_duration;
-    }
+    };
     
     if verbose {
-        println!("  {} RMS values over {:.1}s", rms_values.len(), position);
+        println!("  {} RMS values over {:.1}s", rms_values.len(), rms_values.len() as f64 * chunk_ms as f64 / 1000.0);
+        println!("Pass 2: Detecting groove-in and groove-out...");
     }
     
-    // ==== Smoothing ====
-    let smooth_window = ((smooth_window_secs / chunk_duration) as usize).max(3) | 1;
-    let smoothed = audio_analysis::smooth_rms(&rms_values, smooth_window);
-    
-    // ==== Level estimates ====
-    let noise_floor = audio_analysis::estimate_noise_floor(&smoothed);
-    let music_level = audio_analysis::estimate_music_level(&smoothed);
+    // ==== Smoothing, level estimates, groove-in / groove-out ====
+    let mut analysis = BoundaryAnalysis::for_medium(rms_values, chunk_ms, smooth_window_secs, file_duration, medium, verbose);
+    if let Err(e) = analysis.confirm_needle_drop(wav_file, verbose) {
+        eprintln!("Warning: Cannot check the needle drop: {}", e);
+    }
+    if let Err(e) = analysis.skip_locked_groove(wav_file, verbose) {
+        eprintln!("Warning: Cannot check for a locked groove: {}", e);
+    }
+    let groove_in = analysis.groove_in;
+    let groove_out = analysis.groove_out;
+    let music_duration = analysis.music_duration();
     
     println!("Levels:");
-    println!("  Noise floor: {:.1} dB (groove noise)", noise_floor);
-    println!("  Music level: {:.1} dB (typical music)", music_level);
-    println!("  Difference:  {:.1} dB", music_level - noise_floor);
+    println!("  Noise floor: {:.1} dB ({})", analysis.noise_floor_db,
+             medium.noise_name());
+    println!("  Music level: {:.1} dB (typical music)", analysis.music_level_db);
+    println!("  Difference:  {:.1} dB", analysis.music_level_db - analysis.noise_floor_db);
     println!();
     
-    // ==== Pass 2: Groove-in / Groove-out detection ====
-    if verbose {
-        println!("Pass 2: Detecting groove-in and groove-out...");
-    }
-    
-    let groove_in = detect_groove_in(&smoothed, &timestamps, noise_floor, music_level,
-                                      chunk_duration, verbose);
-    let groove_out = detect_groove_out(&smoothed, &timestamps, noise_floor, music_level,
-                                       file_duration, chunk_duration, verbose);
-    let music_duration = groove_out - groove_in;
-    
     println!("Music region:");
     println!("  Groove-in:  {} ({:.1}s lead-in)", format_timestamp(groove_in), groove_in);
     println!("  Groove-out: {} ({:.1}s lead-out)", format_timestamp(groove_out),
              file_duration - groove_out);
     println!("  Music:      {} ({:.1}s)", format_timestamp(music_duration), music_duration);
+    if let Some(drop) = analysis.needle_drop {
+        let unconfirmed = if drop.confirmed { "" } else { ", unconfirmed" };
+        println!("  Needle drop: {} ({:.1}s thump before groove-in{})", format_timestamp(drop.start),
+                 drop.end - drop.start, unconfirmed);
+    }
+    if let Some(groove) = analysis.locked_groove {
+        println!("  Locked groove: {} ({:.1}s, {:.2}s per revolution)", format_timestamp(groove.start),
+                 file_duration - groove.start, groove.period);
+    }
     println!();
     
-    let music_start_idx = timestamps.iter().position(|&t| t >= groove_in).unwrap_or(0);
-    let music_end_idx = timestamps.iter().position(|&t| t >= groove_out).unwrap_or(timestamps.len());
-    
-    // ==== Step 1: Song identification (Shazam) ====
+    // ==== Step 1: Song identification (Shazam or AcoustID) ====
     let mut track_names: Vec<String> = Vec::new();
     let mut artist: String = "Unknown Artist".to_string();
     let mut album_title: String = "Unknown Album".to_string();
     let mut mb_info: Option<String> = None;
+    let mut release_tags = ReleaseTags::default();
     let mut mb_tracks: Option<Vec<musicbrainz::ExpectedTrack>> = None;
     let mut use_guided_detection = false;
+    // Track count of a release whose lengths do not fit for guided detection
+    let mut expected_songs: Option<usize> = None;
+    // Tracks of such a release, checked for a wrong playback speed
+    let mut speed_check_tracks: Option<Vec<musicbrainz::ExpectedTrack>> = None;
     let mut identified_songs: Vec<album_identifier::IdentifiedSong> = Vec::new();
-
-    if !no_shazam {
-        println!("Song Identification (Shazam):");
-        println!("-----------------------------");
+    let mut side_result: Option<FileSideResult> = album_override.cloned();
+
+    // Release given with --release: only its side is chosen
+    let forced_release = release.and_then(|reference| {
+        let song_titles: Vec<String> = session_songs(wav_file).unwrap_or_default()
+            .into_iter().map(|s| s.title).collect();
+        match lookup::release_side(reference, wav_file, music_duration, &song_titles, verbose) {
+            Ok(Some(result)) => Some(result),
+            Ok(None) => {
+                println!("Release {} has no tracks, identifying songs instead", reference.url());
+                None
+            }
+            Err(e) => {
+                eprintln!("Warning: Cannot fetch release {}: {}", reference.url(), e);
+                None
+            }
+        }
+    });
+    let album_override = forced_release.as_ref().or(album_override);
+
+    // Album and tracks given by hand: nothing to identify or look up
+    let from_metadata = album_override.is_some_and(|o| o.backend == metadata::METADATA_BACKEND);
+    let from_tracklist = album_override.is_some_and(|o| o.backend == tracklist::TRACKLIST_BACKEND);
+    let skip_identification = from_metadata || from_tracklist || forced_release.is_some();
+
+    let stored_songs = if reidentify || no_shazam || skip_identification { None } else { session_songs(wav_file) };
+    if let Some(songs) = stored_songs {
+        println!("Songs from {}: {}", session::SESSION_FILE_NAME, songs.len());
+        for song in &songs {
+            println!("  {} - {}", song.artist, song.title);
+        }
+        println!();
+        track_names = songs.iter()
+            .map(|s| format!("{} - {}", s.artist, s.title))
+            .collect();
+        identified_songs = songs;
+    } else if !no_shazam && !skip_identification {
+        let service = if use_acoustid { "AcoustID" } else { "Shazam" };
+        let title = format!("Song Identification ({}):", service);
+        println!("{}", title);
+        println!("{}", "-".repeat(title.len()));
         
-        let (result, identify_log) = album_identifier::identify_songs(wav_file, None);
+        let regions = adaptive_identify.then(|| preliminary_regions(&analysis, min_prominence_db, min_song_duration));
+        let (result, identify_log) = identify_songs(wav_file, use_acoustid, regions.as_deref(), progress);
         
         // Write identification log file
         {
             let base_path = cuefile::wav_base_path(wav_file);
             let identify_path = format!("{}.identify.txt", base_path.display());
             let mut header = String::new();
-            header.push_str("Song Identification (Shazam):\n");
-            header.push_str("-----------------------------\n");
+            header.push_str(&format!("{}\n{}\n", title, "-".repeat(title.len())));
             header.push_str(&identify_log);
             if let Err(e) = std::fs::write(&identify_path, &header) {
                 eprintln!("Warning: Failed to write identify file {}: {}", identify_path, e);
@@ -1249,9 +1452,18 @@ fn process_file(
 
     // ==== Step 2: Album / side lookup (Discogs → MusicBrainz) ====
     if let Some(ovr) = album_override {
-        // Use pre-computed multi-file album identification result
-        println!("Album / Side Lookup (from multi-file identification):");
-        println!("----------------------------------------------------");
+        // Use the metadata file or the pre-computed multi-file album identification result
+        let title = if from_metadata {
+            format!("Album / Side (from {}):", metadata::metadata_path(wav_file).display())
+        } else if forced_release.is_some() {
+            "Album / Side (from --release):".to_string()
+        } else if from_tracklist {
+            format!("Tracks (from {}):", ovr.release_info)
+        } else {
+            "Album / Side Lookup (from multi-file identification):".to_string()
+        };
+        println!("{}", title);
+        println!("{}", "-".repeat(title.len()));
 
         artist = ovr.artist.clone();
         album_title = ovr.album_title.clone();
@@ -1259,6 +1471,7 @@ fn process_file(
         println!("Release: {} (via {})", ovr.release_info, ovr.backend);
         println!("Assigned side: {}", ovr.side_label);
         mb_info = Some(format!("{} - {} [{}]", artist, album_title, ovr.release_info));
+        release_tags = ReleaseTags::from_release_info(&ovr.release_info, ovr.year);
 
         if !ovr.tracks.is_empty() {
             let expected_duration: f64 = ovr.tracks.iter()
@@ -1272,6 +1485,10 @@ fn process_file(
                 println!("Duration match: {:.1}% error - using guided detection", error_percent);
             } else {
                 println!("Duration match: {:.1}% error - using autonomous detection", error_percent);
+                expected_songs = Some(ovr.tracks.len());
+                if ovr.tracks.iter().all(|t| t.length_seconds > 0.0) {
+                    speed_check_tracks = Some(ovr.tracks.clone());
+                }
             }
 
             track_names = ovr.tracks.iter()
@@ -1286,27 +1503,18 @@ fn process_file(
             println!("No track data for assigned side");
         }
         println!();
-    } else if (!no_discogs || !no_musicbrainz) && !identified_songs.is_empty() {
+    } else if !backends.is_empty() && !identified_songs.is_empty() {
         println!("Album / Side Lookup:");
         println!("--------------------");
 
-        // Build the ordered list of backends to try
-        let discogs_backend = DiscogsBackend;
-        let mb_vinyl = MusicBrainzBackend { vinyl_only: true };
-        let mb_all   = MusicBrainzBackend { vinyl_only: false };
-
-        let mut backends: Vec<&dyn AlbumIdentifier> = Vec::new();
-        if !no_discogs    { backends.push(&discogs_backend); }
-        if !no_musicbrainz { backends.push(&mb_vinyl); }
-        if !no_musicbrainz { backends.push(&mb_all); }
-
-        match lookup::find_album_side_with_fallback(&backends, &identified_songs, music_duration, verbose) {
+        match lookup::find_album_side_with_fallback(backends, &identified_songs, music_duration, verbose) {
             Ok(Some(result)) => {
                 artist = result.artist.clone();
                 album_title = result.album_title.clone();
 
                 println!("Release: {} (via {})", result.release_info, result.backend);
                 mb_info = Some(format!("{} - {} [{}]", artist, album_title, result.release_info));
+                release_tags = ReleaseTags::from_release_info(&result.release_info, result.year);
 
                 let expected_duration: f64 = result.tracks.iter()
                     .map(|t| t.length_seconds).sum();
@@ -1319,6 +1527,8 @@ fn process_file(
                     println!("Duration match: {:.1}% error - using guided detection", error_percent);
                 } else {
                     println!("Duration match: {:.1}% error - using autonomous detection", error_percent);
+                    expected_songs = Some(result.tracks.len());
+                    speed_check_tracks = Some(result.tracks.clone());
                 }
 
                 // Override track names with looked-up data
@@ -1330,6 +1540,18 @@ fn process_file(
                 for t in &result.tracks {
                     println!("  #{} {} ({:.0}s)", t.position, t.title, t.length_seconds);
                 }
+
+                side_result = Some(FileSideResult {
+                    path: wav_file.to_string(),
+                    artist: result.artist,
+                    album_title: result.album_title,
+                    release_info: result.release_info,
+                    year: result.year,
+                    side_label: '?',
+                    tracks: result.tracks,
+                    backend: result.backend,
+                    score: 0.0,
+                });
             }
             Ok(None) => {
                 println!("No matching releases found");
@@ -1341,46 +1563,161 @@ fn process_file(
         println!();
     }
 
+    update_session(wav_file, |session| {
+        if let Some(ref result) = side_result {
+            if !session.assign(result) {
+                eprintln!("Warning: {} already belongs to another release, side not recorded",
+                          session::SESSION_FILE_NAME);
+            }
+        }
+        let side = session.side_mut(wav_file);
+        side.duration = file_duration;
+        side.music_duration = Some(music_duration);
+        if !identified_songs.is_empty() {
+            side.songs = identified_songs.clone();
+        }
+    });
+
     // In identify-only mode, stop after identification (skip boundary detection, CUE, rename)
     if identify_only {
-        return;
+        return None;
+    }
+
+    // Don't spend the boundary analysis on a record that was digitized before
+    if check_duplicates && known_release.is_none() && already_digitized(library_dir, wav_file, &release_tags) {
+        return None;
     }
     
     // Dump mode
     if dump {
         println!("# timestamp_s\traw_rms_db\tsmoothed_rms_db\tin_music");
-        for i in 0..rms_values.len() {
+        let (music_start_idx, music_end_idx) = analysis.music_range();
+        for i in 0..analysis.rms_values.len() {
             let in_music = if i >= music_start_idx && i < music_end_idx { 1 } else { 0 };
-            println!("{:.2}\t{:.2}\t{:.2}\t{}", timestamps[i], rms_values[i], smoothed[i], in_music);
+            println!("{:.2}\t{:.2}\t{:.2}\t{}", analysis.timestamps[i], analysis.rms_values[i], analysis.smoothed[i], in_music);
         }
         println!();
     }
     
+    // Boundary windows between differently identified songs inside the music region
+    let seed_windows: Vec<album_identifier::BoundaryWindow> = if seed_from_matches {
+        let songs: Vec<album_identifier::IdentifiedSong> = identified_songs.iter()
+            .filter(|s| s.timestamp >= groove_in && s.timestamp < groove_out)
+            .cloned()
+            .collect();
+        album_identifier::boundary_windows(&songs, album_identifier::CLIP_SECONDS, 10.0)
+    } else {
+        Vec::new()
+    };
+    
+    // Reviewed boundaries and track marks set while recording take precedence over any detection
+    let reviewed = cuefile::read_reviewed(wav_file);
+    let marks = cuefile::read_marks(wav_file);
+    let mark_windows = boundary::mark_windows(&marks, groove_in, groove_out);
+
     // ==== Pass 3: Find song boundaries within music region ====
-    let valleys = if use_guided_detection {
+    // Guided boundaries with the index of the track each one starts
+    let mut aligned = Vec::new();
+    let (detection_mode, mut valleys) = if let Some(positions) = &reviewed {
+        println!("Pass 3: {} reviewed boundary(ies) from {}",
+                 positions.len(), cuefile::reviewed_path(wav_file).display());
+        (DetectionMode::Reviewed,
+         boundary::exact_boundaries(&analysis.smoothed, &analysis.timestamps, positions,
+                                    (groove_in, groove_out), analysis.chunk_duration))
+    } else if !mark_windows.is_empty() {
+        println!("Pass 3: Boundaries at {} track mark(s) from {}",
+                 mark_windows.len(), cuefile::marks_path(wav_file).display());
+        (DetectionMode::Marks,
+         boundary::find_seeded_boundaries(&analysis.smoothed, &analysis.timestamps, &mark_windows,
+                                          analysis.chunk_duration, verbose))
+    } else if use_guided_detection {
         if verbose {
             println!("Pass 3: Guided boundary detection (using looked-up track positions)...");
         }
-        let search_window = 10.0; // Search ±10 seconds around expected positions
-        find_guided_boundaries(
-            &smoothed, &timestamps,
+        let search_window = medium.guided_search_seconds(); // Search ± around expected positions
+        aligned = boundary::find_guided_boundaries(
+            &analysis.smoothed, &analysis.timestamps,
             mb_tracks.as_ref().unwrap(),
-            groove_in,
+            (groove_in, groove_out),
             search_window,
             verbose,
-        )
+        );
+        (DetectionMode::Guided, aligned.iter().map(|(_, valley)| valley.clone()).collect())
+    } else if !seed_windows.is_empty() {
+        if verbose {
+            println!("Pass 3: Boundary detection seeded by {} identified song change(s)...",
+                     seed_windows.len());
+        }
+        (DetectionMode::Seeded,
+         boundary::find_seeded_boundaries(&analysis.smoothed, &analysis.timestamps, &seed_windows,
+                                          analysis.chunk_duration, verbose))
+    } else if let Some(expected) = expected_songs.filter(|_| analysis.medium != Medium::Tape) {
+        if verbose {
+            println!("Pass 3: Autonomous boundary detection for {} songs (prominence >= {:.1} dB, min song {:.0}s)...",
+                     expected, min_prominence_db, min_song_duration);
+        }
+        let counted = boundary::find_boundaries_for_count(&analysis, min_prominence_db, min_song_duration, expected, verbose);
+        if counted.initial_count + 1 != expected {
+            println!("Song count: {} detected, {} on the release", counted.initial_count + 1, expected);
+            if counted.songs() == expected {
+                println!("  Retried with --min-prominence {:.1}: {} songs ({} runs)",
+                         counted.min_prominence_db, counted.songs(), counted.attempts);
+            } else if counted.min_prominence_db != min_prominence_db {
+                println!("  Retried with --min-prominence {:.1}: {} songs, closest of {} runs",
+                         counted.min_prominence_db, counted.songs(), counted.attempts);
+            } else {
+                println!("  Other --min-prominence values did not come closer, keeping {} songs", counted.songs());
+            }
+        }
+        (DetectionMode::Autonomous, counted.valleys)
     } else {
         if verbose {
             println!("Pass 3: Autonomous boundary detection (prominence >= {:.1} dB, min song {:.0}s)...",
                      min_prominence_db, min_song_duration);
         }
-        find_song_boundaries(
-            &rms_values, &timestamps, &smoothed,
-            music_start_idx, music_end_idx,
-            min_prominence_db, min_song_duration,
-            chunk_duration, noise_floor, music_level, verbose,
-        )
+        (DetectionMode::Autonomous, boundary::find_song_boundaries(&analysis, min_prominence_db, min_song_duration, verbose))
+    };
+    
+    if review && !valleys.is_empty() {
+        review_boundaries(wav_file, &mut valleys);
+    }
+
+    // A release that does not match the side's length may have been played at the wrong speed
+    if let Some(tracks) = &speed_check_tracks {
+        check_playback_speed(wav_file, &valleys, (groove_in, groove_out), tracks, fix_speed);
+    }
+
+    // Boundaries placed from the release also tell how far off speed the deck runs
+    let speed_drift = if detection_mode == DetectionMode::Guided {
+        estimate_speed_drift(&aligned, &valleys, mb_tracks.as_deref().unwrap_or_default(), groove_in, music_duration)
+    } else {
+        None
     };
+    if let Some(drift) = speed_drift {
+        println!("Speed: {} ({} positions, ±{:.1}s)", drift.describe(), drift.compared, drift.residual_seconds);
+    }
+    
+    // ==== Disc ID lookup from the detected track layout ====
+    if mb_info.is_none() && !no_musicbrainz && !valleys.is_empty() {
+        println!();
+        println!("Disc ID Lookup (MusicBrainz):");
+        println!("-----------------------------");
+        let boundaries: Vec<f64> = valleys.iter().map(|v| v.position_seconds).collect();
+        match musicbrainz::find_release_by_track_layout(groove_in, &boundaries, groove_out, true) {
+            Ok(Some(musicbrainz::TrackLayoutMatch { release, tracks })) => {
+                artist = release.artist.clone();
+                album_title = release.title.clone();
+                mb_info = Some(format!("{} - {} [https://musicbrainz.org/release/{}]",
+                                       artist, album_title, release.release_id));
+                release_tags.musicbrainz_release_id = Some(release.release_id.clone());
+                track_names = tracks.iter()
+                    .map(|t| format!("#{} {}", t.position, t.title))
+                    .collect();
+            }
+            Ok(None) => println!("  No release matches the detected track layout"),
+            Err(e) => println!("  Disc ID lookup failed: {}", e),
+        }
+    }
     
     // ==== Results ====
     println!();
@@ -1399,7 +1736,7 @@ fn process_file(
         println!("Tips:");
         println!("  - Try lowering --min-prominence (current: {:.1})", min_prominence_db);
         println!("  - Try lowering --min-song (current: {:.0})", min_song_duration);
-        println!("  - Use --dump to visualise the RMS curve");
+        println!("  - Use --spectrogram or --dump to visualise the RMS curve");
         println!("  - Use --verbose for more detail");
     } else {
         let mut prev_time = groove_in;
@@ -1431,15 +1768,30 @@ fn process_file(
                  valleys.len() + 1, format_timestamp(last_dur), format_timestamp(prev_time), name);
     }
     println!();
+
+    if spectrogram {
+        let boundaries: Vec<f64> = valleys.iter().map(|v| v.position_seconds).collect();
+        match spectrogram::export(wav_file, &analysis, (groove_in, groove_out), &boundaries, progress) {
+            Ok(path) => println!("Spectrogram created: {}", path.display()),
+            Err(e) => eprintln!("Warning: Failed to write spectrogram: {}", e),
+        }
+    }
     
+    let mut quality: Option<audio_analysis::QualityReport> = None;
+    let mut loudness_analysis: Option<loudness::LoudnessAnalysis> = None;
+    let mut warnings: Vec<String> = Vec::new();
+
     // ==== Generate CUE file ====
     if !no_cue {
-        let cue_content = cuefile::generate_cue_file(wav_file, &artist, &album_title, &track_names, groove_in, &valleys);
+        let cue_content = cuefile::generate_cue_file(wav_file, &artist, &album_title, &track_names, (groove_in, groove_out),
+                                                      analysis.needle_drop.filter(|d| d.confirmed).map(|d| (d.start, d.end)),
+                                                      speed_drift.filter(|d| d.is_significant()).map(|d| d.correction()),
+                                                      &valleys, detection_mode, &release_tags);
         
         // Use .cue for MusicBrainz/Shazam matched, .guess.cue otherwise
         let has_metadata_match = mb_info.is_some();
         
-        match cuefile::write_cue_file(wav_file, &cue_content, has_metadata_match) {
+        match cuefile::write_cue_file(wav_file, &cue_content, has_metadata_match, &cue_text) {
             Ok(cue_path) => {
                 println!("CUE file created: {}", cue_path.display());
             }
@@ -1447,6 +1799,18 @@ fn process_file(
                 eprintln!("Warning: Failed to write CUE file: {}", e);
             }
         }
+        if let Ok(sheet) = cuefile::parse_cue(&cue_content) {
+            let tracks: Vec<CatalogTrack> = sheet.tracks.iter()
+                .map(|t| CatalogTrack { number: t.number, title: t.title.clone(), start: t.start })
+                .collect();
+            catalog::update(|catalog| catalog.set_tracks(wav_file, file_duration, (groove_in, groove_out), &tracks));
+        }
+        if wav_markers {
+            match cuefile::parse_cue(&cue_content).and_then(|sheet| cuefile::embed_cue_points(wav_file, &sheet)) {
+                Ok(count) => println!("{} track markers written to {}", count, wav_file),
+                Err(e) => eprintln!("Warning: {}", e),
+            }
+        }
         
         // Generate info file with timing details
         let expected_track_data: Option<Vec<(f64, f64)>> = mb_tracks.as_ref().map(|tracks| {
@@ -1455,7 +1819,7 @@ fn process_file(
                 .collect()
         });
         
-        let info_content = cuefile::generate_info_file(
+        let mut info_content = cuefile::generate_info_file(
             wav_file,
             groove_in,
             groove_out,
@@ -1463,7 +1827,53 @@ fn process_file(
             &track_names,
             expected_track_data.as_deref(),
             mb_info.as_deref(),
+            detection_mode,
         );
+
+        // Overs found by the recorder
+        if let Ok(report) = fs::read_to_string(cuefile::clip_report_path(wav_file)) {
+            info_content.push('\n');
+            info_content.push_str(&report);
+        }
+
+        match audio_analysis::wav_channel_balance(wav_file, analysis_channels) {
+            Ok(Some(balance)) => {
+                for warning in balance.warnings() {
+                    println!("Warning: {}", warning);
+                    warnings.push(warning);
+                }
+                info_content.push('\n');
+                info_content.push_str(&balance.to_text());
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("Warning: Channel balance analysis failed: {}", e),
+        }
+
+        match audio_analysis::quality_report(wav_file, progress) {
+            Ok(report) => {
+                for warning in report.warnings() {
+                    println!("Warning: {}", warning);
+                    warnings.push(warning);
+                }
+                info_content.push('\n');
+                info_content.push_str(&report.to_text());
+                quality = Some(report);
+            }
+            Err(e) => eprintln!("Warning: Quality analysis failed: {}", e),
+        }
+
+        match loudness::analyze_wav(wav_file, progress) {
+            Ok(analysis) => {
+                let mut bounds = vec![groove_in];
+                bounds.extend(valleys.iter().map(|v| v.position_seconds));
+                bounds.push(groove_out);
+                let tracks: Vec<(f64, f64)> = bounds.windows(2).map(|w| (w[0], w[1])).collect();
+                info_content.push('\n');
+                info_content.push_str(&analysis.to_text(&tracks));
+                loudness_analysis = Some(analysis);
+            }
+            Err(e) => eprintln!("Warning: Loudness analysis failed: {}", e),
+        }
         
         match cuefile::write_info_file(wav_file, &info_content, has_metadata_match) {
             Ok(info_path) => {
@@ -1475,10 +1885,49 @@ fn process_file(
         }
     }
 
+    if artist != "Unknown Artist" {
+        let identification = Identification {
+            artist: artist.clone(),
+            album: album_title.clone(),
+            release: side_result.as_ref().map(|r| r.release_info.clone()).or_else(|| {
+                release_tags.musicbrainz_release_id.as_ref().map(|id| format!("https://musicbrainz.org/release/{}", id))
+            }),
+            year: release_tags.year,
+            side: side_result.as_ref().map(|r| r.side_label).filter(|c| c.is_ascii_uppercase()),
+        };
+        catalog::update(|catalog| catalog.set_identification(wav_file, &identification));
+    }
+
     // Rename files unless --no-rename was specified, and we have valid album info
-    if rename && artist != "Unknown Artist" && album_title != "Unknown Album" {
-        rename_recording(wav_file, &artist, &album_title);
-    } else if rename && artist == "Unknown Artist" {
+    let mut renamed = None;
+    if let Some(template) = rename_template.filter(|_| artist != "Unknown Artist" && album_title != "Unknown Album") {
+        let side_label = side_result.as_ref().map(|r| r.side_label).filter(|c| c.is_ascii_uppercase());
+        renamed = rename_recording(wav_file, &artist, &album_title, side_label, release_tags.year, template);
+    } else if rename_template.is_some() && artist == "Unknown Artist" {
         println!("Skipping rename: no album identification available");
     }
+    // The report is written for the renamed recording
+    let wav_file = renamed.as_deref().unwrap_or(wav_file);
+
+    let file_report = write_report.then(|| {
+        let mut file_report = FileReport::new(wav_file, &analysis, (groove_in, groove_out), &valleys, &track_names);
+        if artist != "Unknown Artist" {
+            file_report.artist = artist.clone();
+            file_report.album = album_title.clone();
+        }
+        file_report.release_info = mb_info.clone();
+        file_report.release_tags = release_tags.clone();
+        file_report.songs = identified_songs.clone();
+        file_report.quality = quality;
+        if let Some(analysis) = &loudness_analysis {
+            file_report.set_loudness(analysis);
+        }
+        file_report.warnings = warnings;
+        match report::write_file_report(&file_report) {
+            Ok(path) => println!("Report created: {}", path.display()),
+            Err(e) => eprintln!("Warning: Failed to write report: {}", e),
+        }
+        file_report
+    });
+    file_report
 }
\ No newline at end of file
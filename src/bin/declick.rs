@@ -0,0 +1,171 @@
+//! Remove clicks and pops from recorded sides.
+//!
+//! Writes a cleaned copy next to each input file (`<name>.declicked.wav`)
+//! unless given with `--output`. The original recording is never modified.
+//!
+//! Usage:
+//!     declick [--method derivative|wavelet] [--threshold N] [--max-length MS] file.wav ...
+
+use std::env;
+use std::process;
+
+use autorec::declick::{self, DeclickMethod, DeclickSettings};
+
+fn print_usage() {
+    println!("Remove clicks and pops from vinyl recordings");
+    println!();
+    println!("Usage: declick [OPTIONS] <FILE.wav> [FILE2.wav ...]");
+    println!();
+    println!("Options:");
+    println!("  --method <METHOD>        Click detector:");
+    println!("                             derivative - second difference vs. local noise (default)");
+    println!("                             wavelet    - Haar wavelet details, spares bright music");
+    println!("  --threshold <N>          Detection threshold in multiples of the local noise");
+    println!("                             (default: 8, lower finds more clicks)");
+    println!("  --max-length <MS>        Longest event treated as a click in ms (default: 2)");
+    println!("  --output <FILE>, -o      Output file (only with a single input file)");
+    println!("                             (default: <name>.declicked.wav)");
+    println!("  --dry-run                Only count the clicks, don't write a file");
+    println!("  --verbose, -v            List the position of every click");
+    println!("  --help                   Show this help message");
+    println!();
+    println!("Examples:");
+    println!("  declick side_a.1.wav");
+    println!("  declick --method wavelet --threshold 6 side_a.1.wav side_b.2.wav");
+}
+
+fn format_timestamp(seconds: f64) -> String {
+    let mins = (seconds / 60.0) as u32;
+    let secs = seconds % 60.0;
+    format!("{:02}:{:06.3}", mins, secs)
+}
+
+fn main() {
+    autorec::logging::init_cli();
+    let args: Vec<String> = env::args().collect();
+
+    let mut settings = DeclickSettings::default();
+    let mut output: Option<String> = None;
+    let mut dry_run = false;
+    let mut verbose = false;
+    let mut wav_files: Vec<String> = Vec::new();
+
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--method" => {
+                if i + 1 < args.len() {
+                    settings.method = match DeclickMethod::from_str(&args[i + 1]) {
+                        Some(method) => method,
+                        None => {
+                            eprintln!("Error: Unknown method '{}' (use derivative or wavelet)", args[i + 1]);
+                            process::exit(1);
+                        }
+                    };
+                    i += 1;
+                }
+            }
+            "--threshold" => {
+                if i + 1 < args.len() {
+                    settings.threshold = match args[i + 1].parse::<f64>() {
+                        Ok(t) if t > 0.0 => t,
+                        _ => {
+                            eprintln!("Error: Invalid threshold '{}'", args[i + 1]);
+                            process::exit(1);
+                        }
+                    };
+                    i += 1;
+                }
+            }
+            "--max-length" => {
+                if i + 1 < args.len() {
+                    settings.max_click_ms = match args[i + 1].parse::<f64>() {
+                        Ok(ms) if ms > 0.0 => ms,
+                        _ => {
+                            eprintln!("Error: Invalid click length '{}'", args[i + 1]);
+                            process::exit(1);
+                        }
+                    };
+                    i += 1;
+                }
+            }
+            "--output" | "-o" => {
+                if i + 1 < args.len() {
+                    output = Some(args[i + 1].clone());
+                    i += 1;
+                }
+            }
+            "--dry-run" => dry_run = true,
+            "--verbose" | "-v" => verbose = true,
+            "--help" | "-h" => {
+                print_usage();
+                process::exit(0);
+            }
+            arg if !arg.starts_with("--") => wav_files.push(arg.to_string()),
+            _ => {
+                eprintln!("Unknown option: {}", args[i]);
+                print_usage();
+                process::exit(1);
+            }
+        }
+        i += 1;
+    }
+
+    if wav_files.is_empty() {
+        print_usage();
+        process::exit(1);
+    }
+    if output.is_some() && wav_files.len() > 1 {
+        eprintln!("Error: --output can only be used with a single WAV file");
+        process::exit(1);
+    }
+
+    let mut failed = 0;
+
+    for wav_file in &wav_files {
+        println!("File: {}", wav_file);
+
+        let target = if dry_run {
+            None
+        } else {
+            Some(output.clone().unwrap_or_else(|| declick::declicked_path(wav_file)))
+        };
+
+        match declick::declick_wav(wav_file, target.as_deref(), &settings) {
+            Ok(report) => {
+                let per_minute = if report.duration > 0.0 {
+                    report.total_clicks() as f64 * 60.0 / report.duration
+                } else {
+                    0.0
+                };
+                println!(
+                    "  {} clicks ({:.1}/min, method: {}), {} longer events left alone",
+                    report.total_clicks(),
+                    per_minute,
+                    settings.method.as_str(),
+                    report.skipped
+                );
+                for (ch, count) in report.clicks_per_channel.iter().enumerate() {
+                    println!("  Channel {}: {}", ch, count);
+                }
+                if verbose {
+                    for position in &report.positions {
+                        println!("    {}", format_timestamp(*position));
+                    }
+                }
+                if let Some(target) = target {
+                    println!("  Wrote {}", target);
+                }
+            }
+            Err(e) => {
+                eprintln!("  Error: {}", e);
+                failed += 1;
+            }
+        }
+        println!();
+    }
+
+    if failed > 0 {
+        process::exit(1);
+    }
+}
@@ -4,6 +4,7 @@ use autorec::album_identifier::IdentifiedSong;
 use autorec::discogs;
 
 fn main() {
+    autorec::logging::init_cli();
     println!("=== Discogs find_album_by_songs — 4 Side Test ===\n");
 
     let test_cases: Vec<(&str, f64, Vec<(&str, &str, &str)>, char)> = vec![
@@ -39,6 +40,10 @@ fn main() {
                 artist: artist.to_string(),
                 title: title.to_string(),
                 album: Some(album.to_string()),
+                match_offsets: Vec::new(),
+                song_offset: None,
+                recording_id: None,
+                confidence: None,
             })
             .collect();
 
@@ -14,6 +14,7 @@ use autorec::album_identifier::IdentifiedSong;
 use autorec::discogs;
 
 fn main() {
+    autorec::logging::init_cli();
     let args: Vec<String> = std::env::args().collect();
     let verbose = args.iter().any(|a| a == "--verbose" || a == "-v");
 
@@ -30,7 +31,7 @@ fn main() {
         let wav_file = args.get(1).expect("Usage: discogs_lookup <WAV_FILE> [--verbose]");
         
         println!("Identifying songs in {}...", wav_file);
-        let (result, _log) = autorec::album_identifier::identify_songs(wav_file, None);
+        let (result, _log) = autorec::album_identifier::identify_songs(wav_file, None, &mut autorec::progress::NoProgress);
         let songs = result.expect("Song identification failed");
         
         // Get duration
@@ -218,6 +219,10 @@ fn parse_songs(s: &str) -> Vec<IdentifiedSong> {
                 artist: parts.first().unwrap_or(&"Unknown").to_string(),
                 title: parts.get(1).unwrap_or(&"Unknown").to_string(),
                 album: parts.get(2).map(|s| s.to_string()),
+                match_offsets: Vec::new(),
+                song_offset: None,
+                recording_id: None,
+                confidence: None,
             }
         })
         .collect()
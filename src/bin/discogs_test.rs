@@ -6,6 +6,7 @@ use autorec::discogs;
 use autorec::rate_limiter::RateLimiter;
 
 fn main() {
+    autorec::logging::init_cli();
     let release_id: u64 = std::env::args()
         .skip_while(|a| a != "--release-id")
         .nth(1)
@@ -15,6 +15,7 @@ use std::process;
 
 use autorec::album_identifier::{self, IdentifiedSong};
 use autorec::lookup::{self, AlbumIdentifier, AlbumResult, SideInfo, DiscogsBackend, MusicBrainzBackend};
+use autorec::progress::NoProgress;
 use autorec::wavfile;
 
 struct FileData {
@@ -24,6 +25,7 @@ struct FileData {
 }
 
 fn main() {
+    autorec::logging::init_cli();
     let args: Vec<String> = env::args().skip(1).collect();
 
     let verbose = args.iter().any(|a| a == "--verbose" || a == "-v");
@@ -56,7 +58,7 @@ fn main() {
         let short_name = short(wav_file);
         println!("Identifying: {} ({:.0}s)", short_name, duration);
 
-        let (result, _log) = album_identifier::identify_songs(wav_file, None);
+        let (result, _log) = album_identifier::identify_songs(wav_file, None, &mut NoProgress);
         let songs = match result {
             Ok(s) => s,
             Err(e) => {
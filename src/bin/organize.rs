@@ -0,0 +1,199 @@
+//! File finished album sessions into the music library.
+//!
+//! Looks for session directories (with a `session.toml`) in the capture
+//! directory and moves each complete one, recordings, FLAC copies, CUE
+//! sheets, reports and artwork, into `<library>/Artist/Album (Year)`.
+//! Sessions that are not identified yet or lack a CUE sheet are left alone.
+//!
+//! Usage:
+//!     organize [--library DIR] [--name TEMPLATE] [--on-collision MODE]
+//!              [--dry-run] capture_dir ...
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use autorec::catalog;
+use autorec::config::{Config, OrganizeConfig};
+use autorec::filename_template::FilenameTemplate;
+use autorec::organize::{self, Collision, COLLISION_NAMES, DEFAULT_LIBRARY_TEMPLATE};
+use autorec::session::{Session, SESSION_FILE_NAME};
+
+fn print_usage() {
+    println!("Move finished album sessions into the music library");
+    println!();
+    println!("Usage: organize [OPTIONS] <CAPTURE_DIR> [DIR2 ...]");
+    println!();
+    println!("Options:");
+    println!("  --library <DIR>          Root directory of the music library");
+    println!("                             (default: library_dir in [organize])");
+    println!("  --name <TEMPLATE>        Album directory below the library");
+    println!("                             (default: \"{{album_artist}}/{{album}} ({{year}})\")");
+    println!("  --on-collision <MODE>    When a file exists in the library:");
+    println!("                             skip      - leave the session in place (default)");
+    println!("                             number    - use \"Album (1977) (2)\"");
+    println!("                             overwrite - replace the files");
+    println!("  --dry-run                Only show what would be moved");
+    println!("  --help                   Show this help message");
+    println!();
+    println!("A session is filed once its album is identified and every recording");
+    println!("has a CUE sheet (run cue_creator first).");
+    println!();
+    println!("Examples:");
+    println!("  organize --library /music ~/recordings");
+    println!("  organize --dry-run --name \"{{album_artist}}/{{year}} - {{album}}\" ~/recordings");
+}
+
+fn parse_collision(name: &str) -> Collision {
+    Collision::from_str(name).unwrap_or_else(|| {
+        eprintln!("Error: Invalid collision mode '{}' (use {})", name, COLLISION_NAMES.join(", "));
+        process::exit(1);
+    })
+}
+
+fn main() {
+    autorec::logging::init_cli();
+    let args: Vec<String> = env::args().collect();
+
+    let config = Config::load()
+        .map(|c| c.organize.unwrap_or_default())
+        .unwrap_or_else(|e| {
+            eprintln!("Warning: Ignoring config file: {}", e);
+            OrganizeConfig::default()
+        });
+
+    let mut library: Option<PathBuf> = config.library_dir.as_ref().map(PathBuf::from);
+    let mut template_str: Option<String> = config.filename_template.clone();
+    let mut collision = config.collision.as_deref().map(parse_collision).unwrap_or_default();
+    let mut dry_run = false;
+    let mut capture_dirs: Vec<PathBuf> = Vec::new();
+
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--library" => {
+                if i + 1 < args.len() {
+                    library = Some(PathBuf::from(&args[i + 1]));
+                    i += 1;
+                }
+            }
+            "--name" => {
+                if i + 1 < args.len() {
+                    template_str = Some(args[i + 1].clone());
+                    i += 1;
+                }
+            }
+            "--on-collision" => {
+                if i + 1 < args.len() {
+                    collision = parse_collision(&args[i + 1]);
+                    i += 1;
+                }
+            }
+            "--dry-run" => dry_run = true,
+            "--help" | "-h" => {
+                print_usage();
+                process::exit(0);
+            }
+            arg if !arg.starts_with("--") => capture_dirs.push(PathBuf::from(arg)),
+            _ => {
+                eprintln!("Unknown option: {}", args[i]);
+                print_usage();
+                process::exit(1);
+            }
+        }
+        i += 1;
+    }
+
+    if capture_dirs.is_empty() {
+        print_usage();
+        process::exit(1);
+    }
+    let library = library.unwrap_or_else(|| {
+        eprintln!("Error: No music library given (--library or library_dir in [organize])");
+        process::exit(1);
+    });
+    let template = FilenameTemplate::from_config(
+        template_str.as_deref(),
+        config.filename_sanitize.as_deref(),
+        DEFAULT_LIBRARY_TEMPLATE,
+    )
+    .unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    });
+
+    let mut filed = 0;
+    let mut failed = 0;
+
+    for capture_dir in &capture_dirs {
+        let sessions = organize::find_sessions(capture_dir);
+        if sessions.is_empty() {
+            println!("No sessions found in {}", capture_dir.display());
+        }
+        for dir in sessions {
+            let session = match Session::load(&dir.join(SESSION_FILE_NAME)) {
+                Ok(session) => session,
+                Err(e) => {
+                    eprintln!("{}: {}", dir.display(), e);
+                    failed += 1;
+                    continue;
+                }
+            };
+            if let Some(reason) = organize::incomplete_reason(&dir, &session) {
+                println!("{}: not finished ({}), skipped", dir.display(), reason);
+                continue;
+            }
+            let plan = match organize::plan(&dir, &library, &template, collision) {
+                Ok(plan) => plan,
+                Err(e) => {
+                    eprintln!("{}: {}, skipped", dir.display(), e);
+                    failed += 1;
+                    continue;
+                }
+            };
+
+            println!("{} -> {}", dir.display(), plan.target.display());
+            for (from, _) in &plan.moves {
+                println!("  {}", from.file_name().unwrap_or_default().to_string_lossy());
+            }
+            if dry_run {
+                continue;
+            }
+            match plan.execute() {
+                Ok(()) => {
+                    filed += 1;
+                    catalog::update(|catalog| {
+                        for (from, to) in &plan.moves {
+                            catalog.move_file(&from.to_string_lossy(), &to.to_string_lossy())?;
+                        }
+                        Ok(())
+                    });
+                    // Remove the session directory if nothing else is left in it
+                    if !capture_dirs.iter().any(|c| c == &dir) && is_empty_dir(&dir) {
+                        let _ = fs::remove_dir(&dir);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("  Error: {}", e);
+                    failed += 1;
+                }
+            }
+        }
+    }
+
+    if dry_run {
+        println!();
+        println!("Dry run: no files were moved");
+    } else if filed > 0 {
+        println!();
+        println!("{} album(s) filed into {}", filed, library.display());
+    }
+    if failed > 0 {
+        process::exit(1);
+    }
+}
+
+fn is_empty_dir(dir: &Path) -> bool {
+    fs::read_dir(dir).map(|mut entries| entries.next().is_none()).unwrap_or(false)
+}
@@ -7,10 +7,11 @@
 //! - Adaptive parameter changes
 //! - Summary statistics
 
-use autorec::{pause_detector::AdaptivePauseDetector, SampleFormat};
+use autorec::pause_detector::AdaptivePauseDetector;
+use autorec::wavfile::read_wav_header;
 use std::env;
 use std::fs::File;
-use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::io::{BufReader, Read};
 use std::path::Path;
 use std::process;
 
@@ -39,62 +40,6 @@ fn print_usage() {
     println!("  - Adjust --pause-duration for shorter/longer pause requirements");
 }
 
-#[derive(Debug)]
-struct WavHeader {
-    sample_rate: u32,
-    num_channels: u16,
-    bits_per_sample: u16,
-    data_size: u32,
-}
-
-fn read_wav_header(file: &mut BufReader<File>) -> Result<WavHeader, String> {
-    let mut buf = [0u8; 44];
-    file.read_exact(&mut buf).map_err(|e| format!("Failed to read WAV header: {}", e))?;
-    
-    // Check RIFF header
-    if &buf[0..4] != b"RIFF" {
-        return Err("Not a valid WAV file (missing RIFF header)".to_string());
-    }
-    
-    if &buf[8..12] != b"WAVE" {
-        return Err("Not a valid WAV file (missing WAVE marker)".to_string());
-    }
-    
-    // Parse format chunk
-    if &buf[12..16] != b"fmt " {
-        return Err("Invalid WAV format chunk".to_string());
-    }
-    
-    let num_channels = u16::from_le_bytes([buf[22], buf[23]]);
-    let sample_rate = u32::from_le_bytes([buf[24], buf[25], buf[26], buf[27]]);
-    let bits_per_sample = u16::from_le_bytes([buf[34], buf[35]]);
-    
-    // Find data chunk (might not be at offset 36)
-    file.seek(SeekFrom::Start(36)).map_err(|e| format!("Seek error: {}", e))?;
-    
-    loop {
-        let mut chunk_header = [0u8; 8];
-        if file.read_exact(&mut chunk_header).is_err() {
-            return Err("Could not find data chunk".to_string());
-        }
-        
-        let chunk_size = u32::from_le_bytes([chunk_header[4], chunk_header[5], chunk_header[6], chunk_header[7]]);
-        
-        if &chunk_header[0..4] == b"data" {
-            let data_size = chunk_size;
-            return Ok(WavHeader {
-                sample_rate,
-                num_channels,
-                bits_per_sample,
-                data_size,
-            });
-        }
-        
-        // Skip this chunk
-        file.seek(SeekFrom::Current(chunk_size as i64)).map_err(|e| format!("Seek error: {}", e))?;
-    }
-}
-
 fn format_timestamp(seconds: f64) -> String {
     let mins = (seconds / 60.0) as u32;
     let secs = seconds % 60.0;
@@ -102,6 +47,7 @@ fn format_timestamp(seconds: f64) -> String {
 }
 
 fn main() {
+    autorec::logging::init_cli();
     let args: Vec<String> = env::args().collect();
     
     if args.len() < 2 {
@@ -204,11 +150,10 @@ fn main() {
     }
     
     // Determine format
-    let format = match header.bits_per_sample {
-        16 => SampleFormat::S16,
-        32 => SampleFormat::S32,
-        _ => {
-            eprintln!("Error: Unsupported bit depth: {}. Only 16 and 32 bit supported.", header.bits_per_sample);
+    let format = match header.sample_format() {
+        Some(format) => format,
+        None => {
+            eprintln!("Error: Unsupported bit depth: {}. Only 16, 24 and 32 bit supported.", header.bits_per_sample);
             process::exit(1);
         }
     };
@@ -255,20 +200,7 @@ fn main() {
                     break;
                 }
                 
-                let sample = match format {
-                    SampleFormat::S16 => {
-                        let s = i16::from_le_bytes([buffer[byte_offset], buffer[byte_offset + 1]]);
-                        s as i32
-                    }
-                    SampleFormat::S32 => {
-                        i32::from_le_bytes([
-                            buffer[byte_offset],
-                            buffer[byte_offset + 1],
-                            buffer[byte_offset + 2],
-                            buffer[byte_offset + 3],
-                        ])
-                    }
-                };
+                let sample = format.decode_sample(&buffer[byte_offset..byte_offset + bytes_per_sample]);
                 audio_data[ch].push(sample);
             }
         }
@@ -2,6 +2,7 @@ use autorec::audio_stream::discovery;
 use std::process;
 
 fn main() {
+    autorec::logging::init_cli();
     let args: Vec<String> = std::env::args().collect();
     let show_help = args.len() > 1 && (args[1] == "-h" || args[1] == "--help");
     
@@ -40,7 +41,7 @@ fn main() {
     }
     
     // Display sources grouped by backend
-    for backend in ["pipewire", "pwpipe", "alsa", "file"] {
+    for backend in ["pipewire", "pwpipe", "alsa", "pulse", "file"] {
         if let Some(sources) = by_backend.get(backend) {
             if filter_backend.is_none() || filter_backend.as_ref() == Some(&backend.to_string()) {
                 println!("{}:", backend.to_uppercase());
@@ -74,6 +75,7 @@ fn print_help() {
     println!("    pipewire    Native PipeWire audio sources");
     println!("    pwpipe      PipeWire sources (subprocess mode)");
     println!("    alsa        ALSA audio devices");
+    println!("    pulse       PulseAudio sources (via pactl/parec)");
     println!("    file        Audio files in current directory");
     println!();
     println!("EXAMPLES:");
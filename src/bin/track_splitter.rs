@@ -0,0 +1,417 @@
+//! Split a recorded side into one file per track using its CUE sheet.
+//!
+//! The CUE file is looked up next to the WAV file (`.cue`, then `.guess.cue`)
+//! unless given with `--cue`. Output files are named from the CUE
+//! TITLE/PERFORMER fields, e.g. `01 - Artist - Title.wav` (or by the
+//! template given with `--name` or `filename_template`), and tagged with
+//! the album data of the CUE sheet. FLAC and MP3 files also get ReplayGain
+//! track and album tags. Album data and track titles missing
+//! from the CUE sheet are taken from the `session.toml` that cue_creator
+//! writes next to the recordings.
+//!
+//! With `--trim-lead-in`/`--trim-lead-out` the first track starts shortly
+//! before groove-in and the last one ends shortly after groove-out, instead
+//! of carrying minutes of run-in or run-out groove. `--attenuate-needle-drop`
+//! filters the thump of the lowered stylus out of the first track.
+//! `--correct-speed` resamples the tracks by the deck's speed drift that
+//! cue_creator measured (REM SPEED_CORRECTION).
+//!
+//! Usage:
+//!     track_splitter [--cue FILE] [--output-dir DIR] [--gap MODE] [--format FMT]
+//!                    [--rate HZ] [--bits N] [--cd] [--normalize MODE]
+//!                    [--normalize-peak DBFS] [--no-replaygain]
+//!                    [--trim] [--trim-lead-in] [--trim-lead-out] [--trim-pad SEC]
+//!                    [--attenuate-needle-drop] [--correct-speed]
+//!                    [--name TEMPLATE] [--progress MODE]
+//!                    file.wav ...
+
+use std::env;
+use std::path::PathBuf;
+use std::process;
+
+use autorec::config::{Config, SplitterConfig};
+use autorec::cuefile;
+use autorec::progress;
+use autorec::session::Session;
+use autorec::filename_template::{FilenameTemplate, DEFAULT_TRACK_TEMPLATE};
+use autorec::splitter::{self, Delivery, GapMode, Normalize, Trim};
+use autorec::tagging::OutputFormat;
+
+fn print_usage() {
+    println!("Split recordings into tracks using their CUE sheets");
+    println!();
+    println!("Usage: track_splitter [OPTIONS] <FILE.wav> [FILE2.wav ...]");
+    println!();
+    println!("Options:");
+    println!("  --cue <FILE>             CUE sheet to use (only with a single WAV file)");
+    println!("                             (default: <name>.cue or <name>.guess.cue)");
+    println!("  --output-dir <DIR>       Directory for the track files");
+    println!("                             (default: <name>/ next to the WAV file)");
+    println!("  --gap <MODE>             Where the gap before a track (INDEX 00) goes:");
+    println!("                             append  - end of the previous track (default)");
+    println!("                             prepend - start of the next track");
+    println!("                             discard - dropped");
+    println!("  --format <FORMAT>        Track file format: wav (default), flac or mp3");
+    println!("                             (flac and mp3 are encoded with ffmpeg)");
+    println!("  --rate <HZ>              Resample the tracks to this rate (default: as recorded)");
+    println!("  --bits <N>               Bit depth of the tracks: 16, 24 or 32 (default: as recorded)");
+    println!("  --cd                     Same as --rate 44100 --bits 16");
+    println!("  --mono                   Average the channels into one (for 78s)");
+    println!("  --dither, --no-dither    Dither when reducing the bit depth (default: on)");
+    println!("  --normalize <MODE>       Change the level of the tracks:");
+    println!("                             off   - keep the recorded level (default)");
+    println!("                             album - one gain for all tracks to -18 LUFS");
+    println!("                             track - each track to -18 LUFS");
+    println!("                             (true peaks are kept below -1 dBTP)");
+    println!("  --normalize-peak <DBFS>  With --normalize: bring the true peak to this level");
+    println!("                             instead of the loudness to -18 LUFS");
+    println!("  --replaygain, --no-replaygain");
+    println!("                           Measure loudness and write ReplayGain tags (default: on)");
+    println!("                             (only FLAC and MP3 files get ReplayGain tags)");
+    println!("  --trim-lead-in           Start the first track at groove-in instead of 0:00");
+    println!("  --trim-lead-out          End the last track at groove-out instead of the end");
+    println!("                             of the recording");
+    println!("  --trim                   Same as --trim-lead-in --trim-lead-out");
+    println!("  --trim-pad <SEC>         Groove noise kept before groove-in and after");
+    println!("                             groove-out when trimming (default: {})", splitter::DEFAULT_TRIM_PAD_SECONDS);
+    println!("  --attenuate-needle-drop  Filter the needle-drop thump (REM NEEDLE_DROP) out of");
+    println!("                             the first track, keeping the groove noise");
+    println!("  --correct-speed          Resample the tracks by the deck's speed drift");
+    println!("                             (REM SPEED_CORRECTION from cue_creator)");
+    println!("  --name <TEMPLATE>        Track file names, relative to the output directory");
+    println!("                             (default: \"{}\")", DEFAULT_TRACK_TEMPLATE);
+    println!("                             e.g. \"{{artist}}/{{album}}/{{track:02}} {{title}}.{{ext}}\"");
+    println!("  --progress <MODE>        Report progress on stderr: none (default), bar or json");
+    println!("  --dry-run                Only show the planned tracks");
+    println!("  --help                   Show this help message");
+    println!();
+    println!("Examples:");
+    println!("  track_splitter side_a.1.wav");
+    println!("  track_splitter --gap prepend --output-dir /music/album side_a.1.wav");
+    println!("  track_splitter --format flac side_a.1.wav side_b.2.wav");
+    println!("  track_splitter --cd --format flac side_a.1.wav");
+    println!("  track_splitter --normalize album --normalize-peak -1 side_a.1.wav");
+    println!("  track_splitter --trim --trim-pad 0.5 side_a.1.wav");
+    println!();
+    println!("Defaults for these options can be set in the [splitter] section of the");
+    println!("autorecord config file.");
+}
+
+/// Defaults from the `[splitter]` section of the config file
+fn apply_config(
+    config: &SplitterConfig,
+    gap_mode: &mut GapMode,
+    format: &mut OutputFormat,
+    delivery: &mut Delivery,
+    trim: &mut Trim,
+) -> Result<(), String> {
+    if let Some(ref gap) = config.gap {
+        *gap_mode = GapMode::from_str(gap).ok_or_else(|| format!("unknown gap mode '{}'", gap))?;
+    }
+    if let Some(ref f) = config.format {
+        *format = OutputFormat::from_str(f).ok_or_else(|| format!("unknown format '{}'", f))?;
+    }
+    if let Some(ref normalize) = config.normalize {
+        delivery.normalize = Normalize::from_str(normalize)
+            .ok_or_else(|| format!("unknown normalization '{}'", normalize))?;
+    }
+    delivery.sample_rate = config.rate.or(delivery.sample_rate);
+    delivery.bits_per_sample = config.bits.or(delivery.bits_per_sample);
+    delivery.dither = config.dither.unwrap_or(delivery.dither);
+    delivery.replay_gain = config.replay_gain.unwrap_or(delivery.replay_gain);
+    delivery.peak_target = config.normalize_peak.or(delivery.peak_target);
+    delivery.mono = config.mono.unwrap_or(delivery.mono);
+    delivery.attenuate_needle_drop = config.attenuate_needle_drop.unwrap_or(delivery.attenuate_needle_drop);
+    delivery.correct_speed = config.correct_speed.unwrap_or(delivery.correct_speed);
+    delivery.filenames = FilenameTemplate::from_config(
+        config.filename_template.as_deref(),
+        config.filename_sanitize.as_deref(),
+        DEFAULT_TRACK_TEMPLATE,
+    )
+    .map_err(|e| e.to_string())?;
+    trim.lead_in = config.trim_lead_in.unwrap_or(trim.lead_in);
+    trim.lead_out = config.trim_lead_out.unwrap_or(trim.lead_out);
+    if let Some(pad) = config.trim_pad {
+        if pad < 0.0 {
+            return Err(format!("negative trim_pad {}", pad));
+        }
+        trim.pad = pad;
+    }
+    Ok(())
+}
+
+fn format_timestamp(seconds: f64) -> String {
+    let mins = (seconds / 60.0) as u32;
+    let secs = seconds % 60.0;
+    format!("{:02}:{:05.2}", mins, secs)
+}
+
+fn main() {
+    autorec::logging::init_cli();
+    let args: Vec<String> = env::args().collect();
+
+    let config = Config::load()
+        .map(|c| c.splitter.unwrap_or_default())
+        .unwrap_or_else(|e| {
+            eprintln!("Warning: Ignoring config file: {}", e);
+            SplitterConfig::default()
+        });
+
+    let mut cue_override: Option<PathBuf> = None;
+    let mut output_dir: Option<PathBuf> = config.output_dir.as_ref().map(PathBuf::from);
+    let mut gap_mode = GapMode::Append;
+    let mut format = OutputFormat::Wav;
+    let mut delivery = Delivery::default();
+    let mut trim = Trim::default();
+    if let Err(e) = apply_config(&config, &mut gap_mode, &mut format, &mut delivery, &mut trim) {
+        eprintln!("Error: Invalid [splitter] config: {}", e);
+        process::exit(1);
+    }
+    let mut dry_run = false;
+    let mut progress = progress::create("none").unwrap();
+    let mut wav_files: Vec<String> = Vec::new();
+
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--cue" => {
+                if i + 1 < args.len() {
+                    cue_override = Some(PathBuf::from(&args[i + 1]));
+                    i += 1;
+                }
+            }
+            "--output-dir" | "-o" => {
+                if i + 1 < args.len() {
+                    output_dir = Some(PathBuf::from(&args[i + 1]));
+                    i += 1;
+                }
+            }
+            "--gap" => {
+                if i + 1 < args.len() {
+                    gap_mode = match GapMode::from_str(&args[i + 1]) {
+                        Some(mode) => mode,
+                        None => {
+                            eprintln!("Error: Unknown gap mode '{}' (use append, prepend or discard)", args[i + 1]);
+                            process::exit(1);
+                        }
+                    };
+                    i += 1;
+                }
+            }
+            "--format" => {
+                if i + 1 < args.len() {
+                    format = match OutputFormat::from_str(&args[i + 1]) {
+                        Some(f) => f,
+                        None => {
+                            eprintln!("Error: Unknown format '{}' (use wav, flac or mp3)", args[i + 1]);
+                            process::exit(1);
+                        }
+                    };
+                    i += 1;
+                }
+            }
+            "--normalize" => {
+                if i + 1 < args.len() {
+                    delivery.normalize = match Normalize::from_str(&args[i + 1]) {
+                        Some(mode) => mode,
+                        None => {
+                            eprintln!("Error: Unknown normalization '{}' (use album, track or off)", args[i + 1]);
+                            process::exit(1);
+                        }
+                    };
+                    i += 1;
+                }
+            }
+            "--normalize-peak" => {
+                if i + 1 < args.len() {
+                    delivery.peak_target = match args[i + 1].parse::<f64>() {
+                        Ok(db) if db <= 0.0 => Some(db),
+                        _ => {
+                            eprintln!("Error: Invalid peak level '{}' (dBFS, at most 0)", args[i + 1]);
+                            process::exit(1);
+                        }
+                    };
+                    i += 1;
+                }
+            }
+            "--trim-pad" => {
+                if i + 1 < args.len() {
+                    trim.pad = match args[i + 1].parse::<f64>() {
+                        Ok(pad) if pad >= 0.0 => pad,
+                        _ => {
+                            eprintln!("Error: Invalid trim pad '{}' (seconds)", args[i + 1]);
+                            process::exit(1);
+                        }
+                    };
+                    i += 1;
+                }
+            }
+            "--rate" => {
+                if i + 1 < args.len() {
+                    delivery.sample_rate = match args[i + 1].parse::<u32>() {
+                        Ok(rate) if rate > 0 => Some(rate),
+                        _ => {
+                            eprintln!("Error: Invalid sample rate '{}'", args[i + 1]);
+                            process::exit(1);
+                        }
+                    };
+                    i += 1;
+                }
+            }
+            "--bits" => {
+                if i + 1 < args.len() {
+                    delivery.bits_per_sample = match args[i + 1].parse::<u16>() {
+                        Ok(bits) if matches!(bits, 16 | 24 | 32) => Some(bits),
+                        _ => {
+                            eprintln!("Error: Invalid bit depth '{}' (use 16, 24 or 32)", args[i + 1]);
+                            process::exit(1);
+                        }
+                    };
+                    i += 1;
+                }
+            }
+            "--progress" => {
+                if i + 1 < args.len() {
+                    progress = match progress::create(&args[i + 1]) {
+                        Some(p) => p,
+                        None => {
+                            eprintln!("Error: Unknown progress mode '{}' (use {})", args[i + 1], progress::PROGRESS_NAMES.join(", "));
+                            process::exit(1);
+                        }
+                    };
+                    i += 1;
+                }
+            }
+            "--name" => {
+                if i + 1 < args.len() {
+                    delivery.filenames = match FilenameTemplate::parse(&args[i + 1]) {
+                        Ok(template) => template.with_sanitize(delivery.filenames.sanitize()),
+                        Err(e) => {
+                            eprintln!("Error: {}", e);
+                            process::exit(1);
+                        }
+                    };
+                    i += 1;
+                }
+            }
+            "--cd" => {
+                let cd = Delivery::cd();
+                delivery.sample_rate = cd.sample_rate;
+                delivery.bits_per_sample = cd.bits_per_sample;
+            }
+            "--mono" => delivery.mono = true,
+            "--dither" => delivery.dither = true,
+            "--no-dither" => delivery.dither = false,
+            "--replaygain" => delivery.replay_gain = true,
+            "--no-replaygain" => delivery.replay_gain = false,
+            "--trim" => {
+                trim.lead_in = true;
+                trim.lead_out = true;
+            }
+            "--trim-lead-in" => trim.lead_in = true,
+            "--trim-lead-out" => trim.lead_out = true,
+            "--attenuate-needle-drop" => delivery.attenuate_needle_drop = true,
+            "--correct-speed" => delivery.correct_speed = true,
+            "--dry-run" => dry_run = true,
+            "--help" | "-h" => {
+                print_usage();
+                process::exit(0);
+            }
+            arg if !arg.starts_with("--") => wav_files.push(arg.to_string()),
+            _ => {
+                eprintln!("Unknown option: {}", args[i]);
+                print_usage();
+                process::exit(1);
+            }
+        }
+        i += 1;
+    }
+
+    if wav_files.is_empty() {
+        print_usage();
+        process::exit(1);
+    }
+    if cue_override.is_some() && wav_files.len() > 1 {
+        eprintln!("Error: --cue can only be used with a single WAV file");
+        process::exit(1);
+    }
+
+    let mut failed = 0;
+
+    for wav_file in &wav_files {
+        println!("File: {}", wav_file);
+
+        let cue_path = match cue_override.clone().or_else(|| cuefile::find_cue_file(wav_file)) {
+            Some(p) => p,
+            None => {
+                eprintln!("  No CUE file found (run cue_creator first)");
+                failed += 1;
+                continue;
+            }
+        };
+
+        let mut sheet = match cuefile::read_cue_file(&cue_path) {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("  Error: {}", e);
+                failed += 1;
+                continue;
+            }
+        };
+
+        match Session::load_for(wav_file) {
+            Ok(session) => session.complete_sheet(wav_file, &mut sheet),
+            Err(e) => eprintln!("  Warning: Ignoring session file: {}", e),
+        }
+
+        let target_dir = output_dir.clone().unwrap_or_else(|| cuefile::wav_base_path(wav_file));
+        println!("  CUE: {} ({} tracks, gap mode: {})", cue_path.display(), sheet.tracks.len(), gap_mode.as_str());
+        if delivery.normalize != Normalize::Off {
+            match delivery.peak_target {
+                Some(db) => println!("  Normalize: {} peak to {:.1} dBTP", delivery.normalize.as_str(), db),
+                None => println!("  Normalize: {} loudness to -18 LUFS", delivery.normalize.as_str()),
+            }
+        }
+
+        if let Some((start, end)) = sheet.needle_drop.filter(|_| delivery.attenuate_needle_drop) {
+            println!("  Needle drop: {} - {} (filtered out of the first track)", format_timestamp(start), format_timestamp(end));
+        }
+        if let Some(ratio) = sheet.speed_correction.filter(|_| delivery.correct_speed) {
+            println!("  Speed: corrected, tracks {:.1}% of the recorded length", 100.0 / ratio);
+        }
+
+        let mut segments = splitter::plan_tracks(&sheet, gap_mode);
+        let mut file_trim = trim;
+        if trim.lead_out && sheet.groove_out.is_none() {
+            eprintln!("  Warning: Groove-out unknown (run cue_creator again), lead-out not trimmed");
+            file_trim.lead_out = false;
+        }
+        if let Err(e) = splitter::trim_tracks(&mut segments, &sheet, &file_trim) {
+            eprintln!("  Error: {}", e);
+            failed += 1;
+            continue;
+        }
+        for segment in &segments {
+            let end = segment.end.map(format_timestamp).unwrap_or_else(|| "end".to_string());
+            println!("  {} - {}  {}", format_timestamp(segment.start), end, segment.path_from(&delivery.filenames, &sheet, format));
+        }
+
+        if dry_run {
+            println!();
+            continue;
+        }
+
+        match splitter::split_wav(wav_file, &sheet, &segments, &target_dir, format, &delivery, progress.as_mut()) {
+            Ok(files) => println!("  Wrote {} track(s) to {}", files.len(), target_dir.display()),
+            Err(e) => {
+                eprintln!("  Error: {}", e);
+                failed += 1;
+            }
+        }
+        println!();
+    }
+
+    if failed > 0 {
+        process::exit(1);
+    }
+}
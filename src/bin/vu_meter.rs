@@ -1,4 +1,10 @@
-use autorec::{create_input_stream, display_vu_meter, list_targets, parse_audio_address, process_audio_chunk, validate_and_select_target, SampleFormat, VUMeter};
+use autorec::audio_analysis::parse_channel_list;
+use autorec::pipewire_utils::REGEX_TARGET_PREFIX;
+use autorec::config::ResolvedSource;
+use autorec::decibel::{Calibration, LevelScale, LevelUnit};
+use autorec::display::{display_vu_meter_scaled, LevelHistory};
+use autorec::{create_mapped_input_stream, Config, list_targets_filtered, parse_audio_address, process_audio_chunk, validate_and_select_target, SampleFormat, TargetFilter, VUMeter};
+use regex::Regex;
 use std::env;
 use std::process;
 use std::thread;
@@ -11,21 +17,37 @@ fn print_usage() {
     println!();
     println!("Options:");
     println!("  --list-targets           List available PipeWire recording targets and exit");
+    println!("    --json                 Print the target list as JSON (IDs, rates, channels)");
+    println!("    --media-class <CLASS>  Only list targets whose media class contains CLASS");
+    println!("    --name-regex <REGEX>   Only list targets whose name/description matches REGEX");
+    println!("    --active-only          Only list targets that are currently running");
     println!("  --source <SOURCE>        Audio source address:");
     println!("                             pipewire:device or pw:device");
+    println!("                             pipewire:re:<regex> (first matching device)");
     println!("                             alsa:hw:0,0 or alsa:default");
+    println!("                             pulse:<source> or pulse:default (PulseAudio, uses parec)");
+    println!("                             jack:system:capture_1,system:capture_2 (needs --features jack)");
+    println!("                             net:udp://:5004, net:rtp://:5004 or net:tcp://:5004");
+    println!("                               (PCM sent over the network, see net_stream)");
+    println!("                             stdin: or raw:/path/to/fifo (raw PCM in --rate,");
+    println!("                               --channels and --format, e.g. from pw-cat or sox)");
     println!("                             file:path/to/audio.wav");
     println!("                             /path/to/audio.mp3 (auto-detects as file)");
     println!("                             Auto-detects backend if not specified");
     println!("                             (default: auto-detect PipeWire source)");
     println!("  --rate <RATE>            Sample rate (default: 96000)");
     println!("  --channels <CHANNELS>    Number of channels (default: 2)");
-    println!("  --format <FORMAT>        Sample format: s16, s32 (default: s32)");
+    println!("  --channel-map <LIST>     Show only these input channels, e.g. 3,4");
+    println!("  --format <FORMAT>        Sample format: s16, s24, s24_32, s32 (default: s32)");
     println!("  --interval <INTERVAL>    Update interval in seconds (default: 0.2)");
     println!("  --db-range <RANGE>       dB range to display (default: 90)");
     println!("  --max-db <MAX>           Maximum dB (default: 0)");
     println!("  --off-threshold <THRESH> Threshold for on/off detection in dB (default: -60)");
     println!("  --silence-duration <SEC> Duration of silence before signal is considered off (default: 10)");
+    println!("  --sparkline              Show the last 60s of levels under each bar");
+    println!("  --units <UNIT>           Level display unit: dbfs, dbu, dbv (default: dbfs)");
+    println!("  --calibration <DBU>      Analog level in dBu that reads 0 dBFS");
+    println!("                             (default: saved calibration for the source)");
     println!("  --help                   Show this help message");
     println!();
     println!("Examples:");
@@ -34,27 +56,76 @@ fn print_usage() {
     println!("  vu_meter --source hw:1,0  # Auto-detects as ALSA");
     println!("  vu_meter --source /path/to/song.mp3");
     println!("  vu_meter --source file:audio.wav");
+    println!("  vu_meter --source vinyl  # Named source from the autorecord config");
+}
+
+fn parse_channel_map(list: &str) -> Vec<usize> {
+    parse_channel_list(list).unwrap_or_else(|| {
+        eprintln!("Error: Invalid channel map '{}' (use e.g. 3,4)", list);
+        process::exit(1);
+    })
 }
 
 fn main() {
+    autorec::logging::init_cli();
     let args: Vec<String> = env::args().collect();
 
     // Default values
     let mut source: Option<String> = None;
     let mut rate = 96000;
     let mut channels = 2;
+    let mut channel_map: Vec<usize> = Vec::new();
     let mut format = SampleFormat::S32;
     let mut interval = 0.2;
     let mut db_range = 90.0;
     let mut max_db = 0.0;
     let mut off_threshold = -60.0;
     let mut silence_duration = 10.0;
+    let mut level_unit: Option<LevelUnit> = None;
+    let mut calibration_override: Option<f64> = None;
+    let mut show_sparkline = false;
+
+    // Options set explicitly on the command line win over source alias settings
+    let mut rate_given = false;
+    let mut channels_given = false;
+    let mut channel_map_given = false;
+    let mut format_given = false;
+    let mut off_threshold_given = false;
+
+    // Target listing options (applied after all arguments are parsed)
+    let mut list_targets_mode = false;
+    let mut list_json = false;
+    let mut target_filter = TargetFilter::new();
 
     let mut i = 1;
     while i < args.len() {
         match args[i].as_str() {
             "--list-targets" => {
-                process::exit(list_targets());
+                list_targets_mode = true;
+            }
+            "--json" => {
+                list_json = true;
+            }
+            "--active-only" => {
+                target_filter.active_only = true;
+            }
+            "--media-class" => {
+                if i + 1 < args.len() {
+                    target_filter.media_class = Some(args[i + 1].clone());
+                    i += 1;
+                }
+            }
+            "--name-regex" => {
+                if i + 1 < args.len() {
+                    match Regex::new(&args[i + 1]) {
+                        Ok(re) => target_filter.name_regex = Some(re),
+                        Err(e) => {
+                            eprintln!("Error: Invalid --name-regex '{}': {}", args[i + 1], e);
+                            process::exit(1);
+                        }
+                    }
+                    i += 1;
+                }
             }
             "--source" | "--target" => {
                 if i + 1 < args.len() {
@@ -65,18 +136,28 @@ fn main() {
             "--rate" => {
                 if i + 1 < args.len() {
                     rate = args[i + 1].parse().unwrap_or(96000);
+                    rate_given = true;
                     i += 1;
                 }
             }
             "--channels" => {
                 if i + 1 < args.len() {
                     channels = args[i + 1].parse().unwrap_or(2);
+                    channels_given = true;
+                    i += 1;
+                }
+            }
+            "--channel-map" => {
+                if i + 1 < args.len() {
+                    channel_map = parse_channel_map(&args[i + 1]);
+                    channel_map_given = true;
                     i += 1;
                 }
             }
             "--format" => {
                 if i + 1 < args.len() {
                     format = SampleFormat::from_str(&args[i + 1]).unwrap_or(SampleFormat::S32);
+                    format_given = true;
                     i += 1;
                 }
             }
@@ -101,6 +182,7 @@ fn main() {
             "--off-threshold" => {
                 if i + 1 < args.len() {
                     off_threshold = args[i + 1].parse().unwrap_or(-60.0);
+                    off_threshold_given = true;
                     i += 1;
                 }
             }
@@ -110,6 +192,33 @@ fn main() {
                     i += 1;
                 }
             }
+            "--sparkline" => {
+                show_sparkline = true;
+            }
+            "--units" => {
+                if i + 1 < args.len() {
+                    match LevelUnit::from_str(&args[i + 1]) {
+                        Some(unit) => level_unit = Some(unit),
+                        None => {
+                            eprintln!("Error: Unknown level unit '{}' (use dbfs, dbu or dbv)", args[i + 1]);
+                            process::exit(1);
+                        }
+                    }
+                    i += 1;
+                }
+            }
+            "--calibration" => {
+                if i + 1 < args.len() {
+                    match args[i + 1].parse::<f64>() {
+                        Ok(v) => calibration_override = Some(v),
+                        Err(_) => {
+                            eprintln!("Error: Invalid --calibration value '{}'", args[i + 1]);
+                            process::exit(1);
+                        }
+                    }
+                    i += 1;
+                }
+            }
             "--help" | "-h" => {
                 print_usage();
                 process::exit(0);
@@ -123,9 +232,56 @@ fn main() {
         i += 1;
     }
 
+    if list_targets_mode {
+        process::exit(list_targets_filtered(&target_filter, list_json));
+    }
+
+    // Saved calibrations and source aliases (from the autorecord config) apply here too
+    let saved_config = Config::load().unwrap_or_else(|e| {
+        eprintln!("Warning: Ignoring config: {}", e);
+        Config::new()
+    });
+
+    // Resolve a named source alias
+    let mut source_alias_name: Option<String> = None;
+    let resolved = source.as_deref().map(|s| saved_config.resolve_source(s)).transpose().unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    });
+    if let Some(ResolvedSource { alias, format: alias_format, channel_map: alias_channel_map }) = resolved.flatten() {
+        if !rate_given {
+            rate = alias.rate.unwrap_or(rate);
+        }
+        if !channels_given {
+            channels = alias.channels.unwrap_or(channels);
+        }
+        if !channel_map_given {
+            if let Some(map) = alias_channel_map {
+                channel_map = map;
+            }
+        }
+        if !format_given {
+            format = alias_format.unwrap_or(format);
+        }
+        if !off_threshold_given {
+            off_threshold = alias.off_threshold.unwrap_or(off_threshold);
+        }
+        source_alias_name = source.replace(alias.address);
+    }
+
     // Determine the audio source address
     let source_address = if let Some(src) = source {
-        src
+        // Resolve regex targets (pipewire:re:<pattern>) to a concrete node name
+        match parse_audio_address(&src) {
+            Ok((backend, device)) if backend == "pipewire" && device.starts_with(REGEX_TARGET_PREFIX) => {
+                let (selected_target, error_code) = validate_and_select_target(Some(&device), true);
+                if error_code != 0 {
+                    process::exit(error_code);
+                }
+                format!("pipewire:{}", selected_target.unwrap())
+            }
+            _ => src,
+        }
     } else {
         // Try to auto-detect a PipeWire source
         let (selected_target, error_code) = validate_and_select_target(None, true);
@@ -147,7 +303,26 @@ fn main() {
     println!("Using {} backend with device: {}", backend, device);
 
     // Create audio stream
-    let stream = match create_input_stream(&source_address, rate, channels, format) {
+    let calibration = match calibration_override {
+        Some(full_scale_dbu) => Some(Calibration::new(full_scale_dbu)),
+        None => {
+            let names: Vec<&str> = [Some(source_address.as_str()), source_alias_name.as_deref()]
+                .into_iter().flatten().collect();
+            saved_config.calibration_for_any(&names)
+        }
+    };
+    let level_unit = level_unit
+        .or_else(|| saved_config.level_unit.as_deref().and_then(LevelUnit::from_str))
+        .unwrap_or(LevelUnit::Dbfs);
+    let level_scale = match LevelScale::new(level_unit, calibration.as_ref()) {
+        Ok(scale) => scale,
+        Err(e) => {
+            eprintln!("Error: {}. Use --calibration <DBU> to set one.", e);
+            process::exit(1);
+        }
+    };
+
+    let stream = match create_mapped_input_stream(&source_address, rate, channels, format, &channel_map) {
         Ok(s) => s,
         Err(e) => {
             eprintln!("Failed to create audio stream: {}", e);
@@ -156,6 +331,12 @@ fn main() {
     };
 
     // Create VU meter
+    let mut level_history = if show_sparkline {
+        Some(LevelHistory::new(60.0, interval))
+    } else {
+        None
+    };
+
     let mut meter = VUMeter::new(
         stream,
         interval,
@@ -181,7 +362,10 @@ fn main() {
     loop {
         match process_audio_chunk(&mut meter) {
             Some((metrics, _audio_data)) => {
-                display_vu_meter(&metrics, db_range, max_db, None).ok();
+                if let Some(ref mut history) = level_history {
+                    history.push(&metrics);
+                }
+                display_vu_meter_scaled(&metrics, db_range, max_db, None, &level_scale, level_history.as_ref()).ok();
             }
             None => {
                 println!("\nRecording stopped.");
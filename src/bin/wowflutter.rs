@@ -0,0 +1,97 @@
+//! Wow and flutter measurement from a recorded test tone.
+//!
+//! Record the 3150 Hz track of a test record (e.g. DIN 45545) and run the
+//! recording through this tool to check the speed stability of the deck.
+//!
+//! Usage:
+//!     wowflutter [--frequency HZ] file.wav ...
+
+use std::env;
+use std::process;
+
+use autorec::wow_flutter::{self, DIN_TONE_HZ};
+
+fn print_usage() {
+    println!("Measure wow and flutter from a recorded test tone");
+    println!();
+    println!("Usage: wowflutter [OPTIONS] <FILE.wav> [FILE2.wav ...]");
+    println!();
+    println!("Options:");
+    println!("  --frequency <HZ>, -f     Frequency of the test tone (default: 3150)");
+    println!("  --help                   Show this help message");
+    println!();
+    println!("The recording should contain only the test tone; the first 2 seconds");
+    println!("are skipped while the filters settle. At least 5 seconds are needed,");
+    println!("30 seconds or more give stable results for wow.");
+    println!();
+    println!("Examples:");
+    println!("  wowflutter test_3150.wav");
+    println!("  wowflutter --frequency 3000 test_3000.wav");
+}
+
+fn main() {
+    autorec::logging::init_cli();
+    let args: Vec<String> = env::args().collect();
+
+    let mut frequency = DIN_TONE_HZ;
+    let mut wav_files: Vec<String> = Vec::new();
+
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--frequency" | "-f" => {
+                if i + 1 < args.len() {
+                    frequency = match args[i + 1].parse::<f64>() {
+                        Ok(f) if f > 0.0 => f,
+                        _ => {
+                            eprintln!("Error: Invalid frequency '{}'", args[i + 1]);
+                            process::exit(1);
+                        }
+                    };
+                    i += 1;
+                }
+            }
+            "--help" | "-h" => {
+                print_usage();
+                process::exit(0);
+            }
+            arg if !arg.starts_with("--") => wav_files.push(arg.to_string()),
+            _ => {
+                eprintln!("Unknown option: {}", args[i]);
+                print_usage();
+                process::exit(1);
+            }
+        }
+        i += 1;
+    }
+
+    if wav_files.is_empty() {
+        print_usage();
+        process::exit(1);
+    }
+
+    let mut failed = 0;
+
+    for wav_file in &wav_files {
+        println!("File: {}", wav_file);
+        match wow_flutter::analyze_wav(wav_file, frequency) {
+            Ok(result) => {
+                println!("  Measured:             {:.1} s of {:.0} Hz tone", result.duration, result.nominal_frequency);
+                println!("  Mean frequency:       {:.2} Hz", result.mean_frequency);
+                println!("  Speed error:          {:+.2} %", result.speed_error());
+                println!("  W&F DIN/IEC weighted: {:.3} % (peak, 2 sigma)", result.weighted_peak);
+                println!("  W&F weighted RMS:     {:.3} % (JIS/AES)", result.weighted_rms);
+                println!("  W&F unweighted:       {:.3} % (peak, 2 sigma)", result.unweighted_peak);
+            }
+            Err(e) => {
+                eprintln!("  Error: {}", e);
+                failed += 1;
+            }
+        }
+        println!();
+    }
+
+    if failed > 0 {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,1591 @@
+//! Song boundary detection in vinyl side recordings.
+//!
+//! Three-pass algorithm:
+//!   Pass 1: Compute RMS in small windows across the entire file
+//!   Pass 2: Detect groove-in (start of music) and groove-out (end of music)
+//!   Pass 3: Find "valleys" (local minima) that represent song boundaries
+//!           within the music region only
+//!
+//! Passes 1 and 2 produce a [`BoundaryAnalysis`]. Pass 3 comes in three
+//! flavours: [`find_song_boundaries`] works without any metadata (or only
+//! the number of tracks, [`find_boundaries_for_count`]),
+//! [`find_guided_boundaries`] aligns valleys to the track lengths of a
+//! looked-up release ([`align_tracks`]) and [`find_seeded_boundaries`]
+//! searches between songs identified by Shazam or around track marks set
+//! while recording ([`mark_windows`]).
+//!
+//! Vinyl recording characteristics:
+//!   - Groove-in: 0.5-5s of quiet groove noise before music starts
+//!   - Groove-out: can be minutes of quiet at the end after music stops
+//!   - Song boundaries: brief energy dips (not true silence) between tracks
+//!   - No absolute silence: groove noise is always present
+//!   - Needle drop: lowering the stylus often makes a short low-frequency
+//!     thump in the lead-in, which must not be taken for the music
+//!   - Locked groove: if the arm does not lift, the run-out loop repeats
+//!     until the end of the recording (see [`crate::locked_groove`])
+//!
+//! 78 RPM shellac discs ([`Medium::Shellac`]) have far more surface noise,
+//! and the pauses between pieces only fall back to it instead of below it.
+//!
+//! Tape recordings ([`Medium::Tape`]) are different: leader tape at both
+//! ends is quieter than anything else, and the pauses between songs drop to
+//! the level of the tape hiss. Music starts and ends where the level leaves
+//! the hiss, and boundaries are pauses near the hiss level, found with two
+//! thresholds (hysteresis) instead of valley prominence.
+
+use std::fs::File;
+use std::io::BufReader;
+
+use tracing::info;
+
+use crate::album_identifier::BoundaryWindow;
+use crate::audio_analysis;
+use crate::error::{Error, Result};
+use crate::filters::subsonic::SubsonicFilter;
+use crate::filters::AudioFilter;
+use crate::locked_groove::{self, LockedGroove};
+use crate::musicbrainz::ExpectedTrack;
+use crate::progress::ProgressSink;
+use crate::wavfile;
+
+pub use crate::cuefile::Valley;
+
+/// Default RMS window size in milliseconds
+pub const DEFAULT_CHUNK_MS: u32 = 200;
+/// Default smoothing window in seconds
+pub const DEFAULT_SMOOTH_WINDOW_SECONDS: f64 = 3.0;
+/// Default minimum valley depth below the local average in dB
+pub const DEFAULT_MIN_PROMINENCE_DB: f32 = 3.0;
+/// Default minimum song duration in seconds
+pub const DEFAULT_MIN_SONG_SECONDS: f64 = 30.0;
+
+/// Tape: a pause starts when the level falls below the hiss plus this, in dB
+pub const TAPE_PAUSE_ENTER_DB: f32 = 4.0;
+/// Tape: a pause (and the leader and hiss before the music) ends when the
+/// level rises above the hiss plus this, in dB
+pub const TAPE_PAUSE_EXIT_DB: f32 = 10.0;
+/// Tape: shortest pause between songs in seconds
+pub const TAPE_MIN_PAUSE_SECONDS: f64 = 1.0;
+/// Tape: smoothing of the level for pause detection in seconds; longer
+/// windows would fill short pauses with the music around them
+const TAPE_SMOOTH_WINDOW_SECONDS: f64 = 0.6;
+/// Tape: leader tape carries no hiss and is at least this much quieter, in dB
+const LEADER_MARGIN_DB: f32 = 10.0;
+
+/// Vinyl: song boundaries reach at least this far below the noise floor, in dB
+const VINYL_VALLEY_MARGIN_DB: f32 = 5.0;
+/// Shellac: song boundaries may stay this far above the surface noise, in dB
+const SHELLAC_VALLEY_MARGIN_DB: f32 = 3.0;
+
+/// Vinyl: a needle-drop thump rises at least this far above the noise floor, in dB
+const NEEDLE_DROP_MIN_DB: f32 = 15.0;
+/// Vinyl: a needle-drop thump is over within this many seconds
+const NEEDLE_DROP_MAX_SECONDS: f64 = 0.8;
+/// Vinyl: groove noise separating a needle-drop thump from the music, in seconds
+const NEEDLE_DROP_SETTLE_SECONDS: f64 = 0.4;
+/// Upper end of the frequencies of a needle-drop thump in Hz
+pub const NEEDLE_DROP_CUTOFF_HZ: f64 = 150.0;
+/// Share of its energy a needle-drop thump has below [`NEEDLE_DROP_CUTOFF_HZ`]
+const NEEDLE_DROP_MIN_LOW_SHARE: f64 = 0.5;
+
+/// Kind of recording, which decides how music and song boundaries are found
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Medium {
+    /// Records: groove noise, boundaries are dips below the noise floor
+    #[default]
+    Vinyl,
+    /// Cassettes and reel-to-reel: leader tape, hiss and pauses near the hiss
+    Tape,
+    /// 78 RPM shellac discs: loud surface noise, short sides
+    Shellac,
+}
+
+/// Names accepted by [`Medium::from_str`]
+pub const MEDIUM_NAMES: &[&str] = &["vinyl", "tape", "78"];
+
+impl Medium {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "vinyl" | "record" => Some(Medium::Vinyl),
+            "tape" | "cassette" | "reel" => Some(Medium::Tape),
+            "78" | "78rpm" | "shellac" => Some(Medium::Shellac),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Medium::Vinyl => "vinyl",
+            Medium::Tape => "tape",
+            Medium::Shellac => "78",
+        }
+    }
+
+    /// Name of the noise between songs
+    pub fn noise_name(&self) -> &'static str {
+        match self {
+            Medium::Vinyl => "groove noise",
+            Medium::Tape => "tape hiss",
+            Medium::Shellac => "surface noise",
+        }
+    }
+
+    /// Default minimum song duration in seconds; a 78 side holds about
+    /// three minutes, so pieces on it are short
+    pub fn default_min_song_seconds(&self) -> f64 {
+        match self {
+            Medium::Vinyl | Medium::Tape => DEFAULT_MIN_SONG_SECONDS,
+            Medium::Shellac => 15.0,
+        }
+    }
+
+    /// Seconds searched on each side of an expected boundary by
+    /// [`find_guided_boundaries`]; 78s were cut at anything from 70 to 82 RPM,
+    /// so their track lengths are less exact
+    pub fn guided_search_seconds(&self) -> f64 {
+        match self {
+            Medium::Vinyl | Medium::Tape => 10.0,
+            Medium::Shellac => 20.0,
+        }
+    }
+
+    /// Nominal speed in RPM if the medium has only one
+    pub fn nominal_rpm(&self) -> Option<f64> {
+        match self {
+            Medium::Shellac => Some(crate::speed_check::RPM_78),
+            Medium::Vinyl | Medium::Tape => None,
+        }
+    }
+
+    /// Highest level of a song boundary found by [`find_song_boundaries`]
+    fn max_valley_depth_db(&self, noise_floor_db: f32) -> f32 {
+        match self {
+            Medium::Shellac => noise_floor_db + SHELLAC_VALLEY_MARGIN_DB,
+            Medium::Vinyl | Medium::Tape => noise_floor_db - VINYL_VALLEY_MARGIN_DB,
+        }
+    }
+}
+
+fn format_timestamp(seconds: f64) -> String {
+    let mins = (seconds / 60.0) as u32;
+    let secs = seconds % 60.0;
+    format!("{:02}:{:05.2}", mins, secs)
+}
+
+/// Short transient in the lead-in where the stylus was lowered
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NeedleDrop {
+    /// Start of the thump in seconds
+    pub start: f64,
+    /// End of the thump in seconds
+    pub end: f64,
+    /// Loudest RMS window of the thump in dB
+    pub peak_db: f32,
+    /// Checked in the audio to be a low-frequency thump (see
+    /// [`BoundaryAnalysis::confirm_needle_drop`]); the RMS curve alone does
+    /// not tell it from a click or the first note
+    pub confirmed: bool,
+}
+
+/// Result of passes 1 and 2: the RMS curve, signal levels and music region
+#[derive(Debug, Clone)]
+pub struct BoundaryAnalysis {
+    /// RMS in dB per window
+    pub rms_values: Vec<f32>,
+    /// RMS after smoothing, without a needle-drop thump
+    pub smoothed: Vec<f32>,
+    /// Length of the smoothing window in RMS windows
+    pub smooth_window: usize,
+    /// Start of each window in seconds
+    pub timestamps: Vec<f64>,
+    /// Length of one RMS window in seconds
+    pub chunk_duration: f64,
+    pub file_duration: f64,
+    pub medium: Medium,
+    /// Level of the groove noise (tape: the hiss) in dB
+    pub noise_floor_db: f32,
+    /// Typical level of the music in dB
+    pub music_level_db: f32,
+    /// Start of the music in seconds
+    pub groove_in: f64,
+    /// End of the music in seconds
+    pub groove_out: f64,
+    /// Needle-drop thump before groove-in (records only)
+    pub needle_drop: Option<NeedleDrop>,
+    /// Loop at the end of the recording, groove-out lies before it (records only)
+    pub locked_groove: Option<LockedGroove>,
+}
+
+impl BoundaryAnalysis {
+    /// Analyze an RMS curve of a vinyl recording computed with `chunk_ms` windows
+    pub fn new(rms_values: Vec<f32>, chunk_ms: u32, smooth_window_secs: f64, file_duration: f64, verbose: bool) -> Self {
+        Self::for_medium(rms_values, chunk_ms, smooth_window_secs, file_duration, Medium::Vinyl, verbose)
+    }
+
+    /// Analyze an RMS curve of a recording from `medium`.
+    ///
+    /// A needle drop found in the curve is unconfirmed until
+    /// [`Self::confirm_needle_drop`] has checked it in the audio, as
+    /// [`Self::from_wav`] does.
+    pub fn for_medium(
+        rms_values: Vec<f32>,
+        chunk_ms: u32,
+        smooth_window_secs: f64,
+        file_duration: f64,
+        medium: Medium,
+        verbose: bool,
+    ) -> Self {
+        let chunk_duration = chunk_ms as f64 / 1000.0;
+        let timestamps: Vec<f64> = (0..rms_values.len()).map(|i| i as f64 * chunk_duration).collect();
+
+        let smooth_window = ((smooth_window_secs / chunk_duration) as usize).max(3) | 1;
+        let mut smoothed = audio_analysis::smooth_rms(&rms_values, smooth_window);
+        let music_level_db = audio_analysis::estimate_music_level(&smoothed);
+
+        let (noise_floor_db, groove_in, groove_out, needle_drop) = match medium {
+            Medium::Vinyl | Medium::Shellac => {
+                let noise_floor_db = audio_analysis::estimate_noise_floor(&smoothed);
+                let mut groove_in = detect_groove_in(&smoothed, &timestamps, noise_floor_db, music_level_db,
+                                                     chunk_duration, verbose);
+                // Smoothing spreads a thump over seconds, so groove-in may lie before it
+                let needle_drop = detect_needle_drop(&rms_values, &timestamps, noise_floor_db,
+                                                     groove_in + smooth_window_secs, chunk_duration, verbose);
+                if let Some(drop) = needle_drop {
+                    let quiet: Vec<f32> = rms_values.iter().zip(&timestamps)
+                        .map(|(&v, &t)| if t >= drop.start && t < drop.end { v.min(noise_floor_db) } else { v })
+                        .collect();
+                    smoothed = audio_analysis::smooth_rms(&quiet, smooth_window);
+                    groove_in = detect_groove_in(&smoothed, &timestamps, noise_floor_db, music_level_db,
+                                                 chunk_duration, verbose).max(drop.end);
+                }
+                let groove_out = detect_groove_out(&smoothed, &timestamps, noise_floor_db, music_level_db,
+                                                   file_duration, chunk_duration, verbose);
+                (noise_floor_db, groove_in, groove_out, needle_drop)
+            }
+            Medium::Tape => {
+                let level = tape_level(&rms_values, chunk_duration);
+                let hiss_db = estimate_tape_hiss(&rms_values, chunk_duration);
+                let (start, end) = detect_tape_music(&level, &timestamps, hiss_db, file_duration,
+                                                     chunk_duration, verbose);
+                (hiss_db, start, end, None)
+            }
+        };
+
+        BoundaryAnalysis {
+            rms_values,
+            smoothed,
+            smooth_window,
+            timestamps,
+            chunk_duration,
+            file_duration,
+            medium,
+            noise_floor_db,
+            music_level_db,
+            groove_in,
+            groove_out,
+            needle_drop,
+            locked_groove: None,
+        }
+    }
+
+    /// Compute the RMS curve of a WAV file and analyze it
+    pub fn from_wav(
+        wav_file: &str,
+        chunk_ms: u32,
+        smooth_window_secs: f64,
+        use_mmap: bool,
+        verbose: bool,
+        progress: &mut dyn ProgressSink,
+    ) -> Result<Self> {
+        let mut reader = BufReader::new(File::open(wav_file)?);
+        let header = wavfile::read_wav_header(&mut reader).map_err(Error::Decode)?;
+        let bytes_per_sample = (header.bits_per_sample / 8) as f64;
+        let file_duration = header.data_size as f64
+            / (header.sample_rate as f64 * header.num_channels as f64 * bytes_per_sample);
+
+        let rms_values = audio_analysis::wav_rms(wav_file, chunk_ms, use_mmap, &[], progress)?;
+        let mut analysis = BoundaryAnalysis::new(rms_values, chunk_ms, smooth_window_secs, file_duration, verbose);
+        analysis.confirm_needle_drop(wav_file, verbose)?;
+        analysis.skip_locked_groove(wav_file, verbose)?;
+        Ok(analysis)
+    }
+
+    /// Check that the needle drop found in the RMS curve is a low-frequency
+    /// thump in `wav_file`; a transient that is not (a click, the first note)
+    /// is forgotten and groove-in detected again without skipping it
+    pub fn confirm_needle_drop(&mut self, wav_file: &str, verbose: bool) -> Result<()> {
+        let Some(drop) = self.needle_drop else {
+            return Ok(());
+        };
+        let low_share = needle_drop_low_share(wav_file, &drop)?;
+        if low_share >= NEEDLE_DROP_MIN_LOW_SHARE {
+            self.needle_drop = Some(NeedleDrop { confirmed: true, ..drop });
+            return Ok(());
+        }
+        if verbose {
+            info!("  Transient at {} is no needle drop ({:.0}% of its energy below {} Hz)",
+                  format_timestamp(drop.start), low_share * 100.0, NEEDLE_DROP_CUTOFF_HZ);
+        }
+        self.needle_drop = None;
+        self.smoothed = audio_analysis::smooth_rms(&self.rms_values, self.smooth_window);
+        self.groove_in = detect_groove_in(&self.smoothed, &self.timestamps, self.noise_floor_db,
+                                          self.music_level_db, self.chunk_duration, verbose);
+        Ok(())
+    }
+
+    /// Look for a locked groove at the end of `wav_file` and place
+    /// groove-out before it. A loop with music in it keeps the level up, so
+    /// groove-out detection alone would take it for part of the side.
+    pub fn skip_locked_groove(&mut self, wav_file: &str, verbose: bool) -> Result<()> {
+        if self.medium == Medium::Tape {
+            return Ok(());
+        }
+        let Some(groove) = locked_groove::find_locked_groove(wav_file, self.groove_in, self.file_duration, self.noise_floor_db)? else {
+            return Ok(());
+        };
+        // A recording that loops from the start has no music to keep
+        if groove.start <= self.groove_in {
+            return Ok(());
+        }
+        if verbose {
+            info!("  Locked groove from {} ({:.2}s per revolution, {:.1} RPM)",
+                  format_timestamp(groove.start), groove.period, groove.rpm());
+        }
+        if groove.start < self.groove_out {
+            self.groove_out = groove.start;
+            if verbose {
+                info!("  Groove-out moved to {}, before the loop", format_timestamp(groove.start));
+            }
+        }
+        self.locked_groove = Some(groove);
+        Ok(())
+    }
+
+    /// Length of the music region in seconds
+    pub fn music_duration(&self) -> f64 {
+        self.groove_out - self.groove_in
+    }
+
+    /// Window indices of the music region (start inclusive, end exclusive)
+    pub fn music_range(&self) -> (usize, usize) {
+        let start = self.timestamps.iter().position(|&t| t >= self.groove_in).unwrap_or(0);
+        let end = self.timestamps.iter().position(|&t| t >= self.groove_out).unwrap_or(self.timestamps.len());
+        (start, end)
+    }
+}
+
+/// Detect the groove-in point (where music starts).
+/// Scans from the start for a sustained rise above the midpoint between
+/// noise floor and music level.
+pub fn detect_groove_in(
+    smoothed: &[f32],
+    timestamps: &[f64],
+    noise_floor_db: f32,
+    music_level_db: f32,
+    chunk_duration: f64,
+    verbose: bool,
+) -> f64 {
+    if smoothed.is_empty() {
+        return 0.0;
+    }
+    
+    let threshold = (noise_floor_db + music_level_db) / 2.0;
+    let sustain_chunks = (2.0 / chunk_duration).max(1.0) as usize;
+    
+    for i in 0..smoothed.len().saturating_sub(sustain_chunks) {
+        if smoothed[i] > threshold {
+            let sustained = smoothed[i..i + sustain_chunks].iter().all(|&v| v > threshold);
+            if sustained {
+                // Walk back to find where the rise started
+                let mut start = i;
+                while start > 0 && smoothed[start - 1] < smoothed[start] {
+                    start -= 1;
+                }
+                let groove_in = timestamps[start];
+                if verbose {
+                    info!("  Groove-in detected at {} (threshold: {:.1} dB)",
+                             format_timestamp(groove_in), threshold);
+                }
+                return groove_in;
+            }
+        }
+    }
+    
+    if verbose {
+        info!("  No groove-in detected, using file start");
+    }
+    0.0
+}
+
+/// Look for a needle-drop thump before `search_end` in the unsmoothed RMS
+/// curve.
+///
+/// The thump is the first window at least [`NEEDLE_DROP_MIN_DB`] above the
+/// noise floor. It must fall back to half that height within
+/// [`NEEDLE_DROP_MAX_SECONDS`] and stay there for
+/// [`NEEDLE_DROP_SETTLE_SECONDS`]; music starting with a loud note does not.
+pub fn detect_needle_drop(
+    rms_values: &[f32],
+    timestamps: &[f64],
+    noise_floor_db: f32,
+    search_end: f64,
+    chunk_duration: f64,
+    verbose: bool,
+) -> Option<NeedleDrop> {
+    let settle_db = noise_floor_db + NEEDLE_DROP_MIN_DB / 2.0;
+    let start = (0..rms_values.len())
+        .take_while(|&i| timestamps[i] < search_end)
+        .find(|&i| rms_values[i] > noise_floor_db + NEEDLE_DROP_MIN_DB)?;
+    let end = (start..rms_values.len()).find(|&i| rms_values[i] <= settle_db)?;
+    if (end - start) as f64 * chunk_duration > NEEDLE_DROP_MAX_SECONDS {
+        return None;
+    }
+    let settle_chunks = (NEEDLE_DROP_SETTLE_SECONDS / chunk_duration).ceil() as usize;
+    let settled = rms_values.get(end..end + settle_chunks)?.iter().all(|&v| v <= settle_db);
+    if !settled {
+        return None;
+    }
+
+    let drop = NeedleDrop {
+        start: timestamps[start],
+        end: timestamps[end],
+        peak_db: rms_values[start..end].iter().copied().fold(f32::MIN, f32::max),
+        confirmed: false,
+    };
+    if verbose {
+        info!("  Needle drop at {} ({:.1}s, {:.1} dB above the noise floor)",
+              format_timestamp(drop.start), drop.end - drop.start, drop.peak_db - noise_floor_db);
+    }
+    Some(drop)
+}
+
+/// Share of the energy of `drop` in `wav_file` below [`NEEDLE_DROP_CUTOFF_HZ`]
+pub fn needle_drop_low_share(wav_file: &str, drop: &NeedleDrop) -> Result<f64> {
+    let (header, audio) = wavfile::read_wav_segment(wav_file, drop.start, drop.end - drop.start)
+        .map_err(Error::Decode)?;
+    let mut total = 0.0;
+    let mut high = 0.0;
+    for channel in &audio {
+        let mut samples: Vec<f64> = channel.iter().map(|&s| s as f64).collect();
+        total += samples.iter().map(|s| s * s).sum::<f64>();
+        SubsonicFilter::new(header.sample_rate, NEEDLE_DROP_CUTOFF_HZ).process(&mut samples);
+        high += samples.iter().map(|s| s * s).sum::<f64>();
+    }
+    if total <= 0.0 {
+        return Ok(0.0);
+    }
+    Ok((1.0 - high / total).max(0.0))
+}
+
+/// Detect the groove-out point (where music ends).
+/// Scans from the end backwards for the last sustained music region,
+/// then finds where the final drop occurs.
+pub fn detect_groove_out(
+    smoothed: &[f32],
+    timestamps: &[f64],
+    noise_floor_db: f32,
+    music_level_db: f32,
+    file_duration: f64,
+    chunk_duration: f64,
+    verbose: bool,
+) -> f64 {
+    if smoothed.is_empty() {
+        return file_duration;
+    }
+    
+    let threshold = (noise_floor_db + music_level_db) / 2.0;
+    let sustain_chunks = (5.0 / chunk_duration) as usize;
+    let len = smoothed.len();
+    
+    // Scan from end backwards to find the last region with sustained music
+    for i in (sustain_chunks..len).rev() {
+        let window_start = i.saturating_sub(sustain_chunks);
+        let above_count = smoothed[window_start..=i].iter().filter(|&&v| v > threshold).count();
+        
+        if above_count > sustain_chunks / 2 {
+            // Found last music region. Walk forward to find the drop-off.
+            for j in i..len {
+                if smoothed[j] < threshold {
+                    // Check that it stays below for at least 10s
+                    let check_end = (j + (10.0 / chunk_duration) as usize).min(len);
+                    let stays_below = smoothed[j..check_end].iter().all(|&v| v < threshold);
+                    if stays_below {
+                        let groove_out = timestamps[j];
+                        if verbose {
+                            info!("  Groove-out detected at {} (threshold: {:.1} dB, {:.1}s before end)",
+                                     format_timestamp(groove_out), threshold, file_duration - groove_out);
+                        }
+                        return groove_out;
+                    }
+                }
+            }
+            break;
+        }
+    }
+    
+    if verbose {
+        info!("  No groove-out detected, using file end");
+    }
+    file_duration
+}
+
+/// Level curve used for tape: RMS with a short smoothing
+fn tape_level(rms_values: &[f32], chunk_duration: f64) -> Vec<f32> {
+    let window = ((TAPE_SMOOTH_WINDOW_SECONDS / chunk_duration) as usize).max(1) | 1;
+    audio_analysis::smooth_rms(rms_values, window)
+}
+
+/// Estimate the tape hiss as the quietest second of `rms_values` besides
+/// the leader tape.
+///
+/// The leader is the run of levels close to the quietest one at either end
+/// of the recording. If such levels also occur in between, or nothing
+/// between the leaders is well below the music, there is no leader and the
+/// quietest second of the whole recording is the hiss. Pauses make up only
+/// a few percent of a tape, so a percentile of the levels would miss them.
+fn estimate_tape_hiss(rms_values: &[f32], chunk_duration: f64) -> f32 {
+    let window = ((TAPE_MIN_PAUSE_SECONDS / chunk_duration) as usize).max(1);
+    let quietest_second = |levels: &[f32]| {
+        levels
+            .windows(window.min(levels.len()).max(1))
+            .map(|w| w.iter().sum::<f32>() / w.len() as f32)
+            .fold(f32::INFINITY, f32::min)
+    };
+    let leader_threshold = rms_values.iter().copied().fold(f32::INFINITY, f32::min) + LEADER_MARGIN_DB / 2.0;
+    let is_leader = |v: &&f32| **v < leader_threshold;
+    let head = rms_values.iter().take_while(is_leader).count();
+    let tail = rms_values[head..].iter().rev().take_while(is_leader).count();
+    let inner = &rms_values[head..rms_values.len() - tail];
+
+    let hiss_db = quietest_second(inner);
+    let music_db = audio_analysis::estimate_music_level(rms_values);
+    if inner.iter().any(|v| is_leader(&v)) || hiss_db > music_db - TAPE_PAUSE_EXIT_DB {
+        quietest_second(rms_values)
+    } else {
+        hiss_db
+    }
+}
+
+/// Detect where the music of a tape starts and ends.
+///
+/// Leader tape and hiss before and after the music are skipped: the music
+/// starts at the first rise above `hiss_db` + [`TAPE_PAUSE_EXIT_DB`] that
+/// does not fall back below `hiss_db` + [`TAPE_PAUSE_ENTER_DB`] within two
+/// seconds, and ends where the level falls below the lower threshold after
+/// the last such rise.
+pub fn detect_tape_music(
+    level: &[f32],
+    timestamps: &[f64],
+    hiss_db: f32,
+    file_duration: f64,
+    chunk_duration: f64,
+    verbose: bool,
+) -> (f64, f64) {
+    let enter = hiss_db + TAPE_PAUSE_ENTER_DB;
+    let exit = hiss_db + TAPE_PAUSE_EXIT_DB;
+    let sustain_chunks = (2.0 / chunk_duration).max(1.0) as usize;
+    let len = level.len();
+    let sustained = |i: usize| level[i] > exit && level[i..(i + sustain_chunks).min(len)].iter().all(|&v| v > enter);
+
+    let Some(first) = (0..len).find(|&i| sustained(i)) else {
+        if verbose {
+            info!("  No music found above the tape hiss ({:.1} dB), using the whole file", hiss_db);
+        }
+        return (0.0, file_duration);
+    };
+    let mut start = first;
+    while start > 0 && level[start - 1] > enter {
+        start -= 1;
+    }
+    let last = (first..len).rev().find(|&i| level[i] > exit).unwrap_or(first);
+    let mut end = last;
+    while end + 1 < len && level[end + 1] > enter {
+        end += 1;
+    }
+    let music_start = timestamps[start];
+    let music_end = (timestamps[end] + chunk_duration).min(file_duration);
+
+    if verbose {
+        let is_leader = |&&v: &&f32| v < hiss_db - LEADER_MARGIN_DB;
+        let leader_start = level.iter().take_while(is_leader).count() as f64 * chunk_duration;
+        let leader_end = level.iter().rev().take_while(is_leader).count() as f64 * chunk_duration;
+        info!("  Tape hiss at {:.1} dB; leader tape: {:.1}s at the start, {:.1}s at the end",
+              hiss_db, leader_start, leader_end);
+        info!("  Music from {} to {} (thresholds {:.1}/{:.1} dB)",
+              format_timestamp(music_start), format_timestamp(music_end), enter, exit);
+    }
+    (music_start, music_end)
+}
+
+/// Find the pauses between songs on a tape.
+///
+/// A pause starts when the level falls below the hiss plus
+/// [`TAPE_PAUSE_ENTER_DB`] and only ends when it rises above the hiss plus
+/// [`TAPE_PAUSE_EXIT_DB`], so noise around the lower threshold does not split
+/// it. Pauses shorter than [`TAPE_MIN_PAUSE_SECONDS`] are ignored; of two
+/// pauses closer than the minimum song duration the longer, deeper one is kept.
+pub fn find_tape_boundaries(analysis: &BoundaryAnalysis, min_song_duration_seconds: f64, verbose: bool) -> Vec<Valley> {
+    let chunk_duration = analysis.chunk_duration;
+    let level = tape_level(&analysis.rms_values, chunk_duration);
+    let hiss_db = analysis.noise_floor_db;
+    let enter = hiss_db + TAPE_PAUSE_ENTER_DB;
+    let exit = hiss_db + TAPE_PAUSE_EXIT_DB;
+    let (music_start, music_end) = analysis.music_range();
+    let music_end = music_end.min(level.len());
+    let context_chunks = (5.0 / chunk_duration) as usize;
+    let mean = |range: &[f32]| if range.is_empty() { hiss_db } else { range.iter().sum::<f32>() / range.len() as f32 };
+
+    let mut valleys = Vec::new();
+    let mut pause_start: Option<usize> = None;
+    for i in music_start..music_end {
+        match pause_start {
+            None if level[i] < enter => pause_start = Some(i),
+            Some(start) if level[i] > exit => {
+                pause_start = None;
+                let width = (i - start) as f64 * chunk_duration;
+                if width < TAPE_MIN_PAUSE_SECONDS {
+                    continue;
+                }
+                let depth = level[start..i].iter().copied().fold(f32::INFINITY, f32::min);
+                let left_level = mean(&level[start.saturating_sub(context_chunks).max(music_start)..start]);
+                let right_level = mean(&level[i..(i + context_chunks).min(music_end)]);
+                let min_dip = (left_level.min(right_level) - depth).max(0.0);
+                valleys.push(Valley {
+                    position_seconds: (analysis.timestamps[start] + analysis.timestamps[i]) / 2.0,
+                    depth_db: depth,
+                    prominence_db: min_dip,
+                    left_level_db: left_level,
+                    right_level_db: right_level,
+                    width_seconds: width,
+                    gap_start_seconds: analysis.timestamps[start],
+                    gap_end_seconds: analysis.timestamps[i],
+                    score: min_dip as f64 * (1.0 + width.sqrt()),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    valleys.sort_by(|a, b| b.score.total_cmp(&a.score));
+    let mut filtered: Vec<Valley> = Vec::new();
+    for valley in valleys {
+        if filtered.iter().all(|v| (v.position_seconds - valley.position_seconds).abs() >= min_song_duration_seconds) {
+            filtered.push(valley);
+        }
+    }
+    filtered.sort_by(|a, b| a.position_seconds.total_cmp(&b.position_seconds));
+
+    if verbose {
+        info!("  Tape pauses (below {:.1} dB until above {:.1} dB, at least {:.1}s):", enter, exit, TAPE_MIN_PAUSE_SECONDS);
+        for v in &filtered {
+            info!("    {} - {} depth={:.1}dB w={:.1}s",
+                  format_timestamp(v.gap_start_seconds), format_timestamp(v.gap_end_seconds),
+                  v.depth_db, v.width_seconds);
+        }
+    }
+    filtered
+}
+
+/// Find song boundaries within the music region.
+///
+/// Tape recordings are handed to [`find_tape_boundaries`]; `min_prominence_db`
+/// only applies to vinyl.
+///
+/// Algorithm:
+///   1. Short smoothing (3s) for precise boundary location
+///   2. Long smoothing (30s) for local reference level
+///   3. Find local minima in the short-smoothed curve
+///   4. Measure prominence = depth below long-smoothed reference
+///   5. Measure left/right context levels (15s on each side)
+///   6. Require music-level audio on BOTH sides of the valley
+///   7. Score by minimum of left-dip and right-dip, scaled by prominence
+pub fn find_song_boundaries(
+    analysis: &BoundaryAnalysis,
+    min_prominence_db: f32,
+    min_song_duration_seconds: f64,
+    verbose: bool,
+) -> Vec<Valley> {
+    if analysis.medium == Medium::Tape {
+        return find_tape_boundaries(analysis, min_song_duration_seconds, verbose);
+    }
+    let rms_values = &analysis.rms_values;
+    let timestamps = &analysis.timestamps;
+    let smoothed_short = &analysis.smoothed;
+    let chunk_duration = analysis.chunk_duration;
+    let noise_floor_db = analysis.noise_floor_db;
+    let (music_start_idx, music_end_idx) = analysis.music_range();
+    let len = music_end_idx.min(rms_values.len());
+    if len <= music_start_idx + 10 {
+        return Vec::new();
+    }
+    
+    // Long smoothing for reference level (30 seconds)
+    let long_window = (30.0 / chunk_duration) as usize;
+    let long_smoothed = audio_analysis::smooth_rms(rms_values, long_window.max(3));
+    
+    // Context window: 15 seconds on each side
+    let context_chunks = (15.0 / chunk_duration) as usize;
+    
+    let mut valleys = Vec::new();
+    
+    // Search radius: 5 seconds for local minimum detection
+    let search_radius = (5.0 / chunk_duration) as usize;
+    
+    for i in (music_start_idx + search_radius)..(len.saturating_sub(search_radius)) {
+        let current = smoothed_short[i];
+        
+        // Check if this is a local minimum
+        let range_start = i.saturating_sub(search_radius);
+        let range_end = (i + search_radius).min(len - 1);
+        let is_minimum = (range_start..=range_end).all(|j| j == i || smoothed_short[j] >= current);
+        if !is_minimum {
+            continue;
+        }
+        
+        // Prominence against long-term reference
+        let local_ref = long_smoothed[i];
+        let prominence = local_ref - current;
+        if prominence < min_prominence_db {
+            continue;
+        }
+        
+        // Measure left context (audio before the valley)
+        let left_start = if i > context_chunks + search_radius {
+            i - context_chunks - search_radius
+        } else {
+            music_start_idx
+        };
+        let left_end = i.saturating_sub(search_radius / 2);
+        let left_level = if left_end > left_start {
+            smoothed_short[left_start..left_end].iter().sum::<f32>() / (left_end - left_start) as f32
+        } else {
+            local_ref
+        };
+        
+        // Measure right context (audio after the valley)
+        let right_start = (i + search_radius / 2).min(len - 1);
+        let right_end = (i + context_chunks + search_radius).min(len);
+        let right_level = if right_end > right_start {
+            smoothed_short[right_start..right_end].iter().sum::<f32>() / (right_end - right_start) as f32
+        } else {
+            local_ref
+        };
+        
+        // Dip from both sides
+        let left_dip = left_level - current;
+        let right_dip = right_level - current;
+        let min_dip = left_dip.min(right_dip);
+        
+        // Reject if one side is also quiet (within a quiet passage, not between songs)
+        if min_dip < min_prominence_db * 0.5 {
+            continue;
+        }
+        
+        // Valley width
+        let half_prom_threshold = current + prominence / 2.0;
+        let mut w_start = i;
+        let mut w_end = i;
+        while w_start > music_start_idx && smoothed_short[w_start - 1] < half_prom_threshold {
+            w_start -= 1;
+        }
+        while w_end < len - 1 && smoothed_short[w_end + 1] < half_prom_threshold {
+            w_end += 1;
+        }
+        let width = (w_end - w_start) as f64 * chunk_duration;
+        
+        // Score: emphasise the minimum dip (both sides must have music)
+        let score = (min_dip as f64) * (1.0 + prominence as f64 * 0.1) * (1.0 + width.sqrt());
+        
+        valleys.push(Valley {
+            position_seconds: timestamps[i],
+            depth_db: current,
+            prominence_db: prominence,
+            left_level_db: left_level,
+            right_level_db: right_level,
+            width_seconds: width,
+            gap_start_seconds: timestamps[w_start],
+            gap_end_seconds: timestamps[w_end],
+            score,
+        });
+    }
+    
+    // Remove valleys too close together (min song duration), keep highest score
+    let mut filtered = Vec::new();
+    valleys.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
+    
+    for valley in &valleys {
+        let too_close = filtered.iter().any(|existing: &Valley| {
+            (existing.position_seconds - valley.position_seconds).abs() < min_song_duration_seconds
+        });
+        if !too_close {
+            filtered.push(valley.clone());
+        }
+    }
+    
+    filtered.sort_by(|a, b| a.position_seconds.partial_cmp(&b.position_seconds).unwrap());
+    
+    if verbose && !filtered.is_empty() {
+        info!("  Valley candidates before score filtering:");
+        for v in &filtered {
+            info!("    {} depth={:.1}dB prom={:.1}dB L={:.1}dB R={:.1}dB w={:.1}s score={:.1}",
+                     format_timestamp(v.position_seconds),
+                     v.depth_db, v.prominence_db,
+                     v.left_level_db, v.right_level_db,
+                     v.width_seconds, v.score);
+        }
+    }
+    
+    // Adaptive score threshold: find the largest gap in the sorted scores.
+    // Real song boundaries cluster at high scores, false positives at low scores.
+    // The gap between these clusters is the natural threshold.
+    if filtered.len() > 1 {
+        let mut scores: Vec<f64> = filtered.iter().map(|v| v.score).collect();
+        scores.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        
+        // Find the largest relative gap between consecutive sorted scores
+        let mut best_gap_ratio = 0.0_f64;
+        let mut best_gap_idx = 0;
+        
+        for i in 0..scores.len() - 1 {
+            let lower = scores[i];
+            let upper = scores[i + 1];
+            // Use ratio: a gap from 30 to 75 (2.5x) is more significant than 200 to 300 (1.5x)
+            if lower > 0.0 {
+                let ratio = upper / lower;
+                if ratio > best_gap_ratio {
+                    best_gap_ratio = ratio;
+                    best_gap_idx = i;
+                }
+            }
+        }
+        
+        // Only apply gap filtering if the gap is significant (> 1.5x difference)
+        if best_gap_ratio > 1.5 {
+            let threshold = scores[best_gap_idx];
+            if verbose {
+                info!("  Score gap: {:.1} → {:.1} (ratio {:.1}x), threshold={:.1}",
+                         scores[best_gap_idx], scores[best_gap_idx + 1],
+                         best_gap_ratio, threshold);
+            }
+            filtered.retain(|v| v.score > threshold);
+        } else if verbose {
+            info!("  No significant score gap found (max ratio: {:.1}x)", best_gap_ratio);
+        }
+        
+        // Key insight for vinyl: real song boundaries drop WELL BELOW the noise
+        // floor. During a true inter-song gap, the stylus is in an unmodulated
+        // groove, producing a signal significantly quieter than the estimated
+        // noise floor (which is biased upward by including some musical bleed).
+        // Empirically, real boundaries are 7-16 dB below noise floor, while
+        // false positives (quiet passages within songs) are at or barely below it.
+        // Requiring 5 dB below noise floor cleanly separates them.
+        // On shellac the surface noise drowns the unmodulated groove, so gaps
+        // only get down to about the noise floor.
+        let depth_threshold = analysis.medium.max_valley_depth_db(noise_floor_db);
+        let before_depth = filtered.len();
+        filtered.retain(|v| v.depth_db <= depth_threshold);
+        if verbose {
+            info!("  Depth filter: valleys must reach {:.1} dB (noise floor {:.1} dB{:+.0} dB)",
+                     depth_threshold, noise_floor_db, depth_threshold - noise_floor_db);
+            if filtered.len() < before_depth {
+                info!("    Removed {} valleys that didn't reach deep enough below noise floor",
+                         before_depth - filtered.len());
+            }
+        }
+    }
+    
+    if verbose && !filtered.is_empty() {
+        info!("  Final boundaries:");
+        for v in &filtered {
+            info!("    {} depth={:.1}dB prom={:.1}dB score={:.1}",
+                     format_timestamp(v.position_seconds),
+                     v.depth_db, v.prominence_db, v.score);
+        }
+    }
+    
+    filtered
+}
+
+/// Step by which [`find_boundaries_for_count`] changes the minimum prominence
+pub const PROMINENCE_STEP_DB: f32 = 0.5;
+/// Lowest minimum prominence tried when boundaries are missing
+pub const MIN_RETRY_PROMINENCE_DB: f32 = 1.0;
+/// Highest minimum prominence tried when there are too many boundaries
+pub const MAX_RETRY_PROMINENCE_DB: f32 = 15.0;
+
+/// Result of [`find_boundaries_for_count`]
+#[derive(Debug, Clone)]
+pub struct CountedBoundaries {
+    pub valleys: Vec<Valley>,
+    /// Minimum prominence the boundaries were found with
+    pub min_prominence_db: f32,
+    /// Boundaries found with the minimum prominence asked for
+    pub initial_count: usize,
+    /// Detection runs, 1 if the first one matched
+    pub attempts: usize,
+}
+
+impl CountedBoundaries {
+    pub fn songs(&self) -> usize {
+        self.valleys.len() + 1
+    }
+}
+
+/// Autonomous boundary detection for a side whose track count is known,
+/// e.g. from a release whose track lengths do not fit for guided detection.
+///
+/// If [`find_song_boundaries`] finds too few boundaries, the minimum
+/// prominence is lowered in [`PROMINENCE_STEP_DB`] steps, if it finds too
+/// many, it is raised, until the count matches or the expected count is
+/// passed. The run closest to the expected count wins, the first one on a
+/// tie. Tape boundaries do not depend on the prominence and are not retried.
+pub fn find_boundaries_for_count(
+    analysis: &BoundaryAnalysis,
+    min_prominence_db: f32,
+    min_song_duration_seconds: f64,
+    expected_songs: usize,
+    verbose: bool,
+) -> CountedBoundaries {
+    let target = expected_songs.saturating_sub(1);
+    let valleys = find_song_boundaries(analysis, min_prominence_db, min_song_duration_seconds, verbose);
+    let mut best = CountedBoundaries {
+        initial_count: valleys.len(),
+        valleys,
+        min_prominence_db,
+        attempts: 1,
+    };
+    if best.initial_count == target || analysis.medium == Medium::Tape {
+        return best;
+    }
+
+    let too_few = best.initial_count < target;
+    let step = if too_few { -PROMINENCE_STEP_DB } else { PROMINENCE_STEP_DB };
+    for n in 1.. {
+        let prominence = min_prominence_db + step * n as f32;
+        if !(MIN_RETRY_PROMINENCE_DB..=MAX_RETRY_PROMINENCE_DB).contains(&prominence) {
+            break;
+        }
+        best.attempts += 1;
+        let valleys = find_song_boundaries(analysis, prominence, min_song_duration_seconds, false);
+        let count = valleys.len();
+        if verbose {
+            info!("  Prominence {:.1} dB: {} boundaries, {} expected", prominence, count, target);
+        }
+        if count.abs_diff(target) < best.valleys.len().abs_diff(target) {
+            best.valleys = valleys;
+            best.min_prominence_db = prominence;
+        }
+        if (too_few && count >= target) || (!too_few && count <= target) {
+            break;
+        }
+    }
+    best
+}
+
+/// Largest speed error of the turntable [`align_tracks`] allows for, as a
+/// fraction; the search window around an expected boundary widens by this
+/// share of its position
+pub const MAX_SPEED_ERROR: f64 = 0.03;
+/// Half width in seconds of the range a guided candidate must be the
+/// quietest point of
+const CANDIDATE_RADIUS_SECONDS: f64 = 1.0;
+/// Track length deviation in seconds tolerated by [`align_tracks`] on top of
+/// the relative one
+const LENGTH_TOLERANCE_SECONDS: f64 = 1.5;
+/// Track length deviation tolerated by [`align_tracks`] as a fraction of the
+/// expected length, for rounded release data and off-speed turntables
+const LENGTH_TOLERANCE: f64 = 0.01;
+/// Prominence worth one tolerated length deviation in [`align_tracks`]
+const ALIGN_PROMINENCE_DB: f64 = 3.0;
+
+/// Valley at window `index` with levels measured over `context_window`
+/// windows on each side
+fn context_valley(smoothed: &[f32], timestamps: &[f64], index: usize, context_window: usize) -> Valley {
+    let level = smoothed[index];
+    let left_start = index.saturating_sub(context_window);
+    let right_end = (index + context_window).min(smoothed.len());
+    let left_avg = if index > left_start {
+        smoothed[left_start..index].iter().sum::<f32>() / (index - left_start) as f32
+    } else {
+        level
+    };
+    let right_avg = if right_end > index + 1 {
+        smoothed[index + 1..right_end].iter().sum::<f32>() / (right_end - index - 1) as f32
+    } else {
+        level
+    };
+    let prominence = (left_avg.max(right_avg) - level).max(0.0);
+    Valley {
+        position_seconds: timestamps[index],
+        depth_db: level,
+        prominence_db: prominence,
+        width_seconds: 0.0,
+        gap_start_seconds: timestamps[index],
+        gap_end_seconds: timestamps[index],
+        left_level_db: left_avg,
+        right_level_db: right_avg,
+        score: (prominence * 10.0) as f64,
+    }
+}
+
+/// Cost of a track `length` seconds long that should be `expected` long
+fn length_cost(length: f64, expected: f64) -> f64 {
+    let tolerance = LENGTH_TOLERANCE_SECONDS + LENGTH_TOLERANCE * expected.abs();
+    ((length - expected) / tolerance).powi(2)
+}
+
+/// Align candidate valleys to the track lengths of a release.
+///
+/// Chooses one candidate per boundary between `expected_tracks`, in order,
+/// so that the lengths of all tracks together fit the expected ones best,
+/// with deeper valleys preferred (a Viterbi search over the candidates).
+/// Because each track is only compared to its own length, the error a
+/// turntable running a little fast or slow accumulates over the side does
+/// not push later boundaries out of reach; a candidate may lie up to
+/// `search_window_seconds` plus [`MAX_SPEED_ERROR`] of its position away
+/// from the expected one.
+///
+/// Returns the chosen candidates in order, each with the index in
+/// `expected_tracks` of the track it starts, leaving out boundaries without
+/// any candidate in reach.
+pub fn align_tracks(
+    candidates: &[Valley],
+    expected_tracks: &[ExpectedTrack],
+    music_start: f64,
+    music_end: f64,
+    search_window_seconds: f64,
+) -> Vec<(usize, Valley)> {
+    // Expected boundaries relative to the music start, with their track and candidates
+    let boundaries: Vec<(usize, f64, Vec<&Valley>)> = expected_tracks
+        .iter()
+        .enumerate()
+        .skip(1)
+        .map(|(track_index, track)| {
+            let expected = track.expected_start;
+            let reach = search_window_seconds + MAX_SPEED_ERROR * expected;
+            let reachable = candidates
+                .iter()
+                .filter(|c| (c.position_seconds - music_start - expected).abs() <= reach)
+                .collect();
+            (track_index, expected, reachable)
+        })
+        .filter(|(_, _, reachable): &(usize, f64, Vec<&Valley>)| !reachable.is_empty())
+        .collect();
+    if boundaries.is_empty() {
+        return Vec::new();
+    }
+    let quality = |c: &Valley| -(c.prominence_db as f64) / ALIGN_PROMINENCE_DB;
+
+    // costs[k][c]: best cost of the side up to candidate c of boundary k,
+    // previous[k][c]: candidate of boundary k - 1 on that path
+    let mut costs: Vec<Vec<f64>> = Vec::with_capacity(boundaries.len());
+    let mut previous: Vec<Vec<usize>> = Vec::with_capacity(boundaries.len());
+    for (k, (_, expected, reachable)) in boundaries.iter().enumerate() {
+        let mut row = Vec::with_capacity(reachable.len());
+        let mut links = Vec::with_capacity(reachable.len());
+        for candidate in reachable {
+            let position = candidate.position_seconds;
+            let (cost, link) = if k == 0 {
+                (length_cost(position - music_start, *expected), 0)
+            } else {
+                let (_, prev_expected, prev_reachable) = &boundaries[k - 1];
+                prev_reachable
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, p)| p.position_seconds < position)
+                    .map(|(i, p)| {
+                        let length = position - p.position_seconds;
+                        (costs[k - 1][i] + length_cost(length, expected - prev_expected), i)
+                    })
+                    .min_by(|a, b| a.0.total_cmp(&b.0))
+                    .unwrap_or((f64::INFINITY, 0))
+            };
+            row.push(cost + quality(candidate));
+            links.push(link);
+        }
+        costs.push(row);
+        previous.push(links);
+    }
+
+    // The last track runs to the end of the music
+    let last = boundaries.len() - 1;
+    let (_, last_expected, last_reachable) = &boundaries[last];
+    let side_length: f64 = expected_tracks.iter().map(|t| t.length_seconds).sum();
+    let best = last_reachable
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (costs[last][i] + length_cost(music_end - c.position_seconds, side_length - *last_expected), i))
+        .filter(|(cost, _)| cost.is_finite())
+        .min_by(|a, b| a.0.total_cmp(&b.0));
+    let Some((_, mut index)) = best else {
+        return Vec::new();
+    };
+
+    let mut aligned = Vec::with_capacity(boundaries.len());
+    for k in (0..boundaries.len()).rev() {
+        let (track_index, _, reachable) = &boundaries[k];
+        aligned.push((*track_index, reachable[index].clone()));
+        index = previous[k][index];
+    }
+    aligned.reverse();
+    aligned
+}
+
+/// Guided boundary detection using expected track positions from MusicBrainz.
+/// Every local minimum of the smoothed curve is a candidate, and
+/// [`align_tracks`] picks the ones that fit the track lengths best, each
+/// returned with the index of the track it starts.
+pub fn find_guided_boundaries(
+    smoothed: &[f32],
+    timestamps: &[f64],
+    expected_tracks: &[ExpectedTrack],
+    music: (f64, f64),
+    search_window_seconds: f64,
+    verbose: bool,
+) -> Vec<(usize, Valley)> {
+    if expected_tracks.len() < 2 || timestamps.len() < 2 {
+        return Vec::new();
+    }
+    let (music_start, music_end) = music;
+    let chunk_duration = timestamps[1] - timestamps[0];
+    let radius = ((CANDIDATE_RADIUS_SECONDS / chunk_duration) as usize).max(1);
+    let context_window = (15.0 / chunk_duration) as usize;
+
+    let len = smoothed.len().min(timestamps.len());
+    let candidates: Vec<Valley> = (0..len)
+        .filter(|&i| timestamps[i] > music_start && timestamps[i] < music_end)
+        .filter(|&i| {
+            let range_start = i.saturating_sub(radius);
+            let range_end = (i + radius).min(len - 1);
+            // The first of equally quiet windows
+            (range_start..i).all(|j| smoothed[j] > smoothed[i])
+                && (i + 1..=range_end).all(|j| smoothed[j] >= smoothed[i])
+        })
+        .map(|i| context_valley(smoothed, timestamps, i, context_window))
+        .collect();
+    if verbose {
+        info!("  {} candidate valleys for {} boundaries", candidates.len(), expected_tracks.len() - 1);
+    }
+
+    let boundaries = align_tracks(&candidates, expected_tracks, music_start, music_end, search_window_seconds);
+    if verbose {
+        for (i, valley) in &boundaries {
+            let found = valley.position_seconds - music_start;
+            let track = &expected_tracks[*i];
+            info!("  Track {} boundary: expected={:.1}s, found={:.1}s (offset={:.1}s), depth={:.1}dB, prom={:.1}dB",
+                     i + 1, track.expected_start, found, found - track.expected_start,
+                     valley.depth_db, valley.prominence_db);
+        }
+    }
+    boundaries
+}
+
+/// Boundary detection seeded by song identification.
+/// Searches for the quietest point between consecutive, differently
+/// identified songs (narrowed by the Shazam match offset when available).
+pub fn find_seeded_boundaries(
+    smoothed: &[f32],
+    timestamps: &[f64],
+    windows: &[BoundaryWindow],
+    chunk_duration: f64,
+    verbose: bool,
+) -> Vec<Valley> {
+    let mut boundaries = Vec::new();
+    let context_window = (15.0 / chunk_duration) as usize;
+    
+    for window in windows {
+        let mut min_rms = f32::MAX;
+        let mut min_idx = 0;
+        
+        for (j, &ts) in timestamps.iter().enumerate() {
+            if ts >= window.start && ts <= window.end && j < smoothed.len() && smoothed[j] < min_rms {
+                min_rms = smoothed[j];
+                min_idx = j;
+            }
+        }
+        
+        if min_rms == f32::MAX {
+            continue;
+        }
+        
+        let valley = context_valley(smoothed, timestamps, min_idx, context_window);
+        
+        if verbose {
+            let expected = window.expected
+                .map(|t| format!(", expected={}", format_timestamp(t)))
+                .unwrap_or_default();
+            info!("  Song {} → {}: window {}-{}{}, found={}, depth={:.1}dB, prom={:.1}dB",
+                     window.song_index + 1, window.song_index + 2,
+                     format_timestamp(window.start), format_timestamp(window.end), expected,
+                     format_timestamp(valley.position_seconds), valley.depth_db, valley.prominence_db);
+        }
+        
+        boundaries.push(valley);
+    }
+    
+    boundaries
+}
+
+/// Seconds before a track mark searched for the quietest point; marks are
+/// usually set a moment after the next song has started
+pub const MARK_SEARCH_BEFORE_SECONDS: f64 = 4.0;
+/// Seconds after a track mark searched for the quietest point
+pub const MARK_SEARCH_AFTER_SECONDS: f64 = 1.0;
+
+/// Search windows for [`find_seeded_boundaries`] around track marks set while
+/// recording, so each boundary lands in the pause next to its mark.
+/// Marks outside the music region are ignored.
+pub fn mark_windows(marks: &[f64], groove_in: f64, groove_out: f64) -> Vec<BoundaryWindow> {
+    marks
+        .iter()
+        .filter(|&&mark| mark > groove_in && mark < groove_out)
+        .enumerate()
+        .map(|(song_index, &mark)| BoundaryWindow {
+            song_index,
+            start: (mark - MARK_SEARCH_BEFORE_SECONDS).max(groove_in),
+            end: (mark + MARK_SEARCH_AFTER_SECONDS).min(groove_out),
+            expected: Some(mark),
+        })
+        .collect()
+}
+
+/// Boundaries exactly at reviewed `positions`, with the level around each
+/// measured for the confidence. Positions outside the music region are ignored.
+pub fn exact_boundaries(
+    smoothed: &[f32],
+    timestamps: &[f64],
+    positions: &[f64],
+    (groove_in, groove_out): (f64, f64),
+    chunk_duration: f64,
+) -> Vec<Valley> {
+    let context_window = (15.0 / chunk_duration) as usize;
+    let len = smoothed.len().min(timestamps.len());
+    if len == 0 {
+        return Vec::new();
+    }
+    positions
+        .iter()
+        .filter(|&&position| position > groove_in && position < groove_out)
+        .map(|&position| {
+            let index = timestamps[..len].partition_point(|&t| t < position).min(len - 1);
+            Valley {
+                position_seconds: position,
+                gap_start_seconds: position,
+                gap_end_seconds: position,
+                ..context_valley(smoothed, timestamps, index, context_window)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// RMS curve of a side with three 80 s songs separated by 6 s gaps,
+    /// 5 s lead-in and 30 s lead-out (200 ms windows)
+    fn side_rms() -> Vec<f32> {
+        let mut rms = vec![-40.0; 25];
+        for song in 0..3 {
+            if song > 0 {
+                rms.extend(std::iter::repeat_n(-55.0, 30));
+            }
+            rms.extend(std::iter::repeat_n(-20.0, 400));
+        }
+        rms.extend(std::iter::repeat_n(-40.0, 150));
+        rms
+    }
+
+    #[test]
+    fn test_analysis_and_boundaries() {
+        let rms = side_rms();
+        let duration = rms.len() as f64 * 0.2;
+        let analysis = BoundaryAnalysis::new(rms, DEFAULT_CHUNK_MS, DEFAULT_SMOOTH_WINDOW_SECONDS, duration, false);
+        assert!(analysis.groove_in > 3.0 && analysis.groove_in <= 5.0, "groove-in {}", analysis.groove_in);
+        // The smoothed level stays above the threshold a little after the music
+        assert!(analysis.groove_out >= 257.0 && analysis.groove_out < 262.0, "groove-out {}", analysis.groove_out);
+        assert_eq!(analysis.music_duration(), analysis.groove_out - analysis.groove_in);
+
+        let valleys = find_song_boundaries(&analysis, DEFAULT_MIN_PROMINENCE_DB, DEFAULT_MIN_SONG_SECONDS, false);
+        let positions: Vec<f64> = valleys.iter().map(|v| v.position_seconds).collect();
+        assert_eq!(positions.len(), 2, "{:?}", positions);
+        assert!((positions[0] - 88.0).abs() < 3.0, "{:?}", positions);
+        assert!((positions[1] - 174.0).abs() < 3.0, "{:?}", positions);
+    }
+
+    #[test]
+    fn test_needle_drop() {
+        // Thump 1 s into the lead-in of the side
+        let mut rms = side_rms();
+        rms[5] = -5.0;
+        rms[6] = -12.0;
+        let duration = rms.len() as f64 * 0.2;
+        let analysis = BoundaryAnalysis::new(rms, DEFAULT_CHUNK_MS, DEFAULT_SMOOTH_WINDOW_SECONDS, duration, false);
+        let drop = analysis.needle_drop.expect("needle drop");
+        assert!((drop.start - 1.0).abs() < 0.01 && (drop.end - 1.4).abs() < 0.01, "{:?}", drop);
+        assert_eq!(drop.peak_db, -5.0);
+        assert!(analysis.groove_in > 3.0 && analysis.groove_in <= 5.0, "groove-in {}", analysis.groove_in);
+
+        let plain = BoundaryAnalysis::new(side_rms(), DEFAULT_CHUNK_MS, DEFAULT_SMOOTH_WINDOW_SECONDS, duration, false);
+        assert_eq!(plain.needle_drop, None);
+
+        // Only a low-frequency transient is a needle drop
+        let dir = tempfile::tempdir().unwrap();
+        let rate = 8000;
+        let burst = |frequency: f64| -> Vec<i32> {
+            (0..rate * 2)
+                .map(|i| {
+                    if (rate..rate + rate / 5).contains(&i) {
+                        (8000.0 * (2.0 * std::f64::consts::PI * frequency * i as f64 / rate as f64).sin()) as i32
+                    } else {
+                        0
+                    }
+                })
+                .collect()
+        };
+        let drop = NeedleDrop { start: 1.0, end: 1.2, peak_db: -10.0, confirmed: false };
+        assert!(!analysis.needle_drop.unwrap().confirmed);
+        for (frequency, is_thump) in [(30.0, true), (2000.0, false)] {
+            let path = dir.path().join(format!("{}.wav", frequency)).to_string_lossy().to_string();
+            wavfile::write_wav_samples(&path, &[burst(frequency)], rate as u32, 16).unwrap();
+            let share = needle_drop_low_share(&path, &drop).unwrap();
+            assert_eq!(share >= NEEDLE_DROP_MIN_LOW_SHARE, is_thump, "{} Hz: {}", frequency, share);
+
+            let mut checked = analysis.clone();
+            checked.confirm_needle_drop(&path, false).unwrap();
+            assert_eq!(checked.needle_drop.map(|d| d.confirmed), is_thump.then_some(true), "{} Hz", frequency);
+        }
+    }
+
+    #[test]
+    fn test_boundaries_for_count() {
+        // Like side_rms, but the second gap is shallower and the first song
+        // has a quiet passage: at the default prominence, the quiet passage
+        // is no candidate and the second gap falls below the score gap
+        let mut rms = vec![-40.0; 25];
+        rms.extend(std::iter::repeat_n(-20.0, 200));
+        rms.extend(std::iter::repeat_n(-22.5, 25));
+        rms.extend(std::iter::repeat_n(-20.0, 175));
+        rms.extend(std::iter::repeat_n(-55.0, 30));
+        rms.extend(std::iter::repeat_n(-20.0, 400));
+        rms.extend(std::iter::repeat_n(-50.0, 20));
+        rms.extend(std::iter::repeat_n(-20.0, 400));
+        rms.extend(std::iter::repeat_n(-40.0, 150));
+        let duration = rms.len() as f64 * 0.2;
+        let analysis = BoundaryAnalysis::new(rms, DEFAULT_CHUNK_MS, DEFAULT_SMOOTH_WINDOW_SECONDS, duration, false);
+
+        let counted = find_boundaries_for_count(&analysis, DEFAULT_MIN_PROMINENCE_DB, DEFAULT_MIN_SONG_SECONDS, 3, false);
+        assert_eq!(counted.initial_count, 1);
+        assert_eq!(counted.songs(), 3);
+        assert_eq!(counted.min_prominence_db, 2.0);
+        assert_eq!(counted.attempts, 3);
+        assert!((counted.valleys[1].position_seconds - 173.0).abs() < 3.0, "{:?}", counted.valleys);
+
+        let counted = find_boundaries_for_count(&analysis, MIN_RETRY_PROMINENCE_DB, DEFAULT_MIN_SONG_SECONDS, 2, false);
+        assert_eq!(counted.initial_count, 2);
+        assert_eq!(counted.songs(), 2);
+        assert_eq!(counted.min_prominence_db, 2.5);
+        assert!((counted.valleys[0].position_seconds - 88.0).abs() < 3.0, "{:?}", counted.valleys);
+
+        // Down to the lowest prominence, the closest count is the best
+        let counted = find_boundaries_for_count(&analysis, DEFAULT_MIN_PROMINENCE_DB, DEFAULT_MIN_SONG_SECONDS, 8, false);
+        assert_eq!(counted.songs(), 3);
+        assert_eq!(counted.attempts, 5);
+    }
+
+    #[test]
+    fn test_tape_boundaries() {
+        // 5 s leader, 3 s hiss, three 80 s songs with 3 s pauses (the second
+        // song has a quiet passage), 20 s hiss and 5 s leader again
+        let mut rms = vec![-85.0; 25];
+        rms.extend(std::iter::repeat_n(-60.0, 15));
+        for song in 0..3 {
+            if song > 0 {
+                rms.extend(std::iter::repeat_n(-60.0, 15));
+            }
+            if song == 1 {
+                rms.extend(std::iter::repeat_n(-20.0, 150));
+                rms.extend(std::iter::repeat_n(-45.0, 15));
+                rms.extend(std::iter::repeat_n(-20.0, 235));
+            } else {
+                rms.extend(std::iter::repeat_n(-20.0, 400));
+            }
+        }
+        rms.extend(std::iter::repeat_n(-60.0, 100));
+        rms.extend(std::iter::repeat_n(-85.0, 25));
+        let duration = rms.len() as f64 * 0.2;
+
+        let analysis = BoundaryAnalysis::for_medium(rms, DEFAULT_CHUNK_MS, DEFAULT_SMOOTH_WINDOW_SECONDS, duration, Medium::Tape, false);
+        assert!((analysis.noise_floor_db + 60.0).abs() < 1.0, "hiss {}", analysis.noise_floor_db);
+        assert!((analysis.groove_in - 8.0).abs() < 0.5, "start {}", analysis.groove_in);
+        assert!((analysis.groove_out - 254.0).abs() < 0.5, "end {}", analysis.groove_out);
+
+        let valleys = find_song_boundaries(&analysis, DEFAULT_MIN_PROMINENCE_DB, DEFAULT_MIN_SONG_SECONDS, false);
+        let positions: Vec<f64> = valleys.iter().map(|v| v.position_seconds).collect();
+        assert_eq!(positions.len(), 2, "{:?}", positions);
+        assert!((positions[0] - 89.5).abs() < 1.0, "{:?}", positions);
+        assert!((positions[1] - 172.5).abs() < 1.0, "{:?}", positions);
+        assert!(valleys.iter().all(|v| v.width_seconds > 2.0 && v.gap_end_seconds > v.gap_start_seconds));
+
+        assert_eq!(Medium::from_str("Cassette"), Some(Medium::Tape));
+        assert_eq!(Medium::from_str(Medium::Vinyl.as_str()), Some(Medium::Vinyl));
+        assert_eq!(Medium::from_str("cd"), None);
+    }
+
+    #[test]
+    fn test_tape_short_pauses() {
+        // 5 s leader, 1 s hiss, twelve 90 s songs with 2 s pauses, 1 s hiss
+        // and 5 s leader: the hiss is only 2 % of the recording
+        let mut rms = vec![-85.0; 25];
+        rms.extend(std::iter::repeat_n(-60.0, 5));
+        for song in 0..12 {
+            if song > 0 {
+                rms.extend(std::iter::repeat_n(-60.0, 10));
+            }
+            rms.extend(std::iter::repeat_n(-20.0, 450));
+        }
+        rms.extend(std::iter::repeat_n(-60.0, 5));
+        rms.extend(std::iter::repeat_n(-85.0, 25));
+        let duration = rms.len() as f64 * 0.2;
+
+        let analysis = BoundaryAnalysis::for_medium(rms, DEFAULT_CHUNK_MS, DEFAULT_SMOOTH_WINDOW_SECONDS, duration, Medium::Tape, false);
+        assert!((analysis.noise_floor_db + 60.0).abs() < 1.0, "hiss {}", analysis.noise_floor_db);
+        assert!((analysis.groove_in - 6.0).abs() < 0.5, "start {}", analysis.groove_in);
+        let valleys = find_song_boundaries(&analysis, DEFAULT_MIN_PROMINENCE_DB, DEFAULT_MIN_SONG_SECONDS, false);
+        assert_eq!(valleys.len(), 11);
+        assert!((valleys[0].position_seconds - 97.0).abs() < 1.0, "{}", valleys[0].position_seconds);
+
+        // Without leader tape the quietest second is the hiss
+        let rms: Vec<f32> = [vec![-60.0; 10], vec![-20.0; 450], vec![-60.0; 10], vec![-20.0; 450]].concat();
+        assert_eq!(estimate_tape_hiss(&rms, 0.2), -60.0);
+        let rms: Vec<f32> = [vec![-60.0; 10], vec![-20.0; 900], vec![-60.0; 10]].concat();
+        assert_eq!(estimate_tape_hiss(&rms, 0.2), -60.0);
+    }
+
+    #[test]
+    fn test_shellac_boundaries() {
+        // 78 side: loud surface noise, three 50 s pieces whose 4 s pauses
+        // only fall back to the surface noise
+        let mut rms = vec![-35.0; 25];
+        for song in 0..3 {
+            if song > 0 {
+                rms.extend(std::iter::repeat_n(-35.0, 20));
+            }
+            rms.extend(std::iter::repeat_n(-15.0, 250));
+        }
+        rms.extend(std::iter::repeat_n(-35.0, 50));
+        let duration = rms.len() as f64 * 0.2;
+
+        let vinyl = BoundaryAnalysis::new(rms.clone(), DEFAULT_CHUNK_MS, DEFAULT_SMOOTH_WINDOW_SECONDS, duration, false);
+        assert!(find_song_boundaries(&vinyl, DEFAULT_MIN_PROMINENCE_DB, DEFAULT_MIN_SONG_SECONDS, false).is_empty());
+
+        let medium = Medium::from_str("78").unwrap();
+        assert_eq!(medium, Medium::Shellac);
+        let analysis = BoundaryAnalysis::for_medium(rms, DEFAULT_CHUNK_MS, DEFAULT_SMOOTH_WINDOW_SECONDS, duration, medium, false);
+        let valleys = find_song_boundaries(&analysis, DEFAULT_MIN_PROMINENCE_DB, medium.default_min_song_seconds(), false);
+        let positions: Vec<f64> = valleys.iter().map(|v| v.position_seconds).collect();
+        assert_eq!(positions.len(), 2, "{:?}", positions);
+        assert!((positions[0] - 57.0).abs() < 2.0, "{:?}", positions);
+        assert!((positions[1] - 111.0).abs() < 2.0, "{:?}", positions);
+    }
+
+    #[test]
+    fn test_guided_boundaries() {
+        let rms = side_rms();
+        let duration = rms.len() as f64 * 0.2;
+        let analysis = BoundaryAnalysis::new(rms, DEFAULT_CHUNK_MS, DEFAULT_SMOOTH_WINDOW_SECONDS, duration, false);
+
+        // Expected positions a few seconds off, as with release data
+        let track = |position, expected_start| ExpectedTrack {
+            position,
+            title: format!("Track {}", position),
+            length_seconds: 86.0,
+            expected_start,
+        };
+        let tracks = vec![track(1, 0.0), track(2, 80.0), track(3, 172.0)];
+        let aligned = find_guided_boundaries(&analysis.smoothed, &analysis.timestamps, &tracks,
+                                             (analysis.groove_in, analysis.groove_out), 10.0, false);
+        assert_eq!(aligned.iter().map(|(i, _)| *i).collect::<Vec<_>>(), vec![1, 2]);
+        let valleys: Vec<Valley> = aligned.into_iter().map(|(_, v)| v).collect();
+        assert!((valleys[0].position_seconds - 88.0).abs() < 3.0);
+        assert!((valleys[1].position_seconds - 174.0).abs() < 3.0);
+        assert!(valleys.iter().all(|v| v.prominence_db > 20.0));
+    }
+
+    #[test]
+    fn test_align_tracks_with_drift() {
+        // Six 120 s songs with 4 s gaps on a deck running 3% slow, so the
+        // recording is 3% longer than the release; the fourth song has a
+        // quiet passage right where its end is expected
+        let mut rms = vec![-40.0; 25];
+        for song in 0..6 {
+            if song > 0 {
+                rms.extend(std::iter::repeat_n(-55.0, 20));
+            }
+            if song == 3 {
+                rms.extend(std::iter::repeat_n(-20.0, 570));
+                rms.extend(std::iter::repeat_n(-30.0, 15));
+                rms.extend(std::iter::repeat_n(-20.0, 15));
+            } else {
+                rms.extend(std::iter::repeat_n(-20.0, 600));
+            }
+        }
+        rms.extend(std::iter::repeat_n(-40.0, 150));
+        let duration = rms.len() as f64 * 0.2;
+        let analysis = BoundaryAnalysis::new(rms, DEFAULT_CHUNK_MS, DEFAULT_SMOOTH_WINDOW_SECONDS, duration, false);
+
+        let tracks: Vec<ExpectedTrack> = (0..6)
+            .map(|i| ExpectedTrack {
+                position: i + 1,
+                title: format!("Track {}", i + 1),
+                length_seconds: 124.0 / 1.03,
+                expected_start: i as f64 * 124.0 / 1.03,
+            })
+            .collect();
+        let aligned = find_guided_boundaries(&analysis.smoothed, &analysis.timestamps, &tracks,
+                                             (analysis.groove_in, analysis.groove_out), 10.0, false);
+        let positions: Vec<f64> = aligned.iter().map(|(_, v)| v.position_seconds).collect();
+        assert_eq!(positions.len(), 5, "{:?}", positions);
+        assert_eq!(aligned.iter().map(|(i, _)| *i).collect::<Vec<_>>(), vec![1, 2, 3, 4, 5]);
+        for (i, position) in positions.iter().enumerate() {
+            // Gaps are 4 s long and start every 124 s
+            let gap = 5.0 + 120.0 + i as f64 * 124.0;
+            assert!(*position >= gap && *position <= gap + 4.0, "boundary {}: {:?}", i + 1, positions);
+        }
+
+        // The last boundaries are more than 10 s later than expected
+        let offset = positions[4] - analysis.groove_in - tracks[5].expected_start;
+        assert!(offset > 10.0, "{}", offset);
+    }
+
+    #[test]
+    fn test_mark_boundaries() {
+        let rms = side_rms();
+        let duration = rms.len() as f64 * 0.2;
+        let analysis = BoundaryAnalysis::new(rms, DEFAULT_CHUNK_MS, DEFAULT_SMOOTH_WINDOW_SECONDS, duration, false);
+
+        // Pressed a little late; the mark before the music is dropped
+        let windows = mark_windows(&[2.0, 91.5, 177.0], analysis.groove_in, analysis.groove_out);
+        assert_eq!(windows.len(), 2);
+        assert_eq!(windows[0].song_index, 0);
+        let valleys = find_seeded_boundaries(&analysis.smoothed, &analysis.timestamps, &windows, analysis.chunk_duration, false);
+        assert_eq!(valleys.len(), 2);
+        assert!((valleys[0].position_seconds - 88.0).abs() < 3.0, "{}", valleys[0].position_seconds);
+        assert!((valleys[1].position_seconds - 174.0).abs() < 3.0, "{}", valleys[1].position_seconds);
+    }
+
+    #[test]
+    fn test_exact_boundaries() {
+        let rms = side_rms();
+        let duration = rms.len() as f64 * 0.2;
+        let analysis = BoundaryAnalysis::new(rms, DEFAULT_CHUNK_MS, DEFAULT_SMOOTH_WINDOW_SECONDS, duration, false);
+        let music = (analysis.groove_in, analysis.groove_out);
+
+        // Reviewed positions are kept even inside the music, where marks would snap to the pause
+        let valleys = exact_boundaries(&analysis.smoothed, &analysis.timestamps, &[2.0, 91.5, 177.0], music, analysis.chunk_duration);
+        let positions: Vec<f64> = valleys.iter().map(|v| v.position_seconds).collect();
+        assert_eq!(positions, vec![91.5, 177.0]);
+        assert_eq!(valleys[0].gap_start_seconds, 91.5);
+        assert!(exact_boundaries(&analysis.smoothed, &analysis.timestamps, &[], music, analysis.chunk_duration).is_empty());
+    }
+}
@@ -0,0 +1,368 @@
+//! SQLite catalog of all recordings.
+//!
+//! The info files next to each recording answer questions about one side;
+//! the catalog answers them across hundreds of records. `autorecord` and
+//! `autorecd` add every kept capture with its levels, `cue_creator` adds the
+//! identified release and the track boundaries, and renaming or filing a
+//! recording into the library updates its path. The `autorec` tool lists
+//! and searches it.
+//!
+//! The catalog lives in `~/.state/autorec/catalog.db` next to the saved
+//! defaults. Paths are stored absolute, so the same recording is found
+//! from any working directory.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use tracing::warn;
+
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::recorder::RecordingStats;
+
+/// File name of the catalog in the state directory
+pub const CATALOG_FILE_NAME: &str = "catalog.db";
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS recordings (
+        id INTEGER PRIMARY KEY,
+        path TEXT NOT NULL UNIQUE,
+        started TEXT,
+        duration REAL,
+        average_level_db REAL,
+        true_peak_db REAL,
+        overs INTEGER NOT NULL DEFAULT 0,
+        dropouts INTEGER NOT NULL DEFAULT 0,
+        flac_file TEXT,
+        preview_file TEXT,
+        artist TEXT,
+        album TEXT,
+        release TEXT,
+        year INTEGER,
+        side TEXT,
+        groove_in REAL,
+        groove_out REAL
+    );
+    CREATE TABLE IF NOT EXISTS tracks (
+        recording_id INTEGER NOT NULL REFERENCES recordings(id) ON DELETE CASCADE,
+        number INTEGER NOT NULL,
+        title TEXT,
+        start REAL NOT NULL,
+        PRIMARY KEY (recording_id, number)
+    );
+";
+
+const ENTRY_COLUMNS: &str = "id, path, started, duration, average_level_db, true_peak_db, overs, dropouts, \
+                             artist, album, release, year, side, groove_in, groove_out";
+
+/// Path of the catalog (`~/.state/autorec/catalog.db`)
+pub fn catalog_path() -> Option<PathBuf> {
+    Config::get_config_path().ok().map(|path| path.with_file_name(CATALOG_FILE_NAME))
+}
+
+/// Run `update` on the catalog at [`catalog_path`]. Failures are only
+/// printed: the catalog is never needed to finish a recording.
+pub fn update(update: impl FnOnce(&Catalog) -> Result<()>) {
+    if let Err(e) = Catalog::open_default().and_then(|catalog| update(&catalog)) {
+        warn!("{}", e);
+    }
+}
+
+/// Absolute form of `path`; the file itself may be gone already
+fn absolute(path: &str) -> String {
+    let path = Path::new(path);
+    let dir = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    match (fs::canonicalize(dir), path.file_name()) {
+        (Ok(dir), Some(name)) => dir.join(name).to_string_lossy().to_string(),
+        _ => path.to_string_lossy().to_string(),
+    }
+}
+
+/// A finished capture as the recorder reports it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Capture {
+    pub path: String,
+    /// Local time the recording started, "2024-03-02 20:15:00"
+    pub started: Option<String>,
+    pub duration: f64,
+    pub average_level_db: Option<f64>,
+    pub true_peak_db: Option<f64>,
+    pub overs: usize,
+    pub dropouts: usize,
+    pub flac_file: Option<String>,
+    pub preview_file: Option<String>,
+}
+
+impl Capture {
+    pub fn from_stats(stats: &RecordingStats) -> Self {
+        Capture {
+            path: stats.filename.clone(),
+            started: Some(DateTime::<Local>::from(stats.started).format("%Y-%m-%d %H:%M:%S").to_string()),
+            duration: stats.duration,
+            average_level_db: stats.average_level_db,
+            true_peak_db: stats.max_true_peak_db,
+            overs: stats.clipping.total(),
+            dropouts: stats.dropouts.len(),
+            flac_file: stats.flac_file.clone(),
+            preview_file: stats.preview_file.clone(),
+        }
+    }
+}
+
+/// The release a recording was identified as
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Identification {
+    pub artist: String,
+    pub album: String,
+    /// Release reference (URL)
+    pub release: Option<String>,
+    pub year: Option<u32>,
+    /// Side of the release ('A', 'B', ...)
+    pub side: Option<char>,
+}
+
+/// A track found in a recording
+#[derive(Debug, Clone, PartialEq)]
+pub struct CatalogTrack {
+    pub number: u32,
+    pub title: Option<String>,
+    /// Start in seconds from the beginning of the file
+    pub start: f64,
+}
+
+/// A recording with everything known about it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CatalogEntry {
+    pub path: String,
+    pub started: Option<String>,
+    pub duration: Option<f64>,
+    pub average_level_db: Option<f64>,
+    pub true_peak_db: Option<f64>,
+    pub overs: usize,
+    pub dropouts: usize,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub release: Option<String>,
+    pub year: Option<u32>,
+    pub side: Option<char>,
+    pub groove_in: Option<f64>,
+    pub groove_out: Option<f64>,
+    pub tracks: Vec<CatalogTrack>,
+}
+
+pub struct Catalog {
+    conn: Connection,
+}
+
+impl Catalog {
+    /// Open the catalog at `path`, creating it if needed
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(dir) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let conn = Connection::open(path)?;
+        conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(Catalog { conn })
+    }
+
+    /// Open the catalog at [`catalog_path`]
+    pub fn open_default() -> Result<Self> {
+        let path = catalog_path().ok_or_else(|| Error::Config("HOME environment variable not set".to_string()))?;
+        Self::open(&path)
+    }
+
+    /// ID of the recording at `path`, added if it is not listed yet
+    fn recording_id(&self, path: &str) -> Result<i64> {
+        let path = absolute(path);
+        self.conn.execute("INSERT OR IGNORE INTO recordings (path) VALUES (?1)", params![path])?;
+        Ok(self.conn.query_row("SELECT id FROM recordings WHERE path = ?1", params![path], |row| row.get(0))?)
+    }
+
+    /// Add a finished capture, or update it if the file is listed already
+    pub fn add_capture(&self, capture: &Capture) -> Result<()> {
+        let id = self.recording_id(&capture.path)?;
+        self.conn.execute(
+            "UPDATE recordings SET started = ?2, duration = ?3, average_level_db = ?4, true_peak_db = ?5,
+                 overs = ?6, dropouts = ?7, flac_file = ?8, preview_file = ?9 WHERE id = ?1",
+            params![
+                id,
+                capture.started,
+                capture.duration,
+                capture.average_level_db,
+                capture.true_peak_db,
+                capture.overs as i64,
+                capture.dropouts as i64,
+                capture.flac_file.as_deref().map(absolute),
+                capture.preview_file.as_deref().map(absolute),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Record the release `path` was identified as
+    pub fn set_identification(&self, path: &str, identification: &Identification) -> Result<()> {
+        let id = self.recording_id(path)?;
+        self.conn.execute(
+            "UPDATE recordings SET artist = ?2, album = ?3, release = ?4, year = ?5, side = ?6 WHERE id = ?1",
+            params![
+                id,
+                identification.artist,
+                identification.album,
+                identification.release,
+                identification.year,
+                identification.side.map(|c| c.to_string()),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Replace the boundary results of `path`: the music region and the tracks
+    pub fn set_tracks(&self, path: &str, duration: f64, groove: (f64, f64), tracks: &[CatalogTrack]) -> Result<()> {
+        let id = self.recording_id(path)?;
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute(
+            "UPDATE recordings SET duration = COALESCE(duration, ?2), groove_in = ?3, groove_out = ?4 WHERE id = ?1",
+            params![id, duration, groove.0, groove.1],
+        )?;
+        tx.execute("DELETE FROM tracks WHERE recording_id = ?1", params![id])?;
+        for track in tracks {
+            tx.execute(
+                "INSERT INTO tracks (recording_id, number, title, start) VALUES (?1, ?2, ?3, ?4)",
+                params![id, track.number, track.title, track.start],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Follow a recording or its FLAC copy or preview that was renamed or
+    /// moved; false if `old` is not listed
+    pub fn move_file(&self, old: &str, new: &str) -> Result<bool> {
+        let (old, new) = (absolute(old), absolute(new));
+        let mut changed = 0;
+        for column in ["path", "flac_file", "preview_file"] {
+            changed += self.conn.execute(
+                &format!("UPDATE recordings SET {0} = ?2 WHERE {0} = ?1", column),
+                params![old, new],
+            )?;
+        }
+        Ok(changed > 0)
+    }
+
+    fn entry(&self, row: &Row) -> rusqlite::Result<(i64, CatalogEntry)> {
+        let side: Option<String> = row.get(12)?;
+        Ok((row.get(0)?, CatalogEntry {
+            path: row.get(1)?,
+            started: row.get(2)?,
+            duration: row.get(3)?,
+            average_level_db: row.get(4)?,
+            true_peak_db: row.get(5)?,
+            overs: row.get::<_, i64>(6)? as usize,
+            dropouts: row.get::<_, i64>(7)? as usize,
+            artist: row.get(8)?,
+            album: row.get(9)?,
+            release: row.get(10)?,
+            year: row.get(11)?,
+            side: side.and_then(|s| s.chars().next()),
+            groove_in: row.get(13)?,
+            groove_out: row.get(14)?,
+            tracks: Vec::new(),
+        }))
+    }
+
+    fn entries(&self, filter: &str, pattern: Option<&str>) -> Result<Vec<CatalogEntry>> {
+        let sql = format!("SELECT {} FROM recordings r {} ORDER BY started, path", ENTRY_COLUMNS, filter);
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = match pattern {
+            Some(pattern) => stmt.query_map(params![pattern], |row| self.entry(row))?.collect::<rusqlite::Result<Vec<_>>>()?,
+            None => stmt.query_map([], |row| self.entry(row))?.collect::<rusqlite::Result<Vec<_>>>()?,
+        };
+        let mut tracks = self.conn.prepare("SELECT number, title, start FROM tracks WHERE recording_id = ?1 ORDER BY number")?;
+        let mut entries = Vec::with_capacity(rows.len());
+        for (id, mut entry) in rows {
+            entry.tracks = tracks
+                .query_map(params![id], |row| Ok(CatalogTrack { number: row.get(0)?, title: row.get(1)?, start: row.get(2)? }))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+
+    /// All recordings, oldest first
+    pub fn list(&self) -> Result<Vec<CatalogEntry>> {
+        self.entries("", None)
+    }
+
+    /// Recordings whose artist, album, release, path or a track title
+    /// contains `text` (case-insensitive for ASCII)
+    pub fn search(&self, text: &str) -> Result<Vec<CatalogEntry>> {
+        let escaped = text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+        let pattern = format!("%{}%", escaped);
+        self.entries(
+            "WHERE r.artist LIKE ?1 ESCAPE '\\' OR r.album LIKE ?1 ESCAPE '\\' OR r.release LIKE ?1 ESCAPE '\\'
+                OR r.path LIKE ?1 ESCAPE '\\'
+                OR EXISTS (SELECT 1 FROM tracks t WHERE t.recording_id = r.id AND t.title LIKE ?1 ESCAPE '\\')",
+            Some(&pattern),
+        )
+    }
+
+    /// The entry of the recording at `path`
+    pub fn get(&self, path: &str) -> Result<Option<CatalogEntry>> {
+        let id: Option<i64> = self
+            .conn
+            .query_row("SELECT id FROM recordings WHERE path = ?1", params![absolute(path)], |row| row.get(0))
+            .optional()?;
+        Ok(match id {
+            Some(id) => self.entries(&format!("WHERE r.id = {}", id), None)?.pop(),
+            None => None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_catalog() {
+        let dir = tempfile::tempdir().unwrap();
+        let catalog = Catalog::open(&dir.path().join("state/catalog.db")).unwrap();
+        let wav = dir.path().join("animals.1.wav").to_string_lossy().to_string();
+        catalog.add_capture(&Capture {
+            path: wav.clone(),
+            started: Some("2024-03-02 20:15:00".to_string()),
+            duration: 1292.4,
+            average_level_db: Some(-21.5),
+            overs: 3,
+            ..Default::default()
+        }).unwrap();
+        catalog.set_identification(&wav, &Identification {
+            artist: "Pink Floyd".to_string(),
+            album: "Animals".to_string(),
+            release: Some("https://www.discogs.com/release/1873013".to_string()),
+            year: Some(1977),
+            side: Some('A'),
+        }).unwrap();
+        let tracks = [
+            CatalogTrack { number: 1, title: Some("Pigs on the Wing 1".to_string()), start: 2.0 },
+            CatalogTrack { number: 2, title: Some("Dogs".to_string()), start: 88.5 },
+        ];
+        catalog.set_tracks(&wav, 1292.4, (2.0, 1290.0), &tracks).unwrap();
+
+        let entries = catalog.list().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!((entries[0].overs, entries[0].side, entries[0].year), (3, Some('A'), Some(1977)));
+        assert_eq!(entries[0].tracks, tracks);
+        assert_eq!(catalog.search("dogs").unwrap().len(), 1);
+        assert_eq!(catalog.search("floyd").unwrap().len(), 1);
+        assert!(catalog.search("50%").unwrap().is_empty());
+
+        let moved = dir.path().join("Pink Floyd - Animals.1.wav").to_string_lossy().to_string();
+        assert!(catalog.move_file(&wav, &moved).unwrap());
+        assert!(catalog.get(&wav).unwrap().is_none());
+        assert_eq!(catalog.get(&moved).unwrap().unwrap().album.as_deref(), Some("Animals"));
+    }
+}
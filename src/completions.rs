@@ -0,0 +1,305 @@
+//! Shell completions and man pages for installing the programs.
+//!
+//! The man pages in `man/` are built into the binary, so `autorec man` can
+//! install them where no distribution package does (e.g. after `cargo
+//! install`). `autorec completions <shell>` generates bash, zsh and fish
+//! completions from the options those pages document, so the completions
+//! and the documentation can't disagree.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Man pages of the programs, by program name
+pub const MAN_PAGES: &[(&str, &str)] = &[
+    ("autorec", include_str!("../man/autorec.1")),
+    ("autorecord", include_str!("../man/autorecord.1")),
+    ("cue_creator", include_str!("../man/cue_creator.1")),
+    ("pause_analyzer", include_str!("../man/pause_analyzer.1")),
+    ("vu_meter", include_str!("../man/vu_meter.1")),
+];
+
+/// Commands of `autorec`, completed as its first argument
+pub const AUTOREC_COMMANDS: &[&str] = &["list", "search", "completions", "man"];
+
+/// Shells completions are generated for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+/// Names accepted by [`Shell::from_str`]
+pub const SHELL_NAMES: &[&str] = &["bash", "zsh", "fish"];
+
+impl Shell {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "bash" => Some(Shell::Bash),
+            "zsh" => Some(Shell::Zsh),
+            "fish" => Some(Shell::Fish),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Shell::Bash => "bash",
+            Shell::Zsh => "zsh",
+            Shell::Fish => "fish",
+        }
+    }
+}
+
+/// An option documented in a man page
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProgramOption {
+    /// Long name without the dashes, e.g. "min-prominence"
+    pub long: String,
+    pub short: Option<char>,
+    /// Placeholder of the value, e.g. "SECONDS"; None for a flag
+    pub value: Option<String>,
+    /// First sentence of the description
+    pub description: String,
+}
+
+/// The man page of `program`
+pub fn man_page(program: &str) -> Option<&'static str> {
+    MAN_PAGES.iter().find(|(name, _)| *name == program).map(|(_, page)| *page)
+}
+
+/// Write all man pages to `dir` as `<program>.1`; returns the files written
+pub fn write_man_pages(dir: &Path) -> io::Result<Vec<String>> {
+    fs::create_dir_all(dir)?;
+    let mut written = Vec::new();
+    for (program, page) in MAN_PAGES {
+        let path = dir.join(format!("{}.1", program));
+        fs::write(&path, page)?;
+        written.push(path.display().to_string());
+    }
+    Ok(written)
+}
+
+/// Text of a roff line without font changes and escapes
+fn plain_text(roff: &str) -> String {
+    roff.replace("\\fB", "")
+        .replace("\\fI", "")
+        .replace("\\fR", "")
+        .replace("\\-", "-")
+        .replace('"', "")
+}
+
+/// Options of the `.TP` paragraphs of a man page's OPTIONS section, e.g.
+/// `.BR \-r ", " \-\-rate " " \fIRATE\fR`
+pub fn man_options(page: &str) -> Vec<ProgramOption> {
+    let mut options = Vec::new();
+    let mut lines = page.lines().skip_while(|line| line.trim() != ".SH OPTIONS").skip(1).peekable();
+    while let Some(line) = lines.next() {
+        if line.starts_with(".SH") {
+            break;
+        }
+        let Some(synopsis) = line.strip_prefix(".BR ").filter(|s| s.starts_with("\\-")) else {
+            continue;
+        };
+        let mut option = ProgramOption { long: String::new(), short: None, value: None, description: String::new() };
+        for word in synopsis.split_whitespace() {
+            if word.starts_with("\\fI") {
+                option.value = Some(plain_text(word));
+                continue;
+            }
+            let word = plain_text(word);
+            let word = word.trim_end_matches(',');
+            if let Some(long) = word.strip_prefix("--") {
+                option.long = long.to_string();
+            } else if let Some(short) = word.strip_prefix('-') {
+                option.short = short.chars().next();
+            }
+        }
+
+        let mut description = Vec::new();
+        while let Some(text) = lines.next_if(|l| !l.trim().is_empty() && !l.starts_with('.')) {
+            description.push(plain_text(text.trim()));
+        }
+        let description = description.join(" ");
+        let sentence = description.split(". ").next().unwrap_or_default();
+        option.description = sentence.trim_end_matches('.').to_string();
+        if !option.long.is_empty() {
+            options.push(option);
+        }
+    }
+    options
+}
+
+/// Completion script for all programs with a man page
+pub fn completions(shell: Shell) -> String {
+    let programs: Vec<(&str, Vec<ProgramOption>)> =
+        MAN_PAGES.iter().map(|(program, page)| (*program, man_options(page))).collect();
+    match shell {
+        Shell::Bash => bash_completions(&programs),
+        Shell::Zsh => zsh_completions(&programs),
+        Shell::Fish => fish_completions(&programs),
+    }
+}
+
+fn bash_completions(programs: &[(&str, Vec<ProgramOption>)]) -> String {
+    let mut script = String::from("# bash completion for HiFiBerry AutoRec\n");
+    for (program, options) in programs {
+        let words: Vec<String> = options
+            .iter()
+            .flat_map(|o| o.short.map(|s| format!("-{}", s)).into_iter().chain([format!("--{}", o.long)]))
+            .collect();
+        let function = format!("_autorec_{}", program);
+        script.push_str(&format!("\n{}() {{\n", function));
+        script.push_str("    local cur=\"${COMP_WORDS[COMP_CWORD]}\"\n");
+        if *program == "autorec" {
+            script.push_str("    if [[ $COMP_CWORD -eq 1 && \"$cur\" != -* ]]; then\n");
+            script.push_str(&format!("        COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))\n", AUTOREC_COMMANDS.join(" ")));
+            script.push_str("        return\n    fi\n");
+            script.push_str("    case \"${COMP_WORDS[1]}\" in\n");
+            script.push_str(&format!(
+                "        completions) COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")); return ;;\n",
+                SHELL_NAMES.join(" ")
+            ));
+            let names: Vec<&str> = MAN_PAGES.iter().map(|(name, _)| *name).collect();
+            script.push_str(&format!(
+                "        man) [[ \"$cur\" != -* ]] && COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")) && return ;;\n",
+                names.join(" ")
+            ));
+            script.push_str("    esac\n");
+        }
+        script.push_str("    if [[ \"$cur\" == -* ]]; then\n");
+        script.push_str(&format!("        COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))\n", words.join(" ")));
+        script.push_str("    fi\n}\n");
+        // Without a match, bash completes file names
+        script.push_str(&format!("complete -o default -F {} {}\n", function, program));
+    }
+    script
+}
+
+/// Text for a zsh `_arguments` spec, where brackets, colons and quotes are special
+fn zsh_escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+        .replace(':', "\\:")
+        .replace('\'', "'\\''")
+}
+
+fn zsh_completions(programs: &[(&str, Vec<ProgramOption>)]) -> String {
+    let names: Vec<&str> = programs.iter().map(|(program, _)| *program).collect();
+    let mut script = format!("#compdef {}\n# zsh completion for HiFiBerry AutoRec\n\ncase $service in\n", names.join(" "));
+    for (program, options) in programs {
+        script.push_str(&format!("  {})\n    _arguments \\\n", program));
+        for option in options {
+            let description = zsh_escape(&option.description);
+            let value = option.value.as_deref().map(|v| format!(":{}:", zsh_escape(v))).unwrap_or_default();
+            match option.short {
+                Some(short) => script.push_str(&format!(
+                    "      '(-{s} --{l})'{{-{s},--{l}}}'[{d}]{v}' \\\n",
+                    s = short,
+                    l = option.long,
+                    d = description,
+                    v = value
+                )),
+                None => script.push_str(&format!("      '--{}[{}]{}' \\\n", option.long, description, value)),
+            }
+        }
+        if *program == "autorec" {
+            script.push_str(&format!("      '1:command:({})' \\\n", AUTOREC_COMMANDS.join(" ")));
+            script.push_str("      '2:argument:->argument' \\\n");
+            script.push_str("      && return\n");
+            script.push_str("    case $words[2] in\n");
+            script.push_str(&format!("      completions) _values shell {} ;;\n", SHELL_NAMES.join(" ")));
+            script.push_str(&format!("      man) _values program {} ;;\n", names.join(" ")));
+            script.push_str("    esac\n");
+        } else {
+            script.push_str("      '*:file:_files'\n");
+        }
+        script.push_str("    ;;\n");
+    }
+    script.push_str("esac\n");
+    script
+}
+
+fn fish_completions(programs: &[(&str, Vec<ProgramOption>)]) -> String {
+    let mut script = String::from("# fish completion for HiFiBerry AutoRec\n");
+    for (program, options) in programs {
+        script.push('\n');
+        if *program == "autorec" {
+            let names: Vec<&str> = MAN_PAGES.iter().map(|(name, _)| *name).collect();
+            script.push_str(&format!(
+                "complete -c autorec -f -n __fish_use_subcommand -a '{}'\n",
+                AUTOREC_COMMANDS.join(" ")
+            ));
+            script.push_str(&format!(
+                "complete -c autorec -f -n '__fish_seen_subcommand_from completions' -a '{}'\n",
+                SHELL_NAMES.join(" ")
+            ));
+            script.push_str(&format!(
+                "complete -c autorec -f -n '__fish_seen_subcommand_from man' -a '{}'\n",
+                names.join(" ")
+            ));
+        }
+        for option in options {
+            let mut line = format!("complete -c {} -l {}", program, option.long);
+            if let Some(short) = option.short {
+                line.push_str(&format!(" -s {}", short));
+            }
+            if option.value.is_some() {
+                line.push_str(" -r");
+            }
+            line.push_str(&format!(" -d '{}'\n", option.description.replace('\'', "\\'")));
+            script.push_str(&line);
+        }
+    }
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_man_options() {
+        let options = man_options(man_page("vu_meter").unwrap());
+        let rate = options.iter().find(|o| o.long == "rate").unwrap();
+        assert_eq!(rate.short, Some('r'));
+        assert_eq!(rate.value.as_deref(), Some("RATE"));
+        assert_eq!(rate.description, "Sample rate in Hz (default: 48000)");
+        let mono = options.iter().find(|o| o.long == "mono").unwrap();
+        assert_eq!((mono.short, mono.value.as_deref()), (None, None));
+
+        let options = man_options(man_page("cue_creator").unwrap());
+        let prominence = options.iter().find(|o| o.long == "min-prominence").unwrap();
+        assert_eq!(prominence.value.as_deref(), Some("DB"));
+        // Every documented program has options
+        assert!(MAN_PAGES.iter().all(|(_, page)| !man_options(page).is_empty()));
+    }
+
+    #[test]
+    fn test_completions() {
+        let bash = completions(Shell::Bash);
+        assert!(bash.contains("complete -o default -F _autorec_cue_creator cue_creator\n"));
+        assert!(bash.contains("--min-prominence"));
+        assert!(bash.contains("compgen -W \"list search completions man\""));
+
+        let zsh = completions(Shell::Zsh);
+        assert!(zsh.starts_with("#compdef autorec autorecord cue_creator pause_analyzer vu_meter\n"));
+        assert!(zsh.contains("'(-r --rate)'{-r,--rate}'[Sample rate in Hz (default\\: 48000)]:RATE:' \\\n"));
+
+        let fish = completions(Shell::Fish);
+        assert!(fish.contains("complete -c vu_meter -l rate -s r -r -d 'Sample rate in Hz (default: 48000)'\n"));
+        assert!(fish.contains("complete -c autorec -f -n '__fish_seen_subcommand_from completions' -a 'bash zsh fish'\n"));
+
+        assert_eq!(SHELL_NAMES.iter().filter_map(|s| Shell::from_str(s)).map(|s| s.as_str()).collect::<Vec<_>>(), SHELL_NAMES);
+    }
+
+    #[test]
+    fn test_write_man_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let written = write_man_pages(dir.path()).unwrap();
+        assert_eq!(written.len(), MAN_PAGES.len());
+        assert_eq!(fs::read_to_string(dir.path().join("autorec.1")).unwrap(), man_page("autorec").unwrap());
+    }
+}
@@ -1,8 +1,976 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::io;
 
+use crate::audio_analysis::parse_channel_list;
+use crate::decibel::Calibration;
+use crate::vu_meter::SampleFormat;
+
+/// Calibration key used when no source-specific entry exists
+pub const DEFAULT_CALIBRATION_KEY: &str = "default";
+
+/// System-wide config file, read before the user's files
+pub const SYSTEM_CONFIG_PATH: &str = "/etc/autorec/config.toml";
+
+/// Prefix of the environment variables that override config values
+pub const ENV_PREFIX: &str = "AUTOREC_";
+
+/// The profile selected with `--profile <name>`
+///
+/// Programs look for it before parsing the rest of their arguments, since
+/// the profile provides the defaults of the other options.
+pub fn profile_arg(args: &[String]) -> Option<&str> {
+    args.windows(2).find(|w| w[0] == "--profile").map(|w| w[1].as_str())
+}
+
+/// Value of an environment variable: a TOML number, boolean or array, or
+/// else the text as a string
+fn env_value(raw: &str) -> toml::Value {
+    toml::from_str::<toml::Table>(&format!("value = {}", raw))
+        .ok()
+        .and_then(|mut t| t.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(raw.to_string()))
+}
+
+/// A named audio source with optional per-source parameters
+///
+/// Defined in the config file as e.g.
+/// `[sources.vinyl] address = "pipewire:riaa.monitor"`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SourceAlias {
+    /// Audio source address (anything accepted by `--source`)
+    pub address: String,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate: Option<u32>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channels: Option<usize>,
+    
+    /// Input channels to record, e.g. "3,4"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel_map: Option<String>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub off_threshold: Option<f64>,
+}
+
+/// A source alias with its format and channel map parsed, see
+/// [`Config::resolve_source`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedSource {
+    pub alias: SourceAlias,
+    pub format: Option<SampleFormat>,
+    pub channel_map: Option<Vec<usize>>,
+}
+
+/// Defaults for `cue_creator`, in the `[cue_creator]` section
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CueCreatorConfig {
+    /// Minimum valley prominence for a song boundary in dB
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_prominence: Option<f32>,
+    
+    /// Minimum song duration in seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_song: Option<f64>,
+    
+    /// RMS smoothing window in seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smooth_window: Option<f64>,
+    
+    /// RMS window size in milliseconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chunk_ms: Option<u32>,
+    
+    /// Kind of recording: vinyl or tape (see [`crate::boundary::Medium`])
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub medium: Option<String>,
+    
+    /// Identify songs with AcoustID instead of Shazam
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub use_acoustid: Option<bool>,
+    
+    /// Album lookup backends in order of preference
+    /// (see [`crate::lookup::BACKEND_NAMES`])
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lookup_backends: Option<Vec<String>>,
+    
+    /// Rename recordings after the identified album
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rename: Option<bool>,
+    
+    /// File name template of renamed recordings, without the extension
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rename_template: Option<String>,
+    
+    /// Stop before the boundary analysis if the release was recorded before
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub check_duplicates: Option<bool>,
+    
+    /// Also write the track starts into the WAV file as cue markers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wav_markers: Option<bool>,
+    
+    /// Write a spectrogram PNG next to each recording
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spectrogram: Option<bool>,
+    
+    /// Write an HTML analysis report next to each recording
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub report: Option<bool>,
+    
+    /// Character set of written CUE files: utf8 or latin1
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cue_encoding: Option<String>,
+    
+    /// Write only ASCII into CUE files, transliterating other characters
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cue_transliterate: Option<bool>,
+}
+
+impl CueCreatorConfig {
+    /// Merge with another section, preferring values from other
+    pub fn merge(&mut self, other: &CueCreatorConfig) {
+        if other.min_prominence.is_some() {
+            self.min_prominence = other.min_prominence;
+        }
+        if other.min_song.is_some() {
+            self.min_song = other.min_song;
+        }
+        if other.smooth_window.is_some() {
+            self.smooth_window = other.smooth_window;
+        }
+        if other.chunk_ms.is_some() {
+            self.chunk_ms = other.chunk_ms;
+        }
+        if other.medium.is_some() {
+            self.medium = other.medium.clone();
+        }
+        if other.use_acoustid.is_some() {
+            self.use_acoustid = other.use_acoustid;
+        }
+        if other.lookup_backends.is_some() {
+            self.lookup_backends = other.lookup_backends.clone();
+        }
+        if other.rename.is_some() {
+            self.rename = other.rename;
+        }
+        if other.rename_template.is_some() {
+            self.rename_template = other.rename_template.clone();
+        }
+        if other.check_duplicates.is_some() {
+            self.check_duplicates = other.check_duplicates;
+        }
+        if other.wav_markers.is_some() {
+            self.wav_markers = other.wav_markers;
+        }
+        if other.spectrogram.is_some() {
+            self.spectrogram = other.spectrogram;
+        }
+        if other.report.is_some() {
+            self.report = other.report;
+        }
+        if other.cue_encoding.is_some() {
+            self.cue_encoding = other.cue_encoding.clone();
+        }
+        if other.cue_transliterate.is_some() {
+            self.cue_transliterate = other.cue_transliterate;
+        }
+    }
+
+    fn print(&self) {
+        if let Some(min_prominence) = self.min_prominence {
+            println!("  Min prominence:     {} dB", min_prominence);
+        }
+        if let Some(min_song) = self.min_song {
+            println!("  Min song length:    {} seconds", min_song);
+        }
+        if let Some(smooth_window) = self.smooth_window {
+            println!("  Smoothing window:   {} seconds", smooth_window);
+        }
+        if let Some(chunk_ms) = self.chunk_ms {
+            println!("  RMS window:         {} ms", chunk_ms);
+        }
+        if let Some(medium) = &self.medium {
+            println!("  Medium:             {}", medium);
+        }
+        if let Some(use_acoustid) = self.use_acoustid {
+            println!("  Song identification: {}", if use_acoustid { "AcoustID" } else { "Shazam" });
+        }
+        if let Some(backends) = &self.lookup_backends {
+            println!("  Lookup backends:    {}", backends.join(", "));
+        }
+        if let Some(rename) = self.rename {
+            println!("  Rename recordings:  {}", if rename { "enabled" } else { "disabled" });
+        }
+        if let Some(template) = &self.rename_template {
+            println!("  Rename template:    {}", template);
+        }
+        if let Some(check_duplicates) = self.check_duplicates {
+            println!("  Duplicate check:    {}", if check_duplicates { "enabled" } else { "disabled" });
+        }
+        if let Some(wav_markers) = self.wav_markers {
+            println!("  WAV cue markers:    {}", if wav_markers { "enabled" } else { "disabled" });
+        }
+        if let Some(spectrogram) = self.spectrogram {
+            println!("  Spectrogram:        {}", if spectrogram { "enabled" } else { "disabled" });
+        }
+        if let Some(report) = self.report {
+            println!("  HTML report:        {}", if report { "enabled" } else { "disabled" });
+        }
+        if let Some(encoding) = &self.cue_encoding {
+            println!("  CUE encoding:       {}", encoding);
+        }
+        if let Some(transliterate) = self.cue_transliterate {
+            println!("  CUE transliteration: {}", if transliterate { "enabled" } else { "disabled" });
+        }
+    }
+}
+
+/// Defaults for `track_splitter`, in the `[splitter]` section
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SplitterConfig {
+    /// Directory for the track files (default: next to the recording)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_dir: Option<String>,
+    
+    /// Gap mode (append, prepend, discard)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gap: Option<String>,
+    
+    /// Track file format (wav, flac, mp3)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate: Option<u32>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bits: Option<u16>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dither: Option<bool>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replay_gain: Option<bool>,
+    
+    /// Normalization (off, album, track)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub normalize: Option<String>,
+    
+    /// True-peak target of the normalization in dBTP
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub normalize_peak: Option<f64>,
+    
+    /// Downmix the tracks to mono
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mono: Option<bool>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trim_lead_in: Option<bool>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trim_lead_out: Option<bool>,
+    
+    /// Groove noise kept when trimming, in seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trim_pad: Option<f64>,
+    
+    /// Filter the needle-drop thump out of the first track
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attenuate_needle_drop: Option<bool>,
+    
+    /// Correct the deck's speed drift (REM SPEED_CORRECTION) when splitting
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub correct_speed: Option<bool>,
+    
+    /// Names of the track files (see [`crate::filename_template`])
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filename_template: Option<String>,
+    
+    /// Sanitization of names in file names (safe, ascii, portable)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filename_sanitize: Option<String>,
+}
+
+impl SplitterConfig {
+    /// Merge with another section, preferring values from other
+    pub fn merge(&mut self, other: &SplitterConfig) {
+        if other.output_dir.is_some() {
+            self.output_dir = other.output_dir.clone();
+        }
+        if other.gap.is_some() {
+            self.gap = other.gap.clone();
+        }
+        if other.format.is_some() {
+            self.format = other.format.clone();
+        }
+        if other.rate.is_some() {
+            self.rate = other.rate;
+        }
+        if other.bits.is_some() {
+            self.bits = other.bits;
+        }
+        if other.dither.is_some() {
+            self.dither = other.dither;
+        }
+        if other.replay_gain.is_some() {
+            self.replay_gain = other.replay_gain;
+        }
+        if other.normalize.is_some() {
+            self.normalize = other.normalize.clone();
+        }
+        if other.normalize_peak.is_some() {
+            self.normalize_peak = other.normalize_peak;
+        }
+        if other.mono.is_some() {
+            self.mono = other.mono;
+        }
+        if other.trim_lead_in.is_some() {
+            self.trim_lead_in = other.trim_lead_in;
+        }
+        if other.trim_lead_out.is_some() {
+            self.trim_lead_out = other.trim_lead_out;
+        }
+        if other.trim_pad.is_some() {
+            self.trim_pad = other.trim_pad;
+        }
+        if other.attenuate_needle_drop.is_some() {
+            self.attenuate_needle_drop = other.attenuate_needle_drop;
+        }
+        if other.correct_speed.is_some() {
+            self.correct_speed = other.correct_speed;
+        }
+        if other.filename_template.is_some() {
+            self.filename_template = other.filename_template.clone();
+        }
+        if other.filename_sanitize.is_some() {
+            self.filename_sanitize = other.filename_sanitize.clone();
+        }
+    }
+
+    fn print(&self) {
+        if let Some(output_dir) = &self.output_dir {
+            println!("  Track directory:    {}", output_dir);
+        }
+        if let Some(gap) = &self.gap {
+            println!("  Gap mode:           {}", gap);
+        }
+        if let Some(format) = &self.format {
+            println!("  Track format:       {}", format);
+        }
+        if let Some(rate) = self.rate {
+            println!("  Track sample rate:  {} Hz", rate);
+        }
+        if let Some(bits) = self.bits {
+            println!("  Track bit depth:    {} bit", bits);
+        }
+        if let Some(dither) = self.dither {
+            println!("  Dither:             {}", if dither { "enabled" } else { "disabled" });
+        }
+        if let Some(replay_gain) = self.replay_gain {
+            println!("  ReplayGain tags:    {}", if replay_gain { "enabled" } else { "disabled" });
+        }
+        if let Some(normalize) = &self.normalize {
+            println!("  Normalize:          {}", normalize);
+        }
+        if let Some(normalize_peak) = self.normalize_peak {
+            println!("  Normalize peak:     {} dBTP", normalize_peak);
+        }
+        if let Some(mono) = self.mono {
+            println!("  Mono:               {}", if mono { "enabled" } else { "disabled" });
+        }
+        if let Some(trim_lead_in) = self.trim_lead_in {
+            println!("  Trim lead-in:       {}", if trim_lead_in { "enabled" } else { "disabled" });
+        }
+        if let Some(trim_lead_out) = self.trim_lead_out {
+            println!("  Trim lead-out:      {}", if trim_lead_out { "enabled" } else { "disabled" });
+        }
+        if let Some(trim_pad) = self.trim_pad {
+            println!("  Trim pad:           {} s", trim_pad);
+        }
+        if let Some(attenuate) = self.attenuate_needle_drop {
+            println!("  Needle-drop filter: {}", if attenuate { "enabled" } else { "disabled" });
+        }
+        if let Some(correct) = self.correct_speed {
+            println!("  Speed correction:   {}", if correct { "enabled" } else { "disabled" });
+        }
+        if let Some(template) = &self.filename_template {
+            println!("  Track file names:   {}", template);
+        }
+        if let Some(sanitize) = &self.filename_sanitize {
+            println!("  Track name charset: {}", sanitize);
+        }
+    }
+}
+
+/// Discogs credentials, in the `[discogs]` section
+///
+/// Also the format of `discogs_credentials.toml`. A personal access token is
+/// used if present; otherwise the consumer key and secret of an application,
+/// with the OAuth token of a user who authorized it if there is one.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DiscogsConfig {
+    /// Personal access token (discogs.com → Settings → Developers)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub consumer_key: Option<String>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub consumer_secret: Option<String>,
+    
+    /// OAuth 1.0a access token and secret
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oauth_token: Option<String>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oauth_token_secret: Option<String>,
+    
+    /// Discogs user whose collection is searched first (default: the owner
+    /// of the token)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    
+    /// Search the user's collection before all of Discogs (default: true)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collection: Option<bool>,
+}
+
+impl DiscogsConfig {
+    /// Merge with another section, preferring values from other
+    pub fn merge(&mut self, other: &DiscogsConfig) {
+        if other.token.is_some() {
+            self.token = other.token.clone();
+        }
+        if other.consumer_key.is_some() {
+            self.consumer_key = other.consumer_key.clone();
+        }
+        if other.consumer_secret.is_some() {
+            self.consumer_secret = other.consumer_secret.clone();
+        }
+        if other.oauth_token.is_some() {
+            self.oauth_token = other.oauth_token.clone();
+        }
+        if other.oauth_token_secret.is_some() {
+            self.oauth_token_secret = other.oauth_token_secret.clone();
+        }
+        if other.username.is_some() {
+            self.username = other.username.clone();
+        }
+        if other.collection.is_some() {
+            self.collection = other.collection;
+        }
+    }
+
+    /// Secrets are not printed, only which credentials are set
+    fn print(&self) {
+        if self.token.is_some() {
+            println!("  Discogs:            personal access token");
+        } else if self.oauth_token.is_some() {
+            println!("  Discogs:            OAuth token");
+        } else if self.consumer_key.is_some() {
+            println!("  Discogs:            consumer key");
+        }
+        if let Some(username) = &self.username {
+            println!("  Discogs user:       {}", username);
+        }
+        if let Some(collection) = self.collection {
+            println!("  Discogs collection: {}", if collection { "searched first" } else { "not used" });
+        }
+    }
+}
+
+/// Timeouts and retries of requests to MusicBrainz, Discogs and AcoustID,
+/// in the `[http]` section
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HttpConfig {
+    /// Seconds to wait for a connection (default: 10)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connect_timeout: Option<f64>,
+    
+    /// Seconds a whole request may take (default: 30)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<f64>,
+    
+    /// Retries after network errors and 5xx/429 responses (default: 3)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+}
+
+impl HttpConfig {
+    /// Merge with another section, preferring values from other
+    pub fn merge(&mut self, other: &HttpConfig) {
+        if other.connect_timeout.is_some() {
+            self.connect_timeout = other.connect_timeout;
+        }
+        if other.timeout.is_some() {
+            self.timeout = other.timeout;
+        }
+        if other.retries.is_some() {
+            self.retries = other.retries;
+        }
+    }
+
+    fn print(&self) {
+        if let Some(connect_timeout) = self.connect_timeout {
+            println!("  HTTP connect:       {} seconds", connect_timeout);
+        }
+        if let Some(timeout) = self.timeout {
+            println!("  HTTP timeout:       {} seconds", timeout);
+        }
+        if let Some(retries) = self.retries {
+            println!("  HTTP retries:       {}", retries);
+        }
+    }
+}
+
+/// Files the recorder writes, in the `[output]` section.
+///
+/// The WAV file is always written as captured; a FLAC listening copy in a
+/// delivery format can be written alongside it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OutputConfig {
+    /// Also write a FLAC copy of each recording (needs ffmpeg)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flac: Option<bool>,
+    
+    /// Sample rate of the FLAC copy (default: 44100)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flac_rate: Option<u32>,
+    
+    /// Bits per sample of the FLAC copy, 16 or 24 (default: 16)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flac_bits: Option<u16>,
+    
+    /// Add TPDF dither when the FLAC copy has fewer bits (default: true)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dither: Option<bool>,
+    
+    /// Names of the recordings (see [`crate::filename_template`])
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filename_template: Option<String>,
+    
+    /// Sanitization of names in file names (safe, ascii, portable)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filename_sanitize: Option<String>,
+}
+
+impl OutputConfig {
+    /// Merge with another section, preferring values from other
+    pub fn merge(&mut self, other: &OutputConfig) {
+        if other.flac.is_some() {
+            self.flac = other.flac;
+        }
+        if other.flac_rate.is_some() {
+            self.flac_rate = other.flac_rate;
+        }
+        if other.flac_bits.is_some() {
+            self.flac_bits = other.flac_bits;
+        }
+        if other.dither.is_some() {
+            self.dither = other.dither;
+        }
+        if other.filename_template.is_some() {
+            self.filename_template = other.filename_template.clone();
+        }
+        if other.filename_sanitize.is_some() {
+            self.filename_sanitize = other.filename_sanitize.clone();
+        }
+    }
+
+    fn print(&self) {
+        if let Some(flac) = self.flac {
+            println!("  FLAC copy:          {}", if flac { "enabled" } else { "disabled" });
+        }
+        if let Some(flac_rate) = self.flac_rate {
+            println!("  FLAC copy rate:     {} Hz", flac_rate);
+        }
+        if let Some(flac_bits) = self.flac_bits {
+            println!("  FLAC copy bits:     {}", flac_bits);
+        }
+        if let Some(dither) = self.dither {
+            println!("  FLAC copy dither:   {}", if dither { "enabled" } else { "disabled" });
+        }
+        if let Some(template) = &self.filename_template {
+            println!("  Recording names:    {}", template);
+        }
+        if let Some(sanitize) = &self.filename_sanitize {
+            println!("  Name charset:       {}", sanitize);
+        }
+    }
+}
+
+/// Filing finished sessions into the music library with `organize`, in the
+/// `[organize]` section
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OrganizeConfig {
+    /// Root directory of the music library
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub library_dir: Option<String>,
+    
+    /// Album directory below the library (see [`crate::organize`])
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filename_template: Option<String>,
+    
+    /// Sanitization of names in directory names (safe, ascii, portable)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filename_sanitize: Option<String>,
+    
+    /// What to do when a file exists in the library (skip, number, overwrite)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collision: Option<String>,
+}
+
+impl OrganizeConfig {
+    /// Merge with another section, preferring values from other
+    pub fn merge(&mut self, other: &OrganizeConfig) {
+        if other.library_dir.is_some() {
+            self.library_dir = other.library_dir.clone();
+        }
+        if other.filename_template.is_some() {
+            self.filename_template = other.filename_template.clone();
+        }
+        if other.filename_sanitize.is_some() {
+            self.filename_sanitize = other.filename_sanitize.clone();
+        }
+        if other.collision.is_some() {
+            self.collision = other.collision.clone();
+        }
+    }
+
+    fn print(&self) {
+        if let Some(library_dir) = &self.library_dir {
+            println!("  Music library:      {}", library_dir);
+        }
+        if let Some(template) = &self.filename_template {
+            println!("  Album directories:  {}", template);
+        }
+        if let Some(sanitize) = &self.filename_sanitize {
+            println!("  Library charset:    {}", sanitize);
+        }
+        if let Some(collision) = &self.collision {
+            println!("  Library collisions: {}", collision);
+        }
+    }
+}
+
+/// Status publishing and control of `autorecd` over MQTT, in the `[mqtt]`
+/// section
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MqttConfig {
+    /// Broker address, `host`, `host:port` or `mqtt://host:port`; MQTT is
+    /// off without it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub broker: Option<String>,
+    
+    /// Prefix of all topics (default: autorec)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub topic_prefix: Option<String>,
+    
+    /// Client ID (default: autorec-<hostname>)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    
+    /// Seconds between level updates (default: 1)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interval: Option<f64>,
+}
+
+impl MqttConfig {
+    /// Merge with another section, preferring values from other
+    pub fn merge(&mut self, other: &MqttConfig) {
+        if other.broker.is_some() {
+            self.broker = other.broker.clone();
+        }
+        if other.topic_prefix.is_some() {
+            self.topic_prefix = other.topic_prefix.clone();
+        }
+        if other.client_id.is_some() {
+            self.client_id = other.client_id.clone();
+        }
+        if other.username.is_some() {
+            self.username = other.username.clone();
+        }
+        if other.password.is_some() {
+            self.password = other.password.clone();
+        }
+        if other.interval.is_some() {
+            self.interval = other.interval;
+        }
+    }
+
+    /// The password is not printed
+    fn print(&self) {
+        if let Some(broker) = &self.broker {
+            println!("  MQTT broker:        {}", broker);
+        }
+        if let Some(prefix) = &self.topic_prefix {
+            println!("  MQTT topics:        {}/...", prefix);
+        }
+        if let Some(client_id) = &self.client_id {
+            println!("  MQTT client ID:     {}", client_id);
+        }
+        if let Some(username) = &self.username {
+            println!("  MQTT user:          {}", username);
+        }
+        if let Some(interval) = self.interval {
+            println!("  MQTT level updates: every {}s", interval);
+        }
+    }
+}
+
+/// Log output, in the `[log]` section (see [`crate::logging`])
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LogConfig {
+    /// Filter, a level or per-module levels like "info,autorec::boundary=debug"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub level: Option<String>,
+    
+    /// text or json
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    
+    /// Directory of the `autorecd` log files (default: stderr)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dir: Option<String>,
+    
+    /// New log file hourly, daily or never (default: daily)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rotation: Option<String>,
+}
+
+impl LogConfig {
+    /// Merge with another section, preferring values from other
+    pub fn merge(&mut self, other: &LogConfig) {
+        if other.level.is_some() {
+            self.level = other.level.clone();
+        }
+        if other.format.is_some() {
+            self.format = other.format.clone();
+        }
+        if other.dir.is_some() {
+            self.dir = other.dir.clone();
+        }
+        if other.rotation.is_some() {
+            self.rotation = other.rotation.clone();
+        }
+    }
+
+    fn print(&self) {
+        if let Some(level) = &self.level {
+            println!("  Log level:          {}", level);
+        }
+        if let Some(format) = &self.format {
+            println!("  Log format:         {}", format);
+        }
+        if let Some(dir) = &self.dir {
+            println!("  Log directory:      {}", dir);
+        }
+        if let Some(rotation) = &self.rotation {
+            println!("  Log rotation:       {}", rotation);
+        }
+    }
+}
+
+/// Front panel display, in the `[oled]` section (see `autorec::oled`)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OledConfig {
+    /// Show the recorder state on the display in `autorecd`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    
+    /// i2c or spi (default: i2c)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interface: Option<String>,
+    
+    /// Device node (default: /dev/i2c-1 or /dev/spidev0.0)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device: Option<String>,
+    
+    /// I2C address (default: 0x3c)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<u16>,
+    
+    /// 64 or 32 rows (default: 64)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    
+    /// Turn the picture by 180 degrees
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rotate: Option<bool>,
+    
+    /// GPIO chip of the SPI control lines (default: /dev/gpiochip0)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gpio_chip: Option<String>,
+    
+    /// GPIO line of the data/command pin, needed for SPI
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dc_pin: Option<u32>,
+    
+    /// GPIO line of the reset pin, if connected
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reset_pin: Option<u32>,
+}
+
+impl OledConfig {
+    /// Merge with another section, preferring values from other
+    pub fn merge(&mut self, other: &OledConfig) {
+        if other.enabled.is_some() {
+            self.enabled = other.enabled;
+        }
+        if other.interface.is_some() {
+            self.interface = other.interface.clone();
+        }
+        if other.device.is_some() {
+            self.device = other.device.clone();
+        }
+        if other.address.is_some() {
+            self.address = other.address;
+        }
+        if other.height.is_some() {
+            self.height = other.height;
+        }
+        if other.rotate.is_some() {
+            self.rotate = other.rotate;
+        }
+        if other.gpio_chip.is_some() {
+            self.gpio_chip = other.gpio_chip.clone();
+        }
+        if other.dc_pin.is_some() {
+            self.dc_pin = other.dc_pin;
+        }
+        if other.reset_pin.is_some() {
+            self.reset_pin = other.reset_pin;
+        }
+    }
+
+    fn print(&self) {
+        if let Some(enabled) = self.enabled {
+            println!("  OLED display:       {}", if enabled { "enabled" } else { "disabled" });
+        }
+        if let Some(interface) = &self.interface {
+            println!("  OLED interface:     {}", interface);
+        }
+        if let Some(device) = &self.device {
+            println!("  OLED device:        {}", device);
+        }
+        if let Some(address) = self.address {
+            println!("  OLED address:       {:#04x}", address);
+        }
+        if let Some(height) = self.height {
+            println!("  OLED height:        {} rows", height);
+        }
+        if let Some(rotate) = self.rotate {
+            println!("  OLED rotated:       {}", if rotate { "yes" } else { "no" });
+        }
+        if let Some(chip) = &self.gpio_chip {
+            println!("  OLED GPIO chip:     {}", chip);
+        }
+        if let Some(pin) = self.dc_pin {
+            println!("  OLED D/C pin:       {}", pin);
+        }
+        if let Some(pin) = self.reset_pin {
+            println!("  OLED reset pin:     {}", pin);
+        }
+    }
+}
+
+/// Button and LEDs of `autorecd`, in the `[gpio]` section (see
+/// `autorec::gpio`)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GpioConfig {
+    /// GPIO chip (default: /dev/gpiochip0)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chip: Option<String>,
+    
+    /// Line of the start/stop/split button
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub button_pin: Option<u32>,
+    
+    /// The button connects the line to ground (default: true)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub button_active_low: Option<bool>,
+    
+    /// Seconds the button is held to split the recording (default: 1.5)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub long_press: Option<f64>,
+    
+    /// Line of the LED that is on while armed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub armed_led: Option<u32>,
+    
+    /// Line of the LED that is on while recording and blinks while paused
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recording_led: Option<u32>,
+    
+    /// Line of the LED that lights up on clipping
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clip_led: Option<u32>,
+}
+
+impl GpioConfig {
+    /// Merge with another section, preferring values from other
+    pub fn merge(&mut self, other: &GpioConfig) {
+        if other.chip.is_some() {
+            self.chip = other.chip.clone();
+        }
+        if other.button_pin.is_some() {
+            self.button_pin = other.button_pin;
+        }
+        if other.button_active_low.is_some() {
+            self.button_active_low = other.button_active_low;
+        }
+        if other.long_press.is_some() {
+            self.long_press = other.long_press;
+        }
+        if other.armed_led.is_some() {
+            self.armed_led = other.armed_led;
+        }
+        if other.recording_led.is_some() {
+            self.recording_led = other.recording_led;
+        }
+        if other.clip_led.is_some() {
+            self.clip_led = other.clip_led;
+        }
+    }
+
+    fn print(&self) {
+        if let Some(chip) = &self.chip {
+            println!("  GPIO chip:          {}", chip);
+        }
+        if let Some(pin) = self.button_pin {
+            let active = if self.button_active_low == Some(false) { "active high" } else { "active low" };
+            println!("  Button:             line {} ({})", pin, active);
+        }
+        if let Some(long_press) = self.long_press {
+            println!("  Button long press:  {}s", long_press);
+        }
+        if let Some(pin) = self.armed_led {
+            println!("  Armed LED:          line {}", pin);
+        }
+        if let Some(pin) = self.recording_led {
+            println!("  Recording LED:      line {}", pin);
+        }
+        if let Some(pin) = self.clip_led {
+            println!("  Clip LED:           line {}", pin);
+        }
+    }
+}
+
 /// Configuration defaults that can be saved to a file
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
@@ -15,6 +983,10 @@ pub struct Config {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub channels: Option<usize>,
     
+    /// Input channels to record, e.g. "3,4"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel_map: Option<String>,
+    
     #[serde(skip_serializing_if = "Option::is_none")]
     pub format: Option<String>,
     
@@ -36,11 +1008,123 @@ pub struct Config {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub min_length: Option<f64>,
     
+    /// Stop a recording when the stylus circles in a locked groove
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_on_locked_groove: Option<bool>,
+    
     #[serde(skip_serializing_if = "Option::is_none")]
     pub no_vumeter: Option<bool>,
     
+    /// Front-end of autorecord: vu, text, json or none
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display: Option<String>,
+    
     #[serde(skip_serializing_if = "Option::is_none")]
     pub no_keyboard: Option<bool>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sparkline: Option<bool>,
+    
+    /// Remove a DC offset of the source
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remove_dc: Option<bool>,
+    
+    /// Apply RIAA de-emphasis (source is a flat, non-phono preamp)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub riaa: Option<bool>,
+    
+    /// High-pass filter against rumble and warp subsonics
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subsonic_filter: Option<bool>,
+    
+    /// Corner frequency of the subsonic filter in Hz
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subsonic_corner: Option<f64>,
+    
+    /// Software gain (trim) before metering and recording in dB
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gain: Option<f64>,
+    
+    /// Soft-knee peak limiter instead of hard clipping
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limiter: Option<bool>,
+    
+    /// Ceiling of the limiter in dBFS
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limiter_ceiling: Option<f64>,
+    
+    /// Channel downmix of the recordings (off, mono, ms)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub downmix: Option<String>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detect_interval: Option<f64>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub no_shazam: Option<bool>,
+    
+    /// Strategy for live song boundary detection (see `--pause-strategy`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pause_strategy: Option<String>,
+    
+    /// Low-bitrate preview format written next to each recording (mp3, opus)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preview: Option<String>,
+    
+    /// Preview bitrate in kbit/s
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preview_bitrate: Option<u32>,
+    
+    /// Unit for displayed levels (dbfs, dbu, dbv)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub level_unit: Option<String>,
+    
+    /// Per-source calibration: dBu level at 0 dBFS, keyed by source address
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub calibration: Option<BTreeMap<String, f64>>,
+    
+    /// Named sources that can be used with `--source <name>`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sources: Option<BTreeMap<String, SourceAlias>>,
+    
+    /// Weekly recording windows, e.g. `mon-fri 07:00-09:00`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<Vec<String>>,
+    
+    /// Named profiles selected with `--profile <name>`, in `[profile.<name>]`
+    /// sections; each overrides the values above while it is used
+    #[serde(rename = "profile", skip_serializing_if = "Option::is_none")]
+    pub profiles: Option<BTreeMap<String, Config>>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cue_creator: Option<CueCreatorConfig>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub splitter: Option<SplitterConfig>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub discogs: Option<DiscogsConfig>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http: Option<HttpConfig>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<OutputConfig>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub organize: Option<OrganizeConfig>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mqtt: Option<MqttConfig>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log: Option<LogConfig>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oled: Option<OledConfig>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gpio: Option<GpioConfig>,
 }
 
 impl Config {
@@ -50,6 +1134,7 @@ impl Config {
             source: None,
             rate: None,
             channels: None,
+            channel_map: None,
             format: None,
             interval: None,
             db_range: None,
@@ -57,8 +1142,39 @@ impl Config {
             off_threshold: None,
             silence_duration: None,
             min_length: None,
+            stop_on_locked_groove: None,
             no_vumeter: None,
+            display: None,
             no_keyboard: None,
+            sparkline: None,
+            remove_dc: None,
+            riaa: None,
+            subsonic_filter: None,
+            subsonic_corner: None,
+            gain: None,
+            limiter: None,
+            limiter_ceiling: None,
+            downmix: None,
+            detect_interval: None,
+            no_shazam: None,
+            pause_strategy: None,
+            preview: None,
+            preview_bitrate: None,
+            level_unit: None,
+            calibration: None,
+            sources: None,
+            schedule: None,
+            profiles: None,
+            cue_creator: None,
+            splitter: None,
+            discogs: None,
+            http: None,
+            output: None,
+            organize: None,
+            mqtt: None,
+            log: None,
+            oled: None,
+            gpio: None,
         }
     }
 
@@ -71,20 +1187,99 @@ impl Config {
         Ok(config_dir.join("defaults.toml"))
     }
 
-    /// Load config from file
+    /// Get the user config file path ($XDG_CONFIG_HOME/autorec/config.toml,
+    /// with ~/.config when XDG_CONFIG_HOME is not set)
+    pub fn get_user_config_path() -> Option<PathBuf> {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+            .map(|dir| dir.join("autorec").join("config.toml"))
+    }
+
+    /// Config files in the order they are read; later files override earlier ones
+    pub fn config_files() -> Vec<PathBuf> {
+        let mut files = vec![PathBuf::from(SYSTEM_CONFIG_PATH)];
+        files.extend(Self::get_user_config_path());
+        files.extend(Self::get_config_path().ok());
+        files
+    }
+
+    /// Load the effective config: the system file, the user file, the saved
+    /// defaults and then the `AUTOREC_*` environment variables
     pub fn load() -> Result<Self, Box<dyn std::error::Error>> {
-        let config_path = Self::get_config_path()?;
-        
-        if !config_path.exists() {
-            // Return empty config if file doesn't exist
-            return Ok(Config::new());
-        }
+        Self::load_layered(&Self::config_files(), std::env::vars())
+    }
 
-        let content = fs::read_to_string(&config_path)?;
-        let config: Config = toml::from_str(&content)?;
+    /// Load only the saved defaults (the file written by [`Config::save`])
+    pub fn load_saved() -> Result<Self, Box<dyn std::error::Error>> {
+        Self::load_layered(&[Self::get_config_path()?], Vec::new())
+    }
+
+    /// Merge the files that exist in order, then the `AUTOREC_*` variables of `env`
+    pub fn load_layered(
+        files: &[PathBuf],
+        env: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut config = Config::new();
+        for path in files {
+            if !path.exists() {
+                continue;
+            }
+            let content = fs::read_to_string(path)?;
+            let layer: Config = toml::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e))?;
+            config.merge(&layer);
+        }
+        config.merge(&Self::from_env(env)?);
         Ok(config)
     }
 
+    /// Config from `AUTOREC_<KEY>` variables; a double underscore separates
+    /// a section from its key, e.g. `AUTOREC_OFF_THRESHOLD=-55` or
+    /// `AUTOREC_SPLITTER__FORMAT=flac`. A value that does not fit its field
+    /// as TOML is taken as text, so `AUTOREC_MQTT__PASSWORD=123456` stays a
+    /// string.
+    pub fn from_env(env: impl IntoIterator<Item = (String, String)>) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut table = toml::Table::new();
+        for (name, raw) in env {
+            let Some(key) = name.strip_prefix(ENV_PREFIX) else { continue };
+            let key = key.to_lowercase();
+            let (section, field) = match key.split_once("__") {
+                Some((section, field)) => (Some(section), field),
+                None => (None, key.as_str()),
+            };
+            let mut error = None;
+            for value in [env_value(&raw), toml::Value::String(raw.clone())] {
+                let mut candidate = table.clone();
+                let target = match section {
+                    Some(section) => match candidate
+                        .entry(section.to_string())
+                        .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+                    {
+                        toml::Value::Table(section) => section,
+                        _ => break,
+                    },
+                    None => &mut candidate,
+                };
+                target.insert(field.to_string(), value);
+                match toml::Value::Table(candidate.clone()).try_into::<Config>() {
+                    Ok(_) => {
+                        table = candidate;
+                        error = None;
+                        break;
+                    }
+                    Err(e) => error = Some(e),
+                }
+            }
+            if let Some(e) = error {
+                return Err(format!("{}: {}", name, e).into());
+            }
+        }
+        toml::Value::Table(table)
+            .try_into()
+            .map_err(|e| format!("{}* environment variables: {}", ENV_PREFIX, e).into())
+    }
+
     /// Save config to file
     pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
         let config_path = Self::get_config_path()?;
@@ -111,6 +1306,9 @@ impl Config {
         if other.channels.is_some() {
             self.channels = other.channels;
         }
+        if other.channel_map.is_some() {
+            self.channel_map = other.channel_map.clone();
+        }
         if other.format.is_some() {
             self.format = other.format.clone();
         }
@@ -132,12 +1330,192 @@ impl Config {
         if other.min_length.is_some() {
             self.min_length = other.min_length;
         }
+        if other.stop_on_locked_groove.is_some() {
+            self.stop_on_locked_groove = other.stop_on_locked_groove;
+        }
         if other.no_vumeter.is_some() {
             self.no_vumeter = other.no_vumeter;
         }
+        if other.display.is_some() {
+            self.display = other.display.clone();
+        }
         if other.no_keyboard.is_some() {
             self.no_keyboard = other.no_keyboard;
         }
+        if other.sparkline.is_some() {
+            self.sparkline = other.sparkline;
+        }
+        if other.remove_dc.is_some() {
+            self.remove_dc = other.remove_dc;
+        }
+        if other.riaa.is_some() {
+            self.riaa = other.riaa;
+        }
+        if other.subsonic_filter.is_some() {
+            self.subsonic_filter = other.subsonic_filter;
+        }
+        if other.subsonic_corner.is_some() {
+            self.subsonic_corner = other.subsonic_corner;
+        }
+        if other.gain.is_some() {
+            self.gain = other.gain;
+        }
+        if other.limiter.is_some() {
+            self.limiter = other.limiter;
+        }
+        if other.limiter_ceiling.is_some() {
+            self.limiter_ceiling = other.limiter_ceiling;
+        }
+        if other.downmix.is_some() {
+            self.downmix = other.downmix.clone();
+        }
+        if other.detect_interval.is_some() {
+            self.detect_interval = other.detect_interval;
+        }
+        if other.no_shazam.is_some() {
+            self.no_shazam = other.no_shazam;
+        }
+        if other.pause_strategy.is_some() {
+            self.pause_strategy = other.pause_strategy.clone();
+        }
+        if other.preview.is_some() {
+            self.preview = other.preview.clone();
+        }
+        if other.preview_bitrate.is_some() {
+            self.preview_bitrate = other.preview_bitrate;
+        }
+        if other.level_unit.is_some() {
+            self.level_unit = other.level_unit.clone();
+        }
+        if let Some(other_cal) = &other.calibration {
+            // Calibrations are merged per source rather than replaced
+            let cal = self.calibration.get_or_insert_with(BTreeMap::new);
+            for (source, offset) in other_cal {
+                cal.insert(source.clone(), *offset);
+            }
+        }
+        if let Some(other_sources) = &other.sources {
+            let sources = self.sources.get_or_insert_with(BTreeMap::new);
+            for (name, alias) in other_sources {
+                sources.insert(name.clone(), alias.clone());
+            }
+        }
+        if other.schedule.is_some() {
+            self.schedule = other.schedule.clone();
+        }
+        if let Some(other_profiles) = &other.profiles {
+            // Profiles are merged key by key, like the config itself
+            let profiles = self.profiles.get_or_insert_with(BTreeMap::new);
+            for (name, profile) in other_profiles {
+                profiles.entry(name.clone()).or_default().merge(profile);
+            }
+        }
+        if let Some(other_cue_creator) = &other.cue_creator {
+            self.cue_creator.get_or_insert_with(Default::default).merge(other_cue_creator);
+        }
+        if let Some(other_splitter) = &other.splitter {
+            self.splitter.get_or_insert_with(Default::default).merge(other_splitter);
+        }
+        if let Some(other_discogs) = &other.discogs {
+            self.discogs.get_or_insert_with(Default::default).merge(other_discogs);
+        }
+        if let Some(other_http) = &other.http {
+            self.http.get_or_insert_with(Default::default).merge(other_http);
+        }
+        if let Some(other_output) = &other.output {
+            self.output.get_or_insert_with(Default::default).merge(other_output);
+        }
+        if let Some(other_organize) = &other.organize {
+            self.organize.get_or_insert_with(Default::default).merge(other_organize);
+        }
+        if let Some(other_mqtt) = &other.mqtt {
+            self.mqtt.get_or_insert_with(Default::default).merge(other_mqtt);
+        }
+        if let Some(other_log) = &other.log {
+            self.log.get_or_insert_with(Default::default).merge(other_log);
+        }
+        if let Some(other_oled) = &other.oled {
+            self.oled.get_or_insert_with(Default::default).merge(other_oled);
+        }
+        if let Some(other_gpio) = &other.gpio {
+            self.gpio.get_or_insert_with(Default::default).merge(other_gpio);
+        }
+    }
+
+    /// Names of the profiles in the config
+    pub fn profile_names(&self) -> Vec<&str> {
+        self.profiles.iter().flat_map(|p| p.keys()).map(String::as_str).collect()
+    }
+
+    /// The config with the values of profile `name` applied on top
+    ///
+    /// The profiles themselves are kept, so that `--save-defaults` can still
+    /// write them back.
+    pub fn with_profile(&self, name: &str) -> Result<Config, String> {
+        let Some(profile) = self.profiles.as_ref().and_then(|p| p.get(name)) else {
+            let names = self.profile_names();
+            return Err(if names.is_empty() {
+                format!("Unknown profile '{}' (no [profile.<name>] sections in the config)", name)
+            } else {
+                format!("Unknown profile '{}' (use {})", name, names.join(", "))
+            });
+        };
+        let mut config = self.clone();
+        let mut profile = profile.clone();
+        profile.profiles = None;
+        config.merge(&profile);
+        Ok(config)
+    }
+
+    /// Look up a named source alias
+    pub fn source_alias(&self, name: &str) -> Option<&SourceAlias> {
+        self.sources.as_ref()?.get(name)
+    }
+
+    /// Resolve `source` if it names an alias
+    ///
+    /// Returns `None` for anything else (an address), and an error if the
+    /// alias has an invalid format or channel map, so that every program
+    /// refuses a broken alias the same way.
+    pub fn resolve_source(&self, source: &str) -> Result<Option<ResolvedSource>, String> {
+        let Some(alias) = self.source_alias(source) else {
+            return Ok(None);
+        };
+        let format = alias.format.as_deref()
+            .map(SampleFormat::from_str)
+            .transpose()
+            .map_err(|e| format!("Source alias '{}': {}", source, e))?;
+        let channel_map = alias.channel_map.as_deref()
+            .map(|list| parse_channel_list(list)
+                .ok_or_else(|| format!("Source alias '{}': Invalid channel map '{}' (use e.g. 3,4)", source, list)))
+            .transpose()?;
+        Ok(Some(ResolvedSource { alias: alias.clone(), format, channel_map }))
+    }
+
+    /// Look up the calibration for a source address
+    ///
+    /// Falls back to the `default` entry if the source has no calibration.
+    pub fn calibration_for(&self, source: &str) -> Option<Calibration> {
+        self.calibration_for_any(&[source])
+    }
+
+    /// Look up the calibration of the first of several names for the same
+    /// source (e.g. its address and alias) that has one
+    ///
+    /// The `default` entry is only used if none of them has a calibration.
+    pub fn calibration_for_any(&self, sources: &[&str]) -> Option<Calibration> {
+        let cal = self.calibration.as_ref()?;
+        sources.iter()
+            .find_map(|source| cal.get(*source))
+            .or_else(|| cal.get(DEFAULT_CALIBRATION_KEY))
+            .map(|&full_scale_dbu| Calibration::new(full_scale_dbu))
+    }
+
+    /// Set the calibration (dBu level at 0 dBFS) for a source address
+    pub fn set_calibration(&mut self, source: &str, full_scale_dbu: f64) {
+        self.calibration
+            .get_or_insert_with(BTreeMap::new)
+            .insert(source.to_string(), full_scale_dbu);
     }
 
     /// Print the config in a human-readable format
@@ -153,6 +1531,9 @@ impl Config {
         if let Some(channels) = self.channels {
             println!("  Channels:           {}", channels);
         }
+        if let Some(channel_map) = &self.channel_map {
+            println!("  Channel map:        {}", channel_map);
+        }
         if let Some(format) = &self.format {
             println!("  Format:             {}", format);
         }
@@ -174,12 +1555,131 @@ impl Config {
         if let Some(min_length) = self.min_length {
             println!("  Min recording:      {} seconds", min_length);
         }
+        if let Some(stop) = self.stop_on_locked_groove {
+            println!("  Locked-groove stop: {}", if stop { "enabled" } else { "disabled" });
+        }
         if let Some(no_vumeter) = self.no_vumeter {
             println!("  VU meter:           {}", if no_vumeter { "disabled" } else { "enabled" });
         }
+        if let Some(display) = &self.display {
+            println!("  Display:            {}", display);
+        }
         if let Some(no_keyboard) = self.no_keyboard {
             println!("  Keyboard shortcuts: {}", if no_keyboard { "disabled" } else { "enabled" });
         }
+        if let Some(sparkline) = self.sparkline {
+            println!("  Level sparkline:    {}", if sparkline { "enabled" } else { "disabled" });
+        }
+        if let Some(remove_dc) = self.remove_dc {
+            println!("  DC blocker:         {}", if remove_dc { "enabled" } else { "disabled" });
+        }
+        if let Some(riaa) = self.riaa {
+            println!("  RIAA correction:    {}", if riaa { "enabled" } else { "disabled" });
+        }
+        if let Some(subsonic_filter) = self.subsonic_filter {
+            println!("  Subsonic filter:    {}", if subsonic_filter { "enabled" } else { "disabled" });
+        }
+        if let Some(subsonic_corner) = self.subsonic_corner {
+            println!("  Subsonic corner:    {} Hz", subsonic_corner);
+        }
+        if let Some(gain) = self.gain {
+            println!("  Input gain:         {:+} dB", gain);
+        }
+        if let Some(limiter) = self.limiter {
+            println!("  Limiter:            {}", if limiter { "enabled" } else { "disabled" });
+        }
+        if let Some(limiter_ceiling) = self.limiter_ceiling {
+            println!("  Limiter ceiling:    {} dBFS", limiter_ceiling);
+        }
+        if let Some(downmix) = &self.downmix {
+            println!("  Downmix:            {}", downmix);
+        }
+        if let Some(detect_interval) = self.detect_interval {
+            println!("  Song detection:     every {} seconds", detect_interval);
+        }
+        if let Some(no_shazam) = self.no_shazam {
+            println!("  Shazam:             {}", if no_shazam { "disabled" } else { "enabled" });
+        }
+        if let Some(pause_strategy) = &self.pause_strategy {
+            println!("  Pause strategy:     {}", pause_strategy);
+        }
+        if let Some(preview) = &self.preview {
+            println!("  Preview:            {}", preview);
+        }
+        if let Some(preview_bitrate) = self.preview_bitrate {
+            println!("  Preview bitrate:    {} kbit/s", preview_bitrate);
+        }
+        if let Some(level_unit) = &self.level_unit {
+            println!("  Level unit:         {}", level_unit);
+        }
+        if let Some(calibration) = &self.calibration {
+            for (source, full_scale_dbu) in calibration {
+                println!("  Calibration:        {} = {:+.1} dBu at 0 dBFS", source, full_scale_dbu);
+            }
+        }
+        if let Some(sources) = &self.sources {
+            for (name, alias) in sources {
+                let mut params = Vec::new();
+                if let Some(rate) = alias.rate {
+                    params.push(format!("{} Hz", rate));
+                }
+                if let Some(channels) = alias.channels {
+                    params.push(format!("{} ch", channels));
+                }
+                if let Some(format) = &alias.format {
+                    params.push(format.clone());
+                }
+                if let Some(off_threshold) = alias.off_threshold {
+                    params.push(format!("off {} dB", off_threshold));
+                }
+                if params.is_empty() {
+                    println!("  Source alias:       {} -> {}", name, alias.address);
+                } else {
+                    println!("  Source alias:       {} -> {} ({})", name, alias.address, params.join(", "));
+                }
+            }
+        }
+        if let Some(schedule) = &self.schedule {
+            for rule in schedule {
+                println!("  Schedule:           {}", rule);
+            }
+        }
+        if let Some(cue_creator) = &self.cue_creator {
+            cue_creator.print();
+        }
+        if let Some(splitter) = &self.splitter {
+            splitter.print();
+        }
+        if let Some(discogs) = &self.discogs {
+            discogs.print();
+        }
+        if let Some(http) = &self.http {
+            http.print();
+        }
+        if let Some(output) = &self.output {
+            output.print();
+        }
+        if let Some(organize) = &self.organize {
+            organize.print();
+        }
+        if let Some(mqtt) = &self.mqtt {
+            mqtt.print();
+        }
+        if let Some(log) = &self.log {
+            log.print();
+        }
+        if let Some(oled) = &self.oled {
+            oled.print();
+        }
+        if let Some(gpio) = &self.gpio {
+            gpio.print();
+        }
+        if let Some(profiles) = &self.profiles {
+            for (name, profile) in profiles {
+                println!();
+                profile.print(&format!("Profile {}", name));
+            }
+        }
     }
 }
 
@@ -188,3 +1688,217 @@ impl Default for Config {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calibration_lookup_and_merge() {
+        let mut config = Config::new();
+        assert!(config.calibration_for("pipewire:riaa.monitor").is_none());
+
+        config.set_calibration(DEFAULT_CALIBRATION_KEY, 18.0);
+        assert_eq!(config.calibration_for("pipewire:riaa.monitor"), Some(Calibration::new(18.0)));
+
+        let mut other = Config::new();
+        other.set_calibration("pipewire:riaa.monitor", 6.0);
+        config.merge(&other);
+
+        assert_eq!(config.calibration_for("pipewire:riaa.monitor"), Some(Calibration::new(6.0)));
+        assert_eq!(config.calibration_for("alsa:hw:1,0"), Some(Calibration::new(18.0)));
+        // An alias with its own calibration wins over the default
+        config.set_calibration("riaa", 12.0);
+        assert_eq!(config.calibration_for_any(&["alsa:hw:1,0", "riaa"]), Some(Calibration::new(12.0)));
+    }
+
+    #[test]
+    fn test_calibration_toml_roundtrip() {
+        let mut config = Config::new();
+        config.rate = Some(48000);
+        config.level_unit = Some("dbu".to_string());
+        config.set_calibration("pipewire:riaa.monitor", 12.5);
+
+        let text = toml::to_string_pretty(&config).unwrap();
+        let parsed: Config = toml::from_str(&text).unwrap();
+        assert_eq!(parsed.rate, Some(48000));
+        assert_eq!(parsed.level_unit.as_deref(), Some("dbu"));
+        assert_eq!(parsed.calibration_for("pipewire:riaa.monitor"), Some(Calibration::new(12.5)));
+    }
+
+    #[test]
+    fn test_source_aliases() {
+        let text = r#"
+rate = 48000
+
+[sources.vinyl]
+address = "pipewire:riaa.monitor"
+rate = 96000
+format = "s32"
+
+[sources.tape]
+address = "alsa:hw:1,0"
+"#;
+        let mut config: Config = toml::from_str(text).unwrap();
+        let vinyl = config.source_alias("vinyl").unwrap();
+        assert_eq!(vinyl.address, "pipewire:riaa.monitor");
+        assert_eq!(vinyl.rate, Some(96000));
+        assert_eq!(vinyl.channels, None);
+        assert!(config.source_alias("cd").is_none());
+
+        // Merging adds or replaces individual aliases
+        let mut other = Config::new();
+        other.sources = Some(BTreeMap::from([(
+            "tape".to_string(),
+            SourceAlias {
+                address: "alsa:hw:2,0".to_string(),
+                rate: None,
+                channels: Some(2),
+                channel_map: None,
+                format: None,
+                off_threshold: Some(-50.0),
+            },
+        )]));
+        config.merge(&other);
+        assert_eq!(config.source_alias("tape").unwrap().address, "alsa:hw:2,0");
+        assert!(config.source_alias("vinyl").is_some());
+
+        // Resolving parses the format and channel map and rejects broken aliases
+        let vinyl = config.resolve_source("vinyl").unwrap().unwrap();
+        assert_eq!(vinyl.format, Some(SampleFormat::S32));
+        assert_eq!(vinyl.channel_map, None);
+        assert_eq!(config.resolve_source("pipewire:other").unwrap(), None);
+        let sources = config.sources.as_mut().unwrap();
+        sources.get_mut("vinyl").unwrap().format = Some("s20".to_string());
+        sources.get_mut("tape").unwrap().channel_map = Some("3,x".to_string());
+        assert_eq!(config.resolve_source("vinyl").unwrap_err(), "Source alias 'vinyl': Unsupported format: s20");
+        assert!(config.resolve_source("tape").unwrap_err().starts_with("Source alias 'tape': Invalid channel map '3,x'"));
+    }
+
+    #[test]
+    fn test_tool_sections() {
+        let text = r#"
+rate = 96000
+
+[cue_creator]
+min_prominence = 2.5
+lookup_backends = ["musicbrainz-vinyl", "discogs"]
+rename_template = "{artist} - {album}"
+
+[splitter]
+format = "flac"
+normalize = "album"
+"#;
+        let mut config: Config = toml::from_str(text).unwrap();
+        let cue_creator = config.cue_creator.clone().unwrap();
+        assert_eq!(cue_creator.min_prominence, Some(2.5));
+        assert_eq!(cue_creator.lookup_backends.as_deref(), Some(&["musicbrainz-vinyl".to_string(), "discogs".to_string()][..]));
+        assert_eq!(cue_creator.chunk_ms, None);
+
+        // Sections merge field by field
+        let mut other = Config::new();
+        other.cue_creator = Some(CueCreatorConfig { chunk_ms: Some(100), ..Default::default() });
+        other.splitter = Some(SplitterConfig { format: Some("mp3".to_string()), ..Default::default() });
+        config.merge(&other);
+        let cue_creator = config.cue_creator.as_ref().unwrap();
+        assert_eq!((cue_creator.min_prominence, cue_creator.chunk_ms), (Some(2.5), Some(100)));
+        let splitter = config.splitter.as_ref().unwrap();
+        assert_eq!(splitter.format.as_deref(), Some("mp3"));
+        assert_eq!(splitter.normalize.as_deref(), Some("album"));
+
+        let saved = toml::to_string_pretty(&config).unwrap();
+        let parsed: Config = toml::from_str(&saved).unwrap();
+        assert_eq!(parsed.splitter, config.splitter);
+    }
+
+    #[test]
+    fn test_profiles() {
+        let text = r#"
+rate = 96000
+off_threshold = -60.0
+
+[profile.tape]
+source = "alsa:hw:1,0"
+rate = 48000
+off_threshold = -50.0
+pause_strategy = "energy"
+
+[profile.radio]
+source = "net:udp://0.0.0.0:4010"
+"#;
+        let mut config: Config = toml::from_str(text).unwrap();
+        assert_eq!(config.profile_names(), vec!["radio", "tape"]);
+
+        let tape = config.with_profile("tape").unwrap();
+        assert_eq!(tape.source.as_deref(), Some("alsa:hw:1,0"));
+        assert_eq!((tape.rate, tape.off_threshold), (Some(48000), Some(-50.0)));
+        assert_eq!(tape.pause_strategy.as_deref(), Some("energy"));
+        let radio = config.with_profile("radio").unwrap();
+        assert_eq!((radio.rate, radio.off_threshold), (Some(96000), Some(-60.0)));
+        assert!(config.with_profile("cd").unwrap_err().contains("radio, tape"));
+
+        // Profiles merge key by key
+        let mut tape_update = Config::new();
+        tape_update.silence_duration = Some(5.0);
+        let other = Config { profiles: Some(BTreeMap::from([("tape".to_string(), tape_update)])), ..Config::new() };
+        config.merge(&other);
+        let tape = config.with_profile("tape").unwrap();
+        assert_eq!((tape.rate, tape.silence_duration), (Some(48000), Some(5.0)));
+
+        let saved = toml::to_string_pretty(&config).unwrap();
+        assert!(saved.contains("[profile.tape]"));
+        let parsed: Config = toml::from_str(&saved).unwrap();
+        assert_eq!(parsed.with_profile("tape").unwrap().silence_duration, Some(5.0));
+
+        let args = ["autorecord", "side_a", "--profile", "tape"].map(String::from);
+        assert_eq!(profile_arg(&args), Some("tape"));
+        assert_eq!(profile_arg(&args[..3]), None);
+    }
+
+    #[test]
+    fn test_layered_loading() {
+        let dir = tempfile::tempdir().unwrap();
+        let system = dir.path().join("system.toml");
+        let user = dir.path().join("user.toml");
+        fs::write(&system, "rate = 48000\nchannels = 2\n[calibration]\ndefault = 18.0\n").unwrap();
+        fs::write(&user, "rate = 96000\n[calibration]\n\"alsa:hw:1,0\" = 6.0\n").unwrap();
+        let missing = dir.path().join("missing.toml");
+
+        let env = [
+            ("AUTOREC_OFF_THRESHOLD", "-55"),
+            ("AUTOREC_SOURCE", "pipewire:riaa.monitor"),
+            ("AUTOREC_RIAA", "true"),
+            ("AUTOREC_SPLITTER__FORMAT", "flac"),
+            ("AUTOREC_CUE_CREATOR__LOOKUP_BACKENDS", "[\"discogs\"]"),
+            ("HOME", "/root"),
+        ]
+        .map(|(k, v)| (k.to_string(), v.to_string()));
+        let config = Config::load_layered(&[system.clone(), user, missing], env).unwrap();
+        assert_eq!((config.rate, config.channels), (Some(96000), Some(2)));
+        assert_eq!(config.calibration_for("alsa:hw:1,0"), Some(Calibration::new(6.0)));
+        assert_eq!(config.calibration_for("other"), Some(Calibration::new(18.0)));
+        assert_eq!(config.off_threshold, Some(-55.0));
+        assert_eq!(config.source.as_deref(), Some("pipewire:riaa.monitor"));
+        assert_eq!(config.riaa, Some(true));
+        assert_eq!(config.splitter.unwrap().format.as_deref(), Some("flac"));
+        assert_eq!(config.cue_creator.unwrap().lookup_backends, Some(vec!["discogs".to_string()]));
+
+        let bad = [("AUTOREC_RATE".to_string(), "fast".to_string())];
+        let error = Config::load_layered(std::slice::from_ref(&system), bad).unwrap_err();
+        assert!(error.to_string().starts_with("AUTOREC_RATE: "), "{}", error);
+
+        // Numbers for text fields stay text
+        let env = [
+            ("AUTOREC_MQTT__PASSWORD", "123456"),
+            ("AUTOREC_MQTT__USERNAME", "42"),
+            ("AUTOREC_CHANNEL_MAP", "3"),
+            ("AUTOREC_RATE", "44100"),
+        ]
+        .map(|(k, v)| (k.to_string(), v.to_string()));
+        let config = Config::load_layered(&[system], env).unwrap();
+        let mqtt = config.mqtt.unwrap();
+        assert_eq!((mqtt.password.as_deref(), mqtt.username.as_deref()), (Some("123456"), Some("42")));
+        assert_eq!(config.channel_map.as_deref(), Some("3"));
+        assert_eq!((config.rate, config.channels), (Some(44100), Some(2)));
+    }
+}
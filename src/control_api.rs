@@ -0,0 +1,631 @@
+//! HTTP/JSON control API of the `autorecd` daemon.
+//!
+//! The HTTP server runs on its own thread. It only reads the shared
+//! [`DaemonState`] and passes commands to the capture loop over a channel, so
+//! a slow client never blocks audio processing.
+//!
+//! Endpoints:
+//! - `GET /status`      - armed/recording/paused state, current file, uptime
+//! - `GET /levels`      - latest per-channel levels
+//! - `GET /recordings`  - finished recordings and their CUE status
+//! - `GET /metrics`     - capture state, input levels, write queue, bytes
+//!   written, dropped frames and API requests in the Prometheus text format
+//! - `POST /start`      - arm: record automatically when a signal is present
+//! - `POST /stop`       - disarm and close the current recording
+//! - `POST /split`      - close the current recording and continue in the
+//!   next numbered file
+//! - `POST /pause`      - stop writing audio but keep the current file open
+//! - `POST /resume`     - continue the paused recording once the signal is back
+//! - `POST /cue`        - generate a CUE file, body `{"file": "..."}`,
+//!   default: the latest recording
+//! - `POST /shutdown`   - close the current recording and exit
+//! - `GET /sessions`    - recordings of the catalog, newest first, and
+//!   whether they have a CUE sheet, report and spectrogram
+//! - `GET /boundaries?file=...` - tracks of a recording's CUE sheet
+//! - `POST /boundaries` - body `{"file": "...", "boundaries": [seconds]}`:
+//!   save the reviewed boundaries, which are used exactly as given (none
+//!   means one track), and generate the CUE file again
+//!
+//! - `GET /events`     - WebSocket stream of levels, state changes, song
+//!   boundaries and identified albums (see [`crate::events`])
+//!
+//! The web UI (see [`crate::web_ui`]) is served below `/ui/`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+use serde::Serialize;
+use serde_json::{json, Value};
+use tiny_http::{Header, Response, Server};
+
+use crate::catalog::Catalog;
+use crate::cuefile;
+use crate::events::{self, DaemonEvent, EventHub};
+use crate::recorder::{RecordingStats, WriterStats};
+use crate::vu_meter::ChannelMetrics;
+use crate::web_ui;
+
+/// Default address of the control API (local connections only)
+pub const DEFAULT_LISTEN_ADDRESS: &str = "127.0.0.1:8780";
+
+/// Paths of the API; requests to other paths are counted as "other"
+const ENDPOINTS: &[&str] = &[
+    "/status", "/levels", "/recordings", "/metrics", "/start", "/stop", "/split", "/pause", "/resume", "/cue", "/shutdown",
+    "/sessions", "/boundaries", events::EVENTS_PATH,
+];
+
+/// Methods counted by name in the request metrics; others are counted as "other"
+const METHODS: &[&str] = &["GET", "HEAD", "POST", "PUT", "DELETE", "PATCH", "OPTIONS"];
+
+/// Level reported in the metrics for silence, which is -inf dBFS
+const METRICS_MIN_DBFS: f64 = -120.0;
+
+/// Content type of the Prometheus text exposition format
+const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// Commands from the HTTP API to the capture loop
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApiCommand {
+    Arm,
+    Disarm,
+    Split,
+    Pause,
+    Resume,
+    GenerateCue(String),
+    Shutdown,
+}
+
+/// State of CUE generation for one recording
+#[derive(Debug, Clone, PartialEq)]
+pub enum CueStatus {
+    Running,
+    Done,
+    Failed(String),
+}
+
+impl CueStatus {
+    fn to_json(&self) -> Value {
+        match self {
+            CueStatus::Running => json!("running"),
+            CueStatus::Done => json!("done"),
+            CueStatus::Failed(e) => json!({ "failed": e }),
+        }
+    }
+}
+
+/// Album identified for a recording by cue_creator
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct IdentifiedAlbum {
+    pub file: String,
+    pub artist: String,
+    pub album: String,
+    pub year: Option<u32>,
+    /// Release reference (URL)
+    pub release: Option<String>,
+}
+
+/// Everything the API reports, updated by the capture loop
+pub struct DaemonState {
+    pub source: String,
+    pub armed: bool,
+    pub recording: bool,
+    /// Recording paused through the API or keyboard
+    pub paused: bool,
+    pub current_file: Option<String>,
+    pub levels: Vec<ChannelMetrics>,
+    pub recordings: Vec<RecordingStats>,
+    pub cue_status: HashMap<String, CueStatus>,
+    /// Last error of the recorder (e.g. disk full)
+    pub last_error: Option<String>,
+    /// False while waiting for a lost audio source to come back
+    pub source_connected: bool,
+    /// Album of the latest recording with a CUE file
+    pub album: Option<IdentifiedAlbum>,
+    /// Write queue and byte counters of the recorder
+    pub writer: WriterStats,
+    /// Clients of the event stream
+    pub events: EventHub,
+    /// Answered requests by method, path and status code
+    api_requests: BTreeMap<(String, String, u16), u64>,
+    started: Instant,
+}
+
+impl DaemonState {
+    pub fn new(source: &str, armed: bool) -> Self {
+        DaemonState {
+            source: source.to_string(),
+            armed,
+            recording: false,
+            paused: false,
+            current_file: None,
+            levels: Vec::new(),
+            recordings: Vec::new(),
+            cue_status: HashMap::new(),
+            last_error: None,
+            source_connected: true,
+            album: None,
+            writer: WriterStats::default(),
+            events: EventHub::default(),
+            api_requests: BTreeMap::new(),
+            started: Instant::now(),
+        }
+    }
+
+    /// idle, armed, recording, paused or disconnected
+    pub fn state_name(&self) -> &'static str {
+        if !self.source_connected {
+            "disconnected"
+        } else if self.paused {
+            "paused"
+        } else if self.recording {
+            "recording"
+        } else if self.armed {
+            "armed"
+        } else {
+            "idle"
+        }
+    }
+
+    /// Send the current state to the event stream
+    pub fn publish_state(&mut self) {
+        let event = DaemonEvent::State { state: self.state_name() };
+        self.events.publish(&event);
+    }
+
+    pub(crate) fn status_json(&self) -> Value {
+        json!({
+            "state": self.state_name(),
+            "armed": self.armed,
+            "recording": self.recording,
+            "paused": self.paused,
+            "current_file": self.current_file,
+            "source": self.source,
+            "source_connected": self.source_connected,
+            "uptime": self.started.elapsed().as_secs_f64(),
+            "recordings": self.recordings.len(),
+            "album": self.album,
+            "error": self.last_error,
+        })
+    }
+
+    fn recordings_json(&self) -> Value {
+        let list: Vec<Value> = self.recordings.iter().map(|r| {
+            json!({
+                "file": r.filename,
+                "duration": r.duration,
+                "max_true_peak_db": r.max_true_peak_db,
+                "preview_file": r.preview_file,
+                "flac_file": r.flac_file,
+                "overs": r.clipping.total(),
+                "dropouts": r.dropouts.len(),
+                "cue": self.cue_status.get(&r.filename).map(|s| s.to_json()),
+            })
+        }).collect();
+        json!(list)
+    }
+
+    fn metrics_text(&self) -> String {
+        let mut text = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, samples: Vec<(String, String)>| {
+            text.push_str(&format!("# HELP autorec_{} {}\n# TYPE autorec_{} {}\n", name, help, name, kind));
+            for (labels, value) in samples {
+                text.push_str(&format!("autorec_{}{} {}\n", name, labels, value));
+            }
+        };
+        let flag = |on: bool| vec![(String::new(), (on as u8).to_string())];
+        let per_channel = |value: &dyn Fn(&ChannelMetrics) -> String| -> Vec<(String, String)> {
+            self.levels.iter().enumerate().map(|(i, m)| (format!("{{channel=\"{}\"}}", i + 1), value(m))).collect()
+        };
+
+        metric("armed", "gauge", "Whether recording starts when a signal is present", flag(self.armed));
+        metric("recording", "gauge", "Whether a recording is being written", flag(self.recording));
+        metric("paused", "gauge", "Whether the recording is paused", flag(self.paused));
+        metric("source_connected", "gauge", "Whether the audio source is connected", flag(self.source_connected));
+        metric("uptime_seconds", "gauge", "Seconds since the daemon started",
+            vec![(String::new(), format!("{:.3}", self.started.elapsed().as_secs_f64()))]);
+        metric("input_level_dbfs", "gauge", "Input level per channel in dBFS", per_channel(&|m| format!("{:.2}", m.db.max(METRICS_MIN_DBFS))));
+        metric("input_peak_dbfs", "gauge", "Input peak per channel in dBFS", per_channel(&|m| format!("{:.2}", m.peak_db.max(METRICS_MIN_DBFS))));
+        metric("input_overs_total", "counter", "Overs per channel since the start", per_channel(&|m| m.total_overs.to_string()));
+        metric("write_queue_chunks", "gauge", "Audio chunks waiting to be written to disk",
+            vec![(String::new(), self.writer.queued_chunks.to_string())]);
+        metric("written_bytes_total", "counter", "Bytes of audio written to WAV files",
+            vec![(String::new(), self.writer.bytes_written.to_string())]);
+        metric("dropped_frames_total", "counter", "Frames that could not be written",
+            vec![(String::new(), self.writer.dropped_frames.to_string())]);
+        metric("recordings_total", "counter", "Finished recordings",
+            vec![(String::new(), self.recordings.len().to_string())]);
+        metric("dropouts_total", "counter", "Dropouts found in finished recordings",
+            vec![(String::new(), self.recordings.iter().map(|r| r.dropouts.len()).sum::<usize>().to_string())]);
+        metric("api_requests_total", "counter", "Control API requests by method, path and status",
+            self.api_requests.iter().map(|((method, path, status), count)| {
+                (format!("{{method=\"{}\",path=\"{}\",status=\"{}\"}}", method, path, status), count.to_string())
+            }).collect());
+        text
+    }
+}
+
+pub type SharedState = Arc<Mutex<DaemonState>>;
+
+/// Response produced for one API request
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiResponse {
+    pub status: u16,
+    pub body: Value,
+    /// Plain text sent instead of the JSON body (the metrics)
+    pub text: Option<String>,
+}
+
+impl ApiResponse {
+    fn ok(body: Value) -> Self {
+        ApiResponse { status: 200, body, text: None }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        ApiResponse { status, body: json!({ "error": message }), text: None }
+    }
+}
+
+/// Route one request. `body` is the raw request body (may be empty).
+pub fn handle_request(
+    method: &str,
+    url: &str,
+    body: &str,
+    state: &SharedState,
+    commands: &Sender<ApiCommand>,
+) -> ApiResponse {
+    let path = url.split('?').next().unwrap_or("").trim_end_matches('/');
+    let response = route(method, path, url, body, state, commands);
+
+    let endpoint = match path {
+        "" => "/",
+        path if ENDPOINTS.contains(&path) => path,
+        _ => "other",
+    };
+    let method = METHODS.iter().find(|m| **m == method).copied().unwrap_or("other");
+    let key = (method.to_string(), endpoint.to_string(), response.status);
+    *state.lock().unwrap().api_requests.entry(key).or_insert(0) += 1;
+    response
+}
+
+fn route(method: &str, path: &str, url: &str, body: &str, state: &SharedState, commands: &Sender<ApiCommand>) -> ApiResponse {
+    let send = |command: ApiCommand| {
+        commands.send(command).map_err(|_| ApiResponse::error(503, "Recorder is not running"))
+    };
+
+    match (method, path) {
+        ("GET", "/status") | ("GET", "") => ApiResponse::ok(state.lock().unwrap().status_json()),
+        ("GET", "/levels") => ApiResponse::ok(json!(state.lock().unwrap().levels)),
+        ("GET", "/recordings") => ApiResponse::ok(state.lock().unwrap().recordings_json()),
+        ("GET", "/metrics") => ApiResponse { text: Some(state.lock().unwrap().metrics_text()), ..ApiResponse::ok(Value::Null) },
+        ("POST", "/start") => match send(ApiCommand::Arm) {
+            Ok(()) => ApiResponse::ok(json!({ "armed": true })),
+            Err(e) => e,
+        },
+        ("POST", "/stop") => match send(ApiCommand::Disarm) {
+            Ok(()) => ApiResponse::ok(json!({ "armed": false })),
+            Err(e) => e,
+        },
+        ("POST", "/split") => {
+            let current_file = {
+                let state = state.lock().unwrap();
+                if !state.recording {
+                    return ApiResponse::error(409, "Not recording");
+                }
+                state.current_file.clone()
+            };
+            match send(ApiCommand::Split) {
+                Ok(()) => ApiResponse::ok(json!({ "split": true, "file": current_file })),
+                Err(e) => e,
+            }
+        }
+        ("POST", "/pause") => {
+            // Pausing while idle would hold back the next recording
+            if !state.lock().unwrap().recording {
+                return ApiResponse::error(409, "Not recording");
+            }
+            match send(ApiCommand::Pause) {
+                Ok(()) => ApiResponse::ok(json!({ "paused": true })),
+                Err(e) => e,
+            }
+        }
+        ("POST", "/resume") => match send(ApiCommand::Resume) {
+            Ok(()) => ApiResponse::ok(json!({ "paused": false })),
+            Err(e) => e,
+        },
+        ("POST", "/shutdown") => match send(ApiCommand::Shutdown) {
+            Ok(()) => ApiResponse::ok(json!({ "shutdown": true })),
+            Err(e) => e,
+        },
+        ("POST", "/cue") => {
+            let requested = if body.trim().is_empty() {
+                None
+            } else {
+                match serde_json::from_str::<Value>(body) {
+                    Ok(v) => v.get("file").and_then(|f| f.as_str()).map(String::from),
+                    Err(e) => return ApiResponse::error(400, &format!("Invalid JSON: {}", e)),
+                }
+            };
+
+            // Only files recorded by this daemon may be processed
+            let file = {
+                let state = state.lock().unwrap();
+                match requested {
+                    Some(f) => state.recordings.iter().find(|r| r.filename == f).map(|r| r.filename.clone()),
+                    None => state.recordings.last().map(|r| r.filename.clone()),
+                }
+            };
+            match file {
+                Some(file) => match send(ApiCommand::GenerateCue(file.clone())) {
+                    Ok(()) => ApiResponse { status: 202, ..ApiResponse::ok(json!({ "file": file, "cue": "running" })) },
+                    Err(e) => e,
+                },
+                None => ApiResponse::error(404, "No such recording"),
+            }
+        }
+        ("GET", "/sessions") => match Catalog::open_default().and_then(|catalog| web_ui::sessions_json(&catalog)) {
+            Ok(list) => ApiResponse::ok(list),
+            Err(e) => ApiResponse::error(503, &e.to_string()),
+        },
+        ("GET", "/boundaries") => {
+            let Some(file) = web_ui::query_param(url, "file") else {
+                return ApiResponse::error(400, "Missing file parameter");
+            };
+            match Catalog::open_default().and_then(|catalog| catalog.get(&file)) {
+                Ok(Some(entry)) => match web_ui::boundaries_json(&entry) {
+                    Some(boundaries) => ApiResponse::ok(boundaries),
+                    None => ApiResponse::error(404, "No CUE file"),
+                },
+                Ok(None) => ApiResponse::error(404, "No such recording"),
+                Err(e) => ApiResponse::error(503, &e.to_string()),
+            }
+        }
+        ("POST", "/boundaries") => {
+            let request: Value = match serde_json::from_str(body) {
+                Ok(v) => v,
+                Err(e) => return ApiResponse::error(400, &format!("Invalid JSON: {}", e)),
+            };
+            let file = request.get("file").and_then(|f| f.as_str());
+            let boundaries: Option<Vec<f64>> = request
+                .get("boundaries")
+                .and_then(|b| b.as_array())
+                .and_then(|b| b.iter().map(|v| v.as_f64().filter(|s| s.is_finite() && *s >= 0.0)).collect());
+            let (Some(file), Some(mut boundaries)) = (file, boundaries) else {
+                return ApiResponse::error(400, "Expected file and a list of boundaries in seconds");
+            };
+            boundaries.sort_by(f64::total_cmp);
+            let entry = match Catalog::open_default().and_then(|catalog| catalog.get(file)) {
+                Ok(Some(entry)) => entry,
+                Ok(None) => return ApiResponse::error(404, "No such recording"),
+                Err(e) => return ApiResponse::error(503, &e.to_string()),
+            };
+            if let Err(e) = cuefile::write_reviewed(&entry.path, &boundaries) {
+                return ApiResponse::error(500, &format!("Failed to save boundaries: {}", e));
+            }
+            match send(ApiCommand::GenerateCue(entry.path.clone())) {
+                Ok(()) => ApiResponse {
+                    status: 202,
+                    ..ApiResponse::ok(json!({ "file": entry.path, "boundaries": boundaries.len(), "cue": "running" }))
+                },
+                Err(e) => e,
+            }
+        }
+        ("GET", events::EVENTS_PATH) => ApiResponse::error(426, "WebSocket upgrade required"),
+        (_, path) if ENDPOINTS.contains(&path) => ApiResponse::error(405, "Method not allowed"),
+        _ => ApiResponse::error(404, "Not found"),
+    }
+}
+
+/// Start the HTTP server on `listen` (e.g. "127.0.0.1:8780") in a background thread
+///
+/// Connections to the event stream are served here, so their sockets can
+/// have timeouts; all others are passed on to the HTTP server on a
+/// loopback port.
+pub fn serve(
+    listen: &str,
+    state: SharedState,
+    commands: Sender<ApiCommand>,
+) -> Result<thread::JoinHandle<()>, String> {
+    let listener = TcpListener::bind(listen)
+        .map_err(|e| format!("Failed to listen on {}: {}", listen, e))?;
+    let server = Server::http("127.0.0.1:0")
+        .map_err(|e| format!("Failed to start the HTTP server: {}", e))?;
+    let Some(backend) = server.server_addr().to_ip() else {
+        return Err("HTTP server has no IP address".to_string());
+    };
+
+    let event_state = Arc::clone(&state);
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let state = Arc::clone(&event_state);
+            thread::spawn(move || {
+                if events::is_event_stream(&stream) {
+                    let key = ("GET".to_string(), events::EVENTS_PATH.to_string(), 101);
+                    *state.lock().unwrap().api_requests.entry(key).or_insert(0) += 1;
+                    events::accept(stream, state);
+                } else {
+                    forward(stream, backend);
+                }
+            });
+        }
+    });
+
+    let handle = thread::spawn(move || {
+        let json_type = Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap();
+        let metrics_type = Header::from_bytes(&b"Content-Type"[..], METRICS_CONTENT_TYPE.as_bytes()).unwrap();
+        for mut request in server.incoming_requests() {
+            let mut body = String::new();
+            if request.as_reader().read_to_string(&mut body).is_err() {
+                body.clear();
+            }
+            let method = request.method().as_str().to_uppercase();
+            let range = request.headers().iter().find(|h| h.field.equiv("Range")).map(|h| h.value.to_string());
+            if let Some(page) = web_ui::respond(&method, request.url(), range.as_deref()) {
+                let _ = request.respond(page);
+                continue;
+            }
+            let response = handle_request(&method, request.url(), &body, &state, &commands);
+            let http_response = match response.text {
+                Some(text) => Response::from_string(text).with_header(metrics_type.clone()),
+                None => Response::from_string(response.body.to_string()).with_header(json_type.clone()),
+            };
+            let http_response = http_response.with_status_code(response.status);
+            let _ = request.respond(http_response);
+        }
+    });
+
+    Ok(handle)
+}
+
+/// Pass the connection `client` on to the HTTP server at `backend` until
+/// either side closes it
+fn forward(client: TcpStream, backend: SocketAddr) {
+    let _ = client.set_read_timeout(None);
+    let Ok(server) = TcpStream::connect(backend) else {
+        return;
+    };
+    let (Ok(mut from_client), Ok(mut to_server)) = (client.try_clone(), server.try_clone()) else {
+        return;
+    };
+    let requests = thread::spawn(move || {
+        let _ = io::copy(&mut from_client, &mut to_server);
+        let _ = to_server.shutdown(Shutdown::Write);
+    });
+    let (mut from_server, mut to_client) = (server, client);
+    let _ = io::copy(&mut from_server, &mut to_client);
+    let _ = to_client.shutdown(Shutdown::Both);
+    let _ = requests.join();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+
+    fn state_with_recording() -> SharedState {
+        let mut state = DaemonState::new("pipewire:test", true);
+        state.recordings.push(RecordingStats {
+            filename: "side.1.wav".to_string(),
+            duration: 1200.0,
+            started: std::time::SystemTime::now(),
+            finished: std::time::SystemTime::now(),
+            average_level_db: Some(-20.0),
+            max_true_peak_db: Some(-1.5),
+            preview_file: None,
+            flac_file: None,
+            clipping: Default::default(),
+            dropouts: Vec::new(),
+        });
+        Arc::new(Mutex::new(state))
+    }
+
+    #[test]
+    fn test_status_and_recordings() {
+        let state = state_with_recording();
+        let (tx, _rx) = channel();
+
+        let status = handle_request("GET", "/status", "", &state, &tx);
+        assert_eq!(status.status, 200);
+        assert_eq!(status.body["state"], "armed");
+        assert_eq!(status.body["recordings"], 1);
+
+        state.lock().unwrap().cue_status.insert("side.1.wav".to_string(), CueStatus::Done);
+        let recordings = handle_request("GET", "/recordings/", "", &state, &tx);
+        assert_eq!(recordings.body[0]["file"], "side.1.wav");
+        assert_eq!(recordings.body[0]["cue"], "done");
+    }
+
+    #[test]
+    fn test_commands() {
+        let state = state_with_recording();
+        let (tx, rx) = channel();
+
+        assert_eq!(handle_request("POST", "/stop", "", &state, &tx).status, 200);
+        assert_eq!(rx.try_recv().unwrap(), ApiCommand::Disarm);
+
+        // Splitting and pausing need a running recording
+        assert_eq!(handle_request("POST", "/split", "", &state, &tx).status, 409);
+        assert_eq!(handle_request("POST", "/pause", "", &state, &tx).status, 409);
+        state.lock().unwrap().recording = true;
+        assert_eq!(handle_request("POST", "/split", "", &state, &tx).status, 200);
+        assert_eq!(rx.try_recv().unwrap(), ApiCommand::Split);
+
+        assert_eq!(handle_request("POST", "/pause", "", &state, &tx).status, 200);
+        assert_eq!(rx.try_recv().unwrap(), ApiCommand::Pause);
+        state.lock().unwrap().paused = true;
+        let status = handle_request("GET", "/status", "", &state, &tx);
+        assert_eq!(status.body["state"], "paused");
+
+        let cue = handle_request("POST", "/cue", "", &state, &tx);
+        assert_eq!(cue.status, 202);
+        assert_eq!(rx.try_recv().unwrap(), ApiCommand::GenerateCue("side.1.wav".to_string()));
+
+        // Arbitrary paths are rejected
+        let other = handle_request("POST", "/cue", r#"{"file": "/etc/passwd"}"#, &state, &tx);
+        assert_eq!(other.status, 404);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_errors() {
+        let state = state_with_recording();
+        let (tx, rx) = channel();
+        assert_eq!(handle_request("GET", "/nothing", "", &state, &tx).status, 404);
+        assert_eq!(handle_request("GET", "/start", "", &state, &tx).status, 405);
+        assert_eq!(handle_request("GET", "/events", "", &state, &tx).status, 426);
+        assert_eq!(handle_request("POST", "/cue", "{", &state, &tx).status, 400);
+        assert_eq!(handle_request("GET", "/boundaries", "", &state, &tx).status, 400);
+        assert_eq!(handle_request("POST", "/boundaries", r#"{"file": "side.1.wav", "boundaries": ["x"]}"#, &state, &tx).status, 400);
+
+        drop(rx);
+        assert_eq!(handle_request("POST", "/start", "", &state, &tx).status, 503);
+    }
+
+    #[test]
+    fn test_serve() {
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let (tx, _rx) = channel();
+        serve(&format!("127.0.0.1:{}", port), state_with_recording(), tx).unwrap();
+
+        // Requests other than the event stream are passed on to the HTTP server
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        io::Write::write_all(&mut stream, b"GET /status HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").unwrap();
+        let mut response = String::new();
+        io::Read::read_to_string(&mut stream, &mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        assert!(response.contains(r#""source":"pipewire:test""#), "{}", response);
+    }
+
+    #[test]
+    fn test_metrics() {
+        let state = state_with_recording();
+        let (tx, _rx) = channel();
+        state.lock().unwrap().writer.bytes_written = 4096;
+        handle_request("GET", "/status", "", &state, &tx);
+        handle_request("GET", "/nothing", "", &state, &tx);
+        handle_request("BREW\"{", "/status", "", &state, &tx);
+        state.lock().unwrap().levels = vec![ChannelMetrics {
+            db: f64::NEG_INFINITY, peak_db: -3.0, max_db: -3.0, max_peak_db: -3.0, is_on: false, has_clipped: false,
+            true_peak_db: -3.0, max_true_peak_db: -3.0, overs: 0, total_overs: 0,
+        }];
+
+        let metrics = handle_request("GET", "/metrics", "", &state, &tx);
+        assert_eq!(metrics.status, 200);
+        let text = metrics.text.unwrap();
+        assert!(text.contains("# TYPE autorec_armed gauge\nautorec_armed 1\n"));
+        assert!(text.contains("autorec_recording 0\n"));
+        assert!(text.contains("autorec_written_bytes_total 4096\n"));
+        assert!(text.contains("autorec_recordings_total 1\n"));
+        assert!(text.contains("autorec_api_requests_total{method=\"GET\",path=\"/status\",status=\"200\"} 1\n"));
+        assert!(text.contains("autorec_api_requests_total{method=\"GET\",path=\"other\",status=\"404\"} 1\n"));
+        assert!(text.contains("autorec_api_requests_total{method=\"other\",path=\"/status\",status=\"405\"} 1\n"));
+        assert!(text.contains("autorec_input_level_dbfs{channel=\"1\"} -120.00\n"));
+        assert!(text.contains("autorec_input_peak_dbfs{channel=\"1\"} -3.00\n"));
+        assert!(!text.contains("inf"));
+        assert_eq!(handle_request("POST", "/metrics", "", &state, &tx).status, 405);
+    }
+}
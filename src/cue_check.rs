@@ -0,0 +1,210 @@
+//! Consistency checks of a CUE sheet against its WAV file.
+//!
+//! A CUE sheet edited by hand, or written for an earlier take of a side,
+//! can point past the end of the file or have its tracks out of order.
+//! [`check_structure`] finds such errors. [`compare_durations`] compares
+//! the track lengths of the sheet with the lengths listed for the release
+//! (usually from MusicBrainz) and reports tracks that deviate by more than
+//! a threshold, which usually means a boundary was placed in the wrong
+//! pause.
+
+use std::fmt;
+
+use crate::cuefile::{format_cue_time, CueSheet};
+
+/// Default deviation from the expected track length that is reported, in seconds
+pub const DEFAULT_THRESHOLD_SECONDS: f64 = 5.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The sheet can't be used as it is
+    Error,
+    /// The sheet is usable, but probably wrong
+    Warning,
+}
+
+/// A problem found in a CUE sheet
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub severity: Severity,
+    /// Track number, None for the sheet as a whole
+    pub track: Option<u32>,
+    pub message: String,
+}
+
+impl Finding {
+    fn error(track: Option<u32>, message: String) -> Self {
+        Finding { severity: Severity::Error, track, message }
+    }
+
+    fn warning(track: Option<u32>, message: String) -> Self {
+        Finding { severity: Severity::Warning, track, message }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "ERROR",
+            Severity::Warning => "WARNING",
+        };
+        match self.track {
+            Some(track) => write!(f, "{}: Track {:02}: {}", severity, track, self.message),
+            None => write!(f, "{}: {}", severity, self.message),
+        }
+    }
+}
+
+/// Check track numbers and INDEX times of `sheet` against a WAV file of
+/// `file_duration` seconds
+pub fn check_structure(sheet: &CueSheet, file_duration: f64) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    if sheet.tracks.is_empty() {
+        findings.push(Finding::error(None, "No tracks".to_string()));
+        return findings;
+    }
+
+    let mut previous: Option<(u32, f64)> = None;
+    for track in &sheet.tracks {
+        let number = Some(track.number);
+        match previous {
+            Some((previous_number, _)) if track.number != previous_number + 1 => findings.push(Finding::warning(
+                number,
+                format!("Track number follows track {:02}", previous_number),
+            )),
+            None if track.number != 1 => {
+                findings.push(Finding::warning(number, "First track is not track 01".to_string()))
+            }
+            _ => {}
+        }
+        if let Some((previous_number, previous_start)) = previous {
+            if track.start <= previous_start {
+                findings.push(Finding::error(number, format!(
+                    "INDEX 01 at {} is not after the start of track {:02} ({})",
+                    format_cue_time(track.start), previous_number, format_cue_time(previous_start)
+                )));
+            }
+        }
+        if track.start >= file_duration {
+            findings.push(Finding::error(number, format!(
+                "INDEX 01 at {} is beyond the end of the file ({})",
+                format_cue_time(track.start), format_cue_time(file_duration)
+            )));
+        }
+        if let Some(pregap) = track.pregap_start {
+            if pregap > track.start {
+                findings.push(Finding::error(number, format!(
+                    "INDEX 00 at {} is after INDEX 01 ({})",
+                    format_cue_time(pregap), format_cue_time(track.start)
+                )));
+            }
+            if let Some((previous_number, previous_start)) = previous {
+                if pregap <= previous_start {
+                    findings.push(Finding::error(number, format!(
+                        "INDEX 00 at {} is not after the start of track {:02}",
+                        format_cue_time(pregap), previous_number
+                    )));
+                }
+            }
+        }
+        previous = Some((track.number, track.start));
+    }
+
+    if let (Some(groove_out), Some(last)) = (sheet.groove_out, sheet.tracks.last()) {
+        if groove_out > file_duration {
+            findings.push(Finding::error(None, format!(
+                "GROOVE_OUT at {} is beyond the end of the file ({})",
+                format_cue_time(groove_out), format_cue_time(file_duration)
+            )));
+        }
+        if groove_out <= last.start {
+            findings.push(Finding::error(None, format!(
+                "GROOVE_OUT at {} is not after the start of the last track",
+                format_cue_time(groove_out)
+            )));
+        }
+    }
+    findings
+}
+
+/// Length of each track in seconds: up to the start of the next track (its
+/// pregap included), the last one up to the groove-out or the end of the file
+pub fn track_durations(sheet: &CueSheet, file_duration: f64) -> Vec<f64> {
+    let end = sheet.groove_out.unwrap_or(file_duration).min(file_duration);
+    sheet
+        .tracks
+        .iter()
+        .enumerate()
+        .map(|(i, track)| {
+            let next = sheet.tracks.get(i + 1).map_or(end, |next| next.start);
+            next - track.start
+        })
+        .collect()
+}
+
+/// Compare the track lengths of `sheet` with `expected` lengths in seconds
+/// and report deviations larger than `threshold` seconds
+pub fn compare_durations(sheet: &CueSheet, file_duration: f64, expected: &[f64], threshold: f64) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    if expected.len() != sheet.tracks.len() {
+        findings.push(Finding::warning(None, format!(
+            "{} tracks in the CUE sheet, {} expected",
+            sheet.tracks.len(), expected.len()
+        )));
+    }
+    let durations = track_durations(sheet, file_duration);
+    for ((track, duration), &expected) in sheet.tracks.iter().zip(durations).zip(expected) {
+        let deviation = duration - expected;
+        if expected > 0.0 && deviation.abs() > threshold {
+            findings.push(Finding::warning(Some(track.number), format!(
+                "{:.1} s long, {:.1} s expected ({:+.1} s)",
+                duration, expected, deviation
+            )));
+        }
+    }
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cuefile::parse_cue;
+
+    const CUE: &str = "FILE \"side.wav\" WAVE\n\
+        TRACK 01 AUDIO\n  INDEX 01 00:02:00\n\
+        TRACK 02 AUDIO\n  INDEX 00 03:00:00\n  INDEX 01 03:02:00\n\
+        TRACK 03 AUDIO\n  INDEX 01 06:00:00\n\
+        REM GROOVE_OUT 09:00:00\n";
+
+    #[test]
+    fn test_check_structure() {
+        let sheet = parse_cue(CUE).unwrap();
+        assert!(check_structure(&sheet, 600.0).is_empty());
+
+        // The file is shorter than the sheet
+        let findings = check_structure(&sheet, 300.0);
+        assert_eq!(findings.len(), 2);
+        assert_eq!((findings[0].severity, findings[0].track), (Severity::Error, Some(3)));
+        assert!(findings[1].message.starts_with("GROOVE_OUT"), "{}", findings[1]);
+
+        // Tracks out of order
+        let mut sheet = parse_cue(CUE).unwrap();
+        sheet.tracks[2].start = 100.0;
+        sheet.tracks[2].number = 4;
+        let findings = check_structure(&sheet, 600.0);
+        assert_eq!(findings.len(), 2);
+        assert_eq!(findings[0].severity, Severity::Warning);
+        assert_eq!(findings[1].to_string(), "ERROR: Track 04: INDEX 01 at 01:40:00 is not after the start of track 02 (03:02:00)");
+    }
+
+    #[test]
+    fn test_compare_durations() {
+        let sheet = parse_cue(CUE).unwrap();
+        assert_eq!(track_durations(&sheet, 600.0), vec![180.0, 178.0, 180.0]);
+        assert!(compare_durations(&sheet, 600.0, &[182.0, 176.0, 179.0], DEFAULT_THRESHOLD_SECONDS).is_empty());
+
+        let findings = compare_durations(&sheet, 600.0, &[182.0, 190.0], DEFAULT_THRESHOLD_SECONDS);
+        assert_eq!(findings.len(), 2);
+        assert_eq!(findings[1].to_string(), "WARNING: Track 02: 178.0 s long, 190.0 s expected (-12.0 s)");
+    }
+}
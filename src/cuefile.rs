@@ -5,9 +5,153 @@
 //! naming convention based on MusicBrainz match status.
 
 use std::fs::File;
-use std::io::Write;
+use std::io::{BufReader, Write};
 use std::path::{Path, PathBuf};
 
+use crate::tagging::ReleaseTags;
+use crate::wavfile::{read_wav_header, write_cue_points, WavCuePoint};
+
+/// REM field with the end of the music (groove-out)
+pub const REM_GROOVE_OUT: &str = "GROOVE_OUT";
+/// REM field with the start and end of the needle-drop thump in the lead-in
+pub const REM_NEEDLE_DROP: &str = "NEEDLE_DROP";
+/// REM field with the drift of the turntable to correct when splitting
+/// (measured / expected duration)
+pub const REM_SPEED_CORRECTION: &str = "SPEED_CORRECTION";
+/// REM field of a track with the confidence of the boundary it starts at
+/// (score, prominence in dB, detection mode)
+pub const REM_AUTOREC_CONFIDENCE: &str = "AUTOREC_CONFIDENCE";
+
+/// Character set of written CUE files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CueEncoding {
+    #[default]
+    Utf8,
+    /// ISO 8859-1, for hardware players that can't read UTF-8; characters
+    /// outside of it are transliterated or replaced by '?'
+    Latin1,
+}
+
+impl CueEncoding {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "utf8" | "utf-8" => Some(CueEncoding::Utf8),
+            "latin1" | "latin-1" | "iso-8859-1" => Some(CueEncoding::Latin1),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CueEncoding::Utf8 => "utf8",
+            CueEncoding::Latin1 => "latin1",
+        }
+    }
+}
+
+/// How the text of a CUE file is written to disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CueText {
+    pub encoding: CueEncoding,
+    /// Replace all non-ASCII characters by ASCII approximations
+    pub transliterate: bool,
+}
+
+impl CueText {
+    /// CUE content as bytes in this character set
+    pub fn encode(&self, content: &str) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(content.len());
+        for c in content.chars() {
+            let direct = c.is_ascii()
+                || (!self.transliterate && (self.encoding == CueEncoding::Utf8 || (c as u32) < 0x100));
+            if direct && self.encoding == CueEncoding::Latin1 {
+                bytes.push(c as u8);
+            } else if direct {
+                let mut buffer = [0; 4];
+                bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+            } else {
+                bytes.extend_from_slice(transliterate_char(c).as_deref().unwrap_or("?").as_bytes());
+            }
+        }
+        bytes
+    }
+}
+
+/// Read CUE file bytes: UTF-8 (with or without byte order mark) if valid,
+/// otherwise Latin-1 as written by older rippers and [`CueEncoding::Latin1`]
+pub fn decode_cue(bytes: Vec<u8>) -> (String, CueEncoding) {
+    match String::from_utf8(bytes) {
+        Ok(text) => match text.strip_prefix('\u{feff}') {
+            Some(text) => (text.to_string(), CueEncoding::Utf8),
+            None => (text, CueEncoding::Utf8),
+        },
+        Err(e) => (e.into_bytes().iter().map(|&b| b as char).collect(), CueEncoding::Latin1),
+    }
+}
+
+/// Make a text safe for a quoted CUE field.
+///
+/// CUE sheets have no escape sequences, so double quotes become single
+/// quotes, and line breaks and other control characters become spaces.
+pub fn cue_string(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '"' => '\'',
+            c if c.is_control() => ' ',
+            c => c,
+        })
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+/// ASCII approximation of a text; characters without one become '?'
+pub fn transliterate(s: &str) -> String {
+    s.chars().map(|c| transliterate_char(c).unwrap_or_else(|| "?".to_string())).collect()
+}
+
+/// ASCII approximation of a character, None if there is none
+fn transliterate_char(c: char) -> Option<String> {
+    if c.is_ascii() {
+        return Some(c.to_string());
+    }
+    let lower = c.to_lowercase().next().unwrap_or(c);
+    let ascii = match lower {
+        'ä' | 'æ' => "ae",
+        'ö' | 'œ' => "oe",
+        'ü' => "ue",
+        'ß' => "ss",
+        'à' | 'á' | 'â' | 'ã' | 'å' | 'ā' | 'ă' | 'ą' => "a",
+        'ç' | 'ć' | 'č' => "c",
+        'ď' | 'đ' | 'ð' => "d",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ė' | 'ę' | 'ě' => "e",
+        'ğ' => "g",
+        'ì' | 'í' | 'î' | 'ï' | 'ī' | 'į' | 'ı' => "i",
+        'ł' => "l",
+        'ñ' | 'ń' | 'ň' => "n",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ø' | 'ō' | 'ő' => "o",
+        'ř' => "r",
+        'ś' | 'š' | 'ş' => "s",
+        'ť' | 'ţ' => "t",
+        'þ' => "th",
+        'ù' | 'ú' | 'û' | 'ů' | 'ū' | 'ű' | 'ų' => "u",
+        'ý' | 'ÿ' => "y",
+        'ź' | 'ż' | 'ž' => "z",
+        '‘' | '’' | '‚' | '′' | '“' | '”' | '„' | '«' | '»' => "'",
+        '–' | '—' | '‐' => "-",
+        '…' => "...",
+        '×' => "x",
+        '\u{a0}' => " ",
+        _ => return None,
+    };
+    if c.is_uppercase() {
+        let mut chars = ascii.chars();
+        chars.next().map(|first| first.to_ascii_uppercase().to_string() + chars.as_str())
+    } else {
+        Some(ascii.to_string())
+    }
+}
+
 /// Strip only the .wav extension from a path, preserving side numbers like .4
 /// e.g. "dj_shadow_endtroducing.4.wav" -> "dj_shadow_endtroducing.4"
 pub fn wav_base_path(wav_file: &str) -> PathBuf {
@@ -28,9 +172,102 @@ pub struct Valley {
     pub left_level_db: f32,
     pub right_level_db: f32,
     pub width_seconds: f64,
+    /// Start and end of the quiet gap around the valley in seconds; both
+    /// equal `position_seconds` if the width was not measured
+    pub gap_start_seconds: f64,
+    pub gap_end_seconds: f64,
+    pub score: f64,
+}
+
+/// How the boundaries of a recording were placed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DetectionMode {
+    /// At track marks set while recording
+    Marks,
+    /// Exactly at boundaries saved by a review
+    Reviewed,
+    /// Guided by the track lengths of the release
+    Guided,
+    /// Seeded by the song changes found by identification
+    Seeded,
+    /// From the valleys of the level curve alone
+    Autonomous,
+}
+
+impl DetectionMode {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "marks" => Some(DetectionMode::Marks),
+            "reviewed" => Some(DetectionMode::Reviewed),
+            "guided" => Some(DetectionMode::Guided),
+            "seeded" => Some(DetectionMode::Seeded),
+            "autonomous" => Some(DetectionMode::Autonomous),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DetectionMode::Marks => "marks",
+            DetectionMode::Reviewed => "reviewed",
+            DetectionMode::Guided => "guided",
+            DetectionMode::Seeded => "seeded",
+            DetectionMode::Autonomous => "autonomous",
+        }
+    }
+
+    /// Name shown in the info file
+    pub fn description(&self) -> &'static str {
+        match self {
+            DetectionMode::Marks => "Track marks",
+            DetectionMode::Reviewed => "Reviewed boundaries",
+            DetectionMode::Guided => "Guided (MusicBrainz-based)",
+            DetectionMode::Seeded => "Seeded (identified songs)",
+            DetectionMode::Autonomous => "Autonomous (valley-based)",
+        }
+    }
+}
+
+/// How sure the detection is of a boundary (REM AUTOREC_CONFIDENCE)
+///
+/// Review tools can highlight boundaries with a low score or prominence;
+/// the mode tells how much the two mean, e.g. a boundary at a track mark is
+/// right however shallow its valley is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundaryConfidence {
+    /// Valley score; deeper, wider and more prominent pauses score higher
     pub score: f64,
+    pub prominence_db: f32,
+    pub mode: DetectionMode,
 }
 
+impl BoundaryConfidence {
+    pub fn new(valley: &Valley, mode: DetectionMode) -> Self {
+        BoundaryConfidence {
+            score: valley.score,
+            prominence_db: valley.prominence_db,
+            mode,
+        }
+    }
+
+    /// Value of the REM line: score, prominence and mode
+    pub fn to_rem_value(&self) -> String {
+        format!("{:.1} {:.1} {}", self.score, self.prominence_db, self.mode.as_str())
+    }
+
+    /// Parse the value of a REM AUTOREC_CONFIDENCE line
+    pub fn from_rem_value(value: &str) -> Option<Self> {
+        let mut parts = value.split_whitespace();
+        let score = parts.next()?.parse().ok()?;
+        let prominence_db = parts.next()?.parse().ok()?;
+        let mode = DetectionMode::from_str(parts.next()?)?;
+        Some(BoundaryConfidence { score, prominence_db, mode })
+    }
+}
+
+/// Shortest gap written as a pregap (INDEX 00), in seconds
+pub const MIN_PREGAP_SECONDS: f64 = 0.5;
+
 /// Generate CUE file content from track boundaries.
 ///
 /// # Arguments
@@ -38,8 +275,15 @@ pub struct Valley {
 /// * `artist` - Artist name for the CUE sheet
 /// * `title` - Album/release title for the CUE sheet
 /// * `track_names` - Names for each track (optional)
-/// * `groove_in` - Start time of first track in seconds
-/// * `boundaries` - Valley positions representing track boundaries
+/// * `groove` - Groove-in (start of the first track) and groove-out in seconds
+/// * `needle_drop` - Start and end of the needle-drop thump in seconds, if any
+/// * `speed_correction` - Turntable drift for the splitter to correct, if any
+/// * `boundaries` - Valleys representing track boundaries; a track starts
+///   at the end of the valley's gap (INDEX 01), a gap of at least
+///   [`MIN_PREGAP_SECONDS`] becomes its pregap (INDEX 00)
+/// * `mode` - How the boundaries were placed, written with the score and
+///   prominence of each one as a REM AUTOREC_CONFIDENCE line of its track
+/// * `release` - Year and release IDs from the album lookup, written as REM lines
 ///
 /// # Returns
 /// Complete CUE file content as a string
@@ -48,9 +292,14 @@ pub fn generate_cue_file(
     artist: &str,
     title: &str,
     track_names: &[String],
-    groove_in: f64,
+    groove: (f64, f64),
+    needle_drop: Option<(f64, f64)>,
+    speed_correction: Option<f64>,
     boundaries: &[Valley],
+    mode: DetectionMode,
+    release: &ReleaseTags,
 ) -> String {
+    let (groove_in, groove_out) = groove;
     let wav_filename = Path::new(wav_file)
         .file_name()
         .and_then(|n| n.to_str())
@@ -58,16 +307,28 @@ pub fn generate_cue_file(
     
     let mut cue = String::new();
     cue.push_str(&format!("REM GENERATOR \"HiFiBerry AutoRec boundary_finder\"\n"));
-    cue.push_str(&format!("PERFORMER \"{}\"\n", artist));
-    cue.push_str(&format!("TITLE \"{}\"\n", title));
-    cue.push_str(&format!("FILE \"{}\" WAVE\n", wav_filename));
+    cue.push_str(&release.to_cue_rem());
+    cue.push_str(&format!("REM {} {}\n", REM_GROOVE_OUT, format_cue_time(groove_out)));
+    if let Some((start, end)) = needle_drop {
+        cue.push_str(&format!("REM {} {} {}\n", REM_NEEDLE_DROP, format_cue_time(start), format_cue_time(end)));
+    }
+    if let Some(ratio) = speed_correction {
+        cue.push_str(&format!("REM {} {:.4}\n", REM_SPEED_CORRECTION, ratio));
+    }
+    cue.push_str(&format!("PERFORMER \"{}\"\n", cue_string(artist)));
+    cue.push_str(&format!("TITLE \"{}\"\n", cue_string(title)));
+    cue.push_str(&format!("FILE \"{}\" WAVE\n", cue_string(wav_filename)));
     
-    let mut track_positions = vec![groove_in];
+    // (pregap start, track start, confidence of the boundary)
+    let mut track_positions = vec![(None, groove_in, None)];
     for b in boundaries {
-        track_positions.push(b.position_seconds);
+        let gap_start = b.gap_start_seconds.min(b.position_seconds);
+        let start = b.gap_end_seconds.max(b.position_seconds);
+        let pregap = (start - gap_start >= MIN_PREGAP_SECONDS).then_some(gap_start);
+        track_positions.push((pregap, start, Some(BoundaryConfidence::new(b, mode))));
     }
     
-    for (i, &pos) in track_positions.iter().enumerate() {
+    for (i, &(pregap, pos, confidence)) in track_positions.iter().enumerate() {
         let track_num = i + 1;
         let default_name = format!("Track {}", track_num);
         let track_name = track_names.get(i)
@@ -83,14 +344,15 @@ pub fn generate_cue_file(
         };
         
         cue.push_str(&format!("  TRACK {:02} AUDIO\n", track_num));
-        cue.push_str(&format!("    TITLE \"{}\"\n", clean_name));
-        cue.push_str(&format!("    PERFORMER \"{}\"\n", artist));
-        
-        // Convert position to MM:SS:FF (frames, 75 per second)
-        let minutes = (pos / 60.0) as u32;
-        let seconds = (pos % 60.0) as u32;
-        let frames = ((pos % 1.0) * 75.0) as u32;
-        cue.push_str(&format!("    INDEX 01 {:02}:{:02}:{:02}\n", minutes, seconds, frames));
+        cue.push_str(&format!("    TITLE \"{}\"\n", cue_string(clean_name)));
+        cue.push_str(&format!("    PERFORMER \"{}\"\n", cue_string(artist)));
+        if let Some(confidence) = confidence {
+            cue.push_str(&format!("    REM {} {}\n", REM_AUTOREC_CONFIDENCE, confidence.to_rem_value()));
+        }
+        if let Some(pregap) = pregap {
+            cue.push_str(&format!("    INDEX 00 {}\n", format_cue_time(pregap)));
+        }
+        cue.push_str(&format!("    INDEX 01 {}\n", format_cue_time(pos)));
     }
     
     cue
@@ -102,6 +364,7 @@ pub fn generate_cue_file(
 /// * `wav_file` - Path to the WAV file (used to derive CUE file path)
 /// * `cue_content` - Complete CUE file content
 /// * `has_mb_match` - Whether this recording was matched with MusicBrainz
+/// * `text` - Character set of the file
 ///
 /// # Returns
 /// Path to the created CUE file, or an error
@@ -109,7 +372,7 @@ pub fn generate_cue_file(
 /// # Naming Convention
 /// * If `has_mb_match` is true: Creates `.cue` file (verified track data)
 /// * If `has_mb_match` is false: Creates `.guess.cue` file (autonomous detection)
-pub fn write_cue_file(wav_file: &str, cue_content: &str, has_mb_match: bool) -> Result<PathBuf, std::io::Error> {
+pub fn write_cue_file(wav_file: &str, cue_content: &str, has_mb_match: bool, text: &CueText) -> Result<PathBuf, std::io::Error> {
     let base_path = wav_base_path(wav_file);
     let cue_path = if has_mb_match {
         PathBuf::from(format!("{}.cue", base_path.display()))
@@ -118,7 +381,7 @@ pub fn write_cue_file(wav_file: &str, cue_content: &str, has_mb_match: bool) ->
         PathBuf::from(format!("{}.guess.cue", base_path.display()))
     };
     let mut file = File::create(&cue_path)?;
-    file.write_all(cue_content.as_bytes())?;
+    file.write_all(&text.encode(cue_content))?;
     Ok(cue_path)
 }
 
@@ -146,6 +409,7 @@ pub fn has_cue_file(wav_file: &str) -> bool {
 /// * `track_names` - Track names (if available)
 /// * `expected_tracks` - Expected track data from MusicBrainz (if available)
 /// * `mb_info` - MusicBrainz release information string
+/// * `mode` - How the boundaries were placed
 ///
 /// # Returns
 /// Text content for the info file
@@ -157,6 +421,7 @@ pub fn generate_info_file(
     track_names: &[String],
     expected_tracks: Option<&[(f64, f64)]>, // (expected_start, expected_length)
     mb_info: Option<&str>,
+    mode: DetectionMode,
 ) -> String {
     let mut info = String::new();
     
@@ -181,12 +446,7 @@ pub fn generate_info_file(
     }
     
     // Detection method
-    let detection_method = if expected_tracks.is_some() {
-        "Guided (MusicBrainz-based)"
-    } else {
-        "Autonomous (valley-based)"
-    };
-    info.push_str(&format!("Detection Method: {}\n\n", detection_method));
+    info.push_str(&format!("Detection Method: {}\n\n", mode.description()));
     
     // Track boundaries and adjustments
     if !boundaries.is_empty() {
@@ -204,6 +464,9 @@ pub fn generate_info_file(
             info.push_str(&format!("  Start: {:.2}s\n", current_pos));
             info.push_str(&format!("  End:   {:.2}s\n", boundary.position_seconds));
             info.push_str(&format!("  Duration: {:.2}s\n", boundary.position_seconds - current_pos));
+            info.push_str(&format!("  Boundary score: {:.1}\n", boundary.score));
+            info.push_str(&format!("  Boundary prominence: {:.1} dB\n", boundary.prominence_db));
+            info.push_str(&format!("  Boundary mode: {}\n", mode.as_str()));
             
             // Show adjustment if we have expected data
             if let Some(expected) = expected_tracks {
@@ -274,3 +537,382 @@ pub fn write_info_file(wav_file: &str, info_content: &str, has_mb_match: bool) -
     file.write_all(info_content.as_bytes())?;
     Ok(info_path)
 }
+
+/// Path of the clipping report the recorder writes next to a WAV file.
+///
+/// The report is added to the info file when the CUE sheet is created.
+pub fn clip_report_path(wav_file: &str) -> PathBuf {
+    PathBuf::from(format!("{}.overs.txt", wav_base_path(wav_file).display()))
+}
+
+/// Path of the dropout report the recorder writes next to a WAV file.
+///
+/// Lists the positions where the input stalled during the recording.
+pub fn dropout_report_path(wav_file: &str) -> PathBuf {
+    PathBuf::from(format!("{}.dropouts.txt", wav_base_path(wav_file).display()))
+}
+
+/// Path of the track marks the recorder writes next to a WAV file.
+///
+/// Each line holds the position of a mark in seconds; `cue_creator` places
+/// track boundaries at these marks instead of searching for them.
+pub fn marks_path(wav_file: &str) -> PathBuf {
+    PathBuf::from(format!("{}.marks", wav_base_path(wav_file).display()))
+}
+
+/// Parse a marks file: one position in seconds per line, `#` starts a comment
+pub fn parse_marks(content: &str) -> Vec<f64> {
+    let mut marks: Vec<f64> = content
+        .lines()
+        .filter_map(|line| line.split('#').next()?.trim().parse().ok())
+        .filter(|pos: &f64| pos.is_finite() && *pos >= 0.0)
+        .collect();
+    marks.sort_by(f64::total_cmp);
+    marks
+}
+
+/// Track marks placed while recording `wav_file`; empty if there are none
+pub fn read_marks(wav_file: &str) -> Vec<f64> {
+    std::fs::read_to_string(marks_path(wav_file)).map(|c| parse_marks(&c)).unwrap_or_default()
+}
+
+/// Path of the boundaries saved by a review in the web UI.
+///
+/// Unlike track marks, `cue_creator` places the boundaries exactly at these
+/// positions; a file without positions means the recording is one track.
+pub fn reviewed_path(wav_file: &str) -> PathBuf {
+    PathBuf::from(format!("{}.boundaries", wav_base_path(wav_file).display()))
+}
+
+/// Reviewed boundaries of `wav_file`, None if it was not reviewed
+pub fn read_reviewed(wav_file: &str) -> Option<Vec<f64>> {
+    std::fs::read_to_string(reviewed_path(wav_file)).ok().map(|c| parse_marks(&c))
+}
+
+/// Save the reviewed boundaries of `wav_file`
+pub fn write_reviewed(wav_file: &str, boundaries: &[f64]) -> std::io::Result<()> {
+    let content: String = std::iter::once("# reviewed boundaries, used as they are\n".to_string())
+        .chain(boundaries.iter().map(|b| format!("{:.3}\n", b)))
+        .collect();
+    std::fs::write(reviewed_path(wav_file), content)
+}
+
+/// A track read from a CUE sheet
+#[derive(Debug, Clone, PartialEq)]
+pub struct CueTrack {
+    pub number: u32,
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    /// Start of the pregap (INDEX 00) in seconds, if present
+    pub pregap_start: Option<f64>,
+    /// Start of the track (INDEX 01) in seconds
+    pub start: f64,
+    /// Confidence of the boundary the track starts at (REM AUTOREC_CONFIDENCE)
+    pub confidence: Option<BoundaryConfidence>,
+}
+
+/// A parsed CUE sheet (single FILE only)
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CueSheet {
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    pub file: Option<String>,
+    pub tracks: Vec<CueTrack>,
+    /// Year and release IDs from REM lines
+    pub release: ReleaseTags,
+    /// End of the music in seconds (REM GROOVE_OUT), if known
+    pub groove_out: Option<f64>,
+    /// Start and end of the needle-drop thump in seconds (REM NEEDLE_DROP)
+    pub needle_drop: Option<(f64, f64)>,
+    /// Measured / expected duration to correct when splitting (REM SPEED_CORRECTION)
+    pub speed_correction: Option<f64>,
+}
+
+/// Format seconds as a CUE time (MM:SS:FF, 75 frames per second)
+pub fn format_cue_time(pos: f64) -> String {
+    let minutes = (pos / 60.0) as u32;
+    let seconds = (pos % 60.0) as u32;
+    let frames = ((pos % 1.0) * 75.0) as u32;
+    format!("{:02}:{:02}:{:02}", minutes, seconds, frames)
+}
+
+/// Parse a CUE time (MM:SS:FF, 75 frames per second) into seconds
+pub fn parse_cue_time(s: &str) -> Option<f64> {
+    let parts: Vec<&str> = s.trim().split(':').collect();
+    if parts.len() != 3 {
+        return None;
+    }
+    let minutes: u32 = parts[0].parse().ok()?;
+    let seconds: u32 = parts[1].parse().ok()?;
+    let frames: u32 = parts[2].parse().ok()?;
+    if seconds >= 60 || frames >= 75 {
+        return None;
+    }
+    Some(minutes as f64 * 60.0 + seconds as f64 + frames as f64 / 75.0)
+}
+
+/// Value of a CUE command argument, with surrounding quotes removed
+fn unquote(s: &str) -> String {
+    let s = s.trim();
+    if s.len() >= 2 && s.starts_with('"') && s.ends_with('"') {
+        s[1..s.len() - 1].to_string()
+    } else {
+        s.to_string()
+    }
+}
+
+/// Parse CUE sheet content.
+///
+/// Supports the subset written by [`generate_cue_file`] and common rippers:
+/// TITLE, PERFORMER, FILE, TRACK, INDEX 00/01, REM GROOVE_OUT, REM NEEDLE_DROP,
+/// REM SPEED_CORRECTION, REM AUTOREC_CONFIDENCE and the REM fields of
+/// [`ReleaseTags`]. Other commands are ignored.
+pub fn parse_cue(content: &str) -> Result<CueSheet, String> {
+    let mut sheet = CueSheet::default();
+    let mut current: Option<CueTrack> = None;
+
+    for (line_no, line) in content.lines().enumerate() {
+        let line = line.trim();
+        let (command, rest) = match line.split_once(char::is_whitespace) {
+            Some((c, r)) => (c, r.trim()),
+            None => (line, ""),
+        };
+
+        match command.to_ascii_uppercase().as_str() {
+            "TITLE" | "PERFORMER" => {
+                let value = Some(unquote(rest));
+                let is_title = command.eq_ignore_ascii_case("TITLE");
+                match current.as_mut() {
+                    Some(track) if is_title => track.title = value,
+                    Some(track) => track.performer = value,
+                    None if is_title => sheet.title = value,
+                    None => sheet.performer = value,
+                }
+            }
+            "REM" => {
+                if let Some((field, value)) = rest.split_once(char::is_whitespace) {
+                    if field.eq_ignore_ascii_case(REM_GROOVE_OUT) {
+                        sheet.groove_out = parse_cue_time(value);
+                    } else if field.eq_ignore_ascii_case(REM_NEEDLE_DROP) {
+                        sheet.needle_drop = value.split_once(char::is_whitespace)
+                            .and_then(|(start, end)| Some((parse_cue_time(start)?, parse_cue_time(end)?)));
+                    } else if field.eq_ignore_ascii_case(REM_SPEED_CORRECTION) {
+                        sheet.speed_correction = value.trim().parse().ok().filter(|&r: &f64| r > 0.0);
+                    } else if field.eq_ignore_ascii_case(REM_AUTOREC_CONFIDENCE) {
+                        if let Some(track) = current.as_mut() {
+                            track.confidence = BoundaryConfidence::from_rem_value(value);
+                        }
+                    } else {
+                        sheet.release.parse_cue_rem(field, value);
+                    }
+                }
+            }
+            "FILE" => {
+                if sheet.file.is_some() {
+                    return Err("CUE sheets with multiple FILE entries are not supported".to_string());
+                }
+                // FILE "name.wav" WAVE
+                let name = match rest.rfind(char::is_whitespace) {
+                    Some(pos) => &rest[..pos],
+                    None => rest,
+                };
+                sheet.file = Some(unquote(name));
+            }
+            "TRACK" => {
+                if let Some(track) = current.take() {
+                    sheet.tracks.push(track);
+                }
+                let number = rest.split_whitespace().next()
+                    .and_then(|n| n.parse().ok())
+                    .ok_or_else(|| format!("Line {}: invalid TRACK number", line_no + 1))?;
+                current = Some(CueTrack {
+                    number,
+                    title: None,
+                    performer: None,
+                    pregap_start: None,
+                    start: f64::NAN,
+                    confidence: None,
+                });
+            }
+            "INDEX" => {
+                let track = current.as_mut()
+                    .ok_or_else(|| format!("Line {}: INDEX outside of TRACK", line_no + 1))?;
+                let mut parts = rest.split_whitespace();
+                let index: u32 = parts.next().and_then(|i| i.parse().ok())
+                    .ok_or_else(|| format!("Line {}: invalid INDEX number", line_no + 1))?;
+                let time = parts.next().and_then(parse_cue_time)
+                    .ok_or_else(|| format!("Line {}: invalid INDEX time", line_no + 1))?;
+                match index {
+                    0 => track.pregap_start = Some(time),
+                    1 => track.start = time,
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(track) = current.take() {
+        sheet.tracks.push(track);
+    }
+
+    if let Some(track) = sheet.tracks.iter().find(|t| t.start.is_nan()) {
+        return Err(format!("Track {} has no INDEX 01", track.number));
+    }
+
+    Ok(sheet)
+}
+
+/// Read and parse a CUE file in UTF-8 or Latin-1
+pub fn read_cue_file(path: &Path) -> Result<CueSheet, String> {
+    let bytes = std::fs::read(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    parse_cue(&decode_cue(bytes).0)
+}
+
+/// Find the CUE file belonging to a WAV file (`.cue` preferred over `.guess.cue`)
+pub fn find_cue_file(wav_file: &str) -> Option<PathBuf> {
+    let base_path = wav_base_path(wav_file);
+    let cue_path = PathBuf::from(format!("{}.cue", base_path.display()));
+    let guess_cue_path = PathBuf::from(format!("{}.guess.cue", base_path.display()));
+    if cue_path.exists() {
+        Some(cue_path)
+    } else if guess_cue_path.exists() {
+        Some(guess_cue_path)
+    } else {
+        None
+    }
+}
+
+/// Write the track starts of `sheet` into the WAV file as `cue ` markers
+/// labeled with the track titles, replacing any markers it already has.
+///
+/// Editors like Audacity and Reaper show these markers directly. Returns
+/// the number of markers written.
+pub fn embed_cue_points(wav_file: &str, sheet: &CueSheet) -> Result<usize, String> {
+    let file = File::open(wav_file).map_err(|e| format!("Failed to open {}: {}", wav_file, e))?;
+    let header = read_wav_header(&mut BufReader::new(file))?;
+    let points: Vec<WavCuePoint> = sheet
+        .tracks
+        .iter()
+        .enumerate()
+        .map(|(i, track)| WavCuePoint {
+            id: i as u32 + 1,
+            position: (track.start * header.sample_rate as f64).round() as u32,
+            label: Some(match &track.title {
+                Some(title) => format!("{:02} {}", track.number, title),
+                None => format!("Track {:02}", track.number),
+            }),
+        })
+        .collect();
+    write_cue_points(Path::new(wav_file), &points)
+        .map_err(|e| format!("Failed to write markers to {}: {}", wav_file, e))?;
+    Ok(points.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cue_time() {
+        assert_eq!(parse_cue_time("01:02:00"), Some(62.0));
+        assert_eq!(parse_cue_time("00:00:75"), None);
+        assert_eq!(parse_cue_time("1:02"), None);
+        assert!((parse_cue_time("00:10:15").unwrap() - 10.2).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_generated_cue_roundtrip() {
+        let valleys = vec![Valley {
+            position_seconds: 200.0,
+            depth_db: -70.0,
+            prominence_db: 20.0,
+            left_level_db: -20.0,
+            right_level_db: -20.0,
+            width_seconds: 2.0,
+            gap_start_seconds: 199.0,
+            gap_end_seconds: 201.0,
+            score: 100.0,
+        }];
+        let names = vec!["#1 First".to_string(), "#2 Second".to_string()];
+        let release = ReleaseTags::from_release_info("https://musicbrainz.org/release/abc-123", Some(1994));
+        let content = generate_cue_file("/tmp/side.1.wav", "Artist", "Album", &names, (4.0, 390.0), Some((1.0, 2.0)), Some(1.007), &valleys, DetectionMode::Guided, &release);
+
+        let sheet = parse_cue(&content).unwrap();
+        assert_eq!(sheet.performer.as_deref(), Some("Artist"));
+        assert_eq!(sheet.title.as_deref(), Some("Album"));
+        assert_eq!(sheet.file.as_deref(), Some("side.1.wav"));
+        assert_eq!(sheet.tracks.len(), 2);
+        assert_eq!(sheet.tracks[1].title.as_deref(), Some("Second"));
+        assert_eq!(sheet.tracks[1].performer.as_deref(), Some("Artist"));
+        assert_eq!(sheet.tracks[0].start, 4.0);
+        assert_eq!(sheet.tracks[0].pregap_start, None);
+        assert_eq!(sheet.tracks[1].pregap_start, Some(199.0));
+        assert_eq!(sheet.tracks[1].start, 201.0);
+        assert_eq!(sheet.release, release);
+        assert_eq!(sheet.groove_out, Some(390.0));
+        assert_eq!(sheet.needle_drop, Some((1.0, 2.0)));
+        assert_eq!(sheet.speed_correction, Some(1.007));
+        assert_eq!(sheet.tracks[0].confidence, None);
+        assert_eq!(sheet.tracks[1].confidence,
+                   Some(BoundaryConfidence { score: 100.0, prominence_db: 20.0, mode: DetectionMode::Guided }));
+        let info = generate_info_file("/tmp/side.1.wav", 4.0, 390.0, &valleys, &names, None, None, DetectionMode::Guided);
+        assert!(info.contains("Detection Method: Guided"), "{}", info);
+        assert!(info.contains("  Boundary prominence: 20.0 dB\n  Boundary mode: guided\n"), "{}", info);
+        assert_eq!(format_cue_time(62.2), "01:02:15");
+    }
+
+    #[test]
+    fn test_parse_cue_pregap_and_errors() {
+        let content = "FILE \"a b.wav\" WAVE\n  TRACK 01 AUDIO\n    INDEX 01 00:00:00\n  TRACK 02 AUDIO\n    INDEX 00 03:00:00\n    INDEX 01 03:02:00\n";
+        let sheet = parse_cue(content).unwrap();
+        assert_eq!(sheet.file.as_deref(), Some("a b.wav"));
+        assert_eq!(sheet.tracks[1].pregap_start, Some(180.0));
+        assert_eq!(sheet.tracks[1].start, 182.0);
+
+        assert!(parse_cue("TRACK 01 AUDIO\n  TITLE \"x\"\n").is_err());
+        assert!(parse_cue("INDEX 01 00:00:00\n").is_err());
+    }
+
+    #[test]
+    fn test_cue_text() {
+        let valleys: Vec<Valley> = Vec::new();
+        let names = vec!["Das \"Böse\"\nLied".to_string()];
+        let content = generate_cue_file("/tmp/side.1.wav", "Björk", "東京", &names, (4.0, 390.0), None, None, &valleys, DetectionMode::Autonomous, &ReleaseTags::default());
+        assert!(content.contains("TITLE \"Das 'Böse' Lied\""), "{}", content);
+        assert_eq!(parse_cue(&content).unwrap().tracks[0].title.as_deref(), Some("Das 'Böse' Lied"));
+
+        let latin1 = CueText { encoding: CueEncoding::Latin1, transliterate: false };
+        assert_eq!(latin1.encode("Björk – 東京"), b"Bj\xf6rk - ??".to_vec());
+        let ascii = CueText { encoding: CueEncoding::Utf8, transliterate: true };
+        assert_eq!(ascii.encode("Ärger mit Łódź"), b"Aerger mit Lodz".to_vec());
+        assert_eq!(CueText::default().encode("東京"), "東京".as_bytes().to_vec());
+
+        assert_eq!(decode_cue(b"TITLE \"Bj\xf6rk\"".to_vec()), ("TITLE \"Björk\"".to_string(), CueEncoding::Latin1));
+        assert_eq!(decode_cue("\u{feff}TITLE \"東京\"".as_bytes().to_vec()).0, "TITLE \"東京\"");
+    }
+
+    #[test]
+    fn test_parse_marks() {
+        let marks = parse_marks("# side A\n412.250\n187.5  # late\n\nnonsense\n-3\n");
+        assert_eq!(marks, vec![187.5, 412.25]);
+        assert_eq!(marks_path("/data/side.1.wav"), PathBuf::from("/data/side.1.marks"));
+    }
+
+    #[test]
+    fn test_reviewed_boundaries() {
+        let dir = tempfile::tempdir().unwrap();
+        let wav = dir.path().join("side.1.wav");
+        let wav = wav.to_str().unwrap();
+        assert_eq!(read_reviewed(wav), None);
+
+        write_reviewed(wav, &[187.5, 412.25]).unwrap();
+        assert_eq!(reviewed_path(wav), dir.path().join("side.1.boundaries"));
+        assert_eq!(read_reviewed(wav), Some(vec![187.5, 412.25]));
+
+        // Without boundaries the recording is one track, not unreviewed
+        write_reviewed(wav, &[]).unwrap();
+        assert_eq!(read_reviewed(wav), Some(Vec::new()));
+    }
+}
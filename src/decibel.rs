@@ -111,6 +111,304 @@ pub fn clipping_threshold(reference: f64, percentage: f64) -> i32 {
     (reference * percentage) as i32
 }
 
+/// Consecutive full-scale samples that count as one over
+pub const OVER_MIN_SAMPLES: usize = 3;
+
+/// Counts overs (runs of consecutive full-scale samples) on a single channel
+///
+/// A single sample at full scale may be a legitimate peak, a run of several
+/// is almost certainly a clipped converter. The current run is kept between
+/// calls so overs spanning chunk boundaries are counted once.
+#[derive(Debug, Clone)]
+pub struct OverCounter {
+    threshold: i32,
+    min_run: usize,
+    run: usize,
+}
+
+impl OverCounter {
+    pub fn new(threshold: i32, min_run: usize) -> Self {
+        OverCounter {
+            threshold,
+            min_run: min_run.max(1),
+            run: 0,
+        }
+    }
+
+    /// Process samples and return the number of overs that started in them
+    pub fn process(&mut self, samples: &[i32]) -> usize {
+        let mut overs = 0;
+        for &sample in samples {
+            if sample.saturating_abs() >= self.threshold {
+                self.run += 1;
+                if self.run == self.min_run {
+                    overs += 1;
+                }
+            } else {
+                self.run = 0;
+            }
+        }
+        overs
+    }
+
+    pub fn reset(&mut self) {
+        self.run = 0;
+    }
+}
+
+/// Reference voltage for 0 dBu (1 mW into 600 Ω, sqrt(0.6) V RMS)
+pub const DBU_REFERENCE_VOLTS: f64 = 0.774_596_669_241_483;
+
+/// Reference voltage for 0 dBV (1 V RMS)
+pub const DBV_REFERENCE_VOLTS: f64 = 1.0;
+
+/// Unit used to present signal levels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LevelUnit {
+    /// Decibels relative to digital full scale
+    Dbfs,
+    /// Decibels relative to 0.775 V RMS
+    Dbu,
+    /// Decibels relative to 1 V RMS
+    Dbv,
+}
+
+impl LevelUnit {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "dbfs" | "db" => Some(LevelUnit::Dbfs),
+            "dbu" => Some(LevelUnit::Dbu),
+            "dbv" => Some(LevelUnit::Dbv),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LevelUnit::Dbfs => "dBFS",
+            LevelUnit::Dbu => "dBu",
+            LevelUnit::Dbv => "dBV",
+        }
+    }
+}
+
+/// Convert an RMS voltage to dBu
+pub fn volts_to_dbu(volts: f64) -> f64 {
+    20.0 * (volts / DBU_REFERENCE_VOLTS).log10()
+}
+
+/// Convert a dBu level to RMS voltage
+pub fn dbu_to_volts(dbu: f64) -> f64 {
+    DBU_REFERENCE_VOLTS * 10f64.powf(dbu / 20.0)
+}
+
+/// Convert an RMS voltage to dBV
+pub fn volts_to_dbv(volts: f64) -> f64 {
+    20.0 * (volts / DBV_REFERENCE_VOLTS).log10()
+}
+
+/// Convert a dBV level to RMS voltage
+pub fn dbv_to_volts(dbv: f64) -> f64 {
+    DBV_REFERENCE_VOLTS * 10f64.powf(dbv / 20.0)
+}
+
+/// Convert dBu to dBV (0 dBu ≈ -2.21 dBV)
+pub fn dbu_to_dbv(dbu: f64) -> f64 {
+    dbu + 20.0 * (DBU_REFERENCE_VOLTS / DBV_REFERENCE_VOLTS).log10()
+}
+
+/// Convert dBV to dBu (0 dBV ≈ +2.21 dBu)
+pub fn dbv_to_dbu(dbv: f64) -> f64 {
+    dbv - 20.0 * (DBU_REFERENCE_VOLTS / DBV_REFERENCE_VOLTS).log10()
+}
+
+/// Relation between digital levels and the analog level at the converter input
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Calibration {
+    /// Analog level in dBu that produces a 0 dBFS signal
+    pub full_scale_dbu: f64,
+}
+
+impl Calibration {
+    pub fn new(full_scale_dbu: f64) -> Self {
+        Calibration { full_scale_dbu }
+    }
+
+    /// Build a calibration from a reference measurement
+    ///
+    /// # Arguments
+    /// * `measured_dbfs` - Level read by the meter for the reference signal
+    /// * `reference_dbu` - Known analog level of the reference signal
+    ///
+    /// # Example
+    /// A 0 dBu test tone that reads -18 dBFS means +18 dBu at full scale.
+    pub fn from_reference(measured_dbfs: f64, reference_dbu: f64) -> Self {
+        Calibration { full_scale_dbu: reference_dbu - measured_dbfs }
+    }
+
+    pub fn dbfs_to_dbu(&self, dbfs: f64) -> f64 {
+        dbfs + self.full_scale_dbu
+    }
+
+    pub fn dbu_to_dbfs(&self, dbu: f64) -> f64 {
+        dbu - self.full_scale_dbu
+    }
+
+    pub fn dbfs_to_dbv(&self, dbfs: f64) -> f64 {
+        dbu_to_dbv(self.dbfs_to_dbu(dbfs))
+    }
+
+    pub fn dbv_to_dbfs(&self, dbv: f64) -> f64 {
+        self.dbu_to_dbfs(dbv_to_dbu(dbv))
+    }
+}
+
+/// Maps dBFS values to the unit shown on a meter
+///
+/// All supported conversions are constant offsets in the dB domain,
+/// so a scale is just a unit plus an offset added to dBFS values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LevelScale {
+    pub unit: LevelUnit,
+    pub offset_db: f64,
+}
+
+impl LevelScale {
+    /// Plain dBFS display
+    pub fn dbfs() -> Self {
+        LevelScale { unit: LevelUnit::Dbfs, offset_db: 0.0 }
+    }
+
+    /// Create a scale for the given unit. Analog units require a calibration.
+    pub fn new(unit: LevelUnit, calibration: Option<&Calibration>) -> Result<Self, String> {
+        let offset_db = match (unit, calibration) {
+            (LevelUnit::Dbfs, _) => 0.0,
+            (LevelUnit::Dbu, Some(cal)) => cal.dbfs_to_dbu(0.0),
+            (LevelUnit::Dbv, Some(cal)) => cal.dbfs_to_dbv(0.0),
+            (_, None) => {
+                return Err(format!("Displaying levels in {} requires a calibration", unit.as_str()));
+            }
+        };
+        Ok(LevelScale { unit, offset_db })
+    }
+
+    /// Convert a dBFS value into this scale
+    pub fn apply(&self, dbfs: f64) -> f64 {
+        dbfs + self.offset_db
+    }
+
+    /// Short suffix for meter labels
+    pub fn suffix(&self) -> &'static str {
+        match self.unit {
+            LevelUnit::Dbfs => "dB",
+            LevelUnit::Dbu => "dBu",
+            LevelUnit::Dbv => "dBV",
+        }
+    }
+}
+
+/// Oversampling factor used for true-peak measurement
+pub const TRUE_PEAK_OVERSAMPLING: usize = 4;
+
+/// Filter taps per polyphase branch of the true-peak interpolator
+const TRUE_PEAK_TAPS_PER_PHASE: usize = 12;
+
+/// True-peak (inter-sample peak) detector for a single channel
+///
+/// Upsamples by 4× with a windowed-sinc polyphase interpolator, similar to
+/// ITU-R BS.1770 Annex 2, and reports the largest absolute value. Filter state
+/// is kept between calls so peaks spanning chunk boundaries are found.
+#[derive(Debug, Clone)]
+pub struct TruePeakDetector {
+    /// Coefficients per phase, `phases[p][k]` applies to x[n - k]
+    phases: Vec<Vec<f64>>,
+    history: Vec<f64>,
+    pos: usize,
+}
+
+impl TruePeakDetector {
+    pub fn new() -> Self {
+        let l = TRUE_PEAK_OVERSAMPLING;
+        let n = l * TRUE_PEAK_TAPS_PER_PHASE;
+        let center = (n - 1) as f64 / 2.0;
+
+        let h: Vec<f64> = (0..n)
+            .map(|i| {
+                let t = (i as f64 - center) / l as f64;
+                let sinc = if t.abs() < 1e-12 {
+                    1.0
+                } else {
+                    (std::f64::consts::PI * t).sin() / (std::f64::consts::PI * t)
+                };
+                // Blackman window
+                let w = 0.42 - 0.5 * (2.0 * std::f64::consts::PI * i as f64 / (n - 1) as f64).cos()
+                    + 0.08 * (4.0 * std::f64::consts::PI * i as f64 / (n - 1) as f64).cos();
+                sinc * w
+            })
+            .collect();
+
+        let phases = (0..l)
+            .map(|p| {
+                let coeffs: Vec<f64> = (0..TRUE_PEAK_TAPS_PER_PHASE).map(|k| h[p + k * l]).collect();
+                // Unity gain per phase so DC passes unchanged
+                let sum: f64 = coeffs.iter().sum();
+                coeffs.iter().map(|c| c / sum).collect()
+            })
+            .collect();
+
+        TruePeakDetector {
+            phases,
+            history: vec![0.0; TRUE_PEAK_TAPS_PER_PHASE],
+            pos: 0,
+        }
+    }
+
+    /// Process samples and return the true peak relative to `reference` (linear)
+    pub fn process(&mut self, samples: &[i32], reference: f64) -> f64 {
+        let taps = TRUE_PEAK_TAPS_PER_PHASE;
+        let mut peak = 0.0_f64;
+
+        for &sample in samples {
+            let x = sample as f64 / reference;
+            self.history[self.pos] = x;
+            peak = peak.max(x.abs());
+
+            for coeffs in &self.phases {
+                let mut acc = 0.0;
+                for (k, c) in coeffs.iter().enumerate() {
+                    acc += c * self.history[(self.pos + taps - k) % taps];
+                }
+                peak = peak.max(acc.abs());
+            }
+
+            self.pos = (self.pos + 1) % taps;
+        }
+
+        peak
+    }
+
+    pub fn reset(&mut self) {
+        self.history.iter_mut().for_each(|x| *x = 0.0);
+        self.pos = 0;
+    }
+}
+
+impl Default for TruePeakDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Convert a linear true-peak value (1.0 = full scale) to dBTP
+///
+/// Unlike sample peaks, true peaks may exceed 0 dBTP, so only a floor is applied.
+pub fn true_peak_to_db(true_peak: f64, min_db: f64) -> f64 {
+    if true_peak <= 0.0 {
+        return min_db;
+    }
+    (20.0 * true_peak.log10()).max(min_db)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -254,6 +552,22 @@ mod tests {
         assert!(!detect_clipping(&below_threshold, threshold));
     }
 
+    #[test]
+    fn test_over_counter() {
+        let mut counter = OverCounter::new(30000, 3);
+
+        // Two full-scale samples are a peak, not an over
+        assert_eq!(counter.process(&[1000, 32767, 32767, 1000]), 0);
+
+        // Positive and negative runs are counted once each
+        assert_eq!(counter.process(&[32767, 32767, 32767, 32767, 0, -32768, -32768, -32768]), 2);
+
+        // A run continuing into the next chunk is not counted again,
+        // one completed across the boundary is
+        assert_eq!(counter.process(&[-32768, 0, 32767, 32767]), 0);
+        assert_eq!(counter.process(&[32767, 0]), 1);
+    }
+
     #[test]
     fn test_clipping_threshold() {
         // Test S16 format (max 32768)
@@ -304,4 +618,77 @@ mod tests {
         assert_eq!(calculate_peak_db(&empty, reference, min_db, max_db), min_db);
         assert!(!detect_clipping(&empty, 30000));
     }
+
+    #[test]
+    fn test_voltage_conversions() {
+        assert!(volts_to_dbu(DBU_REFERENCE_VOLTS).abs() < 1e-9);
+        assert!((dbu_to_volts(20.0) - DBU_REFERENCE_VOLTS * 10.0).abs() < 1e-9);
+        assert!(volts_to_dbv(1.0).abs() < 1e-9);
+        assert!((dbv_to_volts(-20.0) - 0.1).abs() < 1e-9);
+        assert!((dbu_to_dbv(0.0) - (-2.218)).abs() < 0.001);
+        assert!((dbv_to_dbu(dbu_to_dbv(4.0)) - 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_calibration() {
+        // 0 dBu tone reading -18 dBFS
+        let cal = Calibration::from_reference(-18.0, 0.0);
+        assert_eq!(cal.full_scale_dbu, 18.0);
+        assert_eq!(cal.dbfs_to_dbu(-18.0), 0.0);
+        assert_eq!(cal.dbu_to_dbfs(4.0), -14.0);
+        assert!((cal.dbv_to_dbfs(cal.dbfs_to_dbv(-6.0)) - (-6.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_level_scale() {
+        let cal = Calibration::new(18.0);
+        assert_eq!(LevelScale::dbfs().apply(-12.0), -12.0);
+
+        let dbu = LevelScale::new(LevelUnit::Dbu, Some(&cal)).unwrap();
+        assert_eq!(dbu.apply(-18.0), 0.0);
+        assert_eq!(dbu.suffix(), "dBu");
+
+        let dbv = LevelScale::new(LevelUnit::Dbv, Some(&cal)).unwrap();
+        assert!((dbv.apply(-18.0) - (-2.218)).abs() < 0.001);
+
+        assert!(LevelScale::new(LevelUnit::Dbu, None).is_err());
+        assert_eq!(LevelUnit::from_str("DBV"), Some(LevelUnit::Dbv));
+        assert_eq!(LevelUnit::from_str("volts"), None);
+    }
+
+    #[test]
+    fn test_true_peak_detects_intersample_overs() {
+        let reference = 32768.0;
+        let mut detector = TruePeakDetector::new();
+
+        // fs/4 sine with 45° phase: samples at ±0.707 of the real amplitude
+        let amplitude = 0.99 * reference;
+        let samples: Vec<i32> = (0..400)
+            .map(|n| {
+                let phase = std::f64::consts::PI / 2.0 * n as f64 + std::f64::consts::PI / 4.0;
+                (amplitude * phase.sin()) as i32
+            })
+            .collect();
+
+        let sample_peak = calculate_peak(&samples) / reference;
+        let true_peak = detector.process(&samples, reference);
+
+        assert!(sample_peak < 0.71);
+        assert!(true_peak > 0.95, "true peak {} should be near 0.99", true_peak);
+        assert!(true_peak < 1.05);
+        assert!(true_peak_to_db(true_peak, -90.0) > -0.5);
+    }
+
+    #[test]
+    fn test_true_peak_dc_and_silence() {
+        let mut detector = TruePeakDetector::new();
+        assert_eq!(detector.process(&[0; 100], 32768.0), 0.0);
+        assert_eq!(true_peak_to_db(0.0, -90.0), -90.0);
+
+        // Ignore the step response, then check steady-state DC
+        detector.reset();
+        detector.process(&[16384; 200], 32768.0);
+        let peak = detector.process(&[16384; 200], 32768.0);
+        assert!((peak - 0.5).abs() < 0.01);
+    }
 }
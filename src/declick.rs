@@ -0,0 +1,456 @@
+//! Click and pop removal for vinyl recordings.
+//!
+//! Clicks are short impulses that stand out from the high-frequency content
+//! around them. Two detectors are available:
+//! - `derivative`: second difference of the signal against a local noise
+//!   estimate (median absolute deviation)
+//! - `wavelet`: undecimated Haar detail coefficients of the two finest
+//!   scales; a sample is only flagged if both scales exceed the threshold,
+//!   which spares bright but band-limited music such as cymbals
+//!
+//! Detected clicks are replaced by a cubic interpolation from the samples
+//! around them. Events longer than the maximum click length are left alone,
+//! as they are more likely music than damage.
+//!
+//! Files are processed in blocks with overlapping context, so memory use
+//! does not depend on the length of the recording.
+
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::cuefile::wav_base_path;
+use crate::error::{Error, Result};
+use crate::wavfile::{read_wav_header, WavSampleFormat, WavWriter, UNKNOWN_DATA_SIZE};
+
+/// Frames per processing block
+const BLOCK_FRAMES: usize = 1 << 18;
+
+/// Context read before and after each block so clicks at block
+/// boundaries are detected and repaired the same way in both blocks
+const CONTEXT_FRAMES: usize = 4096;
+
+/// Samples per window of the local noise estimate
+const NOISE_WINDOW: usize = 2048;
+
+/// Flagged samples closer than this are merged into one click
+const MERGE_GAP: usize = 8;
+
+/// Noise floor of the local estimate (about -100 dBFS), so dither in
+/// digital silence is not taken for clicks
+const MIN_NOISE: f64 = 1e-5;
+
+/// Click detector
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeclickMethod {
+    Derivative,
+    Wavelet,
+}
+
+impl DeclickMethod {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "derivative" | "diff" => Some(DeclickMethod::Derivative),
+            "wavelet" => Some(DeclickMethod::Wavelet),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeclickMethod::Derivative => "derivative",
+            DeclickMethod::Wavelet => "wavelet",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DeclickSettings {
+    pub method: DeclickMethod,
+    /// Detection threshold as a multiple of the local noise level
+    pub threshold: f64,
+    /// Longest event treated as a click, in milliseconds
+    pub max_click_ms: f64,
+}
+
+impl Default for DeclickSettings {
+    fn default() -> Self {
+        DeclickSettings {
+            method: DeclickMethod::Derivative,
+            threshold: 8.0,
+            max_click_ms: 2.0,
+        }
+    }
+}
+
+/// A detected click, `start..end` in samples
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Click {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Click {
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.end == self.start
+    }
+}
+
+/// Result of declicking a file
+#[derive(Debug, Clone, Default)]
+pub struct DeclickReport {
+    /// Repaired clicks per channel
+    pub clicks_per_channel: Vec<usize>,
+    /// Events longer than the maximum click length that were left alone
+    pub skipped: usize,
+    /// Positions of the repaired clicks in seconds
+    pub positions: Vec<f64>,
+    pub duration: f64,
+}
+
+impl DeclickReport {
+    pub fn total_clicks(&self) -> usize {
+        self.clicks_per_channel.iter().sum()
+    }
+}
+
+/// Default output file: `<name>.declicked.wav` next to the input
+pub fn declicked_path(wav_file: &str) -> String {
+    format!("{}.declicked.wav", wav_base_path(wav_file).display())
+}
+
+/// Whether a file is a copy written by [`declicked_path`] rather than a recording
+pub fn is_declicked(path: &Path) -> bool {
+    path.to_str().is_some_and(|p| p.ends_with(".declicked.wav"))
+}
+
+/// Median absolute value of each window, scaled to a standard deviation
+fn local_noise(detail: &[f64]) -> Vec<f64> {
+    let mut scratch = Vec::with_capacity(NOISE_WINDOW);
+    detail
+        .chunks(NOISE_WINDOW)
+        .map(|window| {
+            scratch.clear();
+            scratch.extend(window.iter().map(|d| d.abs()));
+            let mid = scratch.len() / 2;
+            let (_, median, _) = scratch.select_nth_unstable_by(mid, |a, b| a.total_cmp(b));
+            (*median / 0.6745).max(MIN_NOISE)
+        })
+        .collect()
+}
+
+/// Samples whose detail coefficient exceeds `threshold` times the local noise
+fn exceeds(detail: &[f64], threshold: f64) -> Vec<bool> {
+    let noise = local_noise(detail);
+    detail
+        .iter()
+        .enumerate()
+        .map(|(n, d)| d.abs() > threshold * noise[n / NOISE_WINDOW])
+        .collect()
+}
+
+/// Flag samples that look like part of a click
+fn flag_samples(samples: &[f64], method: DeclickMethod, threshold: f64) -> Vec<bool> {
+    let n = samples.len();
+    match method {
+        DeclickMethod::Derivative => {
+            let detail: Vec<f64> = (0..n)
+                .map(|i| if i < 2 { 0.0 } else { samples[i] - 2.0 * samples[i - 1] + samples[i - 2] })
+                .collect();
+            exceeds(&detail, threshold)
+        }
+        DeclickMethod::Wavelet => {
+            let fine: Vec<f64> = (0..n)
+                .map(|i| if i < 1 { 0.0 } else { (samples[i] - samples[i - 1]) / std::f64::consts::SQRT_2 })
+                .collect();
+            let coarse: Vec<f64> = (0..n)
+                .map(|i| {
+                    if i < 3 {
+                        0.0
+                    } else {
+                        (samples[i] + samples[i - 1] - samples[i - 2] - samples[i - 3]) / 2.0
+                    }
+                })
+                .collect();
+            exceeds(&fine, threshold)
+                .into_iter()
+                .zip(exceeds(&coarse, threshold))
+                .map(|(a, b)| a && b)
+                .collect()
+        }
+    }
+}
+
+/// Find clicks in one channel of normalized samples
+///
+/// Returns the clicks to repair and the events that were too long.
+pub fn detect_clicks(samples: &[f64], max_click_len: usize, settings: &DeclickSettings) -> (Vec<Click>, Vec<Click>) {
+    let flags = flag_samples(samples, settings.method, settings.threshold);
+
+    // Group flagged samples; the detail filters look back up to two samples
+    let mut events: Vec<Click> = Vec::new();
+    for (i, _) in flags.iter().enumerate().filter(|(_, &f)| f) {
+        let start = i.saturating_sub(2);
+        let end = (i + 1).min(samples.len());
+        match events.last_mut() {
+            Some(last) if start <= last.end + MERGE_GAP => last.end = end,
+            _ => events.push(Click { start, end }),
+        }
+    }
+
+    events.into_iter().partition(|c| c.len() <= max_click_len)
+}
+
+/// Replace a click by a cubic through the two samples on either side
+pub fn repair_click(samples: &mut [f64], click: Click) {
+    let n = samples.len();
+    if click.is_empty() || click.end > n {
+        return;
+    }
+    if click.start < 2 || click.end + 2 > n {
+        // At the edges, hold the nearest good sample
+        let value = if click.start > 0 {
+            samples[click.start - 1]
+        } else if click.end < n {
+            samples[click.end]
+        } else {
+            0.0
+        };
+        samples[click.start..click.end].iter_mut().for_each(|s| *s = value);
+        return;
+    }
+
+    let xs = [
+        click.start as f64 - 2.0,
+        click.start as f64 - 1.0,
+        click.end as f64,
+        click.end as f64 + 1.0,
+    ];
+    let ys = [
+        samples[click.start - 2],
+        samples[click.start - 1],
+        samples[click.end],
+        samples[click.end + 1],
+    ];
+    for (i, sample) in samples[click.start..click.end].iter_mut().enumerate() {
+        let x = (click.start + i) as f64;
+        // Lagrange interpolation
+        let mut value = 0.0;
+        for j in 0..4 {
+            let mut term = ys[j];
+            for k in 0..4 {
+                if k != j {
+                    term *= (x - xs[k]) / (xs[j] - xs[k]);
+                }
+            }
+            value += term;
+        }
+        *sample = value;
+    }
+}
+
+fn decode_samples(bytes: &[u8], format: WavSampleFormat) -> Vec<f64> {
+    match format {
+        WavSampleFormat::Int16 => bytes
+            .chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]) as f64 / 32768.0)
+            .collect(),
+        WavSampleFormat::Int24 => bytes
+            .chunks_exact(3)
+            .map(|b| (i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8) as f64 / 8388608.0)
+            .collect(),
+        WavSampleFormat::Int32 => bytes
+            .chunks_exact(4)
+            .map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64 / 2147483648.0)
+            .collect(),
+        WavSampleFormat::Float32 => bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64)
+            .collect(),
+    }
+}
+
+fn encode_sample(value: f64, format: WavSampleFormat) -> i32 {
+    let full_scale = match format {
+        WavSampleFormat::Int16 => 32768.0,
+        WavSampleFormat::Int24 => 8388608.0,
+        // Float output goes through write_samples_f32
+        WavSampleFormat::Int32 | WavSampleFormat::Float32 => 2147483648.0,
+    };
+    (value * full_scale).round().clamp(-full_scale, full_scale - 1.0) as i32
+}
+
+/// Remove clicks from `input`, writing the result to `output`
+///
+/// With `output` set to None the file is only analyzed.
+pub fn declick_wav(input: &str, output: Option<&str>, settings: &DeclickSettings) -> Result<DeclickReport> {
+    let file = File::open(input)?;
+    let file_len = file.metadata()?.len();
+    let mut reader = BufReader::new(file);
+    let header = read_wav_header(&mut reader).map_err(Error::Decode)?;
+    let format = WavSampleFormat::from_bits(header.bits_per_sample)
+        .ok_or_else(|| Error::Decode(format!("Unsupported bit depth: {}", header.bits_per_sample)))?;
+    let channels = header.num_channels as usize;
+    if channels == 0 {
+        return Err(Error::Decode("WAV file has no channels".to_string()));
+    }
+
+    let data_start = reader.stream_position()?;
+    let data_size = match header.data_size {
+        0 | UNKNOWN_DATA_SIZE => file_len - data_start,
+        size => size.min(file_len - data_start),
+    };
+    let bytes_per_frame = format.bytes_per_sample() * channels;
+    let total_frames = (data_size / bytes_per_frame as u64) as usize;
+    let rate = header.sample_rate;
+    let max_click_len = ((settings.max_click_ms / 1000.0 * rate as f64) as usize).max(1);
+
+    if let Some(output) = output {
+        if Path::new(output) == Path::new(input) {
+            return Err(Error::Config("Output file must differ from the input file".to_string()));
+        }
+    }
+    let mut writer = match output {
+        Some(path) => Some(WavWriter::new_rf64(path, rate, header.num_channels, format)?),
+        None => None,
+    };
+
+    let mut report = DeclickReport {
+        clicks_per_channel: vec![0; channels],
+        duration: total_frames as f64 / rate as f64,
+        ..Default::default()
+    };
+
+    let mut buffer = Vec::new();
+    let mut block_start = 0;
+    while block_start < total_frames {
+        let block_end = (block_start + BLOCK_FRAMES).min(total_frames);
+        let read_start = block_start.saturating_sub(CONTEXT_FRAMES);
+        let read_end = (block_end + CONTEXT_FRAMES).min(total_frames);
+
+        buffer.resize((read_end - read_start) * bytes_per_frame, 0);
+        reader.seek(SeekFrom::Start(data_start + (read_start * bytes_per_frame) as u64))?;
+        reader.read_exact(&mut buffer)?;
+        let interleaved = decode_samples(&buffer, format);
+
+        let core = (block_start - read_start)..(block_end - read_start);
+        let mut output_channels = Vec::with_capacity(channels);
+        for ch in 0..channels {
+            let mut samples: Vec<f64> = interleaved.iter().skip(ch).step_by(channels).copied().collect();
+            let (clicks, skipped) = detect_clicks(&samples, max_click_len, settings);
+
+            // Repair everything touching this block, but count each event
+            // only in the block where it starts
+            for click in clicks.iter().filter(|c| c.end > core.start && c.start < core.end) {
+                repair_click(&mut samples, *click);
+                if core.contains(&click.start) {
+                    report.clicks_per_channel[ch] += 1;
+                    report.positions.push((read_start + click.start) as f64 / rate as f64);
+                }
+            }
+            report.skipped += skipped.iter().filter(|c| core.contains(&c.start)).count();
+            output_channels.push(samples);
+        }
+
+        if let Some(writer) = writer.as_mut() {
+            let frames = core.flat_map(|frame| output_channels.iter().map(move |s| s[frame]));
+            if format.is_float() {
+                let out: Vec<f32> = frames.map(|s| s as f32).collect();
+                writer.write_samples_f32(&out)?;
+            } else {
+                let out: Vec<i32> = frames.map(|s| encode_sample(s, format)).collect();
+                writer.write_samples(&out)?;
+            }
+        }
+
+        block_start = block_end;
+    }
+
+    if let Some(mut writer) = writer {
+        writer.finalize()?;
+    }
+    report.positions.sort_by(|a, b| a.total_cmp(b));
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(len: usize, rate: f64, freq: f64, amplitude: f64) -> Vec<f64> {
+        (0..len)
+            .map(|n| amplitude * (2.0 * std::f64::consts::PI * freq * n as f64 / rate).sin())
+            .collect()
+    }
+
+    #[test]
+    fn test_detect_and_repair_click() {
+        for method in [DeclickMethod::Derivative, DeclickMethod::Wavelet] {
+            let settings = DeclickSettings { method, ..Default::default() };
+            let clean = sine(8000, 44100.0, 440.0, 0.3);
+            let mut samples = clean.clone();
+            samples[3000] += 0.5;
+            samples[3001] -= 0.3;
+
+            let (clicks, skipped) = detect_clicks(&samples, 88, &settings);
+            assert_eq!(clicks.len(), 1, "{}", method.as_str());
+            assert!(skipped.is_empty());
+            assert!(clicks[0].start <= 3000 && clicks[0].end > 3001);
+
+            repair_click(&mut samples, clicks[0]);
+            let error = samples.iter().zip(&clean).map(|(a, b)| (a - b).abs()).fold(0.0, f64::max);
+            assert!(error < 0.01, "{}: error {}", method.as_str(), error);
+        }
+    }
+
+    #[test]
+    fn test_clean_signal_and_long_events() {
+        let settings = DeclickSettings::default();
+        let clean = sine(8000, 44100.0, 1000.0, 0.5);
+        assert!(detect_clicks(&clean, 88, &settings).0.is_empty());
+
+        // A burst longer than the maximum click length is left alone
+        let mut burst = clean.clone();
+        for (i, s) in burst[4000..4200].iter_mut().enumerate() {
+            *s += if i % 2 == 0 { 0.4 } else { -0.4 };
+        }
+        let (clicks, skipped) = detect_clicks(&burst, 88, &settings);
+        assert!(clicks.is_empty());
+        assert_eq!(skipped.len(), 1);
+    }
+
+    #[test]
+    fn test_declick_wav() {
+        let dir = std::env::temp_dir();
+        let input = dir.join("autorec_declick_test.wav");
+        let input_str = input.to_str().unwrap();
+        let output = declicked_path(input_str);
+        assert!(output.ends_with("autorec_declick_test.declicked.wav"));
+        assert!(is_declicked(Path::new(&output)));
+        assert!(!is_declicked(Path::new(input_str)));
+
+        let rate = 44100;
+        let mut left: Vec<i32> = sine(20000, rate as f64, 440.0, 0.3).iter().map(|s| (s * 32768.0) as i32).collect();
+        let right = left.clone();
+        left[12345] = 30000;
+        crate::wavfile::write_wav_samples(input_str, &[left, right], rate, 16).unwrap();
+
+        let report = declick_wav(input_str, None, &DeclickSettings::default()).unwrap();
+        assert_eq!(report.clicks_per_channel, vec![1, 0]);
+        assert!((report.positions[0] - 12345.0 / rate as f64).abs() < 0.001);
+
+        let report = declick_wav(input_str, Some(&output), &DeclickSettings::default()).unwrap();
+        assert_eq!(report.total_clicks(), 1);
+        let again = declick_wav(&output, None, &DeclickSettings::default()).unwrap();
+        assert_eq!(again.total_clicks(), 0);
+
+        assert!(declick_wav(input_str, Some(input_str), &DeclickSettings::default()).is_err());
+
+        let _ = std::fs::remove_file(&input);
+        let _ = std::fs::remove_file(&output);
+    }
+}
@@ -0,0 +1,209 @@
+//! Builder for tuned pause detectors.
+//!
+//! Collects the parameters shared by all detectors (minimum pause duration,
+//! threshold mode, sensitivity, debounce) so callers don't have to remember
+//! positional constructor arguments.
+//!
+//! ```
+//! use autorec::detection_strategies::builder::{DetectorBuilder, ThresholdMode};
+//!
+//! let detector = DetectorBuilder::new(96000)
+//!     .threshold_mode(ThresholdMode::RelativeDrop { drop_db: 15.0 })
+//!     .min_pause_duration(300)
+//!     .debounce(30_000)
+//!     .build();
+//! assert_eq!(detector.name(), "Relative Drop");
+//! ```
+
+use super::absolute_threshold::AbsoluteThresholdDetector;
+use super::energy_ratio::EnergyRatioDetector;
+use super::relative_drop::RelativeDropDetector;
+use super::transition::TransitionDetector;
+use super::PauseDetectionStrategy;
+use crate::pause_detector::AdaptivePauseDetector;
+
+/// Default minimum pause duration in milliseconds
+pub const DEFAULT_MIN_PAUSE_MS: u32 = 200;
+
+/// Default threshold for absolute detection in dBFS
+pub const DEFAULT_THRESHOLD_DB: f32 = -50.0;
+
+/// Default history window for relative strategies in seconds
+pub const DEFAULT_WINDOW_SECONDS: f32 = 10.0;
+
+/// How a detector decides that the signal is quiet
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThresholdMode {
+    /// RMS below a fixed level in dBFS
+    Absolute { threshold_db: f32 },
+    /// RMS this many dB below the recent average
+    RelativeDrop { drop_db: f32 },
+    /// Energy below this fraction of the recent maximum (e.g. 0.01 = 1%)
+    EnergyRatio { ratio: f32 },
+    /// Quiet period in the bottom `percentile` followed by a rise of `rise_db`
+    Transition { percentile: f32, rise_db: f32 },
+}
+
+impl Default for ThresholdMode {
+    fn default() -> Self {
+        ThresholdMode::Absolute { threshold_db: DEFAULT_THRESHOLD_DB }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DetectorBuilder {
+    sample_rate: u32,
+    min_pause_duration_ms: Option<u32>,
+    threshold_mode: Option<ThresholdMode>,
+    sensitivity_db: f32,
+    debounce_ms: u32,
+    window_seconds: f32,
+}
+
+impl DetectorBuilder {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            min_pause_duration_ms: None,
+            threshold_mode: None,
+            sensitivity_db: 0.0,
+            debounce_ms: 0,
+            window_seconds: DEFAULT_WINDOW_SECONDS,
+        }
+    }
+
+    /// Minimum duration of a quiet period that counts as a pause
+    pub fn min_pause_duration(mut self, ms: u32) -> Self {
+        self.min_pause_duration_ms = Some(ms);
+        self
+    }
+
+    pub fn threshold_mode(mut self, mode: ThresholdMode) -> Self {
+        self.threshold_mode = Some(mode);
+        self
+    }
+
+    /// Shift the threshold by this many dB; positive values detect more pauses
+    pub fn sensitivity(mut self, sensitivity_db: f32) -> Self {
+        self.sensitivity_db = sensitivity_db;
+        self
+    }
+
+    /// Set the debounce, see [`StrategyParams::debounce_ms`](super::factory::StrategyParams::debounce_ms)
+    pub fn debounce(mut self, ms: u32) -> Self {
+        self.debounce_ms = ms;
+        self
+    }
+
+    /// History window for the relative strategies
+    pub fn window(mut self, seconds: f32) -> Self {
+        self.window_seconds = seconds;
+        self
+    }
+
+    /// Threshold mode with sensitivity applied
+    fn effective_mode(&self) -> ThresholdMode {
+        let sens = self.sensitivity_db;
+        match self.threshold_mode.unwrap_or_default() {
+            ThresholdMode::Absolute { threshold_db } => ThresholdMode::Absolute {
+                threshold_db: threshold_db + sens,
+            },
+            ThresholdMode::RelativeDrop { drop_db } => ThresholdMode::RelativeDrop {
+                drop_db: (drop_db - sens).max(0.0),
+            },
+            ThresholdMode::EnergyRatio { ratio } => ThresholdMode::EnergyRatio {
+                ratio: ratio * 10f32.powf(sens / 10.0),
+            },
+            ThresholdMode::Transition { percentile, rise_db } => ThresholdMode::Transition {
+                percentile,
+                rise_db: (rise_db - sens).max(0.0),
+            },
+        }
+    }
+
+    /// Build a fixed-parameter detection strategy
+    pub fn build(&self) -> Box<dyn PauseDetectionStrategy> {
+        let rate = self.sample_rate;
+        let pause_ms = self.min_pause_duration_ms.unwrap_or(DEFAULT_MIN_PAUSE_MS);
+        let window = self.window_seconds;
+
+        match self.effective_mode() {
+            ThresholdMode::Absolute { threshold_db } => Box::new(
+                AbsoluteThresholdDetector::new(rate, threshold_db, pause_ms).with_debounce(self.debounce_ms),
+            ),
+            ThresholdMode::RelativeDrop { drop_db } => Box::new(
+                RelativeDropDetector::new(rate, drop_db, pause_ms, window).with_debounce(self.debounce_ms),
+            ),
+            ThresholdMode::EnergyRatio { ratio } => Box::new(
+                EnergyRatioDetector::new(rate, ratio, pause_ms, window).with_debounce(self.debounce_ms),
+            ),
+            ThresholdMode::Transition { percentile, rise_db } => Box::new(
+                TransitionDetector::new(rate, percentile, rise_db, pause_ms, window).with_debounce(self.debounce_ms),
+            ),
+        }
+    }
+
+    /// Build an adaptive detector that learns its threshold from the groove-in
+    ///
+    /// An explicit absolute threshold or pause duration disables the adaptation
+    /// of that parameter. Other threshold modes are ignored.
+    pub fn build_adaptive(&self) -> AdaptivePauseDetector {
+        let mut detector = AdaptivePauseDetector::new(self.sample_rate);
+
+        if let Some(ThresholdMode::Absolute { threshold_db }) = self.threshold_mode {
+            detector.set_threshold_override(threshold_db + self.sensitivity_db);
+        } else {
+            detector.set_sensitivity(self.sensitivity_db);
+        }
+        if let Some(ms) = self.min_pause_duration_ms {
+            detector.set_pause_duration_override(ms);
+        }
+        detector.set_debounce(self.debounce_ms);
+
+        detector
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_builds_absolute() {
+        let detector = DetectorBuilder::new(48000).build();
+        assert_eq!(detector.name(), "Absolute Threshold");
+        assert_eq!(detector.get_debug_info().threshold, DEFAULT_THRESHOLD_DB);
+    }
+
+    #[test]
+    fn test_sensitivity_shifts_threshold() {
+        let builder = DetectorBuilder::new(48000)
+            .threshold_mode(ThresholdMode::Absolute { threshold_db: -45.0 })
+            .sensitivity(5.0);
+        assert_eq!(builder.effective_mode(), ThresholdMode::Absolute { threshold_db: -40.0 });
+
+        let builder = DetectorBuilder::new(48000)
+            .threshold_mode(ThresholdMode::RelativeDrop { drop_db: 15.0 })
+            .sensitivity(5.0);
+        assert_eq!(builder.effective_mode(), ThresholdMode::RelativeDrop { drop_db: 10.0 });
+
+        let builder = DetectorBuilder::new(48000)
+            .threshold_mode(ThresholdMode::EnergyRatio { ratio: 0.01 })
+            .sensitivity(10.0);
+        match builder.effective_mode() {
+            ThresholdMode::EnergyRatio { ratio } => assert!((ratio - 0.1).abs() < 1e-6),
+            other => panic!("unexpected mode {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_build_adaptive_overrides() {
+        let detector = DetectorBuilder::new(48000)
+            .threshold_mode(ThresholdMode::Absolute { threshold_db: -42.0 })
+            .min_pause_duration(800)
+            .build_adaptive();
+        let info = detector.get_debug_info();
+        assert_eq!(info.threshold_db, -42.0);
+        assert_eq!(info.pause_duration_ms, 800);
+    }
+}
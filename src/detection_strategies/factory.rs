@@ -0,0 +1,212 @@
+//! Create a pause detection strategy by name.
+//!
+//! Used by the binaries to turn `--pause-strategy <name>` and the tuning
+//! flags into a detector. Parameters that are not set use the defaults of
+//! the strategy.
+//!
+//! ```
+//! use autorec::detection_strategies::{self, StrategyParams};
+//!
+//! let mut params = StrategyParams::new(48000);
+//! params.drop_db = Some(20.0);
+//! let detector = detection_strategies::create("relative", &params).unwrap();
+//! assert_eq!(detector.name(), "Relative Drop");
+//! ```
+
+use super::builder::{DetectorBuilder, ThresholdMode, DEFAULT_THRESHOLD_DB};
+use super::guided::GuidedDetector;
+use super::PauseDetectionStrategy;
+use crate::error::{Error, Result};
+use crate::musicbrainz::ExpectedTrack;
+
+/// Names accepted by [`create`]
+pub const STRATEGY_NAMES: [&str; 5] = ["absolute", "relative", "energy", "transition", "guided"];
+
+/// Strategies that need no expected track lengths, e.g. for `autorecd`
+pub const UNGUIDED_STRATEGY_NAMES: [&str; 4] = ["absolute", "relative", "energy", "transition"];
+
+/// Default drop below the recent average for `relative`, in dB
+pub const DEFAULT_DROP_DB: f32 = 15.0;
+/// Default fraction of the recent maximum energy for `energy`
+pub const DEFAULT_ENERGY_RATIO: f32 = 0.01;
+/// Default quiet percentile for `transition`
+pub const DEFAULT_PERCENTILE: f32 = 0.20;
+/// Default level rise after a quiet period for `transition`, in dB
+pub const DEFAULT_RISE_DB: f32 = 10.0;
+/// `transition` looks at longer stretches than the other strategies
+const TRANSITION_MIN_PAUSE_MS: u32 = 500;
+const TRANSITION_WINDOW_SECONDS: f32 = 30.0;
+/// Default search window around each expected boundary for `guided`, in seconds
+pub const DEFAULT_GUIDE_WINDOW_SECONDS: f32 = 10.0;
+
+/// Tuning parameters for [`create`]; each strategy uses the ones that apply to it
+#[derive(Debug, Clone)]
+pub struct StrategyParams {
+    pub sample_rate: u32,
+    /// Minimum duration of a pause in ms
+    pub min_pause_ms: Option<u32>,
+    /// `absolute`: threshold in dBFS
+    pub threshold_db: Option<f32>,
+    /// `relative`: drop below the recent average in dB
+    pub drop_db: Option<f32>,
+    /// `energy`: fraction of the recent maximum energy
+    pub ratio: Option<f32>,
+    /// `transition`: quiet percentile (0.0-1.0)
+    pub percentile: Option<f32>,
+    /// `transition`: rise after the quiet period in dB
+    pub rise_db: Option<f32>,
+    /// History window of `relative`, `energy` and `transition`, or the
+    /// search window of `guided`, in seconds
+    pub window_seconds: Option<f32>,
+    /// Shift of the threshold in dB; positive values detect more pauses
+    pub sensitivity_db: f32,
+    /// Ignore boundaries less than this many ms after the previous one, so
+    /// a quiet passage early in a song does not split it again (0: off)
+    pub debounce_ms: u32,
+    /// `guided`: the tracks expected on the side
+    pub expected_tracks: Vec<ExpectedTrack>,
+}
+
+impl StrategyParams {
+    pub fn new(sample_rate: u32) -> Self {
+        StrategyParams {
+            sample_rate,
+            min_pause_ms: None,
+            threshold_db: None,
+            drop_db: None,
+            ratio: None,
+            percentile: None,
+            rise_db: None,
+            window_seconds: None,
+            sensitivity_db: 0.0,
+            debounce_ms: 0,
+            expected_tracks: Vec::new(),
+        }
+    }
+
+    /// Expected tracks from a list of durations in seconds, for `guided`
+    pub fn set_track_durations(&mut self, durations: &[f64]) {
+        let mut start = 0.0;
+        self.expected_tracks = durations
+            .iter()
+            .enumerate()
+            .map(|(i, &length)| {
+                let track = ExpectedTrack {
+                    position: i as u32 + 1,
+                    title: format!("Track {}", i + 1),
+                    length_seconds: length,
+                    expected_start: start,
+                };
+                start += length;
+                track
+            })
+            .collect();
+    }
+}
+
+/// Parse a comma-separated list of track lengths (`3:45,4:10` or seconds)
+pub fn parse_track_durations(s: &str) -> Option<Vec<f64>> {
+    s.split(',')
+        .map(|item| {
+            let item = item.trim();
+            let seconds = match item.split_once(':') {
+                Some((m, s)) => m.parse::<u32>().ok()? as f64 * 60.0 + s.parse::<f64>().ok()?,
+                None => item.parse::<f64>().ok()?,
+            };
+            Some(seconds).filter(|s| *s > 0.0)
+        })
+        .collect()
+}
+
+/// Create the strategy called `name` (one of [`STRATEGY_NAMES`])
+pub fn create(name: &str, params: &StrategyParams) -> Result<Box<dyn PauseDetectionStrategy>> {
+    let mode = match name.to_lowercase().as_str() {
+        "absolute" => ThresholdMode::Absolute {
+            threshold_db: params.threshold_db.unwrap_or(DEFAULT_THRESHOLD_DB),
+        },
+        "relative" => ThresholdMode::RelativeDrop {
+            drop_db: params.drop_db.unwrap_or(DEFAULT_DROP_DB),
+        },
+        "energy" => ThresholdMode::EnergyRatio {
+            ratio: params.ratio.unwrap_or(DEFAULT_ENERGY_RATIO),
+        },
+        "transition" => ThresholdMode::Transition {
+            percentile: params.percentile.unwrap_or(DEFAULT_PERCENTILE),
+            rise_db: params.rise_db.unwrap_or(DEFAULT_RISE_DB),
+        },
+        "guided" => {
+            if params.expected_tracks.len() < 2 {
+                return Err(Error::Config(
+                    "The guided strategy needs at least two expected tracks".to_string(),
+                ));
+            }
+            let window = params.window_seconds.unwrap_or(DEFAULT_GUIDE_WINDOW_SECONDS);
+            return Ok(Box::new(GuidedDetector::new(
+                params.sample_rate,
+                params.expected_tracks.clone(),
+                window as f64,
+            )));
+        }
+        _ => {
+            return Err(Error::Config(format!(
+                "Unknown pause strategy '{}' (use {})",
+                name,
+                STRATEGY_NAMES.join(", ")
+            )))
+        }
+    };
+
+    let is_transition = matches!(mode, ThresholdMode::Transition { .. });
+    let mut builder = DetectorBuilder::new(params.sample_rate)
+        .threshold_mode(mode)
+        .sensitivity(params.sensitivity_db)
+        .debounce(params.debounce_ms);
+    match params.min_pause_ms {
+        Some(ms) => builder = builder.min_pause_duration(ms),
+        None if is_transition => builder = builder.min_pause_duration(TRANSITION_MIN_PAUSE_MS),
+        None => {}
+    }
+    match params.window_seconds {
+        Some(seconds) => builder = builder.window(seconds),
+        None if is_transition => builder = builder.window(TRANSITION_WINDOW_SECONDS),
+        None => {}
+    }
+    Ok(builder.build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_by_name() {
+        let params = StrategyParams::new(48000);
+        let names: Vec<String> = UNGUIDED_STRATEGY_NAMES
+            .iter()
+            .map(|n| create(n, &params).unwrap().name().to_string())
+            .collect();
+        assert_eq!(names[0], "Absolute Threshold");
+        assert_eq!(names[1], "Relative Drop");
+        assert!(create("Energy", &params).is_ok());
+        assert!(create("spectral", &params).is_err());
+    }
+
+    #[test]
+    fn test_create_with_params() {
+        let mut params = StrategyParams::new(48000);
+        params.threshold_db = Some(-42.0);
+        params.sensitivity_db = 2.0;
+        let detector = create("absolute", &params).unwrap();
+        assert_eq!(detector.get_debug_info().threshold, -40.0);
+
+        // Guided needs the expected tracks
+        assert!(create("guided", &params).is_err());
+        let durations = parse_track_durations("3:00, 240,3:20").unwrap();
+        assert_eq!(durations, vec![180.0, 240.0, 200.0]);
+        assert!(parse_track_durations("3:00,x").is_none());
+        params.set_track_durations(&durations);
+        assert_eq!(params.expected_tracks[2].expected_start, 420.0);
+        let detector = create("guided", &params).unwrap();
+        assert_eq!(detector.song_number(), 1);
+    }
+}
@@ -6,6 +6,7 @@ use crate::musicbrainz::ExpectedTrack;
 use crate::SampleFormat;
 use std::collections::VecDeque;
 use std::time::Instant;
+use tracing::debug;
 
 pub struct GuidedDetector {
     sample_rate: u32,
@@ -61,10 +62,7 @@ impl GuidedDetector {
             return -80.0;
         }
         
-        let max_value = match format {
-            SampleFormat::S16 => 32768.0_f32,
-            SampleFormat::S32 => 2147483648.0_f32,
-        };
+        let max_value = format.max_value() as f32;
         
         let mut sum_squares = 0.0_f64;
         for i in 0..num_samples {
@@ -124,8 +122,8 @@ impl PauseDetectionStrategy for GuidedDetector {
                     self.min_rms_in_window = self.current_rms_db;
                     self.min_rms_position = self.current_position_seconds;
                     
-                    eprintln!("Searching for boundary #{} around {:.1}s (±{:.1}s)", 
-                             self.next_boundary_index, expected_boundary, self.search_window_seconds);
+                    debug!("Searching for boundary #{} around {:.1}s (±{:.1}s)",
+                           self.next_boundary_index, expected_boundary, self.search_window_seconds);
                 }
             }
         }
@@ -145,8 +143,8 @@ impl PauseDetectionStrategy for GuidedDetector {
                 self.next_boundary_index += 1;
                 self.in_search_window = false;
                 
-                eprintln!("Boundary detected at {:.2}s (RMS: {:.1}dB)", 
-                         self.min_rms_position, self.min_rms_in_window);
+                debug!("Boundary detected at {:.2}s (RMS: {:.1}dB)",
+                       self.min_rms_position, self.min_rms_in_window);
                 
                 self.current_position_seconds += chunk_duration;
                 return Some(PauseEvent::SongBoundary);
@@ -5,12 +5,19 @@
 //! - Relative drop detection
 //! - Energy ratio detection
 //! - Spectral change detection
+//!
+//! [`create`] picks a strategy by name, for command-line selection.
 
 pub mod absolute_threshold;
 pub mod relative_drop;
 pub mod energy_ratio;
 pub mod transition;
 pub mod guided;
+pub mod builder;
+pub mod factory;
+
+pub use builder::{DetectorBuilder, ThresholdMode};
+pub use factory::{create, parse_track_durations, StrategyParams, STRATEGY_NAMES, UNGUIDED_STRATEGY_NAMES};
 
 use crate::SampleFormat;
 
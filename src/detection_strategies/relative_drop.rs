@@ -20,6 +20,7 @@ pub struct RelativeDropDetector {
     pause_start: Option<Instant>,
     song_count: u32,
     current_song_start: Instant,
+    debounce_ms: u32,
 }
 
 impl RelativeDropDetector {
@@ -39,9 +40,16 @@ impl RelativeDropDetector {
             pause_start: None,
             song_count: 1,
             current_song_start: Instant::now(),
+            debounce_ms: 0,
         }
     }
     
+    /// Set the debounce, see [`StrategyParams::debounce_ms`](super::factory::StrategyParams::debounce_ms)
+    pub fn with_debounce(mut self, debounce_ms: u32) -> Self {
+        self.debounce_ms = debounce_ms;
+        self
+    }
+    
     fn calculate_rms_db(&self, audio: &[Vec<i32>], format: SampleFormat) -> f32 {
         let num_channels = audio.len();
         let num_samples = audio[0].len();
@@ -50,10 +58,7 @@ impl RelativeDropDetector {
             return -80.0;
         }
         
-        let max_value = match format {
-            SampleFormat::S16 => 32768.0_f32,
-            SampleFormat::S32 => 2147483648.0_f32,
-        };
+        let max_value = format.max_value() as f32;
         
         let mut sum_squares = 0.0_f64;
         for i in 0..num_samples {
@@ -117,7 +122,9 @@ impl PauseDetectionStrategy for RelativeDropDetector {
                 if let Some(start) = self.pause_start {
                     let pause_duration_ms = start.elapsed().as_millis() as u32;
                     
-                    if pause_duration_ms >= self.pause_duration_ms {
+                    if pause_duration_ms >= self.pause_duration_ms
+                        && self.current_song_start.elapsed().as_millis() >= self.debounce_ms as u128
+                    {
                         self.song_count += 1;
                         self.current_song_start = Instant::now();
                         
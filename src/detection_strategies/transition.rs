@@ -22,6 +22,7 @@ pub struct TransitionDetector {
     quiet_start_rms: f32,
     song_count: u32,
     current_song_start: Instant,
+    debounce_ms: u32,
 }
 
 impl TransitionDetector {
@@ -49,9 +50,16 @@ impl TransitionDetector {
             quiet_start_rms: -80.0,
             song_count: 1,
             current_song_start: Instant::now(),
+            debounce_ms: 0,
         }
     }
     
+    /// Set the debounce, see [`StrategyParams::debounce_ms`](super::factory::StrategyParams::debounce_ms)
+    pub fn with_debounce(mut self, debounce_ms: u32) -> Self {
+        self.debounce_ms = debounce_ms;
+        self
+    }
+    
     fn calculate_rms_db(&self, audio: &[Vec<i32>], format: SampleFormat) -> f32 {
         let num_channels = audio.len();
         let num_samples = audio[0].len();
@@ -60,10 +68,7 @@ impl TransitionDetector {
             return -80.0;
         }
         
-        let max_value = match format {
-            SampleFormat::S16 => 32768.0_f32,
-            SampleFormat::S32 => 2147483648.0_f32,
-        };
+        let max_value = format.max_value() as f32;
         
         let mut sum_squares = 0.0_f64;
         for i in 0..num_samples {
@@ -135,7 +140,9 @@ impl PauseDetectionStrategy for TransitionDetector {
                     // Was quiet long enough AND did RMS jump significantly?
                     let rms_jump = self.current_rms_db - self.quiet_start_rms;
                     
-                    if quiet_duration_ms >= self.min_quiet_duration_ms && rms_jump >= self.rise_threshold_db {
+                    if quiet_duration_ms >= self.min_quiet_duration_ms && rms_jump >= self.rise_threshold_db
+                        && self.current_song_start.elapsed().as_millis() >= self.debounce_ms as u128
+                    {
                         // Song boundary detected!
                         self.song_count += 1;
                         self.current_song_start = Instant::now();
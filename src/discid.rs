@@ -0,0 +1,143 @@
+//! CD-style table of contents and MusicBrainz Disc ID for a detected track layout.
+//!
+//! A vinyl side has no real TOC, but the detected track starts and the total
+//! length can be expressed as one: offsets in CD sectors (1/75 s) with the
+//! standard 2 second lead-in. MusicBrainz uses such a TOC for a fuzzy lookup
+//! by track lengths, which for a well-detected side usually finds the exact
+//! release.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use sha1_smol::Sha1;
+
+/// CD sectors (frames) per second
+pub const SECTORS_PER_SECOND: f64 = 75.0;
+
+/// Offset of the first track on a CD (2 second pre-gap)
+pub const LEAD_IN_SECTORS: u32 = 150;
+
+/// Maximum number of tracks in a TOC
+pub const MAX_TRACKS: usize = 99;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Toc {
+    pub first_track: u32,
+    pub last_track: u32,
+    /// Lead-out offset in sectors (end of the last track)
+    pub lead_out: u32,
+    /// Start offset of each track in sectors
+    pub offsets: Vec<u32>,
+}
+
+impl Toc {
+    /// Build a TOC from track starts relative to the first track (seconds)
+    /// and the total length of all tracks (seconds).
+    pub fn from_track_starts(track_starts: &[f64], total_length: f64) -> Result<Self, String> {
+        if track_starts.is_empty() {
+            return Err("No tracks".to_string());
+        }
+        if track_starts.len() > MAX_TRACKS {
+            return Err(format!("Too many tracks for a TOC ({} > {})", track_starts.len(), MAX_TRACKS));
+        }
+
+        let to_sectors = |seconds: f64| LEAD_IN_SECTORS + (seconds.max(0.0) * SECTORS_PER_SECOND).round() as u32;
+        let offsets: Vec<u32> = track_starts.iter().map(|&t| to_sectors(t)).collect();
+        let lead_out = to_sectors(total_length);
+
+        if offsets.windows(2).any(|w| w[1] <= w[0]) {
+            return Err("Track starts are not in ascending order".to_string());
+        }
+        if lead_out <= *offsets.last().unwrap() {
+            return Err("Total length ends before the last track starts".to_string());
+        }
+
+        Ok(Toc {
+            first_track: 1,
+            last_track: offsets.len() as u32,
+            lead_out,
+            offsets,
+        })
+    }
+
+    /// Build a TOC from the boundaries between tracks (absolute positions in
+    /// the recording) and the music region `start..end`.
+    pub fn from_boundaries(start: f64, boundaries: &[f64], end: f64) -> Result<Self, String> {
+        let starts: Vec<f64> = std::iter::once(0.0)
+            .chain(boundaries.iter().map(|b| b - start))
+            .collect();
+        Self::from_track_starts(&starts, end - start)
+    }
+
+    /// MusicBrainz Disc ID (SHA-1 of the TOC, base64 with `._-` substitutions)
+    pub fn disc_id(&self) -> String {
+        let mut hasher = Sha1::new();
+        hasher.update(format!("{:02X}", self.first_track).as_bytes());
+        hasher.update(format!("{:02X}", self.last_track).as_bytes());
+        hasher.update(format!("{:08X}", self.lead_out).as_bytes());
+        for i in 0..MAX_TRACKS {
+            let offset = self.offsets.get(i).copied().unwrap_or(0);
+            hasher.update(format!("{:08X}", offset).as_bytes());
+        }
+
+        STANDARD
+            .encode(hasher.digest().bytes())
+            .replace('+', ".")
+            .replace('/', "_")
+            .replace('=', "-")
+    }
+
+    /// TOC in the `toc` query format used by the MusicBrainz web service:
+    /// first track, last track, lead-out, track offsets
+    pub fn toc_string(&self) -> String {
+        let mut parts = vec![
+            self.first_track.to_string(),
+            self.last_track.to_string(),
+            self.lead_out.to_string(),
+        ];
+        parts.extend(self.offsets.iter().map(|o| o.to_string()));
+        parts.join("+")
+    }
+
+    /// Track lengths in seconds
+    pub fn track_lengths(&self) -> Vec<f64> {
+        self.offsets
+            .iter()
+            .zip(self.offsets.iter().skip(1).chain(std::iter::once(&self.lead_out)))
+            .map(|(start, end)| (end - start) as f64 / SECTORS_PER_SECOND)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disc_id_reference() {
+        // 12-track TOC with its known Disc ID
+        let toc = Toc {
+            first_track: 1,
+            last_track: 12,
+            lead_out: 267257,
+            offsets: vec![150, 22767, 41887, 58317, 72102, 91375, 104652, 115380, 132165, 143932, 159870, 174597],
+        };
+        assert_eq!(toc.disc_id(), "I5l9cCSFccLKFEKS.7wqSZAorPU-");
+    }
+
+    #[test]
+    fn test_toc_from_boundaries() {
+        let toc = Toc::from_boundaries(10.0, &[190.0, 400.0], 610.0).unwrap();
+        assert_eq!(toc.offsets, vec![150, 150 + 180 * 75, 150 + 390 * 75]);
+        assert_eq!(toc.lead_out, 150 + 600 * 75);
+        assert_eq!(toc.last_track, 3);
+        assert_eq!(toc.toc_string(), "1+3+45150+150+13650+29400");
+        assert_eq!(toc.track_lengths(), vec![180.0, 210.0, 210.0]);
+    }
+
+    #[test]
+    fn test_toc_rejects_invalid_layout() {
+        assert!(Toc::from_track_starts(&[], 100.0).is_err());
+        assert!(Toc::from_track_starts(&[0.0, 50.0, 40.0], 100.0).is_err());
+        assert!(Toc::from_track_starts(&[0.0, 50.0], 40.0).is_err());
+    }
+}
@@ -4,70 +4,144 @@
 //! The key advantage over MusicBrainz is that Discogs track positions contain
 //! explicit side letters (A1, A2, B1, B2, C1, …).
 //!
-//! Authentication: uses Discogs key+secret (from discogs_credentials.toml or
-//! `/etc/autorec/discogs_credentials.toml`).  Without credentials the API allows
-//! 25 req/min; with credentials 60 req/min.
+//! Authentication: a personal access token, or else an application's
+//! consumer key+secret, with the OAuth 1.0a token of a user if one is set.
+//! The `[discogs]` config section and `AUTOREC_DISCOGS__*` variables take
+//! precedence over the first `discogs_credentials.toml` found in the working
+//! directory, `/etc/autorec/` and `~/.config/autorec/`.  Without credentials
+//! the API allows 25 req/min and no search; with credentials 60 req/min.
+
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use serde::Deserialize;
-use std::error::Error;
+use tracing::{info, warn};
+use crate::config::{Config, DiscogsConfig};
+use crate::error::{Error, Result};
+use crate::http;
 
 use crate::album_identifier::IdentifiedSong;
 use crate::rate_limiter::RateLimiter;
 
 // ── Discogs credentials ──────────────────────────────────────────────────────
 
-/// Consumer key + secret for Discogs API.
-struct Credentials {
-    key: String,
-    secret: String,
+/// How requests are authenticated
+#[derive(Debug, Clone, PartialEq)]
+enum Credentials {
+    /// Personal access token of a Discogs user
+    Token(String),
+    /// Consumer key + secret of an application
+    Consumer { key: String, secret: String },
+    /// OAuth 1.0a access token of a user who authorized the application
+    OAuth {
+        consumer_key: String,
+        consumer_secret: String,
+        token: String,
+        token_secret: String,
+    },
 }
 
-/// Try to load credentials from known paths, return None if not found.
-fn load_credentials() -> Option<Credentials> {
-    let paths = [
-        // Next to the binary / workspace root
-        "discogs_credentials.toml",
-        // System-wide
-        "/etc/autorec/discogs_credentials.toml",
-    ];
+impl Credentials {
+    fn from_config(config: &DiscogsConfig) -> Option<Self> {
+        if let Some(token) = &config.token {
+            return Some(Credentials::Token(token.clone()));
+        }
+        let key = config.consumer_key.clone()?;
+        let secret = config.consumer_secret.clone()?;
+        Some(match (&config.oauth_token, &config.oauth_token_secret) {
+            (Some(token), Some(token_secret)) => Credentials::OAuth {
+                consumer_key: key,
+                consumer_secret: secret,
+                token: token.clone(),
+                token_secret: token_secret.clone(),
+            },
+            _ => Credentials::Consumer { key, secret },
+        })
+    }
 
-    for path in &paths {
-        if let Ok(content) = std::fs::read_to_string(path) {
-            if let Ok(table) = content.parse::<toml::Table>() {
-                let key = table.get("consumer_key")?.as_str()?.to_string();
-                let secret = table.get("consumer_secret")?.as_str()?.to_string();
-                return Some(Credentials { key, secret });
+    /// Value of the Authorization header. OAuth requests are signed with
+    /// the PLAINTEXT method, which Discogs recommends over HTTPS.
+    fn authorization(&self, timestamp: u64, nonce: &str) -> String {
+        match self {
+            Credentials::Token(token) => format!("Discogs token={}", token),
+            Credentials::Consumer { key, secret } => format!("Discogs key={}, secret={}", key, secret),
+            Credentials::OAuth { consumer_key, consumer_secret, token, token_secret } => {
+                let signature = format!("{}&{}", percent_encode(consumer_secret), percent_encode(token_secret));
+                format!(
+                    "OAuth oauth_consumer_key=\"{}\", oauth_nonce=\"{}\", oauth_token=\"{}\", \
+                     oauth_signature=\"{}\", oauth_signature_method=\"PLAINTEXT\", \
+                     oauth_timestamp=\"{}\", oauth_version=\"1.0\"",
+                    percent_encode(consumer_key), nonce, percent_encode(token),
+                    percent_encode(&signature), timestamp
+                )
             }
         }
     }
+}
 
-    // Try home directory
+/// Percent-encoding of OAuth parameters (RFC 5849: all but unreserved characters)
+fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Credentials files, in order of preference
+fn credentials_files() -> Vec<std::path::PathBuf> {
+    let mut paths = vec![
+        // Next to the binary / workspace root
+        std::path::PathBuf::from("discogs_credentials.toml"),
+        // System-wide
+        std::path::PathBuf::from("/etc/autorec/discogs_credentials.toml"),
+    ];
     if let Some(home) = std::env::var_os("HOME") {
-        let path = std::path::PathBuf::from(home)
-            .join(".config/autorec/discogs_credentials.toml");
-        if let Ok(content) = std::fs::read_to_string(&path) {
-            if let Ok(table) = content.parse::<toml::Table>() {
-                let key = table.get("consumer_key")?.as_str()?.to_string();
-                let secret = table.get("consumer_secret")?.as_str()?.to_string();
-                return Some(Credentials { key, secret });
-            }
-        }
+        paths.push(std::path::PathBuf::from(home).join(".config/autorec/discogs_credentials.toml"));
     }
+    paths
+}
+
+/// Load the Discogs settings once: the `[discogs]` section of the config
+/// (including `AUTOREC_DISCOGS__*` variables) takes precedence over the
+/// first credentials file found
+fn load_config() -> &'static DiscogsConfig {
+    static CONFIG: OnceLock<DiscogsConfig> = OnceLock::new();
+    CONFIG.get_or_init(|| {
+        let mut config = credentials_files()
+            .iter()
+            .filter_map(|path| std::fs::read_to_string(path).ok())
+            .find_map(|content| toml::from_str::<DiscogsConfig>(&content).ok())
+            .unwrap_or_default();
+        if let Some(discogs) = Config::load().ok().and_then(|c| c.discogs) {
+            config.merge(&discogs);
+        }
+        config
+    })
+}
 
-    None
+/// None if neither the config nor a credentials file has any
+fn load_credentials() -> Option<&'static Credentials> {
+    static CREDENTIALS: OnceLock<Option<Credentials>> = OnceLock::new();
+    CREDENTIALS.get_or_init(|| Credentials::from_config(load_config())).as_ref()
 }
 
+/// Collections are read 100 releases per page, at most this many pages
+const MAX_COLLECTION_PAGES: u64 = 50;
+/// Smallest side score (see `score_side`) at which a release from the
+/// user's collection is taken without searching all of Discogs
+const COLLECTION_MIN_SCORE: f64 = 50.0;
+
 const USER_AGENT: &str = "HifiBerryAutorec/0.2 +https://github.com/hifiberry/autorec";
 
 /// Create a rate limiter for Discogs.
 /// Authenticated: 60 req/min → 1.0 s base interval.
 /// Unauthenticated: 25 req/min → 2.5 s base interval.
+/// All callers share one limiter, at the longer interval if both are asked for.
 pub fn create_rate_limiter(authenticated: bool) -> RateLimiter {
-    if authenticated {
-        RateLimiter::from_millis("Discogs", 1000)
-    } else {
-        RateLimiter::from_millis("Discogs", 2500)
-    }
+    let base = Duration::from_millis(if authenticated { 1000 } else { 2500 });
+    RateLimiter::shared("Discogs", base)
 }
 
 // ── API response types ───────────────────────────────────────────────────────
@@ -127,7 +201,6 @@ struct ApiVersionsResponse {
 #[derive(Debug, Deserialize)]
 struct ApiPagination {
     items: u64,
-    #[allow(dead_code)]
     pages: u64,
 }
 
@@ -142,6 +215,34 @@ struct ApiVersion {
     major_formats: Vec<String>,
 }
 
+#[derive(Debug, Deserialize)]
+struct ApiIdentity {
+    username: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiCollectionResponse {
+    pagination: ApiPagination,
+    releases: Vec<ApiCollectionRelease>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiCollectionRelease {
+    id: u64,
+    basic_information: ApiBasicInformation,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiBasicInformation {
+    title: String,
+    #[serde(default)]
+    artists: Vec<ApiArtist>,
+    year: Option<u32>,
+    master_id: Option<u64>,
+    #[serde(default)]
+    formats: Vec<ApiFormat>,
+}
+
 #[derive(Debug, Deserialize)]
 struct ApiSearchResponse {
     pagination: ApiPagination,
@@ -206,10 +307,21 @@ pub struct DiscogsSearchResult {
     pub is_vinyl: bool,
 }
 
+/// A release in the user's collection
+#[derive(Debug, Clone)]
+pub struct CollectionItem {
+    pub release_id: u64,
+    pub master_id: Option<u64>,
+    pub artist: String,
+    pub title: String,
+    pub year: Option<u32>,
+    pub is_vinyl: bool,
+}
+
 // ── Helpers ──────────────────────────────────────────────────────────────────
 
 /// Parse a Discogs duration string like "6:40" or "1:02:30" into seconds.
-fn parse_duration(s: &str) -> f64 {
+pub(crate) fn parse_duration(s: &str) -> f64 {
     let parts: Vec<&str> = s.split(':').collect();
     match parts.len() {
         2 => {
@@ -253,15 +365,29 @@ pub fn parse_discogs_url(url: &str) -> Option<u64> {
 
 // ── API functions ────────────────────────────────────────────────────────────
 
+/// GET an API URL after waiting for the rate limiter; 429 and 503 make the
+/// limiter back off. Transient errors are retried (see [`crate::http`]).
+fn api_call(url: &str, rate_limiter: &mut RateLimiter) -> Result<ureq::Response> {
+    let response = http::call("Discogs", || {
+        rate_limiter.wait_if_needed();
+        let response = api_get(url).call().map_err(Box::new);
+        rate_limiter.report_response(&response);
+        response
+    });
+    Ok(response?)
+}
+
 /// Build a ureq request with proper auth and user-agent headers.
 fn api_get(url: &str) -> ureq::Request {
-    let req = ureq::get(url).set("User-Agent", USER_AGENT);
+    let req = http::agent().get(url).set("User-Agent", USER_AGENT);
 
-    if let Some(creds) = load_credentials() {
-        req.set("Authorization",
-                &format!("Discogs key={}, secret={}", creds.key, creds.secret))
-    } else {
-        req
+    match load_credentials() {
+        Some(creds) => {
+            let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+            let nonce = uuid::Uuid::new_v4().simple().to_string();
+            req.set("Authorization", &creds.authorization(timestamp, &nonce))
+        }
+        None => req,
     }
 }
 
@@ -271,16 +397,12 @@ pub fn has_credentials() -> bool {
 }
 
 /// Fetch a single release by ID and parse into structured sides.
-pub fn fetch_release(release_id: u64, rate_limiter: &mut RateLimiter) -> Result<DiscogsRelease, Box<dyn Error>> {
+pub fn fetch_release(release_id: u64, rate_limiter: &mut RateLimiter) -> Result<DiscogsRelease> {
     let url = format!("https://api.discogs.com/releases/{}", release_id);
 
-    rate_limiter.wait_if_needed();
-
-    let response = api_get(&url).call()?;
+    let response = api_call(&url, rate_limiter)?;
     let api: ApiRelease = serde_json::from_reader(response.into_reader())?;
 
-    rate_limiter.report_success();
-
     let artist = api.artists.first()
         .map(|a| a.name.clone())
         .unwrap_or_else(|| "Unknown Artist".to_string());
@@ -335,16 +457,12 @@ fn group_into_sides(tracks: &[DiscogsTrack]) -> Vec<DiscogsSide> {
 }
 
 /// Fetch the master release to get its ID and main release.
-pub fn fetch_master(master_id: u64, rate_limiter: &mut RateLimiter) -> Result<(String, String, Option<u64>), Box<dyn Error>> {
+pub fn fetch_master(master_id: u64, rate_limiter: &mut RateLimiter) -> Result<(String, String, Option<u64>)> {
     let url = format!("https://api.discogs.com/masters/{}", master_id);
 
-    rate_limiter.wait_if_needed();
-
-    let response = api_get(&url).call()?;
+    let response = api_call(&url, rate_limiter)?;
     let api: ApiMaster = serde_json::from_reader(response.into_reader())?;
 
-    rate_limiter.report_success();
-
     let artist = api.artists.first()
         .map(|a| a.name.clone())
         .unwrap_or_else(|| "Unknown".to_string());
@@ -356,19 +474,15 @@ pub fn fetch_master(master_id: u64, rate_limiter: &mut RateLimiter) -> Result<(S
 pub fn fetch_master_vinyl_versions(
     master_id: u64,
     rate_limiter: &mut RateLimiter,
-) -> Result<Vec<DiscogsSearchResult>, Box<dyn Error>> {
+) -> Result<Vec<DiscogsSearchResult>> {
     let url = format!(
         "https://api.discogs.com/masters/{}/versions?format=Vinyl&per_page=50",
         master_id
     );
 
-    rate_limiter.wait_if_needed();
-
-    let response = api_get(&url).call()?;
+    let response = api_call(&url, rate_limiter)?;
     let api: ApiVersionsResponse = serde_json::from_reader(response.into_reader())?;
 
-    rate_limiter.report_success();
-
     let results = api.versions.into_iter()
         .map(|v| {
             let is_vinyl = v.major_formats.iter().any(|f| f == "Vinyl");
@@ -387,16 +501,16 @@ pub fn fetch_master_vinyl_versions(
     Ok(results)
 }
 
-/// Search Discogs for releases.  **Requires authentication** (key+secret).
+/// Search Discogs for releases.  **Requires authentication** (token or key+secret).
 /// Returns an error if no credentials are available.
 pub fn search_releases(
     query: &str,
     release_type: Option<&str>,
     format: Option<&str>,
     rate_limiter: &mut RateLimiter,
-) -> Result<Vec<DiscogsSearchResult>, Box<dyn Error>> {
+) -> Result<Vec<DiscogsSearchResult>> {
     if !has_credentials() {
-        return Err("Discogs search requires authentication — missing discogs_credentials.toml".into());
+        return Err(Error::Config("Discogs search requires authentication — set a token in the [discogs] config section or discogs_credentials.toml".to_string()));
     }
 
     let mut url = format!(
@@ -411,13 +525,9 @@ pub fn search_releases(
         url.push_str(&format!("&format={}", f));
     }
 
-    rate_limiter.wait_if_needed();
-
-    let response = api_get(&url).call()?;
+    let response = api_call(&url, rate_limiter)?;
     let api: ApiSearchResponse = serde_json::from_reader(response.into_reader())?;
 
-    rate_limiter.report_success();
-
     let results = api.results.into_iter()
         .map(|r| {
             let is_vinyl = r.format.iter().any(|f|
@@ -438,6 +548,89 @@ pub fn search_releases(
     Ok(results)
 }
 
+/// Name of the user the credentials belong to.  Needs a personal token or
+/// an OAuth access token; application keys have no user.
+pub fn fetch_identity(rate_limiter: &mut RateLimiter) -> Result<String> {
+    let response = api_call("https://api.discogs.com/oauth/identity", rate_limiter)?;
+    let api: ApiIdentity = serde_json::from_reader(response.into_reader())?;
+    Ok(api.username)
+}
+
+/// Fetch all releases in a user's collection (the "All" folder).
+pub fn fetch_collection(username: &str, rate_limiter: &mut RateLimiter) -> Result<Vec<CollectionItem>> {
+    let mut items = Vec::new();
+    let mut page = 1;
+    loop {
+        let url = format!(
+            "https://api.discogs.com/users/{}/collection/folders/0/releases?per_page=100&page={}",
+            percent_encode(username), page
+        );
+
+        let response = api_call(&url, rate_limiter)?;
+        let api: ApiCollectionResponse = serde_json::from_reader(response.into_reader())?;
+
+        items.extend(api.releases.into_iter().map(|r| {
+            let info = r.basic_information;
+            CollectionItem {
+                release_id: r.id,
+                master_id: info.master_id.filter(|&id| id != 0),
+                artist: info.artists.first().map(|a| a.name.clone()).unwrap_or_default(),
+                title: info.title,
+                year: info.year.filter(|&y| y != 0),
+                is_vinyl: info.formats.iter().any(|f| f.name.eq_ignore_ascii_case("Vinyl")),
+            }
+        }));
+
+        if page >= api.pagination.pages.min(MAX_COLLECTION_PAGES) {
+            break;
+        }
+        page += 1;
+    }
+    Ok(items)
+}
+
+/// The user's collection, fetched once per process.  None if the lookup is
+/// disabled, there is no user, or it could not be fetched.
+fn user_collection(rate_limiter: &mut RateLimiter, verbose: bool) -> Option<&'static [CollectionItem]> {
+    static COLLECTION: OnceLock<Option<Vec<CollectionItem>>> = OnceLock::new();
+    COLLECTION
+        .get_or_init(|| {
+            let config = load_config();
+            if config.collection == Some(false) {
+                return None;
+            }
+            let username = match (&config.username, load_credentials()?) {
+                (Some(username), _) => username.clone(),
+                // Application keys alone do not identify a user
+                (None, Credentials::Consumer { .. }) => return None,
+                (None, _) => match fetch_identity(rate_limiter) {
+                    Ok(username) => username,
+                    Err(e) => {
+                        if verbose {
+                            info!("Discogs: no user for the collection lookup: {}", e);
+                        }
+                        return None;
+                    }
+                },
+            };
+            match fetch_collection(&username, rate_limiter) {
+                Ok(items) => {
+                    if verbose {
+                        info!("Discogs collection of {}: {} releases", username, items.len());
+                    }
+                    Some(items)
+                }
+                Err(e) => {
+                    if verbose {
+                        warn!("Discogs: failed to fetch the collection of {}: {}", username, e);
+                    }
+                    None
+                }
+            }
+        })
+        .as_deref()
+}
+
 /// Minimal URL-encoding (spaces and a few special characters).
 fn urlencoded(s: &str) -> String {
     s.replace(' ', "+")
@@ -471,10 +664,10 @@ pub fn find_best_side<'a>(
         let score = score_side(side, file_duration_seconds, song_titles);
 
         if verbose {
-            println!("  Side {}: {:.1}s, {} tracks, score={:.1}",
+            info!("  Side {}: {:.1}s, {} tracks, score={:.1}",
                      side.label, side.total_duration, side.tracks.len(), score);
             for t in &side.tracks {
-                println!("    {} {} ({:.0}s)", t.position, t.title, t.duration_secs);
+                info!("    {} {} ({:.0}s)", t.position, t.title, t.duration_secs);
             }
         }
 
@@ -556,6 +749,8 @@ pub fn side_to_expected_tracks(side: &DiscogsSide) -> Vec<crate::musicbrainz::Ex
 ///
 /// Flow:
 /// 1. Determine artist + album from the identified songs
+///    (releases of the artist in the user's collection are tried first;
+///    a good enough match there ends the search)
 /// 2. Search Discogs for the master release
 /// 3. Get vinyl versions of the master, preferring recent pressings
 /// 4. Fetch top candidates and pick the one whose best side matches
@@ -565,14 +760,14 @@ pub fn find_album_by_songs(
     file_duration_seconds: f64,
     vinyl_only: bool,
     verbose: bool,
-) -> Result<Option<DiscogsRelease>, Box<dyn Error>> {
+) -> Result<Option<DiscogsRelease>> {
     if songs.is_empty() {
         return Ok(None);
     }
 
     if !has_credentials() {
         if verbose {
-            println!("Discogs: no credentials, skipping");
+            info!("Discogs: no credentials, skipping");
         }
         return Ok(None);
     }
@@ -583,7 +778,49 @@ pub fn find_album_by_songs(
     let (artist, album) = most_common_artist_album(songs);
 
     if verbose {
-        println!("Discogs search: artist=\"{}\" album=\"{}\"", artist, album);
+        info!("Discogs search: artist=\"{}\" album=\"{}\"", artist, album);
+    }
+
+    let song_titles: Vec<String> = songs.iter().map(|s| s.title.clone()).collect();
+
+    // ── Step 0: the user's own collection ────────────────────────────────
+    if let Some(collection) = user_collection(&mut rl, verbose) {
+        let candidates = collection_candidates(collection, &artist, &album);
+        if verbose {
+            info!("Collection: {} release(s) by \"{}\"", candidates.len(), artist);
+        }
+        let mut best: Option<(DiscogsRelease, f64)> = None;
+        for c in candidates.iter().take(8) {
+            let release = match fetch_release(c.release_id, &mut rl) {
+                Ok(r) => r,
+                Err(e) => {
+                    if verbose {
+                        warn!("Failed to fetch release {}: {}", c.release_id, e);
+                    }
+                    continue;
+                }
+            };
+            if let Some(side) = find_best_side(&release, file_duration_seconds, &song_titles, false) {
+                let score = score_side(side, file_duration_seconds, &song_titles);
+                if verbose {
+                    info!("  Collection release {} \"{}\" — best side {}: score={:.1}",
+                             release.release_id, release.title, side.label, score);
+                }
+                if best.as_ref().is_none_or(|(_, s)| score > *s) {
+                    best = Some((release, score));
+                }
+                if score >= 100.0 {
+                    break;
+                }
+            }
+        }
+        if let Some((release, score)) = best.filter(|(_, s)| *s >= COLLECTION_MIN_SCORE) {
+            if verbose {
+                info!("Selected from collection: {} - {} (id={}, score={:.1})",
+                         release.artist, release.title, release.release_id, score);
+            }
+            return Ok(Some(release));
+        }
     }
 
     let query = if album.is_empty() || album == "Unknown" {
@@ -596,20 +833,20 @@ pub fn find_album_by_songs(
     let master_id = {
         let results = search_releases(&query, Some("master"), None, &mut rl)?;
         if verbose {
-            println!("Master search: {} results", results.len());
+            info!("Master search: {} results", results.len());
             for r in results.iter().take(3) {
-                println!("  id={} \"{}\" master={:?}", r.release_id, r.title, r.master_id);
+                info!("  id={} \"{}\" master={:?}", r.release_id, r.title, r.master_id);
             }
         }
         match results.first() {
             Some(r) => r.master_id.unwrap_or(r.release_id),
             None => {
                 // Fallback: try direct release search
-                if verbose { println!("No master found, trying direct release search"); }
+                if verbose { info!("No master found, trying direct release search"); }
                 let format_filter = if vinyl_only { Some("Vinyl") } else { None };
                 let results = search_releases(&query, Some("release"), format_filter, &mut rl)?;
                 if results.is_empty() {
-                    if verbose { println!("No Discogs results found"); }
+                    if verbose { info!("No Discogs results found"); }
                     return Ok(None);
                 }
                 // Fetch a few directly and pick the best
@@ -619,19 +856,19 @@ pub fn find_album_by_songs(
     };
 
     if verbose {
-        println!("Using master ID: {}", master_id);
+        info!("Using master ID: {}", master_id);
     }
 
     // ── Step 2: get vinyl versions of the master ─────────────────────────
     let versions = fetch_master_vinyl_versions(master_id, &mut rl)?;
 
     if versions.is_empty() {
-        if verbose { println!("No vinyl versions found for master {}", master_id); }
+        if verbose { info!("No vinyl versions found for master {}", master_id); }
         return Ok(None);
     }
 
     if verbose {
-        println!("Found {} vinyl versions", versions.len());
+        info!("Found {} vinyl versions", versions.len());
     }
 
     // Sort versions: prefer recent pressings (likely to match user's copy)
@@ -644,12 +881,11 @@ pub fn find_album_by_songs(
 
     if verbose {
         for (i, v) in sorted_versions.iter().take(5).enumerate() {
-            println!("  {}. id={} \"{}\" year={:?}", i + 1, v.release_id, v.title, v.year);
+            info!("  {}. id={} \"{}\" year={:?}", i + 1, v.release_id, v.title, v.year);
         }
     }
 
     // ── Step 3: fetch top candidates and score ───────────────────────────
-    let song_titles: Vec<String> = songs.iter().map(|s| s.title.clone()).collect();
     let mut best_release: Option<DiscogsRelease> = None;
     let mut best_score = f64::NEG_INFINITY;
 
@@ -659,7 +895,7 @@ pub fn find_album_by_songs(
             Ok(r) => r,
             Err(e) => {
                 if verbose {
-                    println!("  Failed to fetch release {}: {}", v.release_id, e);
+                    warn!("Failed to fetch release {}: {}", v.release_id, e);
                 }
                 continue;
             }
@@ -669,7 +905,7 @@ pub fn find_album_by_songs(
             let score = score_side(side, file_duration_seconds, &song_titles);
 
             if verbose {
-                println!("  Release {} ({}) — best side {}: score={:.1} ({:.0}s, {} tracks)",
+                info!("  Release {} ({}) — best side {}: score={:.1} ({:.0}s, {} tracks)",
                          release.release_id,
                          release.year.map_or("?".into(), |y: u32| y.to_string()),
                          side.label, score, side.total_duration, side.tracks.len());
@@ -682,7 +918,7 @@ pub fn find_album_by_songs(
 
             // Perfect song match + good duration → stop early
             if score >= 100.0 {
-                if verbose { println!("  → Perfect match, stopping search"); }
+                if verbose { info!("  → Perfect match, stopping search"); }
                 break;
             }
         }
@@ -690,7 +926,7 @@ pub fn find_album_by_songs(
 
     if verbose {
         if let Some(ref r) = best_release {
-            println!("Selected: {} - {} (id={}, score={:.1})",
+            info!("Selected: {} - {} (id={}, score={:.1})",
                      r.artist, r.title, r.release_id, best_score);
         }
     }
@@ -706,7 +942,7 @@ fn pick_best_from_search(
     vinyl_only: bool,
     verbose: bool,
     rl: &mut RateLimiter,
-) -> Result<Option<DiscogsRelease>, Box<dyn Error>> {
+) -> Result<Option<DiscogsRelease>> {
     let mut candidates: Vec<&DiscogsSearchResult> = results.iter().collect();
     if vinyl_only {
         let vinyl: Vec<&DiscogsSearchResult> = candidates.iter()
@@ -734,29 +970,112 @@ fn pick_best_from_search(
     Ok(best_release)
 }
 
+/// Lowercase letters and digits only, without a leading "the" and the
+/// " (2)" Discogs adds to tell artists of the same name apart
+fn normalize_name(s: &str) -> String {
+    let s = match s.strip_suffix(')').and_then(|s| s.rsplit_once(" (")) {
+        Some((name, n)) if !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()) => name,
+        _ => s,
+    };
+    let s = s.to_lowercase();
+    let s = s.strip_prefix("the ").unwrap_or(&s);
+    s.chars().filter(|c| c.is_alphanumeric()).collect()
+}
+
+fn names_match(a: &str, b: &str) -> bool {
+    let (a, b) = (normalize_name(a), normalize_name(b));
+    !a.is_empty() && !b.is_empty() && (a.contains(&b) || b.contains(&a))
+}
+
+/// Releases in the collection by `artist`, those whose title matches
+/// `album` first.  Vinyl comes before other formats.
+pub fn collection_candidates<'a>(collection: &'a [CollectionItem], artist: &str, album: &str) -> Vec<&'a CollectionItem> {
+    let mut candidates: Vec<(bool, &CollectionItem)> = collection.iter()
+        .filter(|item| names_match(&item.artist, artist))
+        .map(|item| (names_match(&item.title, album), item))
+        .collect();
+    candidates.sort_by_key(|(title_match, item)| (!title_match, !item.is_vinyl));
+    candidates.into_iter().map(|(_, item)| item).collect()
+}
+
 /// Determine the most common artist and album from a list of identified songs.
 fn most_common_artist_album(songs: &[IdentifiedSong]) -> (String, String) {
     use std::collections::HashMap;
 
-    let mut artist_counts: HashMap<String, usize> = HashMap::new();
-    let mut album_counts: HashMap<String, usize> = HashMap::new();
+    // Songs identified with low confidence count less
+    let mut artist_counts: HashMap<String, f64> = HashMap::new();
+    let mut album_counts: HashMap<String, f64> = HashMap::new();
 
     for song in songs {
-        *artist_counts.entry(song.artist.clone()).or_default() += 1;
+        *artist_counts.entry(song.artist.clone()).or_default() += song.weight();
         if let Some(ref album) = song.album {
-            *album_counts.entry(album.clone()).or_default() += 1;
+            *album_counts.entry(album.clone()).or_default() += song.weight();
         }
     }
 
     let artist = artist_counts.into_iter()
-        .max_by_key(|(_, c)| *c)
+        .max_by(|a, b| a.1.total_cmp(&b.1))
         .map(|(a, _)| a)
         .unwrap_or_else(|| "Unknown".to_string());
 
     let album = album_counts.into_iter()
-        .max_by_key(|(_, c)| *c)
+        .max_by(|a, b| a.1.total_cmp(&b.1))
         .map(|(a, _)| a)
         .unwrap_or_else(|| "Unknown".to_string());
 
     (artist, album)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_credentials() {
+        let mut config = DiscogsConfig {
+            consumer_key: Some("ckey".to_string()),
+            consumer_secret: Some("c&secret".to_string()),
+            ..Default::default()
+        };
+        let consumer = Credentials::from_config(&config).unwrap();
+        assert_eq!(consumer.authorization(0, "n"), "Discogs key=ckey, secret=c&secret");
+
+        config.oauth_token = Some("utoken".to_string());
+        config.oauth_token_secret = Some("usecret".to_string());
+        let oauth = Credentials::from_config(&config).unwrap().authorization(1700000000, "abc");
+        assert!(oauth.starts_with("OAuth oauth_consumer_key=\"ckey\", oauth_nonce=\"abc\", oauth_token=\"utoken\""));
+        // consumer_secret&token_secret, each encoded, then encoded again
+        assert!(oauth.contains("oauth_signature=\"c%2526secret%26usecret\""));
+        assert!(oauth.contains("oauth_timestamp=\"1700000000\""));
+
+        // A personal token wins over everything else
+        config.token = Some("ptoken".to_string());
+        assert_eq!(Credentials::from_config(&config), Some(Credentials::Token("ptoken".to_string())));
+        assert_eq!(Credentials::from_config(&DiscogsConfig::default()), None);
+    }
+
+    #[test]
+    fn test_collection_candidates() {
+        let item = |id, artist: &str, title: &str, is_vinyl| CollectionItem {
+            release_id: id,
+            master_id: None,
+            artist: artist.to_string(),
+            title: title.to_string(),
+            year: None,
+            is_vinyl,
+        };
+        let collection = vec![
+            item(1, "The Beatles", "Revolver", true),
+            item(2, "Beatles, The", "Abbey Road", false),
+            item(3, "The Beatles", "Abbey Road", true),
+            item(4, "Pink Floyd", "Animals", true),
+            item(5, "Nirvana (2)", "Local Anaesthetic", true),
+        ];
+        let ids = |artist, album| -> Vec<u64> {
+            collection_candidates(&collection, artist, album).iter().map(|c| c.release_id).collect()
+        };
+        assert_eq!(ids("Beatles", "Abbey Road (Remastered)"), vec![3, 2, 1]);
+        assert_eq!(ids("Nirvana", "Unknown"), vec![5]);
+        assert!(ids("Genesis", "Abbey Road").is_empty());
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::VecDeque;
 use std::io::{self, Write};
 use crossterm::{
     cursor,
@@ -6,6 +7,7 @@ use crossterm::{
     terminal::{self, Clear, ClearType},
 };
 
+use crate::decibel::LevelScale;
 use crate::vu_meter::ChannelMetrics;
 
 /// Display VU meters for all channels using crossterm with colored bars.
@@ -15,7 +17,7 @@ use crate::vu_meter::ChannelMetrics;
 /// - Peak indicators (>)
 /// - RMS indicators (│)
 /// - Scale markers showing dB values
-/// - ON/OFF and CLIP status
+/// - ON/OFF and CLIP status, number of overs (consecutive full-scale samples)
 /// 
 /// # Arguments
 /// * `metrics` - Array of channel metrics to display
@@ -34,6 +36,10 @@ use crate::vu_meter::ChannelMetrics;
 ///     max_peak_db: -8.0,
 ///     is_on: true,
 ///     has_clipped: false,
+///     true_peak_db: -14.5,
+///     max_true_peak_db: -7.6,
+///     overs: 0,
+///     total_overs: 0,
 /// }];
 /// display_vu_meter(&metrics, 60.0, 0.0, None).ok();
 /// ```
@@ -42,15 +48,103 @@ pub fn display_vu_meter(
     db_range: f64,
     max_db: f64,
     recording_status: Option<&str>,
+) -> Result<(), io::Error> {
+    display_vu_meter_scaled(metrics, db_range, max_db, recording_status, &LevelScale::dbfs(), None)
+}
+
+/// Block characters used for sparklines, from lowest to highest level
+const SPARK_CHARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Ring buffer of recent per-channel RMS levels for sparkline display
+pub struct LevelHistory {
+    channels: Vec<VecDeque<f64>>,
+    capacity: usize,
+}
+
+impl LevelHistory {
+    /// Keep `seconds` of history at one entry per `update_interval` seconds
+    pub fn new(seconds: f64, update_interval: f64) -> Self {
+        let capacity = (seconds / update_interval).ceil().max(1.0) as usize;
+        LevelHistory {
+            channels: Vec::new(),
+            capacity,
+        }
+    }
+
+    /// Append the current RMS level of every channel
+    pub fn push(&mut self, metrics: &[ChannelMetrics]) {
+        if self.channels.len() < metrics.len() {
+            self.channels.resize_with(metrics.len(), VecDeque::new);
+        }
+        for (history, m) in self.channels.iter_mut().zip(metrics) {
+            history.push_back(m.db);
+            if history.len() > self.capacity {
+                history.pop_front();
+            }
+        }
+    }
+
+    pub fn channel(&self, ch: usize) -> Option<&VecDeque<f64>> {
+        self.channels.get(ch)
+    }
+}
+
+/// Render levels as a one-line sparkline of `width` characters.
+///
+/// When there are more values than characters, each character shows the
+/// loudest value of its bucket. Values at or below `min_db` render as spaces.
+pub fn sparkline<'a, I>(values: I, width: usize, min_db: f64, max_db: f64) -> String
+where
+    I: IntoIterator<Item = &'a f64>,
+{
+    let values: Vec<f64> = values.into_iter().copied().collect();
+    if width == 0 || values.is_empty() {
+        return String::new();
+    }
+
+    let range = (max_db - min_db).max(f64::EPSILON);
+    let buckets = width.min(values.len());
+    let mut line = String::with_capacity(width * 3);
+
+    for b in 0..buckets {
+        let start = b * values.len() / buckets;
+        let end = ((b + 1) * values.len() / buckets).max(start + 1);
+        let level = values[start..end].iter().copied().fold(f64::MIN, f64::max);
+
+        if level <= min_db {
+            line.push(' ');
+        } else {
+            let normalized = ((level - min_db) / range).clamp(0.0, 1.0);
+            let idx = (normalized * (SPARK_CHARS.len() - 1) as f64).round() as usize;
+            line.push(SPARK_CHARS[idx]);
+        }
+    }
+
+    line
+}
+
+/// Display VU meters with levels shown in a calibrated unit (dBu, dBV).
+///
+/// `db_range` and `max_db` are still given in dBFS; only the labels and the
+/// scale markers are converted using `scale`. If `history` is given, a
+/// sparkline of recent levels is drawn under each channel's bar.
+pub fn display_vu_meter_scaled(
+    metrics: &[ChannelMetrics],
+    db_range: f64,
+    max_db: f64,
+    recording_status: Option<&str>,
+    scale: &LevelScale,
+    history: Option<&LevelHistory>,
 ) -> Result<(), io::Error> {
     let mut stdout = io::stdout();
+    let suffix = scale.suffix();
     let min_db = max_db - db_range;
     
     // Get terminal size and calculate bar width
     // If terminal size detection fails or returns unreasonably small value, use 80 as default
     let (detected_width, _height) = terminal::size().unwrap_or((80, 24));
     let width = if detected_width < 80 { 80 } else { detected_width };
-    let left_label_width = 14;  // "Ch0: -XX.XdB |"
+    let left_label_width = 12 + suffix.len();  // "Ch0: -XX.XdB |"
     let right_label_width = 27; // "| >-XX.X RMS:-XX.X ON   "
     let bar_width = (width as usize).saturating_sub(left_label_width + right_label_width).max(30);
     
@@ -79,7 +173,8 @@ pub fn display_vu_meter(
         let max_pos = (max_normalized * bar_width as f64) as usize;
         
         // Print label
-        print!("Ch{}: {:5.1}dB |", ch, m.db);
+        let label = format!("Ch{}: {:5.1}{} |", ch, scale.apply(m.db), suffix);
+        print!("{}", label);
         
         // Draw colored bar
         for i in 0..bar_width {
@@ -108,19 +203,33 @@ pub fn display_vu_meter(
         let status = if m.is_on { "ON " } else { "OFF" };
         let clip = if m.has_clipped { " CLIP" } else { "     " };
         
-        print!("| >{:5.1} RMS:{:5.1} {}{}\r\n", m.max_peak_db, m.max_db, status, clip);
+        print!("| >{:5.1} RMS:{:5.1} {}{}", scale.apply(m.max_peak_db), scale.apply(m.max_db), status, clip);
+        if m.total_overs > 0 {
+            execute!(stdout, SetForegroundColor(Color::Red), Print(format!(" OVERS:{}", m.total_overs)), ResetColor)?;
+        }
+        print!("\r\n");
+        
+        // Level history, right-aligned so the newest value sits at the bar's end
+        if let Some(levels) = history.and_then(|h| h.channel(ch)) {
+            let line = sparkline(levels, bar_width, min_db, max_db);
+            let padding = label.chars().count() + bar_width - line.chars().count();
+            execute!(stdout, SetForegroundColor(Color::DarkCyan))?;
+            print!("{}{}", " ".repeat(padding), line);
+            execute!(stdout, ResetColor)?;
+            print!("\r\n");
+        }
         
         // Print scale line (only for first channel)
         if ch == 0 {
             // Print spaces to align with the bar start (matching "Ch0: -XX.XdB |")
-            print!("             ");  // 13 spaces to align with the | before the bar
+            print!("{}", " ".repeat(label.chars().count() - 1));
             
+            // Markers are placed at round values of the displayed unit
+            let first_marker = ((scale.apply(min_db.max(-90.0)) / 10.0).ceil() * 10.0) as i32;
+            let last_marker = scale.apply(max_db).floor() as i32;
             let mut last_pos = 0;
-            for db_marker in (-90..=0).step_by(10) {
-                if db_marker < min_db as i32 || db_marker > max_db as i32 {
-                    continue;
-                }
-                let marker_normalized = ((db_marker as f64 - min_db) / db_range).max(0.0).min(1.0);
+            for db_marker in (first_marker..=last_marker).step_by(10) {
+                let marker_normalized = ((db_marker as f64 - scale.offset_db - min_db) / db_range).max(0.0).min(1.0);
                 let marker_pos = (marker_normalized * bar_width as f64) as usize;
                 
                 // Print spaces to reach marker position
@@ -130,8 +239,9 @@ pub fn display_vu_meter(
                 }
                 
                 // Print marker
+                let zero_label = format!("0{}", suffix);
                 let marker_str = if db_marker == 0 {
-                    "0dB"
+                    zero_label.as_str()
                 } else {
                     &format!("{}", db_marker)
                 };
@@ -145,3 +255,70 @@ pub fn display_vu_meter(
     stdout.flush()?;
     Ok(())
 }
+
+/// Show recent log lines below the VU meters, cut to the terminal width
+pub fn display_messages(lines: &[String]) -> Result<(), io::Error> {
+    if lines.is_empty() {
+        return Ok(());
+    }
+    let mut stdout = io::stdout();
+    let (width, _height) = terminal::size().unwrap_or((80, 24));
+    print!("\r\n");
+    execute!(stdout, SetForegroundColor(Color::DarkGrey))?;
+    for line in lines {
+        let line: String = line.chars().take(width.saturating_sub(1) as usize).collect();
+        print!("{}\r\n", line);
+    }
+    execute!(stdout, ResetColor)?;
+    stdout.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metrics(db: f64) -> ChannelMetrics {
+        ChannelMetrics {
+            db,
+            peak_db: db,
+            max_db: db,
+            max_peak_db: db,
+            is_on: true,
+            has_clipped: false,
+            true_peak_db: db,
+            max_true_peak_db: db,
+            overs: 0,
+            total_overs: 0,
+        }
+    }
+
+    #[test]
+    fn test_sparkline_levels() {
+        let values = [-90.0, -50.0, -30.0, 0.0];
+        assert_eq!(sparkline(&values, 4, -60.0, 0.0), " ▂▅█");
+        assert_eq!(sparkline(&values, 0, -60.0, 0.0), "");
+        assert_eq!(sparkline(&Vec::new(), 10, -60.0, 0.0), "");
+    }
+
+    #[test]
+    fn test_sparkline_buckets_use_max() {
+        let values = [-60.0, 0.0, -60.0, -60.0];
+        // Two buckets: the first contains the 0 dB value
+        assert_eq!(sparkline(&values, 2, -60.0, 0.0), "█ ");
+        // Fewer values than width: one character per value
+        assert_eq!(sparkline(&values[..2], 10, -60.0, 0.0).chars().count(), 2);
+    }
+
+    #[test]
+    fn test_level_history_capacity() {
+        let mut history = LevelHistory::new(1.0, 0.25);
+        for i in 0..10 {
+            history.push(&[metrics(-(i as f64)), metrics(-50.0)]);
+        }
+        let ch0 = history.channel(0).unwrap();
+        assert_eq!(ch0.len(), 4);
+        assert_eq!(ch0.back(), Some(&-9.0));
+        assert_eq!(history.channel(1).unwrap().len(), 4);
+        assert!(history.channel(2).is_none());
+    }
+}
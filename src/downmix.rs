@@ -0,0 +1,151 @@
+//! Channel downmix of captured audio before it is recorded.
+//!
+//! Mono records carry the same signal in both channels; recording them in
+//! mono halves the file size and drops the noise of vertical groove
+//! movement, which is out of phase between the channels. Mid/side keeps two
+//! channels but stores their sum and difference, so the mono signal can be
+//! used on its own. Both are halved to fit the sample size, which drops the
+//! lowest bit of an odd sum: decoded, the left channel of such a frame is one
+//! step (1 LSB) lower than recorded.
+//!
+//! The recorder names the channels of a downmixed file in its iXML chunk
+//! ("Mono", or "Mid" and "Side"); [`Downmix::from_channel_names`] recognizes
+//! them again, e.g. so that the analysis uses only the mid channel.
+
+/// Channel downmix applied by the recorder
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Downmix {
+    /// Record the channels as captured
+    #[default]
+    Off,
+    /// Average all channels into one
+    Mono,
+    /// Mid (L+R)/2 and side (L-R)/2 instead of left and right
+    MidSide,
+}
+
+/// Names accepted by [`Downmix::from_str`]
+pub const DOWNMIX_NAMES: &[&str] = &["off", "mono", "ms"];
+
+/// iXML channel names of a mid/side recording
+pub const MID_SIDE_CHANNEL_NAMES: [&str; 2] = ["Mid", "Side"];
+
+impl Downmix {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "off" | "none" | "stereo" => Some(Downmix::Off),
+            "mono" => Some(Downmix::Mono),
+            "ms" | "m/s" | "mid-side" | "midside" => Some(Downmix::MidSide),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Downmix::Off => "off",
+            Downmix::Mono => "mono",
+            Downmix::MidSide => "ms",
+        }
+    }
+
+    /// Check that the downmix can be applied to `channels` input channels
+    pub fn validate(&self, channels: usize) -> Result<(), String> {
+        match self {
+            Downmix::MidSide if channels != 2 => {
+                Err(format!("Mid/side needs 2 channels, the source has {}", channels))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Number of channels written for `channels` input channels
+    pub fn output_channels(&self, channels: usize) -> usize {
+        match self {
+            Downmix::Mono => 1,
+            Downmix::Off | Downmix::MidSide => channels,
+        }
+    }
+
+    /// Names of the written channels for the iXML chunk (empty: unnamed)
+    pub fn channel_names(&self, channels: usize) -> Vec<String> {
+        let names: &[&str] = match (self, self.output_channels(channels)) {
+            (Downmix::Mono, _) => &["Mono"],
+            (Downmix::MidSide, 2) => &MID_SIDE_CHANNEL_NAMES,
+            (_, 2) => &["Left", "Right"],
+            _ => &[],
+        };
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    /// Downmix of a file whose channels have these names
+    pub fn from_channel_names(names: &[String]) -> Self {
+        if names.len() == 2 && names.iter().zip(MID_SIDE_CHANNEL_NAMES).all(|(a, b)| a == b) {
+            Downmix::MidSide
+        } else if names.len() == 1 && names[0] == "Mono" {
+            Downmix::Mono
+        } else {
+            Downmix::Off
+        }
+    }
+
+    /// Downmix interleaved samples with `channels` channels
+    pub fn apply(&self, samples: Vec<i32>, channels: usize) -> Vec<i32> {
+        match self {
+            Downmix::Off => samples,
+            Downmix::Mono if channels <= 1 => samples,
+            Downmix::Mono => samples
+                .chunks_exact(channels)
+                .map(|frame| (frame.iter().map(|&s| s as i64).sum::<i64>() / channels as i64) as i32)
+                .collect(),
+            Downmix::MidSide => samples
+                .chunks_exact(2)
+                .flat_map(|frame| {
+                    // Rounded down alike, so only the left channel loses the odd bit
+                    let (l, r) = (frame[0] as i64, frame[1] as i64);
+                    [((l + r) >> 1) as i32, ((l - r) >> 1) as i32]
+                })
+                .collect(),
+        }
+    }
+
+    /// Left and right from interleaved mid/side samples, clipped to `max`
+    pub fn decode_mid_side(samples: &mut [i32], max: i32) {
+        for frame in samples.chunks_exact_mut(2) {
+            let (m, s) = (frame[0] as i64, frame[1] as i64);
+            frame[0] = (m + s).clamp(-(max as i64) - 1, max as i64) as i32;
+            frame[1] = (m - s).clamp(-(max as i64) - 1, max as i64) as i32;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_downmix() {
+        let stereo = vec![100, 300, -50, 50, 32767, 32767];
+        assert_eq!(Downmix::Mono.apply(stereo.clone(), 2), vec![200, 0, 32767]);
+        assert_eq!(Downmix::Off.apply(stereo.clone(), 2), stereo);
+
+        let mut ms = Downmix::MidSide.apply(stereo.clone(), 2);
+        assert_eq!(ms, vec![200, -100, 0, -50, 32767, 0]);
+        Downmix::decode_mid_side(&mut ms, 32767);
+        assert_eq!(ms, stereo);
+
+        // Odd sums lose their lowest bit, in the left channel only
+        let odd = vec![3, 0, -3, 0, 0, -3, 32767, -32768];
+        let mut ms = Downmix::MidSide.apply(odd, 2);
+        assert_eq!(ms, vec![1, 1, -2, -2, -2, 1, -1, 32767]);
+        Downmix::decode_mid_side(&mut ms, 32767);
+        assert_eq!(ms, vec![2, 0, -4, 0, -1, -3, 32766, -32768]);
+
+        assert_eq!(Downmix::Mono.output_channels(2), 1);
+        assert!(Downmix::MidSide.validate(1).is_err());
+        for downmix in [Downmix::Off, Downmix::Mono, Downmix::MidSide] {
+            assert_eq!(Downmix::from_str(downmix.as_str()), Some(downmix));
+            assert_eq!(Downmix::from_channel_names(&downmix.channel_names(2)), downmix);
+        }
+        assert_eq!(Downmix::from_str("quad"), None);
+    }
+}
@@ -0,0 +1,125 @@
+//! Finding earlier digitizations of a release.
+//!
+//! Every session manifest names the release its recordings were identified
+//! as (see [`crate::session`]). Before the long boundary analysis of a new
+//! recording, `cue_creator` searches the sessions in the music library (see
+//! [`crate::organize`]) and in the directories next to the recording for the
+//! same MusicBrainz or Discogs release ID, so a record isn't digitized twice
+//! by mistake.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local, NaiveDate};
+
+use crate::organize;
+use crate::session::{Session, SESSION_FILE_NAME};
+use crate::tagging::ReleaseTags;
+
+/// A session that holds a recording of the release
+#[derive(Debug, Clone, PartialEq)]
+pub struct Digitization {
+    pub dir: PathBuf,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    /// Day the first side was recorded
+    pub recorded: Option<NaiveDate>,
+}
+
+/// Whether two releases have the same MusicBrainz or Discogs ID
+pub fn same_release(a: &ReleaseTags, b: &ReleaseTags) -> bool {
+    let same = |a: &Option<String>, b: &Option<String>| matches!((a, b), (Some(a), Some(b)) if a == b);
+    same(&a.musicbrainz_release_id, &b.musicbrainz_release_id) || same(&a.discogs_release_id, &b.discogs_release_id)
+}
+
+/// Modification day of the oldest recording of the session, or of the
+/// manifest if no recording is left
+fn recorded_date(dir: &Path, session: &Session) -> Option<NaiveDate> {
+    let modified = |path: PathBuf| fs::metadata(path).and_then(|m| m.modified()).ok();
+    session
+        .sides
+        .iter()
+        .filter_map(|side| modified(dir.join(&side.file)))
+        .min()
+        .or_else(|| modified(dir.join(SESSION_FILE_NAME)))
+        .map(|time| DateTime::<Local>::from(time).date_naive())
+}
+
+/// Session directories to search for earlier recordings of `wav_file`: all
+/// sessions in `library` and the directories next to the recording's own
+fn session_dirs(library: Option<&Path>, wav_file: &str) -> Vec<PathBuf> {
+    let mut dirs = library.map(organize::find_sessions).unwrap_or_default();
+    let own_dir = Path::new(wav_file).parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    if let Some(entries) = own_dir.parent().and_then(|parent| fs::read_dir(parent).ok()) {
+        let mut siblings: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.join(SESSION_FILE_NAME).is_file())
+            .collect();
+        siblings.sort();
+        dirs.extend(siblings);
+    }
+    dirs
+}
+
+/// Sessions other than the one of `wav_file` that recorded `release`
+pub fn find_digitizations(library: Option<&Path>, wav_file: &str, release: &ReleaseTags) -> Vec<Digitization> {
+    if release.musicbrainz_release_id.is_none() && release.discogs_release_id.is_none() {
+        return Vec::new();
+    }
+    let canonical = |path: &Path| fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let own_dir = canonical(Path::new(wav_file).parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new(".")));
+
+    let mut found: Vec<Digitization> = Vec::new();
+    for dir in session_dirs(library, wav_file) {
+        let dir = canonical(&dir);
+        if dir == own_dir || found.iter().any(|d| d.dir == dir) {
+            continue;
+        }
+        let session = match Session::load(&dir.join(SESSION_FILE_NAME)) {
+            Ok(session) => session,
+            Err(_) => continue,
+        };
+        if same_release(&session.release_tags(), release) {
+            found.push(Digitization {
+                recorded: recorded_date(&dir, &session),
+                artist: session.artist,
+                album: session.album,
+                dir,
+            });
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_digitizations() {
+        let library = tempfile::tempdir().unwrap();
+        let earlier = library.path().join("Pink Floyd/Animals (1977)");
+        fs::create_dir_all(&earlier).unwrap();
+        fs::write(earlier.join(SESSION_FILE_NAME), "artist = \"Pink Floyd\"\nalbum = \"Animals\"\n\
+            release = \"https://www.discogs.com/release/1873013\"\n\n\
+            [[side]]\nfile = \"animals.1.wav\"\nduration = 1292.4\n").unwrap();
+        fs::write(earlier.join("animals.1.wav"), b"").unwrap();
+
+        let capture = tempfile::tempdir().unwrap();
+        let session_dir = capture.path().join("new");
+        fs::create_dir(&session_dir).unwrap();
+        fs::copy(earlier.join(SESSION_FILE_NAME), session_dir.join(SESSION_FILE_NAME)).unwrap();
+        let wav = session_dir.join("recording.1.wav").to_string_lossy().to_string();
+
+        let release = ReleaseTags::from_release_info("https://www.discogs.com/release/1873013", None);
+        let found = find_digitizations(Some(library.path()), &wav, &release);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].album.as_deref(), Some("Animals"));
+        assert_eq!(found[0].recorded, Some(Local::now().date_naive()));
+
+        let other = ReleaseTags::from_release_info("https://www.discogs.com/release/42", None);
+        assert!(find_digitizations(Some(library.path()), &wav, &other).is_empty());
+        assert!(find_digitizations(None, &wav, &release).is_empty());
+    }
+}
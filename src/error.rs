@@ -0,0 +1,70 @@
+//! Error type of the autorec library.
+//!
+//! Library functions return [`Result`] so that callers can tell a missing
+//! file from an unreachable web service or a bad audio address. The message
+//! of every variant is suitable for printing to the user as is.
+
+use std::io;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// File or pipe I/O failed
+    #[error("{0}")]
+    Io(#[from] io::Error),
+
+    /// An audio backend (PipeWire, ALSA, pw-record, ...) could not be used
+    #[error("{0}")]
+    Backend(String),
+
+    /// Audio data could not be read or decoded
+    #[error("{0}")]
+    Decode(String),
+
+    /// A metadata service (MusicBrainz, Discogs) request failed
+    #[error("{0}")]
+    Lookup(String),
+
+    /// A response from a metadata service could not be parsed
+    #[error("Invalid response: {0}")]
+    Json(#[from] serde_json::Error),
+
+    /// Invalid configuration or command-line value (address, regex, ...)
+    #[error("{0}")]
+    Config(String),
+
+    /// The recording catalog could not be read or written
+    #[error("Catalog: {0}")]
+    Catalog(#[from] rusqlite::Error),
+}
+
+impl From<ureq::Error> for Error {
+    fn from(e: ureq::Error) -> Self {
+        Error::Lookup(e.to_string())
+    }
+}
+
+impl From<Box<ureq::Error>> for Error {
+    fn from(e: Box<ureq::Error>) -> Self {
+        Error::Lookup(e.to_string())
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_categories() {
+        let io_err: Error = io::Error::new(io::ErrorKind::NotFound, "missing").into();
+        assert!(matches!(io_err, Error::Io(ref e) if e.kind() == io::ErrorKind::NotFound));
+        assert_eq!(io_err.to_string(), "missing");
+
+        let json_err: Error = serde_json::from_str::<serde_json::Value>("{").unwrap_err().into();
+        assert!(matches!(json_err, Error::Json(_)));
+        assert!(json_err.to_string().starts_with("Invalid response"));
+
+        assert_eq!(Error::Backend("pw-record failed".to_string()).to_string(), "pw-record failed");
+    }
+}
@@ -0,0 +1,275 @@
+//! Event stream of the `autorecd` daemon.
+//!
+//! `GET /events` with a WebSocket upgrade opens a stream of JSON events, so
+//! UIs are told about changes instead of polling the control API (see
+//! [`crate::control_api`]). Every message is one [`DaemonEvent`], tagged by
+//! `event`:
+//!
+//! ```text
+//! {"event":"levels","channels":[{"db":-18.2,...}]}
+//! {"event":"state","state":"recording"}
+//! {"event":"recording_started","file":"side.1.wav"}
+//! {"event":"recording_stopped","file":"side.1.wav"}
+//! {"event":"song_boundary","file":"side.1.wav","song":3}
+//! {"event":"identified","file":"side.1.wav","artist":"...","album":"...","year":1977,"release":null}
+//! {"event":"source_lost"}
+//! {"event":"source_back","seconds":4.2}
+//! ```
+//!
+//! A new connection starts with a `state` event. Clients only receive;
+//! commands still go through the HTTP endpoints. Pings and close frames
+//! from the client are answered. A client that cannot keep up is
+//! disconnected: once [`EVENT_QUEUE`] events are waiting for it, or a write
+//! takes longer than [`WRITE_TIMEOUT`].
+
+use std::io::ErrorKind;
+use std::net::TcpStream;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tracing::debug;
+use tungstenite::{Message, WebSocket};
+
+use crate::control_api::{IdentifiedAlbum, SharedState};
+use crate::vu_meter::ChannelMetrics;
+
+/// Path of the event stream
+pub const EVENTS_PATH: &str = "/events";
+
+/// Events that may wait for a client before it is disconnected
+pub const EVENT_QUEUE: usize = 256;
+
+/// Longest a client may take to accept an event
+pub const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Longest wait for the request of a new connection
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often a connection checks for frames from the client
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Changes of the recorder pushed to the clients of `/events`
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum DaemonEvent {
+    /// Latest per-channel levels, one per audio chunk
+    Levels { channels: Vec<ChannelMetrics> },
+    /// idle, armed, recording, paused or disconnected
+    State { state: &'static str },
+    RecordingStarted { file: String },
+    RecordingStopped { file: String },
+    /// A pause between songs was detected in the current recording
+    SongBoundary { file: String, song: u32 },
+    /// cue_creator identified the album of a recording
+    Identified(IdentifiedAlbum),
+    SourceLost,
+    SourceBack { seconds: f64 },
+}
+
+/// Subscribers of the event stream
+#[derive(Default)]
+pub struct EventHub {
+    subscribers: Vec<SyncSender<String>>,
+}
+
+impl EventHub {
+    /// Receive all events published from now on, as JSON
+    pub fn subscribe(&mut self) -> Receiver<String> {
+        let (sender, receiver) = sync_channel(EVENT_QUEUE);
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    /// Send `event` to all subscribers and drop the ones that are gone or
+    /// have [`EVENT_QUEUE`] events waiting
+    pub fn publish(&mut self, event: &DaemonEvent) {
+        if self.subscribers.is_empty() {
+            return;
+        }
+        let Ok(text) = serde_json::to_string(event) else {
+            return;
+        };
+        self.subscribers.retain(|s| s.try_send(text.clone()).is_ok());
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.subscribers.len()
+    }
+}
+
+/// Whether the request head `head` (request line and headers) asks for a
+/// WebSocket upgrade of the event stream
+pub fn is_event_head(head: &str) -> bool {
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or("").split_whitespace();
+    let path = request_line.nth(1).unwrap_or("").split('?').next().unwrap_or("").trim_end_matches('/');
+    let upgrade = lines.any(|line| {
+        line.split_once(':').is_some_and(|(name, value)| {
+            name.trim().eq_ignore_ascii_case("Upgrade") && value.trim().eq_ignore_ascii_case("websocket")
+        })
+    });
+    head.starts_with("GET ") && path == EVENTS_PATH && upgrade
+}
+
+/// Whether the new connection `stream` asks for the event stream; looks at
+/// the request without taking it from the stream
+pub fn is_event_stream(stream: &TcpStream) -> bool {
+    let deadline = Instant::now() + REQUEST_TIMEOUT;
+    let _ = stream.set_read_timeout(Some(REQUEST_TIMEOUT));
+    let mut head = [0; 4096];
+    loop {
+        let Ok(len) = stream.peek(&mut head) else {
+            return false;
+        };
+        let text = String::from_utf8_lossy(&head[..len]);
+        if let Some(end) = text.find("\r\n\r\n") {
+            return is_event_head(&text[..end]);
+        }
+        if len == 0 || len == head.len() || Instant::now() > deadline {
+            return false;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Complete the WebSocket handshake on `stream` and send the events to the
+/// client until it is gone or disconnected for being too slow
+pub fn accept(stream: TcpStream, state: SharedState) {
+    let _ = stream.set_read_timeout(Some(REQUEST_TIMEOUT));
+    let _ = stream.set_write_timeout(Some(WRITE_TIMEOUT));
+    let mut socket = match tungstenite::accept(stream) {
+        Ok(socket) => socket,
+        Err(e) => {
+            debug!("Event stream handshake failed: {}", e);
+            return;
+        }
+    };
+    let _ = socket.get_ref().set_read_timeout(Some(POLL_INTERVAL));
+
+    let events = {
+        let mut state = state.lock().unwrap();
+        let events = state.events.subscribe();
+        state.publish_state();
+        events
+    };
+    // Ends when the client is gone or too slow, or the daemon exits
+    while stream_events(&mut socket, &events) {}
+    debug!("Event stream closed");
+}
+
+/// Answer the frames of the client and send the waiting events; false once
+/// the connection is over
+fn stream_events(socket: &mut WebSocket<TcpStream>, events: &Receiver<String>) -> bool {
+    match socket.read() {
+        // Pings and close frames are answered on the next flush
+        Ok(_) => {}
+        Err(tungstenite::Error::Io(e)) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+        Err(_) => return false,
+    }
+    loop {
+        match events.try_recv() {
+            Ok(text) => {
+                if socket.write(Message::text(text)).is_err() {
+                    return false;
+                }
+            }
+            Err(TryRecvError::Empty) => break,
+            Err(TryRecvError::Disconnected) => {
+                let _ = socket.close(None);
+                let _ = socket.flush();
+                return false;
+            }
+        }
+    }
+    socket.flush().is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control_api::DaemonState;
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_publish() {
+        let mut hub = EventHub::default();
+        hub.publish(&DaemonEvent::SourceLost);
+        let first = hub.subscribe();
+        let second = hub.subscribe();
+        drop(second);
+
+        hub.publish(&DaemonEvent::RecordingStarted { file: "side.1.wav".to_string() });
+        hub.publish(&DaemonEvent::SongBoundary { file: "side.1.wav".to_string(), song: 2 });
+        assert_eq!(hub.subscriber_count(), 1);
+        assert_eq!(first.try_recv().unwrap(), r#"{"event":"recording_started","file":"side.1.wav"}"#);
+        assert_eq!(first.try_recv().unwrap(), r#"{"event":"song_boundary","file":"side.1.wav","song":2}"#);
+        assert!(first.try_recv().is_err());
+
+        let album = IdentifiedAlbum {
+            file: "side.1.wav".to_string(),
+            artist: "Artist".to_string(),
+            album: "Album".to_string(),
+            year: Some(1977),
+            release: None,
+        };
+        hub.publish(&DaemonEvent::Identified(album));
+        let text = first.try_recv().unwrap();
+        assert!(text.starts_with(r#"{"event":"identified","file":"side.1.wav","artist":"Artist""#));
+    }
+
+    #[test]
+    fn test_slow_subscriber() {
+        let mut hub = EventHub::default();
+        let slow = hub.subscribe();
+        for _ in 0..EVENT_QUEUE {
+            hub.publish(&DaemonEvent::SourceLost);
+        }
+        assert_eq!(hub.subscriber_count(), 1);
+        hub.publish(&DaemonEvent::SourceLost);
+        assert_eq!(hub.subscriber_count(), 0);
+        assert_eq!(slow.try_iter().count(), EVENT_QUEUE);
+    }
+
+    #[test]
+    fn test_event_head() {
+        let head = "GET /events/?x=1 HTTP/1.1\r\nHost: localhost\r\nupgrade: WebSocket\r\nConnection: Upgrade";
+        assert!(is_event_head(head));
+        assert!(!is_event_head("GET /events HTTP/1.1\r\nHost: localhost"));
+        assert!(!is_event_head("GET /status HTTP/1.1\r\nUpgrade: websocket"));
+        assert!(!is_event_head("POST /events HTTP/1.1\r\nUpgrade: websocket"));
+    }
+
+    #[test]
+    fn test_event_stream() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let state: SharedState = Arc::new(Mutex::new(DaemonState::new("pipewire:test", true)));
+        let server_state = Arc::clone(&state);
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            assert!(is_event_stream(&stream));
+            accept(stream, server_state);
+        });
+
+        let stream = TcpStream::connect(address).unwrap();
+        let (mut client, _) = tungstenite::client(format!("ws://{}{}", address, EVENTS_PATH), stream).unwrap();
+        let Message::Text(text) = client.read().unwrap() else { panic!("expected the state") };
+        assert_eq!(text, r#"{"event":"state","state":"armed"}"#);
+
+        client.send(Message::Ping(b"tick".to_vec())).unwrap();
+        assert_eq!(client.read().unwrap(), Message::Pong(b"tick".to_vec()));
+
+        state.lock().unwrap().events.publish(&DaemonEvent::SourceLost);
+        assert_eq!(client.read().unwrap(), Message::text(r#"{"event":"source_lost"}"#));
+
+        client.close(None).unwrap();
+        assert!(matches!(client.read().unwrap(), Message::Close(_)));
+        server.join().unwrap();
+        let mut state = state.lock().unwrap();
+        state.events.publish(&DaemonEvent::SourceLost);
+        assert_eq!(state.events.subscriber_count(), 0);
+    }
+}
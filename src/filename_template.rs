@@ -0,0 +1,441 @@
+//! File name templates for recordings and split tracks.
+//!
+//! A template is a relative path with placeholders, e.g.
+//! `{artist}/{album}/{side_letter}{track:02} - {title}.{ext}`. A `/` in the
+//! template creates directories; placeholder values never do, as they are
+//! sanitized before they are inserted.
+//!
+//! Placeholders:
+//! - text: `{artist}`, `{album}`, `{album_artist}`, `{title}`, `{base}`
+//!   (the name given to the recorder), `{ext}`
+//! - numbers: `{track}`, `{side}`, `{side_letter}` (1 = A), `{year}`; a
+//!   width pads with zeros, e.g. `{track:02}`
+//! - date and time of the recording or split: `{date}` (2024-05-01),
+//!   `{time}` (143000) or any strftime format, e.g. `{date:%Y%m%d}`
+//!
+//! `{{` and `}}` are literal braces. Values that are not known are left
+//! empty, and separators left dangling by them are removed, so
+//! `{track:02} - {artist} - {title}` becomes `03 - Title` without an artist.
+
+use chrono::format::{Item, StrftimeItems};
+use chrono::NaiveDateTime;
+
+use crate::config::OutputConfig;
+use crate::cuefile::transliterate;
+use crate::error::{Error, Result};
+
+/// Default name of recordings, e.g. `side.1.wav`
+pub const DEFAULT_RECORDING_TEMPLATE: &str = "{base}.{side}.{ext}";
+
+/// Default name of split tracks, e.g. `03 - Artist - Title.flac`
+pub const DEFAULT_TRACK_TEMPLATE: &str = "{track:02} - {artist} - {title}.{ext}";
+
+/// Names of the sanitization rules
+pub const SANITIZE_NAMES: &[&str] = &["safe", "ascii", "portable"];
+
+/// How placeholder values are made safe for file names
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Sanitize {
+    /// Remove only characters that are invalid on common file systems
+    #[default]
+    Safe,
+    /// Also transliterate to ASCII ("Björk" becomes "Bjoerk")
+    Ascii,
+    /// ASCII letters, digits, '.', '-' and '_' only; spaces become '_'
+    Portable,
+}
+
+impl Sanitize {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "safe" => Some(Sanitize::Safe),
+            "ascii" => Some(Sanitize::Ascii),
+            "portable" => Some(Sanitize::Portable),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Sanitize::Safe => "safe",
+            Sanitize::Ascii => "ascii",
+            Sanitize::Portable => "portable",
+        }
+    }
+
+    /// Sanitize one placeholder value
+    pub fn apply(&self, value: &str) -> String {
+        let value = match self {
+            Sanitize::Safe => sanitize_for_filename(value),
+            Sanitize::Ascii => sanitize_for_filename(&transliterate(value)),
+            Sanitize::Portable => {
+                let ascii = sanitize_for_filename(&transliterate(value));
+                let mut portable = String::with_capacity(ascii.len());
+                for c in ascii.chars() {
+                    let c = if c.is_ascii_alphanumeric() || matches!(c, '.' | '-') { c } else { '_' };
+                    if !(c == '_' && portable.ends_with('_')) {
+                        portable.push(c);
+                    }
+                }
+                portable.trim_matches('_').to_string()
+            }
+        };
+        // No hidden files, and no ".." from a title
+        value.trim_start_matches('.').to_string()
+    }
+}
+
+/// Sanitize a string for use in filenames.
+/// - Keeps original case, spaces, hyphens, parentheses
+/// - Removes characters that cause filesystem problems: / \ : * ? " < > |
+/// - Collapses multiple spaces
+/// - Trims leading/trailing whitespace
+pub fn sanitize_for_filename(s: &str) -> String {
+    let s: String = s.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => ' ',
+            _ => c,
+        })
+        .collect();
+    // Collapse multiple spaces
+    let mut result = String::new();
+    let mut last_was_space = false;
+    for c in s.chars() {
+        if c == ' ' {
+            if !last_was_space {
+                result.push(c);
+            }
+            last_was_space = true;
+        } else {
+            result.push(c);
+            last_was_space = false;
+        }
+    }
+    result.trim().to_string()
+}
+
+/// Side number of a recording from its name (`album.2.wav` -> 2)
+pub fn side_number(filename: &str) -> Option<u32> {
+    let stem = filename.strip_suffix(".wav").unwrap_or(filename);
+    stem.rsplit('.').next()?.parse().ok()
+}
+
+/// Values for the placeholders; None values are left empty
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TemplateValues {
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub title: Option<String>,
+    pub base: Option<String>,
+    pub ext: Option<String>,
+    pub track: Option<u32>,
+    pub side: Option<u32>,
+    pub year: Option<u32>,
+    pub date: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Artist,
+    Album,
+    AlbumArtist,
+    Title,
+    Base,
+    Ext,
+    Track,
+    Side,
+    SideLetter,
+    Year,
+    Date,
+    Time,
+}
+
+impl Field {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "artist" => Field::Artist,
+            "album" => Field::Album,
+            "album_artist" => Field::AlbumArtist,
+            "title" => Field::Title,
+            "base" => Field::Base,
+            "ext" => Field::Ext,
+            "track" => Field::Track,
+            "side" => Field::Side,
+            "side_letter" => Field::SideLetter,
+            "year" => Field::Year,
+            "date" => Field::Date,
+            "time" => Field::Time,
+            _ => return None,
+        })
+    }
+
+    fn is_number(&self) -> bool {
+        matches!(self, Field::Track | Field::Side | Field::Year)
+    }
+
+    fn is_date(&self) -> bool {
+        matches!(self, Field::Date | Field::Time)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Literal(String),
+    Field(Field, Option<String>),
+}
+
+/// A parsed file name template
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilenameTemplate {
+    template: String,
+    parts: Vec<Part>,
+    sanitize: Sanitize,
+}
+
+impl FilenameTemplate {
+    /// Parse a template; unknown placeholders and bad formats are errors
+    pub fn parse(template: &str) -> Result<Self> {
+        let invalid = |reason: String| Error::Config(format!("File name template '{}': {}", template, reason));
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut placeholder = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => placeholder.push(c),
+                            None => return Err(invalid("missing '}'".to_string())),
+                        }
+                    }
+                    let (name, spec) = match placeholder.split_once(':') {
+                        Some((name, spec)) => (name, Some(spec.to_string())),
+                        None => (placeholder.as_str(), None),
+                    };
+                    let field = Field::from_name(name)
+                        .ok_or_else(|| invalid(format!("unknown placeholder {{{}}}", name)))?;
+                    if let Some(spec) = &spec {
+                        let valid = if field.is_number() {
+                            !spec.is_empty() && spec.chars().all(|c| c.is_ascii_digit())
+                        } else {
+                            field.is_date() && !StrftimeItems::new(spec).any(|item| item == Item::Error)
+                        };
+                        if !valid {
+                            return Err(invalid(format!("invalid format '{}' for {{{}}}", spec, name)));
+                        }
+                    }
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Field(field, spec));
+                }
+                '}' => return Err(invalid("unmatched '}'".to_string())),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(FilenameTemplate { template: template.to_string(), parts, sanitize: Sanitize::default() })
+    }
+
+    /// The template from the `filename_template` and `filename_sanitize`
+    /// values of a config section; `default` if no template is set
+    pub fn from_config(template: Option<&str>, sanitize: Option<&str>, default: &str) -> Result<Self> {
+        let sanitize = match sanitize {
+            Some(name) => Sanitize::from_str(name).ok_or_else(|| {
+                Error::Config(format!("Unknown file name sanitization '{}' (use {})", name, SANITIZE_NAMES.join(", ")))
+            })?,
+            None => Sanitize::default(),
+        };
+        Ok(Self::parse(template.unwrap_or(default))?.with_sanitize(sanitize))
+    }
+
+    /// The template for recordings from the `[output]` section
+    pub fn for_recordings(config: &OutputConfig) -> Result<Self> {
+        Self::from_config(
+            config.filename_template.as_deref(),
+            config.filename_sanitize.as_deref(),
+            DEFAULT_RECORDING_TEMPLATE,
+        )
+    }
+
+    /// The template for recordings, [`DEFAULT_RECORDING_TEMPLATE`]
+    pub fn recording() -> Self {
+        Self::parse(DEFAULT_RECORDING_TEMPLATE).expect("valid default template")
+    }
+
+    /// The template for split tracks, [`DEFAULT_TRACK_TEMPLATE`]
+    pub fn track() -> Self {
+        Self::parse(DEFAULT_TRACK_TEMPLATE).expect("valid default template")
+    }
+
+    pub fn with_sanitize(mut self, sanitize: Sanitize) -> Self {
+        self.sanitize = sanitize;
+        self
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.template
+    }
+
+    pub fn sanitize(&self) -> Sanitize {
+        self.sanitize
+    }
+
+    /// Whether the names depend on the time they are rendered
+    pub fn has_date(&self) -> bool {
+        self.parts.iter().any(|part| matches!(part, Part::Field(field, _) if field.is_date()))
+    }
+
+    /// Whether the names contain the side number or letter
+    pub fn has_side(&self) -> bool {
+        self.parts.iter().any(|part| matches!(part, Part::Field(Field::Side | Field::SideLetter, _)))
+    }
+
+    /// Whether names for different sides differ up to side `side`: with
+    /// `{side}`, or with `{side_letter}` for sides A to Z
+    pub fn numbers_side(&self, side: u32) -> bool {
+        self.parts.iter().any(|part| match part {
+            Part::Field(Field::Side, _) => true,
+            Part::Field(Field::SideLetter, _) => side <= 26,
+            _ => false,
+        })
+    }
+
+    /// The relative path for `values`
+    pub fn render(&self, values: &TemplateValues) -> String {
+        let mut path = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => path.push_str(text),
+                Part::Field(field, spec) => {
+                    path.push_str(&self.sanitize.apply(&field_value(*field, spec.as_deref(), values)))
+                }
+            }
+        }
+        clean_path(&path, self.template.starts_with('/'))
+    }
+}
+
+fn field_value(field: Field, spec: Option<&str>, values: &TemplateValues) -> String {
+    let text = |value: &Option<String>| value.clone().unwrap_or_default();
+    let number = |value: Option<u32>| match (value, spec.and_then(|s| s.parse::<usize>().ok())) {
+        (Some(n), Some(width)) => format!("{:0width$}", n, width = width),
+        (Some(n), None) => n.to_string(),
+        (None, _) => String::new(),
+    };
+    let date = |default: &str| {
+        values.date.map(|date| date.format(spec.unwrap_or(default)).to_string()).unwrap_or_default()
+    };
+    match field {
+        Field::Artist => text(&values.artist),
+        Field::Album => text(&values.album),
+        Field::AlbumArtist => text(&values.album_artist),
+        Field::Title => text(&values.title),
+        Field::Base => text(&values.base),
+        Field::Ext => text(&values.ext),
+        Field::Track => number(values.track),
+        Field::Side => number(values.side),
+        Field::Year => number(values.year),
+        Field::SideLetter => values
+            .side
+            .filter(|side| (1..=26).contains(side))
+            .map(|side| ((b'A' + side as u8 - 1) as char).to_string())
+            .unwrap_or_default(),
+        Field::Date => date("%Y-%m-%d"),
+        Field::Time => date("%H%M%S"),
+    }
+}
+
+/// Tidy each path component: collapse spaces, drop separators and brackets
+/// left by empty values ("03 - - Title", "Artist - .flac", "Album ()") and
+/// name empty components
+fn clean_path(path: &str, absolute: bool) -> String {
+    let separators: &[char] = &[' ', '-', '_'];
+    let components: Vec<String> = path
+        .split('/')
+        .enumerate()
+        .map(|(i, component)| {
+            if i == 0 && absolute {
+                return String::new();
+            }
+            let component = component.replace("()", "").replace("[]", "");
+            let mut component = component.split_whitespace().collect::<Vec<_>>().join(" ");
+            while component.contains("- -") {
+                component = component.replace("- -", "-");
+            }
+            let component = match component.rsplit_once('.') {
+                Some((stem, ext)) if !stem.is_empty() && !ext.contains(' ') => {
+                    format!("{}.{}", stem.trim_matches(separators), ext)
+                }
+                _ => component.trim_matches(separators).to_string(),
+            };
+            if component.is_empty() || component.starts_with('.') {
+                format!("Unknown{}", component)
+            } else {
+                component
+            }
+        })
+        .collect();
+    components.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let values = TemplateValues {
+            artist: Some("AC/DC".to_string()),
+            album: Some("Back in Black".to_string()),
+            title: Some("Hells Bells".to_string()),
+            ext: Some("flac".to_string()),
+            track: Some(1),
+            side: Some(2),
+            date: NaiveDateTime::parse_from_str("2024-05-01 14:30:00", "%Y-%m-%d %H:%M:%S").ok(),
+            ..Default::default()
+        };
+        let template = FilenameTemplate::parse("{artist}/{album}/{side_letter}{track:02} - {title}.{ext}").unwrap();
+        assert_eq!(template.render(&values), "AC DC/Back in Black/B01 - Hells Bells.flac");
+        let template = FilenameTemplate::parse("{date:%Y%m%d}-{time} {{{album}}}.{ext}").unwrap();
+        assert_eq!(template.render(&values), "20240501-143000 {Back in Black}.flac");
+
+        // Missing values leave no dangling separators
+        let values = TemplateValues { title: Some("Intro".to_string()), track: Some(3), ext: Some("wav".to_string()), ..values };
+        let values = TemplateValues { artist: None, ..values };
+        assert_eq!(FilenameTemplate::track().render(&values), "03 - Intro.wav");
+        let values = TemplateValues { title: None, ..values };
+        assert_eq!(FilenameTemplate::parse("{artist}/{track:02} {title}.{ext}").unwrap().render(&values), "Unknown/03.wav");
+        assert_eq!(FilenameTemplate::parse("{album} ({year})").unwrap().render(&values), "Back in Black");
+
+        let recording = TemplateValues { base: Some("side".to_string()), side: Some(4), ext: Some("wav".to_string()), ..Default::default() };
+        assert_eq!(FilenameTemplate::recording().render(&recording), "side.4.wav");
+
+        for bad in ["{artist", "{band}", "{track:x}", "{title:02}", "a}b"] {
+            assert!(FilenameTemplate::parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_sanitize() {
+        assert_eq!(Sanitize::Safe.apply("What? / \"Now\""), "What Now");
+        assert_eq!(Sanitize::Safe.apply(".."), "");
+        assert_eq!(Sanitize::Ascii.apply("Björk: Jóga"), "Bjoerk Joga");
+        assert_eq!(Sanitize::Portable.apply("Björk: Jóga (Live)"), "Bjoerk_Joga_Live");
+        assert_eq!(side_number("/rec/album.2.wav"), Some(2));
+        assert_eq!(side_number("album.wav"), None);
+    }
+}
@@ -0,0 +1,126 @@
+//! DC blocking filter.
+//!
+//! Some ADCs add a constant offset to the signal. It skews RMS levels and
+//! silence detection and takes away a little headroom. The blocker is the
+//! classic one-pole high-pass `y[n] = x[n] - x[n-1] + r * y[n-1]` with its
+//! corner far below anything audible, so it leaves even warp rumble for the
+//! subsonic filter.
+//!
+//! The offset it removes can be read through a [`DcOffsetMeter`].
+
+use std::sync::{Arc, Mutex};
+
+use super::AudioFilter;
+
+/// Corner frequency of the DC blocker in Hz
+pub const CORNER_HZ: f64 = 2.0;
+
+/// Mean offset removed by a [`DcBlocker`] (fraction of full scale)
+///
+/// Clones share the measurement, so the filter can sit in a
+/// [`FilterChain`](super::FilterChain) while the offset is read elsewhere.
+#[derive(Debug, Clone, Default)]
+pub struct DcOffsetMeter {
+    /// Sum of the removed component and number of samples
+    state: Arc<Mutex<(f64, u64)>>,
+}
+
+impl DcOffsetMeter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn add(&self, sum: f64, samples: u64) {
+        let mut state = self.state.lock().unwrap();
+        state.0 += sum;
+        state.1 += samples;
+    }
+
+    /// Mean removed offset since the start, None before any audio
+    pub fn offset(&self) -> Option<f64> {
+        let (sum, samples) = *self.state.lock().unwrap();
+        (samples > 0).then(|| sum / samples as f64)
+    }
+}
+
+/// One-pole DC blocker for one channel
+#[derive(Debug, Clone)]
+pub struct DcBlocker {
+    pole: f64,
+    x1: f64,
+    y1: f64,
+    meter: Option<DcOffsetMeter>,
+}
+
+impl DcBlocker {
+    pub fn new(sample_rate: u32) -> Self {
+        DcBlocker {
+            pole: (-2.0 * std::f64::consts::PI * CORNER_HZ / sample_rate as f64).exp(),
+            x1: 0.0,
+            y1: 0.0,
+            meter: None,
+        }
+    }
+
+    /// Report the removed offset to `meter`
+    pub fn with_meter(mut self, meter: DcOffsetMeter) -> Self {
+        self.meter = Some(meter);
+        self
+    }
+}
+
+impl AudioFilter for DcBlocker {
+    fn process(&mut self, samples: &mut [f64]) {
+        let mut removed = 0.0;
+        for sample in samples.iter_mut() {
+            let x = *sample;
+            let y = x - self.x1 + self.pole * self.y1;
+            self.x1 = x;
+            self.y1 = y;
+            removed += x - y;
+            *sample = y;
+        }
+        if let Some(meter) = &self.meter {
+            meter.add(removed, samples.len() as u64);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.x1 = 0.0;
+        self.y1 = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dc_blocker() {
+        let rate = 48000;
+        let sine = |frequency: f64, n: usize| (2.0 * std::f64::consts::PI * frequency * n as f64 / rate as f64).sin();
+        let settled = |samples: &[f64]| samples[samples.len() / 2..].to_vec();
+
+        // A 1% offset under a 1 kHz tone is removed, the tone stays
+        let meter = DcOffsetMeter::new();
+        assert_eq!(meter.offset(), None);
+        let mut filter = DcBlocker::new(rate).with_meter(meter.clone());
+        let mut audio: Vec<f64> = (0..4 * rate as usize).map(|n| 0.2 * sine(1000.0, n) + 0.01).collect();
+        filter.process(&mut audio);
+        let tail = settled(&audio);
+        let mean = tail.iter().sum::<f64>() / tail.len() as f64;
+        let peak = tail.iter().fold(0.0_f64, |m, s| m.max(s.abs()));
+        assert!(mean.abs() < 1e-4, "mean {}", mean);
+        assert!((peak - 0.2).abs() < 0.001, "peak {}", peak);
+        // Most of the offset is already removed in the first second
+        let offset = meter.offset().unwrap();
+        assert!((offset - 0.01).abs() < 0.001, "offset {}", offset);
+
+        // 20 Hz is hardly touched
+        filter.reset();
+        let mut bass: Vec<f64> = (0..4 * rate as usize).map(|n| 0.5 * sine(20.0, n)).collect();
+        filter.process(&mut bass);
+        let peak = settled(&bass).iter().fold(0.0_f64, |m, s| m.max(s.abs()));
+        assert!(peak > 0.49, "peak {}", peak);
+    }
+}
@@ -0,0 +1,110 @@
+//! Software gain (trim) for sources that are too quiet.
+//!
+//! Interfaces without a hardware gain control, or line-level sources into a
+//! microphone-level input, leave a lot of headroom unused. The trim raises
+//! (or lowers) the level before it is metered and recorded. It cannot add
+//! resolution the ADC did not capture, only scale what is there.
+//!
+//! Peaks that the gain pushes to or beyond full scale are counted in a
+//! [`GainClipMeter`], so the recorder can warn that the gain is too high.
+//! A [`Limiter`](super::limiter::Limiter) later in the chain catches them.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use super::AudioFilter;
+
+/// Largest trim accepted in either direction, in dB
+pub const MAX_GAIN_DB: f64 = 40.0;
+
+/// Shortest time between two warnings about clipping caused by the gain
+pub const CLIP_WARNING_SECONDS: f64 = 10.0;
+
+/// Number of samples a [`Gain`] pushed to full scale or beyond
+///
+/// Clones share the count, so the filter can sit in a
+/// [`FilterChain`](super::FilterChain) while the count is read elsewhere.
+#[derive(Debug, Clone, Default)]
+pub struct GainClipMeter {
+    clipped: Arc<AtomicU64>,
+}
+
+impl GainClipMeter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Samples clipped by the gain since the start
+    pub fn clipped(&self) -> u64 {
+        self.clipped.load(Ordering::Relaxed)
+    }
+}
+
+/// Constant gain for one channel
+#[derive(Debug, Clone)]
+pub struct Gain {
+    factor: f64,
+    meter: Option<GainClipMeter>,
+}
+
+impl Gain {
+    pub fn new(gain_db: f64) -> Self {
+        Gain {
+            factor: 10f64.powf(gain_db / 20.0),
+            meter: None,
+        }
+    }
+
+    /// Count the samples the gain clips in `meter`
+    pub fn with_meter(mut self, meter: GainClipMeter) -> Self {
+        self.meter = Some(meter);
+        self
+    }
+
+    /// Linear gain factor
+    pub fn factor(&self) -> f64 {
+        self.factor
+    }
+}
+
+impl AudioFilter for Gain {
+    fn process(&mut self, samples: &mut [f64]) {
+        let mut clipped = 0;
+        for sample in samples.iter_mut() {
+            let y = *sample * self.factor;
+            // Samples that were at full scale already are the ADC's
+            if y.abs() >= 1.0 && sample.abs() < 1.0 {
+                clipped += 1;
+            }
+            *sample = y;
+        }
+        if let Some(meter) = self.meter.as_ref().filter(|_| clipped > 0) {
+            meter.clipped.fetch_add(clipped, Ordering::Relaxed);
+        }
+    }
+
+    fn reset(&mut self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gain() {
+        let meter = GainClipMeter::new();
+        let mut gain = Gain::new(6.0).with_meter(meter.clone());
+        assert!((gain.factor() - 1.995).abs() < 0.001);
+
+        let mut samples = vec![0.25, -0.4, 0.6, -1.0];
+        gain.process(&mut samples);
+        assert!((samples[0] - 0.499).abs() < 0.001);
+        assert!((samples[1] + 0.798).abs() < 0.001);
+        // 0.6 clips because of the gain, -1.0 was clipped before
+        assert_eq!(meter.clipped(), 1);
+
+        let mut quiet = vec![0.5];
+        Gain::new(-6.0).process(&mut quiet);
+        assert!((quiet[0] - 0.2506).abs() < 0.001);
+    }
+}
@@ -0,0 +1,186 @@
+//! Soft-knee look-ahead peak limiter.
+//!
+//! For unpredictable sources (radio, tapes) an occasional peak would
+//! otherwise be clipped hard at full scale. The limiter delays the audio by
+//! the look-ahead time and lowers the gain smoothly before a peak arrives,
+//! so peaks come out at the ceiling without clipping. Levels in the knee
+//! below the ceiling are reduced gently; quieter audio passes unchanged
+//! (only delayed). After a peak the gain returns over the release time.
+//!
+//! The gain for a peak is the minimum of the wanted gains over the
+//! look-ahead window, averaged over another window of the same length. That
+//! ramp reaches the wanted gain exactly when the peak leaves the delay line.
+//!
+//! The limiter cannot repair samples the ADC already clipped; it catches
+//! peaks that the other filters push beyond full scale and keeps headroom
+//! below it. Each channel is limited on its own.
+
+use std::collections::VecDeque;
+
+use super::AudioFilter;
+
+/// Default ceiling in dBFS
+pub const DEFAULT_CEILING_DB: f64 = -1.0;
+
+/// Half width of the soft knee around the ceiling in dB
+pub const KNEE_DB: f64 = 6.0;
+
+/// Look-ahead (and delay) in seconds
+pub const LOOKAHEAD_SECONDS: f64 = 0.005;
+
+/// Time for the gain to recover by 63% after a peak
+pub const RELEASE_SECONDS: f64 = 0.1;
+
+/// Gain reduction in dB for a peak at `level_db`: none below the knee, a
+/// quadratic curve in the knee and hard limiting to the ceiling above it
+pub fn gain_reduction_db(level_db: f64, ceiling_db: f64) -> f64 {
+    let knee_start = ceiling_db - KNEE_DB;
+    if level_db <= knee_start {
+        0.0
+    } else if level_db <= ceiling_db + KNEE_DB {
+        // The knee curve meets the limit line at the ceiling + knee width,
+        // staying below the ceiling all the way
+        let over = level_db - knee_start;
+        -(over * over) / (4.0 * KNEE_DB)
+    } else {
+        ceiling_db - level_db
+    }
+}
+
+/// Look-ahead limiter for one channel
+#[derive(Debug, Clone)]
+pub struct Limiter {
+    ceiling_db: f64,
+    window: usize,
+    release: f64,
+    delay: VecDeque<f64>,
+    /// Wanted gains of the look-ahead window, as (sample number, gain)
+    /// with increasing gains (monotonic queue for the sliding minimum)
+    minimum: VecDeque<(u64, f64)>,
+    /// Last window of sliding minimums and their sum
+    minimums: VecDeque<f64>,
+    minimum_sum: f64,
+    gain: f64,
+    samples: u64,
+}
+
+impl Limiter {
+    pub fn new(sample_rate: u32, ceiling_db: f64) -> Self {
+        let window = ((sample_rate as f64 * LOOKAHEAD_SECONDS) as usize).max(1);
+        Limiter {
+            ceiling_db,
+            window,
+            release: 1.0 - (-1.0 / (sample_rate as f64 * RELEASE_SECONDS)).exp(),
+            delay: VecDeque::with_capacity(window),
+            minimum: VecDeque::new(),
+            minimums: VecDeque::with_capacity(window),
+            minimum_sum: 0.0,
+            gain: 1.0,
+            samples: 0,
+        }
+    }
+
+    pub fn ceiling_db(&self) -> f64 {
+        self.ceiling_db
+    }
+
+    /// Delay in samples
+    pub fn latency(&self) -> usize {
+        self.window - 1
+    }
+
+    fn process_sample(&mut self, x: f64) -> f64 {
+        let level_db = 20.0 * x.abs().max(1e-10).log10();
+        let wanted = 10f64.powf(gain_reduction_db(level_db, self.ceiling_db) / 20.0);
+
+        // Sliding minimum of the wanted gains
+        while self.minimum.back().is_some_and(|&(_, g)| g >= wanted) {
+            self.minimum.pop_back();
+        }
+        self.minimum.push_back((self.samples, wanted));
+        if self.minimum.front().is_some_and(|&(n, _)| n + self.window as u64 <= self.samples) {
+            self.minimum.pop_front();
+        }
+        self.samples += 1;
+        let minimum = self.minimum.front().map_or(1.0, |&(_, g)| g);
+
+        // Moving average of the minimums; missing history counts as 1.0
+        self.minimums.push_back(minimum);
+        self.minimum_sum += minimum;
+        if self.minimums.len() > self.window {
+            self.minimum_sum -= self.minimums.pop_front().unwrap_or(1.0);
+        }
+        let missing = self.window - self.minimums.len();
+        let target = (self.minimum_sum + missing as f64) / self.window as f64;
+
+        // Follow reductions at once (the ramp is already smooth), recover slowly
+        self.gain = if target < self.gain { target } else { self.gain + (target - self.gain) * self.release };
+
+        self.delay.push_back(x);
+        let delayed = if self.delay.len() > self.latency() { self.delay.pop_front().unwrap_or(0.0) } else { 0.0 };
+        delayed * self.gain
+    }
+}
+
+impl AudioFilter for Limiter {
+    fn process(&mut self, samples: &mut [f64]) {
+        for sample in samples.iter_mut() {
+            *sample = self.process_sample(*sample);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.delay.clear();
+        self.minimum.clear();
+        self.minimums.clear();
+        self.minimum_sum = 0.0;
+        self.gain = 1.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gain_curve() {
+        let ceiling = DEFAULT_CEILING_DB;
+        assert_eq!(gain_reduction_db(-20.0, ceiling), 0.0);
+        assert_eq!(gain_reduction_db(ceiling - KNEE_DB, ceiling), 0.0);
+        // Soft in the knee, never above the ceiling, continuous at its end
+        let mut previous = f64::MIN;
+        for tenth in 0..=200 {
+            let level = ceiling - KNEE_DB + tenth as f64 * 0.1;
+            let out = level + gain_reduction_db(level, ceiling);
+            assert!(out <= ceiling + 1e-9 && out >= previous - 1e-9, "{} -> {}", level, out);
+            previous = out;
+        }
+        assert!((gain_reduction_db(ceiling + KNEE_DB, ceiling) + KNEE_DB).abs() < 1e-9);
+        assert_eq!(gain_reduction_db(ceiling + 10.0, ceiling), -10.0);
+    }
+
+    #[test]
+    fn test_limiter() {
+        let rate = 48000;
+        let sine = |amplitude: f64, n: usize| amplitude * (2.0 * std::f64::consts::PI * 1000.0 * n as f64 / rate as f64).sin();
+        let ceiling = 10f64.powf(DEFAULT_CEILING_DB / 20.0);
+
+        // Quiet music passes unchanged, only delayed
+        let mut limiter = Limiter::new(rate, DEFAULT_CEILING_DB);
+        let quiet: Vec<f64> = (0..rate as usize / 10).map(|n| sine(0.1, n)).collect();
+        let mut out = quiet.clone();
+        limiter.process(&mut out);
+        let latency = limiter.latency();
+        assert!(out[latency..].iter().zip(&quiet).all(|(y, x)| (y - x).abs() < 1e-12));
+
+        // A burst 6 dB over full scale stays under the ceiling
+        limiter.reset();
+        let mut audio: Vec<f64> = (0..rate as usize).map(|n| sine(if (20000..22000).contains(&n) { 2.0 } else { 0.1 }, n)).collect();
+        limiter.process(&mut audio);
+        let peak = audio.iter().fold(0.0_f64, |m, s| m.max(s.abs()));
+        assert!(peak <= ceiling + 1e-9 && peak > ceiling - 0.05, "peak {}", peak);
+        // and the gain has recovered half a second later
+        let tail = audio[rate as usize - 1000..].iter().fold(0.0_f64, |m, s| m.max(s.abs()));
+        assert!((tail - 0.1).abs() < 0.001, "tail {}", tail);
+    }
+}
@@ -0,0 +1,186 @@
+//! Filters applied to captured audio before it is metered and recorded.
+//!
+//! Filters work on samples normalized to ±1.0 and keep their state between
+//! chunks. A [`FilterChain`] holds one instance of each filter per channel and
+//! converts the integer samples of the input stream.
+//!
+//! Available filters:
+//! - DC blocker ([`dc::DcBlocker`])
+//! - Software gain (trim) ([`gain::Gain`])
+//! - RIAA de-emphasis ([`riaa::RiaaFilter`])
+//! - Subsonic (rumble) high-pass ([`subsonic::SubsonicFilter`])
+//! - Soft-knee look-ahead peak limiter ([`limiter::Limiter`])
+
+pub mod dc;
+pub mod gain;
+pub mod limiter;
+pub mod riaa;
+pub mod subsonic;
+
+use crate::SampleFormat;
+
+/// A filter for a single channel of normalized (±1.0) samples
+pub trait AudioFilter: Send {
+    /// Filter samples in place
+    fn process(&mut self, samples: &mut [f64]);
+
+    /// Clear the filter state
+    fn reset(&mut self);
+}
+
+/// Second-order IIR section (transposed direct form II)
+///
+/// Coefficients are normalized so that a0 = 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Biquad {
+    pub b0: f64,
+    pub b1: f64,
+    pub b2: f64,
+    pub a1: f64,
+    pub a2: f64,
+    z1: f64,
+    z2: f64,
+}
+
+impl Biquad {
+    /// Create a section from unnormalized coefficients
+    pub fn new(b0: f64, b1: f64, b2: f64, a0: f64, a1: f64, a2: f64) -> Self {
+        Biquad {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    pub fn process_sample(&mut self, x: f64) -> f64 {
+        let y = self.b0 * x + self.z1;
+        self.z1 = self.b1 * x - self.a1 * y + self.z2;
+        self.z2 = self.b2 * x - self.a2 * y;
+        y
+    }
+
+    /// Magnitude of the frequency response at `frequency` Hz
+    pub fn magnitude(&self, frequency: f64, sample_rate: u32) -> f64 {
+        let w = 2.0 * std::f64::consts::PI * frequency / sample_rate as f64;
+        // Evaluate numerator and denominator at z = e^jw
+        let (c1, s1) = (w.cos(), -w.sin());
+        let (c2, s2) = ((2.0 * w).cos(), -(2.0 * w).sin());
+        let num_re = self.b0 + self.b1 * c1 + self.b2 * c2;
+        let num_im = self.b1 * s1 + self.b2 * s2;
+        let den_re = 1.0 + self.a1 * c1 + self.a2 * c2;
+        let den_im = self.a1 * s1 + self.a2 * s2;
+        (num_re.hypot(num_im)) / (den_re.hypot(den_im))
+    }
+
+    pub fn reset(&mut self) {
+        self.z1 = 0.0;
+        self.z2 = 0.0;
+    }
+}
+
+/// Filters applied to every channel of a stream
+pub struct FilterChain {
+    full_scale: f64,
+    channels: Vec<Vec<Box<dyn AudioFilter>>>,
+    buffer: Vec<f64>,
+}
+
+impl FilterChain {
+    pub fn new(channels: usize, format: SampleFormat) -> Self {
+        FilterChain {
+            full_scale: format.max_value(),
+            channels: (0..channels).map(|_| Vec::new()).collect(),
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Append a filter; `create` is called once per channel
+    pub fn add<F>(&mut self, create: F)
+    where
+        F: Fn() -> Box<dyn AudioFilter>,
+    {
+        self.add_per_channel(|_| create());
+    }
+
+    /// Append a filter; `create` is called with each channel's index
+    pub fn add_per_channel<F>(&mut self, create: F)
+    where
+        F: Fn(usize) -> Box<dyn AudioFilter>,
+    {
+        for (channel, filters) in self.channels.iter_mut().enumerate() {
+            filters.push(create(channel));
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.channels.iter().all(|f| f.is_empty())
+    }
+
+    /// Filter a chunk of per-channel samples in place
+    ///
+    /// Results beyond full scale are clipped.
+    pub fn process(&mut self, audio: &mut [Vec<i32>]) {
+        let full_scale = self.full_scale;
+        for (samples, filters) in audio.iter_mut().zip(self.channels.iter_mut()) {
+            if filters.is_empty() {
+                continue;
+            }
+            self.buffer.clear();
+            self.buffer.extend(samples.iter().map(|&s| s as f64 / full_scale));
+            for filter in filters.iter_mut() {
+                filter.process(&mut self.buffer);
+            }
+            for (sample, &y) in samples.iter_mut().zip(self.buffer.iter()) {
+                *sample = (y * full_scale).round().clamp(-full_scale, full_scale - 1.0) as i32;
+            }
+        }
+    }
+
+    pub fn reset(&mut self) {
+        for filter in self.channels.iter_mut().flatten() {
+            filter.reset();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Halve;
+
+    impl AudioFilter for Halve {
+        fn process(&mut self, samples: &mut [f64]) {
+            samples.iter_mut().for_each(|s| *s *= 0.5);
+        }
+
+        fn reset(&mut self) {}
+    }
+
+    #[test]
+    fn test_biquad_passthrough() {
+        let mut section = Biquad::new(2.0, 0.0, 0.0, 2.0, 0.0, 0.0);
+        assert_eq!(section.process_sample(0.25), 0.25);
+        assert!((section.magnitude(1000.0, 48000) - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_filter_chain() {
+        let mut chain = FilterChain::new(2, SampleFormat::S16);
+        assert!(chain.is_empty());
+
+        let mut audio = vec![vec![1000, -32768], vec![32767, 0]];
+        chain.process(&mut audio);
+        assert_eq!(audio, vec![vec![1000, -32768], vec![32767, 0]]);
+
+        chain.add(|| Box::new(Halve));
+        chain.add(|| Box::new(Halve));
+        assert!(!chain.is_empty());
+        chain.process(&mut audio);
+        assert_eq!(audio, vec![vec![250, -8192], vec![8192, 0]]);
+    }
+}
@@ -0,0 +1,162 @@
+//! RIAA de-emphasis for recordings made through a flat (non-phono) preamp.
+//!
+//! The playback curve has poles at 3180 µs and 75 µs and a zero at 318 µs:
+//!
+//! ```text
+//! H(s) = (1 + s·318µs) / ((1 + s·3180µs) · (1 + s·75µs))
+//! ```
+//!
+//! It is implemented as two first-order sections (shelf and high-frequency
+//! roll-off) in a biquad cascade. Poles and zero are mapped with the matched
+//! z-transform; an extra zero in the roll-off section, fitted to the analog
+//! curve when the filter is created, corrects the treble error of that
+//! mapping (within ±0.35 dB up to 20 kHz at 44.1 kHz). The gain is normalized
+//! to 0 dB at 1 kHz, so bass is boosted by about 19 dB at 20 Hz.
+
+use super::{AudioFilter, Biquad};
+
+/// Bass turnover time constant (50 Hz)
+pub const T_BASS: f64 = 3180e-6;
+/// Bass shelf time constant (500 Hz)
+pub const T_SHELF: f64 = 318e-6;
+/// Treble roll-off time constant (2122 Hz)
+pub const T_TREBLE: f64 = 75e-6;
+
+/// Frequency at which the curve has unity gain
+pub const REFERENCE_FREQUENCY: f64 = 1000.0;
+
+/// Response of the analog RIAA playback curve in dB, 0 dB at 1 kHz
+pub fn riaa_response_db(frequency: f64) -> f64 {
+    let magnitude = |f: f64| {
+        let w = 2.0 * std::f64::consts::PI * f;
+        (1.0 + (w * T_SHELF).powi(2)).sqrt()
+            / ((1.0 + (w * T_BASS).powi(2)).sqrt() * (1.0 + (w * T_TREBLE).powi(2)).sqrt())
+    };
+    20.0 * (magnitude(frequency) / magnitude(REFERENCE_FREQUENCY)).log10()
+}
+
+/// Pole or zero of a first-order corner, mapped with the matched z-transform
+fn matched(tau: f64, sample_rate: u32) -> f64 {
+    (-1.0 / (sample_rate as f64 * tau)).exp()
+}
+
+/// RIAA playback equalization for one channel
+#[derive(Debug, Clone)]
+pub struct RiaaFilter {
+    sections: Vec<Biquad>,
+    gain: f64,
+}
+
+impl RiaaFilter {
+    pub fn new(sample_rate: u32) -> Self {
+        // Fit the correction zero by minimizing the largest deviation from
+        // the analog curve (the error is unimodal in the zero position)
+        let (mut low, mut high) = (-0.9, 0.0);
+        for _ in 0..60 {
+            let a = low + (high - low) / 3.0;
+            let b = high - (high - low) / 3.0;
+            if Self::with_zero(sample_rate, a).max_deviation_db(sample_rate)
+                < Self::with_zero(sample_rate, b).max_deviation_db(sample_rate)
+            {
+                high = b;
+            } else {
+                low = a;
+            }
+        }
+        Self::with_zero(sample_rate, (low + high) / 2.0)
+    }
+
+    fn with_zero(sample_rate: u32, correction_zero: f64) -> Self {
+        let sections = vec![
+            // (1 + s·T_SHELF) / (1 + s·T_BASS)
+            Biquad::new(1.0, -matched(T_SHELF, sample_rate), 0.0, 1.0, -matched(T_BASS, sample_rate), 0.0),
+            // 1 / (1 + s·T_TREBLE), with the correction zero
+            Biquad::new(1.0, -correction_zero, 0.0, 1.0, -matched(T_TREBLE, sample_rate), 0.0),
+        ];
+
+        let mut filter = RiaaFilter { sections, gain: 1.0 };
+        filter.gain = 1.0 / filter.magnitude(REFERENCE_FREQUENCY, sample_rate);
+        filter
+    }
+
+    /// Largest deviation from the analog curve between 20 Hz and 20 kHz
+    /// (or 45% of the sample rate, if lower)
+    fn max_deviation_db(&self, sample_rate: u32) -> f64 {
+        let top = (0.45 * sample_rate as f64).min(20000.0);
+        let steps = 64;
+        (0..=steps)
+            .map(|i| 20.0 * (top / 20.0).powf(i as f64 / steps as f64))
+            .map(|f| (20.0 * self.magnitude(f, sample_rate).log10() - riaa_response_db(f)).abs())
+            .fold(0.0, f64::max)
+    }
+
+    /// Magnitude of the response at `frequency` Hz (1.0 at 1 kHz)
+    pub fn magnitude(&self, frequency: f64, sample_rate: u32) -> f64 {
+        self.sections
+            .iter()
+            .map(|s| s.magnitude(frequency, sample_rate))
+            .product::<f64>()
+            * self.gain
+    }
+}
+
+impl AudioFilter for RiaaFilter {
+    fn process(&mut self, samples: &mut [f64]) {
+        for sample in samples.iter_mut() {
+            let mut y = *sample * self.gain;
+            for section in &mut self.sections {
+                y = section.process_sample(y);
+            }
+            *sample = y;
+        }
+    }
+
+    fn reset(&mut self) {
+        self.sections.iter_mut().for_each(Biquad::reset);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response_db(filter: &RiaaFilter, frequency: f64, rate: u32) -> f64 {
+        20.0 * filter.magnitude(frequency, rate).log10()
+    }
+
+    #[test]
+    fn test_riaa_curve() {
+        for rate in [44100, 48000, 96000] {
+            let filter = RiaaFilter::new(rate);
+            assert!(response_db(&filter, 1000.0, rate).abs() < 1e-9);
+            // Reference values of the RIAA playback curve
+            assert!((response_db(&filter, 20.0, rate) - 19.27).abs() < 0.1);
+            assert!((response_db(&filter, 100.0, rate) - 13.09).abs() < 0.1);
+            assert!(filter.max_deviation_db(rate) < 0.4);
+        }
+        assert!((riaa_response_db(10000.0) + 13.73).abs() < 0.01);
+        assert!((riaa_response_db(20000.0) + 19.62).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_riaa_process_matches_response() {
+        let rate = 48000;
+        let mut filter = RiaaFilter::new(rate);
+
+        // Settle on a 100 Hz sine, then measure its amplitude
+        let samples: Vec<f64> = (0..rate as usize)
+            .map(|n| 0.01 * (2.0 * std::f64::consts::PI * 100.0 * n as f64 / rate as f64).sin())
+            .collect();
+        let mut output = samples.clone();
+        filter.process(&mut output);
+        let peak = output[rate as usize / 2..].iter().fold(0.0_f64, |m, s| m.max(s.abs()));
+
+        let expected = 0.01 * filter.magnitude(100.0, rate);
+        assert!((peak - expected).abs() / expected < 0.01);
+
+        filter.reset();
+        let mut silence = vec![0.0; 10];
+        filter.process(&mut silence);
+        assert!(silence.iter().all(|&s| s == 0.0));
+    }
+}
@@ -0,0 +1,118 @@
+//! Subsonic (rumble) high-pass filter.
+//!
+//! Turntable rumble and the slow movement of warped records put energy
+//! below 20 Hz that nobody hears but that eats headroom. The filter is a
+//! 4th-order Butterworth high-pass (24 dB/octave), built from two biquad
+//! sections designed with the bilinear transform. It is -3 dB at the corner
+//! frequency and flat (within 0.1 dB) from about 2.5 times the corner up.
+
+use super::{AudioFilter, Biquad};
+
+/// Default corner frequency in Hz
+pub const DEFAULT_CORNER_HZ: f64 = 20.0;
+
+/// Q of the two sections of a 4th-order Butterworth filter
+const SECTION_Q: [f64; 2] = [0.541_196_100_146_197, 1.306_562_964_876_376_7];
+
+/// High-pass section (RBJ audio EQ cookbook)
+fn high_pass(corner: f64, q: f64, sample_rate: u32) -> Biquad {
+    let w0 = 2.0 * std::f64::consts::PI * corner / sample_rate as f64;
+    let alpha = w0.sin() / (2.0 * q);
+    let cos = w0.cos();
+    Biquad::new(
+        (1.0 + cos) / 2.0,
+        -(1.0 + cos),
+        (1.0 + cos) / 2.0,
+        1.0 + alpha,
+        -2.0 * cos,
+        1.0 - alpha,
+    )
+}
+
+/// Subsonic high-pass filter for one channel
+#[derive(Debug, Clone)]
+pub struct SubsonicFilter {
+    sections: Vec<Biquad>,
+    corner: f64,
+}
+
+impl SubsonicFilter {
+    pub fn new(sample_rate: u32, corner: f64) -> Self {
+        SubsonicFilter {
+            sections: SECTION_Q.iter().map(|&q| high_pass(corner, q, sample_rate)).collect(),
+            corner,
+        }
+    }
+
+    /// Corner (-3 dB) frequency in Hz
+    pub fn corner(&self) -> f64 {
+        self.corner
+    }
+
+    /// Magnitude of the response at `frequency` Hz
+    pub fn magnitude(&self, frequency: f64, sample_rate: u32) -> f64 {
+        self.sections
+            .iter()
+            .map(|s| s.magnitude(frequency, sample_rate))
+            .product()
+    }
+}
+
+impl AudioFilter for SubsonicFilter {
+    fn process(&mut self, samples: &mut [f64]) {
+        for sample in samples.iter_mut() {
+            let mut y = *sample;
+            for section in &mut self.sections {
+                y = section.process_sample(y);
+            }
+            *sample = y;
+        }
+    }
+
+    fn reset(&mut self) {
+        self.sections.iter_mut().for_each(Biquad::reset);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response_db(filter: &SubsonicFilter, frequency: f64, rate: u32) -> f64 {
+        20.0 * filter.magnitude(frequency, rate).log10()
+    }
+
+    #[test]
+    fn test_subsonic_response() {
+        for rate in [44100, 48000, 96000] {
+            let filter = SubsonicFilter::new(rate, DEFAULT_CORNER_HZ);
+            assert!((response_db(&filter, 20.0, rate) + 3.01).abs() < 0.05);
+            // 24 dB per octave below the corner
+            assert!((response_db(&filter, 10.0, rate) + 24.1).abs() < 0.2);
+            assert!(response_db(&filter, 50.0, rate).abs() < 0.1);
+            assert!(response_db(&filter, 1000.0, rate).abs() < 0.001);
+        }
+        let filter = SubsonicFilter::new(48000, 30.0);
+        assert_eq!(filter.corner(), 30.0);
+        assert!((response_db(&filter, 30.0, 48000) + 3.01).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_subsonic_removes_rumble() {
+        let rate = 48000;
+        let sine = |frequency: f64, n: usize| (2.0 * std::f64::consts::PI * frequency * n as f64 / rate as f64).sin();
+        let settled_peak = |samples: &[f64]| samples[samples.len() / 2..].iter().fold(0.0_f64, |m, s| m.max(s.abs()));
+
+        // 5 Hz warp rumble with a DC offset
+        let mut filter = SubsonicFilter::new(rate, DEFAULT_CORNER_HZ);
+        let mut rumble: Vec<f64> = (0..2 * rate as usize).map(|n| 0.3 * sine(5.0, n) + 0.05).collect();
+        filter.process(&mut rumble);
+        assert!(settled_peak(&rumble) < 0.002, "rumble {}", settled_peak(&rumble));
+
+        // A 1 kHz tone passes unchanged in level
+        filter.reset();
+        let mut tone: Vec<f64> = (0..rate as usize).map(|n| 0.1 * sine(1000.0, n)).collect();
+        filter.process(&mut tone);
+        assert!((settled_peak(&tone) - 0.1).abs() < 0.0005);
+    }
+}
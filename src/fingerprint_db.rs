@@ -0,0 +1,311 @@
+//! Local fingerprint database of songs identified before.
+//!
+//! Every clip that Shazam identifies is also fingerprinted with Chromaprint
+//! (`fpcalc -raw`) and stored with its song in
+//! `~/.local/share/autorec/fpdb/fingerprints.jsonl` (or under
+//! `$XDG_DATA_HOME`). When the same record is digitized or analyzed again,
+//! its clips are found here by comparing fingerprints, without a network
+//! call. Without `fpcalc` the database is not used.
+//!
+//! Raw Chromaprint fingerprints are sequences of 32-bit items, about eight
+//! per second. Two clips of the same music are aligned by voting on the
+//! offsets of items with equal upper bits and compared by the share of
+//! equal bits in the overlap. The alignment also carries the clip's
+//! position within the song over from the stored clip.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+
+use crate::album_identifier::IdentifiedSong;
+use crate::error::{Error, Result};
+
+/// Name of the database file in the database directory
+pub const DB_FILE_NAME: &str = "fingerprints.jsonl";
+
+/// Smallest share of equal bits for two clips to be the same music
+pub const MIN_SIMILARITY: f64 = 0.75;
+
+/// Fewest overlapping items (about 10 seconds) compared at an offset
+const MIN_OVERLAP_ITEMS: usize = 80;
+
+/// Fewest items that must agree on an offset before it is compared
+const MIN_OFFSET_VOTES: usize = 3;
+
+/// Items are matched for offset voting on their upper 20 bits
+const VOTE_SHIFT: u32 = 12;
+
+/// Time between fingerprint items: Chromaprint analyzes 11025 Hz audio in
+/// frames of 4096 samples that overlap by two thirds
+const ITEM_SECONDS: f64 = 4096.0 / 3.0 / 11025.0;
+
+/// A stored clip fingerprint and the song it belongs to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FingerprintEntry {
+    pub artist: String,
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub album: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recording_id: Option<String>,
+    /// Position within the song (seconds) at which the clip starts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub song_offset: Option<f64>,
+    pub fingerprint: Vec<u32>,
+}
+
+impl FingerprintEntry {
+    pub fn new(song: &IdentifiedSong, fingerprint: Vec<u32>) -> Self {
+        FingerprintEntry {
+            artist: song.artist.clone(),
+            title: song.title.clone(),
+            album: song.album.clone(),
+            recording_id: song.recording_id.clone(),
+            song_offset: song.song_offset,
+            fingerprint,
+        }
+    }
+}
+
+/// A stored clip found by [`FingerprintDb::lookup`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FingerprintMatch<'a> {
+    pub entry: &'a FingerprintEntry,
+    /// Share of equal bits (0–1)
+    pub similarity: f64,
+    /// Start of the looked-up clip relative to the stored one (seconds)
+    pub offset_seconds: f64,
+}
+
+impl FingerprintMatch<'_> {
+    /// The stored song, found at `timestamp` in a recording
+    pub fn to_song(&self, timestamp: f64) -> IdentifiedSong {
+        let entry = self.entry;
+        IdentifiedSong {
+            timestamp,
+            title: entry.title.clone(),
+            artist: entry.artist.clone(),
+            album: entry.album.clone(),
+            match_offsets: Vec::new(),
+            song_offset: entry.song_offset.map(|offset| offset + self.offset_seconds),
+            recording_id: entry.recording_id.clone(),
+            confidence: None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct RawFpcalcOutput {
+    fingerprint: Vec<i64>,
+}
+
+/// Raw Chromaprint fingerprint of an audio file (`fpcalc -raw`)
+pub fn raw_fingerprint_file(path: &str) -> Result<Vec<u32>> {
+    let output = Command::new("fpcalc")
+        .args(["-raw", "-json", path])
+        .output()
+        .map_err(|e| Error::Backend(format!("Error running fpcalc (is chromaprint installed?): {}", e)))?;
+    if !output.status.success() {
+        return Err(Error::Decode(format!("fpcalc failed: {}", String::from_utf8_lossy(&output.stderr).trim())));
+    }
+    let parsed: RawFpcalcOutput = serde_json::from_slice(&output.stdout)?;
+    // Depending on the version, items are printed signed or unsigned
+    Ok(parsed.fingerprint.into_iter().map(|item| item as u32).collect())
+}
+
+/// Share of equal bits where `a` and `b` overlap when `b` starts at
+/// `offset` items into `a`; None if they overlap too little
+fn similarity_at(a: &[u32], b: &[u32], offset: isize) -> Option<f64> {
+    let (a, b) = if offset >= 0 { (a.get(offset as usize..)?, b) } else { (a, b.get(offset.unsigned_abs()..)?) };
+    let overlap = a.len().min(b.len());
+    if overlap < MIN_OVERLAP_ITEMS {
+        return None;
+    }
+    let differing: u32 = a.iter().zip(b).map(|(x, y)| (x ^ y).count_ones()).sum();
+    Some(1.0 - differing as f64 / (overlap as f64 * 32.0))
+}
+
+/// Similarity of two fingerprints at their best alignment (0–1); None if
+/// no alignment with enough overlap was found
+pub fn similarity(a: &[u32], b: &[u32]) -> Option<f64> {
+    alignment(a, b).map(|(_, similarity)| similarity)
+}
+
+/// Best alignment of two fingerprints: the number of items `b` starts
+/// after `a`, and the similarity there
+fn alignment(a: &[u32], b: &[u32]) -> Option<(isize, f64)> {
+    let mut positions: HashMap<u32, Vec<usize>> = HashMap::new();
+    for (i, item) in a.iter().enumerate() {
+        positions.entry(item >> VOTE_SHIFT).or_default().push(i);
+    }
+    let mut votes: HashMap<isize, usize> = HashMap::new();
+    for (j, item) in b.iter().enumerate() {
+        for &i in positions.get(&(item >> VOTE_SHIFT)).into_iter().flatten() {
+            *votes.entry(i as isize - j as isize).or_default() += 1;
+        }
+    }
+    let mut offsets: Vec<(isize, usize)> = votes.into_iter().filter(|&(_, n)| n >= MIN_OFFSET_VOTES).collect();
+    offsets.sort_by(|x, y| y.1.cmp(&x.1).then(x.0.cmp(&y.0)));
+    offsets
+        .iter()
+        .take(3)
+        .filter_map(|&(offset, _)| Some((offset, similarity_at(a, b, offset)?)))
+        .max_by(|x, y| x.1.total_cmp(&y.1))
+}
+
+/// Fingerprints of the clips identified so far
+#[derive(Debug, Default)]
+pub struct FingerprintDb {
+    path: PathBuf,
+    entries: Vec<FingerprintEntry>,
+}
+
+impl FingerprintDb {
+    /// `$XDG_DATA_HOME/autorec/fpdb`, or `~/.local/share/autorec/fpdb`
+    pub fn default_dir() -> Option<PathBuf> {
+        std::env::var_os("XDG_DATA_HOME")
+            .filter(|d| !d.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))
+            .map(|data| data.join("autorec").join("fpdb"))
+    }
+
+    /// Open the database in `dir`; empty if it does not exist yet.
+    /// Lines that cannot be read are skipped.
+    pub fn open(dir: &Path) -> Result<Self> {
+        let path = dir.join(DB_FILE_NAME);
+        let entries = match fs::read_to_string(&path) {
+            Ok(content) => content.lines().filter_map(|line| serde_json::from_str(line).ok()).collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(FingerprintDb { path, entries })
+    }
+
+    /// The database in the default directory, if `fpcalc` is installed
+    pub fn open_default() -> Option<Self> {
+        Command::new("fpcalc").arg("-version").output().ok().filter(|o| o.status.success())?;
+        Self::open(&Self::default_dir()?).ok()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The stored clip most similar to `fingerprint`
+    pub fn lookup(&self, fingerprint: &[u32]) -> Option<FingerprintMatch<'_>> {
+        self.entries
+            .iter()
+            .filter_map(|entry| {
+                let (offset, similarity) = alignment(&entry.fingerprint, fingerprint)?;
+                Some(FingerprintMatch { entry, similarity, offset_seconds: offset as f64 * ITEM_SECONDS })
+            })
+            .filter(|m| m.similarity >= MIN_SIMILARITY)
+            .max_by(|x, y| x.similarity.total_cmp(&y.similarity))
+    }
+
+    /// Store a clip fingerprint, unless it is already known as this song
+    pub fn add(&mut self, entry: FingerprintEntry) -> Result<()> {
+        let known = self.lookup(&entry.fingerprint).is_some_and(|m| {
+            m.entry.artist.eq_ignore_ascii_case(&entry.artist) && m.entry.title.eq_ignore_ascii_case(&entry.title)
+        });
+        if known {
+            return Ok(());
+        }
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = fs::OpenOptions::new().create(true).append(true).open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// Identify a clip file from the database, as found at `timestamp`
+    pub fn identify_clip(&self, clip_path: &str, timestamp: f64) -> Option<IdentifiedSong> {
+        if self.is_empty() {
+            return None;
+        }
+        let fingerprint = raw_fingerprint_file(clip_path).ok()?;
+        self.lookup(&fingerprint).map(|m| m.to_song(timestamp))
+    }
+
+    /// Remember that a clip file was identified as `song`
+    pub fn remember_clip(&mut self, clip_path: &str, song: &IdentifiedSong) -> Result<()> {
+        let fingerprint = raw_fingerprint_file(clip_path)?;
+        self.add(FingerprintEntry::new(song, fingerprint))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pseudo-random fingerprint items
+    fn items(seed: u32, n: usize) -> Vec<u32> {
+        let mut x = seed;
+        (0..n)
+            .map(|_| {
+                x = x.wrapping_mul(1664525).wrapping_add(1013904223);
+                x
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_similarity() {
+        let song = items(1, 400);
+        // A clip from 50 items in, with a few low bits changed (noise)
+        let clip: Vec<u32> = song[50..290].iter().enumerate().map(|(i, x)| if i % 4 == 0 { x ^ 0x5 } else { *x }).collect();
+        let s = similarity(&song, &clip).unwrap();
+        assert!(s > 0.95, "{}", s);
+        assert!(similarity(&clip, &song).unwrap() > 0.95);
+
+        // Different music does not align
+        assert!(similarity(&song, &items(2, 240)).is_none_or(|s| s < MIN_SIMILARITY));
+        // Too short to compare
+        assert!(similarity(&song, &song[..40]).is_none());
+    }
+
+    #[test]
+    fn test_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let song = IdentifiedSong {
+            timestamp: 60.0,
+            title: "Dogs".to_string(),
+            artist: "Pink Floyd".to_string(),
+            album: Some("Animals".to_string()),
+            match_offsets: Vec::new(),
+            song_offset: Some(12.0),
+            recording_id: None,
+            confidence: None,
+        };
+        let mut db = FingerprintDb::open(dir.path()).unwrap();
+        assert!(db.is_empty());
+        db.add(FingerprintEntry::new(&song, items(1, 240))).unwrap();
+        // The same clip again is not stored twice
+        db.add(FingerprintEntry::new(&song, items(1, 240))).unwrap();
+        db.add(FingerprintEntry::new(&song, items(3, 240))).unwrap();
+
+        let db = FingerprintDb::open(dir.path()).unwrap();
+        assert_eq!(db.len(), 2);
+        let found = db.lookup(&items(1, 400)[20..200]).unwrap();
+        assert_eq!((found.entry.title.as_str(), found.entry.album.as_deref()), ("Dogs", Some("Animals")));
+        assert_eq!(found.similarity, 1.0);
+        assert!(db.lookup(&items(4, 240)).is_none());
+
+        // The clip starts 20 items after the stored one, 12 s into the song
+        let found_song = found.to_song(120.0);
+        assert_eq!(found_song.timestamp, 120.0);
+        let song_offset = found_song.song_offset.unwrap();
+        assert!((song_offset - (12.0 + 20.0 * ITEM_SECONDS)).abs() < 1e-9, "{}", song_offset);
+    }
+}
@@ -0,0 +1,244 @@
+//! Button and status LEDs on GPIO lines (cargo feature `gpio`).
+//!
+//! Lets `autorecd` run on a box without a screen: a short press of the
+//! button arms or disarms the recorder, holding it splits the running
+//! recording. LEDs show the daemon state:
+//! - armed LED: on while armed
+//! - recording LED: on while recording, blinking while paused
+//! - clip LED: on while the VU meter shows CLIP on any channel
+//!
+//! Like the MQTT client (see [`crate::mqtt`]) the lines are served by a
+//! thread of their own that reads the shared
+//! [`DaemonState`](crate::control_api::DaemonState) and sends commands to
+//! the capture loop. The kernel GPIO API can't set a pull-up; a button
+//! connecting the line to ground needs one in `config.txt`, e.g.
+//! `gpio=17=ip,pu`.
+
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use gpio_cdev::{Chip, LineHandle, LineRequestFlags};
+use tracing::{info, warn};
+
+use crate::config::GpioConfig;
+use crate::control_api::{ApiCommand, DaemonState, SharedState};
+use crate::error::{Error, Result};
+
+pub const DEFAULT_GPIO_CHIP: &str = "/dev/gpiochip0";
+
+/// Seconds the button is held to split unless the config sets `long_press`
+pub const DEFAULT_LONG_PRESS: f64 = 1.5;
+
+/// How often the button is read and the LEDs are set
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Shorter presses are contact bounce
+const DEBOUNCE: Duration = Duration::from_millis(50);
+
+/// Half period of the blinking recording LED
+const BLINK: Duration = Duration::from_millis(500);
+
+/// Lines from the `[gpio]` section
+#[derive(Debug, Clone, PartialEq)]
+pub struct GpioSettings {
+    pub chip: String,
+    pub button_pin: Option<u32>,
+    pub button_active_low: bool,
+    pub long_press: Duration,
+    pub armed_led: Option<u32>,
+    pub recording_led: Option<u32>,
+    pub clip_led: Option<u32>,
+}
+
+impl GpioSettings {
+    /// Settings of `config`; None if neither a button nor an LED is set
+    pub fn from_config(config: &GpioConfig) -> Result<Option<Self>> {
+        if config.button_pin.is_none() && config.armed_led.is_none() && config.recording_led.is_none() && config.clip_led.is_none() {
+            return Ok(None);
+        }
+        let long_press = config.long_press.unwrap_or(DEFAULT_LONG_PRESS);
+        if !long_press.is_finite() || long_press <= 0.0 {
+            return Err(Error::Config(format!("Invalid GPIO long_press: {}", long_press)));
+        }
+        Ok(Some(GpioSettings {
+            chip: config.chip.clone().unwrap_or_else(|| DEFAULT_GPIO_CHIP.to_string()),
+            button_pin: config.button_pin,
+            button_active_low: config.button_active_low.unwrap_or(true),
+            long_press: Duration::from_secs_f64(long_press),
+            armed_led: config.armed_led,
+            recording_led: config.recording_led,
+            clip_led: config.clip_led,
+        }))
+    }
+}
+
+/// A press of the button
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Press {
+    Short,
+    /// Reported once while the button is still held
+    Long,
+}
+
+/// Turns button readings into presses
+#[derive(Debug, Default)]
+pub struct ButtonTracker {
+    pressed_since: Option<Instant>,
+    long_reported: bool,
+}
+
+impl ButtonTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed one reading of the button
+    pub fn poll(&mut self, pressed: bool, now: Instant, long_press: Duration) -> Option<Press> {
+        match (pressed, self.pressed_since) {
+            (true, None) => {
+                self.pressed_since = Some(now);
+                self.long_reported = false;
+                None
+            }
+            (true, Some(since)) if !self.long_reported && now - since >= long_press => {
+                self.long_reported = true;
+                Some(Press::Long)
+            }
+            (false, Some(since)) => {
+                self.pressed_since = None;
+                (!self.long_reported && now - since >= DEBOUNCE).then_some(Press::Short)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// What a press does in the current state: a short press starts or stops,
+/// a long press splits a running recording
+pub fn button_command(press: Press, state: &DaemonState) -> Option<ApiCommand> {
+    match press {
+        Press::Short if state.armed => Some(ApiCommand::Disarm),
+        Press::Short => Some(ApiCommand::Arm),
+        Press::Long if state.recording => Some(ApiCommand::Split),
+        Press::Long => None,
+    }
+}
+
+/// Armed, recording and clip LED; `blink_on` is the phase of the blinking
+/// recording LED while paused
+pub fn led_states(state: &DaemonState, blink_on: bool) -> [bool; 3] {
+    let recording = if state.paused { blink_on } else { state.recording };
+    let clipping = state.levels.iter().any(|m| m.has_clipped);
+    [state.armed, recording, clipping]
+}
+
+fn request_line(chip: &mut Chip, line: u32, flags: LineRequestFlags, name: &str) -> Result<LineHandle> {
+    chip.get_line(line)
+        .and_then(|l| l.request(flags, 0, name))
+        .map_err(|e| Error::Backend(format!("GPIO line {}: {}", line, e)))
+}
+
+/// Request the lines and serve them until the daemon exits
+pub fn start(settings: GpioSettings, state: SharedState, commands: Sender<ApiCommand>) -> Result<()> {
+    let mut chip = Chip::new(&settings.chip).map_err(|e| Error::Backend(format!("GPIO chip {}: {}", settings.chip, e)))?;
+    let button = match settings.button_pin {
+        Some(pin) => {
+            let mut flags = LineRequestFlags::INPUT;
+            if settings.button_active_low {
+                flags |= LineRequestFlags::ACTIVE_LOW;
+            }
+            Some(request_line(&mut chip, pin, flags, "autorec-button")?)
+        }
+        None => None,
+    };
+    let leds: Vec<Option<LineHandle>> = [settings.armed_led, settings.recording_led, settings.clip_led]
+        .into_iter()
+        .map(|pin| pin.map(|pin| request_line(&mut chip, pin, LineRequestFlags::OUTPUT, "autorec-led")).transpose())
+        .collect::<Result<_>>()?;
+    info!("GPIO lines of {} ready", settings.chip);
+
+    thread::spawn(move || {
+        let mut tracker = ButtonTracker::new();
+        let started = Instant::now();
+        let mut shown: Option<[bool; 3]> = None;
+        let mut reported = false;
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            let now = Instant::now();
+            let blink_on = ((now - started).as_millis() / BLINK.as_millis()).is_multiple_of(2);
+
+            let pressed = match &button {
+                Some(button) => match button.get_value() {
+                    Ok(value) => value == 1,
+                    Err(e) => {
+                        if !reported {
+                            warn!("GPIO button: {}", e);
+                            reported = true;
+                        }
+                        false
+                    }
+                },
+                None => false,
+            };
+            let (command, lit) = {
+                let state = state.lock().unwrap();
+                let press = tracker.poll(pressed, now, settings.long_press);
+                (press.and_then(|p| button_command(p, &state)), led_states(&state, blink_on))
+            };
+            if let Some(command) = command {
+                if commands.send(command).is_err() {
+                    break;
+                }
+            }
+            if shown != Some(lit) {
+                for (led, on) in leds.iter().zip(lit) {
+                    if let Some(led) = led {
+                        let _ = led.set_value(on as u8);
+                    }
+                }
+                shown = Some(lit);
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_button_and_leds() {
+        let long = Duration::from_millis(1500);
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+
+        let mut tracker = ButtonTracker::new();
+        assert_eq!(tracker.poll(true, at(0), long), None);
+        assert_eq!(tracker.poll(false, at(20), long), None); // bounce
+        assert_eq!(tracker.poll(true, at(100), long), None);
+        assert_eq!(tracker.poll(false, at(300), long), Some(Press::Short));
+        assert_eq!(tracker.poll(true, at(1000), long), None);
+        assert_eq!(tracker.poll(true, at(2600), long), Some(Press::Long));
+        assert_eq!(tracker.poll(true, at(3000), long), None);
+        assert_eq!(tracker.poll(false, at(3100), long), None);
+
+        let mut state = DaemonState::new("pipewire:test", false);
+        assert_eq!(button_command(Press::Short, &state), Some(ApiCommand::Arm));
+        assert_eq!(button_command(Press::Long, &state), None);
+        state.armed = true;
+        state.recording = true;
+        assert_eq!(button_command(Press::Short, &state), Some(ApiCommand::Disarm));
+        assert_eq!(button_command(Press::Long, &state), Some(ApiCommand::Split));
+
+        assert_eq!(led_states(&state, false), [true, true, false]);
+        state.paused = true;
+        assert_eq!(led_states(&state, false), [true, false, false]);
+        assert_eq!(led_states(&state, true), [true, true, false]);
+
+        assert_eq!(GpioSettings::from_config(&GpioConfig::default()).unwrap(), None);
+        let config = GpioConfig { button_pin: Some(17), long_press: Some(0.0), ..Default::default() };
+        assert!(GpioSettings::from_config(&config).is_err());
+    }
+}
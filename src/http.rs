@@ -0,0 +1,289 @@
+//! HTTP requests to the metadata services.
+//!
+//! MusicBrainz, Discogs and AcoustID are queried through one [`ureq::Agent`]
+//! with connect and request timeouts, so that a stalled connection can't
+//! hang the analysis. Requests that fail with a network error or a 5xx/429
+//! response are retried a few times with growing delays before the error is
+//! returned. Shazam is queried by the `songrec` program, which is run under
+//! the same policy: it is killed when it does not finish within the request
+//! timeout, and failed runs are retried. Timeouts and retries are set in the
+//! `[http]` section of the configuration.
+
+use std::io::{self, Read};
+use std::process::{Command, Output, Stdio};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tracing::warn;
+
+use crate::config::{Config, HttpConfig};
+
+/// Timeouts and retries of metadata requests
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpPolicy {
+    pub connect_timeout: Duration,
+    pub timeout: Duration,
+    /// Retries after the first attempt
+    pub retries: u32,
+    /// Delay before the first retry; doubled for each further one
+    pub retry_delay: Duration,
+}
+
+impl Default for HttpPolicy {
+    fn default() -> Self {
+        HttpPolicy {
+            connect_timeout: Duration::from_secs(10),
+            timeout: Duration::from_secs(30),
+            retries: 3,
+            retry_delay: Duration::from_secs(2),
+        }
+    }
+}
+
+impl HttpPolicy {
+    pub fn from_config(config: &HttpConfig) -> Self {
+        let default = HttpPolicy::default();
+        let seconds = |s: Option<f64>, default: Duration| {
+            s.filter(|s| *s > 0.0).map_or(default, Duration::from_secs_f64)
+        };
+        HttpPolicy {
+            connect_timeout: seconds(config.connect_timeout, default.connect_timeout),
+            timeout: seconds(config.timeout, default.timeout),
+            retries: config.retries.unwrap_or(default.retries),
+            ..default
+        }
+    }
+
+    /// An agent with this policy's timeouts
+    pub fn agent(&self) -> ureq::Agent {
+        ureq::AgentBuilder::new()
+            .timeout_connect(self.connect_timeout)
+            .timeout(self.timeout)
+            .build()
+    }
+
+    /// Run `request` until it succeeds, fails for good or the retries are
+    /// used up. `service` names the service in the retry messages. Errors
+    /// are boxed, as `ureq::Error` is large.
+    pub fn call<F>(&self, service: &str, mut request: F) -> Result<ureq::Response, Box<ureq::Error>>
+    where
+        F: FnMut() -> Result<ureq::Response, Box<ureq::Error>>,
+    {
+        let mut delay = self.retry_delay;
+        let mut attempt = 0;
+        loop {
+            match request() {
+                Err(e) if attempt < self.retries && is_transient(&e) => {
+                    attempt += 1;
+                    warn!("[{}] Request failed ({}), retry {}/{} in {:.0}s...",
+                          service, e, attempt, self.retries, delay.as_secs_f64());
+                    thread::sleep(delay);
+                    delay *= 2;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Run the program `command` builds, which talks to `service` itself,
+    /// killing it after the request timeout. Runs that time out or exit
+    /// with an error are retried like failed requests.
+    pub fn run<F>(&self, service: &str, mut command: F) -> io::Result<Output>
+    where
+        F: FnMut() -> Command,
+    {
+        let mut delay = self.retry_delay;
+        let mut attempt = 0;
+        loop {
+            let result = output_with_timeout(&mut command(), self.timeout);
+            let failure = match &result {
+                Ok(output) if !output.status.success() => {
+                    String::from_utf8_lossy(&output.stderr).trim().to_string()
+                }
+                Err(e) if e.kind() == io::ErrorKind::TimedOut => e.to_string(),
+                _ => return result,
+            };
+            if attempt >= self.retries {
+                return result;
+            }
+            attempt += 1;
+            warn!("[{}] Request failed ({}), retry {}/{} in {:.0}s...",
+                  service, failure, attempt, self.retries, delay.as_secs_f64());
+            thread::sleep(delay);
+            delay *= 2;
+        }
+    }
+}
+
+/// Interval at which [`output_with_timeout`] checks whether the program ended
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Like [`Command::output`], but kill the program and fail with
+/// [`io::ErrorKind::TimedOut`] if it runs longer than `timeout`
+pub fn output_with_timeout(command: &mut Command, timeout: Duration) -> io::Result<Output> {
+    let mut child = command.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+    // Read both pipes while waiting, so a chatty program can't block on them
+    let stdout = child.stdout.take().map(read_pipe);
+    let stderr = child.stderr.take().map(read_pipe);
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("no answer within {:.0}s", timeout.as_secs_f64()),
+            ));
+        }
+        thread::sleep(POLL_INTERVAL);
+    };
+    let collect = |reader: Option<thread::JoinHandle<Vec<u8>>>| {
+        reader.and_then(|r| r.join().ok()).unwrap_or_default()
+    };
+    Ok(Output { status, stdout: collect(stdout), stderr: collect(stderr) })
+}
+
+/// Read `pipe` to the end on its own thread
+fn read_pipe<R: Read + Send + 'static>(mut pipe: R) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buffer = Vec::new();
+        let _ = pipe.read_to_end(&mut buffer);
+        buffer
+    })
+}
+
+/// Network errors and server-side failures may go away when retried;
+/// other client errors (400, 401, 404, ...) and bad URLs will not
+pub fn is_transient(error: &ureq::Error) -> bool {
+    match error {
+        ureq::Error::Status(status, _) => *status == 429 || (500..600).contains(status),
+        ureq::Error::Transport(t) => !matches!(
+            t.kind(),
+            ureq::ErrorKind::InvalidUrl | ureq::ErrorKind::UnknownScheme | ureq::ErrorKind::InvalidProxyUrl
+        ),
+    }
+}
+
+/// The policy from the configuration, read once per run
+pub fn policy() -> &'static HttpPolicy {
+    static POLICY: OnceLock<HttpPolicy> = OnceLock::new();
+    POLICY.get_or_init(|| {
+        let config = Config::load().ok().and_then(|c| c.http).unwrap_or_default();
+        HttpPolicy::from_config(&config)
+    })
+}
+
+/// The agent all metadata requests go through
+pub fn agent() -> &'static ureq::Agent {
+    static AGENT: OnceLock<ureq::Agent> = OnceLock::new();
+    AGENT.get_or_init(|| policy().agent())
+}
+
+/// Send a request built on the shared agent with the configured retries
+pub fn call<F>(service: &str, request: F) -> Result<ureq::Response, Box<ureq::Error>>
+where
+    F: FnMut() -> Result<ureq::Response, Box<ureq::Error>>,
+{
+    policy().call(service, request)
+}
+
+/// Run a program that queries `service` with the configured timeout and retries
+pub fn run<F>(service: &str, command: F) -> io::Result<Output>
+where
+    F: FnMut() -> Command,
+{
+    policy().run(service, command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(code: u16) -> Box<ureq::Error> {
+        Box::new(ureq::Error::Status(code, ureq::Response::new(code, "Error", "").unwrap()))
+    }
+
+    #[test]
+    fn test_retries() {
+        let policy = HttpPolicy { retry_delay: Duration::from_millis(1), ..Default::default() };
+
+        // Two 503s, then success
+        let mut attempts = 0;
+        let response = policy.call("test", || {
+            attempts += 1;
+            if attempts < 3 { Err(status(503)) } else { Ok(ureq::Response::new(200, "OK", "done").unwrap()) }
+        });
+        assert_eq!(response.unwrap().into_string().unwrap(), "done");
+        assert_eq!(attempts, 3);
+
+        // 404 is not retried
+        attempts = 0;
+        let response = policy.call("test", || {
+            attempts += 1;
+            Err(status(404))
+        });
+        assert!(matches!(*response.unwrap_err(), ureq::Error::Status(404, _)));
+        assert_eq!(attempts, 1);
+
+        // The last error is returned once the retries are used up
+        attempts = 0;
+        assert!(policy.call("test", || { attempts += 1; Err(status(502)) }).is_err());
+        assert_eq!(attempts, 4);
+
+        let config = HttpConfig { timeout: Some(5.0), retries: Some(0), ..Default::default() };
+        let policy = HttpPolicy::from_config(&config);
+        assert_eq!((policy.timeout, policy.retries), (Duration::from_secs(5), 0));
+        assert_eq!(policy.connect_timeout, HttpPolicy::default().connect_timeout);
+    }
+
+    fn shell(script: &str) -> Command {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(script);
+        command
+    }
+
+    #[test]
+    fn test_run() {
+        let policy = HttpPolicy {
+            timeout: Duration::from_millis(500),
+            retry_delay: Duration::from_millis(1),
+            ..Default::default()
+        };
+
+        // Two failed runs, then success
+        let mut attempts = 0;
+        let output = policy
+            .run("test", || {
+                attempts += 1;
+                shell(if attempts < 3 { "echo busy >&2; exit 1" } else { "echo done" })
+            })
+            .unwrap();
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "done");
+        assert_eq!(attempts, 3);
+
+        // A program that hangs is killed after the timeout, every time
+        attempts = 0;
+        let started = Instant::now();
+        let policy = HttpPolicy { retries: 1, ..policy };
+        let error = policy
+            .run("test", || {
+                attempts += 1;
+                shell("sleep 10")
+            })
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(attempts, 2);
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        // A missing program is not retried
+        attempts = 0;
+        assert!(policy.run("test", || { attempts += 1; Command::new("/nonexistent/songrec") }).is_err());
+        assert_eq!(attempts, 1);
+    }
+}
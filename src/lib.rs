@@ -1,32 +1,82 @@
+pub mod acoustid;
 pub mod album_finder;
 pub mod audio_analysis;
 pub mod audio_stream;
 pub mod album_identifier;
+pub mod boundary;
+pub mod catalog;
+pub mod completions;
 pub mod config;
+pub mod control_api;
+pub mod cue_check;
 pub mod cuefile;
 pub mod decibel;
+pub mod declick;
 pub mod detection_strategies;
+pub mod discid;
 pub mod discogs;
 pub mod display;
+pub mod downmix;
+pub mod duplicates;
+pub mod error;
+pub mod events;
+pub mod filename_template;
+pub mod filters;
+pub mod fingerprint_db;
+#[cfg(feature = "gpio")]
+pub mod gpio;
+pub mod http;
+#[cfg(feature = "jack")]
+pub mod jack_stream;
+pub mod listening_copy;
+pub mod locked_groove;
+pub mod logging;
 pub mod lookup;
+pub mod loudness;
 pub mod lookup_discogs;
 pub mod lookup_musicbrainz;
+pub mod metadata;
+pub mod mqtt;
 pub mod musicbrainz;
+pub mod net_stream;
+pub mod noise_floor;
+#[cfg(feature = "oled")]
+pub mod oled;
+pub mod organize;
 pub mod pause_detector;
 pub mod rate_limiter;
 pub mod songrec_cache;
+pub mod splitter;
+pub mod song_detect;
+pub mod spectrogram;
+pub mod speed_check;
+pub mod status_display;
+pub mod tagging;
+pub mod tracklist;
 pub mod pipewire_utils;
+pub mod preview;
+pub mod progress;
 pub mod recorder;
+pub mod rename;
+pub mod report;
+pub mod resampler;
+pub mod schedule;
+pub mod session;
+pub mod side_groups;
 pub mod vu_meter;
+pub mod watch;
 pub mod wavfile;
+pub mod web_ui;
+pub mod wow_flutter;
 
 pub use audio_stream::{
-    create_input_stream, parse_audio_address, AlsaInputStream, AudioInputStream, AudioStream,
-    PipeWireInputStream,
+    create_input_stream, create_mapped_input_stream, parse_audio_address, AlsaInputStream, AudioInputStream,
+    AudioOutputStream, AudioStream, ChannelMapStream, PipeWireInputStream, PipeWireOutputStream,
 };
 pub use album_identifier::{identify_songs, IdentifiedSong};
 pub use config::Config;
+pub use error::{Error, Result};
 pub use display::display_vu_meter;
-pub use pipewire_utils::{get_available_targets, list_targets, validate_and_select_target};
+pub use pipewire_utils::{get_available_targets, list_targets, list_targets_filtered, validate_and_select_target, TargetFilter};
 pub use recorder::AudioRecorder;
-pub use vu_meter::{process_audio_chunk, ChannelMetrics, SampleFormat, VUMeter};
+pub use vu_meter::{process_audio_chunk, ChannelMetrics, ReconnectPolicy, SampleFormat, VUMeter};
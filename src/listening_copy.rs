@@ -0,0 +1,285 @@
+//! FLAC listening copy written alongside the archival WAV.
+//!
+//! The recorder keeps the WAV file exactly as captured (e.g. 96 kHz,
+//! 32 bit) as the archive. The listening copy is converted on the fly to a
+//! delivery format, 44.1 kHz / 16 bit with TPDF dither by default, and
+//! encoded to FLAC by `ffmpeg`. Conversion and encoding run on their own
+//! thread; unlike the preview, no audio is ever dropped, the queue grows
+//! instead while the encoder catches up.
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{channel, Sender};
+use std::thread;
+
+use crate::config::OutputConfig;
+use crate::error::{Error, Result};
+use crate::resampler::{Requantizer, Resampler};
+use crate::vu_meter::SampleFormat;
+
+/// Default sample rate of the listening copy
+pub const DEFAULT_SAMPLE_RATE: u32 = 44100;
+
+/// Default bit depth of the listening copy
+pub const DEFAULT_BITS_PER_SAMPLE: u16 = 16;
+
+/// Format of the listening copy the recorder writes for every new file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ListeningCopySettings {
+    pub sample_rate: u32,
+    /// 16 or 24
+    pub bits_per_sample: u16,
+    /// Add TPDF dither when the bit depth is reduced
+    pub dither: bool,
+}
+
+impl Default for ListeningCopySettings {
+    fn default() -> Self {
+        ListeningCopySettings {
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            bits_per_sample: DEFAULT_BITS_PER_SAMPLE,
+            dither: true,
+        }
+    }
+}
+
+impl ListeningCopySettings {
+    /// Settings from the `[output]` section; None if no FLAC copy is wanted
+    pub fn from_config(config: &OutputConfig) -> Result<Option<Self>> {
+        if !config.flac.unwrap_or(false) {
+            return Ok(None);
+        }
+        let default = ListeningCopySettings::default();
+        let settings = ListeningCopySettings {
+            sample_rate: config.flac_rate.unwrap_or(default.sample_rate),
+            bits_per_sample: config.flac_bits.unwrap_or(default.bits_per_sample),
+            dither: config.dither.unwrap_or(default.dither),
+        };
+        if !matches!(settings.bits_per_sample, 16 | 24) {
+            return Err(Error::Config(format!(
+                "FLAC copies have 16 or 24 bits per sample, not {}",
+                settings.bits_per_sample
+            )));
+        }
+        Ok(Some(settings))
+    }
+
+    fn sample_format(&self) -> SampleFormat {
+        if self.bits_per_sample == 16 { SampleFormat::S16 } else { SampleFormat::S24 }
+    }
+}
+
+/// Path of the listening copy belonging to a WAV file (`side.1.wav` -> `side.1.flac`)
+pub fn listening_copy_path(wav_path: &str) -> String {
+    Path::new(wav_path).with_extension("flac").to_string_lossy().to_string()
+}
+
+/// ffmpeg arguments to encode raw little-endian PCM in the copy's format from stdin
+pub fn ffmpeg_args(output: &str, channels: usize, settings: &ListeningCopySettings) -> Vec<String> {
+    let input_format = if settings.bits_per_sample == 16 { "s16le" } else { "s24le" };
+    [
+        "-hide_banner", "-loglevel", "error", "-y",
+        "-f", input_format,
+        "-ar", &settings.sample_rate.to_string(),
+        "-ac", &channels.to_string(),
+        "-i", "pipe:0",
+        "-c:a", "flac",
+        output,
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect()
+}
+
+/// Bits per sample of the values of a capture format
+fn capture_bits(format: SampleFormat) -> u16 {
+    match format {
+        SampleFormat::S16 => 16,
+        SampleFormat::S24 | SampleFormat::S24In32 => 24,
+        SampleFormat::S32 => 32,
+    }
+}
+
+/// Sample-rate and bit-depth conversion to the copy's format
+struct Converter {
+    channels: usize,
+    resampler: Option<Resampler>,
+    requantizer: Requantizer,
+    output_format: SampleFormat,
+}
+
+impl Converter {
+    fn new(rate: u32, channels: usize, format: SampleFormat, settings: &ListeningCopySettings) -> Result<Self> {
+        let resampler = match rate != settings.sample_rate {
+            true => Some(Resampler::new(rate, settings.sample_rate, channels)?),
+            false => None,
+        };
+        Ok(Converter {
+            channels,
+            resampler,
+            requantizer: Requantizer::new(capture_bits(format), settings.bits_per_sample, settings.dither),
+            output_format: settings.sample_format(),
+        })
+    }
+
+    /// Convert interleaved capture samples to PCM bytes
+    fn process(&mut self, samples: Vec<i32>) -> Result<Vec<u8>> {
+        let samples = match self.resampler.as_mut() {
+            Some(resampler) => interleave(&resampler.process(&deinterleave(&samples, self.channels))?),
+            None => samples,
+        };
+        Ok(self.encode(samples))
+    }
+
+    /// The end of the audio, held back by the resampler
+    fn flush(&mut self) -> Result<Vec<u8>> {
+        match self.resampler.as_mut() {
+            Some(resampler) => {
+                let samples = interleave(&resampler.flush()?);
+                Ok(self.encode(samples))
+            }
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn encode(&mut self, mut samples: Vec<i32>) -> Vec<u8> {
+        self.requantizer.process(&mut samples);
+        let mut bytes = Vec::with_capacity(samples.len() * self.output_format.bytes_per_sample());
+        for sample in samples {
+            self.output_format.encode_sample(sample, &mut bytes);
+        }
+        bytes
+    }
+}
+
+/// Converts and encodes one listening copy in the background
+pub struct ListeningCopy {
+    path: String,
+    sender: Option<Sender<Vec<i32>>>,
+    thread_handle: Option<thread::JoinHandle<Result<()>>>,
+}
+
+impl ListeningCopy {
+    /// Start ffmpeg writing to `path` for audio captured at `rate` in `format`
+    pub fn new(
+        path: &str,
+        rate: u32,
+        channels: usize,
+        format: SampleFormat,
+        settings: &ListeningCopySettings,
+    ) -> Result<Self> {
+        let mut converter = Converter::new(rate, channels, format, settings)?;
+        let mut child = Command::new("ffmpeg")
+            .args(ffmpeg_args(path, channels, settings))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| Error::Backend(format!("Failed to start ffmpeg for the FLAC copy: {}", e)))?;
+        let mut stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| Error::Backend("Failed to open ffmpeg stdin".to_string()))?;
+        let (sender, receiver) = channel::<Vec<i32>>();
+
+        let thread_handle = thread::spawn(move || {
+            let mut write = || -> Result<()> {
+                for samples in &receiver {
+                    stdin.write_all(&converter.process(samples)?)?;
+                }
+                Ok(stdin.write_all(&converter.flush()?)?)
+            };
+            let result = write();
+            // After an error, further audio is discarded
+            drop(receiver);
+            // Closing stdin tells ffmpeg to finish the file
+            drop(stdin);
+
+            let output = child.wait_with_output()?;
+            if !output.status.success() {
+                return Err(Error::Backend(format!(
+                    "ffmpeg failed: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )));
+            }
+            result
+        });
+
+        Ok(ListeningCopy {
+            path: path.to_string(),
+            sender: Some(sender),
+            thread_handle: Some(thread_handle),
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Queue interleaved samples in the capture format
+    pub fn write_samples(&mut self, samples: &[i32]) {
+        if let Some(sender) = self.sender.as_ref() {
+            let _ = sender.send(samples.to_vec());
+        }
+    }
+
+    /// Convert the remaining audio and wait for ffmpeg to finish the file
+    pub fn finish(&mut self) -> Result<()> {
+        self.sender.take();
+        match self.thread_handle.take() {
+            Some(handle) => handle
+                .join()
+                .map_err(|_| Error::Backend("FLAC copy thread panicked".to_string()))?,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for ListeningCopy {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+fn deinterleave(samples: &[i32], channels: usize) -> Vec<Vec<i32>> {
+    let mut result = vec![Vec::with_capacity(samples.len() / channels); channels];
+    for frame in samples.chunks_exact(channels) {
+        for (channel, &sample) in result.iter_mut().zip(frame) {
+            channel.push(sample);
+        }
+    }
+    result
+}
+
+fn interleave(channels: &[Vec<i32>]) -> Vec<i32> {
+    let frames = channels.iter().map(|ch| ch.len()).min().unwrap_or(0);
+    let mut interleaved = Vec::with_capacity(frames * channels.len());
+    for frame in 0..frames {
+        interleaved.extend(channels.iter().map(|channel| channel[frame]));
+    }
+    interleaved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_and_args() {
+        assert_eq!(ListeningCopySettings::from_config(&OutputConfig::default()).unwrap(), None);
+        let config = OutputConfig { flac: Some(true), flac_bits: Some(24), ..Default::default() };
+        let settings = ListeningCopySettings::from_config(&config).unwrap().unwrap();
+        assert_eq!((settings.sample_rate, settings.bits_per_sample, settings.dither), (44100, 24, true));
+        let config = OutputConfig { flac: Some(true), flac_bits: Some(20), ..Default::default() };
+        assert!(ListeningCopySettings::from_config(&config).is_err());
+
+        assert_eq!(listening_copy_path("/rec/side.1.wav"), "/rec/side.1.flac");
+        let args = ffmpeg_args("side.1.flac", 2, &ListeningCopySettings::default()).join(" ");
+        assert!(args.contains("-f s16le -ar 44100 -ac 2 -i pipe:0 -c:a flac side.1.flac"), "{}", args);
+
+        let samples = vec![1, 2, 3, 4, 5, 6];
+        assert_eq!(deinterleave(&samples, 2), vec![vec![1, 3, 5], vec![2, 4, 6]]);
+        assert_eq!(interleave(&deinterleave(&samples, 2)), samples);
+    }
+}
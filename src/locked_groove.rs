@@ -0,0 +1,435 @@
+//! Locked-groove detection at the end of a record side.
+//!
+//! The run-out groove of a record ends in a closed loop. When the arm does
+//! not lift, the stylus circles in it until someone stops the turntable, and
+//! the recording never falls silent. Most loops carry only groove noise and
+//! a tick once per revolution; some records have an intentional locked
+//! groove with music in it. Either way the level repeats with the period of
+//! one revolution: 1.8 s at 33⅓ RPM, 1.33 s at 45 and 0.77 s at 78.
+//!
+//! [`find_period`] looks for that repetition with the normalized
+//! autocorrelation of a level envelope, at lags near the period of a
+//! revolution. Music with a steady tempo can repeat with the same period,
+//! e.g. four beats at 133 BPM last one revolution at 33⅓, so [`is_loop`]
+//! also requires the level between the ticks to stay near the quiet level,
+//! or, for a loop with music in it, an almost exact repetition over
+//! [`STOP_REVOLUTIONS`] revolutions. [`find_locked_groove`] applies both to
+//! the end of a recording and walks back to where the loop starts. While recording,
+//! [`LockedGrooveDetector`] watches the incoming audio for a loop, so the
+//! recording can be stopped although the ticks keep the level above the
+//! off-threshold.
+
+use std::collections::VecDeque;
+
+use crate::error::{Error, Result};
+use crate::speed_check::{RPM_33, RPM_45, RPM_78, TOLERANCE};
+use crate::wavfile;
+use crate::SampleFormat;
+
+/// Length of one envelope frame in milliseconds
+pub const ENVELOPE_MS: u32 = 10;
+
+/// Turntable speeds whose revolutions are searched
+pub const LOOP_RPMS: &[f64] = &[RPM_33, RPM_45, RPM_78];
+
+/// Revolutions that must repeat before a loop is recognized
+pub const MIN_REVOLUTIONS: usize = 4;
+
+/// Revolutions that must repeat before [`LockedGrooveDetector`] reports a
+/// loop; more than afterwards, since a recording is stopped on it
+pub const STOP_REVOLUTIONS: usize = 8;
+
+/// [`LockedGrooveDetector`] looks for a loop every this many envelope frames
+const CHECK_FRAMES: usize = 50;
+
+/// Lowest normalized correlation between two revolutions of a loop
+pub const MIN_CORRELATION: f64 = 0.7;
+
+/// Lowest correlation over [`STOP_REVOLUTIONS`] revolutions of a loop that
+/// is not quiet between its ticks, e.g. an intentional locked groove
+pub const MUSIC_LOOP_CORRELATION: f64 = 0.95;
+
+/// Between its ticks, a loop of groove noise stays within this of the
+/// quiet level (noise floor or off-threshold), in dB
+pub const QUIET_LOOP_DB: f32 = 10.0;
+
+/// How far back from the end of a recording the loop start is searched, in seconds
+const SEARCH_SECONDS: f64 = 300.0;
+
+/// Audio read at once while computing the envelope, in seconds
+const READ_BLOCK_SECONDS: f64 = 10.0;
+
+/// A locked groove at the end of a recording
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LockedGroove {
+    /// Start of the loop in seconds
+    pub start: f64,
+    /// Length of one revolution in seconds
+    pub period: f64,
+    /// Normalized correlation between the last revolutions
+    pub correlation: f64,
+}
+
+impl LockedGroove {
+    /// Speed of the turntable judged from the period
+    pub fn rpm(&self) -> f64 {
+        60.0 / self.period
+    }
+}
+
+/// Normalized correlation of two envelope pieces of the same length, from
+/// -1 to 1; 0 if either of them is constant
+pub fn correlation(a: &[f32], b: &[f32]) -> f64 {
+    let n = a.len().min(b.len());
+    if n == 0 {
+        return 0.0;
+    }
+    let mean = |x: &[f32]| x[..n].iter().map(|&v| v as f64).sum::<f64>() / n as f64;
+    let (mean_a, mean_b) = (mean(a), mean(b));
+    let (mut ab, mut aa, mut bb) = (0.0, 0.0, 0.0);
+    for (&x, &y) in a[..n].iter().zip(&b[..n]) {
+        let (x, y) = (x as f64 - mean_a, y as f64 - mean_b);
+        ab += x * y;
+        aa += x * x;
+        bb += y * y;
+    }
+    if aa <= 0.0 || bb <= 0.0 {
+        return 0.0;
+    }
+    ab / (aa * bb).sqrt()
+}
+
+/// Find a revolution period at the end of `envelope`.
+///
+/// For every lag within [`TOLERANCE`] of the revolution of one of the
+/// [`LOOP_RPMS`], the last `revolutions` - 1 lags of the envelope are
+/// compared with the same stretch one lag earlier. Returns the lag in frames
+/// with the highest correlation, if it reaches [`MIN_CORRELATION`].
+pub fn find_period(envelope: &[f32], frame_seconds: f64, revolutions: usize) -> Option<(usize, f64)> {
+    let n = envelope.len();
+    let mut best: Option<(usize, f64)> = None;
+    for &rpm in LOOP_RPMS {
+        let period = 60.0 / rpm / frame_seconds;
+        let min_lag = (period * (1.0 - TOLERANCE)).floor().max(1.0) as usize;
+        let max_lag = (period * (1.0 + TOLERANCE)).ceil() as usize;
+        for lag in min_lag..=max_lag {
+            if lag * revolutions > n {
+                break;
+            }
+            let window = lag * (revolutions - 1);
+            let r = correlation(&envelope[n - window - lag..n - lag], &envelope[n - window..]);
+            if best.is_none_or(|(_, best_r)| r > best_r) {
+                best = Some((lag, r));
+            }
+        }
+    }
+    best.filter(|&(_, r)| r >= MIN_CORRELATION)
+}
+
+/// Whether the repetition with period `lag` at the end of `envelope` is a
+/// locked groove rather than a steady tempo: its median level over
+/// [`MIN_REVOLUTIONS`] revolutions is within [`QUIET_LOOP_DB`] of
+/// `quiet_db`, or [`STOP_REVOLUTIONS`] revolutions correlate by at least
+/// [`MUSIC_LOOP_CORRELATION`]
+pub fn is_loop(envelope: &[f32], lag: usize, quiet_db: f32) -> bool {
+    let n = envelope.len();
+    let mut levels = envelope[n.saturating_sub(lag * MIN_REVOLUTIONS)..].to_vec();
+    levels.sort_by(f32::total_cmp);
+    let median = levels.get(levels.len() / 2).copied().unwrap_or(0.0);
+    if 20.0 * median.max(1e-10).log10() <= quiet_db + QUIET_LOOP_DB {
+        return true;
+    }
+    let window = lag * (STOP_REVOLUTIONS - 1);
+    n >= lag * STOP_REVOLUTIONS
+        && correlation(&envelope[n - window - lag..n - lag], &envelope[n - window..]) >= MUSIC_LOOP_CORRELATION
+}
+
+/// First frame of the loop with period `lag` that ends `envelope`.
+///
+/// A frame belongs to the loop if the revolution starting there matches the
+/// next one. The start is searched backwards in whole revolutions first,
+/// then frame by frame.
+pub fn find_loop_start(envelope: &[f32], lag: usize) -> usize {
+    let n = envelope.len();
+    let matches = |s: usize| correlation(&envelope[s..s + lag], &envelope[s + lag..s + 2 * lag]) >= MIN_CORRELATION;
+    let mut start = n.saturating_sub(2 * lag);
+    while start >= lag && matches(start - lag) {
+        start -= lag;
+    }
+    while start > 0 && matches(start - 1) {
+        start -= 1;
+    }
+    start
+}
+
+/// Level envelope of `wav_file` from `start` to `end` seconds: the RMS of
+/// all channels in frames of [`ENVELOPE_MS`]
+pub fn wav_envelope(wav_file: &str, start: f64, end: f64) -> Result<Vec<f32>> {
+    let frame_seconds = ENVELOPE_MS as f64 / 1000.0;
+    let mut envelope = Vec::new();
+    let mut position = start;
+    while position < end {
+        let length = READ_BLOCK_SECONDS.min(end - position);
+        let (header, audio) = wavfile::read_wav_segment(wav_file, position, length).map_err(Error::Decode)?;
+        let frame_len = ((header.sample_rate as f64 * frame_seconds) as usize).max(1);
+        let frames = audio.first().map_or(0, |c| c.len());
+        if frames == 0 {
+            break;
+        }
+        let max = header.sample_format().map_or(1.0, |f| f.max_value());
+        for frame_start in (0..frames).step_by(frame_len) {
+            let frame_end = (frame_start + frame_len).min(frames);
+            let sum: f64 = audio
+                .iter()
+                .flat_map(|channel| &channel[frame_start..frame_end])
+                .map(|&s| (s as f64 / max).powi(2))
+                .sum();
+            envelope.push((sum / ((frame_end - frame_start) * audio.len()) as f64).sqrt() as f32);
+        }
+        position += length;
+    }
+    Ok(envelope)
+}
+
+/// Look for a locked groove that runs until the end of `wav_file`, starting
+/// no earlier than `search_start` seconds (at most [`SEARCH_SECONDS`] before
+/// the end); `quiet_db` is the level of the groove noise
+pub fn find_locked_groove(wav_file: &str, search_start: f64, file_duration: f64, quiet_db: f32) -> Result<Option<LockedGroove>> {
+    let frame_seconds = ENVELOPE_MS as f64 / 1000.0;
+    let start = search_start.max(file_duration - SEARCH_SECONDS).max(0.0);
+    let envelope = wav_envelope(wav_file, start, file_duration)?;
+    let Some((lag, correlation)) = find_period(&envelope, frame_seconds, MIN_REVOLUTIONS) else {
+        return Ok(None);
+    };
+    if !is_loop(&envelope, lag, quiet_db) {
+        return Ok(None);
+    }
+    let loop_start = find_loop_start(&envelope, lag);
+    Ok(Some(LockedGroove {
+        start: start + loop_start as f64 * frame_seconds,
+        period: lag as f64 * frame_seconds,
+        correlation,
+    }))
+}
+
+/// Watches the audio of a recording for a locked groove
+///
+/// Reports a loop of [`STOP_REVOLUTIONS`] revolutions once, until
+/// [`reset`](Self::reset); its start counts from the first audio fed.
+pub struct LockedGrooveDetector {
+    /// Samples per envelope frame and channel
+    frame_samples: usize,
+    frame_sum: f64,
+    frame_count: usize,
+    envelope: VecDeque<f32>,
+    /// Frames kept, enough for the slowest revolutions
+    capacity: usize,
+    /// Envelope frames since the start
+    frames: u64,
+    since_check: usize,
+    detected: bool,
+    /// Level of the groove noise in dB
+    quiet_db: f32,
+}
+
+impl LockedGrooveDetector {
+    /// Detector for audio at `sample_rate` whose groove noise is about
+    /// `quiet_db`, e.g. the off-threshold
+    pub fn new(sample_rate: u32, quiet_db: f32) -> Self {
+        let frame_seconds = ENVELOPE_MS as f64 / 1000.0;
+        let longest_period = 60.0 / RPM_33 * (1.0 + TOLERANCE);
+        LockedGrooveDetector {
+            frame_samples: ((sample_rate as f64 * frame_seconds) as usize).max(1),
+            frame_sum: 0.0,
+            frame_count: 0,
+            envelope: VecDeque::new(),
+            capacity: (longest_period * STOP_REVOLUTIONS as f64 / frame_seconds).ceil() as usize + 1,
+            frames: 0,
+            since_check: 0,
+            detected: false,
+            quiet_db,
+        }
+    }
+
+    /// Feed audio (one vector per channel); returns the loop when it is found
+    pub fn feed_audio(&mut self, audio: &[Vec<i32>], format: SampleFormat) -> Option<LockedGroove> {
+        let max = format.max_value();
+        let frames = audio.first().map_or(0, |c| c.len());
+        let mut found = None;
+        for i in 0..frames {
+            self.frame_sum += audio.iter().filter_map(|c| c.get(i)).map(|&s| (s as f64 / max).powi(2)).sum::<f64>();
+            self.frame_count += 1;
+            if self.frame_count < self.frame_samples {
+                continue;
+            }
+            let rms = (self.frame_sum / (self.frame_count * audio.len()) as f64).sqrt() as f32;
+            self.frame_sum = 0.0;
+            self.frame_count = 0;
+            if self.envelope.len() == self.capacity {
+                self.envelope.pop_front();
+            }
+            self.envelope.push_back(rms);
+            self.frames += 1;
+            self.since_check += 1;
+            if self.since_check >= CHECK_FRAMES && !self.detected {
+                self.since_check = 0;
+                found = found.or_else(|| self.check());
+            }
+        }
+        found
+    }
+
+    fn check(&mut self) -> Option<LockedGroove> {
+        let frame_seconds = ENVELOPE_MS as f64 / 1000.0;
+        let envelope = self.envelope.make_contiguous();
+        let (lag, correlation) = find_period(envelope, frame_seconds, STOP_REVOLUTIONS)?;
+        if !is_loop(envelope, lag, self.quiet_db) {
+            return None;
+        }
+        let start = self.frames - envelope.len() as u64 + find_loop_start(envelope, lag) as u64;
+        self.detected = true;
+        Some(LockedGroove {
+            start: start as f64 * frame_seconds,
+            period: lag as f64 * frame_seconds,
+            correlation,
+        })
+    }
+
+    /// Start over, e.g. for a new recording
+    pub fn reset(&mut self) {
+        self.frame_sum = 0.0;
+        self.frame_count = 0;
+        self.envelope.clear();
+        self.frames = 0;
+        self.since_check = 0;
+        self.detected = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wavfile::write_wav_samples;
+
+    /// Level of the groove noise in [`side_with_locked_groove`], in dB
+    const NOISE_DB: f32 = -50.0;
+
+    /// 20 s of music, then `loop_seconds` of groove noise with a tick every 1.8 s
+    fn side_with_locked_groove(rate: usize, loop_seconds: usize) -> Vec<i32> {
+        let mut state: u32 = 7;
+        let mut noise = move || {
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            (state >> 16) as f64 / 32768.0 - 1.0
+        };
+        let mut level = 0.5;
+        (0..rate * (20 + loop_seconds))
+            .map(|i| {
+                let t = i as f64 / rate as f64;
+                if t < 20.0 {
+                    // Music: noise with a level changing every 50 ms
+                    if i % (rate / 20) == 0 {
+                        level = 0.2 + 0.3 * (noise() + 1.0);
+                    }
+                    (12000.0 * level * noise()) as i32
+                } else {
+                    let tick = ((t - 20.0) % 1.8) < 0.005;
+                    (200.0 * noise() + if tick { 12000.0 } else { 0.0 }) as i32
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_find_locked_groove() {
+        let dir = tempfile::tempdir().unwrap();
+        let rate = 8000;
+        let samples = side_with_locked_groove(rate, 15);
+        let path = dir.path().join("side.wav").to_string_lossy().to_string();
+        write_wav_samples(&path, std::slice::from_ref(&samples), rate as u32, 16).unwrap();
+
+        let groove = find_locked_groove(&path, 0.0, 35.0, NOISE_DB).unwrap().expect("locked groove");
+        assert!((groove.period - 1.8).abs() < 0.015, "{:?}", groove);
+        assert!((groove.rpm() - RPM_33).abs() < 0.5, "{:?}", groove);
+        assert!((groove.start - 20.0).abs() < 0.5, "{:?}", groove);
+        assert!(groove.correlation >= MIN_CORRELATION);
+
+        // The music alone does not repeat
+        let music = dir.path().join("music.wav").to_string_lossy().to_string();
+        write_wav_samples(&music, &[samples[..rate * 20].to_vec()], rate as u32, 16).unwrap();
+        assert_eq!(find_locked_groove(&music, 0.0, 20.0, NOISE_DB).unwrap(), None);
+    }
+
+    #[test]
+    fn test_locked_groove_detector() {
+        let rate = 8000;
+        let samples = side_with_locked_groove(rate, 30);
+        let mut detector = LockedGrooveDetector::new(rate as u32, NOISE_DB);
+        let mut found = Vec::new();
+        for (i, chunk) in samples.chunks(1600).enumerate() {
+            let audio = vec![chunk.to_vec(), chunk.to_vec()];
+            if let Some(groove) = detector.feed_audio(&audio, SampleFormat::S16) {
+                found.push((i as f64 * 0.2, groove));
+            }
+        }
+        // Reported once, after eight revolutions of the loop
+        assert_eq!(found.len(), 1, "{:?}", found);
+        let (time, groove) = found[0];
+        assert!(time > 20.0 + 1.8 * 7.0 && time < 40.0, "{} {:?}", time, groove);
+        assert!((groove.start - 20.0).abs() < 0.5, "{:?}", groove);
+        assert!((groove.period - 1.8).abs() < 0.015, "{:?}", groove);
+
+        detector.reset();
+        assert!(detector.feed_audio(&[samples[..rate * 20].to_vec()], SampleFormat::S16).is_none());
+    }
+
+    /// `seconds` of music with a kick drum at 133 BPM, whose four beats last
+    /// one revolution at 33⅓ RPM
+    fn steady_beat(rate: usize, seconds: usize) -> Vec<i32> {
+        let mut state: u32 = 11;
+        let mut noise = move || {
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            (state >> 16) as f64 / 32768.0 - 1.0
+        };
+        let beat = 60.0 / 133.0;
+        let mut level = 0.5;
+        (0..rate * seconds)
+            .map(|i| {
+                let t = i as f64 / rate as f64;
+                if i % (rate / 20) == 0 {
+                    level = 0.1 + 0.2 * (noise() + 1.0);
+                }
+                let kick = (-(t % beat) / 0.05).exp();
+                (20000.0 * (0.4 * level + 0.6 * kick) * noise()) as i32
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_steady_beat_is_no_loop() {
+        let dir = tempfile::tempdir().unwrap();
+        let rate = 8000;
+        let samples = steady_beat(rate, 40);
+        let path = dir.path().join("beat.wav").to_string_lossy().to_string();
+        write_wav_samples(&path, std::slice::from_ref(&samples), rate as u32, 16).unwrap();
+
+        // The beat repeats with the period of a revolution, but is neither quiet nor exact
+        let envelope = wav_envelope(&path, 0.0, 40.0).unwrap();
+        let (lag, _) = find_period(&envelope, ENVELOPE_MS as f64 / 1000.0, STOP_REVOLUTIONS).expect("period");
+        assert!(!is_loop(&envelope, lag, NOISE_DB));
+        assert_eq!(find_locked_groove(&path, 0.0, 40.0, NOISE_DB).unwrap(), None);
+
+        let mut detector = LockedGrooveDetector::new(rate as u32, NOISE_DB);
+        for chunk in samples.chunks(1600) {
+            assert_eq!(detector.feed_audio(&[chunk.to_vec()], SampleFormat::S16), None);
+        }
+    }
+
+    #[test]
+    fn test_correlation() {
+        let a = [0.0, 1.0, 0.0, 1.0];
+        assert!((correlation(&a, &a) - 1.0).abs() < 1e-9);
+        assert!((correlation(&a, &[1.0, 0.0, 1.0, 0.0]) + 1.0).abs() < 1e-9);
+        assert_eq!(correlation(&a, &[0.5; 4]), 0.0);
+        assert_eq!(correlation(&[], &[]), 0.0);
+    }
+}
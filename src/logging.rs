@@ -0,0 +1,325 @@
+//! Log output through `tracing`.
+//!
+//! Library code reports progress, warnings and errors with the `tracing`
+//! macros; each program decides in `main` where they go:
+//! - command line tools print them to stderr, informational messages as
+//!   they are and warnings and errors with a "Warning:"/"Error:" prefix
+//!   ([`init_cli`])
+//! - `autorecord` keeps the last lines in a [`LogBuffer`] and shows them
+//!   under the VU meter, so messages of the recording thread don't break
+//!   the display
+//! - `autorecd` writes timestamped lines or JSON to stderr or to a rotating
+//!   log file
+//!
+//! The `[log]` section sets the filter, e.g.
+//! `level = "info,autorec::boundary=debug"`, and the format.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use tracing::{Event, Level, Subscriber};
+use tracing_appender::rolling::{RollingFileAppender, Rotation as AppenderRotation};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields, MakeWriter};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::EnvFilter;
+
+use crate::config::{Config, LogConfig};
+use crate::error::{Error, Result};
+
+/// Filter unless the config sets `level`
+pub const DEFAULT_FILTER: &str = "info";
+
+/// Lines kept by a [`LogBuffer`]
+const BUFFER_LINES: usize = 100;
+
+/// How log lines are written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Plain text
+    #[default]
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+impl LogFormat {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "text" => Some(LogFormat::Text),
+            "json" => Some(LogFormat::Json),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogFormat::Text => "text",
+            LogFormat::Json => "json",
+        }
+    }
+}
+
+/// When a new log file is started
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Rotation {
+    Hourly,
+    #[default]
+    Daily,
+    Never,
+}
+
+impl Rotation {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "hourly" => Some(Rotation::Hourly),
+            "daily" => Some(Rotation::Daily),
+            "never" => Some(Rotation::Never),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Rotation::Hourly => "hourly",
+            Rotation::Daily => "daily",
+            Rotation::Never => "never",
+        }
+    }
+}
+
+/// Log settings from the `[log]` section
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogSettings {
+    /// Filter directives, e.g. "warn,autorec::recorder=info"
+    pub filter: String,
+    pub format: LogFormat,
+    /// Directory of the log files; None writes to stderr
+    pub dir: Option<PathBuf>,
+    pub rotation: Rotation,
+}
+
+impl Default for LogSettings {
+    fn default() -> Self {
+        LogSettings {
+            filter: DEFAULT_FILTER.to_string(),
+            format: LogFormat::default(),
+            dir: None,
+            rotation: Rotation::default(),
+        }
+    }
+}
+
+impl LogSettings {
+    pub fn from_config(config: &LogConfig) -> Result<Self> {
+        let format = match config.format.as_deref() {
+            Some(name) => LogFormat::from_str(name)
+                .ok_or_else(|| Error::Config(format!("Invalid log format '{}' (use text or json)", name)))?,
+            None => LogFormat::default(),
+        };
+        let rotation = match config.rotation.as_deref() {
+            Some(name) => Rotation::from_str(name).ok_or_else(|| {
+                Error::Config(format!("Invalid log rotation '{}' (use hourly, daily or never)", name))
+            })?,
+            None => Rotation::default(),
+        };
+        let settings = LogSettings {
+            filter: config.level.clone().unwrap_or_else(|| DEFAULT_FILTER.to_string()),
+            format,
+            dir: config.dir.as_ref().map(PathBuf::from),
+            rotation,
+        };
+        settings.env_filter()?;
+        Ok(settings)
+    }
+
+    fn env_filter(&self) -> Result<EnvFilter> {
+        EnvFilter::try_new(&self.filter).map_err(|e| Error::Config(format!("Invalid log level '{}': {}", self.filter, e)))
+    }
+}
+
+/// Where log lines go
+pub enum Sink {
+    /// stderr, as plain messages without timestamps
+    Terminal,
+    /// stderr or the rotating files in the settings' `dir`, with timestamps;
+    /// `name` is the start of the file names, e.g. autorecd.2024-05-01.log
+    Service { name: String },
+    /// The buffer shown under the VU meter
+    Buffer(LogBuffer),
+}
+
+/// Set up logging for the whole program; fails if the filter is invalid or
+/// the log directory can't be created
+pub fn init(settings: &LogSettings, sink: Sink) -> Result<()> {
+    let filter = settings.env_filter()?;
+    let (writer, plain) = match sink {
+        Sink::Terminal => (BoxMakeWriter::new(io::stderr), true),
+        Sink::Buffer(buffer) => (BoxMakeWriter::new(buffer), true),
+        Sink::Service { name } => match &settings.dir {
+            Some(dir) => {
+                let rotation = match settings.rotation {
+                    Rotation::Hourly => AppenderRotation::HOURLY,
+                    Rotation::Daily => AppenderRotation::DAILY,
+                    Rotation::Never => AppenderRotation::NEVER,
+                };
+                let appender = RollingFileAppender::builder()
+                    .rotation(rotation)
+                    .filename_prefix(name)
+                    .filename_suffix("log")
+                    .build(dir)
+                    .map_err(|e| Error::Config(format!("Log directory {}: {}", dir.display(), e)))?;
+                (BoxMakeWriter::new(appender), false)
+            }
+            None => (BoxMakeWriter::new(io::stderr), false),
+        },
+    };
+
+    let builder = tracing_subscriber::fmt().with_env_filter(filter).with_writer(writer).with_ansi(false);
+    let result = match (settings.format, plain) {
+        (LogFormat::Json, _) => builder.json().try_init(),
+        (LogFormat::Text, true) => builder.event_format(PlainFormat).try_init(),
+        (LogFormat::Text, false) => builder.try_init(),
+    };
+    result.map_err(|e| Error::Config(format!("Failed to set up logging: {}", e)))
+}
+
+/// Log to stderr with the filter and format of the `[log]` section; for
+/// the command line tools. Logging stays at the defaults if the config is
+/// invalid.
+pub fn init_cli() {
+    let config = Config::load().ok().and_then(|c| c.log).unwrap_or_default();
+    let settings = LogSettings::from_config(&config).unwrap_or_else(|e| {
+        eprintln!("Warning: Ignoring [log] config: {}", e);
+        LogSettings::default()
+    });
+    let _ = init(&settings, Sink::Terminal);
+}
+
+/// Messages as they are, warnings and errors with a prefix and debug
+/// output with the module it comes from
+struct PlainFormat;
+
+impl<S, N> FormatEvent<S, N> for PlainFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(&self, ctx: &FmtContext<'_, S, N>, mut writer: Writer<'_>, event: &Event<'_>) -> fmt::Result {
+        let metadata = event.metadata();
+        match *metadata.level() {
+            Level::ERROR => write!(writer, "Error: ")?,
+            Level::WARN => write!(writer, "Warning: ")?,
+            Level::INFO => {}
+            _ => write!(writer, "[{}] ", metadata.target())?,
+        }
+        ctx.field_format().format_fields(writer.by_ref(), event)?;
+        writeln!(writer)
+    }
+}
+
+/// The latest log lines, for showing them in a full-screen display
+///
+/// After [`release`](LogBuffer::release) new lines go to stderr again.
+#[derive(Clone, Default)]
+pub struct LogBuffer {
+    lines: Arc<Mutex<VecDeque<String>>>,
+    released: Arc<AtomicBool>,
+}
+
+impl LogBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The last `count` lines, oldest first
+    pub fn recent(&self, count: usize) -> Vec<String> {
+        let lines = self.lines.lock().unwrap();
+        lines.iter().skip(lines.len().saturating_sub(count)).cloned().collect()
+    }
+
+    /// Write all further lines to stderr, e.g. when the display is closed
+    pub fn release(&self) {
+        self.released.store(true, Ordering::SeqCst);
+    }
+
+    fn push(&self, text: &str) {
+        let mut lines = self.lines.lock().unwrap();
+        for line in text.lines() {
+            if lines.len() == BUFFER_LINES {
+                lines.pop_front();
+            }
+            lines.push_back(line.to_string());
+        }
+    }
+}
+
+/// Collects one formatted event and adds it to the buffer when dropped
+pub struct LogBufferWriter {
+    buffer: LogBuffer,
+    text: Vec<u8>,
+}
+
+impl Write for LogBufferWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if self.buffer.released.load(Ordering::SeqCst) {
+            return io::stderr().write(data);
+        }
+        self.text.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for LogBufferWriter {
+    fn drop(&mut self) {
+        if !self.text.is_empty() {
+            self.buffer.push(&String::from_utf8_lossy(&self.text));
+        }
+    }
+}
+
+impl<'a> MakeWriter<'a> for LogBuffer {
+    type Writer = LogBufferWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        LogBufferWriter { buffer: self.clone(), text: Vec::new() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_and_buffer() {
+        let config = LogConfig {
+            level: Some("warn,autorec::recorder=debug".to_string()),
+            format: Some("JSON".to_string()),
+            dir: Some("/var/log/autorec".to_string()),
+            rotation: Some("hourly".to_string()),
+        };
+        let settings = LogSettings::from_config(&config).unwrap();
+        assert_eq!(settings.format, LogFormat::Json);
+        assert_eq!(settings.rotation, Rotation::Hourly);
+        assert_eq!(settings.dir, Some(PathBuf::from("/var/log/autorec")));
+        assert!(LogSettings::from_config(&LogConfig { format: Some("xml".to_string()), ..Default::default() }).is_err());
+        assert!(LogSettings::from_config(&LogConfig { level: Some("autorec=loud".to_string()), ..Default::default() }).is_err());
+
+        let buffer = LogBuffer::new();
+        for i in 0..BUFFER_LINES + 2 {
+            let mut writer = buffer.make_writer();
+            writeln!(writer, "line {}", i).unwrap();
+        }
+        assert_eq!(buffer.recent(2), vec![format!("line {}", BUFFER_LINES), format!("line {}", BUFFER_LINES + 1)]);
+        assert_eq!(buffer.recent(1000).len(), BUFFER_LINES);
+    }
+}
@@ -12,7 +12,10 @@
 
 use std::error::Error;
 
+use tracing::{info, warn};
+
 use crate::album_identifier::IdentifiedSong;
+use crate::discogs;
 use crate::musicbrainz;
 
 // Re-export backends so existing `use autorec::lookup::{DiscogsBackend, …}` keeps working.
@@ -30,6 +33,8 @@ pub struct AlbumSideResult {
     pub album_title: String,
     /// Human-readable release reference (URL or ID string)
     pub release_info: String,
+    /// Release year, if the backend knows it
+    pub year: Option<u32>,
     /// Ordered track list for the matched side (in `ExpectedTrack` format)
     pub tracks: Vec<musicbrainz::ExpectedTrack>,
     /// Name of the backend that produced this result
@@ -64,6 +69,8 @@ pub struct AlbumResult {
     pub album_title: String,
     /// Human-readable release reference (URL or ID string)
     pub release_info: String,
+    /// Release year, if the backend knows it
+    pub year: Option<u32>,
     /// All sides of the release, in order
     pub sides: Vec<SideInfo>,
     /// Name of the backend that produced this result
@@ -118,6 +125,7 @@ pub trait AlbumIdentifier {
             artist: side.artist,
             album_title: side.album_title,
             release_info: side.release_info,
+            year: side.year,
             sides: vec![SideInfo {
                 label: '?',
                 tracks: side.tracks,
@@ -145,6 +153,31 @@ pub trait AlbumIdentifier {
     }
 }
 
+// ── Backend selection ────────────────────────────────────────────────────────
+
+/// Backend names accepted by [`backends_by_name`], in the default order
+pub const BACKEND_NAMES: [&str; 3] = ["discogs", "musicbrainz-vinyl", "musicbrainz"];
+
+/// Backends in the order of `names` (see [`BACKEND_NAMES`]).
+/// Returns an error naming the first unknown backend.
+pub fn backends_by_name<S: AsRef<str>>(names: &[S]) -> Result<Vec<Box<dyn AlbumIdentifier>>, String> {
+    names
+        .iter()
+        .map(|name| -> Result<Box<dyn AlbumIdentifier>, String> {
+            match name.as_ref().to_lowercase().as_str() {
+                "discogs" => Ok(Box::new(DiscogsBackend)),
+                "musicbrainz-vinyl" => Ok(Box::new(MusicBrainzBackend { vinyl_only: true })),
+                "musicbrainz" => Ok(Box::new(MusicBrainzBackend { vinyl_only: false })),
+                other => Err(format!(
+                    "Unknown lookup backend '{}' (use {})",
+                    other,
+                    BACKEND_NAMES.join(", ")
+                )),
+            }
+        })
+        .collect()
+}
+
 // ── Fallback strategy ────────────────────────────────────────────────────────
 
 /// Try each backend in order.  Returns the first successful result.
@@ -159,11 +192,11 @@ pub fn find_album_side_with_fallback(
     verbose: bool,
 ) -> Result<Option<AlbumSideResult>, Box<dyn Error>> {
     for (idx, backend) in backends.iter().enumerate() {
-        println!("Trying {}...", backend.name());
+        info!("Trying {}...", backend.name());
 
         match backend.find_album_side(songs, file_duration_seconds, verbose) {
             Ok(Some(mut result)) => {
-                println!(
+                info!(
                     "{}: found {} - {} ({} tracks)",
                     result.backend,
                     result.artist,
@@ -181,7 +214,7 @@ pub fn find_album_side_with_fallback(
                     for (j, other) in backends.iter().enumerate() {
                         if j == idx { continue; }
 
-                        println!("  Trying {} for track durations...", other.name());
+                        info!("  Trying {} for track durations...", other.name());
 
                         match other.fetch_durations_for_album(
                             &result.artist,
@@ -191,7 +224,7 @@ pub fn find_album_side_with_fallback(
                             verbose,
                         ) {
                             Ok(Some(enriched)) => {
-                                println!("  Track durations provided by {}", other.name());
+                                info!("  Track durations provided by {}", other.name());
                                 result.tracks = enriched;
                                 result.backend = format!(
                                     "{} + {} (durations)",
@@ -201,12 +234,12 @@ pub fn find_album_side_with_fallback(
                             }
                             Ok(None) => {
                                 if verbose {
-                                    println!("  {}: no duration data available", other.name());
+                                    info!("  {}: no duration data available", other.name());
                                 }
                             }
                             Err(e) => {
                                 if verbose {
-                                    println!("  {}: duration fetch error: {}", other.name(), e);
+                                    warn!("{}: duration fetch error: {}", other.name(), e);
                                 }
                             }
                         }
@@ -216,10 +249,10 @@ pub fn find_album_side_with_fallback(
                 return Ok(Some(result));
             }
             Ok(None) => {
-                println!("{}: no match found", backend.name());
+                info!("{}: no match found", backend.name());
             }
             Err(e) => {
-                println!("{}: error: {}", backend.name(), e);
+                warn!("{}: {}", backend.name(), e);
             }
         }
     }
@@ -236,11 +269,11 @@ pub fn find_album_with_fallback(
     verbose: bool,
 ) -> Result<Option<AlbumResult>, Box<dyn Error>> {
     for backend in backends.iter() {
-        println!("Trying {}...", backend.name());
+        info!("Trying {}...", backend.name());
 
         match backend.find_album(songs, file_duration_seconds, verbose) {
             Ok(Some(result)) => {
-                println!(
+                info!(
                     "{}: found {} - {} ({} side(s))",
                     result.backend,
                     result.artist,
@@ -250,10 +283,10 @@ pub fn find_album_with_fallback(
                 return Ok(Some(result));
             }
             Ok(None) => {
-                println!("{}: no match found", backend.name());
+                info!("{}: no match found", backend.name());
             }
             Err(e) => {
-                println!("{}: error: {}", backend.name(), e);
+                warn!("{}: {}", backend.name(), e);
             }
         }
     }
@@ -261,6 +294,98 @@ pub fn find_album_with_fallback(
     Ok(None)
 }
 
+// ── Release given by the user ────────────────────────────────────────────────
+
+/// A release named on the command line instead of searching for one
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReleaseReference {
+    Discogs(u64),
+    MusicBrainz(String),
+}
+
+impl ReleaseReference {
+    /// Parse a Discogs or MusicBrainz release URL, a numeric Discogs
+    /// release ID or a MusicBrainz release ID (UUID)
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        if s.contains("discogs.com") {
+            discogs::parse_discogs_url(s).map(ReleaseReference::Discogs)
+        } else if s.contains("musicbrainz.org") {
+            musicbrainz::parse_musicbrainz_url(s)
+                .filter(|id| !id.is_empty())
+                .map(ReleaseReference::MusicBrainz)
+        } else if let Ok(id) = s.parse() {
+            Some(ReleaseReference::Discogs(id))
+        } else {
+            musicbrainz::parse_musicbrainz_url(s).map(ReleaseReference::MusicBrainz)
+        }
+    }
+
+    pub fn url(&self) -> String {
+        match self {
+            ReleaseReference::Discogs(id) => format!("https://www.discogs.com/release/{}", id),
+            ReleaseReference::MusicBrainz(id) => format!("https://musicbrainz.org/release/{}", id),
+        }
+    }
+}
+
+/// Fetch a given release and pick the side that matches a file, by
+/// duration and by the song titles known for it (if any).
+///
+/// Discogs tracks without durations get them from MusicBrainz if possible.
+pub fn release_side(
+    reference: &ReleaseReference,
+    path: &str,
+    file_duration_seconds: f64,
+    song_titles: &[String],
+    verbose: bool,
+) -> Result<Option<FileSideResult>, Box<dyn Error>> {
+    let (artist, album_title, year, side_label, mut tracks, mut backend) = match reference {
+        ReleaseReference::Discogs(id) => {
+            let mut rl = discogs::create_rate_limiter(discogs::has_credentials());
+            let release = discogs::fetch_release(*id, &mut rl)?;
+            let Some(side) = discogs::find_best_side(&release, file_duration_seconds, song_titles, verbose) else {
+                return Ok(None);
+            };
+            let tracks = discogs::side_to_expected_tracks(side);
+            (release.artist.clone(), release.title.clone(), release.year, side.label, tracks, "Discogs".to_string())
+        }
+        ReleaseReference::MusicBrainz(id) => {
+            let release = musicbrainz::fetch_release_details(id)?;
+            let Some(tracks) = musicbrainz::find_best_side(&release.sides, file_duration_seconds, song_titles) else {
+                return Ok(None);
+            };
+            (release.artist, release.title, release.year, '?', tracks, "MusicBrainz".to_string())
+        }
+    };
+    if tracks.is_empty() {
+        return Ok(None);
+    }
+
+    if !tracks.iter().any(|t| t.length_seconds > 0.0) {
+        let titles: Vec<String> = tracks.iter().map(|t| t.title.clone()).collect();
+        let musicbrainz = MusicBrainzBackend { vinyl_only: false };
+        if let Ok(Some(enriched)) =
+            musicbrainz.fetch_durations_for_album(&artist, &album_title, &titles, file_duration_seconds, verbose)
+        {
+            tracks = enriched;
+            backend = format!("{} + {} (durations)", backend, musicbrainz.name());
+        }
+    }
+
+    Ok(Some(FileSideResult {
+        path: path.to_string(),
+        artist,
+        album_title,
+        release_info: reference.url(),
+        year,
+        side_label,
+        tracks,
+        backend,
+        score: 0.0,
+    }))
+}
+
 // ── Multi-file side assignment ───────────────────────────────────────────────
 
 /// Per-file data needed for side assignment.
@@ -284,6 +409,8 @@ pub struct FileSideResult {
     pub album_title: String,
     /// Human-readable release reference (URL or ID string)
     pub release_info: String,
+    /// Release year, if the backend knows it
+    pub year: Option<u32>,
     /// Side letter assigned ('A', 'B', … or '?' if unmatched)
     pub side_label: char,
     /// Ordered track list for the assigned side
@@ -355,25 +482,24 @@ pub fn assign_files_to_album_sides(
     }
 
     if verbose {
-        println!("Score matrix:");
-        print!("  {:>42}", "");
+        info!("Score matrix:");
+        let mut header = format!("  {:>42}", "");
         for side in &album.sides {
-            print!("  Side {} ", side.label);
+            header.push_str(&format!("  Side {} ", side.label));
         }
-        println!();
+        info!("{}", header);
         for (fi, file) in files.iter().enumerate() {
             let name = std::path::Path::new(&file.path)
                 .file_name()
                 .and_then(|n| n.to_str())
                 .unwrap_or(&file.path);
             let s = if name.len() > 42 { &name[..42] } else { name };
-            print!("  {:>42}", s);
+            let mut row = format!("  {:>42}", s);
             for si in 0..n_sides {
-                print!("  {:>6.1}", scores[fi][si]);
+                row.push_str(&format!("  {:>6.1}", scores[fi][si]));
             }
-            println!();
+            info!("{}", row);
         }
-        println!();
     }
 
     // Greedy assignment: pick highest score, mark both file and side as used
@@ -413,6 +539,7 @@ pub fn assign_files_to_album_sides(
                 artist: album.artist.clone(),
                 album_title: album.album_title.clone(),
                 release_info: album.release_info.clone(),
+                year: album.year,
                 side_label: side.label,
                 tracks: side.tracks.clone(),
                 backend: album.backend.clone(),
@@ -424,6 +551,7 @@ pub fn assign_files_to_album_sides(
                 artist: album.artist.clone(),
                 album_title: album.album_title.clone(),
                 release_info: album.release_info.clone(),
+                year: album.year,
                 side_label: '?',
                 tracks: Vec::new(),
                 backend: format!("{} (no side matched)", album.backend),
@@ -432,3 +560,25 @@ pub fn assign_files_to_album_sides(
         }
     }).collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_release_reference() {
+        let mbid = "768a1c5f-3657-4e29-aac4-c1de6ee5221f";
+        assert_eq!(
+            ReleaseReference::parse("https://www.discogs.com/release/1873013-Pink-Floyd-Animals"),
+            Some(ReleaseReference::Discogs(1873013))
+        );
+        assert_eq!(
+            ReleaseReference::parse(&format!("https://musicbrainz.org/release/{}", mbid)),
+            Some(ReleaseReference::MusicBrainz(mbid.to_string()))
+        );
+        assert_eq!(ReleaseReference::parse("1873013"), Some(ReleaseReference::Discogs(1873013)));
+        assert_eq!(ReleaseReference::parse(mbid), Some(ReleaseReference::MusicBrainz(mbid.to_string())));
+        assert_eq!(ReleaseReference::parse("Animals"), None);
+        assert_eq!(ReleaseReference::Discogs(42).url(), "https://www.discogs.com/release/42");
+    }
+}
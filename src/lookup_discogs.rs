@@ -55,6 +55,7 @@ impl AlbumIdentifier for DiscogsBackend {
                 "https://www.discogs.com/release/{}",
                 release.release_id
             ),
+            year: release.year,
             tracks,
             backend: "Discogs".to_string(),
         }))
@@ -91,6 +92,7 @@ impl AlbumIdentifier for DiscogsBackend {
                 "https://www.discogs.com/release/{}",
                 release.release_id
             ),
+            year: release.year,
             sides,
             backend: "Discogs".to_string(),
         }))
@@ -2,10 +2,11 @@
 
 use std::error::Error;
 
+use tracing::info;
+
 use crate::album_identifier::IdentifiedSong;
 use crate::lookup::{AlbumIdentifier, AlbumSideResult};
 use crate::musicbrainz;
-use crate::rate_limiter::RateLimiter;
 
 /// Looks up the album via the MusicBrainz API.
 /// When `vinyl_only` is true only vinyl releases are considered.
@@ -38,7 +39,8 @@ impl AlbumIdentifier for MusicBrainzBackend {
             None => return Ok(None),
         };
 
-        let sides = musicbrainz::fetch_release_sides(&best.release_id)?;
+        let release = musicbrainz::fetch_release_details(&best.release_id)?;
+        let sides = release.sides;
 
         let song_titles: Vec<String> = songs.iter().map(|s| s.title.clone()).collect();
 
@@ -65,6 +67,7 @@ impl AlbumIdentifier for MusicBrainzBackend {
                 "https://musicbrainz.org/release/{}",
                 best.release_id
             ),
+            year: release.year,
             tracks: side_tracks,
             backend: self.name().to_string(),
         }))
@@ -78,14 +81,11 @@ impl AlbumIdentifier for MusicBrainzBackend {
         file_duration_seconds: f64,
         verbose: bool,
     ) -> Result<Option<Vec<musicbrainz::ExpectedTrack>>, Box<dyn Error>> {
-        let mut rl = RateLimiter::from_millis("MusicBrainz", 1100);
-
         let results = musicbrainz::search_release(artist, album_title, 10)?;
-        rl.wait_if_needed();
 
         if results.is_empty() {
             if verbose {
-                println!("  [{}] No releases found for duration enrichment", self.name());
+                info!("  [{}] No releases found for duration enrichment", self.name());
             }
             return Ok(None);
         }
@@ -101,16 +101,15 @@ impl AlbumIdentifier for MusicBrainzBackend {
         for result in &candidates {
             let sides = match musicbrainz::fetch_release_sides(&result.release_id) {
                 Ok(s) => s,
-                Err(_) => { rl.wait_if_needed(); continue; }
+                Err(_) => continue,
             };
-            rl.wait_if_needed();
 
             if let Some(tracks) = musicbrainz::find_best_side(&sides, file_duration_seconds, track_titles) {
                 let total_dur: f64 = tracks.iter().map(|t| t.length_seconds).sum();
                 if total_dur > 0.0 {
                     if verbose {
-                        println!("  [{}] Found durations from release {}",
-                                 self.name(), result.release_id);
+                        info!("  [{}] Found durations from release {}",
+                              self.name(), result.release_id);
                     }
                     return Ok(Some(tracks));
                 }
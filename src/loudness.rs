@@ -0,0 +1,380 @@
+//! Loudness measurement per EBU R128 / ITU-R BS.1770-4.
+//!
+//! The recording is K-weighted (a high-shelf for the head and a high-pass
+//! for the low end) and the mean square of every 100 ms is kept, together
+//! with the true peak of that time. From these, [`LoudnessAnalysis`] gives
+//! the loudness of the whole recording or of any part of it (a track
+//! between two CUE boundaries) without reading the file again:
+//! - integrated loudness: 400 ms blocks, gated at -70 LUFS and 10 LU below
+//!   the ungated mean
+//! - loudness range (LRA): spread between the 10th and 95th percentile of
+//!   the 3 s short-term loudness, gated at -70 LUFS and 20 LU below the mean
+//! - true peak, 4× oversampled (see [`crate::decibel::TruePeakDetector`])
+//!
+//! All channels are weighted equally, as BS.1770 does for mono and stereo.
+
+use crate::audio_analysis::WavData;
+use crate::decibel::{true_peak_to_db, TruePeakDetector};
+use crate::downmix::Downmix;
+use crate::error::Result;
+use crate::filters::Biquad;
+use crate::progress::{ProgressSink, STAGE_LOUDNESS};
+use crate::wavfile::read_channel_names;
+
+/// Blocks below this loudness are never counted, in LUFS
+pub const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+
+/// Target loudness of ReplayGain 2.0 in LUFS
+pub const REPLAY_GAIN_REFERENCE_LUFS: f64 = -18.0;
+
+/// Relative gate of the integrated loudness, in LU
+const RELATIVE_GATE_LU: f64 = -10.0;
+
+/// Relative gate of the loudness range, in LU
+const RANGE_RELATIVE_GATE_LU: f64 = -20.0;
+
+/// Percentiles of the short-term loudness that span the loudness range
+const RANGE_LOW_PERCENTILE: f64 = 0.10;
+const RANGE_HIGH_PERCENTILE: f64 = 0.95;
+
+/// Measurement steps per second; momentary (400 ms) and short-term (3 s)
+/// blocks are made of whole steps
+const HOPS_PER_SECOND: u32 = 10;
+const MOMENTARY_HOPS: usize = 4;
+const SHORT_TERM_HOPS: usize = 30;
+
+/// Floor for dBTP values of silent parts
+const MIN_TRUE_PEAK_DB: f64 = -120.0;
+
+fn power_to_lufs(power: f64) -> f64 {
+    -0.691 + 10.0 * power.log10()
+}
+
+fn lufs_to_power(lufs: f64) -> f64 {
+    10.0_f64.powf((lufs + 0.691) / 10.0)
+}
+
+/// K-weighting filter: high-shelf pre-filter and RLB high-pass, with the
+/// BS.1770 coefficients re-derived for `sample_rate`
+fn k_weighting(sample_rate: u32) -> [Biquad; 2] {
+    let rate = sample_rate as f64;
+
+    let f0 = 1681.974450955533;
+    let gain_db = 3.999843853973347;
+    let q = 0.7071752369554196;
+    let k = (std::f64::consts::PI * f0 / rate).tan();
+    let vh = 10.0_f64.powf(gain_db / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let shelf = Biquad::new(
+        vh + vb * k / q + k * k,
+        2.0 * (k * k - vh),
+        vh - vb * k / q + k * k,
+        1.0 + k / q + k * k,
+        2.0 * (k * k - 1.0),
+        1.0 - k / q + k * k,
+    );
+
+    let f0 = 38.13547087602444;
+    let q = 0.5003270373238773;
+    let k = (std::f64::consts::PI * f0 / rate).tan();
+    let high_pass = Biquad::new(1.0, -2.0, 1.0, 1.0 + k / q + k * k, 2.0 * (k * k - 1.0), 1.0 - k / q + k * k);
+
+    [shelf, high_pass]
+}
+
+/// Loudness of a recording or a part of it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Loudness {
+    /// Integrated loudness in LUFS, None if everything is below the gate
+    pub integrated: Option<f64>,
+    /// Loudness range in LU
+    pub range: f64,
+    /// True peak, linear (1.0 = full scale)
+    pub true_peak: f64,
+}
+
+impl Loudness {
+    pub fn true_peak_db(&self) -> f64 {
+        true_peak_to_db(self.true_peak, MIN_TRUE_PEAK_DB)
+    }
+
+    /// ReplayGain 2.0 gain in dB that brings this to -18 LUFS
+    pub fn replay_gain(&self) -> Option<f64> {
+        self.integrated.map(|lufs| REPLAY_GAIN_REFERENCE_LUFS - lufs)
+    }
+
+    /// e.g. `-14.2 LUFS, LRA 8.1 LU, true peak -0.3 dBTP`
+    pub fn describe(&self) -> String {
+        match self.integrated {
+            Some(lufs) => format!(
+                "{:.1} LUFS, LRA {:.1} LU, true peak {:.1} dBTP",
+                lufs,
+                self.range,
+                self.true_peak_db()
+            ),
+            None => "silent".to_string(),
+        }
+    }
+}
+
+/// Block powers above the absolute gate and no more than `relative_gate_lu`
+/// below the mean of those; None if no block passes the absolute gate
+fn gated_blocks(blocks: &[f64], relative_gate_lu: f64) -> Option<Vec<f64>> {
+    let absolute = lufs_to_power(ABSOLUTE_GATE_LUFS);
+    let above: Vec<f64> = blocks.iter().copied().filter(|&p| p > absolute).collect();
+    if above.is_empty() {
+        return None;
+    }
+    let mean = above.iter().sum::<f64>() / above.len() as f64;
+    let relative = mean * 10.0_f64.powf(relative_gate_lu / 10.0);
+    Some(above.into_iter().filter(|&p| p > relative).collect())
+}
+
+/// Mean power of each block of `length` hops, advancing one hop at a time
+fn blocks(hop_powers: &[f64], length: usize) -> Vec<f64> {
+    hop_powers
+        .windows(length)
+        .map(|w| w.iter().sum::<f64>() / length as f64)
+        .collect()
+}
+
+/// Per-100 ms measurements of a whole recording
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LoudnessAnalysis {
+    hop_powers: Vec<f64>,
+    hop_peaks: Vec<f64>,
+}
+
+impl LoudnessAnalysis {
+    /// Length of the measured audio in seconds
+    pub fn duration(&self) -> f64 {
+        self.hop_powers.len() as f64 / HOPS_PER_SECOND as f64
+    }
+
+    /// Loudness of the whole recording
+    pub fn whole(&self) -> Loudness {
+        self.segment(0.0, None)
+    }
+
+    /// Loudness from `start` to `end` seconds (None for the end of the recording)
+    pub fn segment(&self, start: f64, end: Option<f64>) -> Loudness {
+        let hop = |seconds: f64| ((seconds.max(0.0) * HOPS_PER_SECOND as f64).round() as usize).min(self.hop_powers.len());
+        let first = hop(start);
+        let last = end.map_or(self.hop_powers.len(), hop).max(first);
+        let powers = &self.hop_powers[first..last];
+
+        let integrated = gated_blocks(&blocks(powers, MOMENTARY_HOPS), RELATIVE_GATE_LU)
+            .map(|gated| power_to_lufs(gated.iter().sum::<f64>() / gated.len() as f64));
+
+        let mut levels: Vec<f64> = gated_blocks(&blocks(powers, SHORT_TERM_HOPS), RANGE_RELATIVE_GATE_LU)
+            .unwrap_or_default()
+            .into_iter()
+            .map(power_to_lufs)
+            .collect();
+        levels.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let range = if levels.len() < 2 {
+            0.0
+        } else {
+            let at = |q: f64| levels[((levels.len() - 1) as f64 * q).round() as usize];
+            at(RANGE_HIGH_PERCENTILE) - at(RANGE_LOW_PERCENTILE)
+        };
+
+        Loudness {
+            integrated,
+            range,
+            true_peak: self.hop_peaks[first..last].iter().fold(0.0, |m: f64, &p| m.max(p)),
+        }
+    }
+
+    /// Text for the recording's info file: the whole recording and each
+    /// track, given as (start, end) in seconds
+    pub fn to_text(&self, tracks: &[(f64, f64)]) -> String {
+        let mut text = String::new();
+        text.push_str("Loudness (EBU R128):\n");
+        text.push_str("--------------------\n");
+        text.push_str(&format!("Recording: {}\n", self.whole().describe()));
+        for (i, &(start, end)) in tracks.iter().enumerate() {
+            let loudness = self.segment(start, Some(end));
+            let gain = loudness
+                .replay_gain()
+                .map(|g| format!(", ReplayGain {:+.2} dB", g))
+                .unwrap_or_default();
+            text.push_str(&format!("Track {}: {}{}\n", i + 1, loudness.describe(), gain));
+        }
+        text
+    }
+}
+
+/// Streaming loudness measurement
+pub struct LoudnessMeter {
+    full_scale: f64,
+    hop_frames: usize,
+    filters: Vec<[Biquad; 2]>,
+    peak_detectors: Vec<TruePeakDetector>,
+    hop_energy: f64,
+    hop_peak: f64,
+    hop_fill: usize,
+    analysis: LoudnessAnalysis,
+}
+
+impl LoudnessMeter {
+    /// `full_scale` is the sample value of 0 dBFS
+    pub fn new(sample_rate: u32, channels: usize, full_scale: f64) -> Self {
+        LoudnessMeter {
+            full_scale,
+            hop_frames: (sample_rate / HOPS_PER_SECOND).max(1) as usize,
+            filters: (0..channels).map(|_| k_weighting(sample_rate)).collect(),
+            peak_detectors: (0..channels).map(|_| TruePeakDetector::new()).collect(),
+            hop_energy: 0.0,
+            hop_peak: 0.0,
+            hop_fill: 0,
+            analysis: LoudnessAnalysis::default(),
+        }
+    }
+
+    /// Process de-interleaved audio, one vector per channel
+    pub fn process(&mut self, audio: &[Vec<i32>]) {
+        let frames = audio.iter().map(|ch| ch.len()).min().unwrap_or(0);
+        let mut pos = 0;
+        while pos < frames {
+            let n = (self.hop_frames - self.hop_fill).min(frames - pos);
+            for (ch, samples) in audio.iter().enumerate().take(self.filters.len()) {
+                let samples = &samples[pos..pos + n];
+                let [shelf, high_pass] = &mut self.filters[ch];
+                for &sample in samples {
+                    let y = high_pass.process_sample(shelf.process_sample(sample as f64 / self.full_scale));
+                    self.hop_energy += y * y;
+                }
+                let peak = self.peak_detectors[ch].process(samples, self.full_scale);
+                self.hop_peak = self.hop_peak.max(peak);
+            }
+            pos += n;
+            self.hop_fill += n;
+            if self.hop_fill == self.hop_frames {
+                self.analysis.hop_powers.push(self.hop_energy / self.hop_frames as f64);
+                self.analysis.hop_peaks.push(self.hop_peak);
+                self.hop_energy = 0.0;
+                self.hop_peak = 0.0;
+                self.hop_fill = 0;
+            }
+        }
+    }
+
+    /// The measurements; an incomplete last 100 ms are dropped
+    pub fn finish(self) -> LoudnessAnalysis {
+        self.analysis
+    }
+}
+
+/// Measure the loudness of a WAV file.
+///
+/// Mid/side recordings (see [`crate::downmix`]) are measured as the left
+/// and right channels they decode to.
+///
+/// Progress is reported to `progress` as the stage [`STAGE_LOUDNESS`].
+pub fn analyze_wav(path: &str, progress: &mut dyn ProgressSink) -> Result<LoudnessAnalysis> {
+    let wav = WavData::open(path)?;
+    let (format, channels, frame_bytes) = (wav.format, wav.channels(), wav.frame_bytes());
+    let mid_side = Downmix::from_channel_names(&read_channel_names(path).unwrap_or_default()) == Downmix::MidSide;
+
+    let mut meter = LoudnessMeter::new(wav.header.sample_rate, channels, format.max_value());
+    wav.for_each_data_chunk(false, |data, percent| {
+        let mut samples = format.decode_samples(data);
+        if mid_side {
+            Downmix::decode_mid_side(&mut samples, (format.max_value() - 1.0) as i32);
+        }
+        let mut audio = vec![Vec::with_capacity(data.len() / frame_bytes); channels];
+        for (i, sample) in samples.into_iter().enumerate() {
+            audio[i % channels].push(sample);
+        }
+        meter.process(&audio);
+        progress.progress(percent, STAGE_LOUDNESS, "");
+        Ok(())
+    })?;
+    progress.finish(STAGE_LOUDNESS);
+    Ok(meter.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::NoProgress;
+    use crate::wavfile::write_wav_samples;
+
+    fn sine(rate: u32, frequency: f64, level_dbfs: f64, seconds: f64, full_scale: f64) -> Vec<i32> {
+        let amplitude = 10.0_f64.powf(level_dbfs / 20.0) * full_scale;
+        (0..(seconds * rate as f64) as usize)
+            .map(|n| (amplitude * (2.0 * std::f64::consts::PI * frequency * n as f64 / rate as f64).sin()).round() as i32)
+            .collect()
+    }
+
+    #[test]
+    fn test_stereo_sine_loudness() {
+        // EBU Tech 3341 case 1: stereo 1 kHz at -23 dBFS reads -23 LUFS
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tone.wav").to_string_lossy().to_string();
+        let rate = 48000;
+        let tone = sine(rate, 1000.0, -23.0, 5.0, 8388607.0);
+        write_wav_samples(&path, &[tone.clone(), tone], rate, 24).unwrap();
+
+        let analysis = analyze_wav(&path, &mut NoProgress).unwrap();
+        assert!((analysis.duration() - 5.0).abs() < 1e-9);
+        let loudness = analysis.whole();
+        let lufs = loudness.integrated.unwrap();
+        assert!((lufs + 23.0).abs() < 0.1, "{:.2} LUFS", lufs);
+        assert!((loudness.replay_gain().unwrap() - 5.0).abs() < 0.1);
+        assert!((loudness.true_peak_db() + 23.0).abs() < 0.1);
+        assert!(loudness.range < 0.1);
+
+        let mut meter = LoudnessMeter::new(rate, 2, 32767.0);
+        meter.process(&[vec![0; 48000], vec![0; 48000]]);
+        let silence = meter.finish().whole();
+        assert_eq!(silence.integrated, None);
+        assert_eq!(silence.describe(), "silent");
+    }
+
+    #[test]
+    fn test_mid_side_loudness() {
+        // The tone of the stereo case as mid/side reads as loud, not 3 dB lower
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ms.wav").to_string_lossy().to_string();
+        let rate = 48000;
+        let tone = sine(rate, 1000.0, -23.0, 5.0, 8388607.0);
+        let mut writer = crate::wavfile::WavWriter::new(&path, rate, 2, crate::SampleFormat::S24).unwrap();
+        writer.write_samples(&tone.iter().flat_map(|&m| [m, 0]).collect::<Vec<i32>>()).unwrap();
+        writer.set_ixml(crate::wavfile::WavIxml {
+            track_names: Downmix::MidSide.channel_names(2),
+            ..Default::default()
+        });
+        writer.finalize().unwrap();
+
+        let lufs = analyze_wav(&path, &mut NoProgress).unwrap().whole().integrated.unwrap();
+        assert!((lufs + 23.0).abs() < 0.1, "{:.2} LUFS", lufs);
+    }
+
+    #[test]
+    fn test_loudness_range_and_segments() {
+        // EBU Tech 3342 case 1: 20 s at -20 dBFS, then 20 s at -30 dBFS
+        let rate = 48000;
+        let mut tone = sine(rate, 1000.0, -20.0, 20.0, 32767.0);
+        tone.extend(sine(rate, 1000.0, -30.0, 20.0, 32767.0));
+        let mut meter = LoudnessMeter::new(rate, 1, 32767.0);
+        for chunk in tone.chunks(7000) {
+            meter.process(&[chunk.to_vec()]);
+        }
+        let analysis = meter.finish();
+
+        let range = analysis.whole().range;
+        assert!((range - 10.0).abs() < 1.0, "LRA {:.2}", range);
+        // A mono sine reads 3 dB below its peak level
+        let loud = analysis.segment(0.0, Some(20.0)).integrated.unwrap();
+        let quiet = analysis.segment(20.0, None).integrated.unwrap();
+        assert!((loud + 23.0).abs() < 0.2, "{:.2} LUFS", loud);
+        assert!((quiet + 33.0).abs() < 0.2, "{:.2} LUFS", quiet);
+
+        let text = analysis.to_text(&[(0.0, 20.0), (20.0, 40.0)]);
+        assert!(text.contains("Track 2: -33.0 LUFS"), "{}", text);
+        assert!(text.starts_with("Loudness (EBU R128):"));
+        assert!(text.contains("Track 1: -23.0 LUFS"), "{}", text);
+    }
+}
@@ -0,0 +1,177 @@
+//! Metadata given by hand for a recording.
+//!
+//! Bootlegs, test pressings and obscure releases are unknown to Shazam,
+//! MusicBrainz and Discogs. A `<basename>.meta.toml` next to the WAV file
+//! names the album and its tracks instead; `cue_creator` then skips song
+//! identification and album lookup for that file.
+//!
+//! ```toml
+//! artist = "Pink Floyd"
+//! album = "Live in Pompeii (bootleg)"
+//! release = "https://www.discogs.com/release/1234567"
+//! year = 1972
+//! side = "A"
+//!
+//! [[track]]
+//! title = "Echoes, Part 1"
+//! length = "11:02"
+//!
+//! [[track]]
+//! title = "Careful with That Axe, Eugene"
+//! length = 330
+//! ```
+//!
+//! Only `artist`, `album` and the track titles are required. With the
+//! lengths of all tracks, the boundaries are searched near the expected
+//! positions.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::cuefile::wav_base_path;
+use crate::discogs::parse_duration;
+use crate::error::{Error, Result};
+use crate::lookup::FileSideResult;
+use crate::musicbrainz::ExpectedTrack;
+
+/// Backend name of results taken from a metadata file
+pub const METADATA_BACKEND: &str = "meta.toml";
+
+/// Length of a track: seconds or "M:SS"
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum TrackLength {
+    Seconds(f64),
+    Text(String),
+}
+
+impl TrackLength {
+    pub fn seconds(&self) -> f64 {
+        match self {
+            TrackLength::Seconds(s) => *s,
+            TrackLength::Text(s) => s.trim().parse().unwrap_or_else(|_| parse_duration(s.trim())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct MetadataTrack {
+    pub title: String,
+    #[serde(default)]
+    pub length: Option<TrackLength>,
+}
+
+/// Contents of a `.meta.toml` file
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct RecordingMetadata {
+    pub artist: String,
+    pub album: String,
+    /// Release URL, used for tags when it points to Discogs or MusicBrainz
+    #[serde(default)]
+    pub release: Option<String>,
+    #[serde(default)]
+    pub year: Option<u32>,
+    /// Side of the release this recording is
+    #[serde(default)]
+    pub side: Option<char>,
+    #[serde(default, rename = "track")]
+    pub tracks: Vec<MetadataTrack>,
+}
+
+/// Path of the metadata file of a recording
+pub fn metadata_path(wav_file: &str) -> PathBuf {
+    PathBuf::from(format!("{}.meta.toml", wav_base_path(wav_file).display()))
+}
+
+impl RecordingMetadata {
+    pub fn parse(content: &str) -> Result<Self> {
+        toml::from_str(content).map_err(|e| Error::Config(e.to_string()))
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)?;
+        Self::parse(&content).map_err(|e| Error::Config(format!("{}: {}", path.display(), e)))
+    }
+
+    /// Metadata file of `wav_file`; None if there is none
+    pub fn load_for(wav_file: &str) -> Option<Result<Self>> {
+        let path = metadata_path(wav_file);
+        path.exists().then(|| Self::load(&path))
+    }
+
+    /// Expected tracks, starting at 0; tracks without a length get 0
+    pub fn expected_tracks(&self) -> Vec<ExpectedTrack> {
+        let mut start = 0.0;
+        self.tracks
+            .iter()
+            .enumerate()
+            .map(|(i, track)| {
+                let length = track.length.as_ref().map_or(0.0, TrackLength::seconds);
+                let expected = ExpectedTrack {
+                    position: i as u32 + 1,
+                    title: track.title.clone(),
+                    length_seconds: length,
+                    expected_start: start,
+                };
+                start += length;
+                expected
+            })
+            .collect()
+    }
+
+    /// The metadata as the lookup result of `wav_file`
+    pub fn side_result(&self, wav_file: &str) -> FileSideResult {
+        FileSideResult {
+            path: wav_file.to_string(),
+            artist: self.artist.clone(),
+            album_title: self.album.clone(),
+            // Sides of the same release need the same reference in session.toml
+            release_info: self.release.clone().unwrap_or_else(|| format!("{} - {}", self.artist, self.album)),
+            year: self.year,
+            side_label: self.side.map_or('?', |s| s.to_ascii_uppercase()),
+            tracks: self.expected_tracks(),
+            backend: METADATA_BACKEND.to_string(),
+            score: 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metadata() {
+        let metadata = RecordingMetadata::parse(
+            r#"
+            artist = "Pink Floyd"
+            album = "Live in Pompeii"
+            side = "b"
+
+            [[track]]
+            title = "Echoes, Part 1"
+            length = "11:02"
+
+            [[track]]
+            title = "Careful with That Axe, Eugene"
+            length = 330
+
+            [[track]]
+            title = "Echoes, Part 2"
+            "#,
+        )
+        .unwrap();
+        let result = metadata.side_result("/rec/pompeii.2.wav");
+        assert_eq!(result.side_label, 'B');
+        assert_eq!(result.release_info, "Pink Floyd - Live in Pompeii");
+        assert_eq!(result.backend, METADATA_BACKEND);
+        let starts: Vec<f64> = result.tracks.iter().map(|t| t.expected_start).collect();
+        assert_eq!(starts, vec![0.0, 662.0, 992.0]);
+        assert_eq!(result.tracks[2].length_seconds, 0.0);
+
+        assert_eq!(metadata_path("/rec/pompeii.2.wav"), PathBuf::from("/rec/pompeii.2.meta.toml"));
+        assert!(RecordingMetadata::parse("artist = \"x\"").is_err());
+    }
+}
@@ -0,0 +1,232 @@
+//! MQTT status publishing and control of the `autorecd` daemon.
+//!
+//! Like the HTTP API (see [`crate::control_api`]) the client only reads the
+//! shared [`DaemonState`](crate::control_api::DaemonState) and passes commands to the capture loop, on two
+//! threads of its own: one drives the connection, the other publishes.
+//!
+//! Topics below the prefix (default `autorec`):
+//! - `<prefix>/availability` - `online`, `offline` when the daemon is gone
+//!   (retained, the broker publishes `offline` as last will)
+//! - `<prefix>/state`   - idle, armed, recording, paused or disconnected
+//!   (retained)
+//! - `<prefix>/status`  - the JSON of `GET /status` without the uptime
+//!   (retained)
+//! - `<prefix>/album`   - artist, album, year and release of the latest
+//!   identified recording (retained)
+//! - `<prefix>/levels`  - the JSON of `GET /levels`, every interval
+//! - `<prefix>/command` - subscribed: `start`, `stop`, `split`, `pause` or
+//!   `resume`
+
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use rumqttc::{Client, Event, LastWill, MqttOptions, Packet, QoS};
+use serde_json::{json, Value};
+use tracing::{info, warn};
+
+use crate::config::MqttConfig;
+use crate::control_api::{ApiCommand, SharedState};
+use crate::error::{Error, Result};
+
+pub const DEFAULT_TOPIC_PREFIX: &str = "autorec";
+pub const DEFAULT_PORT: u16 = 1883;
+
+/// Seconds between level updates unless the config sets `interval`
+pub const DEFAULT_INTERVAL: f64 = 1.0;
+
+/// Wait before reconnecting after the broker was lost
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Connection settings from the `[mqtt]` section
+#[derive(Debug, Clone, PartialEq)]
+pub struct MqttSettings {
+    pub host: String,
+    pub port: u16,
+    pub topic_prefix: String,
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub interval: Duration,
+}
+
+impl MqttSettings {
+    /// Settings of `config`; None if no broker is set
+    pub fn from_config(config: &MqttConfig) -> Result<Option<Self>> {
+        let broker = match config.broker.as_deref().map(str::trim) {
+            Some(broker) if !broker.is_empty() => broker,
+            _ => return Ok(None),
+        };
+        let (host, port) = parse_broker(broker)?;
+        let interval = config.interval.unwrap_or(DEFAULT_INTERVAL);
+        if !interval.is_finite() || interval <= 0.0 {
+            return Err(Error::Config(format!("Invalid MQTT interval: {}", interval)));
+        }
+        let client_id = config.client_id.clone().unwrap_or_else(|| {
+            let hostname = fs::read_to_string("/proc/sys/kernel/hostname").unwrap_or_default();
+            match hostname.trim() {
+                "" => DEFAULT_TOPIC_PREFIX.to_string(),
+                hostname => format!("autorec-{}", hostname),
+            }
+        });
+        Ok(Some(MqttSettings {
+            host,
+            port,
+            topic_prefix: config
+                .topic_prefix
+                .as_deref()
+                .map(|p| p.trim_end_matches('/').to_string())
+                .unwrap_or_else(|| DEFAULT_TOPIC_PREFIX.to_string()),
+            client_id,
+            username: config.username.clone(),
+            password: config.password.clone(),
+            interval: Duration::from_secs_f64(interval),
+        }))
+    }
+
+    fn topic(&self, name: &str) -> String {
+        format!("{}/{}", self.topic_prefix, name)
+    }
+}
+
+/// Host and port of a broker address: `host`, `host:port` or
+/// `mqtt://host:port`
+pub fn parse_broker(broker: &str) -> Result<(String, u16)> {
+    let address = broker.strip_prefix("mqtt://").unwrap_or(broker).trim_end_matches('/');
+    let invalid = || Error::Config(format!("Invalid MQTT broker: {}", broker));
+    let (host, port) = match address.rsplit_once(':') {
+        Some((host, port)) => (host, port.parse().map_err(|_| invalid())?),
+        None => (address, DEFAULT_PORT),
+    };
+    if host.is_empty() || host.contains('/') {
+        return Err(invalid());
+    }
+    Ok((host.to_string(), port))
+}
+
+/// Command of a message on the command topic
+pub fn parse_command(payload: &[u8]) -> Option<ApiCommand> {
+    match String::from_utf8_lossy(payload).trim().to_lowercase().as_str() {
+        "start" => Some(ApiCommand::Arm),
+        "stop" => Some(ApiCommand::Disarm),
+        "split" => Some(ApiCommand::Split),
+        "pause" => Some(ApiCommand::Pause),
+        "resume" => Some(ApiCommand::Resume),
+        _ => None,
+    }
+}
+
+/// Connect to the broker and publish the state until the daemon exits.
+///
+/// Connection errors are reported once; the client keeps reconnecting.
+pub fn start(settings: MqttSettings, state: SharedState, commands: Sender<ApiCommand>) {
+    let availability = settings.topic("availability");
+    let command_topic = settings.topic("command");
+
+    let mut options = MqttOptions::new(&settings.client_id, &settings.host, settings.port);
+    options.set_keep_alive(Duration::from_secs(30));
+    options.set_last_will(LastWill::new(&availability, "offline", QoS::AtLeastOnce, true));
+    if let Some(username) = &settings.username {
+        options.set_credentials(username, settings.password.as_deref().unwrap_or(""));
+    }
+    let (client, mut connection) = Client::new(options, 64);
+
+    let broker = format!("{}:{}", settings.host, settings.port);
+    // Set on every (re)connect so the retained topics are published again
+    let connected = Arc::new(AtomicBool::new(false));
+
+    {
+        let client = client.clone();
+        let connected = Arc::clone(&connected);
+        thread::spawn(move || {
+            let mut reported = false;
+            for event in connection.iter() {
+                match event {
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                        info!("Connected to MQTT broker {}", broker);
+                        reported = false;
+                        let _ = client.try_subscribe(&command_topic, QoS::AtLeastOnce);
+                        let _ = client.try_publish(&availability, QoS::AtLeastOnce, true, "online");
+                        connected.store(true, Ordering::SeqCst);
+                    }
+                    Ok(Event::Incoming(Packet::Publish(message))) if message.topic == command_topic => {
+                        match parse_command(&message.payload) {
+                            Some(command) => {
+                                if commands.send(command).is_err() {
+                                    break;
+                                }
+                            }
+                            None => warn!("MQTT: Unknown command '{}'", String::from_utf8_lossy(&message.payload)),
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        if !reported {
+                            warn!("MQTT: {} (retrying)", e);
+                            reported = true;
+                        }
+                        thread::sleep(RECONNECT_DELAY);
+                    }
+                }
+            }
+        });
+    }
+
+    thread::spawn(move || {
+        let mut published: Option<(Value, Value)> = None;
+        loop {
+            thread::sleep(settings.interval);
+            if connected.swap(false, Ordering::SeqCst) {
+                published = None;
+            }
+
+            let (state_name, mut status, album, levels) = {
+                let state = state.lock().unwrap();
+                (state.state_name(), state.status_json(), json!(state.album), json!(state.levels))
+            };
+            if let Some(status) = status.as_object_mut() {
+                status.remove("uptime");
+            }
+            if published.as_ref().map(|(s, _)| s) != Some(&status) {
+                let _ = client.try_publish(settings.topic("state"), QoS::AtLeastOnce, true, state_name);
+                let _ = client.try_publish(settings.topic("status"), QoS::AtLeastOnce, true, status.to_string());
+            }
+            if published.as_ref().map(|(_, a)| a) != Some(&album) && !album.is_null() {
+                let _ = client.try_publish(settings.topic("album"), QoS::AtLeastOnce, true, album.to_string());
+            }
+            let _ = client.try_publish(settings.topic("levels"), QoS::AtMostOnce, false, levels.to_string());
+            published = Some((status, album));
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings() {
+        assert_eq!(parse_broker("mqtt://homeassistant.local:1884").unwrap(), ("homeassistant.local".to_string(), 1884));
+        assert_eq!(parse_broker("10.0.0.2").unwrap(), ("10.0.0.2".to_string(), DEFAULT_PORT));
+        assert!(parse_broker("broker:port").is_err());
+        assert!(parse_broker("http://broker/").is_err());
+
+        assert_eq!(MqttSettings::from_config(&MqttConfig::default()).unwrap(), None);
+        let config = MqttConfig {
+            broker: Some("broker".to_string()),
+            topic_prefix: Some("hifiberry/turntable/".to_string()),
+            client_id: Some("turntable".to_string()),
+            ..Default::default()
+        };
+        let settings = MqttSettings::from_config(&config).unwrap().unwrap();
+        assert_eq!(settings.topic("command"), "hifiberry/turntable/command");
+        assert_eq!(settings.interval, Duration::from_secs(1));
+
+        assert_eq!(parse_command(b"start\n"), Some(ApiCommand::Arm));
+        assert_eq!(parse_command(b"SPLIT"), Some(ApiCommand::Split));
+        assert_eq!(parse_command(b"shutdown"), None);
+    }
+}
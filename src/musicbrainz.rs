@@ -1,14 +1,52 @@
 //! MusicBrainz-guided detection - uses expected track lengths to find boundaries.
 
-use serde::Deserialize;
-use std::error::Error;
+use serde::{Deserialize, Serialize};
+use crate::error::{Error, Result};
 use std::path::Path;
+use tracing::{info, warn};
 
 use crate::album_identifier::IdentifiedSong;
+use crate::discid::Toc;
+use crate::http;
 use crate::rate_limiter::RateLimiter;
 
+const USER_AGENT: &str = "HiFiBerryAutoRec/0.1 (https://github.com/hifiberry/autorec)";
+
+/// The shared MusicBrainz rate limiter (1 request per second, with margin)
+pub fn rate_limiter() -> RateLimiter {
+    RateLimiter::shared("MusicBrainz", std::time::Duration::from_millis(1100))
+}
+
+/// GET a MusicBrainz API URL through the shared rate limiter; a 503 (rate
+/// limit exceeded) makes every caller back off. Transient errors are
+/// retried (see [`crate::http`]). None on 404.
+fn api_get_optional(url: &str) -> Result<Option<ureq::Response>> {
+    let response = http::call("MusicBrainz", || {
+        let mut rl = rate_limiter();
+        rl.wait_if_needed();
+        let response = http::agent().get(url).set("User-Agent", USER_AGENT).call().map_err(Box::new);
+        rl.report_response(&response);
+        response
+    });
+    match response {
+        Ok(r) => Ok(Some(r)),
+        Err(e) if matches!(*e, ureq::Error::Status(404, _)) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn api_get(url: &str) -> Result<ureq::Response> {
+    api_get_optional(url)?.ok_or_else(|| Error::Lookup(format!("Not found: {}", url)))
+}
+
 #[derive(Debug, Deserialize)]
 struct MusicBrainzRelease {
+    #[serde(default)]
+    title: String,
+    #[serde(rename = "artist-credit", default)]
+    artist_credit: Vec<ArtistCredit>,
+    #[serde(default)]
+    date: Option<String>,
     media: Vec<Medium>,
 }
 
@@ -85,6 +123,23 @@ struct RecordingRelease {
     media: Vec<SearchMedium>,
 }
 
+// Disc ID lookup response (exact disc match or fuzzy TOC matches)
+#[derive(Debug, Deserialize)]
+struct DiscIdResponse {
+    #[serde(default)]
+    releases: Vec<DiscIdRelease>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DiscIdRelease {
+    id: String,
+    title: String,
+    #[serde(rename = "artist-credit", default)]
+    artist_credit: Vec<ArtistCredit>,
+    #[serde(default)]
+    media: Vec<SearchMedium>,
+}
+
 #[derive(Debug, Clone)]
 pub struct SearchResult {
     pub release_id: String,
@@ -95,7 +150,7 @@ pub struct SearchResult {
     pub track_count: u32,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExpectedTrack {
     pub position: u32,
     pub title: String,
@@ -112,16 +167,29 @@ pub struct MediumInfo {
     pub total_duration: f64,
 }
 
+/// A release with its artist, title and media
+#[derive(Debug, Clone)]
+pub struct ReleaseDetails {
+    pub release_id: String,
+    pub artist: String,
+    pub title: String,
+    pub year: Option<u32>,
+    pub sides: Vec<MediumInfo>,
+}
+
 /// Fetch all sides/media of a release with per-side track listings.
-pub fn fetch_release_sides(release_id: &str) -> Result<Vec<MediumInfo>, Box<dyn Error>> {
+pub fn fetch_release_sides(release_id: &str) -> Result<Vec<MediumInfo>> {
+    fetch_release_details(release_id).map(|r| r.sides)
+}
+
+/// Fetch a release with its artist, title, year and per-side track listings.
+pub fn fetch_release_details(release_id: &str) -> Result<ReleaseDetails> {
     let url = format!(
-        "https://musicbrainz.org/ws/2/release/{}?inc=recordings&fmt=json",
+        "https://musicbrainz.org/ws/2/release/{}?inc=recordings+artist-credits&fmt=json",
         release_id
     );
     
-    let response = ureq::get(&url)
-        .set("User-Agent", "HiFiBerryAutoRec/0.1 (https://github.com/hifiberry/autorec)")
-        .call()?;
+    let response = api_get(&url)?;
     
     let release: MusicBrainzRelease = serde_json::from_reader(response.into_reader())?;
     
@@ -154,11 +222,17 @@ pub fn fetch_release_sides(release_id: &str) -> Result<Vec<MediumInfo>, Box<dyn
         });
     }
     
-    Ok(sides)
+    Ok(ReleaseDetails {
+        release_id: release_id.to_string(),
+        artist: release.artist_credit.first().map(|ac| ac.name.clone()).unwrap_or_default(),
+        title: release.title,
+        year: release.date.as_deref().and_then(|d| d.get(..4)).and_then(|y| y.parse().ok()),
+        sides,
+    })
 }
 
 /// Fetch all tracks from a release as a flat list (legacy, uses first medium only).
-pub fn fetch_release_info(release_id: &str) -> Result<Vec<ExpectedTrack>, Box<dyn Error>> {
+pub fn fetch_release_info(release_id: &str) -> Result<Vec<ExpectedTrack>> {
     let sides = fetch_release_sides(release_id)?;
     if let Some(first) = sides.first() {
         Ok(first.tracks.clone())
@@ -421,7 +495,7 @@ pub fn parse_recording_filename(path: &str) -> Option<(Vec<String>, u32)> {
 
 /// Search MusicBrainz for a release by artist and release name.
 /// Returns up to `limit` results sorted by score.
-pub fn search_release(artist: &str, release: &str, limit: u32) -> Result<Vec<SearchResult>, Box<dyn Error>> {
+pub fn search_release(artist: &str, release: &str, limit: u32) -> Result<Vec<SearchResult>> {
     // URL-encode the query by replacing spaces with +
     let artist_q = artist.replace(' ', "+");
     let release_q = release.replace(' ', "+");
@@ -431,9 +505,7 @@ pub fn search_release(artist: &str, release: &str, limit: u32) -> Result<Vec<Sea
         artist_q, release_q, limit
     );
 
-    let response = ureq::get(&url)
-        .set("User-Agent", "HiFiBerryAutoRec/0.1 (https://github.com/hifiberry/autorec)")
-        .call()?;
+    let response = api_get(&url)?;
 
     let search: SearchResponse = serde_json::from_reader(response.into_reader())?;
 
@@ -462,25 +534,112 @@ pub fn search_release(artist: &str, release: &str, limit: u32) -> Result<Vec<Sea
     Ok(results)
 }
 
+/// Look up releases by the Disc ID / TOC of a detected track layout.
+///
+/// MusicBrainz first tries an exact Disc ID match and otherwise falls back to
+/// a fuzzy match on the track offsets in the TOC. Results are in the order
+/// returned by MusicBrainz; score is 100 for all of them.
+pub fn lookup_by_toc(toc: &Toc) -> Result<Vec<SearchResult>> {
+    let url = format!(
+        "https://musicbrainz.org/ws/2/discid/{}?toc={}&inc=artist-credits&cdstubs=no&fmt=json",
+        toc.disc_id(),
+        toc.toc_string()
+    );
+
+    // 404: neither the Disc ID nor the TOC is known
+    let Some(response) = api_get_optional(&url)? else {
+        return Ok(Vec::new());
+    };
+
+    let lookup: DiscIdResponse = serde_json::from_reader(response.into_reader())?;
+
+    let results = lookup.releases.into_iter()
+        .map(|r| {
+            let is_vinyl = r.media.iter().any(|m| {
+                m.format.as_deref().map_or(false, |f| f.contains("Vinyl"))
+            });
+            let track_count = r.media.iter().filter_map(|m| m.track_count).sum();
+            SearchResult {
+                release_id: r.id,
+                title: r.title,
+                artist: r.artist_credit.first().map(|ac| ac.name.clone()).unwrap_or_default(),
+                score: 100,
+                is_vinyl,
+                track_count,
+            }
+        })
+        .collect();
+
+    Ok(results)
+}
+
+/// Release and side found by [`find_release_by_track_layout`]
+#[derive(Debug, Clone)]
+pub struct TrackLayoutMatch {
+    pub release: SearchResult,
+    pub tracks: Vec<ExpectedTrack>,
+}
+
+/// Identify the release and side from a detected track layout.
+///
+/// `boundaries` are the detected song boundaries between `music_start` and
+/// `music_end` (seconds in the recording). Returns the best release (vinyl
+/// preferred) and the tracks of the side that fits the layout.
+pub fn find_release_by_track_layout(
+    music_start: f64,
+    boundaries: &[f64],
+    music_end: f64,
+    verbose: bool,
+) -> Result<Option<TrackLayoutMatch>> {
+    let toc = Toc::from_boundaries(music_start, boundaries, music_end).map_err(Error::Lookup)?;
+    if verbose {
+        info!("  Disc ID: {} (TOC {})", toc.disc_id(), toc.toc_string());
+    }
+
+    let mut results = lookup_by_toc(&toc)?;
+    if results.is_empty() {
+        return Ok(None);
+    }
+    // Stable sort keeps the MusicBrainz order within vinyl / non-vinyl
+    results.sort_by_key(|r| !r.is_vinyl);
+
+    let music_duration = music_end - music_start;
+
+    for result in results.iter().take(5) {
+        let sides = match fetch_release_sides(&result.release_id) {
+            Ok(s) => s,
+            Err(_) => continue,
+        };
+        if let Some(tracks) = find_best_side(&sides, music_duration, &[]) {
+            if tracks.len() == toc.offsets.len() {
+                if verbose {
+                    info!("  Matched {} - {} ({})", result.artist, result.title, result.release_id);
+                }
+                return Ok(Some(TrackLayoutMatch { release: result.clone(), tracks }));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
 /// Search MusicBrainz by trying all possible artist/release splits of the filename words.
 /// E.g. for ["dj", "shadow", "endtroducing"], tries:
 ///   - artist="dj", release="shadow endtroducing"
 ///   - artist="dj shadow", release="endtroducing"
 /// Returns all matching results (score >= 80) from all splits.
-pub fn search_release_by_filename(words: &[String], verbose: bool) -> Result<Vec<SearchResult>, Box<dyn Error>> {
+pub fn search_release_by_filename(words: &[String], verbose: bool) -> Result<Vec<SearchResult>> {
     if words.len() < 2 {
         return Ok(Vec::new());
     }
 
     let mut all_results = Vec::new();
-    let mut rl = RateLimiter::from_millis("MusicBrainz", 1100);
-
     for split in 1..words.len() {
         let artist = words[..split].join(" ");
         let release = words[split..].join(" ");
 
         if verbose {
-            eprintln!("  Searching: artist=\"{}\" release=\"{}\"", artist, release);
+            info!("  Searching: artist=\"{}\" release=\"{}\"", artist, release);
         }
 
         match search_release(&artist, &release, 5) {
@@ -494,15 +653,10 @@ pub fn search_release_by_filename(words: &[String], verbose: bool) -> Result<Vec
             }
             Err(e) => {
                 if verbose {
-                    eprintln!("  Search failed: {}", e);
+                    warn!("Search failed: {}", e);
                 }
             }
         }
-
-        // MusicBrainz rate limit
-        if split < words.len() - 1 {
-            rl.wait_if_needed();
-        }
     }
 
     // Deduplicate by release_id
@@ -518,17 +672,15 @@ pub fn rank_by_duration_match(
     results: &[SearchResult],
     music_duration_seconds: f64,
     verbose: bool,
-) -> Result<Vec<(SearchResult, f64)>, Box<dyn Error>> {
+) -> Result<Vec<(SearchResult, f64)>> {
     let mut ranked = Vec::new();
-    let mut rl = RateLimiter::from_millis("MusicBrainz", 1100);
-
     for result in results {
         // Fetch full track info with per-side data
         let sides = match fetch_release_sides(&result.release_id) {
             Ok(s) => s,
             Err(e) => {
                 if verbose {
-                    eprintln!("  Failed to fetch tracks for {}: {}", result.release_id, e);
+                    warn!("Failed to fetch tracks for {}: {}", result.release_id, e);
                 }
                 continue;
             }
@@ -541,14 +693,11 @@ pub fn rank_by_duration_match(
         let best_error = best_duration_error(&sides, music_duration_seconds);
 
         if verbose {
-            eprintln!("  {} - {}: {} media, best error {:.1}s",
+            info!("  {} - {}: {} media, best error {:.1}s",
                      result.artist, result.title, sides.len(), best_error);
         }
 
         ranked.push((result.clone(), best_error));
-
-        // MusicBrainz rate limit
-        rl.wait_if_needed();
     }
 
     // Sort by match error (lower is better)
@@ -563,22 +712,21 @@ pub fn auto_lookup_release(
     filepath: &str,
     music_duration_seconds: f64,
     verbose: bool,
-) -> Result<Option<SearchResult>, Box<dyn Error>> {
+) -> Result<Option<SearchResult>> {
     // Parse filename
     let (words, side) = match parse_recording_filename(filepath) {
         Some((w, s)) => (w, s),
         None => {
             if verbose {
-                eprintln!("Could not parse filename: {}", filepath);
+                warn!("Could not parse filename: {}", filepath);
             }
             return Ok(None);
         }
     };
 
     if verbose {
-        println!("Parsed filename: words={:?}, side={}", words, side);
-        println!("Music duration (without grooves): {:.1}s", music_duration_seconds);
-        println!();
+        info!("Parsed filename: words={:?}, side={}", words, side);
+        info!("Music duration (without grooves): {:.1}s", music_duration_seconds);
     }
 
     // Search for all matching releases
@@ -586,14 +734,13 @@ pub fn auto_lookup_release(
     
     if search_results.is_empty() {
         if verbose {
-            println!("No MusicBrainz matches found");
+            info!("No MusicBrainz matches found");
         }
         return Ok(None);
     }
 
     if verbose {
-        println!();
-        println!("Found {} potential matches, ranking by duration...", search_results.len());
+        info!("Found {} potential matches, ranking by duration...", search_results.len());
     }
 
     // Rank all results by duration match
@@ -606,18 +753,17 @@ pub fn auto_lookup_release(
     let (best, error) = &ranked[0];
     
     if verbose {
-        println!();
-        println!("Best match: {} - {} (score: {}, vinyl: {})",
+        info!("Best match: {} - {} (score: {}, vinyl: {})",
                  best.artist, best.title, best.score, best.is_vinyl);
-        println!("Release ID: {}", best.release_id);
-        println!("Duration match error: {:.1}s", error);
+        info!("Release ID: {}", best.release_id);
+        info!("Duration match error: {:.1}s", error);
     }
 
     // Accept if error is within 5% or 30 seconds (whichever is larger)
     let threshold = (music_duration_seconds * 0.05).max(30.0);
     if *error > threshold {
         if verbose {
-            println!("Duration mismatch too large (threshold: {:.1}s)", threshold);
+            info!("Duration mismatch too large (threshold: {:.1}s)", threshold);
         }
         return Ok(None);
     }
@@ -627,7 +773,7 @@ pub fn auto_lookup_release(
 
 /// Search MusicBrainz for recordings matching a song title and artist.
 /// Returns releases that contain the matching recordings.
-fn search_recording(artist: &str, title: &str, limit: u32) -> Result<Vec<SearchResult>, Box<dyn Error>> {
+fn search_recording(artist: &str, title: &str, limit: u32) -> Result<Vec<SearchResult>> {
     // URL-encode the query
     let artist_q = artist.replace(' ', "+");
     let title_q = title.replace(' ', "+");
@@ -637,9 +783,7 @@ fn search_recording(artist: &str, title: &str, limit: u32) -> Result<Vec<SearchR
         title_q, artist_q, limit
     );
 
-    let response = ureq::get(&url)
-        .set("User-Agent", "HiFiBerryAutoRec/0.1 (https://github.com/hifiberry/autorec)")
-        .call()?;
+    let response = api_get(&url)?;
 
     let search: RecordingSearchResponse = serde_json::from_reader(response.into_reader())?;
 
@@ -697,7 +841,7 @@ pub fn find_album_by_songs(
     music_duration_seconds: f64,
     vinyl_only: bool,
     verbose: bool,
-) -> Result<Option<(SearchResult, usize)>, Box<dyn Error>> {
+) -> Result<Option<(SearchResult, usize)>> {
     if songs.is_empty() {
         return Ok(None);
     }
@@ -712,23 +856,21 @@ pub fn find_album_by_songs(
         }
     }
 
-    println!("Searching for {} unique song(s) on MusicBrainz...", unique_songs.len());
+    info!("Searching for {} unique song(s) on MusicBrainz...", unique_songs.len());
 
     // For each unique song, search MusicBrainz recordings and collect release IDs
     // release_id -> (SearchResult, match_count)
     let mut release_counts: std::collections::HashMap<String, (SearchResult, usize)> =
         std::collections::HashMap::new();
-    let mut rl = RateLimiter::from_millis("MusicBrainz", 1100);
-
     for (i, (artist, title)) in unique_songs.iter().enumerate() {
         if verbose {
-            println!("  [{}/{}] Searching: {} - {}", i + 1, unique_songs.len(), artist, title);
+            info!("  [{}/{}] Searching: {} - {}", i + 1, unique_songs.len(), artist, title);
         }
 
         match search_recording(artist, title, 10) {
             Ok(releases) => {
                 if verbose {
-                    println!("    Found {} releases", releases.len());
+                    info!("    Found {} releases", releases.len());
                 }
                 for r in releases {
                     release_counts.entry(r.release_id.clone())
@@ -738,17 +880,14 @@ pub fn find_album_by_songs(
             }
             Err(e) => {
                 if verbose {
-                    println!("    Search failed: {}", e);
+                    warn!("Search failed: {}", e);
                 }
             }
         }
-
-        // MusicBrainz rate limit
-        rl.wait_if_needed();
     }
 
     if release_counts.is_empty() {
-        println!("No releases found containing the identified songs");
+        info!("No releases found containing the identified songs");
         return Ok(None);
     }
 
@@ -763,16 +902,16 @@ pub fn find_album_by_songs(
             .cloned()
             .collect();
         if !vinyl_candidates.is_empty() {
-            println!("Filtered to {} vinyl releases (from {} total)", vinyl_candidates.len(), candidates.len());
+            info!("Filtered to {} vinyl releases (from {} total)", vinyl_candidates.len(), candidates.len());
             candidates = vinyl_candidates;
             candidates.sort_by(|a, b| b.1.cmp(&a.1));
         } else {
-            println!("No vinyl releases found, using all {} releases", candidates.len());
+            info!("No vinyl releases found, using all {} releases", candidates.len());
         }
     }
 
     let max_song_count = candidates[0].1;
-    println!("Found {} releases, best candidates match {} song(s)", candidates.len(), max_song_count);
+    info!("Found {} releases, best candidates match {} song(s)", candidates.len(), max_song_count);
 
     // Take top candidates: those with at least (max - 1) matching songs, up to 15
     let top_candidates: Vec<(SearchResult, usize)> = candidates.into_iter()
@@ -782,11 +921,11 @@ pub fn find_album_by_songs(
 
     if verbose {
         for (r, count) in &top_candidates {
-            println!("  {} - {} ({} songs, vinyl: {})", r.artist, r.title, count, r.is_vinyl);
+            info!("  {} - {} ({} songs, vinyl: {})", r.artist, r.title, count, r.is_vinyl);
         }
     }
 
-    println!("Ranking {} candidates by duration match...", top_candidates.len());
+    info!("Ranking {} candidates by duration match...", top_candidates.len());
 
     // Rank by duration match
     let search_results: Vec<SearchResult> = top_candidates.iter().map(|(r, _)| r.clone()).collect();
@@ -806,7 +945,7 @@ pub fn find_album_by_songs(
     // Accept if error is within 5% or 30 seconds (whichever is larger)
     let threshold = (music_duration_seconds * 0.05).max(30.0);
     if *error > threshold {
-        println!("Best match duration error too large: {:.1}s (threshold: {:.1}s)", error, threshold);
+        info!("Best match duration error too large: {:.1}s (threshold: {:.1}s)", error, threshold);
         return Ok(None);
     }
 
@@ -0,0 +1,416 @@
+//! Network capture backend (`net:` addresses).
+//!
+//! Receives interleaved PCM sent by another machine, e.g. a Raspberry Pi
+//! next to the turntable streaming to a machine that analyzes and stores
+//! the recordings:
+//!
+//! - `net:udp://0.0.0.0:5004` - raw PCM datagrams, little-endian
+//! - `net:rtp://0.0.0.0:5004` - RTP with linear PCM payload (L16/L24,
+//!   big-endian as in RFC 3551); lost packets are replaced by silence
+//! - `net:tcp://0.0.0.0:5004` - raw little-endian PCM stream from one
+//!   sender at a time, e.g. `arecord -t raw ... | nc host 5004`
+//!
+//! Sample rate, channels and format are not transmitted and have to match
+//! the sender. Without a host the backend listens on all interfaces, without
+//! a port on [`DEFAULT_PORT`].
+
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Read};
+use std::net::{SocketAddr, TcpListener, ToSocketAddrs, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::audio_stream::{AudioInputStream, AudioStream};
+use crate::error::{Error, Result};
+use crate::vu_meter::SampleFormat;
+
+/// Port used when the address has none
+pub const DEFAULT_PORT: u16 = 5004;
+
+/// How long `read_chunk` waits for audio before the source counts as lost
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Socket timeout, i.e. how quickly the receiver thread notices `stop`
+const SOCKET_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Audio kept when nobody reads; older audio is dropped
+const MAX_BUFFER_SECONDS: usize = 10;
+
+/// Larger sequence jumps (either way) are a restarted sender, not lost or
+/// late packets
+const MAX_LOST_PACKETS: u16 = 100;
+
+/// Transport of a `net:` address
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NetProtocol {
+    Udp,
+    Rtp,
+    Tcp,
+}
+
+/// Parse the part of a `net:` address after the backend, e.g.
+/// "rtp://0.0.0.0:5004", "udp://:5004" or "tcp://192.168.1.10"
+pub fn parse_net_address(device: &str) -> Result<(NetProtocol, SocketAddr)> {
+    let (scheme, host) = device
+        .split_once("://")
+        .ok_or_else(|| Error::Config(format!("Network address needs udp://, rtp:// or tcp://: {}", device)))?;
+    let protocol = match scheme.to_lowercase().as_str() {
+        "udp" => NetProtocol::Udp,
+        "rtp" => NetProtocol::Rtp,
+        "tcp" => NetProtocol::Tcp,
+        _ => return Err(Error::Config(format!("Unknown network protocol: {}", scheme))),
+    };
+    let host = host.trim_end_matches('/');
+    let host = if host.is_empty() || host.starts_with(':') { format!("0.0.0.0{}", host) } else { host.to_string() };
+    // A bare host (or IPv6 address in brackets) gets the default port
+    let with_port = if host.rsplit_once(':').is_some_and(|(_, port)| port.parse::<u16>().is_ok()) {
+        host
+    } else {
+        format!("{}:{}", host, DEFAULT_PORT)
+    };
+    let addr = with_port
+        .to_socket_addrs()
+        .map_err(|e| Error::Config(format!("Invalid network address {}: {}", device, e)))?
+        .next()
+        .ok_or_else(|| Error::Config(format!("Invalid network address: {}", device)))?;
+    Ok((protocol, addr))
+}
+
+/// Sequence number and payload of an RTP packet
+fn rtp_payload(packet: &[u8]) -> Option<(u16, &[u8])> {
+    if packet.len() < 12 || packet[0] >> 6 != 2 {
+        return None;
+    }
+    let sequence = u16::from_be_bytes([packet[2], packet[3]]);
+    let mut start = 12 + 4 * (packet[0] & 0x0f) as usize;
+    if packet[0] & 0x10 != 0 {
+        // Header extension: 16-bit profile, 16-bit length in 32-bit words
+        let length = packet.get(start + 2..start + 4)?;
+        start += 4 + 4 * u16::from_be_bytes([length[0], length[1]]) as usize;
+    }
+    let mut end = packet.len();
+    if packet[0] & 0x20 != 0 {
+        end = end.checked_sub(*packet.last()? as usize)?;
+    }
+    Some((sequence, packet.get(start..end)?))
+}
+
+/// Turns received packets into little-endian PCM bytes
+struct Depacketizer {
+    protocol: NetProtocol,
+    bytes_per_sample: usize,
+    next_sequence: Option<u16>,
+}
+
+impl Depacketizer {
+    fn new(protocol: NetProtocol, format: SampleFormat) -> Self {
+        Depacketizer { protocol, bytes_per_sample: format.bytes_per_sample(), next_sequence: None }
+    }
+
+    /// Append the audio of `packet` to `out`
+    fn push(&mut self, packet: &[u8], out: &mut Vec<u8>) {
+        if self.protocol != NetProtocol::Rtp {
+            out.extend_from_slice(packet);
+            return;
+        }
+        let Some((sequence, payload)) = rtp_payload(packet) else { return };
+        if let Some(expected) = self.next_sequence {
+            let lost = sequence.wrapping_sub(expected);
+            if lost > u16::MAX - MAX_LOST_PACKETS {
+                // Late or duplicate packet
+                return;
+            }
+            if lost <= MAX_LOST_PACKETS {
+                out.resize(out.len() + lost as usize * payload.len(), 0);
+            }
+        }
+        self.next_sequence = Some(sequence.wrapping_add(1));
+        for sample in payload.chunks_exact(self.bytes_per_sample) {
+            out.extend(sample.iter().rev());
+        }
+    }
+}
+
+/// Bytes received but not read yet
+struct SharedBuffer {
+    data: Mutex<VecDeque<u8>>,
+    capacity: usize,
+}
+
+impl SharedBuffer {
+    fn append(&self, bytes: &[u8], frame_bytes: usize) {
+        let mut data = self.data.lock().unwrap();
+        data.extend(bytes);
+        if data.len() > self.capacity {
+            let excess = data.len() - self.capacity;
+            data.drain(..excess.div_ceil(frame_bytes) * frame_bytes);
+        }
+    }
+}
+
+/// Bound socket, moved into the receiver thread
+enum Receiver {
+    Datagram(UdpSocket),
+    Stream(TcpListener),
+}
+
+impl Receiver {
+    fn bind(protocol: NetProtocol, addr: SocketAddr) -> std::io::Result<Self> {
+        Ok(match protocol {
+            NetProtocol::Udp | NetProtocol::Rtp => {
+                let socket = UdpSocket::bind(addr)?;
+                socket.set_read_timeout(Some(SOCKET_TIMEOUT))?;
+                Receiver::Datagram(socket)
+            }
+            NetProtocol::Tcp => {
+                let listener = TcpListener::bind(addr)?;
+                listener.set_nonblocking(true)?;
+                Receiver::Stream(listener)
+            }
+        })
+    }
+
+    fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        match self {
+            Receiver::Datagram(socket) => socket.local_addr(),
+            Receiver::Stream(listener) => listener.local_addr(),
+        }
+    }
+
+    /// Receive until `running` is cleared
+    fn run(self, mut depacketizer: Depacketizer, frame_bytes: usize, buffer: Arc<SharedBuffer>, running: Arc<AtomicBool>) {
+        let mut packet = vec![0u8; 65536];
+        let mut audio = Vec::new();
+        match self {
+            Receiver::Datagram(socket) => {
+                while running.load(Ordering::Relaxed) {
+                    if let Ok(n) = socket.recv(&mut packet) {
+                        audio.clear();
+                        depacketizer.push(&packet[..n], &mut audio);
+                        buffer.append(&audio, frame_bytes);
+                    }
+                }
+            }
+            Receiver::Stream(listener) => {
+                while running.load(Ordering::Relaxed) {
+                    let mut stream = match listener.accept() {
+                        Ok((stream, _)) => stream,
+                        Err(_) => {
+                            thread::sleep(SOCKET_TIMEOUT);
+                            continue;
+                        }
+                    };
+                    if stream.set_nonblocking(false).and_then(|_| stream.set_read_timeout(Some(SOCKET_TIMEOUT))).is_err() {
+                        continue;
+                    }
+                    // One sender at a time, until it disconnects
+                    while running.load(Ordering::Relaxed) {
+                        match stream.read(&mut packet) {
+                            Ok(0) => break,
+                            Ok(n) => buffer.append(&packet[..n], frame_bytes),
+                            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                            Err(_) => break,
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Audio input stream receiving PCM over the network
+pub struct NetInputStream {
+    protocol: NetProtocol,
+    addr: SocketAddr,
+    rate: u32,
+    channels: usize,
+    format: SampleFormat,
+    buffer: Arc<SharedBuffer>,
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+    local_addr: Option<SocketAddr>,
+}
+
+impl NetInputStream {
+    /// Create a network stream for a `net:` address (see [`parse_net_address`])
+    pub fn new(device: &str, rate: u32, channels: usize, format: SampleFormat) -> Result<Self> {
+        let (protocol, addr) = parse_net_address(device)?;
+        let capacity = MAX_BUFFER_SECONDS * rate as usize * channels * format.bytes_per_sample();
+        Ok(NetInputStream {
+            protocol,
+            addr,
+            rate,
+            channels,
+            format,
+            buffer: Arc::new(SharedBuffer { data: Mutex::new(VecDeque::new()), capacity }),
+            running: Arc::new(AtomicBool::new(false)),
+            thread: None,
+            local_addr: None,
+        })
+    }
+
+    pub fn protocol(&self) -> NetProtocol {
+        self.protocol
+    }
+
+    /// Address the stream listens on while it is active
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+}
+
+impl AudioStream for NetInputStream {
+    fn sample_rate(&self) -> u32 {
+        self.rate
+    }
+
+    fn channels(&self) -> usize {
+        self.channels
+    }
+
+    fn sample_format(&self) -> SampleFormat {
+        self.format
+    }
+}
+
+impl AudioInputStream for NetInputStream {
+    fn read_chunk(&mut self, frames: usize) -> Option<Vec<Vec<i32>>> {
+        if !self.is_active() {
+            return None;
+        }
+        let chunk_size = frames * self.bytes_per_frame();
+
+        let deadline = Instant::now() + READ_TIMEOUT;
+        let bytes: Vec<u8> = loop {
+            let mut data = self.buffer.data.lock().unwrap();
+            if data.len() >= chunk_size {
+                break data.drain(..chunk_size).collect();
+            }
+            drop(data);
+            if Instant::now() >= deadline {
+                return None;
+            }
+            thread::sleep(Duration::from_millis(10));
+        };
+
+        let mut audio = vec![Vec::with_capacity(frames); self.channels];
+        for (i, sample) in self.format.decode_samples(&bytes).into_iter().enumerate() {
+            audio[i % self.channels].push(sample);
+        }
+        Some(audio)
+    }
+
+    fn start(&mut self) -> Result<()> {
+        if self.thread.is_some() {
+            return Ok(());
+        }
+        let receiver = Receiver::bind(self.protocol, self.addr)
+            .map_err(|e| Error::Backend(format!("Failed to listen on {}: {}", self.addr, e)))?;
+        self.local_addr = receiver.local_addr().ok();
+
+        self.buffer.data.lock().unwrap().clear();
+        self.running.store(true, Ordering::Relaxed);
+        let depacketizer = Depacketizer::new(self.protocol, self.format);
+        let frame_bytes = self.bytes_per_frame();
+        let buffer = self.buffer.clone();
+        let running = self.running.clone();
+        self.thread = Some(thread::spawn(move || receiver.run(depacketizer, frame_bytes, buffer, running)));
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        self.local_addr = None;
+        self.buffer.data.lock().unwrap().clear();
+    }
+
+    fn is_active(&self) -> bool {
+        self.thread.is_some()
+    }
+}
+
+impl Drop for NetInputStream {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::net::TcpStream;
+
+    #[test]
+    fn test_parse_net_address() {
+        let (protocol, addr) = parse_net_address("rtp://0.0.0.0:6000").unwrap();
+        assert_eq!(protocol, NetProtocol::Rtp);
+        assert_eq!(addr, "0.0.0.0:6000".parse().unwrap());
+        assert_eq!(parse_net_address("udp://:5005").unwrap().1, "0.0.0.0:5005".parse().unwrap());
+        assert_eq!(parse_net_address("tcp://127.0.0.1").unwrap(), (NetProtocol::Tcp, "127.0.0.1:5004".parse().unwrap()));
+        assert_eq!(parse_net_address("UDP://").unwrap().1.port(), DEFAULT_PORT);
+        assert!(parse_net_address("0.0.0.0:5004").is_err());
+        assert!(parse_net_address("sctp://:5004").is_err());
+    }
+
+    #[test]
+    fn test_rtp_depacketizer() {
+        let packet = |sequence: u16, payload: &[u8]| {
+            let mut p = vec![0x80, 96, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+            p[2..4].copy_from_slice(&sequence.to_be_bytes());
+            p.extend_from_slice(payload);
+            p
+        };
+        let mut depacketizer = Depacketizer::new(NetProtocol::Rtp, SampleFormat::S16);
+        let mut out = Vec::new();
+        // L16 is big-endian
+        depacketizer.push(&packet(65535, &[0x01, 0x02, 0xff, 0xfe]), &mut out);
+        assert_eq!(out, [0x02, 0x01, 0xfe, 0xff]);
+        // One packet lost across the wrap: replaced by silence
+        depacketizer.push(&packet(1, &[0x00, 0x03, 0x00, 0x04]), &mut out);
+        assert_eq!(out[4..], [0, 0, 0, 0, 0x03, 0x00, 0x04, 0x00]);
+        // Late packet and garbage are ignored
+        depacketizer.push(&packet(0, &[0x00, 0x05, 0x00, 0x06]), &mut out);
+        depacketizer.push(&[0x00; 8], &mut out);
+        assert_eq!(out.len(), 12);
+        // A restarted sender continues without a gap
+        depacketizer.push(&packet(30000, &[0x00, 0x07]), &mut out);
+        assert_eq!(out[12..], [0x07, 0x00]);
+
+        // CSRC, extension and padding
+        let mut p = vec![0xb1, 96, 0, 2, 0, 0, 0, 0, 0, 0, 0, 1, 9, 9, 9, 9, 0xbe, 0xde, 0, 1, 7, 7, 7, 7];
+        p.extend_from_slice(&[0x12, 0x34, 0, 0, 3]);
+        assert_eq!(rtp_payload(&p), Some((2, &[0x12, 0x34][..])));
+        p[28] = 200;
+        assert_eq!(rtp_payload(&p), None);
+        assert_eq!(rtp_payload(&p[..14]), None);
+    }
+
+    #[test]
+    fn test_net_input_stream() {
+        // UDP datagrams
+        let mut stream = NetInputStream::new("udp://127.0.0.1:0", 48000, 2, SampleFormat::S16).unwrap();
+        stream.start().unwrap();
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+        sender.send_to(&[1, 0, 2, 0, 3, 0, 4, 0], stream.local_addr().unwrap()).unwrap();
+        assert_eq!(stream.read_chunk(2), Some(vec![vec![1, 3], vec![2, 4]]));
+        stream.stop();
+        assert!(!stream.is_active());
+        assert_eq!(stream.read_chunk(1), None);
+
+        // TCP stream, split within a sample
+        let mut stream = NetInputStream::new("tcp://127.0.0.1:0", 48000, 1, SampleFormat::S16).unwrap();
+        stream.start().unwrap();
+        let mut sender = TcpStream::connect(stream.local_addr().unwrap()).unwrap();
+        sender.write_all(&[0xff, 0xff, 5]).unwrap();
+        sender.flush().unwrap();
+        thread::sleep(Duration::from_millis(50));
+        sender.write_all(&[0]).unwrap();
+        assert_eq!(stream.read_chunk(2), Some(vec![vec![-1, 5]]));
+    }
+}
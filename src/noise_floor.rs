@@ -0,0 +1,158 @@
+//! Suggest the on/off detection settings from the noise of the source.
+//!
+//! `autorecord --calibrate` listens to the source while nothing is playing
+//! (or a needle sits in a silent groove) and feeds the level of every chunk
+//! to [`NoiseFloorCalibration`]. The off threshold is put a margin above the
+//! loudest noise, ignoring the odd click, and the silence duration is kept
+//! short enough to fit between the clicks that still get above it.
+
+use crate::vu_meter::ChannelMetrics;
+
+/// How long `--calibrate` listens, in seconds
+pub const CALIBRATION_SECONDS: f64 = 30.0;
+
+/// Distance of the off threshold above the noise, in dB
+const MARGIN_DB: f64 = 6.0;
+/// Share of the chunks that may be louder than the noise peak (clicks, pops)
+const PEAK_PERCENTILE: f64 = 0.99;
+/// Highest threshold suggested; louder "noise" is most likely music
+pub const MAX_OFF_THRESHOLD: f64 = -30.0;
+/// Noise floors above this suggest that something was playing
+const LOUD_NOISE_DB: f64 = -40.0;
+const MIN_SILENCE_DURATION: f64 = 5.0;
+const MAX_SILENCE_DURATION: f64 = 10.0;
+
+/// Result of a calibration
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoiseFloorResult {
+    /// Median level of the loudest channel in dBFS
+    pub noise_floor_db: f64,
+    /// Level exceeded by 1% of the chunks in dBFS
+    pub noise_peak_db: f64,
+    pub off_threshold: f64,
+    pub silence_duration: f64,
+    /// Chunks above the suggested threshold
+    pub clicks: usize,
+    pub warning: Option<String>,
+}
+
+/// Collects the levels of the chunks read during calibration
+pub struct NoiseFloorCalibration {
+    chunk_seconds: f64,
+    levels: Vec<f64>,
+}
+
+impl NoiseFloorCalibration {
+    /// `chunk_seconds` is the update interval of the VU meter
+    pub fn new(chunk_seconds: f64) -> Self {
+        NoiseFloorCalibration { chunk_seconds, levels: Vec::new() }
+    }
+
+    /// Add the levels of one chunk; the loudest channel counts
+    pub fn add(&mut self, metrics: &[ChannelMetrics]) {
+        let level = metrics.iter().map(|m| m.db).fold(f64::NEG_INFINITY, f64::max);
+        if level.is_finite() {
+            self.levels.push(level);
+        }
+    }
+
+    /// Seconds of audio added so far
+    pub fn seconds(&self) -> f64 {
+        self.levels.len() as f64 * self.chunk_seconds
+    }
+
+    /// The suggested settings; None without any audio
+    pub fn result(&self) -> Option<NoiseFloorResult> {
+        if self.levels.is_empty() {
+            return None;
+        }
+        let mut sorted = self.levels.clone();
+        sorted.sort_by(f64::total_cmp);
+        let percentile = |p: f64| sorted[((sorted.len() - 1) as f64 * p).round() as usize];
+        let noise_floor_db = percentile(0.5);
+        let noise_peak_db = percentile(PEAK_PERCENTILE);
+
+        let off_threshold = (noise_peak_db + MARGIN_DB).ceil().min(MAX_OFF_THRESHOLD);
+
+        // The meter reports "off" once no chunk of the silence window is above
+        // the threshold, so the window has to fit between the clicks
+        let mut clicks = 0;
+        let mut quiet_run = 0;
+        let mut longest_quiet_run = 0;
+        for &level in &self.levels {
+            if level > off_threshold {
+                clicks += 1;
+                quiet_run = 0;
+            } else {
+                quiet_run += 1;
+                longest_quiet_run = longest_quiet_run.max(quiet_run);
+            }
+        }
+        let silence_duration = (longest_quiet_run as f64 * self.chunk_seconds * 0.8)
+            .clamp(MIN_SILENCE_DURATION, MAX_SILENCE_DURATION)
+            .round();
+
+        let warning = if noise_floor_db > LOUD_NOISE_DB {
+            Some(format!(
+                "The noise floor of {:.1} dBFS is very high. Was something playing? Calibrate with the source silent.",
+                noise_floor_db
+            ))
+        } else if longest_quiet_run as f64 * self.chunk_seconds < MIN_SILENCE_DURATION {
+            Some(format!("{} chunks were louder than the threshold; recordings may not stop on silence.", clicks))
+        } else {
+            None
+        };
+
+        Some(NoiseFloorResult { noise_floor_db, noise_peak_db, off_threshold, silence_duration, clicks, warning })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metrics(db: f64) -> Vec<ChannelMetrics> {
+        let channel = ChannelMetrics {
+            db,
+            peak_db: db + 10.0,
+            max_db: db,
+            max_peak_db: db + 10.0,
+            is_on: false,
+            has_clipped: false,
+            true_peak_db: db + 10.0,
+            max_true_peak_db: db + 10.0,
+            overs: 0,
+            total_overs: 0,
+        };
+        vec![channel.clone(), ChannelMetrics { db: db - 3.0, ..channel }]
+    }
+
+    #[test]
+    fn test_calibration() {
+        let mut calibration = NoiseFloorCalibration::new(0.2);
+        assert_eq!(calibration.result(), None);
+
+        // Groove noise around -62 dBFS with one click
+        for i in 0..150 {
+            let level = if i == 75 { -35.0 } else { -62.0 + (i % 5) as f64 * 0.5 };
+            calibration.add(&metrics(level));
+        }
+        assert!((calibration.seconds() - 30.0).abs() < 1e-9);
+        let result = calibration.result().unwrap();
+        assert_eq!(result.noise_floor_db, -61.0);
+        assert_eq!(result.noise_peak_db, -60.0);
+        assert_eq!(result.off_threshold, -54.0);
+        assert_eq!(result.clicks, 1);
+        assert_eq!(result.silence_duration, 10.0);
+        assert_eq!(result.warning, None);
+
+        // Music instead of silence
+        let mut calibration = NoiseFloorCalibration::new(0.2);
+        for _ in 0..50 {
+            calibration.add(&metrics(-18.0));
+        }
+        let result = calibration.result().unwrap();
+        assert_eq!(result.off_threshold, MAX_OFF_THRESHOLD);
+        assert!(result.warning.unwrap().contains("Was something playing"));
+    }
+}
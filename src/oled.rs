@@ -0,0 +1,411 @@
+//! SSD1306 front panel display (cargo feature `oled`).
+//!
+//! Shows the recorder state, a level bar per channel and the identified
+//! song on the 128x64 or 128x32 monochrome OLEDs found on many Raspberry Pi
+//! cases, connected by I2C or by SPI with a data/command GPIO line. The
+//! display is a [`StatusDisplay`]; the picture is redrawn at most
+//! [`FRAME_RATE`] times a second and only sent when it changed.
+
+use std::convert::Infallible;
+use std::io::{self, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use embedded_graphics::mono_font::iso_8859_1::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{Line, PrimitiveStyle, Rectangle};
+use embedded_graphics::text::{Baseline, Text};
+use gpio_cdev::{Chip, LineHandle, LineRequestFlags};
+use i2cdev::core::I2CDevice;
+use i2cdev::linux::LinuxI2CDevice;
+use spidev::{SpiModeFlags, Spidev, SpidevOptions};
+use tracing::info;
+
+use crate::config::OledConfig;
+use crate::error::{Error, Result};
+use crate::status_display::{StatusDisplay, StatusEvent, StatusFrame};
+
+pub const WIDTH: u32 = 128;
+pub const DEFAULT_I2C_DEVICE: &str = "/dev/i2c-1";
+pub const DEFAULT_SPI_DEVICE: &str = "/dev/spidev0.0";
+pub const DEFAULT_ADDRESS: u16 = 0x3c;
+pub const DEFAULT_GPIO_CHIP: &str = "/dev/gpiochip0";
+
+/// Maximum redraws per second
+pub const FRAME_RATE: u32 = 10;
+
+/// Range of the level bars in dB below full scale
+const BAR_RANGE_DB: f64 = 60.0;
+
+/// How long an event stays on the bottom line
+const MESSAGE_TIME: Duration = Duration::from_secs(5);
+
+/// Bytes sent per I2C transfer
+const I2C_CHUNK: usize = 128;
+
+/// How the display is connected
+#[derive(Debug, Clone, PartialEq)]
+pub enum OledInterface {
+    I2c { device: String, address: u16 },
+    Spi { device: String, gpio_chip: String, dc_pin: u32 },
+}
+
+/// Display settings from the `[oled]` section
+#[derive(Debug, Clone, PartialEq)]
+pub struct OledSettings {
+    pub interface: OledInterface,
+    /// 64 or 32
+    pub height: u32,
+    pub rotate: bool,
+    /// Reset line on the GPIO chip of `gpio_chip`
+    pub reset_pin: Option<u32>,
+    pub gpio_chip: String,
+}
+
+impl OledSettings {
+    pub fn from_config(config: &OledConfig) -> Result<Self> {
+        let gpio_chip = config.gpio_chip.clone().unwrap_or_else(|| DEFAULT_GPIO_CHIP.to_string());
+        let interface = match config.interface.as_deref().unwrap_or("i2c").to_lowercase().as_str() {
+            "i2c" => OledInterface::I2c {
+                device: config.device.clone().unwrap_or_else(|| DEFAULT_I2C_DEVICE.to_string()),
+                address: config.address.unwrap_or(DEFAULT_ADDRESS),
+            },
+            "spi" => OledInterface::Spi {
+                device: config.device.clone().unwrap_or_else(|| DEFAULT_SPI_DEVICE.to_string()),
+                gpio_chip: gpio_chip.clone(),
+                dc_pin: config
+                    .dc_pin
+                    .ok_or_else(|| Error::Config("An SPI display needs dc_pin in [oled]".to_string()))?,
+            },
+            other => return Err(Error::Config(format!("Invalid OLED interface '{}' (use i2c or spi)", other))),
+        };
+        let height = config.height.unwrap_or(64);
+        if height != 64 && height != 32 {
+            return Err(Error::Config(format!("Invalid OLED height {} (use 64 or 32)", height)));
+        }
+        Ok(OledSettings { interface, height, rotate: config.rotate.unwrap_or(false), reset_pin: config.reset_pin, gpio_chip })
+    }
+}
+
+/// Picture in the memory layout of the SSD1306: one byte per 8 rows of a
+/// column, pages of 8 rows from top to bottom
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameBuffer {
+    height: u32,
+    data: Vec<u8>,
+}
+
+impl FrameBuffer {
+    pub fn new(height: u32) -> Self {
+        FrameBuffer { height, data: vec![0; (WIDTH * height / 8) as usize] }
+    }
+
+    pub fn clear(&mut self) {
+        self.data.fill(0);
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> bool {
+        x < WIDTH && y < self.height && self.data[(x + (y / 8) * WIDTH) as usize] & (1 << (y % 8)) != 0
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl OriginDimensions for FrameBuffer {
+    fn size(&self) -> Size {
+        Size::new(WIDTH, self.height)
+    }
+}
+
+impl DrawTarget for FrameBuffer {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> std::result::Result<(), Infallible>
+    where
+        I: IntoIterator<Item = Pixel<BinaryColor>>,
+    {
+        for Pixel(point, color) in pixels {
+            if point.x < 0 || point.y < 0 || point.x as u32 >= WIDTH || point.y as u32 >= self.height {
+                continue;
+            }
+            let (x, y) = (point.x as u32, point.y as u32);
+            let index = (x + (y / 8) * WIDTH) as usize;
+            if color.is_on() {
+                self.data[index] |= 1 << (y % 8);
+            } else {
+                self.data[index] &= !(1 << (y % 8));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Text the font can show; characters outside Latin-1 (emoji) are left out
+fn printable(text: &str, max_chars: usize) -> String {
+    text.chars().filter(|c| (*c as u32) < 0x100).collect::<String>().trim().chars().take(max_chars).collect()
+}
+
+/// Draw the state into `buffer`: a status line, one level bar per channel
+/// and, if there is room, the song or the latest event
+pub fn render(buffer: &mut FrameBuffer, frame: &StatusFrame, message: Option<&str>) {
+    buffer.clear();
+    let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+    let columns = (WIDTH / FONT_6X10.character_size.width) as usize;
+    let line_height = FONT_6X10.character_size.height as i32;
+
+    let status = if frame.paused {
+        "PAUSED".to_string()
+    } else if frame.recording {
+        match frame.file {
+            Some(file) => format!("REC {}", file.rsplit('/').next().unwrap_or(file)),
+            None => "REC".to_string(),
+        }
+    } else if frame.waiting_for_schedule {
+        "Waiting for schedule".to_string()
+    } else {
+        "Waiting for signal".to_string()
+    };
+    let _ = Text::with_baseline(&printable(&status, columns), Point::zero(), style, Baseline::Top).draw(buffer);
+
+    // Bars fill the space between the status line and the bottom lines
+    let text_lines = if buffer.height >= 64 { 2 } else { 0 };
+    let top = line_height + 1;
+    let bottom = buffer.height as i32 - text_lines * line_height - 1;
+    let channels = frame.levels.len().max(1) as i32;
+    let bar_height = ((bottom - top) / channels - 1).clamp(1, 8);
+    for (ch, m) in frame.levels.iter().enumerate() {
+        let y = top + ch as i32 * (bar_height + 1);
+        if y + bar_height > bottom + 1 {
+            break;
+        }
+        let fraction = |db: f64| ((db + BAR_RANGE_DB) / BAR_RANGE_DB).clamp(0.0, 1.0);
+        let length = (fraction(m.db) * WIDTH as f64) as u32;
+        if length > 0 {
+            let _ = Rectangle::new(Point::new(0, y), Size::new(length, bar_height as u32))
+                .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+                .draw(buffer);
+        }
+        // Peak hold as a vertical line
+        let peak = ((fraction(m.max_peak_db) * (WIDTH - 1) as f64) as i32).max(length as i32);
+        let _ = Line::new(Point::new(peak, y), Point::new(peak, y + bar_height - 1))
+            .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1))
+            .draw(buffer);
+    }
+
+    if text_lines > 0 {
+        let text = message.or(frame.song).unwrap_or("");
+        let text = printable(text, columns * 2);
+        let (first, second) = text.split_at(text.char_indices().nth(columns).map_or(text.len(), |(i, _)| i));
+        for (i, line) in [first, second].iter().enumerate() {
+            let y = bottom + 1 + i as i32 * line_height;
+            let _ = Text::with_baseline(line.trim(), Point::new(0, y), style, Baseline::Top).draw(buffer);
+        }
+    }
+}
+
+/// Command and data transfers of one connection
+trait Bus {
+    fn command(&mut self, bytes: &[u8]) -> io::Result<()>;
+    fn data(&mut self, bytes: &[u8]) -> io::Result<()>;
+}
+
+struct I2cBus {
+    device: LinuxI2CDevice,
+}
+
+impl Bus for I2cBus {
+    fn command(&mut self, bytes: &[u8]) -> io::Result<()> {
+        for &byte in bytes {
+            self.device.write(&[0x00, byte]).map_err(io::Error::other)?;
+        }
+        Ok(())
+    }
+
+    fn data(&mut self, bytes: &[u8]) -> io::Result<()> {
+        for chunk in bytes.chunks(I2C_CHUNK) {
+            let mut message = Vec::with_capacity(chunk.len() + 1);
+            message.push(0x40);
+            message.extend_from_slice(chunk);
+            self.device.write(&message).map_err(io::Error::other)?;
+        }
+        Ok(())
+    }
+}
+
+struct SpiBus {
+    device: Spidev,
+    dc: LineHandle,
+}
+
+impl Bus for SpiBus {
+    fn command(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.dc.set_value(0).map_err(io::Error::other)?;
+        self.device.write_all(bytes)
+    }
+
+    fn data(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.dc.set_value(1).map_err(io::Error::other)?;
+        self.device.write_all(bytes)
+    }
+}
+
+fn request_line(chip: &str, line: u32, name: &str) -> Result<LineHandle> {
+    let mut chip = Chip::new(chip).map_err(|e| Error::Backend(format!("GPIO chip {}: {}", chip, e)))?;
+    chip.get_line(line)
+        .and_then(|l| l.request(LineRequestFlags::OUTPUT, 1, name))
+        .map_err(|e| Error::Backend(format!("GPIO line {}: {}", line, e)))
+}
+
+/// An SSD1306 showing the recorder state
+pub struct OledDisplay {
+    bus: Box<dyn Bus>,
+    buffer: FrameBuffer,
+    shown: Option<Vec<u8>>,
+    last_draw: Option<Instant>,
+    message: Option<(String, Instant)>,
+}
+
+impl OledDisplay {
+    /// Open the display, reset it if a reset line is set and switch it on
+    pub fn open(settings: &OledSettings) -> Result<Self> {
+        if let Some(pin) = settings.reset_pin {
+            let reset = request_line(&settings.gpio_chip, pin, "autorec-oled-reset")?;
+            let toggle = |value| reset.set_value(value).map_err(|e| Error::Backend(format!("OLED reset: {}", e)));
+            toggle(0)?;
+            thread::sleep(Duration::from_millis(10));
+            toggle(1)?;
+            thread::sleep(Duration::from_millis(10));
+        }
+        let bus: Box<dyn Bus> = match &settings.interface {
+            OledInterface::I2c { device, address } => Box::new(I2cBus {
+                device: LinuxI2CDevice::new(device, *address)
+                    .map_err(|e| Error::Backend(format!("OLED {}: {}", device, e)))?,
+            }),
+            OledInterface::Spi { device, gpio_chip, dc_pin } => {
+                let mut spi = Spidev::open(device).map_err(|e| Error::Backend(format!("OLED {}: {}", device, e)))?;
+                let options = SpidevOptions::new().bits_per_word(8).max_speed_hz(8_000_000).mode(SpiModeFlags::SPI_MODE_0).build();
+                spi.configure(&options).map_err(|e| Error::Backend(format!("OLED {}: {}", device, e)))?;
+                Box::new(SpiBus { device: spi, dc: request_line(gpio_chip, *dc_pin, "autorec-oled-dc")? })
+            }
+        };
+        let mut display = OledDisplay { bus, buffer: FrameBuffer::new(settings.height), shown: None, last_draw: None, message: None };
+        display.init(settings).map_err(|e| Error::Backend(format!("OLED: {}", e)))?;
+        Ok(display)
+    }
+
+    fn init(&mut self, settings: &OledSettings) -> io::Result<()> {
+        let (segment_remap, com_scan) = if settings.rotate { (0xa0, 0xc0) } else { (0xa1, 0xc8) };
+        let com_pins = if settings.height == 64 { 0x12 } else { 0x02 };
+        self.bus.command(&[
+            0xae, // display off
+            0xd5, 0x80, // clock divider
+            0xa8, settings.height as u8 - 1, // multiplex ratio
+            0xd3, 0x00, // no display offset
+            0x40, // start line 0
+            0x8d, 0x14, // charge pump on
+            0x20, 0x00, // horizontal addressing
+            segment_remap,
+            com_scan,
+            0xda, com_pins,
+            0x81, 0xcf, // contrast
+            0xd9, 0xf1, // pre-charge period
+            0xdb, 0x40, // VCOMH level
+            0xa4, // show the RAM content
+            0xa6, // not inverted
+            0xaf, // display on
+        ])?;
+        self.flush()
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let pages = (self.buffer.height / 8) as u8;
+        self.bus.command(&[0x21, 0, (WIDTH - 1) as u8, 0x22, 0, pages - 1])?;
+        self.bus.data(self.buffer.bytes())?;
+        self.shown = Some(self.buffer.bytes().to_vec());
+        Ok(())
+    }
+}
+
+impl StatusDisplay for OledDisplay {
+    fn update(&mut self, frame: &StatusFrame) -> io::Result<()> {
+        let interval = Duration::from_secs(1) / FRAME_RATE;
+        if self.last_draw.is_some_and(|t| t.elapsed() < interval) {
+            return Ok(());
+        }
+        self.last_draw = Some(Instant::now());
+        if self.message.as_ref().is_some_and(|(_, since)| since.elapsed() > MESSAGE_TIME) {
+            self.message = None;
+        }
+        render(&mut self.buffer, frame, self.message.as_ref().map(|(text, _)| text.as_str()));
+        if self.shown.as_deref() == Some(self.buffer.bytes()) {
+            return Ok(());
+        }
+        self.flush()
+    }
+
+    fn event(&mut self, event: &StatusEvent) {
+        info!("{}", event);
+        self.message = Some((event.to_string(), Instant::now()));
+    }
+}
+
+impl Drop for OledDisplay {
+    fn drop(&mut self) {
+        let _ = self.bus.command(&[0xae]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vu_meter::ChannelMetrics;
+
+    fn channel(db: f64) -> ChannelMetrics {
+        ChannelMetrics {
+            db,
+            peak_db: db,
+            max_db: db,
+            max_peak_db: db + 6.0,
+            is_on: true,
+            has_clipped: false,
+            true_peak_db: db,
+            max_true_peak_db: db,
+            overs: 0,
+            total_overs: 0,
+        }
+    }
+
+    #[test]
+    fn test_settings_and_render() {
+        assert_eq!(
+            OledSettings::from_config(&OledConfig::default()).unwrap().interface,
+            OledInterface::I2c { device: DEFAULT_I2C_DEVICE.to_string(), address: DEFAULT_ADDRESS }
+        );
+        let spi = OledConfig { interface: Some("spi".to_string()), ..Default::default() };
+        assert!(OledSettings::from_config(&spi).is_err());
+        assert!(OledSettings::from_config(&OledConfig { height: Some(48), ..Default::default() }).is_err());
+
+        let levels = [channel(-30.0), channel(-60.0)];
+        let frame = StatusFrame { recording: true, file: Some("/data/side_a.wav"), song: Some("Motörhead - Ace of Spades"), levels: &levels, ..Default::default() };
+        let mut buffer = FrameBuffer::new(64);
+        render(&mut buffer, &frame, None);
+        // First bar: -30 dB fills half the width, the peak hold sits at -24 dB
+        let y = 12;
+        assert!(buffer.pixel(0, y) && buffer.pixel(60, y));
+        assert!(!buffer.pixel(70, y));
+        assert!(buffer.pixel(76, y));
+        // Second bar is silent
+        assert!(!buffer.pixel(10, y + 9));
+        assert!((0..WIDTH).any(|x| buffer.pixel(x, 60)));
+
+        let mut small = FrameBuffer::new(32);
+        render(&mut small, &frame, Some("Audio source lost"));
+        assert_eq!(small.bytes().len(), 512);
+        assert_eq!(printable("🎵 Song #2", 20), "Song #2");
+    }
+}
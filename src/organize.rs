@@ -0,0 +1,300 @@
+//! Filing finished album sessions into a music library.
+//!
+//! A session directory holds the recordings of one album and their
+//! `session.toml` (see [`crate::session`]). Once the album is identified and
+//! every side has a CUE sheet the session is complete, and [`plan`] works out
+//! where its files go: a directory of the library named by a
+//! [`FilenameTemplate`], `Artist/Album (Year)` by default.
+//! [`OrganizePlan::execute`] moves the files there, copying them when the
+//! library is on another file system. The names inside the session manifest
+//! and the CUE sheets are relative, so they stay valid.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::cuefile;
+use crate::error::{Error, Result};
+use crate::filename_template::{FilenameTemplate, TemplateValues};
+use crate::session::{Session, SESSION_FILE_NAME};
+
+/// Album directory in the library unless the config sets `filename_template`
+pub const DEFAULT_LIBRARY_TEMPLATE: &str = "{album_artist}/{album} ({year})";
+
+/// Images in a session directory that are filed with the album
+pub const ARTWORK_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp", "gif"];
+
+/// Appended to the name of a library file while it is being overwritten
+const BACKUP_SUFFIX: &str = ".organize-backup";
+
+/// Names of the collision policies
+pub const COLLISION_NAMES: &[&str] = &["skip", "number", "overwrite"];
+
+/// What to do when the library already has a file of the same name
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Collision {
+    /// Leave the session where it is
+    #[default]
+    Skip,
+    /// File the album into the next free directory, "Album (1977) (2)"
+    Number,
+    /// Replace the files in the library
+    Overwrite,
+}
+
+impl Collision {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "skip" => Some(Collision::Skip),
+            "number" => Some(Collision::Number),
+            "overwrite" => Some(Collision::Overwrite),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Collision::Skip => "skip",
+            Collision::Number => "number",
+            Collision::Overwrite => "overwrite",
+        }
+    }
+}
+
+/// Directories below `capture_dir` (and including it) with a session manifest
+pub fn find_sessions(capture_dir: &Path) -> Vec<PathBuf> {
+    let mut sessions = Vec::new();
+    if capture_dir.join(SESSION_FILE_NAME).is_file() {
+        sessions.push(capture_dir.to_path_buf());
+    }
+    let mut subdirs: Vec<PathBuf> = fs::read_dir(capture_dir)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| p.is_dir() && !p.file_name().is_some_and(|n| n.to_string_lossy().starts_with('.')))
+                .collect()
+        })
+        .unwrap_or_default();
+    subdirs.sort();
+    for dir in subdirs {
+        sessions.extend(find_sessions(&dir));
+    }
+    sessions
+}
+
+/// Why the session in `dir` can't be filed yet; None if it is complete
+pub fn incomplete_reason(dir: &Path, session: &Session) -> Option<String> {
+    if session.artist.is_none() || session.album.is_none() {
+        return Some("album not identified".to_string());
+    }
+    if session.sides.is_empty() {
+        return Some("no recordings listed".to_string());
+    }
+    for side in &session.sides {
+        let wav = dir.join(&side.file);
+        if !wav.is_file() {
+            return Some(format!("{} is missing", side.file));
+        }
+        if cuefile::find_cue_file(&wav.to_string_lossy()).is_none() {
+            return Some(format!("{} has no CUE sheet", side.file));
+        }
+    }
+    None
+}
+
+/// Files of the session in `dir`: the manifest, the files named after a
+/// recording (CUE sheets, FLAC copies, reports) and the artwork
+pub fn session_files(dir: &Path, session: &Session) -> Result<Vec<PathBuf>> {
+    let stems: Vec<String> = session
+        .sides
+        .iter()
+        .map(|side| format!("{}.", side.file.strip_suffix(".wav").unwrap_or(&side.file)))
+        .collect();
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+        let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let artwork = path
+            .extension()
+            .is_some_and(|e| ARTWORK_EXTENSIONS.contains(&e.to_string_lossy().to_lowercase().as_str()));
+        if name == SESSION_FILE_NAME || artwork || stems.iter().any(|stem| name.starts_with(stem.as_str())) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// The moves that file one session into the library
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrganizePlan {
+    pub source: PathBuf,
+    /// Album directory in the library
+    pub target: PathBuf,
+    pub moves: Vec<(PathBuf, PathBuf)>,
+}
+
+/// Plan filing the complete session in `dir` into `library`.
+/// Fails if a file exists in the library and `collision` is Skip.
+pub fn plan(dir: &Path, library: &Path, template: &FilenameTemplate, collision: Collision) -> Result<OrganizePlan> {
+    let session = Session::load(&dir.join(SESSION_FILE_NAME))?;
+    let values = TemplateValues {
+        artist: session.artist.clone(),
+        album: session.album.clone(),
+        album_artist: session.artist.clone(),
+        year: session.year,
+        ..Default::default()
+    };
+    let album_dir = library.join(template.render(&values));
+    let files = session_files(dir, &session)?;
+    let moves_into = |target: &Path| -> Vec<(PathBuf, PathBuf)> {
+        files.iter().map(|f| (f.clone(), target.join(f.file_name().unwrap_or_default()))).collect()
+    };
+    let taken = |moves: &[(PathBuf, PathBuf)]| moves.iter().find(|(_, to)| to.exists()).map(|(_, to)| to.clone());
+
+    let mut target = album_dir.clone();
+    let mut moves = moves_into(&target);
+    match (collision, taken(&moves)) {
+        (_, None) | (Collision::Overwrite, _) => {}
+        (Collision::Skip, Some(path)) => {
+            return Err(Error::Io(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already exists", path.display()),
+            )))
+        }
+        (Collision::Number, Some(_)) => {
+            let mut number = 2;
+            while taken(&moves).is_some() {
+                target = PathBuf::from(format!("{} ({})", album_dir.display(), number));
+                moves = moves_into(&target);
+                number += 1;
+            }
+        }
+    }
+    Ok(OrganizePlan { source: dir.to_path_buf(), target, moves })
+}
+
+/// Rename `from` to `to`, or copy and delete it if that isn't possible
+/// (e.g. from a local disk to a network share)
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    fs::copy(from, to)?;
+    fs::remove_file(from)
+}
+
+/// Move `from` to `to`; a file at `to` is renamed to a backup first,
+/// which is added to `backups` with its original path
+fn replace_file(from: &Path, to: &Path, backups: &mut Vec<(PathBuf, PathBuf)>) -> io::Result<()> {
+    if to.exists() {
+        let mut backup = to.as_os_str().to_owned();
+        backup.push(BACKUP_SUFFIX);
+        let backup = PathBuf::from(backup);
+        fs::rename(to, &backup)?;
+        backups.push((backup, to.to_path_buf()));
+    }
+    move_file(from, to)
+}
+
+impl OrganizePlan {
+    /// Move the files; if one fails the files already moved are moved back
+    /// and the library files they replaced are restored
+    pub fn execute(&self) -> Result<()> {
+        fs::create_dir_all(&self.target)?;
+        let mut backups = Vec::new();
+        for (i, (from, to)) in self.moves.iter().enumerate() {
+            if let Err(e) = replace_file(from, to, &mut backups) {
+                for (from, to) in self.moves[..i].iter().rev() {
+                    let _ = move_file(to, from);
+                }
+                for (backup, path) in &backups {
+                    let _ = fs::rename(backup, path);
+                }
+                return Err(Error::Io(io::Error::new(
+                    e.kind(),
+                    format!("Failed to move {}: {}", from.display(), e),
+                )));
+            }
+        }
+        for (backup, _) in &backups {
+            let _ = fs::remove_file(backup);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_and_execute() {
+        let capture = tempfile::tempdir().unwrap();
+        let library = tempfile::tempdir().unwrap();
+        let dir = capture.path().join("animals");
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join(SESSION_FILE_NAME), "artist = \"Pink Floyd\"\nalbum = \"Animals\"\nyear = 1977\n\n\
+            [[side]]\nfile = \"animals.1.wav\"\nduration = 1292.4\n").unwrap();
+        for name in ["animals.1.wav", "animals.1.cue", "animals.1.flac", "cover.jpg", "notes.md"] {
+            fs::write(dir.join(name), b"").unwrap();
+        }
+        assert_eq!(find_sessions(capture.path()), vec![dir.clone()]);
+        let session = Session::load(&dir.join(SESSION_FILE_NAME)).unwrap();
+        assert_eq!(incomplete_reason(&dir, &session), None);
+
+        let template = FilenameTemplate::parse(DEFAULT_LIBRARY_TEMPLATE).unwrap();
+        let album_dir = library.path().join("Pink Floyd/Animals (1977)");
+        let filed = plan(&dir, library.path(), &template, Collision::Skip).unwrap();
+        assert_eq!(filed.target, album_dir);
+        assert_eq!(filed.moves.len(), 5);
+        filed.execute().unwrap();
+        assert!(album_dir.join("animals.1.cue").exists());
+        assert!(dir.join("notes.md").exists());
+        assert!(!dir.join("animals.1.wav").exists());
+
+        // The album is in the library already
+        for (from, to) in &filed.moves {
+            fs::copy(to, from).unwrap();
+        }
+        assert!(plan(&dir, library.path(), &template, Collision::Skip).is_err());
+        let numbered = plan(&dir, library.path(), &template, Collision::Number).unwrap();
+        assert_eq!(numbered.target, library.path().join("Pink Floyd/Animals (1977) (2)"));
+    }
+
+    #[test]
+    fn test_overwrite_rollback() {
+        let capture = tempfile::tempdir().unwrap();
+        let library = tempfile::tempdir().unwrap();
+        let dir = capture.path().join("animals");
+        let album_dir = library.path().join("Pink Floyd/Animals (1977)");
+        fs::create_dir(&dir).unwrap();
+        fs::create_dir_all(&album_dir).unwrap();
+        fs::write(dir.join(SESSION_FILE_NAME), "artist = \"Pink Floyd\"\nalbum = \"Animals\"\nyear = 1977\n\n\
+            [[side]]\nfile = \"animals.1.wav\"\nduration = 1292.4\n").unwrap();
+        for name in ["animals.1.wav", "animals.1.cue", "cover.jpg"] {
+            fs::write(dir.join(name), "new").unwrap();
+            fs::write(album_dir.join(name), "old").unwrap();
+        }
+
+        let template = FilenameTemplate::parse(DEFAULT_LIBRARY_TEMPLATE).unwrap();
+        let filed = plan(&dir, library.path(), &template, Collision::Overwrite).unwrap();
+        assert_eq!(filed.target, album_dir);
+
+        // cover.jpg is moved after both recording files and fails
+        fs::remove_file(dir.join("cover.jpg")).unwrap();
+        assert!(filed.execute().is_err());
+        assert_eq!(fs::read_to_string(dir.join("animals.1.cue")).unwrap(), "new");
+        assert_eq!(fs::read_to_string(album_dir.join("animals.1.cue")).unwrap(), "old");
+        assert_eq!(fs::read_to_string(album_dir.join("cover.jpg")).unwrap(), "old");
+
+        fs::write(dir.join("cover.jpg"), "new").unwrap();
+        filed.execute().unwrap();
+        assert_eq!(fs::read_to_string(album_dir.join("animals.1.wav")).unwrap(), "new");
+        assert_eq!(fs::read_dir(&album_dir).unwrap().count(), 4);
+    }
+}
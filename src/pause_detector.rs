@@ -6,6 +6,7 @@
 
 use crate::SampleFormat;
 use std::time::{Duration, Instant};
+use tracing::{debug, info};
 
 const TRAINING_SKIP_MS: u32 = 500;          // Skip first 500ms (click)
 const MUSIC_DETECT_DELTA_DB: f32 = 10.0;    // Music is 10dB+ above noise floor
@@ -51,6 +52,8 @@ pub struct AdaptivePauseDetector {
     pause_duration_ms: u32,       // For this long
     threshold_override: Option<f32>,
     pause_duration_override: Option<u32>,
+    sensitivity_db: f32,          // Added to the learned threshold
+    debounce_ms: u32,             // Minimum time between boundaries
     
     // Current pause state
     in_pause: bool,
@@ -82,6 +85,8 @@ impl AdaptivePauseDetector {
             pause_duration_ms: 200,     // Initial default
             threshold_override: None,
             pause_duration_override: None,
+            sensitivity_db: 0.0,
+            debounce_ms: 0,
             
             in_pause: false,
             pause_start: None,
@@ -159,6 +164,16 @@ impl AdaptivePauseDetector {
         self.pause_duration_ms = duration_ms;
     }
     
+    /// Shift the learned pause threshold by `sensitivity_db` (positive = more pauses)
+    pub fn set_sensitivity(&mut self, sensitivity_db: f32) {
+        self.sensitivity_db = sensitivity_db;
+    }
+    
+    /// Set the debounce, see [`StrategyParams::debounce_ms`](crate::detection_strategies::factory::StrategyParams::debounce_ms)
+    pub fn set_debounce(&mut self, debounce_ms: u32) {
+        self.debounce_ms = debounce_ms;
+    }
+    
     /// Get debug information about the current detection state
     pub fn get_debug_info(&self) -> DebugInfo {
         DebugInfo {
@@ -182,10 +197,7 @@ impl AdaptivePauseDetector {
             return -80.0;
         }
         
-        let max_value = match format {
-            SampleFormat::S16 => 32768.0_f32,
-            SampleFormat::S32 => 2147483648.0_f32,
-        };
+        let max_value = format.max_value() as f32;
         
         // Mix to mono and calculate RMS
         let mut sum_squares = 0.0_f64;
@@ -233,12 +245,15 @@ impl AdaptivePauseDetector {
                 if detect_start.elapsed().as_millis() >= MUSIC_DETECT_DURATION_MS as u128 {
                     // Music confirmed! Transition to active mode
                     self.noise_floor_db = current_noise_floor;
-                    self.pause_threshold_db = self.noise_floor_db.max(-50.0);
+                    self.pause_threshold_db = match self.threshold_override {
+                        Some(threshold_db) => threshold_db,
+                        None => self.noise_floor_db.max(-50.0) + self.sensitivity_db,
+                    };
                     self.state = DetectorState::Active;
                     self.current_song_start = Instant::now();
                     self.last_pause_time = Instant::now();
-                    eprintln!("Pause detector: Training complete. Noise floor: {:.1} dB, Threshold: {:.1} dB", 
-                             self.noise_floor_db, self.pause_threshold_db);
+                    info!("Pause detector: Training complete. Noise floor: {:.1} dB, Threshold: {:.1} dB",
+                          self.noise_floor_db, self.pause_threshold_db);
                 }
             }
         } else {
@@ -280,7 +295,9 @@ impl AdaptivePauseDetector {
                     let pause_duration_ms = start.elapsed().as_millis() as u32;
                     
                     // Was the pause long enough?
-                    if pause_duration_ms >= self.pause_duration_ms {
+                    if pause_duration_ms >= self.pause_duration_ms
+                        && self.current_song_start.elapsed().as_millis() >= self.debounce_ms as u128
+                    {
                         // Song boundary detected!
                         let song_duration = self.current_song_start.elapsed();
                         self.song_durations.push(song_duration);
@@ -324,7 +341,7 @@ impl AdaptivePauseDetector {
         let total: Duration = self.song_durations.iter().sum();
         let avg_secs = total.as_secs() / self.song_durations.len() as u64;
         
-        eprintln!("Pause detector: Avg song length: {}s (from {} songs)", avg_secs, self.song_durations.len());
+        debug!("Avg song length: {}s (from {} songs)", avg_secs, self.song_durations.len());
         
         // If average song length is too short, we're detecting too many pauses
         if avg_secs < MIN_SONG_LENGTH_SECS as u64 {
@@ -333,8 +350,8 @@ impl AdaptivePauseDetector {
             if self.pause_duration_override.is_none() {
                 let old_duration = self.pause_duration_ms;
                 self.pause_duration_ms = (self.pause_duration_ms + 100).min(1500);
-                eprintln!("Pause detector: Songs too short, increasing pause duration: {}ms -> {}ms", 
-                         old_duration, self.pause_duration_ms);
+                debug!("Songs too short, increasing pause duration: {}ms -> {}ms",
+                       old_duration, self.pause_duration_ms);
             }
         }
     }
@@ -349,7 +366,7 @@ impl AdaptivePauseDetector {
         // Decrease the RMS threshold (make it easier to detect pauses)
         let old_threshold = self.pause_threshold_db;
         self.pause_threshold_db = (self.pause_threshold_db + 2.0).min(-30.0);
-        eprintln!("Pause detector: No pause for 6min, increasing sensitivity: {:.1}dB -> {:.1}dB", 
-                 old_threshold, self.pause_threshold_db);
+        debug!("No pause for 6min, increasing sensitivity: {:.1}dB -> {:.1}dB",
+               old_threshold, self.pause_threshold_db);
     }
 }
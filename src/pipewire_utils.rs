@@ -1,39 +1,183 @@
+use crate::error::{Error, Result};
+use regex::Regex;
+use serde::Serialize;
 use std::process::{Command, Stdio};
 
-#[derive(Debug, Clone)]
+/// Prefix for targets that are selected by a regular expression instead of
+/// an exact node name, e.g. `re:alsa_input\.usb-.*`
+pub const REGEX_TARGET_PREFIX: &str = "re:";
+
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct Source {
     pub name: String,
     pub description: Option<String>,
+    /// PipeWire object ID
+    pub id: Option<u32>,
+    pub media_class: Option<String>,
+    pub rate: Option<u32>,
+    pub channels: Option<u32>,
+    /// True if the node is currently running (producing audio)
+    pub active: bool,
 }
 
-/// Get list of available PipeWire recording targets
-pub fn get_available_targets() -> Vec<Source> {
-    match Command::new("pw-cli")
-        .arg("list-objects")
+/// Filter applied when listing or selecting recording targets
+#[derive(Debug, Clone, Default)]
+pub struct TargetFilter {
+    /// Case-insensitive substring of the media class (e.g. "Audio/Source")
+    pub media_class: Option<String>,
+    /// Regex matched against the node name and description
+    pub name_regex: Option<Regex>,
+    /// Only include nodes that are currently running
+    pub active_only: bool,
+}
+
+impl TargetFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check whether a source passes all configured filters
+    pub fn matches(&self, source: &Source) -> bool {
+        if self.active_only && !source.active {
+            return false;
+        }
+
+        if let Some(ref class) = self.media_class {
+            match source.media_class {
+                Some(ref c) if c.to_lowercase().contains(&class.to_lowercase()) => {}
+                _ => return false,
+            }
+        }
+
+        if let Some(ref re) = self.name_regex {
+            let desc_match = source.description.as_deref().map(|d| re.is_match(d)).unwrap_or(false);
+            if !re.is_match(&source.name) && !desc_match {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+fn run_command(program: &str, args: &[&str]) -> Option<String> {
+    let child = Command::new(program)
+        .args(args)
         .stdout(Stdio::piped())
         .stderr(Stdio::null())
         .spawn()
-    {
-        Ok(child) => {
-            let output = match child.wait_with_output() {
-                Ok(output) => output,
-                Err(_) => return Vec::new(),
-            };
+        .ok()?;
+    let output = child.wait_with_output().ok()?;
 
-            if !output.status.success() {
-                return Vec::new();
-            }
+    if !output.status.success() {
+        return None;
+    }
+
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
 
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            parse_pw_cli_output(&stdout)
+/// Get list of available PipeWire recording targets
+///
+/// Uses `pw-dump` when available, as it also reports node state and the
+/// negotiated format. Falls back to parsing `pw-cli list-objects`.
+pub fn get_available_targets() -> Vec<Source> {
+    if let Some(json) = run_command("pw-dump", &[]) {
+        let sources = parse_pw_dump_output(&json);
+        if !sources.is_empty() {
+            return sources;
         }
-        Err(_) => Vec::new(),
     }
+
+    match run_command("pw-cli", &["list-objects"]) {
+        Some(stdout) => parse_pw_cli_output(&stdout),
+        None => Vec::new(),
+    }
+}
+
+/// Get available recording targets that pass the given filter
+pub fn get_filtered_targets(filter: &TargetFilter) -> Vec<Source> {
+    get_available_targets()
+        .into_iter()
+        .filter(|s| filter.matches(s))
+        .collect()
+}
+
+fn is_source_class(class: &str) -> bool {
+    class.contains("Source") || class.contains("source") || class.contains("Input")
+}
+
+fn parse_pw_cli_id(line: &str) -> Option<u32> {
+    let rest = line.trim().strip_prefix("id")?;
+    let digits: String = rest
+        .trim_start()
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    digits.parse().ok()
+}
+
+/// Parse the JSON output of `pw-dump` into a list of recording sources
+fn parse_pw_dump_output(output: &str) -> Vec<Source> {
+    let objects: Vec<serde_json::Value> = match serde_json::from_str(output) {
+        Ok(serde_json::Value::Array(objects)) => objects,
+        _ => return Vec::new(),
+    };
+
+    let mut sources = Vec::new();
+
+    for obj in &objects {
+        if obj["type"].as_str() != Some("PipeWire:Interface:Node") {
+            continue;
+        }
+
+        let info = &obj["info"];
+        let props = &info["props"];
+
+        let media_class = match props["media.class"].as_str() {
+            Some(class) if is_source_class(class) => class.to_string(),
+            _ => continue,
+        };
+
+        let name = match props["node.name"].as_str() {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+
+        let description = props["node.description"]
+            .as_str()
+            .or_else(|| props["node.nick"].as_str())
+            .map(|s| s.to_string());
+
+        // Prefer the negotiated format, fall back to the node properties
+        let format = &info["params"]["Format"][0];
+        let rate = format["rate"]
+            .as_u64()
+            .or_else(|| props["audio.rate"].as_u64())
+            .map(|v| v as u32);
+        let channels = format["channels"]
+            .as_u64()
+            .or_else(|| props["audio.channels"].as_u64())
+            .map(|v| v as u32);
+
+        sources.push(Source {
+            name,
+            description,
+            id: obj["id"].as_u64().map(|v| v as u32),
+            media_class: Some(media_class),
+            rate,
+            channels,
+            active: info["state"].as_str() == Some("running"),
+        });
+    }
+
+    sources
 }
 
 fn parse_pw_cli_output(output: &str) -> Vec<Source> {
     let mut sources = Vec::new();
     let mut current_obj: Option<Source> = None;
+    let mut current_id: Option<u32> = None;
+    let mut current_class: Option<String> = None;
     let mut is_source = false;
 
     for line in output.lines() {
@@ -46,12 +190,16 @@ fn parse_pw_cli_output(output: &str) -> Vec<Source> {
                 }
             }
             current_obj = None;
+            current_id = parse_pw_cli_id(line);
+            current_class = None;
             is_source = false;
         } else if line.contains("node.name") {
             if let Some(name) = extract_quoted_value(line) {
                 current_obj = Some(Source {
                     name: name.to_string(),
-                    description: None,
+                    id: current_id,
+                    media_class: current_class.clone(),
+                    ..Default::default()
                 });
             }
         } else if line.contains("node.description") || line.contains("node.nick") {
@@ -61,9 +209,23 @@ fn parse_pw_cli_output(output: &str) -> Vec<Source> {
                 }
             }
         } else if line.contains("media.class") {
-            if line.contains("Source") || line.contains("source") || line.contains("Input") {
+            if let Some(class) = extract_quoted_value(line) {
+                current_class = Some(class.to_string());
+                if let Some(ref mut obj) = current_obj {
+                    obj.media_class = Some(class.to_string());
+                }
+            }
+            if is_source_class(line) {
                 is_source = true;
             }
+        } else if line.contains("audio.rate") {
+            if let (Some(v), Some(ref mut obj)) = (extract_quoted_value(line), current_obj.as_mut()) {
+                obj.rate = v.parse().ok();
+            }
+        } else if line.contains("audio.channels") {
+            if let (Some(v), Some(ref mut obj)) = (extract_quoted_value(line), current_obj.as_mut()) {
+                obj.channels = v.parse().ok();
+            }
         }
     }
 
@@ -88,7 +250,23 @@ fn extract_quoted_value(line: &str) -> Option<&str> {
 
 /// List available PipeWire recording targets
 pub fn list_targets() -> i32 {
-    let sources = get_available_targets();
+    list_targets_filtered(&TargetFilter::default(), false)
+}
+
+/// List PipeWire recording targets matching a filter, optionally as JSON
+pub fn list_targets_filtered(filter: &TargetFilter, json: bool) -> i32 {
+    let sources = get_filtered_targets(filter);
+
+    if json {
+        match serde_json::to_string_pretty(&sources) {
+            Ok(out) => println!("{}", out),
+            Err(e) => {
+                eprintln!("Error: Failed to serialize targets: {}", e);
+                return 1;
+            }
+        }
+        return if sources.is_empty() { 1 } else { 0 };
+    }
 
     if sources.is_empty() {
         println!("No recording sources found or could not query PipeWire.");
@@ -99,7 +277,25 @@ pub fn list_targets() -> i32 {
     println!("Available PipeWire recording targets:");
     println!();
     for src in sources {
-        println!("  {}", src.name);
+        let mut details = Vec::new();
+        if let Some(id) = src.id {
+            details.push(format!("id {}", id));
+        }
+        if let Some(rate) = src.rate {
+            details.push(format!("{} Hz", rate));
+        }
+        if let Some(channels) = src.channels {
+            details.push(format!("{} ch", channels));
+        }
+        if src.active {
+            details.push("active".to_string());
+        }
+
+        if details.is_empty() {
+            println!("  {}", src.name);
+        } else {
+            println!("  {} [{}]", src.name, details.join(", "));
+        }
         if let Some(desc) = src.description {
             println!("    {}", desc);
         }
@@ -109,13 +305,60 @@ pub fn list_targets() -> i32 {
     0
 }
 
+/// Select a target whose name or description matches a regex
+///
+/// Running nodes are preferred over idle ones; otherwise the first match wins.
+pub fn select_target_by_regex(sources: &[Source], pattern: &str) -> Result<Option<String>> {
+    let re = Regex::new(pattern).map_err(|e| Error::Config(format!("Invalid target regex '{}': {}", pattern, e)))?;
+    let filter = TargetFilter {
+        name_regex: Some(re),
+        ..Default::default()
+    };
+
+    let matching: Vec<&Source> = sources.iter().filter(|s| filter.matches(s)).collect();
+    let selected = matching
+        .iter()
+        .find(|s| s.active)
+        .or_else(|| matching.first())
+        .map(|s| s.name.clone());
+
+    Ok(selected)
+}
+
 /// Validate or auto-select a PipeWire target
 ///
+/// A target of the form `re:<pattern>` is resolved to the first matching node
+/// name, so saved configs keep working when PipeWire renames a device.
+///
 /// Returns (target_name, error_code) where error_code is 0 for success, 1 for error
 pub fn validate_and_select_target(specified_target: Option<&str>, verbose: bool) -> (Option<String>, i32) {
     let available_targets = get_available_targets();
     let target_names: Vec<String> = available_targets.iter().map(|s| s.name.clone()).collect();
 
+    if let Some(pattern) = specified_target.and_then(|t| t.strip_prefix(REGEX_TARGET_PREFIX)) {
+        return match select_target_by_regex(&available_targets, pattern) {
+            Ok(Some(name)) => {
+                if verbose {
+                    println!("Target regex '{}' matched: {}", pattern, name);
+                }
+                (Some(name), 0)
+            }
+            Ok(None) => {
+                if verbose {
+                    eprintln!("Error: No target matches regex '{}'.", pattern);
+                    eprintln!("\nRun with --list-targets for more details.");
+                }
+                (None, 1)
+            }
+            Err(e) => {
+                if verbose {
+                    eprintln!("Error: {}", e);
+                }
+                (None, 1)
+            }
+        };
+    }
+
     if let Some(target) = specified_target {
         // Validate that the specified target exists
         if !target_names.is_empty() && !target_names.contains(&target.to_string()) {
@@ -205,8 +448,121 @@ id 43, type PipeWire:Interface:Node
         let source = Source {
             name: "test".to_string(),
             description: Some("Test Description".to_string()),
+            ..Default::default()
         };
         assert_eq!(source.name, "test");
         assert_eq!(source.description, Some("Test Description".to_string()));
+        assert!(!source.active);
+    }
+
+    #[test]
+    fn test_parse_pw_cli_output_details() {
+        let output = r#"
+id 57, type PipeWire:Interface:Node/3
+    node.name = "alsa_input.usb-Behringer"
+    media.class = "Audio/Source"
+    audio.rate = "48000"
+    audio.channels = "2"
+"#;
+
+        let sources = parse_pw_cli_output(output);
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].id, Some(57));
+        assert_eq!(sources[0].media_class.as_deref(), Some("Audio/Source"));
+        assert_eq!(sources[0].rate, Some(48000));
+        assert_eq!(sources[0].channels, Some(2));
+    }
+
+    #[test]
+    fn test_parse_pw_dump_output() {
+        let output = r#"[
+  { "id": 0, "type": "PipeWire:Interface:Core", "info": {} },
+  { "id": 61, "type": "PipeWire:Interface:Node",
+    "info": { "state": "running",
+      "props": { "node.name": "riaa.monitor", "node.description": "RIAA Monitor",
+                 "media.class": "Audio/Source", "audio.rate": 44100 },
+      "params": { "Format": [ { "rate": 96000, "channels": 2 } ] } } },
+  { "id": 62, "type": "PipeWire:Interface:Node",
+    "info": { "state": "suspended",
+      "props": { "node.name": "alsa_output.speakers", "media.class": "Audio/Sink" } } },
+  { "id": 63, "type": "PipeWire:Interface:Node",
+    "info": { "state": "idle",
+      "props": { "node.name": "alsa_input.mic", "node.nick": "Mic",
+                 "media.class": "Audio/Source", "audio.rate": 48000, "audio.channels": 1 } } }
+]"#;
+
+        let sources = parse_pw_dump_output(output);
+        assert_eq!(sources.len(), 2);
+
+        assert_eq!(sources[0].name, "riaa.monitor");
+        assert_eq!(sources[0].id, Some(61));
+        assert_eq!(sources[0].rate, Some(96000));
+        assert_eq!(sources[0].channels, Some(2));
+        assert!(sources[0].active);
+
+        assert_eq!(sources[1].description.as_deref(), Some("Mic"));
+        assert_eq!(sources[1].rate, Some(48000));
+        assert_eq!(sources[1].channels, Some(1));
+        assert!(!sources[1].active);
+
+        assert!(parse_pw_dump_output("not json").is_empty());
+    }
+
+    fn sample_sources() -> Vec<Source> {
+        vec![
+            Source {
+                name: "alsa_input.usb-old".to_string(),
+                media_class: Some("Audio/Source".to_string()),
+                ..Default::default()
+            },
+            Source {
+                name: "alsa_input.usb-new".to_string(),
+                description: Some("USB Audio CODEC".to_string()),
+                media_class: Some("Audio/Source".to_string()),
+                active: true,
+                ..Default::default()
+            },
+            Source {
+                name: "alsa_output.hdmi.monitor".to_string(),
+                media_class: Some("Audio/Sink".to_string()),
+                ..Default::default()
+            },
+        ]
+    }
+
+    #[test]
+    fn test_target_filter() {
+        let sources = sample_sources();
+
+        let all = TargetFilter::new();
+        assert!(sources.iter().all(|s| all.matches(s)));
+
+        let active = TargetFilter { active_only: true, ..Default::default() };
+        assert_eq!(sources.iter().filter(|s| active.matches(s)).count(), 1);
+
+        let class = TargetFilter { media_class: Some("audio/source".to_string()), ..Default::default() };
+        assert_eq!(sources.iter().filter(|s| class.matches(s)).count(), 2);
+
+        let by_desc = TargetFilter { name_regex: Some(Regex::new("CODEC").unwrap()), ..Default::default() };
+        let matched: Vec<_> = sources.iter().filter(|s| by_desc.matches(s)).collect();
+        assert_eq!(matched.len(), 1);
+        assert_eq!(matched[0].name, "alsa_input.usb-new");
+    }
+
+    #[test]
+    fn test_select_target_by_regex() {
+        let sources = sample_sources();
+
+        // The active node wins over the first match
+        assert_eq!(
+            select_target_by_regex(&sources, r"^alsa_input\.usb-").unwrap(),
+            Some("alsa_input.usb-new".to_string())
+        );
+        assert_eq!(
+            select_target_by_regex(&sources, "hdmi").unwrap(),
+            Some("alsa_output.hdmi.monitor".to_string())
+        );
+        assert_eq!(select_target_by_regex(&sources, "bluetooth").unwrap(), None);
+        assert!(select_target_by_regex(&sources, "(").is_err());
     }
 }
@@ -0,0 +1,264 @@
+//! Low-bitrate preview files written alongside the archival WAV.
+//!
+//! A preview (MP3 or Opus) is small enough to audition a side on a phone
+//! without copying the multi-GB master. Encoding is done by `ffmpeg`, fed raw
+//! PCM through a pipe from its own thread, so a slow encoder never delays
+//! writing the WAV file. If the encoder falls behind, chunks are dropped from
+//! the preview only.
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{sync_channel, SyncSender, TrySendError};
+use std::thread;
+
+use crate::vu_meter::SampleFormat;
+
+/// Default preview bitrate in kbit/s
+pub const DEFAULT_PREVIEW_BITRATE: u32 = 128;
+
+/// Number of chunks that may be queued for the encoder before chunks are dropped
+const QUEUE_CHUNKS: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PreviewFormat {
+    Mp3,
+    Opus,
+}
+
+impl PreviewFormat {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "mp3" => Some(PreviewFormat::Mp3),
+            "opus" | "ogg" => Some(PreviewFormat::Opus),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PreviewFormat::Mp3 => "mp3",
+            PreviewFormat::Opus => "opus",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        self.as_str()
+    }
+
+    fn codec(&self) -> &'static str {
+        match self {
+            PreviewFormat::Mp3 => "libmp3lame",
+            PreviewFormat::Opus => "libopus",
+        }
+    }
+}
+
+/// Preview settings used by the recorder for every new file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PreviewSettings {
+    pub format: PreviewFormat,
+    pub bitrate_kbps: u32,
+}
+
+impl PreviewSettings {
+    pub fn new(format: PreviewFormat) -> Self {
+        PreviewSettings {
+            format,
+            bitrate_kbps: DEFAULT_PREVIEW_BITRATE,
+        }
+    }
+}
+
+/// Path of the preview belonging to a WAV file (`side.1.wav` -> `side.1.mp3`)
+pub fn preview_path(wav_path: &str, format: PreviewFormat) -> String {
+    Path::new(wav_path)
+        .with_extension(format.extension())
+        .to_string_lossy()
+        .to_string()
+}
+
+/// ffmpeg arguments to encode raw little-endian PCM from stdin into `output`
+pub fn ffmpeg_args(
+    output: &str,
+    rate: u32,
+    channels: usize,
+    format: SampleFormat,
+    settings: &PreviewSettings,
+) -> Vec<String> {
+    let input_format = match format {
+        SampleFormat::S16 => "s16le",
+        // ffmpeg has no 24-in-32 input, those samples are sent packed
+        SampleFormat::S24 | SampleFormat::S24In32 => "s24le",
+        SampleFormat::S32 => "s32le",
+    };
+    let mut args: Vec<String> = vec![
+        "-hide_banner", "-loglevel", "error", "-y",
+        "-f", input_format,
+        "-ar", &rate.to_string(),
+        "-ac", &channels.to_string(),
+        "-i", "pipe:0",
+        "-c:a", settings.format.codec(),
+        "-b:a", &format!("{}k", settings.bitrate_kbps),
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect();
+
+    // Opus only supports a few sample rates; 48 kHz is always valid
+    if settings.format == PreviewFormat::Opus {
+        args.extend(["-ar", "48000"].map(String::from));
+    }
+    args.push(output.to_string());
+    args
+}
+
+/// Encodes one preview file in the background
+pub struct PreviewEncoder {
+    path: String,
+    format: SampleFormat,
+    sender: Option<SyncSender<Vec<u8>>>,
+    thread_handle: Option<thread::JoinHandle<Result<(), String>>>,
+    dropped_chunks: usize,
+}
+
+impl PreviewEncoder {
+    /// Start ffmpeg writing to `path`
+    pub fn new(
+        path: &str,
+        rate: u32,
+        channels: usize,
+        format: SampleFormat,
+        settings: &PreviewSettings,
+    ) -> Result<Self, String> {
+        let mut child: Child = Command::new("ffmpeg")
+            .args(ffmpeg_args(path, rate, channels, format, settings))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to start ffmpeg for preview: {}", e))?;
+
+        let mut stdin = child.stdin.take().ok_or("Failed to open ffmpeg stdin")?;
+        let (sender, receiver) = sync_channel::<Vec<u8>>(QUEUE_CHUNKS);
+
+        let thread_handle = thread::spawn(move || {
+            let mut write_error = None;
+            for chunk in receiver {
+                if let Err(e) = stdin.write_all(&chunk) {
+                    write_error = Some(format!("Error writing to preview encoder: {}", e));
+                    break;
+                }
+            }
+            // Closing stdin tells ffmpeg to finish the file
+            drop(stdin);
+
+            let output = child
+                .wait_with_output()
+                .map_err(|e| format!("Failed to wait for ffmpeg: {}", e))?;
+            if !output.status.success() {
+                return Err(format!(
+                    "ffmpeg failed: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ));
+            }
+            match write_error {
+                Some(e) => Err(e),
+                None => Ok(()),
+            }
+        });
+
+        Ok(PreviewEncoder {
+            path: path.to_string(),
+            format,
+            sender: Some(sender),
+            thread_handle: Some(thread_handle),
+            dropped_chunks: 0,
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Number of chunks skipped because the encoder could not keep up
+    pub fn dropped_chunks(&self) -> usize {
+        self.dropped_chunks
+    }
+
+    /// Queue interleaved samples without blocking
+    pub fn write_samples(&mut self, samples: &[i32]) {
+        if let Some(sender) = self.sender.as_ref() {
+            let bytes = samples_to_bytes(samples, self.format);
+            match sender.try_send(bytes) {
+                Ok(()) => {}
+                Err(TrySendError::Full(_)) | Err(TrySendError::Disconnected(_)) => self.dropped_chunks += 1,
+            }
+        }
+    }
+
+    /// Flush the remaining audio and wait for ffmpeg to finish the file
+    pub fn finish(&mut self) -> Result<(), String> {
+        self.sender.take();
+        match self.thread_handle.take() {
+            Some(handle) => handle
+                .join()
+                .map_err(|_| "Preview encoder thread panicked".to_string())?,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for PreviewEncoder {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+/// Convert interleaved samples to little-endian PCM bytes
+fn samples_to_bytes(samples: &[i32], format: SampleFormat) -> Vec<u8> {
+    let format = match format {
+        SampleFormat::S24In32 => SampleFormat::S24,
+        other => other,
+    };
+    let mut bytes = Vec::with_capacity(samples.len() * format.bytes_per_sample());
+    for &sample in samples {
+        format.encode_sample(sample, &mut bytes);
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preview_format_parsing() {
+        assert_eq!(PreviewFormat::from_str("MP3"), Some(PreviewFormat::Mp3));
+        assert_eq!(PreviewFormat::from_str("opus"), Some(PreviewFormat::Opus));
+        assert_eq!(PreviewFormat::from_str("flac"), None);
+    }
+
+    #[test]
+    fn test_preview_path() {
+        assert_eq!(preview_path("side.1.wav", PreviewFormat::Mp3), "side.1.mp3");
+        assert_eq!(preview_path("/tmp/rec/side.2.wav", PreviewFormat::Opus), "/tmp/rec/side.2.opus");
+    }
+
+    #[test]
+    fn test_ffmpeg_args() {
+        let settings = PreviewSettings { format: PreviewFormat::Opus, bitrate_kbps: 96 };
+        let args = ffmpeg_args("out.opus", 96000, 2, SampleFormat::S32, &settings);
+        let joined = args.join(" ");
+        assert!(joined.contains("-f s32le -ar 96000 -ac 2 -i pipe:0"));
+        assert!(joined.contains("-c:a libopus -b:a 96k -ar 48000"));
+        assert_eq!(args.last().unwrap(), "out.opus");
+    }
+
+    #[test]
+    fn test_samples_to_bytes() {
+        assert_eq!(samples_to_bytes(&[1, -1], SampleFormat::S16), vec![1, 0, 0xff, 0xff]);
+        assert_eq!(samples_to_bytes(&[256], SampleFormat::S32), vec![0, 1, 0, 0]);
+        assert_eq!(samples_to_bytes(&[-2], SampleFormat::S24In32), vec![0xfe, 0xff, 0xff]);
+    }
+}
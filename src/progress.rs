@@ -0,0 +1,187 @@
+//! Progress reporting for long analyses.
+//!
+//! Functions that work through a whole recording (the RMS pass, song
+//! identification, splitting) take a `&mut dyn ProgressSink` and report how
+//! far they got. [`TerminalProgress`] draws a progress bar on stderr,
+//! [`JsonLinesProgress`] writes one JSON object per update for other
+//! programs and [`NoProgress`] ignores the updates.
+
+use std::io::{self, Write};
+
+/// Stage of [`crate::audio_analysis::wav_rms`]
+pub const STAGE_RMS: &str = "rms";
+/// Stage of song identification (Shazam or AcoustID)
+pub const STAGE_IDENTIFY: &str = "identify";
+/// Stage of [`crate::loudness::analyze_wav`]
+pub const STAGE_LOUDNESS: &str = "loudness";
+/// Stage of [`crate::audio_analysis::quality_report`]
+pub const STAGE_QUALITY: &str = "quality";
+/// Stage of [`crate::spectrogram::wav_spectrogram`]
+pub const STAGE_SPECTROGRAM: &str = "spectrogram";
+/// Stage of [`crate::splitter::split_wav`]
+pub const STAGE_SPLIT: &str = "split";
+
+/// Names accepted by [`create`]
+pub const PROGRESS_NAMES: [&str; 3] = ["none", "bar", "json"];
+
+/// Receiver of progress updates
+pub trait ProgressSink {
+    /// `percent` (0-100) of `stage` is done; `message` describes the current step
+    fn progress(&mut self, percent: f32, stage: &str, message: &str);
+
+    /// `stage` is complete
+    fn finish(&mut self, stage: &str) {
+        self.progress(100.0, stage, "");
+    }
+}
+
+/// Ignores all updates
+pub struct NoProgress;
+
+impl ProgressSink for NoProgress {
+    fn progress(&mut self, _percent: f32, _stage: &str, _message: &str) {}
+}
+
+/// Drops updates that would not change the output
+#[derive(Default)]
+struct Throttle {
+    stage: String,
+    percent: i32,
+    message: String,
+}
+
+impl Throttle {
+    fn changed(&mut self, percent: f32, stage: &str, message: &str) -> bool {
+        let percent = percent.clamp(0.0, 100.0) as i32;
+        if percent == self.percent && stage == self.stage && message == self.message {
+            return false;
+        }
+        self.percent = percent;
+        self.stage = stage.to_string();
+        self.message = message.to_string();
+        true
+    }
+}
+
+/// Progress bar on stderr, redrawn in place
+pub struct TerminalProgress {
+    width: usize,
+    throttle: Throttle,
+}
+
+impl TerminalProgress {
+    pub fn new() -> Self {
+        TerminalProgress { width: 30, throttle: Throttle { percent: -1, ..Default::default() } }
+    }
+
+    /// One line of the bar, e.g. `rms       [#######        ]  50% message`
+    fn line(&self, percent: f32, stage: &str, message: &str) -> String {
+        let percent = percent.clamp(0.0, 100.0);
+        let filled = (percent / 100.0 * self.width as f32).round() as usize;
+        format!(
+            "{:<10}[{}{}] {:3.0}% {}",
+            stage,
+            "#".repeat(filled),
+            " ".repeat(self.width - filled),
+            percent,
+            message
+        )
+    }
+}
+
+impl Default for TerminalProgress {
+    fn default() -> Self {
+        TerminalProgress::new()
+    }
+}
+
+impl ProgressSink for TerminalProgress {
+    fn progress(&mut self, percent: f32, stage: &str, message: &str) {
+        if !self.throttle.changed(percent, stage, message) {
+            return;
+        }
+        let mut stderr = io::stderr().lock();
+        // Clear the rest of a longer previous line
+        let _ = write!(stderr, "\r{}\x1b[K", self.line(percent, stage, message));
+        if percent >= 100.0 {
+            let _ = writeln!(stderr);
+        }
+        let _ = stderr.flush();
+    }
+}
+
+/// One JSON object per update and line:
+/// `{"stage":"rms","percent":42.0,"message":""}`
+pub struct JsonLinesProgress<W: Write> {
+    writer: W,
+    throttle: Throttle,
+}
+
+impl<W: Write> JsonLinesProgress<W> {
+    pub fn new(writer: W) -> Self {
+        JsonLinesProgress { writer, throttle: Throttle { percent: -1, ..Default::default() } }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> ProgressSink for JsonLinesProgress<W> {
+    fn progress(&mut self, percent: f32, stage: &str, message: &str) {
+        if !self.throttle.changed(percent, stage, message) {
+            return;
+        }
+        let line = serde_json::json!({
+            "stage": stage,
+            "percent": (percent.clamp(0.0, 100.0) as f64 * 10.0).round() / 10.0,
+            "message": message,
+        });
+        let _ = writeln!(self.writer, "{}", line);
+        let _ = self.writer.flush();
+    }
+}
+
+/// Create the sink called `name` (one of [`PROGRESS_NAMES`]); JSON lines go to stderr
+pub fn create(name: &str) -> Option<Box<dyn ProgressSink>> {
+    match name.to_lowercase().as_str() {
+        "none" => Some(Box::new(NoProgress)),
+        "bar" => Some(Box::new(TerminalProgress::new())),
+        "json" => Some(Box::new(JsonLinesProgress::new(io::stderr()))),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_lines() {
+        let mut sink = JsonLinesProgress::new(Vec::new());
+        sink.progress(0.0, STAGE_RMS, "");
+        sink.progress(0.4, STAGE_RMS, "");
+        sink.progress(42.25, STAGE_RMS, "");
+        sink.progress(10.0, STAGE_IDENTIFY, "Identifying song at 01:00");
+        sink.finish(STAGE_IDENTIFY);
+
+        let output = String::from_utf8(sink.into_inner()).unwrap();
+        let lines: Vec<serde_json::Value> = output.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        // The update at 0.4% does not change the whole percent
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[1]["percent"], 42.3);
+        assert_eq!(lines[2]["stage"], "identify");
+        assert_eq!(lines[2]["message"], "Identifying song at 01:00");
+        assert_eq!(lines[3]["percent"], 100.0);
+    }
+
+    #[test]
+    fn test_terminal_line() {
+        let bar = TerminalProgress::new();
+        assert_eq!(bar.line(50.0, STAGE_SPLIT, "02 - Dogs.wav"),
+                   format!("split     [{}{}]  50% 02 - Dogs.wav", "#".repeat(15), " ".repeat(15)));
+        assert!(bar.line(150.0, STAGE_RMS, "").contains("100%"));
+        assert!(create("json").is_some());
+        assert!(create("spinner").is_none());
+    }
+}
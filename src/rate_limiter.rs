@@ -2,15 +2,23 @@
 //!
 //! Used by songrec (Shazam), MusicBrainz, and Discogs API clients
 //! to stay within their respective rate limits.
+//!
+//! [`RateLimiter::shared`] returns a handle to the one limiter of a service
+//! in this process, so that requests from different modules and threads are
+//! spaced together and a backoff after an error applies to all of them.
 
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
 use std::time::{Duration, Instant};
 use std::thread;
 
-/// A rate limiter that enforces a minimum interval between requests
-/// with optional adaptive backoff on failures.
-pub struct RateLimiter {
-    name: String,
-    last_request: Option<Instant>,
+use rand::Rng;
+use tracing::{debug, info};
+
+/// Interval and backoff state, possibly shared by several handles
+struct LimiterState {
+    /// Earliest time of the next request
+    next_request: Option<Instant>,
     current_interval: Duration,
     base_interval: Duration,
     max_interval: Duration,
@@ -18,6 +26,16 @@ pub struct RateLimiter {
     successes_to_reduce: u32,
 }
 
+/// A rate limiter that enforces a minimum interval between requests
+/// with optional adaptive backoff on failures.
+pub struct RateLimiter {
+    name: String,
+    state: Arc<Mutex<LimiterState>>,
+}
+
+/// Limiters returned by [`RateLimiter::shared`], by service name
+static SHARED: OnceLock<Mutex<HashMap<String, Arc<Mutex<LimiterState>>>>> = OnceLock::new();
+
 impl RateLimiter {
     /// Create a new rate limiter.
     ///
@@ -27,15 +45,50 @@ impl RateLimiter {
     /// * `successes_to_reduce` — how many consecutive successes before halving the interval
     ///   (set to 0 to disable adaptive backoff reduction)
     pub fn new(name: &str, base_interval: Duration, max_interval: Duration, successes_to_reduce: u32) -> Self {
-        RateLimiter {
-            name: name.to_string(),
-            last_request: None,
+        let state = LimiterState {
+            next_request: None,
             current_interval: base_interval,
             base_interval,
             max_interval,
             success_count: 0,
             successes_to_reduce,
+        };
+        RateLimiter { name: name.to_string(), state: Arc::new(Mutex::new(state)) }
+    }
+
+    /// The process-wide limiter of service `name`, created with
+    /// `base_interval` (like [`RateLimiter::from_millis`]) on first use.
+    ///
+    /// All handles share the spacing of requests and the backoff state. A
+    /// later call with a longer base interval raises it for all handles, so
+    /// the strictest limit asked for (e.g. anonymous Discogs requests next to
+    /// authenticated ones) holds; a shorter one is ignored.
+    pub fn shared(name: &str, base_interval: Duration) -> Self {
+        let mut limiters = SHARED.get_or_init(Default::default).lock().unwrap_or_else(|e| e.into_inner());
+        let state = limiters
+            .entry(name.to_string())
+            .or_insert_with(|| Self::new(name, base_interval, base_interval * 16, 10).state)
+            .clone();
+        let limiter = RateLimiter { name: name.to_string(), state };
+        {
+            let mut state = limiter.state();
+            if base_interval > state.base_interval {
+                debug!("[{}] Base interval raised to {:.1}s", name, base_interval.as_secs_f64());
+                state.base_interval = base_interval;
+                state.max_interval = state.max_interval.max(base_interval * 16);
+                state.current_interval = state.current_interval.max(base_interval);
+            }
         }
+        limiter
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, LimiterState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Current interval between requests
+    pub fn interval(&self) -> Duration {
+        self.state().current_interval
     }
 
     /// Convenience: create a rate limiter from a base interval in seconds.
@@ -54,51 +107,108 @@ impl RateLimiter {
 
     /// Sleep if not enough time has elapsed since the last request.
     /// Must be called *before* making a request.
+    ///
+    /// The request time is reserved before sleeping, so concurrent callers
+    /// of a shared limiter queue up one interval apart. While backing off,
+    /// up to a quarter of the interval is added at random, so that clients
+    /// throttled at the same time don't retry in step.
     pub fn wait_if_needed(&mut self) {
-        if let Some(last) = self.last_request {
-            let elapsed = last.elapsed();
-            if elapsed < self.current_interval {
-                let wait_time = self.current_interval - elapsed;
-                println!("  [{}] Rate limiting: waiting {:.1}s...",
-                         self.name, wait_time.as_secs_f64());
-                thread::sleep(wait_time);
+        let now = Instant::now();
+        let slot = {
+            let mut state = self.state();
+            let slot = state.next_request.map_or(now, |next| next.max(now));
+            let mut interval = state.current_interval;
+            if interval > state.base_interval {
+                interval += interval.mul_f64(rand::thread_rng().gen_range(0.0..0.25));
             }
+            state.next_request = Some(slot + interval);
+            slot
+        };
+        let wait_time = slot - now;
+        if !wait_time.is_zero() {
+            info!("  [{}] Rate limiting: waiting {:.1}s...",
+                  self.name, wait_time.as_secs_f64());
+            thread::sleep(wait_time);
         }
-        self.last_request = Some(Instant::now());
     }
 
     /// Report a successful request.  After enough consecutive successes
     /// the interval is halved (down to the base).
     pub fn report_success(&mut self) {
-        if self.successes_to_reduce == 0 {
+        let mut state = self.state();
+        if state.successes_to_reduce == 0 {
             return; // adaptive reduction disabled
         }
 
-        self.success_count += 1;
+        state.success_count += 1;
 
-        if self.success_count >= self.successes_to_reduce && self.current_interval > self.base_interval {
-            let new_interval = self.current_interval / 2;
-            if new_interval >= self.base_interval {
-                self.current_interval = new_interval;
+        if state.success_count >= state.successes_to_reduce && state.current_interval > state.base_interval {
+            let new_interval = state.current_interval / 2;
+            if new_interval >= state.base_interval {
+                state.current_interval = new_interval;
             } else {
-                self.current_interval = self.base_interval;
+                state.current_interval = state.base_interval;
             }
-            println!("  [{}] Rate limit reduced to {:.1}s after {} successes",
-                     self.name, self.current_interval.as_secs_f64(), self.success_count);
-            self.success_count = 0;
+            debug!("[{}] Rate limit reduced to {:.1}s after {} successes",
+                   self.name, state.current_interval.as_secs_f64(), state.success_count);
+            state.success_count = 0;
         }
     }
 
     /// Report a failed request.  Doubles the interval (up to max).
     pub fn report_failure(&mut self) {
-        let new_interval = self.current_interval * 2;
-        if new_interval <= self.max_interval {
-            self.current_interval = new_interval;
+        let mut state = self.state();
+        let new_interval = state.current_interval * 2;
+        if new_interval <= state.max_interval {
+            state.current_interval = new_interval;
         } else {
-            self.current_interval = self.max_interval;
+            state.current_interval = state.max_interval;
+        }
+        debug!("[{}] Rate limit increased to {:.1}s due to error",
+               self.name, state.current_interval.as_secs_f64());
+        state.success_count = 0;
+    }
+
+    /// Report the outcome of an HTTP request: 503 (service unavailable) and
+    /// 429 (too many requests) back off, any other response counts as a
+    /// success. Network errors leave the interval alone.
+    pub fn report_response<T>(&mut self, response: &std::result::Result<T, Box<ureq::Error>>) {
+        match response.as_ref().map_err(|e| &**e) {
+            Err(ureq::Error::Status(503 | 429, _)) => self.report_failure(),
+            Err(ureq::Error::Transport(_)) => {}
+            _ => self.report_success(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shared_backoff() {
+        let base = Duration::from_millis(10);
+        let mut first = RateLimiter::shared("test-service", base / 2);
+        // A longer base interval is raised for all handles, a shorter one ignored
+        let second = RateLimiter::shared("test-service", base);
+        assert_eq!(first.interval(), base);
+        assert_eq!(RateLimiter::shared("test-service", base / 4).interval(), base);
+
+        // A backoff reported through one handle applies to all of them
+        first.report_failure();
+        first.report_failure();
+        assert_eq!(second.interval(), base * 4);
+        for _ in 0..10 {
+            first.report_success();
         }
-        println!("  [{}] Rate limit increased to {:.1}s due to error",
-                 self.name, self.current_interval.as_secs_f64());
-        self.success_count = 0;
+        assert_eq!(second.interval(), base * 2);
+        assert_eq!(RateLimiter::shared("other-service", base).interval(), base);
+
+        // Requests are spaced at least one interval apart, with jitter
+        let start = Instant::now();
+        first.wait_if_needed();
+        RateLimiter::shared("test-service", base).wait_if_needed();
+        let elapsed = start.elapsed();
+        assert!(elapsed >= base * 2 && elapsed < base * 10, "{:?}", elapsed);
     }
 }
@@ -1,23 +1,147 @@
-use std::fs::File;
-use std::io::{self, Write};
 use std::path::Path;
 use std::sync::mpsc::{channel, Receiver, Sender};
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::{Duration, Instant};
+use std::time::{Duration, SystemTime};
 
+use tracing::{error, info, warn};
+
+use crate::audio_analysis::{Dropout, DropoutDetector};
+use crate::cuefile::{clip_report_path, dropout_report_path, marks_path};
+use crate::downmix::Downmix;
+use crate::error::Error;
+use crate::filename_template::{FilenameTemplate, TemplateValues};
+use crate::listening_copy::{listening_copy_path, ListeningCopy, ListeningCopySettings};
+use crate::preview::{preview_path, PreviewEncoder, PreviewSettings};
 use crate::vu_meter::SampleFormat;
+use crate::wavfile::{WavBext, WavIxml, WavWriter};
 
 #[derive(Debug)]
 enum RecorderCommand {
     Start,
     Write(Vec<i32>),
-    Stop,
+    Overs(Vec<usize>),
+    /// Add a track mark at the current position
+    Mark,
+    /// Close the current file; `keep` keeps it even if it is shorter than
+    /// the minimum length
+    Stop { keep: bool },
+    SetPreview(Option<PreviewSettings>),
+    SetListeningCopy(Option<ListeningCopySettings>),
+    SetDownmix(Downmix),
+}
+
+/// Pause state of the recorder, see [`AudioRecorder::pause`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PauseState {
+    Running,
+    Paused,
+    /// Resumed, but the signal has not come back yet
+    Resumed,
+}
+
+/// Positions listed in a clipping report; overs beyond this are only counted
+pub const MAX_OVER_POSITIONS: usize = 100;
+
+/// Overs (runs of consecutive full-scale samples) during one recording
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClipSummary {
+    /// Number of overs per channel
+    pub overs_per_channel: Vec<usize>,
+    /// (position in seconds, channel) of the chunks containing overs
+    pub positions: Vec<(f64, usize)>,
+}
+
+impl ClipSummary {
+    pub fn total(&self) -> usize {
+        self.overs_per_channel.iter().sum()
+    }
+
+    /// Add the overs of the chunk starting at `position` seconds
+    pub fn add(&mut self, position: f64, overs: &[usize]) {
+        if self.overs_per_channel.len() < overs.len() {
+            self.overs_per_channel.resize(overs.len(), 0);
+        }
+        for (ch, &count) in overs.iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+            self.overs_per_channel[ch] += count;
+            if self.positions.len() < MAX_OVER_POSITIONS {
+                self.positions.push((position, ch));
+            }
+        }
+    }
+
+    /// Text for the recording's info file
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        text.push_str("Clipping:\n");
+        text.push_str("---------\n");
+        text.push_str(&format!("Total overs: {}\n", self.total()));
+        for (ch, count) in self.overs_per_channel.iter().enumerate() {
+            text.push_str(&format!("  Channel {}: {}\n", ch, count));
+        }
+        if !self.positions.is_empty() {
+            text.push_str("Overs at:\n");
+            for (position, ch) in &self.positions {
+                text.push_str(&format!("  {:.2}s (channel {})\n", position, ch));
+            }
+            if self.positions.len() == MAX_OVER_POSITIONS {
+                text.push_str("  ... (further positions omitted)\n");
+            }
+        }
+        text
+    }
+}
+
+/// Text of the dropout report written next to a recording
+pub fn dropout_report(dropouts: &[Dropout]) -> String {
+    let mut text = format!("Dropouts: {}\n", dropouts.len());
+    for dropout in dropouts {
+        text.push_str(&format!("  {}\n", dropout.describe()));
+    }
+    text
+}
+
+/// Statistics of a finished (kept) recording
+#[derive(Debug, Clone)]
+pub struct RecordingStats {
+    pub filename: String,
+    /// Length of the recorded audio in seconds (without pauses)
+    pub duration: f64,
+    /// Wall-clock time the recording started and ended
+    pub started: SystemTime,
+    pub finished: SystemTime,
+    /// Mean level of the recording in dBFS (RMS over all channels)
+    pub average_level_db: Option<f64>,
+    /// Highest true peak seen during the recording in dBTP, if reported
+    pub max_true_peak_db: Option<f64>,
+    /// Low-bitrate preview written alongside the WAV file
+    pub preview_file: Option<String>,
+    /// FLAC listening copy written alongside the WAV file
+    pub flac_file: Option<String>,
+    /// Overs seen during the recording, if reported
+    pub clipping: ClipSummary,
+    /// Runs of identical samples in the middle of the recording
+    pub dropouts: Vec<Dropout>,
+}
+
+/// Counters of the recording thread, e.g. for monitoring a long-running daemon
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct WriterStats {
+    /// Audio chunks handed to the recording thread and not written yet
+    pub queued_chunks: usize,
+    /// Bytes of audio written to WAV files
+    pub bytes_written: u64,
+    /// Frames lost because they could not be written (e.g. disk full)
+    pub dropped_frames: u64,
 }
 
 #[allow(dead_code)]
 pub struct AudioRecorder {
     base_filename: String,
+    filename_template: FilenameTemplate,
     rate: u32,
     channels: usize,
     format: SampleFormat,
@@ -25,15 +149,20 @@ pub struct AudioRecorder {
 
     recording: Arc<Mutex<bool>>,
     current_file: Arc<Mutex<Option<String>>>,
-    recording_start_time: Arc<Mutex<Option<Instant>>>,
     next_file_number: Arc<Mutex<usize>>,
     recorded_files: Arc<Mutex<Vec<String>>>,
+    max_true_peak_db: Arc<Mutex<Option<f64>>>,
+    recording_stats: Arc<Mutex<Vec<RecordingStats>>>,
+    last_error: Arc<Mutex<Option<Error>>>,
+    writer_stats: Arc<Mutex<WriterStats>>,
+    pause_state: Mutex<PauseState>,
 
     sender: Sender<RecorderCommand>,
     thread_handle: Option<thread::JoinHandle<()>>,
 }
 
 impl AudioRecorder {
+    /// Recorder writing `<base_filename>.1.wav`, `<base_filename>.2.wav`, ...
     pub fn new(
         base_filename: String,
         rate: u32,
@@ -41,15 +170,22 @@ impl AudioRecorder {
         format: SampleFormat,
         min_length: f64,
     ) -> Self {
-        // Initialize file counter by checking existing files
-        let base_no_ext = if base_filename.ends_with(".wav") {
-            base_filename.trim_end_matches(".wav").to_string()
-        } else {
-            base_filename.clone()
-        };
+        Self::with_filename_template(base_filename, FilenameTemplate::recording(), rate, channels, format, min_length)
+    }
 
+    /// Recorder naming its files by `filename_template`, relative to the
+    /// directory of `base_filename`
+    pub fn with_filename_template(
+        base_filename: String,
+        filename_template: FilenameTemplate,
+        rate: u32,
+        channels: usize,
+        format: SampleFormat,
+        min_length: f64,
+    ) -> Self {
+        // Initialize file counter by checking existing files
         let mut n = 1;
-        while Path::new(&format!("{}.{}.wav", base_no_ext, n)).exists() {
+        while Path::new(&Self::get_next_filename(&filename_template, &base_filename, n)).exists() {
             n += 1;
         }
 
@@ -57,155 +193,385 @@ impl AudioRecorder {
 
         let recording = Arc::new(Mutex::new(false));
         let current_file = Arc::new(Mutex::new(None));
-        let recording_start_time = Arc::new(Mutex::new(None));
         let next_file_number = Arc::new(Mutex::new(n));
         let recorded_files = Arc::new(Mutex::new(Vec::new()));
+        let max_true_peak_db = Arc::new(Mutex::new(None));
+        let recording_stats = Arc::new(Mutex::new(Vec::new()));
+        let last_error = Arc::new(Mutex::new(None));
+        let writer_stats = Arc::new(Mutex::new(WriterStats::default()));
 
         // Start recording thread
         let thread_handle = {
             let base_filename = base_filename.clone();
+            let filename_template = filename_template.clone();
             let rate = rate;
             let channels = channels;
             let format = format;
             let min_length = min_length;
             let recording = Arc::clone(&recording);
             let current_file = Arc::clone(&current_file);
-            let recording_start_time = Arc::clone(&recording_start_time);
             let next_file_number = Arc::clone(&next_file_number);
             let recorded_files = Arc::clone(&recorded_files);
+            let max_true_peak_db = Arc::clone(&max_true_peak_db);
+            let recording_stats = Arc::clone(&recording_stats);
+            let last_error = Arc::clone(&last_error);
+            let writer_stats = Arc::clone(&writer_stats);
 
             thread::spawn(move || {
                 Self::recording_worker(
                     receiver,
                     base_filename,
+                    filename_template,
                     rate,
                     channels,
                     format,
                     min_length,
                     recording,
                     current_file,
-                    recording_start_time,
                     next_file_number,
                     recorded_files,
+                    max_true_peak_db,
+                    recording_stats,
+                    last_error,
+                    writer_stats,
                 );
             })
         };
 
         AudioRecorder {
             base_filename,
+            filename_template,
             rate,
             channels,
             format,
             min_length,
             recording,
             current_file,
-            recording_start_time,
             next_file_number,
             recorded_files,
+            max_true_peak_db,
+            recording_stats,
+            last_error,
+            writer_stats,
+            pause_state: Mutex::new(PauseState::Running),
             sender,
             thread_handle: Some(thread_handle),
         }
     }
 
-    fn get_next_filename(base_filename: &str, file_number: usize) -> String {
-        let base_no_ext = if base_filename.ends_with(".wav") {
-            base_filename.trim_end_matches(".wav")
-        } else {
-            base_filename
+    fn get_next_filename(template: &FilenameTemplate, base_filename: &str, file_number: usize) -> String {
+        let base = Path::new(base_filename.strip_suffix(".wav").unwrap_or(base_filename));
+        let values = TemplateValues {
+            base: base.file_name().map(|name| name.to_string_lossy().to_string()),
+            side: Some(file_number as u32),
+            ext: Some("wav".to_string()),
+            date: Some(chrono::Local::now().naive_local()),
+            ..Default::default()
         };
-        format!("{}.{}.wav", base_no_ext, file_number)
+        let mut filename = template.render(&values);
+        if let Some(stem) = filename.strip_suffix(".wav") {
+            filename.truncate(stem.len());
+        }
+        // Without a side in the name the file number is appended, otherwise
+        // the search for a new name would never end
+        if file_number > 1 && !template.numbers_side(file_number as u32) {
+            filename.push_str(&format!(".{}", file_number));
+        }
+        filename.push_str(".wav");
+        base.parent().unwrap_or(Path::new("")).join(filename).to_string_lossy().to_string()
     }
 
     fn recording_worker(
         receiver: Receiver<RecorderCommand>,
         base_filename: String,
+        filename_template: FilenameTemplate,
         rate: u32,
         channels: usize,
         format: SampleFormat,
         min_length: f64,
         recording: Arc<Mutex<bool>>,
         current_file: Arc<Mutex<Option<String>>>,
-        recording_start_time: Arc<Mutex<Option<Instant>>>,
         next_file_number: Arc<Mutex<usize>>,
         recorded_files: Arc<Mutex<Vec<String>>>,
+        max_true_peak_db: Arc<Mutex<Option<f64>>>,
+        recording_stats: Arc<Mutex<Vec<RecordingStats>>>,
+        last_error: Arc<Mutex<Option<Error>>>,
+        writer_stats: Arc<Mutex<WriterStats>>,
     ) {
+        let report = |message: &str, e: std::io::Error| {
+            error!("{}: {}", message, e);
+            *last_error.lock().unwrap() = Some(Error::Io(e));
+        };
+
         let mut wav_writer: Option<WavWriter> = None;
+        let mut preview_settings: Option<PreviewSettings> = None;
+        let mut preview: Option<PreviewEncoder> = None;
+        let mut listening_copy_settings: Option<ListeningCopySettings> = None;
+        let mut listening_copy: Option<ListeningCopy> = None;
+        let mut frames_written: usize = 0;
+        let mut chunk_start: usize = 0;
+        let mut clipping = ClipSummary::default();
+        let mut started = SystemTime::now();
+        let mut sum_squares = 0.0;
+        let mut dropouts = DropoutDetector::new(channels, rate);
+        // Downmix of the open file and its number of channels
+        let mut downmix = Downmix::Off;
+        let mut file_downmix = downmix;
+        let mut file_channels = channels;
 
         while let Ok(command) = receiver.recv() {
             match command {
                 RecorderCommand::Start => {
                     let is_recording = *recording.lock().unwrap();
                     if !is_recording {
-                        let file_number = next_file_number.lock().unwrap();
-                        let filename = Self::get_next_filename(&base_filename, *file_number);
+                        let mut file_number = next_file_number.lock().unwrap();
+                        let mut filename = Self::get_next_filename(&filename_template, &base_filename, *file_number);
+                        // Names with a date may only now run into existing files
+                        while Path::new(&filename).exists() {
+                            *file_number += 1;
+                            filename = Self::get_next_filename(&filename_template, &base_filename, *file_number);
+                        }
                         drop(file_number);
+                        // Directories from the template are created below the
+                        // directory of the base name, which must exist
+                        let base_dir = Path::new(&base_filename).parent().unwrap_or(Path::new(""));
+                        let parent = Path::new(&filename).parent().unwrap_or(Path::new(""));
+                        if parent != base_dir && (base_dir.as_os_str().is_empty() || base_dir.is_dir()) {
+                            if let Err(e) = std::fs::create_dir_all(parent) {
+                                report("Failed to create the recording directory", e);
+                                continue;
+                            }
+                        }
 
-                        match WavWriter::new(&filename, rate, channels, format) {
-                            Ok(writer) => {
+                        file_downmix = downmix;
+                        file_channels = downmix.output_channels(channels);
+                        match WavWriter::new_rf64(&filename, rate, file_channels as u16, format) {
+                            Ok(mut writer) => {
+                                Self::describe_recording(&mut writer, &filename, file_downmix.channel_names(channels));
                                 wav_writer = Some(writer);
                                 *current_file.lock().unwrap() = Some(filename.clone());
                                 *recording.lock().unwrap() = true;
-                                *recording_start_time.lock().unwrap() = Some(Instant::now());
-                                println!("\nStarted recording to {}", filename);
-                            }
-                            Err(e) => {
-                                eprintln!("\nFailed to start recording: {}", e);
+                                *max_true_peak_db.lock().unwrap() = None;
+                                frames_written = 0;
+                                chunk_start = 0;
+                                clipping = ClipSummary::default();
+                                started = SystemTime::now();
+                                sum_squares = 0.0;
+                                dropouts = DropoutDetector::new(file_channels, rate);
+                                info!("Started recording to {}", filename);
+
+                                if let Some(settings) = &preview_settings {
+                                    let path = preview_path(&filename, settings.format);
+                                    match PreviewEncoder::new(&path, rate, file_channels, format, settings) {
+                                        Ok(encoder) => preview = Some(encoder),
+                                        Err(e) => warn!("Preview disabled: {}", e),
+                                    }
+                                }
+                                if let Some(settings) = &listening_copy_settings {
+                                    let path = listening_copy_path(&filename);
+                                    match ListeningCopy::new(&path, rate, file_channels, format, settings) {
+                                        Ok(copy) => listening_copy = Some(copy),
+                                        Err(e) => warn!("FLAC copy disabled: {}", e),
+                                    }
+                                }
                             }
+                            Err(e) => report("Failed to start recording", e),
                         }
                     }
                 }
                 RecorderCommand::Write(samples) => {
+                    let samples = file_downmix.apply(samples, channels);
+                    let frames = samples.len() / file_channels.max(1);
+                    writer_stats.lock().unwrap().queued_chunks -= 1;
                     if let Some(ref mut writer) = wav_writer {
-                        if let Err(e) = writer.write_samples(&samples) {
-                            eprintln!("\nError writing audio data: {}", e);
+                        let written = writer.write_samples(&samples);
+                        {
+                            let mut stats = writer_stats.lock().unwrap();
+                            match &written {
+                                Ok(()) => stats.bytes_written += (samples.len() * format.bytes_per_sample()) as u64,
+                                Err(_) => stats.dropped_frames += frames as u64,
+                            }
+                        }
+                        if let Err(e) = written {
+                            report("Failed to write audio data", e);
+                        }
+                        chunk_start = frames_written;
+                        frames_written += frames;
+                        sum_squares += samples.iter().map(|&s| (s as f64) * (s as f64)).sum::<f64>();
+                        for dropout in dropouts.process_samples(&samples) {
+                            warn!("Dropout of {}", dropout.describe());
+                        }
+                    }
+                    if let Some(ref mut encoder) = preview {
+                        encoder.write_samples(&samples);
+                    }
+                    if let Some(ref mut copy) = listening_copy {
+                        copy.write_samples(&samples);
+                    }
+                }
+                RecorderCommand::Overs(overs) => {
+                    // Overs refer to the chunk written just before
+                    if wav_writer.is_some() {
+                        clipping.add(chunk_start as f64 / rate as f64, &overs);
+                    }
+                }
+                RecorderCommand::Mark => {
+                    if let (Some(_), Some(filename)) = (&wav_writer, current_file.lock().unwrap().as_deref()) {
+                        let position = frames_written as f64 / rate as f64;
+                        let line = format!("{:.3}\n", position);
+                        let written = std::fs::OpenOptions::new()
+                            .create(true)
+                            .append(true)
+                            .open(marks_path(filename))
+                            .and_then(|mut file| std::io::Write::write_all(&mut file, line.as_bytes()));
+                        match written {
+                            Ok(()) => info!("Track mark at {:.1}s in {}", position, filename),
+                            Err(e) => report("Failed to write track mark", e),
                         }
                     }
                 }
-                RecorderCommand::Stop => {
+                RecorderCommand::SetPreview(settings) => {
+                    preview_settings = settings;
+                }
+                RecorderCommand::SetListeningCopy(settings) => {
+                    listening_copy_settings = settings;
+                }
+                RecorderCommand::SetDownmix(new_downmix) => {
+                    downmix = new_downmix;
+                }
+                RecorderCommand::Stop { keep } => {
                     if let Some(mut writer) = wav_writer.take() {
                         if let Err(e) = writer.finalize() {
-                            eprintln!("\nError finalizing WAV file: {}", e);
+                            report("Failed to finalize WAV file", e);
                         }
 
                         *recording.lock().unwrap() = false;
 
-                        let duration = recording_start_time
-                            .lock()
-                            .unwrap()
-                            .map(|t| t.elapsed().as_secs_f64())
-                            .unwrap_or(0.0);
+                        let duration = frames_written as f64 / rate as f64;
+                        let sample_count = (frames_written * file_channels) as f64;
+                        let average_level_db = (sum_squares > 0.0).then(|| {
+                            10.0 * (sum_squares / sample_count / format.max_value().powi(2)).log10()
+                        });
 
                         let filename = current_file.lock().unwrap().take().unwrap();
+                        let true_peak = max_true_peak_db.lock().unwrap().take();
+
+                        let preview_file = preview.take().and_then(|mut encoder| {
+                            if encoder.dropped_chunks() > 0 {
+                                warn!(
+                                    "Preview encoder could not keep up, {} chunks missing in {}",
+                                    encoder.dropped_chunks(),
+                                    encoder.path()
+                                );
+                            }
+                            match encoder.finish() {
+                                Ok(()) => Some(encoder.path().to_string()),
+                                Err(e) => {
+                                    error!("Failed to finish preview: {}", e);
+                                    None
+                                }
+                            }
+                        });
+                        let flac_file = listening_copy.take().and_then(|mut copy| match copy.finish() {
+                            Ok(()) => Some(copy.path().to_string()),
+                            Err(e) => {
+                                error!("Failed to finish FLAC copy: {}", e);
+                                None
+                            }
+                        });
 
-                        if duration < min_length {
-                            println!(
-                                "\nRecording too short ({:.1}s < {:.1}s), deleting {}",
+                        if duration < min_length && !keep {
+                            info!(
+                                "Recording too short ({:.1}s < {:.1}s), deleting {}",
                                 duration, min_length, filename
                             );
                             if let Err(e) = std::fs::remove_file(&filename) {
-                                eprintln!("\nError deleting file: {}", e);
+                                error!("Failed to delete {}: {}", filename, e);
+                            }
+                            for file in preview_file.iter().chain(&flac_file) {
+                                let _ = std::fs::remove_file(file);
                             }
+                            let _ = std::fs::remove_file(marks_path(&filename));
                             // Don't increment file number since file was deleted
                         } else {
-                            println!(
-                                "\nStopped recording to {} (duration: {:.1}s)",
-                                filename, duration
-                            );
+                            match true_peak {
+                                Some(tp) => info!(
+                                    "Stopped recording to {} (duration: {:.1}s, true peak: {:.1} dBTP)",
+                                    filename, duration, tp
+                                ),
+                                None => info!(
+                                    "Stopped recording to {} (duration: {:.1}s)",
+                                    filename, duration
+                                ),
+                            }
+                            let clipping = std::mem::take(&mut clipping);
+                            if clipping.total() > 0 {
+                                warn!("{} overs in {}", clipping.total(), filename);
+                                if let Err(e) = std::fs::write(clip_report_path(&filename), clipping.to_text()) {
+                                    error!("Failed to write clipping report: {}", e);
+                                }
+                            }
+                            let dropouts = std::mem::replace(&mut dropouts, DropoutDetector::new(file_channels, rate)).finish();
+                            if !dropouts.is_empty() {
+                                warn!("{} dropouts in {}, consider recording it again", dropouts.len(), filename);
+                                if let Err(e) = std::fs::write(dropout_report_path(&filename), dropout_report(&dropouts)) {
+                                    error!("Failed to write dropout report: {}", e);
+                                }
+                            }
                             // Add to recorded files list
                             recorded_files.lock().unwrap().push(filename.clone());
+                            recording_stats.lock().unwrap().push(RecordingStats {
+                                filename: filename.clone(),
+                                duration,
+                                started,
+                                finished: SystemTime::now(),
+                                average_level_db,
+                                max_true_peak_db: true_peak,
+                                preview_file,
+                                flac_file,
+                                clipping,
+                                dropouts,
+                            });
                             // Increment file number for next recording since this file was kept
                             let mut file_number = next_file_number.lock().unwrap();
                             *file_number += 1;
                         }
-
-                        *recording_start_time.lock().unwrap() = None;
                     }
                 }
             }
         }
     }
 
+    /// Broadcast Wave and iXML provenance of a new recording
+    fn describe_recording(writer: &mut WavWriter, filename: &str, track_names: Vec<String>) {
+        let software = concat!("autorec ", env!("CARGO_PKG_VERSION"));
+        let name = Path::new(filename).file_stem().unwrap_or_default().to_string_lossy().to_string();
+        let (rate, channels, bits) = (writer.sample_rate(), writer.channels(), writer.format().bits_per_sample());
+        writer.set_bext(WavBext {
+            description: name.clone(),
+            originator: software.to_string(),
+            originator_reference: uuid::Uuid::new_v4().simple().to_string(),
+            coding_history: vec![WavBext::pcm_history(rate, bits, channels, software)],
+            ..WavBext::new(chrono::Local::now().naive_local(), rate)
+        });
+        writer.set_ixml(WavIxml {
+            project: Some(name),
+            note: Some(format!("Recorded by {}", software)),
+            track_names,
+        });
+    }
+
     pub fn write_audio(&self, audio_data: &[Vec<i32>], is_on: bool) {
+        {
+            let mut pause_state = self.pause_state.lock().unwrap();
+            match *pause_state {
+                PauseState::Paused => return,
+                // Keep the file open until the signal comes back
+                PauseState::Resumed if !is_on => return,
+                PauseState::Resumed => *pause_state = PauseState::Running,
+                PauseState::Running => {}
+            }
+        }
         if is_on {
             let is_recording = *self.recording.lock().unwrap();
             if !is_recording {
@@ -225,15 +591,131 @@ impl AudioRecorder {
                 }
             }
 
-            let _ = self.sender.send(RecorderCommand::Write(interleaved));
+            self.writer_stats.lock().unwrap().queued_chunks += 1;
+            if self.sender.send(RecorderCommand::Write(interleaved)).is_err() {
+                self.writer_stats.lock().unwrap().queued_chunks -= 1;
+            }
         } else {
             let is_recording = *self.recording.lock().unwrap();
             if is_recording {
-                let _ = self.sender.send(RecorderCommand::Stop);
+                let _ = self.sender.send(RecorderCommand::Stop { keep: false });
             }
         }
     }
 
+    /// Finish the current file now, e.g. because the audio source was lost.
+    ///
+    /// Waits until the file is finalized; the next audio above the threshold
+    /// starts a new file.
+    pub fn stop_recording(&self) {
+        if !self.is_recording() {
+            return;
+        }
+        let _ = self.sender.send(RecorderCommand::Stop { keep: false });
+        for _ in 0..50 {
+            if !self.is_recording() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    /// Close the current file and continue in the next numbered file, e.g.
+    /// to mark a side change by hand
+    ///
+    /// The closed file is kept even if it is shorter than the minimum
+    /// length. Returns false if nothing is being recorded.
+    pub fn split_recording(&self) -> bool {
+        if !self.is_recording() {
+            return false;
+        }
+        let _ = self.sender.send(RecorderCommand::Stop { keep: true });
+        let _ = self.sender.send(RecorderCommand::Start);
+        true
+    }
+
+    /// Stop writing audio without closing the current file, e.g. while the
+    /// record is turned over
+    ///
+    /// Nothing is recorded and no new file is started until [`resume`].
+    ///
+    /// [`resume`]: AudioRecorder::resume
+    pub fn pause(&self) {
+        *self.pause_state.lock().unwrap() = PauseState::Paused;
+    }
+
+    /// Continue recording after [`pause`]
+    ///
+    /// Audio is appended to the current file as soon as the signal is back;
+    /// the silence until then does not end the recording.
+    ///
+    /// [`pause`]: AudioRecorder::pause
+    pub fn resume(&self) {
+        let mut pause_state = self.pause_state.lock().unwrap();
+        if *pause_state == PauseState::Paused {
+            *pause_state = PauseState::Resumed;
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.pause_state.lock().unwrap() == PauseState::Paused
+    }
+
+    /// Mark the start of a new track at the current position
+    ///
+    /// Marks are appended to the recording's `.marks` file, which
+    /// `cue_creator` uses instead of searching for boundaries. Returns false
+    /// if nothing is being recorded.
+    pub fn add_mark(&self) -> bool {
+        if !self.is_recording() {
+            return false;
+        }
+        let _ = self.sender.send(RecorderCommand::Mark);
+        true
+    }
+
+    /// Write a low-bitrate preview next to each recorded file (None disables it)
+    ///
+    /// Takes effect from the next recording on.
+    pub fn set_preview(&self, settings: Option<PreviewSettings>) {
+        let _ = self.sender.send(RecorderCommand::SetPreview(settings));
+    }
+
+    /// Write a FLAC listening copy next to each recorded file (None disables it)
+    ///
+    /// Takes effect from the next recording on.
+    pub fn set_listening_copy(&self, settings: Option<ListeningCopySettings>) {
+        let _ = self.sender.send(RecorderCommand::SetListeningCopy(settings));
+    }
+
+    /// Downmix the channels of each recorded file, see [`Downmix`]
+    ///
+    /// Takes effect from the next recording on.
+    pub fn set_downmix(&self, downmix: Downmix) {
+        let _ = self.sender.send(RecorderCommand::SetDownmix(downmix));
+    }
+
+    /// Report the true peak (dBTP) of the audio just written
+    ///
+    /// The maximum over the current recording is included in its statistics.
+    pub fn note_true_peak(&self, true_peak_db: f64) {
+        if !self.is_recording() {
+            return;
+        }
+        let mut max = self.max_true_peak_db.lock().unwrap();
+        *max = Some(max.map_or(true_peak_db, |m| m.max(true_peak_db)));
+    }
+
+    /// Report the overs per channel of the audio just written
+    ///
+    /// Overs are listed with their position in the recording's clipping
+    /// report, which `cue_creator` adds to the info file.
+    pub fn note_overs(&self, overs: &[usize]) {
+        if overs.iter().any(|&n| n > 0) {
+            let _ = self.sender.send(RecorderCommand::Overs(overs.to_vec()));
+        }
+    }
+
     pub fn is_recording(&self) -> bool {
         *self.recording.lock().unwrap()
     }
@@ -246,10 +728,27 @@ impl AudioRecorder {
         self.recorded_files.lock().unwrap().clone()
     }
 
+    pub fn get_recording_stats(&self) -> Vec<RecordingStats> {
+        self.recording_stats.lock().unwrap().clone()
+    }
+
+    /// Write queue and byte counters of the recording thread
+    pub fn writer_stats(&self) -> WriterStats {
+        *self.writer_stats.lock().unwrap()
+    }
+
+    /// Take the last error of the recording thread, if any
+    ///
+    /// The recorder keeps running after an error (e.g. a full disk), so
+    /// callers should poll this to notice failed writes.
+    pub fn take_error(&self) -> Option<Error> {
+        self.last_error.lock().unwrap().take()
+    }
+
     pub fn close(&mut self) {
         let is_recording = *self.recording.lock().unwrap();
         if is_recording {
-            let _ = self.sender.send(RecorderCommand::Stop);
+            let _ = self.sender.send(RecorderCommand::Stop { keep: false });
             // Give thread time to process stop command
             thread::sleep(Duration::from_millis(100));
         }
@@ -269,112 +768,62 @@ impl Drop for AudioRecorder {
     }
 }
 
-// Simple WAV file writer
-struct WavWriter {
-    file: File,
-    data_size: usize,
-    rate: u32,
-    channels: usize,
-    format: SampleFormat,
-}
-
-impl WavWriter {
-    fn new(filename: &str, rate: u32, channels: usize, format: SampleFormat) -> io::Result<Self> {
-        let mut file = File::create(filename)?;
-
-        // Write WAV header (will be updated in finalize)
-        let bits_per_sample = (format.bytes_per_sample() * 8) as u16;
-        Self::write_wav_header(&mut file, 0, rate, channels as u16, bits_per_sample)?;
-
-        Ok(WavWriter {
-            file,
-            data_size: 0,
-            rate,
-            channels,
-            format,
-        })
-    }
-
-    fn write_wav_header(
-        file: &mut File,
-        data_size: usize,
-        rate: u32,
-        channels: u16,
-        bits_per_sample: u16,
-    ) -> io::Result<()> {
-        let byte_rate = rate * channels as u32 * (bits_per_sample / 8) as u32;
-        let block_align = channels * (bits_per_sample / 8);
-
-        file.write_all(b"RIFF")?;
-        file.write_all(&((data_size + 36) as u32).to_le_bytes())?;
-        file.write_all(b"WAVE")?;
-        file.write_all(b"fmt ")?;
-        file.write_all(&16u32.to_le_bytes())?; // fmt chunk size
-        file.write_all(&1u16.to_le_bytes())?; // audio format (1 = PCM)
-        file.write_all(&channels.to_le_bytes())?;
-        file.write_all(&rate.to_le_bytes())?;
-        file.write_all(&byte_rate.to_le_bytes())?;
-        file.write_all(&block_align.to_le_bytes())?;
-        file.write_all(&bits_per_sample.to_le_bytes())?;
-        file.write_all(b"data")?;
-        file.write_all(&(data_size as u32).to_le_bytes())?;
-
-        Ok(())
-    }
-
-    fn write_samples(&mut self, samples: &[i32]) -> io::Result<()> {
-        match self.format {
-            SampleFormat::S16 => {
-                for &sample in samples {
-                    let s16 = (sample as i16).to_le_bytes();
-                    self.file.write_all(&s16)?;
-                    self.data_size += 2;
-                }
-            }
-            SampleFormat::S32 => {
-                for &sample in samples {
-                    let s32 = sample.to_le_bytes();
-                    self.file.write_all(&s32)?;
-                    self.data_size += 4;
-                }
-            }
-        }
-        Ok(())
-    }
-
-    fn finalize(&mut self) -> io::Result<()> {
-        use std::io::Seek;
-
-        // Update header with correct data size
-        self.file.seek(io::SeekFrom::Start(0))?;
-        let bits_per_sample = (self.format.bytes_per_sample() * 8) as u16;
-        Self::write_wav_header(
-            &mut self.file,
-            self.data_size,
-            self.rate,
-            self.channels as u16,
-            bits_per_sample,
-        )?;
-        self.file.flush()?;
-        Ok(())
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::fs;
+    use std::time::Instant;
+
+    /// Poll `condition` until the recording thread has caught up, for at
+    /// most five seconds
+    fn wait_until(mut condition: impl FnMut() -> bool) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !condition() {
+            if Instant::now() > deadline {
+                return false;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        true
+    }
 
     #[test]
     fn test_get_next_filename() {
-        let filename = AudioRecorder::get_next_filename("test", 1);
+        let template = FilenameTemplate::recording();
+        let filename = AudioRecorder::get_next_filename(&template, "test", 1);
         assert_eq!(filename, "test.1.wav");
 
-        let filename = AudioRecorder::get_next_filename("test.wav", 5);
+        let filename = AudioRecorder::get_next_filename(&template, "test.wav", 5);
         assert_eq!(filename, "test.5.wav");
 
-        let filename = AudioRecorder::get_next_filename("path/to/recording", 10);
+        let filename = AudioRecorder::get_next_filename(&template, "path/to/recording", 10);
         assert_eq!(filename, "path/to/recording.10.wav");
+
+        let template = FilenameTemplate::parse("{base}/{date:%Y}/side {side_letter}").unwrap();
+        let filename = AudioRecorder::get_next_filename(&template, "/rec/vinyl", 2);
+        let year = chrono::Local::now().format("%Y").to_string();
+        assert_eq!(filename, format!("/rec/vinyl/{}/side B.wav", year));
+        // Past Z the number is appended
+        let filename = AudioRecorder::get_next_filename(&template, "/rec/vinyl", 27);
+        assert_eq!(filename, format!("/rec/vinyl/{}/side.27.wav", year));
+
+        // A template without a side gets the file number from the second file on
+        let template = FilenameTemplate::parse("{base} {date:%Y}").unwrap();
+        assert_eq!(AudioRecorder::get_next_filename(&template, "vinyl", 1), format!("vinyl {}.wav", year));
+        assert_eq!(AudioRecorder::get_next_filename(&template, "vinyl", 2), format!("vinyl {}.2.wav", year));
+    }
+
+    #[test]
+    fn test_file_numbering_without_side() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("vinyl").to_string_lossy().to_string();
+        std::fs::write(format!("{}.wav", base), [0; 64]).unwrap();
+        std::fs::write(format!("{}.2.wav", base), [0; 64]).unwrap();
+        let template = FilenameTemplate::parse("{base}").unwrap();
+        let mut recorder = AudioRecorder::with_filename_template(base.clone(), template, 100, 2, SampleFormat::S16, 0.0);
+        recorder.write_audio(&[vec![1000; 100], vec![1000; 100]], true);
+        assert!(wait_until(|| recorder.current_filename() == Some(format!("{}.3.wav", base))));
+        recorder.close();
     }
 
     #[test]
@@ -422,6 +871,11 @@ mod tests {
         // Should be recording now
         assert!(recorder.is_recording());
 
+        recorder.note_true_peak(-6.0);
+        recorder.note_true_peak(-1.5);
+        recorder.note_true_peak(-3.0);
+        assert_eq!(*recorder.max_true_peak_db.lock().unwrap(), Some(-1.5));
+
         // Stop and cleanup
         recorder.write_audio(&audio_data, false);
         std::thread::sleep(Duration::from_millis(100));
@@ -432,71 +886,214 @@ mod tests {
     }
 
     #[test]
-    fn test_wav_header_generation() {
-        let temp_dir = std::env::temp_dir();
-        let test_file = temp_dir.join("test_wav_header.wav");
-        let test_file_str = test_file.to_str().unwrap();
+    fn test_clip_summary() {
+        let mut summary = ClipSummary::default();
+        assert_eq!(summary.total(), 0);
+
+        summary.add(1.5, &[2, 0]);
+        summary.add(3.0, &[0, 1]);
+        assert_eq!(summary.total(), 3);
+        assert_eq!(summary.overs_per_channel, vec![2, 1]);
+        assert_eq!(summary.positions, vec![(1.5, 0), (3.0, 1)]);
+
+        let text = summary.to_text();
+        assert!(text.contains("Total overs: 3"));
+        assert!(text.contains("1.50s (channel 0)"));
+    }
 
-        {
-            let mut writer =
-                WavWriter::new(test_file_str, 48000, 2, SampleFormat::S16).unwrap();
+    #[test]
+    fn test_overs_are_reported() {
+        let test_file = std::env::temp_dir().join("test_recording_overs");
+        let test_file_str = test_file.to_str().unwrap().to_string();
+        let wav_file = format!("{}.1.wav", test_file_str);
+        let report_file = clip_report_path(&wav_file);
+        let _ = fs::remove_file(&wav_file);
 
-            // Write some samples
-            let samples = vec![1000i32, -1000, 2000, -2000];
-            writer.write_samples(&samples).unwrap();
-            writer.finalize().unwrap();
-        }
+        let mut recorder = AudioRecorder::new(test_file_str.clone(), 100, 2, SampleFormat::S16, 0.0);
+        let audio_data = vec![vec![1000; 100], vec![1000; 100]];
+        recorder.write_audio(&audio_data, true);
+        assert!(wait_until(|| recorder.is_recording()));
+        recorder.write_audio(&audio_data, true);
+        recorder.note_overs(&[0, 2]);
+        recorder.write_audio(&audio_data, false);
+        assert!(wait_until(|| recorder.get_recording_stats().len() == 1));
+        recorder.close();
 
-        // Read file and verify it exists and has content
-        let metadata = fs::metadata(test_file_str).unwrap();
-        assert!(metadata.len() > 44); // Should have header + data
+        let stats = recorder.get_recording_stats();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].clipping.overs_per_channel, vec![0, 2]);
+        assert_eq!(stats[0].clipping.positions, vec![(1.0, 1)]);
+        let report = fs::read_to_string(&report_file).unwrap();
+        assert!(report.contains("Total overs: 2"));
 
-        // Cleanup
-        fs::remove_file(test_file_str).ok();
+        let _ = fs::remove_file(&wav_file);
+        let _ = fs::remove_file(&report_file);
     }
 
     #[test]
-    fn test_wav_writer_s16() {
-        let temp_dir = std::env::temp_dir();
-        let test_file = temp_dir.join("test_s16.wav");
-        let test_file_str = test_file.to_str().unwrap();
+    fn test_dropouts_are_reported() {
+        let test_file = std::env::temp_dir().join("test_recording_dropouts");
+        let test_file_str = test_file.to_str().unwrap().to_string();
+        let wav_file = format!("{}.1.wav", test_file_str);
+        let report_file = dropout_report_path(&wav_file);
+        let _ = fs::remove_file(&wav_file);
+
+        let mut recorder = AudioRecorder::new(test_file_str.clone(), 1000, 2, SampleFormat::S16, 0.0);
+        let music: Vec<i32> = (0..500).map(|i| 1000 + (i % 7) * 100).collect();
+        // 500 ms of music, then the input stalls for 50 ms
+        let mut stalled = music.clone();
+        stalled[200..250].fill(0);
+        recorder.write_audio(&[music.clone(), music.clone()], true);
+        assert!(wait_until(|| recorder.is_recording()));
+        recorder.write_audio(&[stalled.clone(), stalled], true);
+        recorder.write_audio(&[music.clone(), music], false);
+        assert!(wait_until(|| recorder.get_recording_stats().len() == 1));
+        recorder.close();
 
-        {
-            let mut writer =
-                WavWriter::new(test_file_str, 44100, 1, SampleFormat::S16).unwrap();
+        let stats = recorder.get_recording_stats();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].dropouts.len(), 1);
+        assert!((stats[0].dropouts[0].start_seconds - 0.7).abs() < 1e-9);
+        assert!((stats[0].dropouts[0].duration_seconds - 0.05).abs() < 1e-9);
+        let report = fs::read_to_string(&report_file).unwrap();
+        assert!(report.contains("Dropouts: 1\n  50.0 ms at 0:00.70"), "{}", report);
 
-            let samples = vec![0, 1000, -1000, 16000, -16000];
-            writer.write_samples(&samples).unwrap();
-            writer.finalize().unwrap();
-        }
+        let _ = fs::remove_file(&wav_file);
+        let _ = fs::remove_file(&report_file);
+    }
+
+    #[test]
+    fn test_stop_recording_starts_new_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("side").to_string_lossy().to_string();
+        let mut recorder = AudioRecorder::new(base.clone(), 100, 2, SampleFormat::S16, 0.0);
+        let audio_data = vec![vec![1000; 100], vec![1000; 100]];
 
-        let metadata = fs::metadata(test_file_str).unwrap();
-        // Header (44 bytes) + 5 samples * 2 bytes = 54 bytes
-        assert_eq!(metadata.len(), 54);
+        // Source lost in the middle of a recording
+        recorder.write_audio(&audio_data, true);
+        assert!(wait_until(|| recorder.is_recording()));
+        recorder.stop_recording();
+        assert!(!recorder.is_recording());
+        assert_eq!(recorder.get_recorded_files(), vec![format!("{}.1.wav", base)]);
 
-        fs::remove_file(test_file_str).ok();
+        // Audio after the reconnect goes to the next file
+        recorder.write_audio(&audio_data, true);
+        assert!(wait_until(|| recorder.current_filename() == Some(format!("{}.2.wav", base))));
+        recorder.close();
     }
 
     #[test]
-    fn test_wav_writer_s32() {
-        let temp_dir = std::env::temp_dir();
-        let test_file = temp_dir.join("test_s32.wav");
-        let test_file_str = test_file.to_str().unwrap();
+    fn test_downmix() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("mono").to_string_lossy().to_string();
+        let mut recorder = AudioRecorder::new(base.clone(), 100, 2, SampleFormat::S16, 0.0);
+        recorder.set_downmix(Downmix::Mono);
+        recorder.write_audio(&[vec![1000; 100], vec![3000; 100]], true);
+        assert!(wait_until(|| recorder.is_recording()));
+        recorder.write_audio(&[vec![0; 10], vec![0; 10]], false);
+        assert!(wait_until(|| !recorder.get_recorded_files().is_empty()));
+        recorder.close();
 
-        {
-            let mut writer =
-                WavWriter::new(test_file_str, 96000, 2, SampleFormat::S32).unwrap();
+        let wav_file = format!("{}.1.wav", base);
+        let (header, audio) = crate::wavfile::read_wav_segment(&wav_file, 0.0, 10.0).unwrap();
+        assert_eq!(header.num_channels, 1);
+        assert_eq!(audio[0], vec![2000; 100]);
+        assert_eq!(crate::wavfile::read_channel_names(&wav_file).unwrap(), vec!["Mono"]);
+    }
 
-            let samples = vec![0, 100000, -100000, 1000000, -1000000];
-            writer.write_samples(&samples).unwrap();
-            writer.finalize().unwrap();
-        }
+    #[test]
+    fn test_split_recording() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("side").to_string_lossy().to_string();
+        let mut recorder = AudioRecorder::new(base.clone(), 100, 2, SampleFormat::S16, 600.0);
+        let audio_data = vec![vec![1000; 100], vec![1000; 100]];
+
+        assert!(!recorder.split_recording());
+        recorder.write_audio(&audio_data, true);
+        assert!(wait_until(|| recorder.is_recording()));
+        assert!(recorder.split_recording());
+        recorder.write_audio(&audio_data, true);
+        assert!(wait_until(|| recorder.current_filename() == Some(format!("{}.2.wav", base))));
 
-        let metadata = fs::metadata(test_file_str).unwrap();
-        // Header (44 bytes) + 5 samples * 4 bytes = 64 bytes
-        assert_eq!(metadata.len(), 64);
+        // The first part is kept although it is shorter than the minimum length
+        assert_eq!(recorder.get_recorded_files(), vec![format!("{}.1.wav", base)]);
+        assert_eq!(recorder.current_filename(), Some(format!("{}.2.wav", base)));
+        recorder.close();
+    }
+
+    #[test]
+    fn test_add_mark() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("side").to_string_lossy().to_string();
+        let mut recorder = AudioRecorder::new(base.clone(), 100, 2, SampleFormat::S16, 600.0);
+        let audio_data = vec![vec![1000; 100], vec![1000; 100]];
+
+        assert!(!recorder.add_mark());
+        recorder.write_audio(&audio_data, true);
+        recorder.write_audio(&audio_data, true);
+        assert!(wait_until(|| recorder.is_recording()));
+        assert!(recorder.add_mark());
+
+        // The mark is at the position after both seconds of audio
+        let marks_file = format!("{}.1.marks", base);
+        assert!(wait_until(|| Path::new(&marks_file).exists()));
+        assert_eq!(fs::read_to_string(&marks_file).unwrap(), "2.000\n");
+        recorder.close();
+    }
+
+    #[test]
+    fn test_pause_and_resume() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("album").to_string_lossy().to_string();
+        let mut recorder = AudioRecorder::new(base.clone(), 100, 2, SampleFormat::S16, 0.0);
+        let music = vec![vec![1000; 100], vec![1000; 100]];
+        let silence = vec![vec![0; 100], vec![0; 100]];
+
+        recorder.write_audio(&music, true);
+        recorder.pause();
+        assert!(recorder.is_paused());
+        // Turning the record over: neither written nor ending the file
+        recorder.write_audio(&silence, false);
+        recorder.resume();
+        assert!(!recorder.is_paused());
+        recorder.write_audio(&silence, false);
+        recorder.write_audio(&music, true);
+        assert!(wait_until(|| recorder.is_recording()));
+
+        recorder.write_audio(&silence, false);
+        assert!(wait_until(|| recorder.get_recording_stats().len() == 1));
+        recorder.close();
+        let stats = recorder.get_recording_stats();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].filename, format!("{}.1.wav", base));
+        assert_eq!(stats[0].duration, 2.0);
+        // Constant 1000 of a full scale of 32768
+        let level = stats[0].average_level_db.unwrap();
+        assert!((level - 20.0 * (1000.0f64 / 32768.0).log10()).abs() < 0.01, "{}", level);
+    }
 
-        fs::remove_file(test_file_str).ok();
+    #[test]
+    fn test_write_error_is_reported() {
+        let test_file = std::env::temp_dir().join("autorec_missing_dir").join("recording");
+        let mut recorder = AudioRecorder::new(
+            test_file.to_str().unwrap().to_string(),
+            48000,
+            2,
+            SampleFormat::S32,
+            1.0,
+        );
+
+        recorder.write_audio(&[vec![1000; 100], vec![1000; 100]], true);
+
+        let mut error = None;
+        assert!(wait_until(|| {
+            error = recorder.take_error();
+            error.is_some()
+        }));
+        assert!(!recorder.is_recording());
+        assert!(matches!(error, Some(Error::Io(_))));
+        assert!(recorder.take_error().is_none());
+        recorder.close();
     }
 
     #[test]
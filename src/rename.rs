@@ -0,0 +1,182 @@
+//! Renaming a recording together with the files that belong to it.
+//!
+//! A recording is accompanied by CUE sheets, reports, a `.meta.toml` and an
+//! entry in the session manifest. [`RenamePlan::new`] collects the files to
+//! move and refuses if any of the new names is taken, so nothing is moved
+//! unless everything can be. [`RenamePlan::execute`] moves the files, points
+//! the `FILE` line of the CUE sheets and the session manifest at the new WAV
+//! file, and undoes the steps already done if a later one fails.
+
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::cuefile::{self, CueText};
+use crate::error::{Error, Result};
+use crate::session::{self, Session};
+
+/// Suffixes of the files that are renamed with a recording, the WAV file first
+pub const RECORDING_SUFFIXES: &[&str] = &[
+    ".wav", ".cue", ".guess.cue", ".cue.txt", ".guess.cue.txt", ".identify.txt", ".overs.txt",
+    ".dropouts.txt", ".marks", ".boundaries", ".meta.toml", ".declicked.wav", ".speed.wav", ".flac", ".mp3", ".opus",
+    ".spectrogram.png", ".report.html",
+];
+
+/// The moves needed to rename a recording
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenamePlan {
+    pub old_wav: PathBuf,
+    pub new_wav: PathBuf,
+    /// Existing files and their new paths
+    pub moves: Vec<(PathBuf, PathBuf)>,
+}
+
+enum Undo {
+    Move(PathBuf, PathBuf),
+    Restore(PathBuf, Vec<u8>),
+}
+
+fn with_suffix(base: &Path, suffix: &str) -> PathBuf {
+    let mut path = OsString::from(base.as_os_str());
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+/// Write `content` to a temporary file next to `path` that then replaces it
+fn write_replacing(path: &Path, content: &[u8]) -> Result<()> {
+    let dir = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let temp = tempfile::NamedTempFile::new_in(dir)?;
+    fs::write(temp.path(), content)?;
+    temp.persist(path).map_err(|e| Error::Io(e.error))?;
+    Ok(())
+}
+
+/// `content` of a CUE sheet with its `FILE` line naming `wav_name`
+fn set_cue_file(content: &str, wav_name: &str) -> String {
+    let mut updated: Vec<String> = content
+        .lines()
+        .map(|line| {
+            if line.starts_with("FILE \"") && line.ends_with("\" WAVE") {
+                format!("FILE \"{}\" WAVE", cuefile::cue_string(wav_name))
+            } else {
+                line.to_string()
+            }
+        })
+        .collect();
+    if content.ends_with('\n') {
+        updated.push(String::new());
+    }
+    updated.join("\n")
+}
+
+impl RenamePlan {
+    /// Plan renaming `wav_file` and its files to `new_base` (the new path
+    /// without `.wav`). Fails if one of the new paths exists.
+    pub fn new(wav_file: &str, new_base: &Path) -> Result<Self> {
+        let old_base = cuefile::wav_base_path(wav_file);
+        let mut moves = Vec::new();
+        for suffix in RECORDING_SUFFIXES {
+            let from = with_suffix(&old_base, suffix);
+            if !from.exists() {
+                continue;
+            }
+            let to = with_suffix(new_base, suffix);
+            if to.exists() {
+                return Err(Error::Io(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{} already exists", to.display()),
+                )));
+            }
+            moves.push((from, to));
+        }
+        Ok(RenamePlan {
+            old_wav: PathBuf::from(wav_file),
+            new_wav: with_suffix(new_base, ".wav"),
+            moves,
+        })
+    }
+
+    /// Whether the recording already has the new name
+    pub fn is_noop(&self) -> bool {
+        self.old_wav == self.new_wav
+    }
+
+    /// Move the files and update the CUE sheets and the session manifest;
+    /// on failure the files are left as they were
+    pub fn execute(&self) -> Result<()> {
+        if self.is_noop() {
+            return Ok(());
+        }
+        let mut undo = Vec::new();
+        let result = self.apply(&mut undo);
+        if result.is_err() {
+            for step in undo.into_iter().rev() {
+                let _ = match step {
+                    Undo::Move(from, to) => fs::rename(to, from).map_err(Error::from),
+                    Undo::Restore(path, content) => write_replacing(&path, &content),
+                };
+            }
+        }
+        result
+    }
+
+    fn apply(&self, undo: &mut Vec<Undo>) -> Result<()> {
+        for (from, to) in &self.moves {
+            if let Some(dir) = to.parent().filter(|p| !p.as_os_str().is_empty()) {
+                fs::create_dir_all(dir)?;
+            }
+            fs::rename(from, to)?;
+            undo.push(Undo::Move(from.clone(), to.clone()));
+        }
+
+        let wav_name = self.new_wav.file_name().unwrap_or_default().to_string_lossy().to_string();
+        for (_, cue) in self.moves.iter().filter(|(_, to)| to.extension().is_some_and(|e| e == "cue")) {
+            let original = fs::read(cue)?;
+            // Written back in the character set it was read in
+            let (content, encoding) = cuefile::decode_cue(original.clone());
+            let text = CueText { encoding, transliterate: false };
+            write_replacing(cue, &text.encode(&set_cue_file(&content, &wav_name)))?;
+            undo.push(Undo::Restore(cue.clone(), original));
+        }
+
+        let old_wav = self.old_wav.to_string_lossy();
+        let manifest = session::session_path(&old_wav);
+        if self.moves.iter().any(|(from, _)| *from == self.old_wav) && manifest.exists() {
+            let original = fs::read(&manifest)?;
+            let mut session = Session::load(&manifest)?;
+            if session.rename_file(&old_wav, &self.new_wav.to_string_lossy()) {
+                session.save(&manifest)?;
+                undo.push(Undo::Restore(manifest, original));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rename_plan() {
+        let dir = tempfile::tempdir().unwrap();
+        let wav = dir.path().join("recording.3.wav");
+        fs::write(&wav, b"RIFF").unwrap();
+        fs::write(dir.path().join("recording.3.cue"), "TITLE \"Album\"\nFILE \"recording.3.wav\" WAVE\n").unwrap();
+        fs::write(dir.path().join("recording.3.identify.txt"), "").unwrap();
+        let wav = wav.to_string_lossy().to_string();
+
+        // A taken name stops the whole rename
+        fs::write(dir.path().join("Artist - Album (Side B).identify.txt"), "").unwrap();
+        assert!(RenamePlan::new(&wav, &dir.path().join("Artist - Album (Side B)")).is_err());
+
+        let plan = RenamePlan::new(&wav, &dir.path().join("Artist - Album (Side C)")).unwrap();
+        assert_eq!(plan.moves.len(), 3);
+        plan.execute().unwrap();
+        assert!(!Path::new(&wav).exists());
+        assert!(dir.path().join("Artist - Album (Side C).wav").exists());
+        let cue = fs::read_to_string(dir.path().join("Artist - Album (Side C).cue")).unwrap();
+        assert_eq!(cue, "TITLE \"Album\"\nFILE \"Artist - Album (Side C).wav\" WAVE\n");
+    }
+}
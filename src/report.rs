@@ -0,0 +1,371 @@
+//! HTML analysis reports.
+//!
+//! `cue_creator --report` writes `<recording>.report.html` next to each
+//! recording, and `report.html` into the directory when a whole directory is
+//! processed. A report shows the levels of the recording, its RMS curve with
+//! the detected boundaries, the boundary scores, the identified songs and
+//! tracks and links to the release on MusicBrainz and Discogs. The pages have
+//! no external resources, so they can be copied and opened anywhere.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::album_identifier::IdentifiedSong;
+use crate::audio_analysis::{format_dc_offset, QualityReport};
+use crate::boundary::BoundaryAnalysis;
+use crate::cuefile::{wav_base_path, Valley};
+use crate::error::Result;
+use crate::loudness::{Loudness, LoudnessAnalysis};
+use crate::tagging::ReleaseTags;
+
+/// Name of the report of a processed directory
+pub const SESSION_REPORT_NAME: &str = "report.html";
+
+/// Points of the RMS plot
+const PLOT_WIDTH: usize = 1000;
+const PLOT_HEIGHT: usize = 200;
+
+const STYLE: &str = "body{font-family:sans-serif;margin:2em;color:#222;background:#fafafa}\
+section{background:#fff;border:1px solid #ddd;border-radius:6px;padding:1em 1.5em;margin-bottom:2em}\
+table{border-collapse:collapse;margin:0.5em 0 1em}\
+th,td{padding:0.2em 0.8em;border-bottom:1px solid #eee;text-align:left}\
+td.n{text-align:right;font-variant-numeric:tabular-nums}\
+.warning{color:#b00}\
+.file{color:#777;font-size:90%}\
+svg{width:100%;height:auto;background:#111;border-radius:4px}";
+
+/// Where a recording's report is written: `<name>.report.html`
+pub fn report_path(wav_file: &str) -> PathBuf {
+    PathBuf::from(format!("{}.report.html", wav_base_path(wav_file).display()))
+}
+
+fn format_timestamp(seconds: f64) -> String {
+    let mins = (seconds / 60.0) as u32;
+    let secs = seconds % 60.0;
+    format!("{:02}:{:05.2}", mins, secs)
+}
+
+/// Text with the HTML special characters escaped
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// One track between two boundaries
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReportTrack {
+    pub title: Option<String>,
+    pub start: f64,
+    pub end: f64,
+    pub loudness: Option<Loudness>,
+}
+
+/// Everything the report of one recording shows
+#[derive(Debug, Clone)]
+pub struct FileReport {
+    pub wav_file: String,
+    pub artist: String,
+    pub album: String,
+    /// Description of the matched release
+    pub release_info: Option<String>,
+    pub release_tags: ReleaseTags,
+    pub duration: f64,
+    /// Groove-in and groove-out in seconds
+    pub music: (f64, f64),
+    pub noise_floor_db: f32,
+    pub music_level_db: f32,
+    pub chunk_duration: f64,
+    pub rms_values: Vec<f32>,
+    pub smoothed: Vec<f32>,
+    pub boundaries: Vec<Valley>,
+    pub tracks: Vec<ReportTrack>,
+    pub songs: Vec<IdentifiedSong>,
+    pub quality: Option<QualityReport>,
+    pub loudness: Option<Loudness>,
+    pub warnings: Vec<String>,
+}
+
+impl FileReport {
+    /// Report of the boundary analysis; the tracks are the parts of the
+    /// music range between the `boundaries`, named after `track_names`
+    pub fn new(wav_file: &str, analysis: &BoundaryAnalysis, music: (f64, f64), boundaries: &[Valley], track_names: &[String]) -> Self {
+        let mut starts = vec![music.0];
+        starts.extend(boundaries.iter().map(|v| v.position_seconds));
+        let mut ends: Vec<f64> = starts[1..].to_vec();
+        ends.push(music.1);
+        let tracks = starts
+            .iter()
+            .zip(ends)
+            .enumerate()
+            .map(|(i, (&start, end))| ReportTrack { title: track_names.get(i).cloned(), start, end, loudness: None })
+            .collect();
+        FileReport {
+            wav_file: wav_file.to_string(),
+            artist: String::new(),
+            album: String::new(),
+            release_info: None,
+            release_tags: ReleaseTags::default(),
+            duration: analysis.file_duration,
+            music,
+            noise_floor_db: analysis.noise_floor_db,
+            music_level_db: analysis.music_level_db,
+            chunk_duration: analysis.chunk_duration,
+            rms_values: analysis.rms_values.clone(),
+            smoothed: analysis.smoothed.clone(),
+            boundaries: boundaries.to_vec(),
+            tracks,
+            songs: Vec::new(),
+            quality: None,
+            loudness: None,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Take the loudness of the recording and of each track from `analysis`
+    pub fn set_loudness(&mut self, analysis: &LoudnessAnalysis) {
+        self.loudness = Some(analysis.whole());
+        for track in &mut self.tracks {
+            track.loudness = Some(analysis.segment(track.start, Some(track.end)));
+        }
+    }
+
+    fn title(&self) -> String {
+        match (self.artist.is_empty(), self.album.is_empty()) {
+            (false, false) => format!("{} - {}", self.artist, self.album),
+            _ => Path::new(&self.wav_file).file_name().and_then(|n| n.to_str()).unwrap_or(&self.wav_file).to_string(),
+        }
+    }
+
+    /// The RMS curve as an SVG: raw levels in grey, the smoothed curve in
+    /// white, the noise floor dashed, groove-in/out green and boundaries red
+    fn plot(&self) -> String {
+        let duration = self.duration.max(self.chunk_duration).max(f64::MIN_POSITIVE);
+        let top = self.rms_values.iter().copied().fold(self.music_level_db, f32::max) + 3.0;
+        let bottom = self.noise_floor_db - 10.0;
+        let x_of = |seconds: f64| seconds / duration * PLOT_WIDTH as f64;
+        let y_of = |db: f32| {
+            let fraction = ((db - bottom) / (top - bottom).max(1.0)).clamp(0.0, 1.0);
+            (1.0 - fraction as f64) * PLOT_HEIGHT as f64
+        };
+
+        // One point per plot column
+        let columns = self.rms_values.len().clamp(1, PLOT_WIDTH);
+        let per_column = self.rms_values.len() as f64 / columns as f64;
+        let points = |values: &[f32], combine: fn(&[f32]) -> f32| -> String {
+            (0..columns)
+                .filter_map(|c| {
+                    let range = (c as f64 * per_column) as usize..(((c + 1) as f64 * per_column) as usize).min(values.len());
+                    let slice = values.get(range.clone()).filter(|s| !s.is_empty())?;
+                    let seconds = (range.start as f64 + slice.len() as f64 / 2.0) * self.chunk_duration;
+                    Some(format!("{:.1},{:.1}", x_of(seconds), y_of(combine(slice))))
+                })
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let max = |s: &[f32]| s.iter().copied().fold(f32::MIN, f32::max);
+        let mean = |s: &[f32]| s.iter().sum::<f32>() / s.len() as f32;
+
+        let mut svg = format!(
+            "<svg viewBox=\"0 0 {} {}\" xmlns=\"http://www.w3.org/2000/svg\">\n",
+            PLOT_WIDTH, PLOT_HEIGHT
+        );
+        svg.push_str(&format!(
+            "<rect x=\"{:.1}\" y=\"0\" width=\"{:.1}\" height=\"{}\" fill=\"#1c2a1c\"/>\n",
+            x_of(self.music.0),
+            x_of(self.music.1 - self.music.0),
+            PLOT_HEIGHT
+        ));
+        let floor = y_of(self.noise_floor_db);
+        svg.push_str(&format!(
+            "<line x1=\"0\" y1=\"{:.1}\" x2=\"{}\" y2=\"{:.1}\" stroke=\"#3050a0\" stroke-dasharray=\"4 4\"/>\n",
+            floor, PLOT_WIDTH, floor
+        ));
+        svg.push_str(&format!("<polyline points=\"{}\" fill=\"none\" stroke=\"#666\"/>\n", points(&self.rms_values, max)));
+        svg.push_str(&format!("<polyline points=\"{}\" fill=\"none\" stroke=\"#fff\"/>\n", points(&self.smoothed, mean)));
+        let line = |seconds: f64, colour: &str, title: String| {
+            let x = x_of(seconds);
+            format!(
+                "<line x1=\"{:.1}\" y1=\"0\" x2=\"{:.1}\" y2=\"{}\" stroke=\"{}\"><title>{}</title></line>\n",
+                x, x, PLOT_HEIGHT, colour, title
+            )
+        };
+        svg.push_str(&line(self.music.0, "#0c0", format!("Groove-in {}", format_timestamp(self.music.0))));
+        svg.push_str(&line(self.music.1, "#0c0", format!("Groove-out {}", format_timestamp(self.music.1))));
+        for (i, valley) in self.boundaries.iter().enumerate() {
+            let title = format!("Boundary {} at {}", i + 1, format_timestamp(valley.position_seconds));
+            svg.push_str(&line(valley.position_seconds, "#f44", title));
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    /// The report as a `<section>` of a page
+    pub fn to_html(&self) -> String {
+        let mut html = String::from("<section>\n");
+        html.push_str(&format!("<h2>{}</h2>\n", escape_html(&self.title())));
+        html.push_str(&format!("<p class=\"file\">{}</p>\n", escape_html(&self.wav_file)));
+
+        let mut links = Vec::new();
+        if let Some(id) = &self.release_tags.musicbrainz_release_id {
+            links.push(format!("<a href=\"https://musicbrainz.org/release/{}\">MusicBrainz</a>", escape_html(id)));
+        }
+        if let Some(id) = &self.release_tags.discogs_release_id {
+            links.push(format!("<a href=\"https://www.discogs.com/release/{}\">Discogs</a>", escape_html(id)));
+        }
+        if self.release_info.is_some() || !links.is_empty() {
+            let info = self.release_info.as_deref().map(escape_html).unwrap_or_default();
+            let year = self.release_tags.year.map(|y| format!(" ({})", y)).unwrap_or_default();
+            html.push_str(&format!("<p>Release: {}{} {}</p>\n", info, year, links.join(" ")));
+        }
+        for warning in &self.warnings {
+            html.push_str(&format!("<p class=\"warning\">Warning: {}</p>\n", escape_html(warning)));
+        }
+
+        html.push_str("<h3>Levels</h3>\n<table>\n");
+        let mut row = |name: &str, value: String| html.push_str(&format!("<tr><th>{}</th><td>{}</td></tr>\n", name, escape_html(&value)));
+        row("Duration", format_timestamp(self.duration));
+        row("Music", format!("{} - {}", format_timestamp(self.music.0), format_timestamp(self.music.1)));
+        row("Noise floor", format!("{:.1} dBFS", self.noise_floor_db));
+        row("Music level", format!("{:.1} dBFS", self.music_level_db));
+        if let Some(quality) = &self.quality {
+            row("Estimated SNR", format!("{:.1} dB", quality.snr_db()));
+            row("DC offset", format_dc_offset(&quality.dc_offset));
+            match quality.dropouts.len() {
+                0 => row("Dropouts", "none".to_string()),
+                _ => row("Dropouts", quality.dropouts.iter().map(|d| d.describe()).collect::<Vec<_>>().join(", ")),
+            }
+        }
+        if let Some(loudness) = &self.loudness {
+            row("Loudness", loudness.describe());
+        }
+        html.push_str("</table>\n");
+
+        html.push_str("<h3>RMS curve</h3>\n");
+        html.push_str(&self.plot());
+
+        html.push_str("<h3>Tracks</h3>\n<table>\n<tr><th>#</th><th>Title</th><th>Start</th><th>Length</th><th>Loudness</th></tr>\n");
+        for (i, track) in self.tracks.iter().enumerate() {
+            html.push_str(&format!(
+                "<tr><td class=\"n\">{}</td><td>{}</td><td class=\"n\">{}</td><td class=\"n\">{}</td><td>{}</td></tr>\n",
+                i + 1,
+                escape_html(track.title.as_deref().unwrap_or("")),
+                format_timestamp(track.start),
+                format_timestamp(track.end - track.start),
+                track.loudness.map(|l| l.describe()).unwrap_or_default()
+            ));
+        }
+        html.push_str("</table>\n");
+
+        if !self.boundaries.is_empty() {
+            html.push_str(
+                "<h3>Boundaries</h3>\n<table>\n<tr><th>#</th><th>Position</th><th>Depth</th><th>Prominence</th>\
+<th>Left</th><th>Right</th><th>Width</th><th>Score</th></tr>\n",
+            );
+            for (i, v) in self.boundaries.iter().enumerate() {
+                html.push_str(&format!(
+                    "<tr><td class=\"n\">{}</td><td class=\"n\">{}</td><td class=\"n\">{:.1} dB</td><td class=\"n\">{:.1} dB</td>\
+<td class=\"n\">{:.1} dB</td><td class=\"n\">{:.1} dB</td><td class=\"n\">{:.1} s</td><td class=\"n\">{:.1}</td></tr>\n",
+                    i + 1,
+                    format_timestamp(v.position_seconds),
+                    v.depth_db,
+                    v.prominence_db,
+                    v.left_level_db,
+                    v.right_level_db,
+                    v.width_seconds,
+                    v.score
+                ));
+            }
+            html.push_str("</table>\n");
+        }
+
+        if !self.songs.is_empty() {
+            html.push_str("<h3>Identified songs</h3>\n<table>\n<tr><th>At</th><th>Artist</th><th>Title</th><th>Album</th></tr>\n");
+            for song in &self.songs {
+                html.push_str(&format!(
+                    "<tr><td class=\"n\">{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                    format_timestamp(song.timestamp),
+                    escape_html(&song.artist),
+                    escape_html(&song.title),
+                    escape_html(song.album.as_deref().unwrap_or(""))
+                ));
+            }
+            html.push_str("</table>\n");
+        }
+        html.push_str("</section>\n");
+        html
+    }
+}
+
+/// A page with the reports of `reports`
+pub fn render_page(title: &str, reports: &[FileReport]) -> String {
+    let mut html = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str(&format!("<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n", escape_html(title), STYLE));
+    html.push_str(&format!("<h1>{}</h1>\n", escape_html(title)));
+    for report in reports {
+        html.push_str(&report.to_html());
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// Write the report of one recording next to it; returns its path
+pub fn write_file_report(report: &FileReport) -> Result<PathBuf> {
+    let path = report_path(&report.wav_file);
+    fs::write(&path, render_page(&report.title(), std::slice::from_ref(report)))?;
+    Ok(path)
+}
+
+/// Write the reports of a processed directory into `dir/report.html`;
+/// returns its path
+pub fn write_session_report(dir: &Path, reports: &[FileReport]) -> Result<PathBuf> {
+    let path = dir.join(SESSION_REPORT_NAME);
+    let name = dir.file_name().and_then(|n| n.to_str()).unwrap_or("Recordings");
+    fs::write(&path, render_page(&format!("Analysis of {}", name), reports))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_report_html() {
+        let rms = [vec![-70.0_f32; 10], vec![-20.0; 100], vec![-60.0; 5], vec![-20.0; 100], vec![-70.0; 10]].concat();
+        let analysis = BoundaryAnalysis::new(rms, 200, 1.0, 45.0, false);
+        let valley = Valley {
+            position_seconds: 22.0,
+            depth_db: 40.0,
+            prominence_db: 38.5,
+            left_level_db: -20.0,
+            right_level_db: -20.0,
+            width_seconds: 1.0,
+            gap_start_seconds: 21.5,
+            gap_end_seconds: 22.5,
+            score: 12.3,
+        };
+        let mut report = FileReport::new("/rec/side_a.wav", &analysis, (2.0, 43.0), &[valley], &["Tom & Jerry".to_string()]);
+        report.artist = "<Artist>".to_string();
+        report.album = "Album".to_string();
+        report.release_tags.musicbrainz_release_id = Some("abc-123".to_string());
+        assert_eq!(report.tracks.len(), 2);
+        assert_eq!((report.tracks[1].start, report.tracks[1].end), (22.0, 43.0));
+
+        let html = render_page("Test", &[report]);
+        assert!(html.contains("<h2>&lt;Artist&gt; - Album</h2>"));
+        assert!(html.contains("Tom &amp; Jerry"));
+        assert!(html.contains("https://musicbrainz.org/release/abc-123"));
+        assert!(html.contains("<td class=\"n\">12.3</td>"));
+        assert!(html.contains("<title>Boundary 1 at 00:22.00</title>"));
+        assert_eq!(report_path("/rec/side_a.wav"), PathBuf::from("/rec/side_a.report.html"));
+    }
+}
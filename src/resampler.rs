@@ -0,0 +1,263 @@
+//! Sample-rate and bit-depth conversion.
+//!
+//! [`Resampler`] converts a stream of per-channel integer samples between two
+//! fixed sample rates with an FFT resampler (rubato). Input may arrive in
+//! chunks of any size; the filter delay is removed, so the output starts at
+//! the same instant as the input and [`Resampler::flush`] ends it at the
+//! matching length.
+//!
+//! [`Requantizer`] changes the bit depth of integer samples, with TPDF
+//! dither when bits are dropped (e.g. 24-bit masters to 16-bit delivery
+//! files).
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rubato::{FftFixedIn, Resampler as _};
+
+use crate::error::{Error, Result};
+
+/// Input frames per resampler chunk
+const CHUNK_FRAMES: usize = 1024;
+
+/// FFT sub-chunks per chunk; more reduce the delay but cost CPU
+const SUB_CHUNKS: usize = 2;
+
+/// Streaming sample-rate converter for per-channel `i32` samples.
+///
+/// Samples are converted without normalization, so any integer scale
+/// (16, 24 or 32 bit) can be passed and comes back in the same scale.
+pub struct Resampler {
+    inner: FftFixedIn<f64>,
+    input_rate: u32,
+    output_rate: u32,
+    /// Input that does not fill a chunk yet
+    pending: Vec<Vec<f64>>,
+    /// Output frames still to drop at the start (filter delay)
+    delay: usize,
+    frames_in: u64,
+    frames_out: u64,
+}
+
+impl Resampler {
+    pub fn new(input_rate: u32, output_rate: u32, channels: usize) -> Result<Self> {
+        if channels == 0 {
+            return Err(Error::Config("Resampler needs at least one channel".to_string()));
+        }
+        let inner = FftFixedIn::<f64>::new(
+            input_rate as usize,
+            output_rate as usize,
+            CHUNK_FRAMES,
+            SUB_CHUNKS,
+            channels,
+        )
+        .map_err(|e| Error::Config(format!("Cannot resample {} Hz to {} Hz: {}", input_rate, output_rate, e)))?;
+        let delay = inner.output_delay();
+        Ok(Resampler {
+            inner,
+            input_rate,
+            output_rate,
+            pending: vec![Vec::new(); channels],
+            delay,
+            frames_in: 0,
+            frames_out: 0,
+        })
+    }
+
+    pub fn input_rate(&self) -> u32 {
+        self.input_rate
+    }
+
+    pub fn output_rate(&self) -> u32 {
+        self.output_rate
+    }
+
+    pub fn channels(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Resample the next block of input.
+    ///
+    /// Returns the output that is complete so far; it may be empty while
+    /// the first chunk is being filled.
+    pub fn process(&mut self, input: &[Vec<i32>]) -> Result<Vec<Vec<i32>>> {
+        if input.len() != self.channels() {
+            return Err(Error::Decode(format!(
+                "Resampler expects {} channels, got {}",
+                self.channels(),
+                input.len()
+            )));
+        }
+        let frames = input.iter().map(|ch| ch.len()).min().unwrap_or(0);
+        for (pending, channel) in self.pending.iter_mut().zip(input) {
+            pending.extend(channel[..frames].iter().map(|&s| s as f64));
+        }
+        self.frames_in += frames as u64;
+
+        let mut output = vec![Vec::new(); self.channels()];
+        while self.pending[0].len() >= self.inner.input_frames_next() {
+            let needed = self.inner.input_frames_next();
+            let chunk: Vec<Vec<f64>> = self.pending.iter_mut().map(|ch| ch.drain(..needed).collect()).collect();
+            let resampled = self
+                .inner
+                .process(&chunk, None)
+                .map_err(|e| Error::Decode(format!("Resampling failed: {}", e)))?;
+            self.append_output(&resampled, &mut output, u64::MAX);
+        }
+        Ok(output)
+    }
+
+    /// Process the remaining input and return the rest of the output.
+    ///
+    /// The total output is the input length scaled by the rate ratio. The
+    /// resampler is reset afterwards and can be used for a new stream.
+    pub fn flush(&mut self) -> Result<Vec<Vec<i32>>> {
+        let expected = (self.frames_in * self.output_rate as u64).div_ceil(self.input_rate as u64);
+        let channels = self.channels();
+        let mut output = vec![Vec::new(); channels];
+        let mut input = Some(std::mem::replace(&mut self.pending, vec![Vec::new(); channels]));
+
+        while self.frames_out < expected {
+            let resampled = self
+                .inner
+                .process_partial(input.take().as_deref(), None)
+                .map_err(|e| Error::Decode(format!("Resampling failed: {}", e)))?;
+            self.append_output(&resampled, &mut output, expected);
+        }
+
+        self.inner.reset();
+        self.delay = self.inner.output_delay();
+        self.frames_in = 0;
+        self.frames_out = 0;
+        Ok(output)
+    }
+
+    /// Append resampled frames after the filter delay, up to `limit` frames in total
+    fn append_output(&mut self, resampled: &[Vec<f64>], output: &mut [Vec<i32>], limit: u64) {
+        let available = resampled[0].len();
+        let skip = self.delay.min(available);
+        self.delay -= skip;
+        let take = ((available - skip) as u64).min(limit - self.frames_out) as usize;
+        for (out, channel) in output.iter_mut().zip(resampled) {
+            out.extend(channel[skip..skip + take].iter().map(|&s| to_i32(s)));
+        }
+        self.frames_out += take as u64;
+    }
+}
+
+fn to_i32(sample: f64) -> i32 {
+    sample.round().clamp(i32::MIN as f64, i32::MAX as f64) as i32
+}
+
+/// Resample a whole signal at once
+pub fn resample(input: &[Vec<i32>], input_rate: u32, output_rate: u32) -> Result<Vec<Vec<i32>>> {
+    if input_rate == output_rate {
+        return Ok(input.to_vec());
+    }
+    let mut resampler = Resampler::new(input_rate, output_rate, input.len())?;
+    let mut output = resampler.process(input)?;
+    for (out, rest) in output.iter_mut().zip(resampler.flush()?) {
+        out.extend(rest);
+    }
+    Ok(output)
+}
+
+/// Bit-depth conversion of integer samples
+pub struct Requantizer {
+    from_bits: u16,
+    to_bits: u16,
+    dither: bool,
+    rng: StdRng,
+}
+
+impl Requantizer {
+    /// Convert from `from_bits` to `to_bits`; `dither` adds TPDF dither of
+    /// one LSB of the target when bits are dropped
+    pub fn new(from_bits: u16, to_bits: u16, dither: bool) -> Self {
+        Requantizer {
+            from_bits,
+            to_bits,
+            dither,
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    /// Convert interleaved or single-channel samples in place
+    pub fn process(&mut self, samples: &mut [i32]) {
+        if self.to_bits >= self.from_bits {
+            let shift = self.to_bits - self.from_bits;
+            for sample in samples.iter_mut() {
+                *sample = sample.saturating_mul(1 << shift);
+            }
+            return;
+        }
+
+        let scale = (1u64 << (self.from_bits - self.to_bits)) as f64;
+        let max = ((1i64 << (self.to_bits - 1)) - 1) as f64;
+        let min = -((1i64 << (self.to_bits - 1)) as f64);
+        for sample in samples.iter_mut() {
+            let mut value = *sample as f64 / scale;
+            if self.dither {
+                value += self.rng.gen::<f64>() - self.rng.gen::<f64>();
+            }
+            *sample = value.round().clamp(min, max) as i32;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(rate: u32, freq: f64, frames: usize) -> Vec<i32> {
+        (0..frames)
+            .map(|i| ((2.0 * std::f64::consts::PI * freq * i as f64 / rate as f64).sin() * 10000.0) as i32)
+            .collect()
+    }
+
+    #[test]
+    fn test_resample_length_and_phase() {
+        let input = vec![sine(44100, 1000.0, 44100); 2];
+        let output = resample(&input, 44100, 96000).unwrap();
+        assert_eq!(output.len(), 2);
+        assert_eq!(output[0].len(), 96000);
+
+        // The delay is removed: the output follows the same sine
+        let expected = sine(96000, 1000.0, 96000);
+        for i in (1000..95000).step_by(997) {
+            assert!((output[0][i] - expected[i]).abs() < 50, "frame {}: {} vs {}", i, output[0][i], expected[i]);
+        }
+    }
+
+    #[test]
+    fn test_resample_in_chunks() {
+        let input = sine(96000, 440.0, 20000);
+        let whole = resample(std::slice::from_ref(&input), 96000, 44100).unwrap();
+
+        let mut resampler = Resampler::new(96000, 44100, 1).unwrap();
+        let mut chunked = Vec::new();
+        for chunk in input.chunks(777) {
+            chunked.extend(resampler.process(&[chunk.to_vec()]).unwrap().remove(0));
+        }
+        chunked.extend(resampler.flush().unwrap().remove(0));
+        assert_eq!(chunked, whole[0]);
+        assert_eq!(chunked.len(), 9188);
+    }
+
+    #[test]
+    fn test_requantize() {
+        let mut samples = vec![256, -384, 8388607, -8388608];
+        Requantizer::new(24, 16, false).process(&mut samples);
+        assert_eq!(samples, vec![1, -2, 32767, -32768]);
+
+        let mut samples = vec![1, -1];
+        Requantizer::new(16, 24, true).process(&mut samples);
+        assert_eq!(samples, vec![256, -256]);
+
+        // Dither keeps the average level and stays within one LSB
+        let mut samples = vec![64; 20000];
+        Requantizer::new(24, 16, true).process(&mut samples);
+        assert!(samples.iter().all(|&s| (-1..=2).contains(&s)));
+        let mean = samples.iter().sum::<i32>() as f64 / samples.len() as f64;
+        assert!((mean - 0.25).abs() < 0.05, "mean {}", mean);
+    }
+}
@@ -0,0 +1,224 @@
+//! Recording schedules.
+//!
+//! A [`Schedule`] decides when the recorder is armed, e.g. to capture a radio
+//! broadcast through the usual signal detection. It combines an optional
+//! one-off window (`--start-at`, `--stop-at`) with weekly rules such as
+//! `mon-fri 07:00-09:00`. All times are local time.
+
+use chrono::{Datelike, Duration, NaiveDateTime, NaiveTime, Weekday};
+
+use crate::error::{Error, Result};
+
+/// Recurring recording window on some days of the week
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduleRule {
+    /// Days on which the window starts; all days if empty
+    pub days: Vec<Weekday>,
+    pub start: NaiveTime,
+    /// End of the window; before `start` if the window crosses midnight
+    pub stop: NaiveTime,
+}
+
+fn parse_weekday(s: &str) -> Result<Weekday> {
+    s.parse().map_err(|_| Error::Config(format!("Unknown day '{}'", s)))
+}
+
+/// Parse `mon-fri`, `sat,sun`, `fri-mon` or `*`
+fn parse_days(s: &str) -> Result<Vec<Weekday>> {
+    if s == "*" || s.eq_ignore_ascii_case("daily") {
+        return Ok(Vec::new());
+    }
+    let mut days = Vec::new();
+    for part in s.split(',') {
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let mut day = parse_weekday(first)?;
+                let last = parse_weekday(last)?;
+                while day != last {
+                    days.push(day);
+                    day = day.succ();
+                }
+                days.push(last);
+            }
+            None => days.push(parse_weekday(part)?),
+        }
+    }
+    Ok(days)
+}
+
+fn parse_time_of_day(s: &str) -> Result<NaiveTime> {
+    NaiveTime::parse_from_str(s, "%H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(s, "%H:%M:%S"))
+        .map_err(|_| Error::Config(format!("Invalid time '{}' (use HH:MM)", s)))
+}
+
+impl ScheduleRule {
+    /// Parse `[DAYS] HH:MM-HH:MM`, e.g. `mon-fri 07:00-09:00` or `23:00-01:00`
+    pub fn parse(s: &str) -> Result<Self> {
+        let parts: Vec<&str> = s.split_whitespace().collect();
+        let (days, window) = match parts.as_slice() {
+            [window] => (Vec::new(), *window),
+            [days, window] => (parse_days(days)?, *window),
+            _ => return Err(Error::Config(format!("Invalid schedule '{}' (use e.g. mon-fri 07:00-09:00)", s))),
+        };
+        let (start, stop) = window
+            .split_once('-')
+            .ok_or_else(|| Error::Config(format!("Invalid schedule window '{}' (use HH:MM-HH:MM)", window)))?;
+        let (start, stop) = (parse_time_of_day(start)?, parse_time_of_day(stop)?);
+        if start == stop {
+            return Err(Error::Config(format!("Schedule window '{}' is empty", window)));
+        }
+        Ok(ScheduleRule { days, start, stop })
+    }
+
+    fn runs_on(&self, day: Weekday) -> bool {
+        self.days.is_empty() || self.days.contains(&day)
+    }
+
+    /// Whether `now` is inside one of the rule's windows
+    pub fn is_active(&self, now: NaiveDateTime) -> bool {
+        let time = now.time();
+        let today = now.weekday();
+        if self.start < self.stop {
+            self.runs_on(today) && time >= self.start && time < self.stop
+        } else {
+            // Crosses midnight: the window may have started yesterday
+            (self.runs_on(today) && time >= self.start) || (self.runs_on(today.pred()) && time < self.stop)
+        }
+    }
+}
+
+/// Parse a point in time: `+30m`, `+2h`, `+90s` after `reference`,
+/// `YYYY-MM-DD HH:MM`, or `HH:MM` for its next occurrence after `reference`
+pub fn parse_time_spec(s: &str, reference: NaiveDateTime) -> Result<NaiveDateTime> {
+    let invalid = || Error::Config(format!("Invalid time '{}' (use HH:MM, YYYY-MM-DD HH:MM or +30m)", s));
+    if let Some(offset) = s.strip_prefix('+') {
+        let (number, unit) = offset.split_at(offset.find(|c: char| !c.is_ascii_digit()).unwrap_or(offset.len()));
+        let value: i64 = number.parse().map_err(|_| invalid())?;
+        let seconds = match unit {
+            "" | "s" => value,
+            "m" => value * 60,
+            "h" => value * 3600,
+            _ => return Err(invalid()),
+        };
+        return Ok(reference + Duration::seconds(seconds));
+    }
+    for format in ["%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S"] {
+        if let Ok(datetime) = NaiveDateTime::parse_from_str(s, format) {
+            return Ok(datetime);
+        }
+    }
+    let time = parse_time_of_day(s).map_err(|_| invalid())?;
+    let datetime = reference.date().and_time(time);
+    Ok(if datetime > reference { datetime } else { datetime + Duration::days(1) })
+}
+
+/// When the recorder is armed
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Schedule {
+    /// Nothing is recorded before this time
+    pub start_at: Option<NaiveDateTime>,
+    /// Nothing is recorded from this time on; the schedule is finished
+    pub stop_at: Option<NaiveDateTime>,
+    /// Weekly windows; if empty, the whole time from start to stop
+    pub rules: Vec<ScheduleRule>,
+}
+
+impl Schedule {
+    /// Build a schedule from command-line and config values
+    ///
+    /// Relative and time-of-day start times count from `now`; stop times
+    /// count from the start time, so `--start-at 20:00 --stop-at +2h`
+    /// records two hours.
+    pub fn parse(start_at: Option<&str>, stop_at: Option<&str>, rules: &[String], now: NaiveDateTime) -> Result<Self> {
+        let start_at = start_at.map(|s| parse_time_spec(s, now)).transpose()?;
+        let stop_at = stop_at.map(|s| parse_time_spec(s, start_at.unwrap_or(now))).transpose()?;
+        if let (Some(start), Some(stop)) = (start_at, stop_at) {
+            if stop <= start {
+                return Err(Error::Config(format!("Stop time {} is not after start time {}", stop, start)));
+            }
+        }
+        let rules = rules.iter().map(|r| ScheduleRule::parse(r)).collect::<Result<_>>()?;
+        Ok(Schedule { start_at, stop_at, rules })
+    }
+
+    /// No start, stop or rules: record whenever there is a signal
+    pub fn is_empty(&self) -> bool {
+        self.start_at.is_none() && self.stop_at.is_none() && self.rules.is_empty()
+    }
+
+    /// Whether the recorder should be armed at `now`
+    pub fn is_active(&self, now: NaiveDateTime) -> bool {
+        if self.start_at.is_some_and(|t| now < t) || self.is_finished(now) {
+            return false;
+        }
+        self.rules.is_empty() || self.rules.iter().any(|r| r.is_active(now))
+    }
+
+    /// The stop time has passed; nothing will be recorded any more
+    pub fn is_finished(&self, now: NaiveDateTime) -> bool {
+        self.stop_at.is_some_and(|t| now >= t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn test_schedule_rule() {
+        // 2026-10-16 is a Friday
+        let rule = ScheduleRule::parse("mon-fri 07:00-09:00").unwrap();
+        assert_eq!(rule.days.len(), 5);
+        assert!(rule.is_active(at("2026-10-16 07:00")));
+        assert!(!rule.is_active(at("2026-10-16 09:00")));
+        assert!(!rule.is_active(at("2026-10-17 08:00")));
+
+        // Friday night into Saturday, but not Saturday night
+        let night = ScheduleRule::parse("fri 23:00-01:00").unwrap();
+        assert!(night.is_active(at("2026-10-16 23:30")));
+        assert!(night.is_active(at("2026-10-17 00:30")));
+        assert!(!night.is_active(at("2026-10-17 23:30")));
+
+        assert_eq!(ScheduleRule::parse("sat,sun 20:00-22:00").unwrap().days, vec![Weekday::Sat, Weekday::Sun]);
+        assert!(ScheduleRule::parse("* 20:00-22:00").unwrap().days.is_empty());
+        assert!(ScheduleRule::parse("someday 20:00-22:00").is_err());
+        assert!(ScheduleRule::parse("20:00-20:00").is_err());
+        assert!(ScheduleRule::parse("mon 20:00").is_err());
+    }
+
+    #[test]
+    fn test_parse_time_spec() {
+        let now = at("2026-10-16 12:00");
+        assert_eq!(parse_time_spec("+30m", now).unwrap(), at("2026-10-16 12:30"));
+        assert_eq!(parse_time_spec("+2h", now).unwrap(), at("2026-10-16 14:00"));
+        assert_eq!(parse_time_spec("+90", now).unwrap(), at("2026-10-16 12:01") + Duration::seconds(30));
+        assert_eq!(parse_time_spec("13:15", now).unwrap(), at("2026-10-16 13:15"));
+        assert_eq!(parse_time_spec("08:00", now).unwrap(), at("2026-10-17 08:00"));
+        assert_eq!(parse_time_spec("2026-12-24 18:00", now).unwrap(), at("2026-12-24 18:00"));
+        assert!(parse_time_spec("+5d", now).is_err());
+        assert!(parse_time_spec("noon", now).is_err());
+    }
+
+    #[test]
+    fn test_schedule() {
+        let now = at("2026-10-16 12:00");
+        let once = Schedule::parse(Some("20:00"), Some("+2h"), &[], now).unwrap();
+        assert!(!once.is_active(at("2026-10-16 19:59")));
+        assert!(once.is_active(at("2026-10-16 21:00")));
+        assert!(!once.is_active(at("2026-10-16 22:00")));
+        assert!(once.is_finished(at("2026-10-16 22:00")));
+
+        let weekly = Schedule::parse(None, None, &["sat 10:00-11:00".to_string()], now).unwrap();
+        assert!(!weekly.is_empty());
+        assert!(weekly.is_active(at("2026-10-17 10:30")));
+        assert!(!weekly.is_finished(at("2030-01-01 00:00")));
+
+        assert!(Schedule::parse(None, None, &[], now).unwrap().is_empty());
+        assert!(Schedule::parse(Some("2026-10-16 20:00"), Some("2026-10-16 19:00"), &[], now).is_err());
+    }
+}
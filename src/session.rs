@@ -0,0 +1,338 @@
+//! Session manifest for multi-side album recordings.
+//!
+//! The sides of an album are recorded into separate WAV files in one
+//! directory. `cue_creator` writes what it learned about them into a
+//! `session.toml` next to the files: the length of each recording, the
+//! identified songs, the chosen release and which side each file is. Later
+//! runs of `cue_creator` and `track_splitter` read it instead of identifying
+//! the songs and looking up the album again.
+//!
+//! ```toml
+//! artist = "Pink Floyd"
+//! album = "Animals"
+//! release = "https://www.discogs.com/release/1873013"
+//! year = 1977
+//! backend = "Discogs"
+//!
+//! [[side]]
+//! file = "animals.1.wav"
+//! duration = 1292.4
+//! music_duration = 1268.0
+//! label = "A"
+//!
+//! [[side.songs]]
+//! timestamp = 40.0
+//! title = "Pigs on the Wing 1"
+//! artist = "Pink Floyd"
+//! ```
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::album_identifier::IdentifiedSong;
+use crate::cuefile::CueSheet;
+use crate::error::{Error, Result};
+use crate::lookup::FileSideResult;
+use crate::musicbrainz::ExpectedTrack;
+use crate::tagging::ReleaseTags;
+
+/// Name of the manifest file in the recording directory
+pub const SESSION_FILE_NAME: &str = "session.toml";
+
+/// All sides of one album recording
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Session {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artist: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub album: Option<String>,
+    /// Release reference (URL) of the chosen release
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub release: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub year: Option<u32>,
+    /// Name of the backend that found the release
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backend: Option<String>,
+    #[serde(default, rename = "side", skip_serializing_if = "Vec::is_empty")]
+    pub sides: Vec<SessionSide>,
+}
+
+/// One recorded file of the session
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionSide {
+    /// File name of the recording, relative to the session directory
+    pub file: String,
+    /// Length of the recording in seconds
+    pub duration: f64,
+    /// Length of the music region (groove-in to groove-out) in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub music_duration: Option<f64>,
+    /// Side of the release this file was assigned to ('A', 'B', ...)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<char>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub songs: Vec<IdentifiedSong>,
+    /// Track list of the assigned side
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tracks: Vec<ExpectedTrack>,
+}
+
+/// Path of the manifest for a recording
+pub fn session_path(wav_file: &str) -> PathBuf {
+    Path::new(wav_file)
+        .parent()
+        .unwrap_or(Path::new(""))
+        .join(SESSION_FILE_NAME)
+}
+
+/// File name under which a recording is listed in the manifest
+fn file_key(wav_file: &str) -> String {
+    Path::new(wav_file)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| wav_file.to_string())
+}
+
+impl Session {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)?;
+        toml::from_str(&content).map_err(|e| Error::Config(format!("{}: {}", path.display(), e)))
+    }
+
+    /// Load the manifest next to `wav_file`; an empty session if there is none
+    pub fn load_for(wav_file: &str) -> Result<Self> {
+        let path = session_path(wav_file);
+        if path.exists() {
+            Session::load(&path)
+        } else {
+            Ok(Session::default())
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let content = toml::to_string_pretty(self).map_err(|e| Error::Config(e.to_string()))?;
+        fs::write(path, content)?;
+        Ok(())
+    }
+
+    pub fn side(&self, wav_file: &str) -> Option<&SessionSide> {
+        let key = file_key(wav_file);
+        self.sides.iter().find(|s| s.file == key)
+    }
+
+    /// Entry of `wav_file`, added if it is not listed yet
+    pub fn side_mut(&mut self, wav_file: &str) -> &mut SessionSide {
+        let key = file_key(wav_file);
+        match self.sides.iter().position(|s| s.file == key) {
+            Some(index) => &mut self.sides[index],
+            None => {
+                self.sides.push(SessionSide { file: key, ..Default::default() });
+                self.sides.last_mut().unwrap()
+            }
+        }
+    }
+
+    /// Songs identified earlier for `wav_file`
+    pub fn songs(&self, wav_file: &str) -> Option<&[IdentifiedSong]> {
+        self.side(wav_file)
+            .map(|s| s.songs.as_slice())
+            .filter(|songs| !songs.is_empty())
+    }
+
+    /// Record the release and side found for a file.
+    ///
+    /// A session holds one release; returns false (and records nothing)
+    /// when the result names a different release than the session.
+    pub fn assign(&mut self, result: &FileSideResult) -> bool {
+        if self.release.as_ref().is_some_and(|r| *r != result.release_info) {
+            return false;
+        }
+        self.artist = Some(result.artist.clone());
+        self.album = Some(result.album_title.clone());
+        self.release = Some(result.release_info.clone());
+        self.year = result.year.or(self.year);
+        self.backend = Some(result.backend.clone());
+        let side = self.side_mut(&result.path);
+        side.label = Some(result.side_label).filter(|&l| l != '?');
+        side.tracks = result.tracks.clone();
+        true
+    }
+
+    /// The stored release and side of `wav_file`, in the form of a multi-file
+    /// lookup result
+    pub fn side_result(&self, wav_file: &str) -> Option<FileSideResult> {
+        let side = self.side(wav_file)?;
+        if side.tracks.is_empty() {
+            return None;
+        }
+        Some(FileSideResult {
+            path: wav_file.to_string(),
+            artist: self.artist.clone()?,
+            album_title: self.album.clone()?,
+            release_info: self.release.clone()?,
+            year: self.year,
+            side_label: side.label.unwrap_or('?'),
+            tracks: side.tracks.clone(),
+            backend: self.backend.clone().unwrap_or_default(),
+            score: 0.0,
+        })
+    }
+
+    /// Follow a rename of a recording; returns false if it is not listed
+    pub fn rename_file(&mut self, old: &str, new: &str) -> bool {
+        let old_key = file_key(old);
+        match self.sides.iter_mut().find(|s| s.file == old_key) {
+            Some(side) => {
+                side.file = file_key(new);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn release_tags(&self) -> ReleaseTags {
+        match self.release {
+            Some(ref release) => ReleaseTags::from_release_info(release, self.year),
+            None => ReleaseTags { year: self.year, ..Default::default() },
+        }
+    }
+
+    /// Fill in album data and track titles that a CUE sheet of `wav_file` lacks
+    pub fn complete_sheet(&self, wav_file: &str, sheet: &mut CueSheet) {
+        if sheet.title.is_none() {
+            sheet.title = self.album.clone();
+        }
+        if sheet.performer.is_none() {
+            sheet.performer = self.artist.clone();
+        }
+        if sheet.release.is_empty() {
+            sheet.release = self.release_tags();
+        }
+        let Some(side) = self.side(wav_file) else { return };
+        if sheet.groove_out.is_none() {
+            // Older CUE sheets have no groove-out; the music starts at track 1
+            sheet.groove_out = side.music_duration.zip(sheet.tracks.first()).map(|(d, t)| t.start + d);
+        }
+        if side.tracks.len() == sheet.tracks.len() {
+            for (track, expected) in sheet.tracks.iter_mut().zip(&side.tracks) {
+                if track.title.is_none() {
+                    track.title = Some(expected.title.clone());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cuefile::CueTrack;
+
+    fn song(timestamp: f64, title: &str) -> IdentifiedSong {
+        IdentifiedSong {
+            timestamp,
+            title: title.to_string(),
+            artist: "Pink Floyd".to_string(),
+            album: None,
+            match_offsets: Vec::new(),
+            song_offset: None,
+            recording_id: None,
+            confidence: None,
+        }
+    }
+
+    fn side_result(path: &str, label: char) -> FileSideResult {
+        FileSideResult {
+            path: path.to_string(),
+            artist: "Pink Floyd".to_string(),
+            album_title: "Animals".to_string(),
+            release_info: "https://www.discogs.com/release/1873013".to_string(),
+            year: Some(1977),
+            side_label: label,
+            tracks: vec![
+                ExpectedTrack { position: 1, title: "Pigs on the Wing 1".to_string(), length_seconds: 85.0, expected_start: 0.0 },
+                ExpectedTrack { position: 2, title: "Dogs".to_string(), length_seconds: 1024.0, expected_start: 85.0 },
+            ],
+            backend: "Discogs".to_string(),
+            score: 120.0,
+        }
+    }
+
+    #[test]
+    fn test_session_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let wav = dir.path().join("animals.1.wav");
+        let wav = wav.to_str().unwrap();
+
+        let mut session = Session::default();
+        let side = session.side_mut(wav);
+        side.duration = 1292.4;
+        side.songs = vec![song(40.0, "Pigs on the Wing 1"), song(160.0, "Dogs")];
+        assert!(session.assign(&side_result(wav, 'A')));
+        session.side_mut("animals.2.wav").duration = 1100.0;
+
+        let path = session_path(wav);
+        assert_eq!(path, dir.path().join(SESSION_FILE_NAME));
+        session.save(&path).unwrap();
+
+        let loaded = Session::load_for(wav).unwrap();
+        assert_eq!(loaded.sides.len(), 2);
+        assert_eq!(loaded.side(wav).unwrap().file, "animals.1.wav");
+        assert_eq!(loaded.songs(wav).unwrap()[1].title, "Dogs");
+        assert!(loaded.songs("animals.2.wav").is_none());
+
+        let result = loaded.side_result(wav).unwrap();
+        assert_eq!(result.side_label, 'A');
+        assert_eq!(result.tracks.len(), 2);
+        assert_eq!(result.year, Some(1977));
+        assert!(loaded.side_result("animals.2.wav").is_none());
+        assert_eq!(loaded.release_tags().discogs_release_id.as_deref(), Some("1873013"));
+
+        // No manifest yet: empty session
+        let other = dir.path().join("sub").join("x.wav");
+        assert!(Session::load_for(other.to_str().unwrap()).unwrap().sides.is_empty());
+    }
+
+    #[test]
+    fn test_session_single_release_and_rename() {
+        let mut session = Session::default();
+        assert!(session.assign(&side_result("/rec/a.1.wav", 'A')));
+        let mut other = side_result("/rec/b.1.wav", 'A');
+        other.release_info = "https://www.discogs.com/release/42".to_string();
+        assert!(!session.assign(&other));
+        assert!(session.side("b.1.wav").is_none());
+
+        assert!(session.rename_file("/rec/a.1.wav", "/rec/Pink Floyd - Animals.1.wav"));
+        assert!(session.side("Pink Floyd - Animals.1.wav").is_some());
+        assert!(!session.rename_file("/rec/missing.wav", "/rec/x.wav"));
+    }
+
+    #[test]
+    fn test_complete_sheet() {
+        let mut session = Session::default();
+        session.assign(&side_result("/rec/a.1.wav", 'A'));
+        session.side_mut("/rec/a.1.wav").music_duration = Some(1109.0);
+        let track = |number, title: Option<&str>| CueTrack {
+            number,
+            title: title.map(str::to_string),
+            performer: None,
+            pregap_start: None,
+            start: 2.0,
+            confidence: None,
+        };
+        let mut sheet = CueSheet {
+            tracks: vec![track(1, None), track(2, Some("Dogs (edit)"))],
+            ..Default::default()
+        };
+        session.complete_sheet("/rec/a.1.wav", &mut sheet);
+        assert_eq!(sheet.title.as_deref(), Some("Animals"));
+        assert_eq!(sheet.performer.as_deref(), Some("Pink Floyd"));
+        assert_eq!(sheet.release.year, Some(1977));
+        assert_eq!(sheet.tracks[0].title.as_deref(), Some("Pigs on the Wing 1"));
+        assert_eq!(sheet.tracks[1].title.as_deref(), Some("Dogs (edit)"));
+        assert_eq!(sheet.groove_out, Some(1111.0));
+    }
+}
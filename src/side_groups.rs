@@ -0,0 +1,199 @@
+//! Grouping consecutive recordings into the sides of one album.
+//!
+//! When a record is turned over, the recorder stops on the silence and starts
+//! a new file for the next side a minute later. [`SideGrouping`] finds such
+//! runs of recordings: each one started soon after the previous one ended and
+//! has a similar average level (the same record on the same turntable).
+//! [`organize_album`] moves the sides of one album into their own directory
+//! as `<name>.A.wav`, `<name>.B.wav`, ... and writes a session manifest that
+//! lists them, so that `cue_creator` identifies them as one album.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+use crate::recorder::RecordingStats;
+use crate::session::{session_path, Session};
+
+/// Longest pause between two sides of an album, in seconds
+pub const DEFAULT_MAX_GAP_SECONDS: f64 = 300.0;
+/// Largest difference of the average levels of two sides, in dB
+pub const DEFAULT_MAX_LEVEL_DIFFERENCE_DB: f64 = 6.0;
+/// Sides are labelled A to Z
+const MAX_SIDES: usize = 26;
+
+/// When two consecutive recordings are sides of the same album
+#[derive(Debug, Clone, Copy)]
+pub struct SideGrouping {
+    pub max_gap_seconds: f64,
+    pub max_level_difference_db: f64,
+}
+
+impl Default for SideGrouping {
+    fn default() -> Self {
+        SideGrouping {
+            max_gap_seconds: DEFAULT_MAX_GAP_SECONDS,
+            max_level_difference_db: DEFAULT_MAX_LEVEL_DIFFERENCE_DB,
+        }
+    }
+}
+
+impl SideGrouping {
+    fn same_album(&self, previous: &RecordingStats, next: &RecordingStats) -> bool {
+        let gap = next.started.duration_since(previous.finished).map_or(0.0, |d| d.as_secs_f64());
+        let similar_level = match (previous.average_level_db, next.average_level_db) {
+            (Some(a), Some(b)) => (a - b).abs() <= self.max_level_difference_db,
+            _ => true,
+        };
+        gap <= self.max_gap_seconds && similar_level
+    }
+
+    /// Indices of the recordings of each album, in recording order.
+    /// Recordings that are not part of a group are left out.
+    pub fn group(&self, recordings: &[RecordingStats]) -> Vec<Vec<usize>> {
+        let mut groups = Vec::new();
+        let mut current: Vec<usize> = Vec::new();
+        for (i, recording) in recordings.iter().enumerate() {
+            let continues = current.last().is_some_and(|&last| {
+                current.len() < MAX_SIDES && self.same_album(&recordings[last], recording)
+            });
+            if !continues {
+                if current.len() > 1 {
+                    groups.push(std::mem::take(&mut current));
+                }
+                current.clear();
+            }
+            current.push(i);
+        }
+        if current.len() > 1 {
+            groups.push(current);
+        }
+        groups
+    }
+}
+
+/// Label of the side at `index` ('A', 'B', ...)
+pub fn side_label(index: usize) -> char {
+    (b'A' + index.min(MAX_SIDES - 1) as u8) as char
+}
+
+/// First free directory `<base>-1`, `<base>-2`, ... next to `base`
+fn album_dir(base: &str) -> (PathBuf, String) {
+    let path = Path::new(base);
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().trim_end_matches(".wav").to_string())
+        .unwrap_or_else(|| "album".to_string());
+    let parent = path.parent().unwrap_or(Path::new(""));
+    let mut n = 1;
+    while parent.join(format!("{}-{}", name, n)).exists() {
+        n += 1;
+    }
+    (parent.join(format!("{}-{}", name, n)), name)
+}
+
+/// Move the files of one album (in side order) into a new directory named
+/// after `base`, the base filename of the recorder.
+///
+/// Each WAV file is renamed to `<name>.<side>.wav`; files next to it that
+/// share its name (track marks, clipping report, preview) follow it. Returns
+/// the new paths of the WAV files.
+pub fn organize_album(sides: &[&RecordingStats], base: &str) -> Result<Vec<String>> {
+    if sides.len() > MAX_SIDES {
+        return Err(Error::Config(format!("An album has at most {} sides", MAX_SIDES)));
+    }
+    let (dir, name) = album_dir(base);
+    fs::create_dir_all(&dir)?;
+
+    let mut session = Session::default();
+    let mut moved = Vec::new();
+    for (index, side) in sides.iter().enumerate() {
+        let label = side_label(index);
+        let wav = Path::new(&side.filename);
+        let source_dir = wav.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let stem = wav.file_name().unwrap_or_default().to_string_lossy().trim_end_matches(".wav").to_string();
+        let prefix = format!("{}.", stem);
+
+        for entry in fs::read_dir(source_dir)? {
+            let file_name = entry?.file_name().to_string_lossy().to_string();
+            if let Some(suffix) = file_name.strip_prefix(&prefix) {
+                fs::rename(source_dir.join(&file_name), dir.join(format!("{}.{}.{}", name, label, suffix)))?;
+            }
+        }
+
+        let new_path = dir.join(format!("{}.{}.wav", name, label)).to_string_lossy().to_string();
+        let entry = session.side_mut(&new_path);
+        entry.duration = side.duration;
+        entry.label = Some(label);
+        moved.push(new_path);
+    }
+    if let Some(first) = moved.first() {
+        session.save(&session_path(first))?;
+    }
+    Ok(moved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+
+    fn recording(filename: &str, start: u64, duration: u64, level: f64) -> RecordingStats {
+        let started = SystemTime::UNIX_EPOCH + Duration::from_secs(start);
+        RecordingStats {
+            filename: filename.to_string(),
+            duration: duration as f64,
+            started,
+            finished: started + Duration::from_secs(duration),
+            average_level_db: Some(level),
+            max_true_peak_db: None,
+            preview_file: None,
+            flac_file: None,
+            clipping: Default::default(),
+            dropouts: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_group_sides() {
+        let recordings = vec![
+            recording("r.1.wav", 0, 1200, -18.0),
+            recording("r.2.wav", 1260, 1100, -19.5),
+            // Long break: the next album
+            recording("r.3.wav", 4000, 1300, -17.0),
+            recording("r.4.wav", 5350, 1250, -16.0),
+            // Quieter source right after: not the same record
+            recording("r.5.wav", 6650, 900, -30.0),
+        ];
+        let groups = SideGrouping::default().group(&recordings);
+        assert_eq!(groups, vec![vec![0, 1], vec![2, 3]]);
+        assert_eq!(side_label(1), 'B');
+    }
+
+    #[test]
+    fn test_organize_album() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("vinyl").to_string_lossy().to_string();
+        for file in ["vinyl.1.wav", "vinyl.1.marks", "vinyl.2.wav", "vinyl.2.mp3", "vinyl.10.wav"] {
+            fs::write(dir.path().join(file), "x").unwrap();
+        }
+        let sides = [
+            recording(&format!("{}.1.wav", base), 0, 1200, -18.0),
+            recording(&format!("{}.2.wav", base), 1260, 1100, -18.0),
+        ];
+
+        let moved = organize_album(&[&sides[0], &sides[1]], &base).unwrap();
+        let album = dir.path().join("vinyl-1");
+        assert_eq!(moved, vec![
+            album.join("vinyl.A.wav").to_string_lossy().to_string(),
+            album.join("vinyl.B.wav").to_string_lossy().to_string(),
+        ]);
+        assert!(album.join("vinyl.A.marks").exists());
+        assert!(album.join("vinyl.B.mp3").exists());
+        assert!(dir.path().join("vinyl.10.wav").exists());
+
+        let session = Session::load(&album.join("session.toml")).unwrap();
+        assert_eq!(session.side(&moved[1]).unwrap().label, Some('B'));
+        assert_eq!(session.side(&moved[1]).unwrap().duration, 1100.0);
+    }
+}
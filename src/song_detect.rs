@@ -0,0 +1,206 @@
+//! Background song identification during recording.
+//!
+//! The capture loop must never block on network or subprocess work, so song
+//! identification runs on a dedicated worker thread. The capture loop keeps the
+//! most recent audio in a [`SnapshotBuffer`] and periodically hands a copy to
+//! the [`SongDetector`]. Results come back as [`SongDetectEvent`]s that the
+//! capture loop polls without blocking.
+
+use std::collections::VecDeque;
+use std::sync::mpsc::{channel, sync_channel, Receiver, SyncSender, TryRecvError, TrySendError};
+use std::thread;
+
+use crate::album_identifier::{identify_wav_clip, songrec_rate_limiter, IdentifiedSong};
+use crate::rate_limiter::RateLimiter;
+use crate::vu_meter::SampleFormat;
+use crate::wavfile::{write_wav_samples, WavSampleFormat};
+
+/// Length of audio kept for identification (seconds)
+pub const SNAPSHOT_SECONDS: f64 = 20.0;
+
+/// Minimum amount of buffered audio before a snapshot is worth identifying
+pub const MIN_SNAPSHOT_SECONDS: f64 = 12.0;
+
+/// Result of a background identification request
+#[derive(Debug, Clone)]
+pub enum SongDetectEvent {
+    /// A song was recognized. `position` is the recording position in seconds
+    /// at which the snapshot was taken.
+    Identified { position: f64, song: IdentifiedSong },
+    /// songrec ran but did not recognize the snapshot
+    NoMatch { position: f64 },
+    /// Identification failed (songrec missing, network error, ...)
+    Error { position: f64, message: String },
+}
+
+/// Fixed-length ring buffer holding the most recent audio per channel
+pub struct SnapshotBuffer {
+    channels: Vec<VecDeque<i32>>,
+    capacity: usize,
+    sample_rate: u32,
+}
+
+impl SnapshotBuffer {
+    pub fn new(sample_rate: u32, num_channels: usize, seconds: f64) -> Self {
+        let capacity = (sample_rate as f64 * seconds) as usize;
+        SnapshotBuffer {
+            channels: (0..num_channels).map(|_| VecDeque::with_capacity(capacity)).collect(),
+            capacity,
+            sample_rate,
+        }
+    }
+
+    /// Append a chunk of per-channel audio, dropping the oldest samples
+    pub fn push(&mut self, audio: &[Vec<i32>]) {
+        for (buf, samples) in self.channels.iter_mut().zip(audio) {
+            // Only the tail of very large chunks can survive
+            let skip = samples.len().saturating_sub(self.capacity);
+            buf.extend(&samples[skip..]);
+            let excess = buf.len().saturating_sub(self.capacity);
+            buf.drain(..excess);
+        }
+    }
+
+    /// Buffered audio length in seconds
+    pub fn seconds(&self) -> f64 {
+        let frames = self.channels.iter().map(|c| c.len()).min().unwrap_or(0);
+        frames as f64 / self.sample_rate as f64
+    }
+
+    /// Copy the buffered audio
+    pub fn snapshot(&self) -> Vec<Vec<i32>> {
+        self.channels.iter().map(|c| c.iter().copied().collect()).collect()
+    }
+
+    pub fn clear(&mut self) {
+        for c in &mut self.channels {
+            c.clear();
+        }
+    }
+}
+
+struct DetectJob {
+    audio: Vec<Vec<i32>>,
+    position: f64,
+}
+
+/// Worker thread that identifies audio snapshots with songrec
+pub struct SongDetector {
+    jobs: Option<SyncSender<DetectJob>>,
+    events: Receiver<SongDetectEvent>,
+    thread_handle: Option<thread::JoinHandle<()>>,
+}
+
+impl SongDetector {
+    pub fn new(sample_rate: u32, format: SampleFormat) -> Self {
+        // Capacity 1: at most one snapshot waits while another is processed
+        let (job_sender, job_receiver) = sync_channel::<DetectJob>(1);
+        let (event_sender, event_receiver) = channel();
+
+        let thread_handle = thread::spawn(move || {
+            let mut rate_limiter = songrec_rate_limiter();
+            // S24In32 holds 24-bit values, written as 24-bit WAV
+            let bits_per_sample = WavSampleFormat::from(format).bits_per_sample();
+
+            for job in job_receiver {
+                let event = Self::identify(&job, sample_rate, bits_per_sample, &mut rate_limiter);
+                if event_sender.send(event).is_err() {
+                    break;
+                }
+            }
+        });
+
+        SongDetector {
+            jobs: Some(job_sender),
+            events: event_receiver,
+            thread_handle: Some(thread_handle),
+        }
+    }
+
+    fn identify(
+        job: &DetectJob,
+        sample_rate: u32,
+        bits_per_sample: u16,
+        rate_limiter: &mut RateLimiter,
+    ) -> SongDetectEvent {
+        let position = job.position;
+        let temp = match tempfile::Builder::new().prefix("autorec_detect_").suffix(".wav").tempfile() {
+            Ok(t) => t,
+            Err(e) => {
+                return SongDetectEvent::Error { position, message: format!("Failed to create temp file: {}", e) };
+            }
+        };
+        let path = temp.path().to_string_lossy().to_string();
+
+        if let Err(message) = write_wav_samples(&path, &job.audio, sample_rate, bits_per_sample) {
+            return SongDetectEvent::Error { position, message };
+        }
+
+        match identify_wav_clip(&path, rate_limiter) {
+            Ok(Some(mut song)) => {
+                song.timestamp = position;
+                SongDetectEvent::Identified { position, song }
+            }
+            Ok(None) => SongDetectEvent::NoMatch { position },
+            Err(message) => SongDetectEvent::Error { position, message },
+        }
+    }
+
+    /// Queue a snapshot for identification without blocking.
+    ///
+    /// Returns false if the worker is still busy and the snapshot was dropped.
+    pub fn submit(&self, audio: Vec<Vec<i32>>, position: f64) -> bool {
+        match self.jobs.as_ref() {
+            Some(jobs) => match jobs.try_send(DetectJob { audio, position }) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) | Err(TrySendError::Disconnected(_)) => false,
+            },
+            None => false,
+        }
+    }
+
+    /// Fetch the next finished result, if any
+    pub fn try_recv(&self) -> Option<SongDetectEvent> {
+        match self.events.try_recv() {
+            Ok(event) => Some(event),
+            Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => None,
+        }
+    }
+}
+
+impl Drop for SongDetector {
+    fn drop(&mut self) {
+        // Closing the job channel ends the worker loop
+        self.jobs.take();
+        if let Some(handle) = self.thread_handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_buffer_keeps_latest_audio() {
+        let mut buf = SnapshotBuffer::new(10, 2, 1.0);
+        buf.push(&[vec![1, 2, 3, 4, 5, 6], vec![-1, -2, -3, -4, -5, -6]]);
+        buf.push(&[vec![7, 8, 9, 10, 11, 12], vec![-7, -8, -9, -10, -11, -12]]);
+
+        let snap = buf.snapshot();
+        assert_eq!(snap[0], vec![3, 4, 5, 6, 7, 8, 9, 10, 11, 12]);
+        assert_eq!(snap[1][0], -3);
+        assert!((buf.seconds() - 1.0).abs() < 1e-9);
+
+        buf.clear();
+        assert_eq!(buf.seconds(), 0.0);
+    }
+
+    #[test]
+    fn test_snapshot_buffer_large_chunk() {
+        let mut buf = SnapshotBuffer::new(4, 1, 1.0);
+        buf.push(&[(0..10).collect()]);
+        assert_eq!(buf.snapshot()[0], vec![6, 7, 8, 9]);
+    }
+}
@@ -0,0 +1,364 @@
+//! Spectrogram pictures of recordings.
+//!
+//! `cue_creator --spectrogram` writes `<recording>.spectrogram.png` next to
+//! the recording: the spectrum over time on top (logarithmic frequency axis,
+//! 20 Hz at the bottom), below it the RMS curve of the boundary analysis.
+//! Green lines mark the groove-in and groove-out, red lines the detected song
+//! boundaries, so a missed or extra boundary shows at a glance where the
+//! numbers of `--dump` need a plot first.
+
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+
+use crate::audio_analysis::{FrameReassembler, WavData};
+use crate::boundary::BoundaryAnalysis;
+use crate::cuefile::wav_base_path;
+use crate::error::Result;
+use crate::progress::{ProgressSink, STAGE_SPECTROGRAM};
+use crate::SampleFormat;
+
+/// Width of the picture in pixels, one column per time slice
+pub const WIDTH: usize = 1600;
+
+/// Height of the spectrum in pixels
+pub const SPECTRUM_HEIGHT: usize = 320;
+
+/// Height of the RMS curve in pixels
+pub const CURVE_HEIGHT: usize = 160;
+
+/// Samples per FFT; 93 ms at 44.1 kHz
+const FFT_SIZE: usize = 4096;
+
+const MIN_FREQUENCY: f64 = 20.0;
+const MAX_FREQUENCY: f64 = 20000.0;
+
+/// Range of the colour scale below the loudest point
+const DYNAMIC_RANGE_DB: f32 = 90.0;
+
+const BACKGROUND: [u8; 3] = [16, 16, 16];
+const MUSIC_BACKGROUND: [u8; 3] = [24, 36, 24];
+const RAW_RMS: [u8; 3] = [96, 96, 96];
+const SMOOTHED_RMS: [u8; 3] = [255, 255, 255];
+const NOISE_FLOOR: [u8; 3] = [48, 80, 160];
+const GROOVE: [u8; 3] = [0, 200, 0];
+const BOUNDARY: [u8; 3] = [255, 64, 64];
+
+/// Colours of the spectrum from silent to loud
+const COLOUR_MAP: [[u8; 3]; 6] = [[0, 0, 0], [32, 0, 96], [160, 0, 128], [240, 120, 0], [255, 230, 80], [255, 255, 255]];
+
+/// Where a recording's spectrogram is written: `<name>.spectrogram.png`
+pub fn spectrogram_path(wav_file: &str) -> PathBuf {
+    PathBuf::from(format!("{}.spectrogram.png", wav_base_path(wav_file).display()))
+}
+
+/// Power per frequency row and time column
+#[derive(Debug, Clone)]
+pub struct Spectrogram {
+    pub columns: usize,
+    pub rows: usize,
+    /// Level in dB, column by column, row 0 is the lowest frequency
+    pub db: Vec<f32>,
+}
+
+impl Spectrogram {
+    pub fn level(&self, column: usize, row: usize) -> f32 {
+        self.db[column * self.rows + row]
+    }
+}
+
+/// Streaming spectrogram of interleaved little-endian PCM, downmixed to mono.
+///
+/// The recording is cut into `columns` time slices; the power spectra of
+/// the FFT blocks starting in a slice are averaged and reduced to `rows`
+/// logarithmically spaced frequency bands.
+pub struct SpectrogramAnalyzer {
+    format: SampleFormat,
+    channels: usize,
+    max_value: f32,
+    reassembler: FrameReassembler,
+    fft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    block: Vec<Complex<f32>>,
+    /// Frames per column
+    column_frames: f64,
+    /// Position of the current block in frames
+    block_start: u64,
+    /// FFT bins averaged into each row
+    row_bins: Vec<(usize, usize)>,
+    power: Vec<f64>,
+    counts: Vec<u32>,
+    columns: usize,
+}
+
+impl SpectrogramAnalyzer {
+    /// Analyzer for `total_frames` frames at `sample_rate`
+    pub fn new(format: SampleFormat, channels: usize, sample_rate: u32, total_frames: u64, columns: usize, rows: usize) -> Self {
+        let columns = columns.max(1);
+        let rows = rows.max(1);
+        let fft = FftPlanner::new().plan_fft_forward(FFT_SIZE);
+        let window = (0..FFT_SIZE)
+            .map(|i| (0.5 - 0.5 * (2.0 * std::f64::consts::PI * i as f64 / FFT_SIZE as f64).cos()) as f32)
+            .collect();
+
+        // Band edges on a log scale; narrow bands at the bottom get the
+        // nearest bin
+        let bin_hz = sample_rate as f64 / FFT_SIZE as f64;
+        let max_frequency = MAX_FREQUENCY.min(sample_rate as f64 / 2.0);
+        let ratio = (max_frequency / MIN_FREQUENCY).ln();
+        let edge = |row: usize| MIN_FREQUENCY * (ratio * row as f64 / rows as f64).exp() / bin_hz;
+        let row_bins = (0..rows)
+            .map(|row| {
+                let low = edge(row).round() as usize;
+                let high = (edge(row + 1).round() as usize).max(low + 1);
+                (low.min(FFT_SIZE / 2), high.min(FFT_SIZE / 2 + 1))
+            })
+            .collect();
+
+        SpectrogramAnalyzer {
+            format,
+            channels: channels.max(1),
+            max_value: format.max_value() as f32,
+            reassembler: FrameReassembler::new(format, channels),
+            fft,
+            window,
+            block: Vec::with_capacity(FFT_SIZE),
+            column_frames: (total_frames.max(1) as f64 / columns as f64).max(1.0),
+            block_start: 0,
+            row_bins,
+            power: vec![0.0; columns * rows],
+            counts: vec![0; columns],
+            columns,
+        }
+    }
+
+    /// Feed interleaved PCM bytes
+    pub fn process_bytes(&mut self, data: &[u8]) {
+        let mut reassembler = std::mem::take(&mut self.reassembler);
+        reassembler.process(data, |frame| self.add_frame(frame));
+        self.reassembler = reassembler;
+    }
+
+    fn add_frame(&mut self, frame: &[u8]) {
+        let sum: f32 = frame
+            .chunks_exact(self.format.bytes_per_sample())
+            .map(|b| self.format.decode_sample(b) as f32 / self.max_value)
+            .sum();
+        self.block.push(Complex::new(sum / self.channels as f32 * self.window[self.block.len()], 0.0));
+        if self.block.len() == FFT_SIZE {
+            self.finish_block();
+        }
+    }
+
+    fn finish_block(&mut self) {
+        self.fft.process(&mut self.block);
+        let column = ((self.block_start as f64 / self.column_frames) as usize).min(self.columns - 1);
+        let rows = self.row_bins.len();
+        let scale = 4.0 / (FFT_SIZE * FFT_SIZE) as f64;
+        for (row, &(low, high)) in self.row_bins.iter().enumerate() {
+            let bins = &self.block[low..high];
+            let sum: f64 = bins.iter().map(|c| c.norm_sqr() as f64).sum();
+            self.power[column * rows + row] += sum / bins.len().max(1) as f64 * scale;
+        }
+        self.counts[column] += 1;
+        self.block_start += FFT_SIZE as u64;
+        self.block.clear();
+    }
+
+    /// Average the columns; columns without a block of their own repeat
+    /// the one before
+    pub fn finish(self) -> Spectrogram {
+        let rows = self.row_bins.len();
+        let mut db = vec![-200.0_f32; self.columns * rows];
+        let mut previous: Option<usize> = None;
+        for column in 0..self.columns {
+            let source = if self.counts[column] > 0 { Some(column) } else { previous };
+            if let Some(source) = source {
+                for row in 0..rows {
+                    let power = self.power[source * rows + row] / self.counts[source] as f64;
+                    db[column * rows + row] = if power > 0.0 { (10.0 * power.log10()) as f32 } else { -200.0 };
+                }
+                previous = Some(source);
+            }
+        }
+        Spectrogram { columns: self.columns, rows, db }
+    }
+}
+
+/// Spectrogram of a whole WAV file with `columns` time slices
+pub fn wav_spectrogram(path: &str, columns: usize, rows: usize, progress: &mut dyn ProgressSink) -> Result<Spectrogram> {
+    let wav = WavData::open(path)?;
+    let mut analyzer = SpectrogramAnalyzer::new(wav.format, wav.channels(), wav.header.sample_rate, wav.frames(), columns, rows);
+    wav.for_each_data_chunk(false, |data, percent| {
+        analyzer.process_bytes(data);
+        progress.progress(percent, STAGE_SPECTROGRAM, "");
+        Ok(())
+    })?;
+    progress.finish(STAGE_SPECTROGRAM);
+    Ok(analyzer.finish())
+}
+
+/// An RGB picture
+#[derive(Debug, Clone)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    fn new(width: usize, height: usize) -> Self {
+        Image { width, height, pixels: vec![0; width * height * 3] }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 3] {
+        let i = (y * self.width + x) * 3;
+        [self.pixels[i], self.pixels[i + 1], self.pixels[i + 2]]
+    }
+
+    fn set(&mut self, x: usize, y: usize, colour: [u8; 3]) {
+        if x < self.width && y < self.height {
+            let i = (y * self.width + x) * 3;
+            self.pixels[i..i + 3].copy_from_slice(&colour);
+        }
+    }
+
+    fn vertical_line(&mut self, x: usize, from: usize, to: usize, colour: [u8; 3]) {
+        for y in from.min(to)..=from.max(to) {
+            self.set(x, y, colour);
+        }
+    }
+
+    /// Write the picture as a PNG file
+    pub fn write_png(&self, path: &Path) -> Result<()> {
+        let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(io::Error::other)?;
+        writer.write_image_data(&self.pixels).map_err(io::Error::other)?;
+        writer.finish().map_err(io::Error::other)?;
+        Ok(())
+    }
+}
+
+/// Colour of `value` between 0 (silent) and 1 (loudest)
+fn colour_map(value: f32) -> [u8; 3] {
+    let position = value.clamp(0.0, 1.0) * (COLOUR_MAP.len() - 1) as f32;
+    let index = (position as usize).min(COLOUR_MAP.len() - 2);
+    let fraction = position - index as f32;
+    let (from, to) = (COLOUR_MAP[index], COLOUR_MAP[index + 1]);
+    [0, 1, 2].map(|c| (from[c] as f32 + (to[c] as f32 - from[c] as f32) * fraction).round() as u8)
+}
+
+/// Draw the spectrum above the RMS curve of `analysis`, with the music
+/// range `music` and the song `boundaries` (seconds) as vertical lines
+pub fn render(spectrogram: &Spectrogram, analysis: &BoundaryAnalysis, music: (f64, f64), boundaries: &[f64]) -> Image {
+    let width = spectrogram.columns;
+    let rows = spectrogram.rows;
+    let mut image = Image::new(width, rows + 1 + CURVE_HEIGHT);
+
+    let loudest = spectrogram.db.iter().copied().fold(f32::MIN, f32::max);
+    for x in 0..width {
+        for row in 0..rows {
+            let value = (spectrogram.level(x, row) - loudest + DYNAMIC_RANGE_DB) / DYNAMIC_RANGE_DB;
+            image.set(x, rows - 1 - row, colour_map(value));
+        }
+    }
+
+    let duration = analysis.file_duration.max(analysis.chunk_duration);
+    let x_of = |seconds: f64| ((seconds / duration * width as f64).max(0.0) as usize).min(width - 1);
+    let top = rows + 1;
+    let bottom = top + CURVE_HEIGHT - 1;
+    let curve_max = analysis.rms_values.iter().copied().fold(analysis.music_level_db, f32::max) + 3.0;
+    let curve_min = analysis.noise_floor_db - 10.0;
+    let y_of = |db: f32| {
+        let fraction = ((db - curve_min) / (curve_max - curve_min).max(1.0)).clamp(0.0, 1.0);
+        bottom - (fraction * (CURVE_HEIGHT - 1) as f32).round() as usize
+    };
+
+    let (music_start, music_end) = (x_of(music.0), x_of(music.1));
+    for x in 0..width {
+        let colour = if x >= music_start && x <= music_end { MUSIC_BACKGROUND } else { BACKGROUND };
+        image.vertical_line(x, top, bottom, colour);
+        image.set(x, y_of(analysis.noise_floor_db), NOISE_FLOOR);
+    }
+
+    // RMS windows falling on each column: raw level as a span, the smoothed
+    // curve as a line through the column means
+    let mut previous: Option<usize> = None;
+    for x in 0..width {
+        let first = ((x as f64 / width as f64 * duration) / analysis.chunk_duration) as usize;
+        let last = (((x + 1) as f64 / width as f64 * duration) / analysis.chunk_duration) as usize;
+        let range = first.min(analysis.rms_values.len())..last.max(first + 1).min(analysis.rms_values.len());
+        if range.is_empty() {
+            continue;
+        }
+        let raw = &analysis.rms_values[range.clone()];
+        let low = raw.iter().copied().fold(f32::MAX, f32::min);
+        let high = raw.iter().copied().fold(f32::MIN, f32::max);
+        image.vertical_line(x, y_of(low), y_of(high), RAW_RMS);
+
+        let smoothed = &analysis.smoothed[range];
+        let y = y_of(smoothed.iter().sum::<f32>() / smoothed.len() as f32);
+        image.vertical_line(x, previous.unwrap_or(y), y, SMOOTHED_RMS);
+        previous = Some(y);
+    }
+
+    for seconds in [music.0, music.1] {
+        image.vertical_line(x_of(seconds), 0, bottom, GROOVE);
+    }
+    for &seconds in boundaries {
+        image.vertical_line(x_of(seconds), 0, bottom, BOUNDARY);
+    }
+    image
+}
+
+/// Analyze `wav_file` and write its spectrogram next to it; returns the path
+/// of the picture
+pub fn export(wav_file: &str, analysis: &BoundaryAnalysis, music: (f64, f64), boundaries: &[f64], progress: &mut dyn ProgressSink) -> Result<PathBuf> {
+    let spectrogram = wav_spectrogram(wav_file, WIDTH, SPECTRUM_HEIGHT, progress)?;
+    let path = spectrogram_path(wav_file);
+    render(&spectrogram, analysis, music, boundaries).write_png(&path)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spectrogram_and_render() {
+        // 1 kHz for a second, then silence, at 8 kHz
+        let rate = 8000;
+        let mut bytes = Vec::new();
+        for i in 0..rate * 2 {
+            let value = if i < rate { (8000.0 * (2.0 * std::f64::consts::PI * 1000.0 * i as f64 / rate as f64).sin()) as i16 } else { 0 };
+            bytes.extend_from_slice(&value.to_le_bytes());
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        let mut analyzer = SpectrogramAnalyzer::new(SampleFormat::S16, 2, rate as u32, rate as u64 * 2, 8, 40);
+        analyzer.process_bytes(&bytes[..1001]);
+        analyzer.process_bytes(&bytes[1001..]);
+        let spectrogram = analyzer.finish();
+        assert_eq!((spectrogram.columns, spectrogram.rows), (8, 40));
+
+        // log(1000 / 20) / log(4000 / 20) of the way up
+        let tone_row = (40.0 * (50.0_f64).ln() / (200.0_f64).ln()) as usize;
+        let loudest = (0..40).max_by(|&a, &b| spectrogram.level(0, a).total_cmp(&spectrogram.level(0, b))).unwrap();
+        assert!(loudest.abs_diff(tone_row) <= 1, "tone in row {}", loudest);
+        assert!(spectrogram.level(0, tone_row) > spectrogram.level(0, 5) + 40.0);
+
+        let rms = [vec![-20.0_f32; 5], vec![-70.0; 5]].concat();
+        let analysis = BoundaryAnalysis::new(rms, 200, 0.6, 2.0, false);
+        let image = render(&spectrogram, &analysis, (0.0, 1.0), &[0.5]);
+        assert_eq!((image.width, image.height), (8, 40 + 1 + CURVE_HEIGHT));
+        assert_eq!(image.pixel(2, 0), BOUNDARY);
+        assert_eq!(image.pixel(4, 45), GROOVE);
+        assert_eq!(colour_map(0.0), [0, 0, 0]);
+        assert_eq!(colour_map(1.0), [255, 255, 255]);
+    }
+}
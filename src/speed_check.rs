@@ -0,0 +1,376 @@
+//! Wrong-speed detection (33⅓ vs 45 RPM, and 78s captured at 45).
+//!
+//! A 45 RPM record played at 33⅓ RPM takes 35% longer than its track list
+//! says; a 33⅓ RPM record played at 45 RPM is 26% shorter. Comparing the
+//! measured durations with the looked-up track lengths shows which case
+//! applies, and [`correct_wav_speed`] resamples a recording by 45/33⅓ (or
+//! 33⅓/45) so it plays at the right speed and pitch. Turntables without a
+//! 78 RPM setting play shellac at 45, which is 73% too long; that is
+//! corrected by 78/45.
+//!
+//! Turntables also run a little off speed, typically by a fraction of a
+//! percent. [`estimate_drift`] measures this from the track boundaries of a
+//! guided detection, and [`drift_rates`] gives the resampling rates that
+//! correct it when the tracks are split.
+
+use std::path::{Path, PathBuf};
+
+use crate::audio_analysis::WavData;
+use crate::error::{Error, Result};
+use crate::resampler::Resampler;
+use crate::wavfile::WavWriter;
+
+pub const RPM_33: f64 = 100.0 / 3.0;
+pub const RPM_45: f64 = 45.0;
+pub const RPM_78: f64 = 78.0;
+
+/// Relative deviation from a speed ratio that still counts as that speed
+pub const TOLERANCE: f64 = 0.05;
+
+/// 45 / 33⅓ as a fraction, used as resampling rates
+const RATIO_NUMERATOR: u32 = 27;
+const RATIO_DENOMINATOR: u32 = 20;
+/// 78 / 45 as a fraction
+const RATIO_78_NUMERATOR: u32 = 26;
+const RATIO_78_DENOMINATOR: u32 = 15;
+
+/// Speed errors smaller than this are not reported as drift
+pub const MIN_DRIFT: f64 = 0.002;
+
+/// Resolution of a drift correction; coarser ratios keep the resampler small
+const DRIFT_STEPS: u32 = 1000;
+
+/// Speed at which a record was played, judged from its durations
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlaybackSpeed {
+    /// Durations match the release
+    Correct,
+    /// A 45 RPM record played at 33⅓ RPM (too slow)
+    Record45At33,
+    /// A 33⅓ RPM record played at 45 RPM (too fast)
+    Record33At45,
+    /// A 78 RPM record played at 45 RPM (much too slow)
+    Record78At45,
+    /// Durations match neither speed
+    Unknown,
+}
+
+impl PlaybackSpeed {
+    /// Measured duration divided by the expected one for this speed
+    pub fn duration_ratio(&self) -> Option<f64> {
+        match self {
+            PlaybackSpeed::Correct => Some(1.0),
+            PlaybackSpeed::Record45At33 => Some(RPM_45 / RPM_33),
+            PlaybackSpeed::Record33At45 => Some(RPM_33 / RPM_45),
+            PlaybackSpeed::Record78At45 => Some(RPM_78 / RPM_45),
+            PlaybackSpeed::Unknown => None,
+        }
+    }
+
+    /// Playback speed of a record made for `record_rpm` that was played at
+    /// `played_rpm`; Unknown if there is no correction for the combination
+    pub fn from_rpm(record_rpm: f64, played_rpm: f64) -> Self {
+        let same = |a: f64, b: f64| (a - b).abs() < 1.0;
+        match (record_rpm, played_rpm) {
+            (r, p) if same(r, p) => PlaybackSpeed::Correct,
+            (r, p) if same(r, RPM_45) && same(p, RPM_33) => PlaybackSpeed::Record45At33,
+            (r, p) if same(r, RPM_33) && same(p, RPM_45) => PlaybackSpeed::Record33At45,
+            (r, p) if same(r, RPM_78) && same(p, RPM_45) => PlaybackSpeed::Record78At45,
+            _ => PlaybackSpeed::Unknown,
+        }
+    }
+
+    pub fn is_wrong(&self) -> bool {
+        matches!(
+            self,
+            PlaybackSpeed::Record45At33 | PlaybackSpeed::Record33At45 | PlaybackSpeed::Record78At45
+        )
+    }
+
+    pub fn describe(&self) -> &'static str {
+        match self {
+            PlaybackSpeed::Correct => "played at the correct speed",
+            PlaybackSpeed::Record45At33 => "45 RPM record played at 33 1/3 RPM",
+            PlaybackSpeed::Record33At45 => "33 1/3 RPM record played at 45 RPM",
+            PlaybackSpeed::Record78At45 => "78 RPM record played at 45 RPM",
+            PlaybackSpeed::Unknown => "durations do not match any playback speed",
+        }
+    }
+}
+
+/// Result of [`check_durations`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpeedCheck {
+    /// Median of measured / expected duration
+    pub ratio: f64,
+    /// Number of durations compared
+    pub compared: usize,
+    pub speed: PlaybackSpeed,
+}
+
+/// Compare measured durations with the expected ones (pairwise, in seconds).
+///
+/// Pairs with a zero or negative duration are skipped; returns None if no
+/// pair is left.
+pub fn check_durations(measured: &[f64], expected: &[f64]) -> Option<SpeedCheck> {
+    let mut ratios: Vec<f64> = measured
+        .iter()
+        .zip(expected)
+        .filter(|(&m, &e)| m > 0.0 && e > 0.0)
+        .map(|(m, e)| m / e)
+        .collect();
+    if ratios.is_empty() {
+        return None;
+    }
+    ratios.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let middle = ratios.len() / 2;
+    let ratio = if ratios.len().is_multiple_of(2) {
+        (ratios[middle - 1] + ratios[middle]) / 2.0
+    } else {
+        ratios[middle]
+    };
+
+    let speed = [
+        PlaybackSpeed::Correct,
+        PlaybackSpeed::Record45At33,
+        PlaybackSpeed::Record33At45,
+        PlaybackSpeed::Record78At45,
+    ]
+    .into_iter()
+    .find(|speed| {
+        let expected = speed.duration_ratio().unwrap();
+        ((ratio - expected) / expected).abs() <= TOLERANCE
+    })
+    .unwrap_or(PlaybackSpeed::Unknown);
+
+    Some(SpeedCheck { ratio, compared: ratios.len(), speed })
+}
+
+/// Small speed error of a turntable, see [`estimate_drift`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpeedDrift {
+    /// Measured positions divided by the expected ones; above 1 the deck
+    /// runs slow and the recording is too long
+    pub ratio: f64,
+    /// RMS deviation of the measured positions from the scaled expected
+    /// ones in seconds
+    pub residual_seconds: f64,
+    /// Number of positions compared
+    pub compared: usize,
+}
+
+impl SpeedDrift {
+    /// Speed error in percent, positive if the deck runs fast
+    pub fn percent(&self) -> f64 {
+        (1.0 / self.ratio - 1.0) * 100.0
+    }
+
+    /// Whether the error is at least [`MIN_DRIFT`]
+    pub fn is_significant(&self) -> bool {
+        (self.ratio - 1.0).abs() >= MIN_DRIFT
+    }
+
+    /// The ratio rounded to the resolution of a correction
+    pub fn correction(&self) -> f64 {
+        (self.ratio * DRIFT_STEPS as f64).round() / DRIFT_STEPS as f64
+    }
+
+    pub fn describe(&self) -> String {
+        if !self.is_significant() {
+            return format!("deck speed within {:.1}%", MIN_DRIFT * 100.0);
+        }
+        let percent = self.percent();
+        format!("deck runs {:.1}% {}", percent.abs(), if percent > 0.0 { "fast" } else { "slow" })
+    }
+}
+
+/// Estimate the speed error of the turntable from positions in the music
+/// (seconds from its start, e.g. track boundaries and the end of the side)
+/// and where the release puts them.
+///
+/// An off-speed deck stretches the whole side by the same factor, so the
+/// measured positions are fitted as a multiple of the expected ones (least
+/// squares). Unlike [`check_durations`] this uses every boundary, and the
+/// residuals tell how well the boundaries follow a single factor. Pairs with
+/// a zero or negative position are skipped; returns None if fewer than two
+/// are left.
+pub fn estimate_drift(measured: &[f64], expected: &[f64]) -> Option<SpeedDrift> {
+    let pairs: Vec<(f64, f64)> = measured
+        .iter()
+        .zip(expected)
+        .filter(|(&m, &e)| m > 0.0 && e > 0.0)
+        .map(|(&m, &e)| (m, e))
+        .collect();
+    if pairs.len() < 2 {
+        return None;
+    }
+    let ratio = pairs.iter().map(|(m, e)| m * e).sum::<f64>() / pairs.iter().map(|(_, e)| e * e).sum::<f64>();
+    let residual = pairs.iter().map(|(m, e)| (m - ratio * e).powi(2)).sum::<f64>() / pairs.len() as f64;
+    Some(SpeedDrift {
+        ratio,
+        residual_seconds: residual.sqrt(),
+        compared: pairs.len(),
+    })
+}
+
+/// Input and output rate for a [`Resampler`] that converts `input_rate` to
+/// `output_rate` and also shortens the audio by the drift `ratio`.
+///
+/// Only the ratio of the two rates matters to the resampler. The drift is
+/// rounded to [`SpeedDrift::correction`], which keeps the reduced rates, and
+/// with them the resampler's FFT, small.
+pub fn drift_rates(input_rate: u32, output_rate: u32, ratio: f64) -> (u32, u32) {
+    let steps = (ratio * DRIFT_STEPS as f64).round() as u64;
+    let input = input_rate as u64 * steps;
+    let output = output_rate as u64 * DRIFT_STEPS as u64;
+    let divisor = gcd(input, output);
+    ((input / divisor) as u32, (output / divisor) as u32)
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// Path of the speed-corrected copy of a recording (`name.speed.wav`)
+pub fn corrected_path(wav_file: &str) -> PathBuf {
+    let path = Path::new(wav_file);
+    let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    path.with_file_name(format!("{}.speed.wav", stem))
+}
+
+/// Whether a file is a copy written to [`corrected_path`] rather than a recording
+pub fn is_speed_corrected(path: &Path) -> bool {
+    path.to_str().is_some_and(|p| p.ends_with(".speed.wav"))
+}
+
+/// Resampler that changes the playback speed of `speed` to the correct one
+fn speed_resampler(speed: PlaybackSpeed, channels: usize) -> Result<Resampler> {
+    // Output length = input length * output rate / input rate
+    match speed {
+        PlaybackSpeed::Record45At33 => Resampler::new(RATIO_NUMERATOR, RATIO_DENOMINATOR, channels),
+        PlaybackSpeed::Record33At45 => Resampler::new(RATIO_DENOMINATOR, RATIO_NUMERATOR, channels),
+        PlaybackSpeed::Record78At45 => Resampler::new(RATIO_78_NUMERATOR, RATIO_78_DENOMINATOR, channels),
+        _ => Err(Error::Config(format!("No speed correction for: {}", speed.describe()))),
+    }
+}
+
+fn write_frames(writer: &mut WavWriter, audio: &[Vec<i32>]) -> Result<()> {
+    let frames = audio.iter().map(|ch| ch.len()).min().unwrap_or(0);
+    let mut interleaved = Vec::with_capacity(frames * audio.len());
+    for frame in 0..frames {
+        interleaved.extend(audio.iter().map(|ch| ch[frame]));
+    }
+    writer.write_samples(&interleaved)?;
+    Ok(())
+}
+
+/// Write a copy of `input` played at the correct speed to `output`.
+///
+/// Sample rate, channels and sample format are kept; only the length (and
+/// pitch) changes.
+pub fn correct_wav_speed(input: &str, output: &str, speed: PlaybackSpeed) -> Result<()> {
+    let wav = WavData::open(input)?;
+    let channels = wav.channels();
+    let mut resampler = speed_resampler(speed, channels)?;
+    let mut writer = WavWriter::new_rf64(output, wav.header.sample_rate, wav.header.num_channels, wav.format)?;
+    let (format, frame_bytes) = (wav.format, wav.frame_bytes());
+    wav.for_each_data_chunk(false, |data, _| {
+        let mut audio = vec![Vec::with_capacity(data.len() / frame_bytes); channels];
+        for (i, sample) in format.decode_samples(data).into_iter().enumerate() {
+            audio[i % channels].push(sample);
+        }
+        write_frames(&mut writer, &resampler.process(&audio)?)
+    })?;
+    write_frames(&mut writer, &resampler.flush()?)?;
+    writer.finalize()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wavfile::{read_wav_segment, write_wav_samples};
+
+    #[test]
+    fn test_check_durations() {
+        // Side of a 45 played at 33: every track 35% longer
+        let expected = [180.0, 200.0, 150.0];
+        let measured: Vec<f64> = expected.iter().map(|e| e * 1.35).collect();
+        let check = check_durations(&measured, &expected).unwrap();
+        assert_eq!(check.speed, PlaybackSpeed::Record45At33);
+        assert_eq!(check.compared, 3);
+        assert!((check.ratio - 1.35).abs() < 1e-9);
+
+        // One badly detected boundary does not change the median
+        let check = check_durations(&[134.0, 110.0, 300.0], &[180.0, 150.0, 200.0]).unwrap();
+        assert_eq!(check.speed, PlaybackSpeed::Record33At45);
+
+        assert_eq!(check_durations(&[1210.0], &[1200.0]).unwrap().speed, PlaybackSpeed::Correct);
+        assert_eq!(check_durations(&[1500.0], &[1200.0]).unwrap().speed, PlaybackSpeed::Unknown);
+        assert!(check_durations(&[0.0], &[1200.0]).is_none());
+
+        // A 78 captured at 45
+        let check = check_durations(&[312.0, 286.0], &[180.0, 165.0]).unwrap();
+        assert_eq!(check.speed, PlaybackSpeed::Record78At45);
+        assert_eq!(PlaybackSpeed::from_rpm(RPM_78, RPM_45), PlaybackSpeed::Record78At45);
+        assert_eq!(PlaybackSpeed::from_rpm(RPM_45, 33.0), PlaybackSpeed::Record45At33);
+        assert_eq!(PlaybackSpeed::from_rpm(RPM_78, 78.0), PlaybackSpeed::Correct);
+        assert_eq!(PlaybackSpeed::from_rpm(RPM_78, RPM_33), PlaybackSpeed::Unknown);
+        assert_eq!(corrected_path("/rec/side_a.wav"), PathBuf::from("/rec/side_a.speed.wav"));
+        assert!(is_speed_corrected(&corrected_path("/rec/side_a.wav")));
+        assert!(!is_speed_corrected(Path::new("/rec/side_a.wav")));
+    }
+
+    #[test]
+    fn test_estimate_drift() {
+        // Deck 0.7% slow: every position 1/0.993 later, with some jitter
+        let expected = [180.0, 400.0, 610.0, 850.0, 1100.0];
+        let jitter = [0.8, -1.1, 0.5, -0.4, 0.0];
+        let measured: Vec<f64> = expected.iter().zip(jitter).map(|(e, j)| e / 0.993 + j).collect();
+        let drift = estimate_drift(&measured, &expected).unwrap();
+        assert_eq!(drift.compared, 5);
+        assert!((drift.percent() + 0.7).abs() < 0.05, "{}", drift.percent());
+        assert!(drift.residual_seconds < 1.0, "{}", drift.residual_seconds);
+        assert!(drift.is_significant());
+        assert_eq!(drift.describe(), "deck runs 0.7% slow");
+        assert_eq!(drift.correction(), 1.007);
+
+        let exact = estimate_drift(&[300.0, 600.0], &[300.0, 600.0]).unwrap();
+        assert_eq!(exact.describe(), "deck speed within 0.2%");
+        assert!(estimate_drift(&[300.0, 0.0], &[300.0, 600.0]).is_none());
+
+        // 96 kHz to 44.1 kHz, 0.7% shorter
+        let (input, output) = drift_rates(96000, 44100, drift.ratio);
+        assert_eq!((input, output), (8056, 3675));
+        assert_eq!(drift_rates(48000, 48000, 1.0), (1, 1));
+    }
+
+    #[test]
+    fn test_correct_wav_speed() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("slow.wav").to_string_lossy().to_string();
+        let output = dir.path().join("fixed.wav").to_string_lossy().to_string();
+        let rate = 8000;
+        // 2.7 s of a 100 Hz tone that should be 135 Hz
+        let tone: Vec<i32> = (0..21600)
+            .map(|n| ((2.0 * std::f64::consts::PI * 100.0 * n as f64 / rate as f64).sin() * 10000.0) as i32)
+            .collect();
+        write_wav_samples(&input, &[tone.clone(), tone], rate, 16).unwrap();
+
+        correct_wav_speed(&input, &output, PlaybackSpeed::Record45At33).unwrap();
+        let (header, audio) = read_wav_segment(&output, 0.0, 10.0).unwrap();
+        assert_eq!(header.sample_rate, rate);
+        assert_eq!(header.num_channels, 2);
+        assert_eq!(audio[0].len(), 16000);
+
+        // Count zero crossings in the settled middle second: 135 Hz
+        let crossings = audio[0][4000..12000].windows(2).filter(|w| (w[0] < 0) != (w[1] < 0)).count();
+        assert!((crossings as i32 - 270).abs() <= 2, "{} crossings", crossings);
+
+        assert!(correct_wav_speed(&input, &output, PlaybackSpeed::Correct).is_err());
+    }
+}
@@ -0,0 +1,922 @@
+//! Split a recording into one file per track using its CUE sheet.
+//!
+//! Audio is copied without conversion into WAV files, or encoded to FLAC or
+//! MP3 by `ffmpeg`. Each output file is tagged (see [`crate::tagging`]) with
+//! title, artist, album, track number, year and release IDs from the CUE sheet.
+//!
+//! A [`Delivery`] format converts the tracks on the way, e.g. from a
+//! 96 kHz/24-bit master to 44.1 kHz/16-bit files with dither, or to mono
+//! for 78s, which were recorded in mono and only gain noise from a second
+//! channel. Mid/side recordings (see [`crate::downmix`]) are decoded to
+//! left and right.
+//!
+//! FLAC and MP3 files also get ReplayGain tags, measured on the recording
+//! with [`crate::loudness`]; the album values cover all tracks of the sheet.
+//! With [`Normalize`] the gain is applied to the audio instead.
+//!
+//! [`Trim`] cuts the silent run-in and run-out groove down to a short pad:
+//! the first track then starts just before groove-in (INDEX 01 of track 1)
+//! and the last one ends just after groove-out (`REM GROOVE_OUT`). The
+//! needle-drop thump in the run-in (`REM NEEDLE_DROP`) can be filtered out
+//! of the first track, leaving only the groove noise. A turntable that ran a
+//! little off speed (`REM SPEED_CORRECTION`) can be corrected by resampling
+//! the tracks, which changes their length and pitch.
+
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::cuefile::CueSheet;
+use crate::boundary::NEEDLE_DROP_CUTOFF_HZ;
+use crate::downmix::Downmix;
+use crate::filename_template::{self, FilenameTemplate, TemplateValues};
+use crate::filters::subsonic::SubsonicFilter;
+use crate::filters::AudioFilter;
+use crate::loudness::{self, LoudnessAnalysis};
+use crate::progress::{ProgressSink, STAGE_SPLIT};
+use crate::resampler::{Requantizer, Resampler};
+use crate::speed_check;
+use crate::tagging::{self, OutputFormat, ReplayGain, TrackTags};
+use crate::vu_meter::SampleFormat;
+use crate::wavfile::{read_channel_names, read_wav_header, WavSampleFormat, WavWriter};
+
+pub use crate::filename_template::sanitize_for_filename;
+
+/// Frames copied per read
+const COPY_FRAMES: usize = 65536;
+
+/// Loudness normalization never raises the true peak above this, in dBTP
+pub const NORMALIZE_PEAK_CEILING_DB: f64 = -1.0;
+
+/// Groove noise kept around groove-in and groove-out by default, in seconds
+pub const DEFAULT_TRIM_PAD_SECONDS: f64 = 1.0;
+
+/// Crossfade into and out of the needle-drop filter, in seconds
+const NEEDLE_DROP_FADE_SECONDS: f64 = 0.02;
+
+/// Gain applied to the track files
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Normalize {
+    /// Audio is copied at its recorded level
+    Off,
+    /// One gain for all tracks of the sheet, keeping their relative levels
+    Album,
+    /// Each track gets its own gain
+    Track,
+}
+
+impl Normalize {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "off" | "none" => Some(Normalize::Off),
+            "album" => Some(Normalize::Album),
+            "track" => Some(Normalize::Track),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Normalize::Off => "off",
+            Normalize::Album => "album",
+            Normalize::Track => "track",
+        }
+    }
+}
+
+/// Sample rate and bit depth of the track files, if they should differ
+/// from the recording, and their names
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delivery {
+    pub sample_rate: Option<u32>,
+    pub bits_per_sample: Option<u16>,
+    /// Add TPDF dither when the bit depth is reduced
+    pub dither: bool,
+    /// Write ReplayGain tags (FLAC and MP3 only)
+    pub replay_gain: bool,
+    pub normalize: Normalize,
+    /// Normalize the true peak to this level in dBTP instead of the
+    /// loudness to the ReplayGain reference (-18 LUFS)
+    pub peak_target: Option<f64>,
+    /// Average the channels into one
+    pub mono: bool,
+    /// Filter the needle-drop thump (REM NEEDLE_DROP) out of the first track
+    pub attenuate_needle_drop: bool,
+    /// Correct the turntable drift (REM SPEED_CORRECTION) by resampling
+    pub correct_speed: bool,
+    /// Names of the track files, relative to the output directory
+    pub filenames: FilenameTemplate,
+}
+
+impl Default for Delivery {
+    fn default() -> Self {
+        Delivery {
+            sample_rate: None,
+            bits_per_sample: None,
+            dither: true,
+            replay_gain: true,
+            normalize: Normalize::Off,
+            peak_target: None,
+            mono: false,
+            attenuate_needle_drop: false,
+            correct_speed: false,
+            filenames: FilenameTemplate::track(),
+        }
+    }
+}
+
+impl Delivery {
+    /// 44.1 kHz, 16 bit with dither
+    pub fn cd() -> Self {
+        Delivery {
+            sample_rate: Some(44100),
+            bits_per_sample: Some(16),
+            dither: true,
+            replay_gain: true,
+            normalize: Normalize::Off,
+            peak_target: None,
+            mono: false,
+            attenuate_needle_drop: false,
+            correct_speed: false,
+            filenames: FilenameTemplate::track(),
+        }
+    }
+}
+
+/// What to do with the gap between INDEX 00 and INDEX 01 of a track
+/// (and with the lead-in before the first track)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GapMode {
+    /// Gap belongs to the end of the previous track; lead-in is dropped
+    Append,
+    /// Gap belongs to the start of the next track; lead-in goes to track 1
+    Prepend,
+    /// Gap and lead-in are dropped
+    Discard,
+}
+
+impl GapMode {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "append" | "previous" => Some(GapMode::Append),
+            "prepend" | "next" => Some(GapMode::Prepend),
+            "discard" | "drop" => Some(GapMode::Discard),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GapMode::Append => "append",
+            GapMode::Prepend => "prepend",
+            GapMode::Discard => "discard",
+        }
+    }
+}
+
+/// Removal of the run-in and run-out groove
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Trim {
+    /// Start the first track at groove-in instead of 0:00
+    pub lead_in: bool,
+    /// End the last track at groove-out instead of the end of the file
+    pub lead_out: bool,
+    /// Groove noise kept before groove-in and after groove-out, in seconds
+    pub pad: f64,
+}
+
+impl Default for Trim {
+    fn default() -> Self {
+        Trim { lead_in: false, lead_out: false, pad: DEFAULT_TRIM_PAD_SECONDS }
+    }
+}
+
+/// Part of the recording that becomes one output file
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackSegment {
+    pub number: u32,
+    pub title: String,
+    pub performer: String,
+    /// Start in seconds
+    pub start: f64,
+    /// End in seconds, None for the end of the file
+    pub end: Option<f64>,
+}
+
+impl TrackSegment {
+    /// Output file name, e.g. `03 - Artist - Title.wav`
+    pub fn filename(&self) -> String {
+        self.filename_as(OutputFormat::Wav)
+    }
+
+    /// Output file name with the extension of `format`
+    pub fn filename_as(&self, format: OutputFormat) -> String {
+        self.path_from(&FilenameTemplate::track(), &CueSheet::default(), format)
+    }
+
+    /// Output path relative to the output directory, named by `template`
+    /// with the album data of `sheet`
+    pub fn path_from(&self, template: &FilenameTemplate, sheet: &CueSheet, format: OutputFormat) -> String {
+        let values = TemplateValues {
+            artist: Some(self.performer.clone()),
+            album: sheet.title.clone(),
+            album_artist: sheet.performer.clone(),
+            title: Some(self.title.clone()),
+            ext: Some(format.extension().to_string()),
+            track: Some(self.number),
+            side: sheet.file.as_deref().and_then(filename_template::side_number),
+            year: sheet.release.year,
+            date: Some(chrono::Local::now().naive_local()),
+            ..Default::default()
+        };
+        template.render(&values)
+    }
+
+    /// Tags for this track's file
+    pub fn tags(&self, sheet: &CueSheet) -> TrackTags {
+        TrackTags {
+            title: Some(self.title.clone()),
+            artist: Some(self.performer.clone()).filter(|p| !p.is_empty()),
+            album: sheet.title.clone(),
+            album_artist: sheet.performer.clone(),
+            track_number: Some(self.number),
+            track_total: Some(sheet.tracks.len() as u32),
+            release: sheet.release.clone(),
+            replay_gain: None,
+        }
+    }
+}
+
+/// ReplayGain of `segment` after `applied_db` of gain, with the album
+/// values taken from `album`
+fn segment_replay_gain(
+    analysis: &LoudnessAnalysis,
+    segment: &TrackSegment,
+    album: (f64, Option<f64>),
+    applied_db: f64,
+) -> Option<ReplayGain> {
+    let track = analysis.segment(segment.start, segment.end);
+    let album = analysis.segment(album.0, album.1);
+    let factor = 10.0_f64.powf(applied_db / 20.0);
+    Some(ReplayGain {
+        track_gain: track.replay_gain()? - applied_db,
+        track_peak: track.true_peak * factor,
+        album_gain: album.replay_gain().map(|g| g - applied_db),
+        album_peak: album.replay_gain().map(|_| album.true_peak * factor),
+    })
+}
+
+/// Gain in dB that `delivery` applies to `segment`
+fn normalization_gain(
+    analysis: &LoudnessAnalysis,
+    segment: &TrackSegment,
+    album: (f64, Option<f64>),
+    delivery: &Delivery,
+) -> f64 {
+    let loudness = match delivery.normalize {
+        Normalize::Off => return 0.0,
+        Normalize::Album => analysis.segment(album.0, album.1),
+        Normalize::Track => analysis.segment(segment.start, segment.end),
+    };
+    if loudness.true_peak <= 0.0 {
+        return 0.0;
+    }
+    let peak_db = loudness.true_peak_db();
+    match delivery.peak_target {
+        Some(target) => target - peak_db,
+        None => loudness
+            .replay_gain()
+            .map_or(0.0, |gain| gain.min(NORMALIZE_PEAK_CEILING_DB - peak_db)),
+    }
+}
+
+/// Compute the segment of every track in the CUE sheet
+pub fn plan_tracks(sheet: &CueSheet, gap_mode: GapMode) -> Vec<TrackSegment> {
+    let album_performer = sheet.performer.clone().unwrap_or_default();
+    let tracks = &sheet.tracks;
+    let mut segments = Vec::with_capacity(tracks.len());
+
+    for (i, track) in tracks.iter().enumerate() {
+        let gap_start = track.pregap_start.unwrap_or(track.start).min(track.start);
+        let start = match gap_mode {
+            GapMode::Prepend if i == 0 => 0.0,
+            GapMode::Prepend => gap_start,
+            GapMode::Append | GapMode::Discard => track.start,
+        };
+
+        let end = tracks.get(i + 1).map(|next| {
+            let next_gap_start = next.pregap_start.unwrap_or(next.start).min(next.start);
+            match gap_mode {
+                GapMode::Append => next.start,
+                GapMode::Prepend | GapMode::Discard => next_gap_start,
+            }
+        });
+
+        segments.push(TrackSegment {
+            number: track.number,
+            title: track.title.clone().unwrap_or_else(|| format!("Track {}", track.number)),
+            performer: track.performer.clone().unwrap_or_else(|| album_performer.clone()),
+            start,
+            end,
+        });
+    }
+
+    segments
+}
+
+/// Apply `trim` to the first and last of the planned `segments`.
+///
+/// Fails without changing anything if the lead-out should be trimmed but
+/// the sheet has no groove-out.
+pub fn trim_tracks(segments: &mut [TrackSegment], sheet: &CueSheet, trim: &Trim) -> Result<(), String> {
+    if trim.lead_out && sheet.groove_out.is_none() {
+        return Err("CUE sheet has no groove-out (REM GROOVE_OUT)".to_string());
+    }
+    let (Some(first_track), Some(last)) = (sheet.tracks.first(), segments.len().checked_sub(1)) else {
+        return Ok(());
+    };
+    if trim.lead_in {
+        segments[0].start = (first_track.start - trim.pad).max(0.0);
+    }
+    if let Some(groove_out) = sheet.groove_out.filter(|_| trim.lead_out) {
+        let end = (groove_out + trim.pad).max(segments[last].start);
+        segments[last].end = Some(segments[last].end.map_or(end, |e| e.min(end)));
+    }
+    Ok(())
+}
+
+/// Encode a WAV file to FLAC or MP3 with ffmpeg
+fn encode(wav_path: &Path, output: &Path, format: OutputFormat) -> Result<(), String> {
+    let codec_args: &[&str] = match format {
+        OutputFormat::Flac => &["-c:a", "flac"],
+        OutputFormat::Mp3 => &["-c:a", "libmp3lame", "-b:a", "320k"],
+        OutputFormat::Wav => return Err("WAV files are not encoded".to_string()),
+    };
+    let result = Command::new("ffmpeg")
+        .args(["-hide_banner", "-loglevel", "error", "-y", "-i"])
+        .arg(wav_path)
+        // Tags are written by the tagging module
+        .args(["-map_metadata", "-1"])
+        .args(codec_args)
+        .arg(output)
+        .output()
+        .map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    if !result.status.success() {
+        return Err(format!("ffmpeg failed: {}", String::from_utf8_lossy(&result.stderr).trim()));
+    }
+    Ok(())
+}
+
+/// High-pass over the needle-drop thump, which removes its low frequencies
+/// and keeps the groove noise
+struct NeedleDropFilter {
+    /// Frames of the thump, counted from the start of the track
+    start: u64,
+    end: u64,
+    /// Length of the crossfades in frames
+    fade: u64,
+    /// Frames processed so far
+    position: u64,
+    filters: Vec<SubsonicFilter>,
+}
+
+impl NeedleDropFilter {
+    fn new(sample_rate: u32, channels: usize, start: u64, end: u64) -> Self {
+        NeedleDropFilter {
+            start,
+            end,
+            fade: (NEEDLE_DROP_FADE_SECONDS * sample_rate as f64) as u64,
+            position: 0,
+            filters: vec![SubsonicFilter::new(sample_rate, NEEDLE_DROP_CUTOFF_HZ); channels],
+        }
+    }
+
+    /// Filter interleaved samples, fading in the filter before the thump and
+    /// out after it
+    fn process(&mut self, samples: &mut [i32], max: f64) {
+        let channels = self.filters.len();
+        for frame in samples.chunks_exact_mut(channels) {
+            let position = self.position;
+            self.position += 1;
+            if position + self.fade < self.start || position >= self.end + self.fade {
+                continue;
+            }
+            let mix = if position < self.start {
+                1.0 - (self.start - position) as f64 / self.fade as f64
+            } else if position >= self.end {
+                1.0 - (position - self.end) as f64 / self.fade as f64
+            } else {
+                1.0
+            };
+            for (sample, filter) in frame.iter_mut().zip(&mut self.filters) {
+                let mut y = [*sample as f64];
+                filter.process(&mut y);
+                *sample = (*sample as f64 * (1.0 - mix) + y[0] * mix).round().clamp(-max - 1.0, max) as i32;
+            }
+        }
+    }
+}
+
+/// Sample-rate and bit-depth conversion, downmix and gain of one track
+struct Converter {
+    input_format: SampleFormat,
+    channels: usize,
+    /// Decode a mid/side recording to left and right first
+    mid_side: bool,
+    /// Average the channels of each frame
+    mono: bool,
+    /// Filter the needle-drop thump, before anything else
+    needle_drop: Option<NeedleDropFilter>,
+    resampler: Option<Resampler>,
+    /// Linear gain, applied before requantization
+    gain: f64,
+    requantizer: Requantizer,
+}
+
+impl Converter {
+    fn new(
+        input_rate: u32,
+        output_rate: u32,
+        channels: usize,
+        input_format: WavSampleFormat,
+        output_format: WavSampleFormat,
+        delivery: &Delivery,
+        gain_db: f64,
+    ) -> Result<Self, String> {
+        let input_bits = input_format.bits_per_sample();
+        let resampler = if input_rate != output_rate {
+            Some(Resampler::new(input_rate, output_rate, channels).map_err(|e| e.to_string())?)
+        } else {
+            None
+        };
+        Ok(Converter {
+            input_format: SampleFormat::from_bits(input_bits)
+                .ok_or_else(|| format!("Unsupported bits per sample: {}", input_bits))?,
+            channels,
+            mid_side: false,
+            mono: delivery.mono,
+            needle_drop: None,
+            resampler,
+            gain: 10.0_f64.powf(gain_db / 20.0),
+            requantizer: Requantizer::new(input_bits, output_format.bits_per_sample(), delivery.dither),
+        })
+    }
+
+    /// Convert raw PCM data to interleaved output samples
+    fn process(&mut self, data: &[u8]) -> Result<Vec<i32>, String> {
+        let mut samples = self.input_format.decode_samples(data);
+        if let Some(filter) = self.needle_drop.as_mut() {
+            filter.process(&mut samples, self.input_format.max_value() - 1.0);
+        }
+        match self.resampler.as_mut() {
+            Some(resampler) => {
+                let mut channels = vec![Vec::with_capacity(samples.len() / self.channels); self.channels];
+                for (i, sample) in samples.into_iter().enumerate() {
+                    channels[i % self.channels].push(sample);
+                }
+                let resampled = resampler.process(&channels).map_err(|e| e.to_string())?;
+                Ok(self.finish(&resampled))
+            }
+            None => Ok(self.finish_interleaved(samples)),
+        }
+    }
+
+    /// Output still held back by the resampler
+    fn flush(&mut self) -> Result<Vec<i32>, String> {
+        match self.resampler.as_mut() {
+            Some(resampler) => {
+                let resampled = resampler.flush().map_err(|e| e.to_string())?;
+                Ok(self.finish(&resampled))
+            }
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn finish(&mut self, channels: &[Vec<i32>]) -> Vec<i32> {
+        let frames = channels.iter().map(|ch| ch.len()).min().unwrap_or(0);
+        let mut interleaved = Vec::with_capacity(frames * channels.len());
+        for frame in 0..frames {
+            for channel in channels {
+                interleaved.push(channel[frame]);
+            }
+        }
+        self.finish_interleaved(interleaved)
+    }
+
+    fn finish_interleaved(&mut self, mut samples: Vec<i32>) -> Vec<i32> {
+        if self.mid_side {
+            Downmix::decode_mid_side(&mut samples, (self.input_format.max_value() - 1.0) as i32);
+        }
+        if self.mono && self.channels > 1 {
+            samples = samples
+                .chunks_exact(self.channels)
+                .map(|frame| (frame.iter().map(|&s| s as i64).sum::<i64>() / self.channels as i64) as i32)
+                .collect();
+        }
+        if self.gain != 1.0 {
+            let max = self.input_format.max_value();
+            for sample in samples.iter_mut() {
+                *sample = (*sample as f64 * self.gain).round().clamp(-max - 1.0, max) as i32;
+            }
+        }
+        self.requantizer.process(&mut samples);
+        samples
+    }
+}
+
+/// Split `wav_path` into the planned `segments` (see [`plan_tracks`]) of
+/// `sheet`, one file per track in `output_dir`.
+///
+/// Returns the paths of the written files. Progress through the recording
+/// is reported to `progress` as the stage [`STAGE_SPLIT`].
+pub fn split_wav(
+    wav_path: &str,
+    sheet: &CueSheet,
+    segments: &[TrackSegment],
+    output_dir: &Path,
+    format: OutputFormat,
+    delivery: &Delivery,
+    progress: &mut dyn ProgressSink,
+) -> Result<Vec<PathBuf>, String> {
+    if segments.is_empty() {
+        return Err("CUE sheet contains no tracks".to_string());
+    }
+
+    let file = File::open(wav_path).map_err(|e| format!("Failed to open {}: {}", wav_path, e))?;
+    let mut reader = BufReader::new(file);
+    let header = read_wav_header(&mut reader)?;
+    let sample_format = WavSampleFormat::from_bits(header.bits_per_sample)
+        .ok_or_else(|| format!("Unsupported bits per sample: {}", header.bits_per_sample))?;
+    let data_start = reader.stream_position().map_err(|e| format!("Seek error: {}", e))?;
+
+    let bytes_per_frame = sample_format.bytes_per_sample() * header.num_channels as usize;
+    let total_frames = header.data_size / bytes_per_frame as u64;
+    let rate = header.sample_rate as f64;
+
+    let out_rate = delivery.sample_rate.unwrap_or(header.sample_rate);
+    let out_format = match delivery.bits_per_sample {
+        Some(bits) => WavSampleFormat::from_bits(bits)
+            .ok_or_else(|| format!("Unsupported output bits per sample: {}", bits))?,
+        None => sample_format,
+    };
+    let out_channels = if delivery.mono { 1 } else { header.num_channels };
+    // Mid/side recordings are split into left and right
+    let mid_side = Downmix::from_channel_names(&read_channel_names(wav_path).unwrap_or_default()) == Downmix::MidSide;
+    let speed_correction = sheet.speed_correction.filter(|_| delivery.correct_speed);
+    let convert = out_rate != header.sample_rate
+        || out_format != sample_format
+        || out_channels != header.num_channels
+        || mid_side
+        || speed_correction.is_some();
+    // Only the ratio of the rates matters to the resampler
+    let (resample_in, resample_out) = match speed_correction {
+        Some(ratio) => speed_check::drift_rates(header.sample_rate, out_rate, ratio),
+        None => (header.sample_rate, out_rate),
+    };
+
+    std::fs::create_dir_all(output_dir)
+        .map_err(|e| format!("Failed to create {}: {}", output_dir.display(), e))?;
+
+    let album = (segments[0].start, segments[segments.len() - 1].end);
+    let tag_replay_gain = delivery.replay_gain && format != OutputFormat::Wav;
+    let analysis = if tag_replay_gain || delivery.normalize != Normalize::Off {
+        Some(loudness::analyze_wav(wav_path, progress).map_err(|e| format!("Loudness analysis failed: {}", e))?)
+    } else {
+        None
+    };
+
+    let mut written = Vec::new();
+    let mut buffer = vec![0u8; COPY_FRAMES * bytes_per_frame];
+
+    for (index, segment) in segments.iter().enumerate() {
+        let start_frame = ((segment.start * rate).round() as u64).min(total_frames);
+        let end_frame = segment.end
+            .map(|e| ((e * rate).round() as u64).min(total_frames))
+            .unwrap_or(total_frames);
+        if end_frame <= start_frame {
+            return Err(format!("Track {} is empty or beyond the end of the file", segment.number));
+        }
+
+        let filename = segment.path_from(&delivery.filenames, sheet, format);
+        let out_path = output_dir.join(&filename);
+        if let Some(parent) = out_path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let gain_db = analysis.as_ref().map_or(0.0, |a| normalization_gain(a, segment, album, delivery));
+        let mut tags = segment.tags(sheet);
+        if tag_replay_gain {
+            tags.replay_gain = analysis.as_ref().and_then(|a| segment_replay_gain(a, segment, album, gain_db));
+        }
+        // Encoded formats go through a temporary WAV file
+        let wav_out = match format {
+            OutputFormat::Wav => out_path.clone(),
+            _ => output_dir.join(format!(".{}.tmp.wav", segment.number)),
+        };
+        let out_str = wav_out.to_string_lossy().to_string();
+        let mut writer = WavWriter::new(&out_str, out_rate, out_channels, out_format)
+            .map_err(|e| format!("Failed to create {}: {}", out_str, e))?;
+        writer.set_info(tags.to_wav_info());
+
+        reader.seek(SeekFrom::Start(data_start + start_frame * bytes_per_frame as u64))
+            .map_err(|e| format!("Seek error: {}", e))?;
+        let mut remaining = (end_frame - start_frame) as usize * bytes_per_frame;
+        // The thump lies in the run-in, before the first track's music
+        let needle_drop = sheet.needle_drop
+            .filter(|_| delivery.attenuate_needle_drop && index == 0)
+            .map(|(start, end)| (((start * rate).round() as u64).min(total_frames), ((end * rate).round() as u64).min(total_frames)))
+            .filter(|&(start, end)| end > start_frame && start < end_frame)
+            .map(|(start, end)| NeedleDropFilter::new(header.sample_rate, header.num_channels as usize,
+                                                      start.saturating_sub(start_frame), end - start_frame));
+        let mut converter = if convert || gain_db != 0.0 || needle_drop.is_some() {
+            let mut converter = Converter::new(resample_in, resample_out, header.num_channels as usize, sample_format, out_format, delivery, gain_db)?;
+            converter.mid_side = mid_side;
+            converter.needle_drop = needle_drop;
+            Some(converter)
+        } else {
+            None
+        };
+        while remaining > 0 {
+            let n = remaining.min(buffer.len());
+            reader.read_exact(&mut buffer[..n])
+                .map_err(|e| format!("Failed to read audio data: {}", e))?;
+            match converter.as_mut() {
+                Some(converter) => {
+                    let samples = converter.process(&buffer[..n])?;
+                    writer.write_samples(&samples)
+                }
+                None => writer.write_raw(&buffer[..n]),
+            }
+            .map_err(|e| format!("Failed to write {}: {}", out_str, e))?;
+            remaining -= n;
+            let position = end_frame - (remaining / bytes_per_frame) as u64;
+            progress.progress(position as f32 * 100.0 / total_frames as f32, STAGE_SPLIT, &filename);
+        }
+        if let Some(mut converter) = converter {
+            let samples = converter.flush()?;
+            writer.write_samples(&samples).map_err(|e| format!("Failed to write {}: {}", out_str, e))?;
+        }
+        writer.finalize().map_err(|e| format!("Failed to finalize {}: {}", out_str, e))?;
+        drop(writer);
+
+        if format != OutputFormat::Wav {
+            let encoded = encode(&wav_out, &out_path, format);
+            let _ = std::fs::remove_file(&wav_out);
+            encoded?;
+            tagging::tag_file(&out_path, &tags)
+                .map_err(|e| format!("Failed to tag {}: {}", out_path.display(), e))?;
+        }
+
+        written.push(out_path);
+    }
+    progress.finish(STAGE_SPLIT);
+
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cuefile::{parse_cue, CueTrack};
+    use crate::progress::NoProgress;
+    use crate::wavfile::write_wav_samples;
+
+    fn sheet_with_pregap() -> CueSheet {
+        CueSheet {
+            title: Some("Album".to_string()),
+            performer: Some("Artist".to_string()),
+            file: None,
+            release: Default::default(),
+            groove_out: Some(20.0),
+            needle_drop: None,
+            speed_correction: None,
+            tracks: vec![
+                CueTrack { number: 1, title: Some("One".to_string()), performer: None, pregap_start: None, start: 2.0, confidence: None },
+                CueTrack { number: 2, title: Some("Two/Too".to_string()), performer: None, pregap_start: Some(10.0), start: 12.0, confidence: None },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_plan_tracks_gap_modes() {
+        let sheet = sheet_with_pregap();
+
+        let append = plan_tracks(&sheet, GapMode::Append);
+        assert_eq!((append[0].start, append[0].end), (2.0, Some(12.0)));
+        assert_eq!((append[1].start, append[1].end), (12.0, None));
+
+        let prepend = plan_tracks(&sheet, GapMode::Prepend);
+        assert_eq!((prepend[0].start, prepend[0].end), (0.0, Some(10.0)));
+        assert_eq!(prepend[1].start, 10.0);
+
+        let discard = plan_tracks(&sheet, GapMode::Discard);
+        assert_eq!((discard[0].start, discard[0].end), (2.0, Some(10.0)));
+        assert_eq!(discard[1].start, 12.0);
+
+        assert_eq!(append[1].filename(), "02 - Artist - Two Too.wav");
+        assert_eq!(append[1].filename_as(OutputFormat::Flac), "02 - Artist - Two Too.flac");
+
+        let tags = append[1].tags(&sheet);
+        assert_eq!(tags.artist.as_deref(), Some("Artist"));
+        assert_eq!((tags.track_number, tags.track_total), (Some(2), Some(2)));
+    }
+
+    #[test]
+    fn test_trim_tracks() {
+        let mut sheet = sheet_with_pregap();
+        let trim = Trim { lead_in: true, lead_out: true, pad: 1.5 };
+
+        let mut prepend = plan_tracks(&sheet, GapMode::Prepend);
+        trim_tracks(&mut prepend, &sheet, &trim).unwrap();
+        assert_eq!((prepend[0].start, prepend[0].end), (0.5, Some(10.0)));
+        assert_eq!((prepend[1].start, prepend[1].end), (10.0, Some(21.5)));
+
+        // Groove noise is kept, the pad does not cut into the music
+        let mut append = plan_tracks(&sheet, GapMode::Append);
+        trim_tracks(&mut append, &sheet, &Trim { pad: 0.0, ..trim }).unwrap();
+        assert_eq!((append[0].start, append[1].end), (2.0, Some(20.0)));
+
+        let untrimmed = plan_tracks(&sheet, GapMode::Prepend);
+        let mut segments = untrimmed.clone();
+        trim_tracks(&mut segments, &sheet, &Trim::default()).unwrap();
+        assert_eq!(segments, untrimmed);
+
+        sheet.groove_out = None;
+        assert!(trim_tracks(&mut segments, &sheet, &trim).is_err());
+        assert_eq!(segments, untrimmed);
+    }
+
+    #[test]
+    fn test_needle_drop_filter() {
+        // 30 Hz thump from 1.0 to 1.25 s at 8 kHz, after 2 kHz groove noise
+        let rate = 8000;
+        let mut samples: Vec<i32> = (0..rate * 2)
+            .map(|i| {
+                let t = i as f64 / rate as f64;
+                let noise = 100.0 * (2.0 * std::f64::consts::PI * 2000.0 * t).sin();
+                let thump = if (1.0..1.25).contains(&t) { 10000.0 * (2.0 * std::f64::consts::PI * 30.0 * t).sin() } else { 0.0 };
+                (noise + thump) as i32
+            })
+            .collect();
+        let original = samples.clone();
+        let mut filter = NeedleDropFilter::new(rate as u32, 1, 8000, 10000);
+        // Processed in chunks, as while splitting
+        for chunk in samples.chunks_mut(999) {
+            filter.process(chunk, 32767.0);
+        }
+
+        let energy = |s: &[i32]| s.iter().map(|&v| (v as f64).powi(2)).sum::<f64>();
+        assert!(energy(&samples[8000..10000]) < energy(&original[8000..10000]) / 100.0);
+        // The groove noise passes, the rest is untouched
+        assert!(energy(&samples[9000..9100]) > energy(&original[0..100]) / 2.0);
+        assert_eq!(samples[..7800], original[..7800]);
+        assert_eq!(samples[10200..], original[10200..]);
+    }
+
+    #[test]
+    fn test_split_wav() {
+        let dir = tempfile::tempdir().unwrap();
+        let wav = dir.path().join("side.wav");
+        let wav_str = wav.to_string_lossy().to_string();
+        // 3 seconds at 100 Hz, sample value = frame index
+        let samples: Vec<i32> = (0..300).collect();
+        write_wav_samples(&wav_str, &[samples.clone(), samples], 100, 16).unwrap();
+
+        let sheet = parse_cue(
+            "PERFORMER \"A\"\nTITLE \"B\"\nFILE \"side.wav\" WAVE\n  TRACK 01 AUDIO\n    TITLE \"X\"\n    INDEX 01 00:00:00\n  TRACK 02 AUDIO\n    TITLE \"Y\"\n    INDEX 01 00:01:00\n",
+        ).unwrap();
+        let out_dir = dir.path().join("tracks");
+        let files = split_wav(&wav_str, &sheet, &plan_tracks(&sheet, GapMode::Append), &out_dir, OutputFormat::Wav, &Delivery::default(), &mut NoProgress).unwrap();
+        assert_eq!(files.len(), 2);
+        assert!(files[0].ends_with("01 - A - X.wav"));
+
+        let mut reader = BufReader::new(File::open(&files[1]).unwrap());
+        let header = read_wav_header(&mut reader).unwrap();
+        assert_eq!(header.data_size, 200 * 2 * 2);
+        let mut first = [0u8; 2];
+        reader.read_exact(&mut first).unwrap();
+        assert_eq!(i16::from_le_bytes(first), 100);
+    }
+
+    #[test]
+    fn test_split_wav_mono() {
+        let dir = tempfile::tempdir().unwrap();
+        let wav = dir.path().join("side.wav");
+        let wav_str = wav.to_string_lossy().to_string();
+        let left: Vec<i32> = (0..300).collect();
+        let right: Vec<i32> = left.iter().map(|s| s * 3).collect();
+        write_wav_samples(&wav_str, &[left, right], 100, 16).unwrap();
+
+        let sheet = parse_cue(
+            "PERFORMER \"A\"\nTITLE \"B\"\nFILE \"side.wav\" WAVE\n  TRACK 01 AUDIO\n    TITLE \"X\"\n    INDEX 01 00:00:00\n  TRACK 02 AUDIO\n    TITLE \"Y\"\n    INDEX 01 00:01:00\n",
+        ).unwrap();
+        let out_dir = dir.path().join("tracks");
+        // The average of both channels
+        let delivery = Delivery { mono: true, ..Delivery::default() };
+        let files = split_wav(&wav_str, &sheet, &plan_tracks(&sheet, GapMode::Append), &out_dir, OutputFormat::Wav, &delivery, &mut NoProgress).unwrap();
+        let (header, audio) = crate::wavfile::read_wav_segment(&files[1].to_string_lossy(), 0.0, f64::INFINITY).unwrap();
+        assert_eq!(header.num_channels, 1);
+        assert_eq!(audio[0][..2], [200, 202]);
+    }
+
+    #[test]
+    fn test_split_wav_delivery() {
+        let dir = tempfile::tempdir().unwrap();
+        let wav = dir.path().join("side.wav");
+        let wav_str = wav.to_string_lossy().to_string();
+        // 1 second of a 24-bit 1 kHz sine at 96 kHz
+        let samples: Vec<i32> = (0..96000)
+            .map(|i| ((i as f64 * 2.0 * std::f64::consts::PI / 96.0).sin() * 4_000_000.0) as i32)
+            .collect();
+        write_wav_samples(&wav_str, &[samples.clone(), samples], 96000, 24).unwrap();
+
+        let sheet = parse_cue(
+            "FILE \"side.wav\" WAVE\n  TRACK 01 AUDIO\n    INDEX 01 00:00:00\n  TRACK 02 AUDIO\n    INDEX 01 00:00:30\n",
+        ).unwrap();
+        let out_dir = dir.path().join("tracks");
+        let files = split_wav(&wav_str, &sheet, &plan_tracks(&sheet, GapMode::Append), &out_dir, OutputFormat::Wav, &Delivery::cd(), &mut NoProgress).unwrap();
+
+        let mut reader = BufReader::new(File::open(&files[0]).unwrap());
+        let header = read_wav_header(&mut reader).unwrap();
+        assert_eq!((header.sample_rate, header.bits_per_sample), (44100, 16));
+        // 30 CD frames = 0.4 s
+        assert_eq!(header.data_size, 17640 * 2 * 2);
+
+        let mut data = vec![0u8; header.data_size as usize];
+        reader.read_exact(&mut data).unwrap();
+        let peak = SampleFormat::S16.decode_samples(&data).iter().map(|s| s.abs()).max().unwrap();
+        assert!((15500..=15700).contains(&peak), "peak {}", peak);
+    }
+
+    #[test]
+    fn test_split_wav_speed_correction() {
+        let dir = tempfile::tempdir().unwrap();
+        let wav = dir.path().join("side.wav");
+        let wav_str = wav.to_string_lossy().to_string();
+        // 1 second of a 16-bit 1 kHz sine at 44.1 kHz
+        let samples: Vec<i32> = (0..44100)
+            .map(|i| ((i as f64 * 2.0 * std::f64::consts::PI / 44.1).sin() * 16000.0) as i32)
+            .collect();
+        write_wav_samples(&wav_str, &[samples.clone(), samples], 44100, 16).unwrap();
+
+        // A deck 1% slow: the tracks get 1% shorter, only if asked for
+        let mut sheet = parse_cue(
+            "FILE \"side.wav\" WAVE\n  TRACK 01 AUDIO\n    INDEX 01 00:00:00\n  TRACK 02 AUDIO\n    INDEX 01 00:00:30\n",
+        ).unwrap();
+        sheet.speed_correction = Some(1.01);
+        let out_dir = dir.path().join("tracks");
+        let corrected = Delivery { correct_speed: true, ..Delivery::cd() };
+        for (delivery, frames) in [(Delivery::cd(), 17640), (corrected, 17466)] {
+            let files = split_wav(&wav_str, &sheet, &plan_tracks(&sheet, GapMode::Append), &out_dir, OutputFormat::Wav, &delivery, &mut NoProgress).unwrap();
+            let header = read_wav_header(&mut BufReader::new(File::open(&files[0]).unwrap())).unwrap();
+            assert_eq!(header.data_size, frames * 2 * 2);
+        }
+    }
+
+    #[test]
+    fn test_split_wav_normalize() {
+        let dir = tempfile::tempdir().unwrap();
+        let wav = dir.path().join("side.wav");
+        let wav_str = wav.to_string_lossy().to_string();
+        // 2 s of 1 kHz at -30 dBFS, then 2 s at -20 dBFS
+        let rate = 48000;
+        let samples: Vec<i32> = (0..4 * rate)
+            .map(|i| {
+                let level = if i < 2 * rate { -30.0 } else { -20.0 };
+                let phase = i as f64 * 2.0 * std::f64::consts::PI * 1000.0 / rate as f64;
+                (10.0_f64.powf(level / 20.0) * 32767.0 * phase.sin()).round() as i32
+            })
+            .collect();
+        write_wav_samples(&wav_str, &[samples], rate as u32, 16).unwrap();
+        let sheet = parse_cue(
+            "FILE \"side.wav\" WAVE\n  TRACK 01 AUDIO\n    INDEX 01 00:00:00\n  TRACK 02 AUDIO\n    INDEX 01 00:02:00\n",
+        ).unwrap();
+
+        let peaks_db = |normalize: Normalize, peak_target: Option<f64>| -> Vec<f64> {
+            let delivery = Delivery { normalize, peak_target, ..Delivery::default() };
+            let out_dir = dir.path().join(normalize.as_str());
+            let segments = plan_tracks(&sheet, GapMode::Append);
+            let files = split_wav(&wav_str, &sheet, &segments, &out_dir, OutputFormat::Wav, &delivery, &mut NoProgress).unwrap();
+            files
+                .iter()
+                .map(|f| {
+                    let (_, audio) = crate::wavfile::read_wav_segment(&f.to_string_lossy(), 0.0, f64::INFINITY).unwrap();
+                    let peak = audio[0].iter().map(|s| s.abs()).max().unwrap();
+                    20.0 * (peak as f64 / 32767.0).log10()
+                })
+                .collect()
+        };
+
+        // A mono sine at -18 LUFS peaks at -15 dBFS
+        let track = peaks_db(Normalize::Track, None);
+        assert!(track.iter().all(|p| (p + 15.0).abs() < 0.2), "{:?}", track);
+        // Album gain keeps the 10 dB step between the tracks
+        let album = peaks_db(Normalize::Album, None);
+        assert!((album[1] - album[0] - 10.0).abs() < 0.1, "{:?}", album);
+        assert!(album[1] > -20.0 && album[1] < -1.0, "{:?}", album);
+        let album = peaks_db(Normalize::Album, Some(-1.0));
+        assert!((album[1] + 1.0).abs() < 0.1 && (album[0] + 11.0).abs() < 0.1, "{:?}", album);
+        assert_eq!(Normalize::from_str("TRACK"), Some(Normalize::Track));
+    }
+}
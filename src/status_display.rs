@@ -0,0 +1,284 @@
+//! Front-ends showing the state of a running recording.
+//!
+//! The capture loop collects a [`StatusFrame`] per update and reports
+//! [`StatusEvent`]s; the [`StatusDisplay`] chosen at runtime decides what to
+//! show:
+//! - `vu`   - the terminal VU meter, with recent log lines below it
+//! - `text` - a log line whenever the recording state changes
+//! - `json` - one JSON object per update and per event on stdout
+//! - `oled` - the front panel OLED of the `[oled]` section (cargo feature
+//!   `oled`, see `autorec::oled`)
+//! - `none` - nothing
+//!
+//! Other front-ends (e.g. a web page) implement the trait as well.
+
+use std::fmt;
+use std::io::{self, Write};
+
+use serde::Serialize;
+use tracing::info;
+
+use crate::config::OledConfig;
+use crate::decibel::LevelScale;
+use crate::display::{display_messages, display_vu_meter_scaled, LevelHistory};
+use crate::error::Result;
+use crate::logging::LogBuffer;
+use crate::vu_meter::ChannelMetrics;
+
+/// Log lines shown below the VU meter
+const LOG_LINES: usize = 4;
+
+pub const DISPLAY_NAMES: [&str; 5] = ["vu", "text", "json", "oled", "none"];
+
+/// Which front-end to use
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DisplayKind {
+    #[default]
+    Vu,
+    Text,
+    Json,
+    Oled,
+    None,
+}
+
+impl DisplayKind {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "vu" => Some(DisplayKind::Vu),
+            "text" => Some(DisplayKind::Text),
+            "json" => Some(DisplayKind::Json),
+            "oled" => Some(DisplayKind::Oled),
+            "none" => Some(DisplayKind::None),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DisplayKind::Vu => "vu",
+            DisplayKind::Text => "text",
+            DisplayKind::Json => "json",
+            DisplayKind::Oled => "oled",
+            DisplayKind::None => "none",
+        }
+    }
+}
+
+/// State of the recorder at one update
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StatusFrame<'a> {
+    pub recording: bool,
+    pub paused: bool,
+    /// File being recorded
+    pub file: Option<&'a str>,
+    /// Outside the scheduled recording windows
+    pub waiting_for_schedule: bool,
+    /// Progress of the song boundary detection
+    pub detector: Option<String>,
+    /// Latest identified song, "Artist - Title"
+    pub song: Option<&'a str>,
+    pub levels: &'a [ChannelMetrics],
+}
+
+impl StatusFrame<'_> {
+    /// One-line summary, e.g. "[RECORDING to side_a.wav]  ♪ Artist - Title";
+    /// None while waiting for a signal
+    pub fn status_line(&self) -> Option<String> {
+        let mut parts: Vec<String> = Vec::new();
+        if self.paused {
+            parts.push("[PAUSED]".to_string());
+        } else if self.recording {
+            match self.file {
+                Some(file) => parts.push(format!("[RECORDING to {}]", file)),
+                None => parts.push("[RECORDING]".to_string()),
+            }
+        } else if self.waiting_for_schedule {
+            parts.push("[WAITING FOR SCHEDULE]".to_string());
+        }
+        if let Some(detector) = self.detector.as_ref().filter(|_| self.recording) {
+            parts.push(detector.clone());
+        }
+        if let Some(song) = self.song {
+            parts.push(format!("♪ {}", song));
+        }
+        (!parts.is_empty()).then(|| parts.join("  "))
+    }
+}
+
+/// Something that happened during the recording
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum StatusEvent {
+    /// The pause detector found the start of song `song`
+    SongBoundary { song: u32 },
+    Identified { artist: String, title: String },
+    SourceLost,
+    SourceBack { seconds: f64 },
+}
+
+impl fmt::Display for StatusEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StatusEvent::SongBoundary { song } => write!(f, "Song boundary: song {} starts", song),
+            StatusEvent::Identified { artist, title } => write!(f, "Identified: {} - {}", artist, title),
+            StatusEvent::SourceLost => write!(f, "Audio source lost, waiting for it to come back..."),
+            StatusEvent::SourceBack { seconds } => write!(f, "Audio source is back after {:.0}s", seconds),
+        }
+    }
+}
+
+/// A front-end for the recorder
+pub trait StatusDisplay {
+    /// Show the current state; called for every chunk of audio
+    fn update(&mut self, frame: &StatusFrame) -> io::Result<()>;
+
+    /// Report an event; logged unless the display shows it otherwise
+    fn event(&mut self, event: &StatusEvent) {
+        info!("{}", event);
+    }
+}
+
+/// The full-screen VU meter
+pub struct TerminalDisplay {
+    db_range: f64,
+    max_db: f64,
+    scale: LevelScale,
+    history: Option<LevelHistory>,
+    log: Option<LogBuffer>,
+}
+
+impl TerminalDisplay {
+    /// `history` adds a level sparkline under each bar, `log` shows its
+    /// latest lines under the meters
+    pub fn new(db_range: f64, max_db: f64, scale: LevelScale, history: Option<LevelHistory>, log: Option<LogBuffer>) -> Self {
+        TerminalDisplay { db_range, max_db, scale, history, log }
+    }
+}
+
+impl StatusDisplay for TerminalDisplay {
+    fn update(&mut self, frame: &StatusFrame) -> io::Result<()> {
+        if let Some(history) = &mut self.history {
+            history.push(frame.levels);
+        }
+        let status = frame.status_line();
+        display_vu_meter_scaled(frame.levels, self.db_range, self.max_db, status.as_deref(), &self.scale, self.history.as_ref())?;
+        match &self.log {
+            Some(log) => display_messages(&log.recent(LOG_LINES)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Logs the status line whenever it changes
+#[derive(Default)]
+pub struct TextDisplay {
+    last: Option<Option<String>>,
+}
+
+impl TextDisplay {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl StatusDisplay for TextDisplay {
+    fn update(&mut self, frame: &StatusFrame) -> io::Result<()> {
+        let status = frame.status_line();
+        if self.last.as_ref() != Some(&status) {
+            info!("{}", status.as_deref().unwrap_or("Waiting for signal..."));
+            self.last = Some(status);
+        }
+        Ok(())
+    }
+}
+
+/// Writes every frame and event as one line of JSON
+pub struct JsonDisplay<W: Write> {
+    out: W,
+}
+
+impl JsonDisplay<io::Stdout> {
+    pub fn stdout() -> Self {
+        JsonDisplay { out: io::stdout() }
+    }
+}
+
+impl<W: Write> JsonDisplay<W> {
+    pub fn new(out: W) -> Self {
+        JsonDisplay { out }
+    }
+
+    fn write_line<T: Serialize>(&mut self, value: &T) -> io::Result<()> {
+        serde_json::to_writer(&mut self.out, value)?;
+        writeln!(self.out)?;
+        self.out.flush()
+    }
+}
+
+impl<W: Write> StatusDisplay for JsonDisplay<W> {
+    fn update(&mut self, frame: &StatusFrame) -> io::Result<()> {
+        self.write_line(frame)
+    }
+
+    fn event(&mut self, event: &StatusEvent) {
+        let _ = self.write_line(event);
+    }
+}
+
+/// The front panel display of the `[oled]` section
+#[cfg(feature = "oled")]
+pub fn open_oled(config: &OledConfig) -> Result<Box<dyn StatusDisplay>> {
+    let settings = crate::oled::OledSettings::from_config(config)?;
+    Ok(Box::new(crate::oled::OledDisplay::open(&settings)?))
+}
+
+/// The front panel display of the `[oled]` section
+#[cfg(not(feature = "oled"))]
+pub fn open_oled(_config: &OledConfig) -> Result<Box<dyn StatusDisplay>> {
+    Err(crate::error::Error::Config("OLED support is not enabled (build with --features oled)".to_string()))
+}
+
+/// Shows nothing
+pub struct NullDisplay;
+
+impl StatusDisplay for NullDisplay {
+    fn update(&mut self, _frame: &StatusFrame) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn event(&mut self, _event: &StatusEvent) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_line_and_json() {
+        let mut frame = StatusFrame {
+            recording: true,
+            file: Some("side_a.wav"),
+            detector: Some("🎵 Song #2".to_string()),
+            song: Some("Pink Floyd - Time"),
+            ..Default::default()
+        };
+        assert_eq!(frame.status_line().unwrap(), "[RECORDING to side_a.wav]  🎵 Song #2  ♪ Pink Floyd - Time");
+        frame.recording = false;
+        frame.song = None;
+        assert_eq!(frame.status_line(), None);
+        frame.waiting_for_schedule = true;
+        assert_eq!(frame.status_line().unwrap(), "[WAITING FOR SCHEDULE]");
+
+        let mut display = JsonDisplay::new(Vec::new());
+        display.update(&frame).unwrap();
+        display.event(&StatusEvent::SongBoundary { song: 3 });
+        let output = String::from_utf8(display.out).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains("\"waiting_for_schedule\":true"));
+        assert_eq!(lines[1], r#"{"event":"song_boundary","song":3}"#);
+
+        assert_eq!(DisplayKind::from_str("JSON"), Some(DisplayKind::Json));
+        assert_eq!(DisplayKind::from_str("lcd"), None);
+    }
+}
@@ -0,0 +1,583 @@
+//! Metadata tags for per-track output files.
+//!
+//! The same [`TrackTags`] are written in the native format of each file type:
+//! - WAV: RIFF LIST/INFO chunk
+//! - FLAC: Vorbis comments
+//! - MP3: ID3v2.4
+//!
+//! ReplayGain values (see [`crate::loudness`]) go into the Vorbis comments
+//! and ID3 TXXX frames; RIFF INFO has no field for them.
+//!
+//! Release year and IDs come from the album lookup. They are stored in the
+//! CUE sheet as REM lines (see [`ReleaseTags::to_cue_rem`]), so the splitter
+//! can tag files long after cue_creator has run.
+//!
+//! Existing tags are replaced, the audio data is copied unchanged.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::error::{Error, Result};
+use crate::wavfile::WavInfo;
+
+/// REM field holding the release year
+pub const REM_DATE: &str = "DATE";
+/// REM field holding the MusicBrainz release ID
+pub const REM_MUSICBRAINZ_ALBUMID: &str = "MUSICBRAINZ_ALBUMID";
+/// REM field holding the Discogs release ID
+pub const REM_DISCOGS_RELEASE_ID: &str = "DISCOGS_RELEASE_ID";
+
+/// Vendor string of the Vorbis comment block
+const VORBIS_VENDOR: &str = "HiFiBerry AutoRec";
+
+/// FLAC metadata block types
+const FLAC_STREAMINFO: u8 = 0;
+const FLAC_PADDING: u8 = 1;
+const FLAC_VORBIS_COMMENT: u8 = 4;
+
+/// File formats the splitter can write
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Wav,
+    Flac,
+    Mp3,
+}
+
+impl OutputFormat {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "wav" | "wave" => Some(OutputFormat::Wav),
+            "flac" => Some(OutputFormat::Flac),
+            "mp3" => Some(OutputFormat::Mp3),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OutputFormat::Wav => "wav",
+            OutputFormat::Flac => "flac",
+            OutputFormat::Mp3 => "mp3",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        self.as_str()
+    }
+
+    /// Format of a file, from its extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension().and_then(|e| e.to_str()).and_then(Self::from_str)
+    }
+}
+
+/// Release-level metadata that is not part of a plain CUE sheet
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReleaseTags {
+    pub year: Option<u32>,
+    pub musicbrainz_release_id: Option<String>,
+    pub discogs_release_id: Option<String>,
+}
+
+impl ReleaseTags {
+    /// Release IDs from a lookup result's release URL
+    /// (`https://musicbrainz.org/release/<id>` or `https://www.discogs.com/release/<id>`)
+    pub fn from_release_info(release_info: &str, year: Option<u32>) -> Self {
+        let id_after = |marker: &str| {
+            release_info.find(marker).map(|pos| {
+                release_info[pos + marker.len()..]
+                    .split(|c: char| c == '/' || c == '?' || c == ']' || c.is_whitespace())
+                    .next()
+                    .unwrap_or_default()
+                    .to_string()
+            })
+            .filter(|id| !id.is_empty())
+        };
+        ReleaseTags {
+            year,
+            musicbrainz_release_id: id_after("musicbrainz.org/release/"),
+            discogs_release_id: id_after("discogs.com/release/"),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.year.is_none() && self.musicbrainz_release_id.is_none() && self.discogs_release_id.is_none()
+    }
+
+    /// REM lines for the CUE sheet header
+    pub fn to_cue_rem(&self) -> String {
+        let mut rem = String::new();
+        if let Some(year) = self.year {
+            rem.push_str(&format!("REM {} {}\n", REM_DATE, year));
+        }
+        if let Some(ref id) = self.musicbrainz_release_id {
+            rem.push_str(&format!("REM {} {}\n", REM_MUSICBRAINZ_ALBUMID, id));
+        }
+        if let Some(ref id) = self.discogs_release_id {
+            rem.push_str(&format!("REM {} {}\n", REM_DISCOGS_RELEASE_ID, id));
+        }
+        rem
+    }
+
+    /// Take a CUE REM field (`REM <field> <value>`); returns false for unknown fields
+    pub fn parse_cue_rem(&mut self, field: &str, value: &str) -> bool {
+        let value = value.trim().trim_matches('"');
+        match field.to_ascii_uppercase().as_str() {
+            REM_DATE => {
+                // Also accept full dates (1977-03-01)
+                self.year = value.get(..4).and_then(|y| y.parse().ok());
+            }
+            REM_MUSICBRAINZ_ALBUMID => self.musicbrainz_release_id = Some(value.to_string()),
+            REM_DISCOGS_RELEASE_ID => self.discogs_release_id = Some(value.to_string()),
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// ReplayGain 2.0 gains in dB and true peaks (linear, 1.0 = full scale)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ReplayGain {
+    pub track_gain: f64,
+    pub track_peak: f64,
+    pub album_gain: Option<f64>,
+    pub album_peak: Option<f64>,
+}
+
+impl ReplayGain {
+    /// Tag names and values as written by common ReplayGain scanners
+    pub fn fields(&self) -> Vec<(&'static str, String)> {
+        let gain = |g: f64| format!("{:.2} dB", g);
+        let peak = |p: f64| format!("{:.6}", p);
+        let mut fields = vec![
+            ("REPLAYGAIN_TRACK_GAIN", gain(self.track_gain)),
+            ("REPLAYGAIN_TRACK_PEAK", peak(self.track_peak)),
+        ];
+        if let Some(g) = self.album_gain {
+            fields.push(("REPLAYGAIN_ALBUM_GAIN", gain(g)));
+        }
+        if let Some(p) = self.album_peak {
+            fields.push(("REPLAYGAIN_ALBUM_PEAK", peak(p)));
+        }
+        fields
+    }
+}
+
+/// Tags of one track file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrackTags {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub track_number: Option<u32>,
+    pub track_total: Option<u32>,
+    pub release: ReleaseTags,
+    pub replay_gain: Option<ReplayGain>,
+}
+
+impl TrackTags {
+    /// RIFF INFO has no fields for release IDs, they go into the comment
+    pub fn to_wav_info(&self) -> WavInfo {
+        let mut ids = Vec::new();
+        if let Some(ref id) = self.release.musicbrainz_release_id {
+            ids.push(format!("MusicBrainz release {}", id));
+        }
+        if let Some(ref id) = self.release.discogs_release_id {
+            ids.push(format!("Discogs release {}", id));
+        }
+        WavInfo {
+            title: self.title.clone(),
+            artist: self.artist.clone(),
+            album: self.album.clone(),
+            track: self.track_number.map(|n| n.to_string()),
+            date: self.release.year.map(|y| y.to_string()),
+            comment: Some(ids.join(", ")).filter(|c| !c.is_empty()),
+            ..Default::default()
+        }
+    }
+
+    /// Vorbis comment fields, using the names common taggers use
+    pub fn vorbis_comments(&self) -> Vec<(&'static str, String)> {
+        let fields = [
+            ("TITLE", self.title.clone()),
+            ("ARTIST", self.artist.clone()),
+            ("ALBUM", self.album.clone()),
+            ("ALBUMARTIST", self.album_artist.clone()),
+            ("TRACKNUMBER", self.track_number.map(|n| n.to_string())),
+            ("TRACKTOTAL", self.track_total.map(|n| n.to_string())),
+            ("DATE", self.release.year.map(|y| y.to_string())),
+            ("MUSICBRAINZ_ALBUMID", self.release.musicbrainz_release_id.clone()),
+            ("DISCOGS_RELEASE_ID", self.release.discogs_release_id.clone()),
+        ];
+        let mut comments: Vec<(&'static str, String)> = fields
+            .into_iter()
+            .filter_map(|(key, value)| value.filter(|v| !v.is_empty()).map(|v| (key, v)))
+            .collect();
+        comments.extend(self.replay_gain.iter().flat_map(ReplayGain::fields));
+        comments
+    }
+
+    fn track_position(&self) -> Option<String> {
+        match (self.track_number, self.track_total) {
+            (Some(n), Some(total)) => Some(format!("{}/{}", n, total)),
+            (Some(n), None) => Some(n.to_string()),
+            _ => None,
+        }
+    }
+
+    /// Complete ID3v2.4 tag (header and frames), text in UTF-8
+    pub fn id3v2_tag(&self) -> Vec<u8> {
+        let text_frames = [
+            (b"TIT2", self.title.clone()),
+            (b"TPE1", self.artist.clone()),
+            (b"TALB", self.album.clone()),
+            (b"TPE2", self.album_artist.clone()),
+            (b"TRCK", self.track_position()),
+            (b"TDRC", self.release.year.map(|y| y.to_string())),
+        ];
+        // User-defined text frames, descriptions as written by Picard and the Discogs tagger
+        let mut user_frames = vec![
+            ("MusicBrainz Album Id", self.release.musicbrainz_release_id.clone()),
+            ("DISCOGS_RELEASE_ID", self.release.discogs_release_id.clone()),
+        ];
+        user_frames.extend(self.replay_gain.iter().flat_map(ReplayGain::fields).map(|(k, v)| (k, Some(v))));
+
+        let mut frames = Vec::new();
+        for (id, value) in text_frames {
+            if let Some(value) = value.filter(|v| !v.is_empty()) {
+                push_id3_frame(&mut frames, id, value.as_bytes());
+            }
+        }
+        for (description, value) in user_frames {
+            if let Some(value) = value.filter(|v| !v.is_empty()) {
+                let mut payload = description.as_bytes().to_vec();
+                payload.push(0);
+                payload.extend_from_slice(value.as_bytes());
+                push_id3_frame(&mut frames, b"TXXX", &payload);
+            }
+        }
+
+        let mut tag = b"ID3".to_vec();
+        tag.extend_from_slice(&[4, 0, 0]); // version 2.4.0, no flags
+        tag.extend_from_slice(&syncsafe(frames.len() as u32));
+        tag.extend_from_slice(&frames);
+        tag
+    }
+}
+
+/// 28-bit integer in four 7-bit bytes
+fn syncsafe(value: u32) -> [u8; 4] {
+    [
+        ((value >> 21) & 0x7f) as u8,
+        ((value >> 14) & 0x7f) as u8,
+        ((value >> 7) & 0x7f) as u8,
+        (value & 0x7f) as u8,
+    ]
+}
+
+fn push_id3_frame(frames: &mut Vec<u8>, id: &[u8; 4], text: &[u8]) {
+    frames.extend_from_slice(id);
+    frames.extend_from_slice(&syncsafe(text.len() as u32 + 1));
+    frames.extend_from_slice(&[0, 0]); // flags
+    frames.push(3); // UTF-8
+    frames.extend_from_slice(text);
+}
+
+/// Payload of a FLAC VORBIS_COMMENT block
+fn vorbis_comment_block(tags: &TrackTags) -> Vec<u8> {
+    let comments = tags.vorbis_comments();
+    let mut block = (VORBIS_VENDOR.len() as u32).to_le_bytes().to_vec();
+    block.extend_from_slice(VORBIS_VENDOR.as_bytes());
+    block.extend_from_slice(&(comments.len() as u32).to_le_bytes());
+    for (key, value) in comments {
+        let comment = format!("{}={}", key, value);
+        block.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+        block.extend_from_slice(comment.as_bytes());
+    }
+    block
+}
+
+/// Write `tags` to an audio file, replacing the tags it has.
+/// The format is taken from the file extension.
+pub fn tag_file(path: &Path, tags: &TrackTags) -> Result<()> {
+    let format = OutputFormat::from_path(path)
+        .ok_or_else(|| Error::Config(format!("Don't know how to tag {}", path.display())))?;
+    rewrite(path, |reader, writer| match format {
+        OutputFormat::Wav => write_wav_tags(reader, writer, tags),
+        OutputFormat::Flac => write_flac_tags(reader, writer, tags),
+        OutputFormat::Mp3 => write_mp3_tags(reader, writer, tags),
+    })
+}
+
+/// Copy `path` through `write` into a temporary file that then replaces it
+fn rewrite<F>(path: &Path, write: F) -> Result<()>
+where
+    F: FnOnce(&mut BufReader<File>, &mut BufWriter<File>) -> Result<()>,
+{
+    let dir = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let mut reader = BufReader::new(File::open(path)?);
+    let temp = tempfile::NamedTempFile::new_in(dir)?;
+    let mut writer = BufWriter::new(temp.reopen()?);
+    write(&mut reader, &mut writer)?;
+    writer.flush()?;
+    drop(writer);
+    temp.persist(path).map_err(|e| Error::Io(e.error))?;
+    Ok(())
+}
+
+fn copy_bytes(reader: &mut BufReader<File>, writer: &mut BufWriter<File>, len: u64) -> Result<()> {
+    let copied = io::copy(&mut reader.by_ref().take(len), writer)?;
+    if copied < len {
+        return Err(Error::Decode("File is truncated".to_string()));
+    }
+    Ok(())
+}
+
+/// Copy all chunks except LIST/INFO, then append the new INFO chunk
+fn write_wav_tags(reader: &mut BufReader<File>, writer: &mut BufWriter<File>, tags: &TrackTags) -> Result<()> {
+    let mut header = [0u8; 12];
+    reader.read_exact(&mut header)?;
+    if &header[0..4] != b"RIFF" || &header[8..12] != b"WAVE" {
+        return Err(Error::Decode("Not a RIFF/WAVE file".to_string()));
+    }
+    writer.write_all(&header)?;
+
+    loop {
+        let mut chunk_header = [0u8; 8];
+        match reader.read_exact(&mut chunk_header) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        }
+        let size = u32::from_le_bytes([chunk_header[4], chunk_header[5], chunk_header[6], chunk_header[7]]) as u64;
+        let padded = size + size % 2;
+
+        if &chunk_header[0..4] == b"LIST" && size >= 4 {
+            let mut list_type = [0u8; 4];
+            reader.read_exact(&mut list_type)?;
+            if &list_type == b"INFO" {
+                reader.seek(SeekFrom::Current(padded as i64 - 4))?;
+                continue;
+            }
+            writer.write_all(&chunk_header)?;
+            writer.write_all(&list_type)?;
+            copy_bytes(reader, writer, padded - 4)?;
+        } else {
+            writer.write_all(&chunk_header)?;
+            // The last chunk may lack its pad byte
+            let copied = io::copy(&mut reader.by_ref().take(padded), writer)?;
+            if copied < size {
+                return Err(Error::Decode("WAV file is truncated".to_string()));
+            }
+            if copied < padded {
+                writer.write_all(&[0])?;
+            }
+        }
+    }
+
+    let info = tags.to_wav_info();
+    if !info.is_empty() {
+        let payload = info.list_payload();
+        writer.write_all(b"LIST")?;
+        writer.write_all(&(payload.len() as u32).to_le_bytes())?;
+        writer.write_all(&payload)?;
+    }
+
+    let riff_size = writer.stream_position()? - 8;
+    let riff_size = u32::try_from(riff_size)
+        .map_err(|_| Error::Decode("WAV file exceeds 4 GiB".to_string()))?;
+    writer.seek(SeekFrom::Start(4))?;
+    writer.write_all(&riff_size.to_le_bytes())?;
+    Ok(())
+}
+
+/// Copy all metadata blocks except comments and padding, with the new
+/// comment block right after STREAMINFO
+fn write_flac_tags(reader: &mut BufReader<File>, writer: &mut BufWriter<File>, tags: &TrackTags) -> Result<()> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != b"fLaC" {
+        return Err(Error::Decode("Not a FLAC file".to_string()));
+    }
+
+    let mut blocks: Vec<(u8, Vec<u8>)> = Vec::new();
+    loop {
+        let mut block_header = [0u8; 4];
+        reader.read_exact(&mut block_header)?;
+        let last = block_header[0] & 0x80 != 0;
+        let block_type = block_header[0] & 0x7f;
+        let len = u32::from_be_bytes([0, block_header[1], block_header[2], block_header[3]]) as usize;
+        let mut data = vec![0u8; len];
+        reader.read_exact(&mut data)?;
+
+        match block_type {
+            FLAC_VORBIS_COMMENT | FLAC_PADDING => {}
+            FLAC_STREAMINFO => {
+                blocks.push((block_type, data));
+                blocks.push((FLAC_VORBIS_COMMENT, vorbis_comment_block(tags)));
+            }
+            _ => blocks.push((block_type, data)),
+        }
+        if last {
+            break;
+        }
+    }
+    if blocks.first().map(|(t, _)| *t) != Some(FLAC_STREAMINFO) {
+        return Err(Error::Decode("FLAC file has no STREAMINFO block".to_string()));
+    }
+
+    writer.write_all(b"fLaC")?;
+    let count = blocks.len();
+    for (i, (block_type, data)) in blocks.into_iter().enumerate() {
+        let last = if i + 1 == count { 0x80 } else { 0 };
+        let len = (data.len() as u32).to_be_bytes();
+        writer.write_all(&[block_type | last, len[1], len[2], len[3]])?;
+        writer.write_all(&data)?;
+    }
+    io::copy(reader, writer)?;
+    Ok(())
+}
+
+/// Replace a leading ID3v2 tag (or add one)
+fn write_mp3_tags(reader: &mut BufReader<File>, writer: &mut BufWriter<File>, tags: &TrackTags) -> Result<()> {
+    let mut header = [0u8; 10];
+    let mut read = 0;
+    while read < header.len() {
+        match reader.read(&mut header[read..])? {
+            0 => break,
+            n => read += n,
+        }
+    }
+
+    writer.write_all(&tags.id3v2_tag())?;
+    if read == header.len() && &header[0..3] == b"ID3" {
+        let size = header[6..10].iter().fold(0u64, |acc, &b| (acc << 7) | (b & 0x7f) as u64);
+        // Footer flag
+        let footer = if header[5] & 0x10 != 0 { 10 } else { 0 };
+        reader.seek(SeekFrom::Start(10 + size + footer))?;
+    } else {
+        writer.write_all(&header[..read])?;
+    }
+    io::copy(reader, writer)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wavfile::{read_wav_header, write_wav_samples};
+
+    fn sample_tags() -> TrackTags {
+        TrackTags {
+            title: Some("Song".to_string()),
+            artist: Some("Artist".to_string()),
+            album: Some("Album".to_string()),
+            album_artist: Some("Artist".to_string()),
+            track_number: Some(3),
+            track_total: Some(5),
+            release: ReleaseTags::from_release_info("https://www.discogs.com/release/12345", Some(1977)),
+            replay_gain: Some(ReplayGain { track_gain: -3.2, track_peak: 0.98, album_gain: Some(-2.5), album_peak: None }),
+        }
+    }
+
+    #[test]
+    fn test_release_tags() {
+        let mb = ReleaseTags::from_release_info("https://musicbrainz.org/release/0a1b-2c3d", None);
+        assert_eq!(mb.musicbrainz_release_id.as_deref(), Some("0a1b-2c3d"));
+        assert_eq!(mb.discogs_release_id, None);
+        assert!(ReleaseTags::from_release_info("unknown", None).is_empty());
+
+        let release = sample_tags().release;
+        assert_eq!(release.discogs_release_id.as_deref(), Some("12345"));
+        let rem = release.to_cue_rem();
+        assert_eq!(rem, "REM DATE 1977\nREM DISCOGS_RELEASE_ID 12345\n");
+
+        let mut parsed = ReleaseTags::default();
+        assert!(parsed.parse_cue_rem("DATE", "1977-05-01"));
+        assert!(parsed.parse_cue_rem("DISCOGS_RELEASE_ID", "12345"));
+        assert!(!parsed.parse_cue_rem("COMMENT", "x"));
+        assert_eq!(parsed, release);
+    }
+
+    #[test]
+    fn test_tag_wav_replaces_info() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("track.wav");
+        let path_str = path.to_string_lossy().to_string();
+        write_wav_samples(&path_str, &[vec![1, 2, 3]], 44100, 16).unwrap();
+
+        tag_file(&path, &sample_tags()).unwrap();
+        let mut retagged = sample_tags();
+        retagged.title = Some("Other".to_string());
+        tag_file(&path, &retagged).unwrap();
+
+        let bytes = std::fs::read(&path).unwrap();
+        let riff_size = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as usize;
+        assert_eq!(riff_size, bytes.len() - 8);
+        let text = String::from_utf8_lossy(&bytes);
+        assert_eq!(text.matches("INFO").count(), 1);
+        assert!(text.contains("Other") && !text.contains("Song"));
+        assert!(text.contains("Discogs release 12345"));
+
+        let mut reader = BufReader::new(File::open(&path).unwrap());
+        assert_eq!(read_wav_header(&mut reader).unwrap().data_size, 6);
+    }
+
+    #[test]
+    fn test_tag_flac() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("track.flac");
+        // STREAMINFO, an old comment and padding (last), then "audio"
+        let mut flac = b"fLaC".to_vec();
+        flac.extend_from_slice(&[FLAC_STREAMINFO, 0, 0, 34]);
+        flac.extend_from_slice(&[7u8; 34]);
+        flac.extend_from_slice(&[FLAC_VORBIS_COMMENT, 0, 0, 3]);
+        flac.extend_from_slice(b"old");
+        flac.extend_from_slice(&[0x80 | FLAC_PADDING, 0, 0, 8]);
+        flac.extend_from_slice(&[0u8; 8]);
+        flac.extend_from_slice(b"audio");
+        std::fs::write(&path, &flac).unwrap();
+
+        tag_file(&path, &sample_tags()).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(&bytes[0..4], b"fLaC");
+        assert_eq!(bytes[4], FLAC_STREAMINFO);
+        assert_eq!(bytes[8..42], [7u8; 34]);
+        assert_eq!(bytes[42], 0x80 | FLAC_VORBIS_COMMENT);
+        let block = vorbis_comment_block(&sample_tags());
+        assert_eq!(&bytes[46..46 + block.len()], &block[..]);
+        assert!(bytes.ends_with(b"audio"));
+        assert_eq!(bytes.len(), 46 + block.len() + 5);
+
+        let text = String::from_utf8_lossy(&block);
+        assert!(text.contains("TRACKNUMBER=3") && text.contains("DISCOGS_RELEASE_ID=12345"));
+        assert!(text.contains("DATE=1977"));
+        assert!(text.contains("REPLAYGAIN_TRACK_GAIN=-3.20 dB") && text.contains("REPLAYGAIN_TRACK_PEAK=0.980000"));
+        assert!(text.contains("REPLAYGAIN_ALBUM_GAIN=-2.50 dB") && !text.contains("REPLAYGAIN_ALBUM_PEAK"));
+    }
+
+    #[test]
+    fn test_tag_mp3() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("track.mp3");
+        std::fs::write(&path, [0xff, 0xfb, 0x90, 0x00]).unwrap();
+
+        tag_file(&path, &sample_tags()).unwrap();
+        let tag = sample_tags().id3v2_tag();
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(&bytes[..tag.len()], &tag[..]);
+        assert_eq!(&bytes[tag.len()..], &[0xff, 0xfb, 0x90, 0x00]);
+        assert!(String::from_utf8_lossy(&tag).contains("3/5"));
+        assert!(String::from_utf8_lossy(&tag).contains("REPLAYGAIN_TRACK_GAIN\0-3.20 dB"));
+
+        // The old tag is replaced, not stacked
+        tag_file(&path, &TrackTags { title: Some("X".to_string()), ..Default::default() }).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(bytes.windows(3).filter(|w| w == b"ID3").count(), 1);
+        assert!(bytes.ends_with(&[0xff, 0xfb, 0x90, 0x00]));
+
+        assert!(tag_file(&dir.path().join("track.ogg"), &sample_tags()).is_err());
+    }
+}
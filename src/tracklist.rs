@@ -0,0 +1,197 @@
+//! Track lists typed in by the user.
+//!
+//! `cue_creator --tracklist` takes the track lengths printed on the back
+//! cover instead of looking the release up. The file has one track per
+//! line with its length before or after the title:
+//!
+//! ```text
+//! # Animals, side A
+//! A1  Pigs on the Wing 1   1:25
+//! A2  Dogs                17:03
+//! ```
+//!
+//! A CUE sheet (e.g. one written by hand with rough positions) works as
+//! well; its track starts give the expected positions.
+
+use std::fs;
+use std::path::Path;
+
+use crate::cuefile::{self, CueSheet};
+use crate::error::{Error, Result};
+use crate::lookup::FileSideResult;
+use crate::musicbrainz::ExpectedTrack;
+
+/// Backend name of results taken from a track list
+pub const TRACKLIST_BACKEND: &str = "tracklist";
+
+/// Tracks of one side with the album data the file has
+#[derive(Debug, Clone, Default)]
+pub struct Tracklist {
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub tracks: Vec<ExpectedTrack>,
+}
+
+/// Parse `M:SS`, `MM:SS` or `H:MM:SS` (optionally in parentheses) into seconds
+fn parse_length(token: &str) -> Option<f64> {
+    let token = token.trim_start_matches('(').trim_end_matches(')');
+    let parts: Vec<&str> = token.split(':').collect();
+    if !(2..=3).contains(&parts.len()) || parts.iter().any(|p| p.is_empty() || !p.chars().all(|c| c.is_ascii_digit())) {
+        return None;
+    }
+    if parts[1..].iter().any(|p| p.len() != 2 || !p.parse::<u32>().is_ok_and(|v| v < 60)) {
+        return None;
+    }
+    Some(parts.iter().fold(0.0, |total, p| total * 60.0 + p.parse::<f64>().unwrap_or(0.0)))
+}
+
+/// Track number or position in front of a title: `1`, `1.`, `A1`, `B2)`
+fn is_track_number(token: &str) -> bool {
+    let token = token.trim_end_matches(['.', ')']);
+    let digits = token.strip_prefix(|c: char| c.is_ascii_uppercase()).unwrap_or(token);
+    !digits.is_empty() && digits.len() <= 3 && digits.chars().all(|c| c.is_ascii_digit())
+}
+
+fn expected_tracks(tracks: impl IntoIterator<Item = (String, f64)>) -> Vec<ExpectedTrack> {
+    let mut start = 0.0;
+    tracks
+        .into_iter()
+        .enumerate()
+        .map(|(i, (title, length))| {
+            let track = ExpectedTrack { position: i as u32 + 1, title, length_seconds: length, expected_start: start };
+            start += length;
+            track
+        })
+        .collect()
+}
+
+impl Tracklist {
+    /// Parse a text track list: one `LENGTH TITLE` or `TITLE LENGTH` per line
+    pub fn parse_text(content: &str) -> Result<Self> {
+        let mut tracks = Vec::new();
+        for (line_no, line) in content.lines().enumerate() {
+            // Only whole lines are comments; titles may contain '#'
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut words: Vec<&str> = line.split_whitespace().collect();
+            let length = if let Some(length) = parse_length(words[0]) {
+                words.remove(0);
+                length
+            } else if let Some(length) = words.last().and_then(|w| parse_length(w)) {
+                words.pop();
+                length
+            } else {
+                return Err(Error::Config(format!("Line {}: no track length (M:SS) in '{}'", line_no + 1, line)));
+            };
+            if words.len() > 1 && is_track_number(words[0]) {
+                words.remove(0);
+            }
+            let title = words.join(" ").trim_matches(|c: char| c == '-' || c.is_whitespace()).to_string();
+            tracks.push((title, length));
+        }
+        if tracks.is_empty() {
+            return Err(Error::Config("The track list is empty".to_string()));
+        }
+        Ok(Tracklist { tracks: expected_tracks(tracks), ..Default::default() })
+    }
+
+    /// Track lengths from the starts in a CUE sheet; the last track ends at
+    /// the groove-out if the sheet has one
+    pub fn from_cue(sheet: &CueSheet) -> Result<Self> {
+        if sheet.tracks.is_empty() {
+            return Err(Error::Config("The CUE sheet has no tracks".to_string()));
+        }
+        let ends = sheet.tracks.iter().skip(1).map(|t| Some(t.start)).chain([sheet.groove_out]);
+        let tracks = sheet.tracks.iter().zip(ends).map(|(track, end)| {
+            let title = track.title.clone().unwrap_or_else(|| format!("Track {}", track.number));
+            (title, end.map_or(0.0, |end| (end - track.start).max(0.0)))
+        });
+        Ok(Tracklist {
+            artist: sheet.performer.clone(),
+            album: sheet.title.clone(),
+            tracks: expected_tracks(tracks),
+        })
+    }
+
+    /// Read a track list; files ending in `.cue` are read as CUE sheets
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = cuefile::decode_cue(fs::read(path)?).0;
+        let is_cue = path.extension().is_some_and(|e| e.eq_ignore_ascii_case("cue"));
+        let tracklist = if is_cue {
+            cuefile::parse_cue(&content).map_err(Error::Config).and_then(|sheet| Self::from_cue(&sheet))
+        } else {
+            Self::parse_text(&content)
+        };
+        tracklist.map_err(|e| Error::Config(format!("{}: {}", path.display(), e)))
+    }
+
+    /// The track list as the lookup result of `wav_file`; `source` names
+    /// the file it came from
+    pub fn side_result(&self, wav_file: &str, source: &str) -> FileSideResult {
+        FileSideResult {
+            path: wav_file.to_string(),
+            artist: self.artist.clone().unwrap_or_else(|| "Unknown Artist".to_string()),
+            album_title: self.album.clone().unwrap_or_else(|| "Unknown Album".to_string()),
+            release_info: source.to_string(),
+            year: None,
+            side_label: '?',
+            tracks: self.tracks.clone(),
+            backend: TRACKLIST_BACKEND.to_string(),
+            score: 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_text() {
+        let tracklist = Tracklist::parse_text(
+            "# Animals, side A\n\
+             A1  Pigs on the Wing 1   1:25\n\
+             \n\
+             17:03 Dogs\n\
+             3. Sheep - (10:25)\n             B1 Symphony #9 4:30\n",
+        )
+        .unwrap();
+        let tracks = &tracklist.tracks;
+        assert_eq!(tracks.len(), 4);
+        assert_eq!(tracks[0].title, "Pigs on the Wing 1");
+        assert_eq!(tracks[1].title, "Dogs");
+        assert_eq!(tracks[1].expected_start, 85.0);
+        assert_eq!(tracks[2].title, "Sheep");
+        assert_eq!(tracks[2].expected_start, 85.0 + 1023.0);
+        assert_eq!(tracks[2].length_seconds, 625.0);
+        assert_eq!(tracks[3].title, "Symphony #9");
+        assert_eq!(tracks[3].length_seconds, 270.0);
+
+        assert_eq!(parse_length("1:02:30"), Some(3750.0));
+        assert_eq!(parse_length("3:75"), None);
+        assert!(Tracklist::parse_text("Dogs\n").is_err());
+        assert!(Tracklist::parse_text("# nothing\n").is_err());
+    }
+
+    #[test]
+    fn test_from_cue() {
+        let sheet = cuefile::parse_cue(
+            "PERFORMER \"Pink Floyd\"\nTITLE \"Animals\"\nREM GROOVE_OUT 18:32:00\n\
+             TRACK 01 AUDIO\n  TITLE \"Pigs on the Wing 1\"\n  INDEX 01 00:02:00\n\
+             TRACK 02 AUDIO\n  INDEX 01 01:27:00\n",
+        )
+        .unwrap();
+        let tracklist = Tracklist::from_cue(&sheet).unwrap();
+        assert_eq!(tracklist.artist.as_deref(), Some("Pink Floyd"));
+        assert_eq!(tracklist.tracks[0].length_seconds, 85.0);
+        assert_eq!(tracklist.tracks[1].title, "Track 2");
+        assert_eq!(tracklist.tracks[1].expected_start, 85.0);
+        assert_eq!(tracklist.tracks[1].length_seconds, 1025.0);
+
+        let result = tracklist.side_result("/rec/a.wav", "animals.cue");
+        assert_eq!(result.backend, TRACKLIST_BACKEND);
+        assert_eq!(result.album_title, "Animals");
+    }
+}
@@ -1,10 +1,17 @@
 use crate::audio_stream::AudioInputStream;
 use crate::decibel;
+use crate::filters::FilterChain;
 use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use serde::Serialize;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SampleFormat {
     S16,
+    /// 24-bit samples packed into 3 bytes
+    S24,
+    /// 24-bit samples in the low 3 bytes of a 32-bit word
+    S24In32,
     S32,
 }
 
@@ -12,21 +19,35 @@ impl SampleFormat {
     pub fn from_str(s: &str) -> Result<Self, String> {
         match s {
             "s16" | "s16le" => Ok(SampleFormat::S16),
+            "s24" | "s24le" | "s24_3le" => Ok(SampleFormat::S24),
+            "s24_32" | "s24_32le" | "s24-32le" => Ok(SampleFormat::S24In32),
             "s32" | "s32le" => Ok(SampleFormat::S32),
             _ => Err(format!("Unsupported format: {}", s)),
         }
     }
 
+    /// Integer format of a WAV file with this bit depth
+    pub fn from_bits(bits_per_sample: u16) -> Option<Self> {
+        match bits_per_sample {
+            16 => Some(SampleFormat::S16),
+            24 => Some(SampleFormat::S24),
+            32 => Some(SampleFormat::S32),
+            _ => None,
+        }
+    }
+
     pub fn bytes_per_sample(&self) -> usize {
         match self {
             SampleFormat::S16 => 2,
-            SampleFormat::S32 => 4,
+            SampleFormat::S24 => 3,
+            SampleFormat::S24In32 | SampleFormat::S32 => 4,
         }
     }
 
     pub fn max_value(&self) -> f64 {
         match self {
             SampleFormat::S16 => 32768.0,
+            SampleFormat::S24 | SampleFormat::S24In32 => 8388608.0,
             SampleFormat::S32 => 2147483648.0,
         }
     }
@@ -34,9 +55,67 @@ impl SampleFormat {
     pub fn as_str(&self) -> &str {
         match self {
             SampleFormat::S16 => "s16",
+            SampleFormat::S24 => "s24",
+            SampleFormat::S24In32 => "s24_32",
             SampleFormat::S32 => "s32",
         }
     }
+
+    /// Decode one little-endian sample from `bytes_per_sample()` bytes
+    pub fn decode_sample(&self, b: &[u8]) -> i32 {
+        match self {
+            SampleFormat::S16 => i16::from_le_bytes([b[0], b[1]]) as i32,
+            // Sign-extend from bit 23; the padding byte of S24In32 is ignored
+            SampleFormat::S24 | SampleFormat::S24In32 => i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8,
+            SampleFormat::S32 => i32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+        }
+    }
+
+    /// Decode interleaved little-endian PCM; a trailing partial sample is ignored
+    pub fn decode_samples(&self, bytes: &[u8]) -> Vec<i32> {
+        bytes
+            .chunks_exact(self.bytes_per_sample())
+            .map(|b| self.decode_sample(b))
+            .collect()
+    }
+
+    /// Append one sample as little-endian bytes
+    pub fn encode_sample(&self, sample: i32, out: &mut Vec<u8>) {
+        match self {
+            SampleFormat::S16 => out.extend_from_slice(&(sample as i16).to_le_bytes()),
+            SampleFormat::S24 => out.extend_from_slice(&sample.to_le_bytes()[..3]),
+            SampleFormat::S24In32 => out.extend_from_slice(&((sample << 8) >> 8).to_le_bytes()),
+            SampleFormat::S32 => out.extend_from_slice(&sample.to_le_bytes()),
+        }
+    }
+}
+
+/// What to do when the capture source disappears (USB unplug, PipeWire restart)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReconnectPolicy {
+    /// Try to reconnect at all; otherwise a lost source ends the capture
+    pub enabled: bool,
+    /// Give up after the source was gone this long (None: wait forever)
+    pub timeout: Option<Duration>,
+    /// Time between reconnection attempts
+    pub retry_interval: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        ReconnectPolicy {
+            enabled: true,
+            timeout: None,
+            retry_interval: Duration::from_secs(2),
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Whether to stop waiting for a source that was lost at `lost_since`
+    pub fn expired(&self, lost_since: Instant) -> bool {
+        !self.enabled || self.timeout.is_some_and(|t| lost_since.elapsed() >= t)
+    }
 }
 
 pub struct VUMeter<S: AudioInputStream> {
@@ -53,6 +132,13 @@ pub struct VUMeter<S: AudioInputStream> {
     db_history: Vec<VecDeque<f64>>,
     clip_history: Vec<VecDeque<bool>>,
     peak_history: Vec<VecDeque<f64>>,
+    true_peak_detectors: Vec<decibel::TruePeakDetector>,
+    true_peak_history: Vec<VecDeque<f64>>,
+    over_counters: Vec<decibel::OverCounter>,
+    total_overs: Vec<usize>,
+    filters: Option<FilterChain>,
+    /// Audio read while probing a reconnected source
+    pending_chunk: Option<Vec<Vec<i32>>>,
 }
 
 impl<S: AudioInputStream> VUMeter<S> {
@@ -73,6 +159,11 @@ impl<S: AudioInputStream> VUMeter<S> {
         let db_history = vec![VecDeque::new(); channels];
         let clip_history = vec![VecDeque::new(); channels];
         let peak_history = vec![VecDeque::new(); channels];
+        let true_peak_detectors = vec![decibel::TruePeakDetector::new(); channels];
+        let true_peak_history = vec![VecDeque::new(); channels];
+        let over_threshold = decibel::clipping_threshold(stream.sample_format().max_value(), 0.999);
+        let over_counters = vec![decibel::OverCounter::new(over_threshold, decibel::OVER_MIN_SAMPLES); channels];
+        let total_overs = vec![0; channels];
 
         VUMeter {
             stream,
@@ -87,10 +178,22 @@ impl<S: AudioInputStream> VUMeter<S> {
             db_history,
             clip_history,
             peak_history,
+            true_peak_detectors,
+            true_peak_history,
+            over_counters,
+            total_overs,
+            filters: None,
+            pending_chunk: None,
         }
     }
 
-    pub fn start(&mut self) -> Result<(), String> {
+    /// Filter the captured audio (e.g. RIAA correction) before it is metered
+    /// and returned for recording
+    pub fn set_filters(&mut self, filters: FilterChain) {
+        self.filters = if filters.is_empty() { None } else { Some(filters) };
+    }
+
+    pub fn start(&mut self) -> crate::Result<()> {
         self.stream.start()
     }
 
@@ -98,8 +201,43 @@ impl<S: AudioInputStream> VUMeter<S> {
         self.stream.stop()
     }
 
+    /// Restart the stream after the source was lost.
+    ///
+    /// Returns true once audio is flowing again; the audio read to check
+    /// this is returned by the next [`read_audio_chunk`](Self::read_audio_chunk).
+    /// Meant to be called every [`ReconnectPolicy::retry_interval`].
+    pub fn try_reconnect(&mut self) -> bool {
+        self.stream.stop();
+        if self.stream.start().is_err() {
+            return false;
+        }
+        match self.stream.read_chunk(self.frames_per_update) {
+            Some(audio) => {
+                self.pending_chunk = Some(audio);
+                true
+            }
+            None => {
+                self.stream.stop();
+                false
+            }
+        }
+    }
+
+    /// Whether the input ended (e.g. a file was played to the end); such a
+    /// source is not lost and reconnecting would only replay it
+    pub fn source_ended(&self) -> bool {
+        self.stream.at_end()
+    }
+
     pub fn read_audio_chunk(&mut self) -> Option<Vec<Vec<i32>>> {
-        self.stream.read_chunk(self.frames_per_update)
+        let mut audio = match self.pending_chunk.take() {
+            Some(audio) => audio,
+            None => self.stream.read_chunk(self.frames_per_update)?,
+        };
+        if let Some(filters) = &mut self.filters {
+            filters.process(&mut audio);
+        }
+        Some(audio)
     }
 
     pub fn calculate_db(&self, audio_channel: &[i32]) -> f64 {
@@ -120,6 +258,28 @@ impl<S: AudioInputStream> VUMeter<S> {
         )
     }
 
+    /// True peak (4× oversampled) in dBTP. Keeps filter state per channel.
+    pub fn calculate_true_peak_db(&mut self, channel: usize, audio_channel: &[i32]) -> f64 {
+        let reference = self.stream.sample_format().max_value();
+        match self.true_peak_detectors.get_mut(channel) {
+            Some(detector) => decibel::true_peak_to_db(detector.process(audio_channel, reference), self.min_db),
+            None => self.min_db,
+        }
+    }
+
+    /// Track the true peak over the silence window and return its maximum
+    pub fn update_true_peak_history(&mut self, channel: usize, true_peak_db: f64) -> f64 {
+        let history = match self.true_peak_history.get_mut(channel) {
+            Some(h) => h,
+            None => return self.min_db,
+        };
+        history.push_back(true_peak_db);
+        if history.len() > self.history_size {
+            history.pop_front();
+        }
+        history.iter().copied().fold(self.min_db, f64::max)
+    }
+
     pub fn detect_clipping(&self, audio_channel: &[i32]) -> bool {
         let threshold = decibel::clipping_threshold(
             self.stream.sample_format().max_value(),
@@ -128,6 +288,20 @@ impl<S: AudioInputStream> VUMeter<S> {
         decibel::detect_clipping(audio_channel, threshold)
     }
 
+    /// Count overs (consecutive full-scale samples) in a chunk
+    ///
+    /// Returns the overs in this chunk and the total since the meter was created.
+    pub fn count_overs(&mut self, channel: usize, audio_channel: &[i32]) -> (usize, usize) {
+        match (self.over_counters.get_mut(channel), self.total_overs.get_mut(channel)) {
+            (Some(counter), Some(total)) => {
+                let overs = counter.process(audio_channel);
+                *total += overs;
+                (overs, *total)
+            }
+            _ => (0, 0),
+        }
+    }
+
     pub fn update_history(
         &mut self,
         channel: usize,
@@ -191,6 +365,9 @@ pub fn process_audio_chunk<S: AudioInputStream>(vu_meter: &mut VUMeter<S>) -> Op
         let is_clipping = vu_meter.detect_clipping(channel_data);
         let (max_db, max_peak_db, is_on, has_clipped) =
             vu_meter.update_history(ch, db, peak_db, is_clipping);
+        let true_peak_db = vu_meter.calculate_true_peak_db(ch, channel_data);
+        let max_true_peak_db = vu_meter.update_true_peak_history(ch, true_peak_db);
+        let (overs, total_overs) = vu_meter.count_overs(ch, channel_data);
 
         metrics.push(ChannelMetrics {
             db,
@@ -199,13 +376,17 @@ pub fn process_audio_chunk<S: AudioInputStream>(vu_meter: &mut VUMeter<S>) -> Op
             max_peak_db,
             is_on,
             has_clipped,
+            true_peak_db,
+            max_true_peak_db,
+            overs,
+            total_overs,
         });
     }
 
     Some((metrics, audio))
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ChannelMetrics {
     pub db: f64,
     pub peak_db: f64,
@@ -213,12 +394,20 @@ pub struct ChannelMetrics {
     pub max_peak_db: f64,
     pub is_on: bool,
     pub has_clipped: bool,
+    /// True peak of this chunk in dBTP (may exceed 0)
+    pub true_peak_db: f64,
+    /// Maximum true peak over the silence window in dBTP
+    pub max_true_peak_db: f64,
+    /// Overs (runs of consecutive full-scale samples) in this chunk
+    pub overs: usize,
+    /// Overs since the meter was started
+    pub total_overs: usize,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::audio_stream::{AudioStream, PipeWireInputStream};
+    use crate::audio_stream::{AudioStream, FileInputStream, PipeWireInputStream};
 
     fn create_test_meter() -> VUMeter<PipeWireInputStream> {
         let stream = PipeWireInputStream::new(
@@ -259,6 +448,28 @@ mod tests {
         assert_eq!(SampleFormat::S32.max_value(), 2147483648.0);
         assert_eq!(SampleFormat::S16.as_str(), "s16");
         assert_eq!(SampleFormat::S32.as_str(), "s32");
+        assert_eq!(SampleFormat::S24.bytes_per_sample(), 3);
+        assert_eq!(SampleFormat::S24In32.bytes_per_sample(), 4);
+        assert_eq!(SampleFormat::S24In32.max_value(), 8388608.0);
+        assert_eq!(SampleFormat::from_str("s24_3le"), Ok(SampleFormat::S24));
+        assert_eq!(SampleFormat::from_str(SampleFormat::S24In32.as_str()), Ok(SampleFormat::S24In32));
+        assert_eq!(SampleFormat::from_bits(24), Some(SampleFormat::S24));
+    }
+
+    #[test]
+    fn test_sample_format_decode_s24() {
+        // -2, 8388607 packed and in 32-bit words
+        let packed = [0xfe, 0xff, 0xff, 0xff, 0xff, 0x7f];
+        assert_eq!(SampleFormat::S24.decode_samples(&packed), vec![-2, 8388607]);
+        let in32 = [0xfe, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f, 0x00];
+        assert_eq!(SampleFormat::S24In32.decode_samples(&in32), vec![-2, 8388607]);
+
+        for format in [SampleFormat::S16, SampleFormat::S24, SampleFormat::S24In32, SampleFormat::S32] {
+            let mut bytes = Vec::new();
+            format.encode_sample(-1000, &mut bytes);
+            assert_eq!(bytes.len(), format.bytes_per_sample());
+            assert_eq!(format.decode_sample(&bytes), -1000);
+        }
     }
 
     #[test]
@@ -325,6 +536,19 @@ mod tests {
         assert!(meter.detect_clipping(&clipped));
     }
 
+    #[test]
+    fn test_count_overs() {
+        let mut meter = create_test_meter();
+        let full_scale = i32::MAX;
+
+        let mut chunk = vec![1000i32; 100];
+        chunk[10..20].iter_mut().for_each(|s| *s = full_scale);
+        assert_eq!(meter.count_overs(0, &chunk), (1, 1));
+        assert_eq!(meter.count_overs(0, &chunk), (1, 2));
+        assert_eq!(meter.count_overs(1, &[1000; 100]), (0, 0));
+        assert_eq!(meter.count_overs(5, &chunk), (0, 0));
+    }
+
     #[test]
     fn test_update_history() {
         let mut meter = create_test_meter();
@@ -364,11 +588,110 @@ mod tests {
             max_peak_db: -12.0,
             is_on: true,
             has_clipped: false,
+            true_peak_db: -14.0,
+            max_true_peak_db: -11.5,
+            overs: 0,
+            total_overs: 2,
         };
 
         assert_eq!(metrics.db, -20.0);
         assert!(metrics.is_on);
         assert!(!metrics.has_clipped);
+        assert!(metrics.max_true_peak_db >= metrics.max_peak_db);
     }
-}
 
+    #[test]
+    fn test_true_peak_history() {
+        let mut meter = create_test_meter();
+        let tp = meter.calculate_true_peak_db(0, &[0; 64]);
+        assert_eq!(tp, meter.min_db);
+
+        assert_eq!(meter.update_true_peak_history(0, -3.0), -3.0);
+        assert_eq!(meter.update_true_peak_history(0, -10.0), -3.0);
+        assert_eq!(meter.update_true_peak_history(5, 0.0), meter.min_db);
+    }
+
+    /// Stream that only delivers audio after a number of failed starts
+    struct FlakyStream {
+        failed_starts: usize,
+        active: bool,
+    }
+
+    impl AudioStream for FlakyStream {
+        fn sample_rate(&self) -> u32 {
+            1000
+        }
+
+        fn channels(&self) -> usize {
+            1
+        }
+
+        fn sample_format(&self) -> SampleFormat {
+            SampleFormat::S16
+        }
+    }
+
+    impl AudioInputStream for FlakyStream {
+        fn read_chunk(&mut self, frames: usize) -> Option<Vec<Vec<i32>>> {
+            self.active.then(|| vec![vec![100; frames]])
+        }
+
+        fn start(&mut self) -> crate::Result<()> {
+            if self.failed_starts > 0 {
+                self.failed_starts -= 1;
+                return Err(crate::Error::Backend("device not found".to_string()));
+            }
+            self.active = true;
+            Ok(())
+        }
+
+        fn stop(&mut self) {
+            self.active = false;
+        }
+
+        fn is_active(&self) -> bool {
+            self.active
+        }
+    }
+
+    #[test]
+    fn test_try_reconnect() {
+        let stream = FlakyStream { failed_starts: 1, active: false };
+        let mut meter = VUMeter::new(stream, 0.1, 90.0, 0.0, -60.0, 10.0);
+        assert!(meter.read_audio_chunk().is_none());
+
+        assert!(!meter.try_reconnect());
+        assert!(meter.try_reconnect());
+        // The probe chunk is not lost
+        assert_eq!(meter.read_audio_chunk().unwrap()[0].len(), 100);
+
+        let policy = ReconnectPolicy { timeout: Some(Duration::ZERO), ..Default::default() };
+        assert!(policy.expired(Instant::now()));
+        assert!(!ReconnectPolicy::default().expired(Instant::now()));
+        assert!(ReconnectPolicy { enabled: false, ..Default::default() }.expired(Instant::now()));
+    }
+
+    #[test]
+    fn test_file_source_ends() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("side.wav").to_string_lossy().to_string();
+        // 0.35 s of stereo audio, read in 0.1 s chunks
+        crate::wavfile::write_wav_samples(&path, &[vec![8000; 350], vec![-8000; 350]], 1000, 16).unwrap();
+        let stream = FileInputStream::new(path, 1000, 2, SampleFormat::S16).unwrap();
+        let mut meter = VUMeter::new(stream, 0.1, 90.0, 0.0, -60.0, 10.0);
+        meter.start().unwrap();
+
+        let mut frames = 0;
+        while let Some(audio) = meter.read_audio_chunk() {
+            frames += audio[0].len();
+            assert!(frames <= 350, "the file is played again");
+        }
+        assert_eq!(frames, 350);
+        // The capture loop stops here instead of reconnecting
+        assert!(meter.source_ended());
+
+        let mut lost = VUMeter::new(FlakyStream { failed_starts: 0, active: false }, 0.1, 90.0, 0.0, -60.0, 10.0);
+        assert!(lost.read_audio_chunk().is_none());
+        assert!(!lost.source_ended());
+    }
+}
@@ -0,0 +1,178 @@
+//! Watch a directory for finished recordings.
+//!
+//! [`WatchFolder`] listens for inotify events on WAV files in a directory.
+//! A file that was created, written or moved in is only reported once its
+//! size has not changed for the settle time: a recorder on another machine
+//! writing to a network share may keep a file open (or reopen it) for a
+//! long time, so a single close event does not mean the file is complete.
+
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use inotify::{Inotify, WatchMask};
+
+use crate::declick;
+use crate::error::{Error, Result};
+use crate::speed_check;
+
+/// Time a file's size must stay unchanged before it is reported, in seconds
+pub const DEFAULT_SETTLE_SECONDS: u64 = 10;
+
+/// Files seen in events that have not been reported yet
+#[derive(Debug)]
+struct PendingFiles {
+    /// Last seen size and when it was first seen at that size
+    files: HashMap<PathBuf, (Option<u64>, Instant)>,
+    settle: Duration,
+}
+
+impl PendingFiles {
+    fn new(settle: Duration) -> Self {
+        PendingFiles { files: HashMap::new(), settle }
+    }
+
+    /// The file changed; its settle time starts again
+    fn touch(&mut self, path: PathBuf, now: Instant) {
+        self.files.insert(path, (None, now));
+    }
+
+    /// Remove and return the files whose size has been stable for the
+    /// settle time; files that disappeared are dropped
+    fn take_ready(&mut self, now: Instant) -> Vec<PathBuf> {
+        let mut ready = Vec::new();
+        let settle = self.settle;
+        self.files.retain(|path, (size, since)| {
+            let Ok(metadata) = std::fs::metadata(path) else { return false };
+            let current = Some(metadata.len());
+            if *size != current {
+                *size = current;
+                *since = now;
+                return true;
+            }
+            if now.duration_since(*since) < settle {
+                return true;
+            }
+            ready.push(path.clone());
+            false
+        });
+        ready.sort();
+        ready
+    }
+}
+
+fn is_wav(path: &Path) -> bool {
+    path.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("wav"))
+}
+
+/// Whether `path` is a recording to process: a WAV file that is not a
+/// cleaned or speed-corrected copy of another one
+pub fn is_recording(path: &Path) -> bool {
+    is_wav(path) && !declick::is_declicked(path) && !speed_check::is_speed_corrected(path)
+}
+
+/// inotify watch on one directory (not recursive)
+pub struct WatchFolder {
+    inotify: Inotify,
+    dir: PathBuf,
+    pending: PendingFiles,
+    buffer: Vec<u8>,
+}
+
+impl WatchFolder {
+    pub fn new(dir: &Path, settle: Duration) -> Result<Self> {
+        if !dir.is_dir() {
+            return Err(Error::Config(format!("Not a directory: {}", dir.display())));
+        }
+        let inotify = Inotify::init()?;
+        inotify.watches().add(
+            dir,
+            WatchMask::CREATE | WatchMask::MODIFY | WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO,
+        )?;
+        Ok(WatchFolder {
+            inotify,
+            dir: dir.to_path_buf(),
+            pending: PendingFiles::new(settle),
+            buffer: vec![0; 4096],
+        })
+    }
+
+    /// Check `path` again, e.g. when it was still in use
+    pub fn requeue(&mut self, path: PathBuf) {
+        self.pending.touch(path, Instant::now());
+    }
+
+    /// Read the queued events without blocking and return the WAV files
+    /// that are complete since the last call
+    pub fn poll(&mut self) -> Result<Vec<PathBuf>> {
+        let now = Instant::now();
+        loop {
+            let events = match self.inotify.read_events(&mut self.buffer) {
+                Ok(events) => events,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e.into()),
+            };
+            let mut any = false;
+            for event in events {
+                any = true;
+                let Some(name) = event.name else { continue };
+                let path = self.dir.join(name);
+                if is_recording(&path) {
+                    self.pending.touch(path, now);
+                }
+            }
+            if !any {
+                break;
+            }
+        }
+        Ok(self.pending.take_ready(now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_pending_files_settle() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("side.1.wav");
+        let mut file = std::fs::File::create(&path).unwrap();
+        file.write_all(&[0; 100]).unwrap();
+
+        let start = Instant::now();
+        let mut pending = PendingFiles::new(Duration::from_secs(10));
+        pending.touch(path.clone(), start);
+        assert!(pending.take_ready(start).is_empty());
+        // Still growing: the settle time starts again
+        file.write_all(&[0; 100]).unwrap();
+        assert!(pending.take_ready(start + Duration::from_secs(11)).is_empty());
+        assert!(pending.take_ready(start + Duration::from_secs(20)).is_empty());
+        assert_eq!(pending.take_ready(start + Duration::from_secs(21)), vec![path.clone()]);
+        assert!(pending.take_ready(start + Duration::from_secs(40)).is_empty());
+
+        // Deleted before it settled
+        pending.touch(path.clone(), start);
+        std::fs::remove_file(&path).unwrap();
+        assert!(pending.take_ready(start).is_empty());
+        assert!(pending.files.is_empty());
+    }
+
+    #[test]
+    fn test_watch_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut watch = WatchFolder::new(dir.path(), Duration::ZERO).unwrap();
+        std::fs::write(dir.path().join("side.1.wav"), [0; 64]).unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "x").unwrap();
+        std::fs::write(dir.path().join("side.1.declicked.wav"), [0; 64]).unwrap();
+        std::fs::write(dir.path().join("side.1.speed.wav"), [0; 64]).unwrap();
+
+        // The first poll sees the size, the second one finds it unchanged
+        assert!(watch.poll().unwrap().is_empty());
+        assert_eq!(watch.poll().unwrap(), vec![dir.path().join("side.1.wav")]);
+        assert!(watch.poll().unwrap().is_empty());
+        assert!(WatchFolder::new(&dir.path().join("missing"), Duration::ZERO).is_err());
+    }
+}
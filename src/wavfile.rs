@@ -1,7 +1,13 @@
-//! WAV file I/O utilities for reading headers and audio data.
+//! WAV file I/O utilities for reading headers and audio data, and a
+//! streaming writer shared by the recorder and the export tools.
 
-use std::fs::File;
-use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use chrono::{NaiveDateTime, Timelike};
+
+use crate::vu_meter::SampleFormat;
 
 /// WAV file header information
 #[derive(Debug)]
@@ -9,50 +15,161 @@ pub struct WavHeader {
     pub sample_rate: u32,
     pub num_channels: u16,
     pub bits_per_sample: u16,
-    pub data_size: u32,
+    /// Size of the audio data in bytes (from the ds64 chunk for RF64 files)
+    pub data_size: u64,
+}
+
+impl WavHeader {
+    /// Integer sample format of the audio data; 24-bit files are packed
+    pub fn sample_format(&self) -> Option<SampleFormat> {
+        SampleFormat::from_bits(self.bits_per_sample)
+    }
+}
+
+/// Data size left in the header by writers that were never finalized
+/// (and by RF64 files without a ds64 chunk)
+pub const UNKNOWN_DATA_SIZE: u64 = u32::MAX as u64;
+
+/// Tail shared by the GUIDs of Sony Wave64 chunks after the four-character ID
+const W64_GUID_TAIL: [u8; 12] = [0xF3, 0xAC, 0xD3, 0x11, 0x8C, 0xD1, 0x00, 0xC0, 0x4F, 0x8E, 0xDB, 0x8A];
+
+/// Tail of the Wave64 `riff` GUID, which differs from the other chunks'
+const W64_RIFF_GUID_TAIL: [u8; 12] = [0x2E, 0x91, 0xCF, 0x11, 0xA5, 0xD6, 0x28, 0xDB, 0x04, 0xC1, 0x00, 0x00];
+
+/// Channels, sample rate and bits per sample from a `fmt ` chunk payload
+fn parse_fmt(payload: &[u8]) -> Result<(u16, u32, u16), String> {
+    if payload.len() < 16 {
+        return Err("Invalid WAV format chunk".to_string());
+    }
+    Ok((
+        u16::from_le_bytes([payload[2], payload[3]]),
+        u32::from_le_bytes([payload[4], payload[5], payload[6], payload[7]]),
+        u16::from_le_bytes([payload[14], payload[15]]),
+    ))
 }
 
 /// Read and parse a WAV file header.
 ///
+/// Reads RIFF/WAVE, RF64 and Sony Wave64 (W64) files. Chunks other than
+/// `fmt `, `ds64` and `data` (JUNK, LIST, ...) are skipped wherever they are,
+/// and the 64-bit data size of RF64 files is taken from the ds64 chunk.
+/// WAVE_FORMAT_EXTENSIBLE format chunks (multichannel files) are read like
+/// plain ones.
+///
 /// # Arguments
 /// * `file` - Buffered file reader positioned at the start of the WAV file
 ///
 /// # Returns
-/// Parsed WAV header information, or an error message
+/// Parsed WAV header information, or an error message. The reader is left
+/// at the start of the audio data.
 pub fn read_wav_header(file: &mut BufReader<File>) -> Result<WavHeader, String> {
-    let mut buf = [0u8; 44];
-    file.read_exact(&mut buf).map_err(|e| format!("Failed to read WAV header: {}", e))?;
-    
-    if &buf[0..4] != b"RIFF" || &buf[8..12] != b"WAVE" || &buf[12..16] != b"fmt " {
+    let mut riff = [0u8; 12];
+    file.read_exact(&mut riff).map_err(|e| format!("Failed to read WAV header: {}", e))?;
+
+    if &riff[0..4] == b"riff" && riff[4..12] == W64_RIFF_GUID_TAIL[..8] {
+        return read_w64_header(file);
+    }
+    let is_rf64 = &riff[0..4] == b"RF64";
+    if (&riff[0..4] != b"RIFF" && !is_rf64) || &riff[8..12] != b"WAVE" {
         return Err("Not a valid WAV file".to_string());
     }
-    
-    let num_channels = u16::from_le_bytes([buf[22], buf[23]]);
-    let sample_rate = u32::from_le_bytes([buf[24], buf[25], buf[26], buf[27]]);
-    let bits_per_sample = u16::from_le_bytes([buf[34], buf[35]]);
-    
-    file.seek(SeekFrom::Start(36)).map_err(|e| format!("Seek error: {}", e))?;
-    
+
+    // (channels, sample rate, bits per sample)
+    let mut fmt: Option<(u16, u32, u16)> = None;
+    let mut ds64_data_size: Option<u64> = None;
+
     loop {
         let mut chunk_header = [0u8; 8];
         if file.read_exact(&mut chunk_header).is_err() {
             return Err("Could not find data chunk".to_string());
         }
-        
         let chunk_size = u32::from_le_bytes([chunk_header[4], chunk_header[5], chunk_header[6], chunk_header[7]]);
-        
-        if &chunk_header[0..4] == b"data" {
-            return Ok(WavHeader {
-                sample_rate,
-                num_channels,
-                bits_per_sample,
-                data_size: chunk_size,
-            });
+        // Chunks are word aligned
+        let padded_size = chunk_size as u64 + chunk_size as u64 % 2;
+
+        match &chunk_header[0..4] {
+            b"fmt " | b"ds64" => {
+                let mut payload = vec![0u8; padded_size as usize];
+                file.read_exact(&mut payload).map_err(|e| format!("Failed to read WAV header: {}", e))?;
+                if &chunk_header[0..4] == b"fmt " {
+                    fmt = Some(parse_fmt(&payload)?);
+                } else if payload.len() >= 16 {
+                    // RIFF size, then data size
+                    let mut size = [0u8; 8];
+                    size.copy_from_slice(&payload[8..16]);
+                    ds64_data_size = Some(u64::from_le_bytes(size));
+                }
+            }
+            b"data" => {
+                let (num_channels, sample_rate, bits_per_sample) =
+                    fmt.ok_or_else(|| "WAV file has no format chunk before the data".to_string())?;
+                let data_size = match ds64_data_size {
+                    Some(size) if is_rf64 && chunk_size == u32::MAX => size,
+                    _ => chunk_size as u64,
+                };
+                return Ok(WavHeader {
+                    sample_rate,
+                    num_channels,
+                    bits_per_sample,
+                    data_size,
+                });
+            }
+            _ => {
+                file.seek(SeekFrom::Current(padded_size as i64)).map_err(|e| format!("Seek error: {}", e))?;
+            }
+        }
+    }
+}
+
+/// Rest of [`read_wav_header`] for Wave64 files, after the first 12 bytes.
+///
+/// Wave64 chunks have a 16-byte GUID and a 64-bit size that includes the
+/// 24-byte chunk header, and are aligned to 8 bytes.
+fn read_w64_header(file: &mut BufReader<File>) -> Result<WavHeader, String> {
+    // Rest of the riff GUID, 64-bit file size and the wave GUID
+    let mut rest = [0u8; 28];
+    file.read_exact(&mut rest).map_err(|e| format!("Failed to read WAV header: {}", e))?;
+    if rest[0..4] != W64_RIFF_GUID_TAIL[8..] || &rest[12..16] != b"wave" || rest[16..28] != W64_GUID_TAIL {
+        return Err("Not a valid WAV file".to_string());
+    }
+
+    let mut fmt: Option<(u16, u32, u16)> = None;
+    loop {
+        let mut chunk_header = [0u8; 24];
+        if file.read_exact(&mut chunk_header).is_err() {
+            return Err("Could not find data chunk".to_string());
+        }
+        let mut size = [0u8; 8];
+        size.copy_from_slice(&chunk_header[16..24]);
+        let payload_size = u64::from_le_bytes(size)
+            .checked_sub(24)
+            .ok_or_else(|| "Invalid Wave64 chunk size".to_string())?;
+        let padded_size = payload_size.next_multiple_of(8);
+        let known = chunk_header[4..16] == W64_GUID_TAIL;
+
+        match &chunk_header[0..4] {
+            b"fmt " if known => {
+                let mut payload = vec![0u8; padded_size as usize];
+                file.read_exact(&mut payload).map_err(|e| format!("Failed to read WAV header: {}", e))?;
+                fmt = Some(parse_fmt(&payload)?);
+            }
+            b"data" if known => {
+                let (num_channels, sample_rate, bits_per_sample) =
+                    fmt.ok_or_else(|| "WAV file has no format chunk before the data".to_string())?;
+                return Ok(WavHeader {
+                    sample_rate,
+                    num_channels,
+                    bits_per_sample,
+                    data_size: payload_size,
+                });
+            }
+            _ => {
+                file.seek(SeekFrom::Current(padded_size as i64)).map_err(|e| format!("Seek error: {}", e))?;
+            }
         }
-        
-        file.seek(SeekFrom::Current(chunk_size as i64)).map_err(|e| format!("Seek error: {}", e))?;
     }
 }
+
 /// Extract a segment from a WAV file and write it to a new WAV file
 ///
 /// # Arguments
@@ -96,62 +213,1184 @@ pub fn extract_wav_segment(
     segment_data.truncate(bytes_read);
     
     // Write output file
-    let mut output_file = File::create(output_path)
+    let format = WavSampleFormat::from_bits(header.bits_per_sample)
+        .ok_or_else(|| format!("Unsupported bits per sample: {}", header.bits_per_sample))?;
+    let mut writer = WavWriter::new(output_path, header.sample_rate, header.num_channels, format)
         .map_err(|e| format!("Failed to create output file: {}", e))?;
-    
-    // Write WAV header
-    write_wav_header(
-        &mut output_file,
-        segment_data.len(),
-        header.sample_rate,
-        header.num_channels,
-        header.bits_per_sample,
-    )?;
-    
-    // Write data
-    output_file.write_all(&segment_data)
+    writer.write_raw(&segment_data)
         .map_err(|e| format!("Failed to write segment data: {}", e))?;
+    writer.finalize()
+        .map_err(|e| format!("Failed to finalize output file: {}", e))?;
     
     Ok(())
 }
 
-/// Write a WAV file header
-fn write_wav_header(
-    file: &mut File,
-    data_size: usize,
+/// Read a segment of a WAV file as per-channel samples
+///
+/// The segment is clipped to the audio data; a start before 0 reads from
+/// the beginning.
+///
+/// # Returns
+/// The header and one vector of samples per channel, or an error message
+pub fn read_wav_segment(
+    input_path: &str,
+    start_seconds: f64,
+    duration_seconds: f64,
+) -> Result<(WavHeader, Vec<Vec<i32>>), String> {
+    let input_file = File::open(input_path)
+        .map_err(|e| format!("Failed to open input file: {}", e))?;
+    let mut reader = BufReader::new(input_file);
+    let header = read_wav_header(&mut reader)?;
+    let format = header.sample_format()
+        .ok_or_else(|| format!("Unsupported bits per sample: {}", header.bits_per_sample))?;
+
+    let channels = header.num_channels as usize;
+    let bytes_per_frame = (format.bytes_per_sample() * channels) as u64;
+    let total_frames = header.data_size / bytes_per_frame;
+    let start_frame = ((start_seconds.max(0.0) * header.sample_rate as f64).round() as u64).min(total_frames);
+    let end_frame = (((start_seconds + duration_seconds).max(0.0) * header.sample_rate as f64).round() as u64)
+        .min(total_frames)
+        .max(start_frame);
+
+    reader.seek(SeekFrom::Current((start_frame * bytes_per_frame) as i64))
+        .map_err(|e| format!("Failed to seek to start position: {}", e))?;
+    let mut data = vec![0u8; ((end_frame - start_frame) * bytes_per_frame) as usize];
+    reader.read_exact(&mut data)
+        .map_err(|e| format!("Failed to read segment data: {}", e))?;
+
+    let mut audio = vec![Vec::with_capacity((end_frame - start_frame) as usize); channels];
+    for (i, sample) in format.decode_samples(&data).into_iter().enumerate() {
+        audio[i % channels].push(sample);
+    }
+    Ok((header, audio))
+}
+
+/// Write per-channel samples to a new PCM WAV file
+///
+/// Samples are taken as signed integers of `bits_per_sample` width (16, 24 or 32)
+/// and interleaved on output.
+///
+/// # Returns
+/// Ok(()) on success, or an error message
+pub fn write_wav_samples(
+    output_path: &str,
+    audio: &[Vec<i32>],
     sample_rate: u32,
-    channels: u16,
     bits_per_sample: u16,
 ) -> Result<(), String> {
-    let byte_rate = sample_rate * channels as u32 * (bits_per_sample / 8) as u32;
-    let block_align = channels * (bits_per_sample / 8);
-
-    file.write_all(b"RIFF")
-        .map_err(|e| format!("Write error: {}", e))?;
-    file.write_all(&((data_size + 36) as u32).to_le_bytes())
-        .map_err(|e| format!("Write error: {}", e))?;
-    file.write_all(b"WAVE")
-        .map_err(|e| format!("Write error: {}", e))?;
-    file.write_all(b"fmt ")
-        .map_err(|e| format!("Write error: {}", e))?;
-    file.write_all(&16u32.to_le_bytes())
-        .map_err(|e| format!("Write error: {}", e))?;
-    file.write_all(&1u16.to_le_bytes())
-        .map_err(|e| format!("Write error: {}", e))?;
-    file.write_all(&channels.to_le_bytes())
-        .map_err(|e| format!("Write error: {}", e))?;
-    file.write_all(&sample_rate.to_le_bytes())
-        .map_err(|e| format!("Write error: {}", e))?;
-    file.write_all(&byte_rate.to_le_bytes())
-        .map_err(|e| format!("Write error: {}", e))?;
-    file.write_all(&block_align.to_le_bytes())
-        .map_err(|e| format!("Write error: {}", e))?;
-    file.write_all(&bits_per_sample.to_le_bytes())
-        .map_err(|e| format!("Write error: {}", e))?;
-    file.write_all(b"data")
-        .map_err(|e| format!("Write error: {}", e))?;
-    file.write_all(&(data_size as u32).to_le_bytes())
-        .map_err(|e| format!("Write error: {}", e))?;
+    let format = WavSampleFormat::from_bits(bits_per_sample)
+        .ok_or_else(|| format!("Unsupported bits per sample: {}", bits_per_sample))?;
+    let num_frames = audio.iter().map(|ch| ch.len()).min().unwrap_or(0);
+
+    let mut interleaved = Vec::with_capacity(num_frames * audio.len());
+    for frame in 0..num_frames {
+        for channel in audio {
+            interleaved.push(channel[frame]);
+        }
+    }
+
+    let mut writer = WavWriter::new(output_path, sample_rate, audio.len() as u16, format)
+        .map_err(|e| format!("Failed to create output file: {}", e))?;
+    writer.write_samples(&interleaved)
+        .map_err(|e| format!("Failed to write sample data: {}", e))?;
+    writer.finalize()
+        .map_err(|e| format!("Failed to finalize output file: {}", e))?;
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Sample encoding of a WAV file written by [`WavWriter`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WavSampleFormat {
+    Int16,
+    Int24,
+    Int32,
+    /// 32-bit IEEE float
+    Float32,
+}
+
+impl WavSampleFormat {
+    /// Integer PCM format for a bit depth
+    pub fn from_bits(bits_per_sample: u16) -> Option<Self> {
+        match bits_per_sample {
+            16 => Some(WavSampleFormat::Int16),
+            24 => Some(WavSampleFormat::Int24),
+            32 => Some(WavSampleFormat::Int32),
+            _ => None,
+        }
+    }
+
+    pub fn bits_per_sample(&self) -> u16 {
+        match self {
+            WavSampleFormat::Int16 => 16,
+            WavSampleFormat::Int24 => 24,
+            WavSampleFormat::Int32 | WavSampleFormat::Float32 => 32,
+        }
+    }
+
+    pub fn bytes_per_sample(&self) -> usize {
+        self.bits_per_sample() as usize / 8
+    }
+
+    pub fn is_float(&self) -> bool {
+        *self == WavSampleFormat::Float32
+    }
+}
+
+impl From<SampleFormat> for WavSampleFormat {
+    fn from(format: SampleFormat) -> Self {
+        match format {
+            SampleFormat::S16 => WavSampleFormat::Int16,
+            SampleFormat::S24 | SampleFormat::S24In32 => WavSampleFormat::Int24,
+            SampleFormat::S32 => WavSampleFormat::Int32,
+        }
+    }
+}
+
+/// Metadata written as a LIST/INFO chunk
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WavInfo {
+    /// INAM
+    pub title: Option<String>,
+    /// IART
+    pub artist: Option<String>,
+    /// IPRD
+    pub album: Option<String>,
+    /// ITRK
+    pub track: Option<String>,
+    /// IGNR
+    pub genre: Option<String>,
+    /// ICRD
+    pub date: Option<String>,
+    /// ICMT
+    pub comment: Option<String>,
+    /// ISFT
+    pub software: Option<String>,
+}
+
+impl WavInfo {
+    fn entries(&self) -> Vec<(&'static [u8; 4], &str)> {
+        let fields: [(&'static [u8; 4], &Option<String>); 8] = [
+            (b"INAM", &self.title),
+            (b"IART", &self.artist),
+            (b"IPRD", &self.album),
+            (b"ITRK", &self.track),
+            (b"IGNR", &self.genre),
+            (b"ICRD", &self.date),
+            (b"ICMT", &self.comment),
+            (b"ISFT", &self.software),
+        ];
+        fields
+            .iter()
+            .filter_map(|(id, value)| value.as_deref().map(|v| (*id, v)))
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries().is_empty()
+    }
+
+    /// Payload of the LIST chunk (list type and subchunks)
+    pub fn list_payload(&self) -> Vec<u8> {
+        let mut payload = b"INFO".to_vec();
+        for (id, value) in self.entries() {
+            let mut text = value.as_bytes().to_vec();
+            text.push(0);
+            payload.extend_from_slice(id);
+            payload.extend_from_slice(&(text.len() as u32).to_le_bytes());
+            payload.extend_from_slice(&text);
+            if text.len() % 2 == 1 {
+                payload.push(0);
+            }
+        }
+        payload
+    }
+}
+
+/// Broadcast Wave (EBU Tech 3285) description, written as a bext chunk
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WavBext {
+    /// Free text, up to 256 characters
+    pub description: String,
+    /// Program or person that made the recording, up to 32 characters
+    pub originator: String,
+    /// Unique reference of the recording, up to 32 characters
+    pub originator_reference: String,
+    /// `yyyy-mm-dd`
+    pub origination_date: String,
+    /// `hh:mm:ss`
+    pub origination_time: String,
+    /// Time of the first sample, in samples since midnight
+    pub time_reference: u64,
+    /// One line per processing step, e.g. `A=PCM,F=96000,W=32,M=stereo,T=autorec`
+    pub coding_history: Vec<String>,
+}
+
+/// Size of the fixed part of a version 1 bext chunk
+const BEXT_FIXED_SIZE: usize = 602;
+
+impl WavBext {
+    /// A description of a recording started at `started` (local time)
+    pub fn new(started: NaiveDateTime, sample_rate: u32) -> Self {
+        let seconds = started.time().num_seconds_from_midnight() as u64;
+        WavBext {
+            origination_date: started.format("%Y-%m-%d").to_string(),
+            origination_time: started.format("%H:%M:%S").to_string(),
+            time_reference: seconds * sample_rate as u64,
+            ..Default::default()
+        }
+    }
+
+    /// Coding history line for PCM audio written by `software`
+    pub fn pcm_history(sample_rate: u32, bits_per_sample: u16, channels: u16, software: &str) -> String {
+        let mode = match channels {
+            1 => ",M=mono",
+            2 => ",M=stereo",
+            0 => "",
+            _ => ",M=multitrack",
+        };
+        format!("A=PCM,F={},W={}{},T={}", sample_rate, bits_per_sample, mode, software)
+    }
+
+    /// Payload of the bext chunk
+    pub fn payload(&self) -> Vec<u8> {
+        let mut payload = Vec::with_capacity(BEXT_FIXED_SIZE);
+        for (text, size) in [
+            (&self.description, 256),
+            (&self.originator, 32),
+            (&self.originator_reference, 32),
+            (&self.origination_date, 10),
+            (&self.origination_time, 8),
+        ] {
+            let bytes = text.as_bytes();
+            let len = bytes.len().min(size);
+            payload.extend_from_slice(&bytes[..len]);
+            payload.resize(payload.len() + size - len, 0);
+        }
+        payload.extend_from_slice(&self.time_reference.to_le_bytes()); // low, high
+        payload.extend_from_slice(&1u16.to_le_bytes()); // version
+        payload.resize(BEXT_FIXED_SIZE, 0); // UMID and reserved
+        for line in &self.coding_history {
+            payload.extend_from_slice(line.as_bytes());
+            payload.extend_from_slice(b"\r\n");
+        }
+        payload
+    }
+}
+
+/// Production metadata written as an iXML chunk
+///
+/// Sample rate, bit depth and the track list are filled in by the writer.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WavIxml {
+    pub project: Option<String>,
+    pub note: Option<String>,
+    /// Channel names, e.g. `["Left", "Right"]`
+    pub track_names: Vec<String>,
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+impl WavIxml {
+    /// The iXML document for a file with the given format
+    pub fn document(&self, sample_rate: u32, bits_per_sample: u16, channels: u16) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<BWFXML>\n");
+        xml.push_str("  <IXML_VERSION>1.61</IXML_VERSION>\n");
+        if let Some(project) = &self.project {
+            xml.push_str(&format!("  <PROJECT>{}</PROJECT>\n", xml_escape(project)));
+        }
+        if let Some(note) = &self.note {
+            xml.push_str(&format!("  <NOTE>{}</NOTE>\n", xml_escape(note)));
+        }
+        xml.push_str("  <SPEED>\n");
+        xml.push_str(&format!("    <FILE_SAMPLE_RATE>{}</FILE_SAMPLE_RATE>\n", sample_rate));
+        xml.push_str(&format!("    <AUDIO_BIT_DEPTH>{}</AUDIO_BIT_DEPTH>\n", bits_per_sample));
+        xml.push_str("  </SPEED>\n");
+        xml.push_str("  <TRACK_LIST>\n");
+        xml.push_str(&format!("    <TRACK_COUNT>{}</TRACK_COUNT>\n", channels));
+        for channel in 1..=channels {
+            let name = self.track_names.get(channel as usize - 1).cloned().unwrap_or_else(|| format!("Channel {}", channel));
+            xml.push_str("    <TRACK>\n");
+            xml.push_str(&format!("      <CHANNEL_INDEX>{}</CHANNEL_INDEX>\n", channel));
+            xml.push_str(&format!("      <INTERLEAVE_INDEX>{}</INTERLEAVE_INDEX>\n", channel));
+            xml.push_str(&format!("      <NAME>{}</NAME>\n", xml_escape(&name)));
+            xml.push_str("    </TRACK>\n");
+        }
+        xml.push_str("  </TRACK_LIST>\n");
+        xml.push_str("</BWFXML>\n");
+        xml
+    }
+
+    /// Channel names from the TRACK_LIST of an iXML document
+    pub fn parse_track_names(document: &str) -> Vec<String> {
+        let Some(start) = document.find("<TRACK_LIST>") else { return Vec::new() };
+        let end = document[start..].find("</TRACK_LIST>").map_or(document.len(), |e| start + e);
+        document[start..end]
+            .split("<NAME>")
+            .skip(1)
+            .filter_map(|rest| rest.split_once("</NAME>"))
+            .map(|(name, _)| {
+                name.replace("&quot;", "\"").replace("&lt;", "<").replace("&gt;", ">").replace("&amp;", "&")
+            })
+            .collect()
+    }
+}
+
+/// Channel names stored in the iXML chunk of a WAV file (empty if it has
+/// none, e.g. while the recording is still being written)
+pub fn read_channel_names(path: &str) -> io::Result<Vec<String>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let header = read_wav_header(&mut reader).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    if header.data_size == UNKNOWN_DATA_SIZE {
+        return Ok(Vec::new());
+    }
+    reader.seek(SeekFrom::Current((header.data_size + header.data_size % 2) as i64))?;
+    loop {
+        let mut chunk_header = [0u8; 8];
+        if reader.read_exact(&mut chunk_header).is_err() {
+            return Ok(Vec::new());
+        }
+        let chunk_size = u32::from_le_bytes([chunk_header[4], chunk_header[5], chunk_header[6], chunk_header[7]]) as u64;
+        if &chunk_header[0..4] == b"iXML" {
+            let mut payload = vec![0u8; chunk_size as usize];
+            reader.read_exact(&mut payload)?;
+            return Ok(WavIxml::parse_track_names(&String::from_utf8_lossy(&payload)));
+        }
+        reader.seek(SeekFrom::Current((chunk_size + chunk_size % 2) as i64))?;
+    }
+}
+
+/// A marker stored in the cue chunk, with an optional label (LIST/adtl/labl)
+#[derive(Debug, Clone, PartialEq)]
+pub struct WavCuePoint {
+    pub id: u32,
+    /// Position in sample frames from the start of the data
+    pub position: u32,
+    pub label: Option<String>,
+}
+
+/// The `cue ` chunk and, if any point has a label, the LIST/adtl chunk
+/// for `cue_points`; nothing if there are none
+fn cue_chunks(cue_points: &[WavCuePoint]) -> Vec<(&'static [u8; 4], Vec<u8>)> {
+    if cue_points.is_empty() {
+        return Vec::new();
+    }
+    let mut cue = (cue_points.len() as u32).to_le_bytes().to_vec();
+    for point in cue_points {
+        cue.extend_from_slice(&point.id.to_le_bytes());
+        cue.extend_from_slice(&point.position.to_le_bytes());
+        cue.extend_from_slice(b"data");
+        cue.extend_from_slice(&0u32.to_le_bytes()); // chunk start
+        cue.extend_from_slice(&0u32.to_le_bytes()); // block start
+        cue.extend_from_slice(&point.position.to_le_bytes()); // sample offset
+    }
+    let mut chunks = vec![(b"cue ", cue)];
+
+    if cue_points.iter().any(|c| c.label.is_some()) {
+        let mut adtl = b"adtl".to_vec();
+        for point in cue_points {
+            if let Some(ref label) = point.label {
+                let mut text = label.as_bytes().to_vec();
+                text.push(0);
+                adtl.extend_from_slice(b"labl");
+                adtl.extend_from_slice(&((text.len() + 4) as u32).to_le_bytes());
+                adtl.extend_from_slice(&point.id.to_le_bytes());
+                adtl.extend_from_slice(&text);
+                if text.len() % 2 == 1 {
+                    adtl.push(0);
+                }
+            }
+        }
+        chunks.push((b"LIST", adtl));
+    }
+    chunks
+}
+
+/// Replace the cue points (and their labels) of an existing WAV file.
+///
+/// The audio data is not touched. `cue ` and LIST/adtl chunks after the data
+/// are removed and the new ones appended at the end of the file; such chunks
+/// before the data are turned into JUNK. Works on RIFF and RF64 files, but
+/// not on files whose writer was never finalized.
+pub fn write_cue_points(path: &Path, cue_points: &[WavCuePoint]) -> io::Result<()> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+    let file_len = file.metadata()?.len();
+
+    let mut riff = [0u8; 12];
+    file.read_exact(&mut riff)?;
+    let is_rf64 = &riff[0..4] == b"RF64";
+    if (&riff[0..4] != b"RIFF" && !is_rf64) || &riff[8..12] != b"WAVE" {
+        return Err(invalid("Not a valid WAV file"));
+    }
+
+    // Offset of the ds64 payload, and the 64-bit data size from it
+    let mut ds64: Option<(u64, u64)> = None;
+    // End of the data chunk (with padding), once found
+    let mut data_end: Option<u64> = None;
+    // Chunks after the data that are kept
+    let mut trailing: Vec<([u8; 4], Vec<u8>)> = Vec::new();
+    let mut position = 12;
+    while position + 8 <= file_len {
+        let mut header = [0u8; 8];
+        file.seek(SeekFrom::Start(position))?;
+        file.read_exact(&mut header)?;
+        let id: [u8; 4] = [header[0], header[1], header[2], header[3]];
+        let mut size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as u64;
+        if &id == b"data" {
+            size = match ds64 {
+                Some((_, data_size)) if is_rf64 && size == u32::MAX as u64 => data_size,
+                _ if size == UNKNOWN_DATA_SIZE => return Err(invalid("WAV file was not finalized")),
+                _ => size,
+            };
+        }
+        let payload_start = position + 8;
+        let next = payload_start + size + size % 2;
+
+        let mut payload = Vec::new();
+        if &id != b"data" {
+            payload = vec![0u8; size.min(file_len.saturating_sub(payload_start)) as usize];
+            file.read_exact(&mut payload)?;
+        }
+        let is_cue = &id == b"cue " || (&id == b"LIST" && payload.starts_with(b"adtl"));
+        match (&id, data_end) {
+            (b"ds64", _) if payload.len() >= 16 => {
+                ds64 = Some((payload_start, u64::from_le_bytes(payload[8..16].try_into().unwrap_or_default())));
+            }
+            (b"data", _) => data_end = Some(next.min(file_len)),
+            (_, None) if is_cue => {
+                file.seek(SeekFrom::Start(position))?;
+                file.write_all(b"JUNK")?;
+            }
+            (_, Some(_)) if !is_cue => trailing.push((id, payload)),
+            _ => {}
+        }
+        position = next;
+    }
+    let data_end = data_end.ok_or_else(|| invalid("Could not find data chunk"))?;
+
+    file.set_len(data_end)?;
+    file.seek(SeekFrom::Start(data_end))?;
+    let mut writer = BufWriter::new(&mut file);
+    let new_chunks = cue_chunks(cue_points);
+    let chunks = trailing.iter().map(|(id, p)| (id, p)).chain(new_chunks.iter().map(|(id, p)| (*id, p)));
+    for (id, payload) in chunks {
+        writer.write_all(id)?;
+        writer.write_all(&(payload.len() as u32).to_le_bytes())?;
+        writer.write_all(payload)?;
+        if payload.len() % 2 == 1 {
+            writer.write_all(&[0])?;
+        }
+    }
+    let riff_size = writer.stream_position()? - 8;
+    match ds64 {
+        Some((ds64_payload, _)) if is_rf64 => {
+            writer.seek(SeekFrom::Start(ds64_payload))?;
+            writer.write_all(&riff_size.to_le_bytes())?;
+        }
+        _ => {
+            let size = u32::try_from(riff_size).map_err(|_| invalid("WAV file exceeds 4 GiB, use RF64"))?;
+            writer.seek(SeekFrom::Start(4))?;
+            writer.write_all(&size.to_le_bytes())?;
+        }
+    }
+    writer.flush()
+}
+
+/// Size of the ds64 chunk payload reserved for RF64 conversion
+const DS64_SIZE: u32 = 28;
+
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+/// SubFormat GUID of WAVE_FORMAT_EXTENSIBLE after the format tag
+const KSDATAFORMAT_SUBTYPE_SUFFIX: [u8; 14] =
+    [0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71];
+
+/// Streaming WAV writer
+///
+/// The header is written with placeholder sizes and patched in [`finalize`](Self::finalize).
+/// INFO, bext, iXML and cue chunks are appended after the audio data on finalize, so
+/// metadata can be added at any time while writing.
+///
+/// With [`new_rf64`](Self::new_rf64) space for a ds64 chunk is reserved (as a JUNK chunk),
+/// and the file is converted to RF64 on finalize if it grew beyond 4 GiB.
+///
+/// Files with more than two channels get a WAVE_FORMAT_EXTENSIBLE format chunk, which
+/// most software expects for multichannel audio.
+pub struct WavWriter {
+    file: BufWriter<File>,
+    sample_rate: u32,
+    channels: u16,
+    format: WavSampleFormat,
+    rf64: bool,
+    /// Offset of the data chunk size field
+    data_size_pos: u64,
+    data_size: u64,
+    info: WavInfo,
+    bext: Option<WavBext>,
+    ixml: Option<WavIxml>,
+    cue_points: Vec<WavCuePoint>,
+    finalized: bool,
+}
+
+impl WavWriter {
+    /// Create a plain RIFF/WAVE file (limited to 4 GiB)
+    pub fn new<F: Into<WavSampleFormat>>(path: &str, sample_rate: u32, channels: u16, format: F) -> io::Result<Self> {
+        Self::create(path, sample_rate, channels, format.into(), false)
+    }
+
+    /// Create a WAV file that is converted to RF64 if it exceeds 4 GiB
+    pub fn new_rf64<F: Into<WavSampleFormat>>(path: &str, sample_rate: u32, channels: u16, format: F) -> io::Result<Self> {
+        Self::create(path, sample_rate, channels, format.into(), true)
+    }
+
+    fn create(path: &str, sample_rate: u32, channels: u16, format: WavSampleFormat, rf64: bool) -> io::Result<Self> {
+        let mut writer = WavWriter {
+            file: BufWriter::new(File::create(path)?),
+            sample_rate,
+            channels,
+            format,
+            rf64,
+            data_size_pos: 0,
+            data_size: 0,
+            info: WavInfo::default(),
+            bext: None,
+            ixml: None,
+            cue_points: Vec::new(),
+            finalized: false,
+        };
+        writer.write_header()?;
+        Ok(writer)
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        let bytes_per_sample = self.format.bytes_per_sample() as u16;
+        let block_align = self.channels * bytes_per_sample;
+        let byte_rate = self.sample_rate * block_align as u32;
+        let f = &mut self.file;
+
+        f.write_all(b"RIFF")?;
+        f.write_all(&0u32.to_le_bytes())?; // patched in finalize
+        f.write_all(b"WAVE")?;
+
+        if self.rf64 {
+            // Placeholder that becomes the ds64 chunk if needed
+            f.write_all(b"JUNK")?;
+            f.write_all(&DS64_SIZE.to_le_bytes())?;
+            f.write_all(&[0u8; DS64_SIZE as usize])?;
+        }
+
+        let format_tag: u16 = if self.format.is_float() { WAVE_FORMAT_IEEE_FLOAT } else { WAVE_FORMAT_PCM };
+        let extensible = self.channels > 2;
+        f.write_all(b"fmt ")?;
+        if extensible {
+            f.write_all(&40u32.to_le_bytes())?;
+            f.write_all(&WAVE_FORMAT_EXTENSIBLE.to_le_bytes())?;
+        } else if self.format.is_float() {
+            f.write_all(&18u32.to_le_bytes())?;
+            f.write_all(&format_tag.to_le_bytes())?;
+        } else {
+            f.write_all(&16u32.to_le_bytes())?;
+            f.write_all(&format_tag.to_le_bytes())?;
+        }
+        f.write_all(&self.channels.to_le_bytes())?;
+        f.write_all(&self.sample_rate.to_le_bytes())?;
+        f.write_all(&byte_rate.to_le_bytes())?;
+        f.write_all(&block_align.to_le_bytes())?;
+        f.write_all(&self.format.bits_per_sample().to_le_bytes())?;
+        if extensible {
+            f.write_all(&22u16.to_le_bytes())?; // cbSize
+            f.write_all(&self.format.bits_per_sample().to_le_bytes())?; // valid bits
+            // No speaker positions: the channels are e.g. several decks
+            f.write_all(&0u32.to_le_bytes())?;
+            f.write_all(&format_tag.to_le_bytes())?;
+            f.write_all(&KSDATAFORMAT_SUBTYPE_SUFFIX)?;
+        } else if self.format.is_float() {
+            f.write_all(&0u16.to_le_bytes())?; // cbSize
+        }
+        if self.format.is_float() {
+            // Non-PCM formats require a fact chunk with the frame count
+            f.write_all(b"fact")?;
+            f.write_all(&4u32.to_le_bytes())?;
+            f.write_all(&0u32.to_le_bytes())?;
+        }
+
+        f.write_all(b"data")?;
+        self.data_size_pos = f.stream_position()?;
+        f.write_all(&0u32.to_le_bytes())?;
+        Ok(())
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    pub fn format(&self) -> WavSampleFormat {
+        self.format
+    }
+
+    /// Number of complete frames written so far
+    pub fn frames_written(&self) -> u64 {
+        self.data_size / (self.channels as u64 * self.format.bytes_per_sample() as u64)
+    }
+
+    /// Set the LIST/INFO metadata written on finalize
+    pub fn set_info(&mut self, info: WavInfo) {
+        self.info = info;
+    }
+
+    /// Set the Broadcast Wave description written on finalize
+    pub fn set_bext(&mut self, bext: WavBext) {
+        self.bext = Some(bext);
+    }
+
+    /// Set the iXML metadata written on finalize
+    pub fn set_ixml(&mut self, ixml: WavIxml) {
+        self.ixml = Some(ixml);
+    }
+
+    /// Add a cue point at a frame position. Returns the cue point ID.
+    pub fn add_cue_point(&mut self, position: u32, label: Option<&str>) -> u32 {
+        let id = self.cue_points.len() as u32 + 1;
+        self.cue_points.push(WavCuePoint {
+            id,
+            position,
+            label: label.map(|l| l.to_string()),
+        });
+        id
+    }
+
+    /// Write interleaved integer samples
+    ///
+    /// Samples must be in the range of the target format (e.g. i16 range for
+    /// `Int16`, 24-bit range for `Int24`). For `Float32` they are taken as
+    /// full-scale 32-bit values.
+    pub fn write_samples(&mut self, samples: &[i32]) -> io::Result<()> {
+        for &sample in samples {
+            match self.format {
+                WavSampleFormat::Int16 => self.file.write_all(&(sample as i16).to_le_bytes())?,
+                WavSampleFormat::Int24 => self.file.write_all(&sample.to_le_bytes()[..3])?,
+                WavSampleFormat::Int32 => self.file.write_all(&sample.to_le_bytes())?,
+                WavSampleFormat::Float32 => {
+                    let value = sample as f32 / 2147483648.0;
+                    self.file.write_all(&value.to_le_bytes())?
+                }
+            }
+        }
+        self.data_size += (samples.len() * self.format.bytes_per_sample()) as u64;
+        Ok(())
+    }
+
+    /// Write interleaved float samples in the range -1.0..1.0
+    pub fn write_samples_f32(&mut self, samples: &[f32]) -> io::Result<()> {
+        for &sample in samples {
+            let clamped = sample.clamp(-1.0, 1.0) as f64;
+            match self.format {
+                WavSampleFormat::Int16 => {
+                    let v = (clamped * 32767.0).round() as i16;
+                    self.file.write_all(&v.to_le_bytes())?
+                }
+                WavSampleFormat::Int24 => {
+                    let v = (clamped * 8388607.0).round() as i32;
+                    self.file.write_all(&v.to_le_bytes()[..3])?
+                }
+                WavSampleFormat::Int32 => {
+                    let v = (clamped * 2147483647.0).round() as i32;
+                    self.file.write_all(&v.to_le_bytes())?
+                }
+                WavSampleFormat::Float32 => self.file.write_all(&sample.to_le_bytes())?,
+            }
+        }
+        self.data_size += (samples.len() * self.format.bytes_per_sample()) as u64;
+        Ok(())
+    }
+
+    /// Write already encoded sample data (whole frames in the writer's format)
+    pub fn write_raw(&mut self, data: &[u8]) -> io::Result<()> {
+        self.file.write_all(data)?;
+        self.data_size += data.len() as u64;
+        Ok(())
+    }
+
+    fn write_chunk(&mut self, id: &[u8; 4], payload: &[u8]) -> io::Result<()> {
+        self.file.write_all(id)?;
+        self.file.write_all(&(payload.len() as u32).to_le_bytes())?;
+        self.file.write_all(payload)?;
+        if payload.len() % 2 == 1 {
+            self.file.write_all(&[0])?;
+        }
+        Ok(())
+    }
+
+    /// Write metadata chunks and patch the header sizes
+    pub fn finalize(&mut self) -> io::Result<()> {
+        if self.finalized {
+            return Ok(());
+        }
+        self.finalized = true;
+
+        // Chunks must be word aligned
+        if self.data_size % 2 == 1 {
+            self.file.write_all(&[0])?;
+        }
+        if !self.info.is_empty() {
+            let payload = self.info.list_payload();
+            self.write_chunk(b"LIST", &payload)?;
+        }
+        if let Some(bext) = &self.bext {
+            let payload = bext.payload();
+            self.write_chunk(b"bext", &payload)?;
+        }
+        if let Some(ixml) = &self.ixml {
+            let document = ixml.document(self.sample_rate, self.format.bits_per_sample(), self.channels);
+            self.write_chunk(b"iXML", document.as_bytes())?;
+        }
+        for (id, payload) in cue_chunks(&self.cue_points) {
+            self.write_chunk(id, &payload)?;
+        }
+
+        let file_size = self.file.stream_position()?;
+        let riff_size = file_size - 8;
+        let frames = self.frames_written();
+
+        if self.rf64 && riff_size > u32::MAX as u64 {
+            self.file.seek(SeekFrom::Start(0))?;
+            self.file.write_all(b"RF64")?;
+            self.file.write_all(&u32::MAX.to_le_bytes())?;
+            self.file.seek(SeekFrom::Start(12))?;
+            self.file.write_all(b"ds64")?;
+            self.file.write_all(&DS64_SIZE.to_le_bytes())?;
+            self.file.write_all(&riff_size.to_le_bytes())?;
+            self.file.write_all(&self.data_size.to_le_bytes())?;
+            self.file.write_all(&frames.to_le_bytes())?;
+            self.file.write_all(&0u32.to_le_bytes())?; // table length
+            self.file.seek(SeekFrom::Start(self.data_size_pos))?;
+            self.file.write_all(&u32::MAX.to_le_bytes())?;
+        } else {
+            if riff_size > u32::MAX as u64 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "WAV file exceeds 4 GiB, use RF64",
+                ));
+            }
+            self.file.seek(SeekFrom::Start(4))?;
+            self.file.write_all(&(riff_size as u32).to_le_bytes())?;
+            self.file.seek(SeekFrom::Start(self.data_size_pos))?;
+            self.file.write_all(&(self.data_size as u32).to_le_bytes())?;
+        }
+
+        if self.format.is_float() {
+            // fact chunk sample count sits right before the data chunk header
+            self.file.seek(SeekFrom::Start(self.data_size_pos - 8))?;
+            self.file.write_all(&(frames.min(u32::MAX as u64) as u32).to_le_bytes())?;
+        }
+
+        self.file.seek(SeekFrom::End(0))?;
+        self.file.flush()
+    }
+}
+
+impl Drop for WavWriter {
+    fn drop(&mut self) {
+        if !self.finalized {
+            let _ = self.finalize();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir().join(name).to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_wav_header_generation() {
+        let test_file_str = temp_path("test_wav_header.wav");
+
+        {
+            let mut writer = WavWriter::new(&test_file_str, 48000, 2, SampleFormat::S16).unwrap();
+
+            // Write some samples
+            let samples = vec![1000i32, -1000, 2000, -2000];
+            writer.write_samples(&samples).unwrap();
+            writer.finalize().unwrap();
+        }
+
+        // Read file and verify it exists and has content
+        let metadata = fs::metadata(&test_file_str).unwrap();
+        assert!(metadata.len() > 44); // Should have header + data
+
+        // Cleanup
+        fs::remove_file(&test_file_str).ok();
+    }
+
+    #[test]
+    fn test_wav_writer_s16() {
+        let test_file_str = temp_path("test_s16.wav");
+
+        {
+            let mut writer = WavWriter::new(&test_file_str, 44100, 1, SampleFormat::S16).unwrap();
+
+            let samples = vec![0, 1000, -1000, 16000, -16000];
+            writer.write_samples(&samples).unwrap();
+            writer.finalize().unwrap();
+        }
+
+        let metadata = fs::metadata(&test_file_str).unwrap();
+        // Header (44 bytes) + 5 samples * 2 bytes = 54 bytes
+        assert_eq!(metadata.len(), 54);
+
+        fs::remove_file(&test_file_str).ok();
+    }
+
+    #[test]
+    fn test_wav_writer_s32() {
+        let test_file_str = temp_path("test_s32.wav");
+
+        {
+            let mut writer = WavWriter::new(&test_file_str, 96000, 2, SampleFormat::S32).unwrap();
+
+            let samples = vec![0, 100000, -100000, 1000000, -1000000];
+            writer.write_samples(&samples).unwrap();
+            writer.finalize().unwrap();
+        }
+
+        let metadata = fs::metadata(&test_file_str).unwrap();
+        // Header (44 bytes) + 5 samples * 4 bytes = 64 bytes
+        assert_eq!(metadata.len(), 64);
+
+        fs::remove_file(&test_file_str).ok();
+    }
+
+    #[test]
+    fn test_wav_writer_s24_roundtrip() {
+        let test_file_str = temp_path("test_s24.wav");
+
+        {
+            let mut writer = WavWriter::new(&test_file_str, 48000, 2, WavSampleFormat::Int24).unwrap();
+            writer.write_samples(&[8388607, -8388608, 1, -1]).unwrap();
+        }
+
+        let mut reader = BufReader::new(File::open(&test_file_str).unwrap());
+        let header = read_wav_header(&mut reader).unwrap();
+        assert_eq!(header.bits_per_sample, 24);
+        assert_eq!(header.data_size, 12);
+
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(&data[0..3], &[0xFF, 0xFF, 0x7F]);
+        assert_eq!(&data[3..6], &[0x00, 0x00, 0x80]);
+
+        fs::remove_file(&test_file_str).ok();
+    }
+
+    #[test]
+    fn test_wav_writer_float() {
+        let test_file_str = temp_path("test_f32.wav");
+
+        {
+            let mut writer = WavWriter::new(&test_file_str, 48000, 1, WavSampleFormat::Float32).unwrap();
+            writer.write_samples_f32(&[0.5, -0.25]).unwrap();
+            writer.finalize().unwrap();
+        }
+
+        let bytes = fs::read(&test_file_str).unwrap();
+        // fmt chunk with format tag 3 and cbSize
+        assert_eq!(u16::from_le_bytes([bytes[20], bytes[21]]), 3);
+        assert_eq!(&bytes[38..42], b"fact");
+        assert_eq!(u32::from_le_bytes([bytes[46], bytes[47], bytes[48], bytes[49]]), 2);
+        assert_eq!(&bytes[50..54], b"data");
+        assert_eq!(f32::from_le_bytes([bytes[58], bytes[59], bytes[60], bytes[61]]), 0.5);
+
+        fs::remove_file(&test_file_str).ok();
+    }
+
+    #[test]
+    fn test_wav_writer_multichannel() {
+        let test_file_str = temp_path("test_multichannel.wav");
+
+        {
+            let mut writer = WavWriter::new(&test_file_str, 96000, 4, SampleFormat::S32).unwrap();
+            writer.write_samples(&[1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+            writer.finalize().unwrap();
+        }
+
+        let bytes = fs::read(&test_file_str).unwrap();
+        // 40-byte fmt chunk with WAVE_FORMAT_EXTENSIBLE and the PCM SubFormat
+        assert_eq!(u32::from_le_bytes([bytes[16], bytes[17], bytes[18], bytes[19]]), 40);
+        assert_eq!(u16::from_le_bytes([bytes[20], bytes[21]]), 0xFFFE);
+        assert_eq!(u16::from_le_bytes([bytes[38], bytes[39]]), 32);
+        assert_eq!(u16::from_le_bytes([bytes[44], bytes[45]]), 1);
+        assert_eq!(&bytes[60..64], b"data");
+
+        let mut reader = BufReader::new(File::open(&test_file_str).unwrap());
+        let header = read_wav_header(&mut reader).unwrap();
+        assert_eq!(header.num_channels, 4);
+        assert_eq!(header.bits_per_sample, 32);
+        assert_eq!(header.data_size, 32);
+        assert_eq!(WavBext::pcm_history(96000, 32, 4, "autorec"), "A=PCM,F=96000,W=32,M=multitrack,T=autorec");
+
+        fs::remove_file(&test_file_str).ok();
+    }
+
+    #[test]
+    fn test_wav_writer_metadata_chunks() {
+        let test_file_str = temp_path("test_metadata.wav");
+
+        {
+            let mut writer = WavWriter::new(&test_file_str, 44100, 1, SampleFormat::S16).unwrap();
+            writer.write_samples(&[1, 2, 3]).unwrap();
+            writer.set_info(WavInfo {
+                title: Some("Side A".to_string()),
+                artist: Some("Artist".to_string()),
+                ..Default::default()
+            });
+            assert_eq!(writer.add_cue_point(0, Some("Track 1")), 1);
+            assert_eq!(writer.add_cue_point(2, None), 2);
+            writer.finalize().unwrap();
+        }
+
+        let bytes = fs::read(&test_file_str).unwrap();
+        let riff_size = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as usize;
+        assert_eq!(riff_size + 8, bytes.len());
+        // 3 samples * 2 bytes = 6 bytes of data, then the LIST chunk
+        assert_eq!(&bytes[50..54], b"LIST");
+        assert_eq!(&bytes[58..62], b"INFO");
+        assert!(bytes.windows(4).any(|w| w == b"cue "));
+        assert!(bytes.windows(4).any(|w| w == b"labl"));
+
+        // Readers still find the data chunk
+        let mut reader = BufReader::new(File::open(&test_file_str).unwrap());
+        assert_eq!(read_wav_header(&mut reader).unwrap().data_size, 6);
+
+        fs::remove_file(&test_file_str).ok();
+    }
+
+    #[test]
+    fn test_wav_writer_bext_ixml() {
+        let test_file_str = temp_path("test_bext.wav");
+        let started = NaiveDateTime::parse_from_str("2024-03-09 21:15:30", "%Y-%m-%d %H:%M:%S").unwrap();
+
+        {
+            let mut writer = WavWriter::new(&test_file_str, 48000, 2, SampleFormat::S16).unwrap();
+            writer.write_samples(&[1, 2]).unwrap();
+            writer.set_bext(WavBext {
+                description: "Side A".to_string(),
+                originator: "autorec".to_string(),
+                coding_history: vec![WavBext::pcm_history(48000, 16, 2, "autorec")],
+                ..WavBext::new(started, 48000)
+            });
+            writer.set_ixml(WavIxml { project: Some("Rock & Roll".to_string()), ..Default::default() });
+            writer.finalize().unwrap();
+        }
+
+        let bytes = fs::read(&test_file_str).unwrap();
+        let riff_size = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as usize;
+        assert_eq!(riff_size + 8, bytes.len());
+        // bext follows the 4 bytes of audio
+        assert_eq!(&bytes[48..52], b"bext");
+        let bext = &bytes[56..];
+        assert_eq!(&bext[..6], b"Side A");
+        assert_eq!(&bext[256..263], b"autorec");
+        assert_eq!(&bext[320..338], b"2024-03-0921:15:30");
+        let time_reference = u64::from_le_bytes(bext[338..346].try_into().unwrap());
+        assert_eq!(time_reference, (21 * 3600 + 15 * 60 + 30) * 48000);
+        assert_eq!(&bext[602..641], b"A=PCM,F=48000,W=16,M=stereo,T=autorec\r\n");
+
+        let text = String::from_utf8_lossy(&bytes);
+        assert!(text.contains("iXML"));
+        assert!(text.contains("<PROJECT>Rock &amp; Roll</PROJECT>"));
+        assert!(text.contains("<FILE_SAMPLE_RATE>48000</FILE_SAMPLE_RATE>"));
+        assert!(text.contains("<TRACK_COUNT>2</TRACK_COUNT>"));
+
+        let mut reader = BufReader::new(File::open(&test_file_str).unwrap());
+        assert_eq!(read_wav_header(&mut reader).unwrap().data_size, 4);
+        assert_eq!(read_channel_names(&test_file_str).unwrap(), vec!["Channel 1", "Channel 2"]);
+
+        let track_names = vec!["Mid".to_string(), "Side & more".to_string()];
+        {
+            let mut writer = WavWriter::new_rf64(&test_file_str, 48000, 2, SampleFormat::S16).unwrap();
+            writer.write_samples(&[1, 2, 3, 4, 5, 6]).unwrap();
+            writer.set_ixml(WavIxml { track_names: track_names.clone(), ..Default::default() });
+            writer.finalize().unwrap();
+        }
+        assert_eq!(read_channel_names(&test_file_str).unwrap(), track_names);
+
+        fs::remove_file(&test_file_str).ok();
+    }
+
+    #[test]
+    fn test_write_cue_points_in_place() {
+        let test_file_str = temp_path("test_cue_update.wav");
+
+        {
+            let mut writer = WavWriter::new(&test_file_str, 44100, 1, SampleFormat::S16).unwrap();
+            writer.write_samples(&[1, 2, 3]).unwrap();
+            writer.set_info(WavInfo { title: Some("Side A".to_string()), ..Default::default() });
+            writer.add_cue_point(1, Some("Old marker"));
+            writer.finalize().unwrap();
+        }
+
+        let points = vec![
+            WavCuePoint { id: 1, position: 0, label: Some("01 First".to_string()) },
+            WavCuePoint { id: 2, position: 2, label: Some("02 Second".to_string()) },
+        ];
+        write_cue_points(Path::new(&test_file_str), &points).unwrap();
+        // Writing again replaces the markers instead of adding to them
+        write_cue_points(Path::new(&test_file_str), &points).unwrap();
+
+        let bytes = fs::read(&test_file_str).unwrap();
+        let riff_size = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as usize;
+        assert_eq!(riff_size + 8, bytes.len());
+        let count = |pattern: &[u8]| bytes.windows(pattern.len()).filter(|w| *w == pattern).count();
+        assert_eq!(count(b"cue "), 1);
+        assert_eq!(count(b"adtl"), 1);
+        assert_eq!(count(b"Old marker"), 0);
+        assert_eq!(count(b"02 Second"), 1);
+        // Audio and INFO are kept
+        assert_eq!(&bytes[44..50], &[1, 0, 2, 0, 3, 0]);
+        assert_eq!(count(b"INFO"), 1);
+        let mut reader = BufReader::new(File::open(&test_file_str).unwrap());
+        assert_eq!(read_wav_header(&mut reader).unwrap().data_size, 6);
+
+        fs::remove_file(&test_file_str).ok();
+    }
+
+    #[test]
+    fn test_wav_writer_rf64_reserve() {
+        let test_file_str = temp_path("test_rf64_small.wav");
+
+        {
+            let mut writer = WavWriter::new_rf64(&test_file_str, 48000, 2, SampleFormat::S16).unwrap();
+            writer.write_samples(&[1, 2, 3, 4]).unwrap();
+        }
+
+        // Small files stay plain WAV with a JUNK chunk
+        let bytes = fs::read(&test_file_str).unwrap();
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(&bytes[12..16], b"JUNK");
+        assert_eq!(bytes.len(), 44 + 36 + 8);
+
+        let mut reader = BufReader::new(File::open(&test_file_str).unwrap());
+        let header = read_wav_header(&mut reader).unwrap();
+        assert_eq!((header.sample_rate, header.num_channels, header.data_size), (48000, 2, 8));
+        assert_eq!(reader.stream_position().unwrap(), 44 + 36);
+
+        fs::remove_file(&test_file_str).ok();
+    }
+
+    #[test]
+    fn test_read_rf64_header() {
+        let test_file_str = temp_path("test_rf64_header.wav");
+        // Data size beyond 4 GiB, only stored in ds64
+        let data_size: u64 = 5 * 1024 * 1024 * 1024;
+
+        let mut bytes = b"RF64".to_vec();
+        bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+        bytes.extend_from_slice(b"WAVEds64");
+        bytes.extend_from_slice(&DS64_SIZE.to_le_bytes());
+        bytes.extend_from_slice(&(data_size + 100).to_le_bytes());
+        bytes.extend_from_slice(&data_size.to_le_bytes());
+        bytes.extend_from_slice(&(data_size / 8).to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(b"fmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&2u16.to_le_bytes());
+        bytes.extend_from_slice(&192000u32.to_le_bytes());
+        bytes.extend_from_slice(&(192000u32 * 8).to_le_bytes());
+        bytes.extend_from_slice(&8u16.to_le_bytes());
+        bytes.extend_from_slice(&32u16.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+        bytes.extend_from_slice(&[0u8; 8]);
+        fs::write(&test_file_str, &bytes).unwrap();
+
+        let mut reader = BufReader::new(File::open(&test_file_str).unwrap());
+        let header = read_wav_header(&mut reader).unwrap();
+        assert_eq!(header.sample_rate, 192000);
+        assert_eq!(header.bits_per_sample, 32);
+        assert_eq!(header.data_size, data_size);
+        assert_eq!(reader.stream_position().unwrap(), bytes.len() as u64 - 8);
+
+        fs::remove_file(&test_file_str).ok();
+    }
+
+    #[test]
+    fn test_read_w64_header() {
+        let test_file_str = temp_path("test_w64_header.w64");
+        let guid = |id: &[u8; 4]| [id.as_slice(), &W64_GUID_TAIL].concat();
+        // Data size beyond 4 GiB
+        let data_size: u64 = 5 * 1024 * 1024 * 1024;
+
+        let mut bytes = [b"riff".as_slice(), &W64_RIFF_GUID_TAIL].concat();
+        bytes.extend_from_slice(&(data_size + 128).to_le_bytes());
+        bytes.extend_from_slice(&guid(b"wave"));
+        // A chunk to skip, padded to 8 bytes
+        bytes.extend_from_slice(&guid(b"junk"));
+        bytes.extend_from_slice(&(24u64 + 3).to_le_bytes());
+        bytes.extend_from_slice(&[0u8; 8]);
+        bytes.extend_from_slice(&guid(b"fmt "));
+        bytes.extend_from_slice(&(24u64 + 16).to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&2u16.to_le_bytes());
+        bytes.extend_from_slice(&192000u32.to_le_bytes());
+        bytes.extend_from_slice(&(192000u32 * 6).to_le_bytes());
+        bytes.extend_from_slice(&6u16.to_le_bytes());
+        bytes.extend_from_slice(&24u16.to_le_bytes());
+        bytes.extend_from_slice(&guid(b"data"));
+        bytes.extend_from_slice(&(24 + data_size).to_le_bytes());
+        bytes.extend_from_slice(&[0u8; 12]);
+        fs::write(&test_file_str, &bytes).unwrap();
+
+        let mut reader = BufReader::new(File::open(&test_file_str).unwrap());
+        let header = read_wav_header(&mut reader).unwrap();
+        assert_eq!((header.sample_rate, header.num_channels, header.bits_per_sample), (192000, 2, 24));
+        assert_eq!(header.data_size, data_size);
+        assert_eq!(reader.stream_position().unwrap(), bytes.len() as u64 - 12);
+
+        fs::remove_file(&test_file_str).ok();
+    }
+
+    #[test]
+    fn test_write_wav_samples_and_extract_segment() {
+        let full = temp_path("test_extract_full.wav");
+        let segment = temp_path("test_extract_segment.wav");
+
+        let left: Vec<i32> = (0..100).collect();
+        let right: Vec<i32> = (0..100).map(|v| -v).collect();
+        write_wav_samples(&full, &[left, right], 100, 16).unwrap();
+
+        extract_wav_segment(&full, &segment, 0.5, 0.2).unwrap();
+        let mut reader = BufReader::new(File::open(&segment).unwrap());
+        let header = read_wav_header(&mut reader).unwrap();
+        assert_eq!(header.num_channels, 2);
+        assert_eq!(header.data_size, 20 * 2 * 2);
+
+        let mut first = [0u8; 4];
+        reader.read_exact(&mut first).unwrap();
+        assert_eq!(i16::from_le_bytes([first[0], first[1]]), 50);
+        assert_eq!(i16::from_le_bytes([first[2], first[3]]), -50);
+
+        // Per-channel samples, clipped to the data
+        let (_, audio) = read_wav_segment(&full, 0.9, 0.5).unwrap();
+        assert_eq!(audio, vec![(90..100).collect::<Vec<i32>>(), (90..100).map(|v| -v).collect()]);
+        let (_, audio) = read_wav_segment(&full, -0.1, 0.15).unwrap();
+        assert_eq!(audio[0], vec![0, 1, 2, 3, 4]);
+
+        fs::remove_file(&full).ok();
+        fs::remove_file(&segment).ok();
+    }
+}
@@ -0,0 +1,224 @@
+//! Web UI of the `autorecd` daemon.
+//!
+//! The pages are compiled into the binary and served by the control API
+//! (see [`crate::control_api`]) below `/ui/`. They show live VU meters,
+//! buttons for the recording commands, the recordings of the catalog with
+//! their reports and spectrograms, and a boundary review: the recording
+//! plays in the browser, boundaries can be moved, added or removed, and
+//! saving stores them as reviewed boundaries and runs `cue_creator` again.
+//!
+//! Besides the pages:
+//! - `GET /files/<kind>?file=...` - the WAV file (`audio`, with range
+//!   requests for seeking), `report` or `spectrogram` of a catalog recording
+//!
+//! Only files of recordings in the catalog are served.
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+use serde_json::{json, Value};
+use tiny_http::{Header, Response, ResponseBox, StatusCode};
+
+use crate::catalog::{Catalog, CatalogEntry};
+use crate::cuefile;
+use crate::report::report_path;
+use crate::spectrogram::spectrogram_path;
+
+/// Recordings listed by `GET /sessions`, newest first
+const MAX_SESSIONS: usize = 200;
+
+const INDEX_HTML: &str = include_str!("../web/index.html");
+const APP_JS: &str = include_str!("../web/app.js");
+const STYLE_CSS: &str = include_str!("../web/style.css");
+
+/// Content and type of a page of the UI
+pub fn asset(path: &str) -> Option<(&'static str, &'static str)> {
+    match path.trim_end_matches('/') {
+        "/ui" | "/ui/index.html" => Some(("text/html; charset=utf-8", INDEX_HTML)),
+        "/ui/app.js" => Some(("text/javascript; charset=utf-8", APP_JS)),
+        "/ui/style.css" => Some(("text/css; charset=utf-8", STYLE_CSS)),
+        _ => None,
+    }
+}
+
+/// Decode `%XX` and `+` of a URL query value
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok().and_then(|h| u8::from_str_radix(h, 16).ok());
+                match hex {
+                    Some(byte) => {
+                        decoded.push(byte);
+                        i += 2;
+                    }
+                    None => decoded.push(b'%'),
+                }
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Value of the query parameter `name` of `url`
+pub fn query_param(url: &str, name: &str) -> Option<String> {
+    let query = url.split_once('?')?.1;
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('=').or(Some((pair, ""))))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| percent_decode(value))
+}
+
+/// The catalog's recordings, newest first, with the files found next to them
+pub fn sessions_json(catalog: &Catalog) -> crate::error::Result<Value> {
+    let entries = catalog.list()?;
+    let list: Vec<Value> = entries
+        .iter()
+        .rev()
+        .take(MAX_SESSIONS)
+        .map(|e| {
+            json!({
+                "file": e.path,
+                "started": e.started,
+                "duration": e.duration,
+                "artist": e.artist,
+                "album": e.album,
+                "side": e.side.map(String::from),
+                "year": e.year,
+                "release": e.release,
+                "tracks": e.tracks.len(),
+                "exists": Path::new(&e.path).exists(),
+                "cue": cuefile::find_cue_file(&e.path).is_some(),
+                "report": report_path(&e.path).exists(),
+                "spectrogram": spectrogram_path(&e.path).exists(),
+            })
+        })
+        .collect();
+    Ok(json!(list))
+}
+
+/// Tracks of the CUE sheet of `entry` for the boundary review; None if it
+/// has no CUE sheet
+pub fn boundaries_json(entry: &CatalogEntry) -> Option<Value> {
+    let sheet = cuefile::read_cue_file(&cuefile::find_cue_file(&entry.path)?).ok()?;
+    let tracks: Vec<Value> = sheet
+        .tracks
+        .iter()
+        .map(|t| json!({ "number": t.number, "title": t.title, "start": t.start }))
+        .collect();
+    Some(json!({
+        "file": entry.path,
+        "duration": entry.duration,
+        "groove_in": entry.groove_in,
+        "groove_out": sheet.groove_out.or(entry.groove_out),
+        "reviewed": cuefile::reviewed_path(&entry.path).exists(),
+        "tracks": tracks,
+    }))
+}
+
+/// Byte range of a `Range: bytes=a-b` header within a file of `len` bytes
+fn parse_range(header: &str, len: u64) -> Option<(u64, u64)> {
+    let spec = header.trim().strip_prefix("bytes=")?.split(',').next()?.trim();
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = match (start.parse::<u64>().ok(), end.parse::<u64>().ok()) {
+        (Some(start), Some(end)) => (start, end.min(len.saturating_sub(1))),
+        (Some(start), None) => (start, len.saturating_sub(1)),
+        // Suffix range: the last `end` bytes
+        (None, Some(suffix)) => (len.saturating_sub(suffix), len.saturating_sub(1)),
+        (None, None) => return None,
+    };
+    (start <= end && start < len).then_some((start, end))
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).unwrap()
+}
+
+fn error_response(status: u16, message: &str) -> ResponseBox {
+    Response::from_string(json!({ "error": message }).to_string())
+        .with_header(header("Content-Type", "application/json"))
+        .with_status_code(status)
+        .boxed()
+}
+
+/// Serve `path` with the given content type, honouring a range request
+fn file_response(path: &Path, content_type: &str, range: Option<&str>) -> ResponseBox {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(_) => return error_response(404, "No such file"),
+    };
+    let len = file.metadata().map(|m| m.len()).unwrap_or(0);
+    let mut headers = vec![header("Content-Type", content_type), header("Accept-Ranges", "bytes")];
+    match range {
+        Some(range) => match parse_range(range, len) {
+            Some((start, end)) if file.seek(SeekFrom::Start(start)).is_ok() => {
+                let length = end - start + 1;
+                headers.push(header("Content-Range", &format!("bytes {}-{}/{}", start, end, len)));
+                let body: Box<dyn Read + Send> = Box::new(file.take(length));
+                Response::new(StatusCode(206), headers, body, Some(length as usize), None)
+            }
+            _ => {
+                let response = error_response(416, "Invalid range");
+                response.with_header(header("Content-Range", &format!("bytes */{}", len)))
+            }
+        },
+        None => Response::new(StatusCode(200), headers, Box::new(file) as Box<dyn Read + Send>, Some(len as usize), None),
+    }
+}
+
+/// Answer a request for a page of the UI or a file of a recording; None for
+/// the API's own endpoints
+pub fn respond(method: &str, url: &str, range: Option<&str>) -> Option<ResponseBox> {
+    let path = url.split('?').next().unwrap_or("");
+    if method != "GET" {
+        return None;
+    }
+    if let Some((content_type, body)) = asset(path) {
+        return Some(Response::from_string(body).with_header(header("Content-Type", content_type)).boxed());
+    }
+    let kind = path.strip_prefix("/files/")?;
+    let Some(file) = query_param(url, "file") else {
+        return Some(error_response(400, "Missing file parameter"));
+    };
+    let entry = match Catalog::open_default().and_then(|catalog| catalog.get(&file)) {
+        Ok(Some(entry)) => entry,
+        Ok(None) => return Some(error_response(404, "No such recording")),
+        Err(e) => return Some(error_response(503, &e.to_string())),
+    };
+    Some(match kind {
+        "audio" => file_response(Path::new(&entry.path), "audio/wav", range),
+        "report" => file_response(&report_path(&entry.path), "text/html; charset=utf-8", None),
+        "spectrogram" => file_response(&spectrogram_path(&entry.path), "image/png", None),
+        _ => error_response(404, "Not found"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_and_range() {
+        assert_eq!(query_param("/files/audio?file=%2Fdata%2FSide+A%C3%A4.wav&x=1", "file").unwrap(), "/data/Side Aä.wav");
+        assert_eq!(query_param("/files/audio?x=1", "file"), None);
+        assert_eq!(query_param("/boundaries?file=a%2", "file").unwrap(), "a%2");
+
+        assert_eq!(parse_range("bytes=0-", 1000), Some((0, 999)));
+        assert_eq!(parse_range("bytes=100-199", 1000), Some((100, 199)));
+        assert_eq!(parse_range("bytes=900-5000", 1000), Some((900, 999)));
+        assert_eq!(parse_range("bytes=-100", 1000), Some((900, 999)));
+        assert_eq!(parse_range("bytes=1000-", 1000), None);
+        assert_eq!(parse_range("items=0-1", 1000), None);
+
+        assert!(asset("/ui/").unwrap().1.contains("app.js"));
+        assert_eq!(asset("/ui/secret"), None);
+    }
+}
@@ -0,0 +1,271 @@
+//! Wow and flutter measurement from a recorded test tone.
+//!
+//! The speed variations of a turntable show up as frequency modulation of a
+//! test tone (3150 Hz per DIN 45507 / IEC 60386, 3000 Hz on some test
+//! records). The tone is band-pass filtered and its frequency is measured
+//! cycle by cycle from interpolated zero crossings. The relative frequency
+//! deviation is resampled to [`DEMOD_RATE`] and passed through the
+//! DIN/IEC weighting filter, which peaks at 4 Hz.
+//!
+//! Reported values:
+//! - weighted peak: the "2 sigma" value (95th percentile) of the weighted
+//!   deviation, which is what most software meters give as DIN/IEC peak
+//! - weighted RMS (as in JIS C 5551 / AES6)
+//! - unweighted peak (95th percentile), including slow drift
+//! - the speed error from the mean frequency
+
+use crate::error::{Error, Result};
+use crate::filters::Biquad;
+use crate::wavfile::read_wav_segment;
+
+/// Standard test tone frequency of DIN 45507 / IEC 60386 in Hz
+pub const DIN_TONE_HZ: f64 = 3150.0;
+
+/// Sample rate of the demodulated frequency deviation in Hz
+pub const DEMOD_RATE: u32 = 1000;
+
+/// Start of the recording that is skipped while the filters settle, in seconds
+const SETTLE_SECONDS: f64 = 2.0;
+
+/// Shortest recording that gives a meaningful result, after settling
+const MIN_MEASURE_SECONDS: f64 = 3.0;
+
+/// Corners of the weighting filter: three first-order high-passes and one
+/// first-order low-pass; at [`DEMOD_RATE`] within 1 dB of the IEC 60386 table
+/// from 0.2 to 100 Hz
+const WEIGHTING_HIGH_PASS_HZ: f64 = 0.63;
+const WEIGHTING_LOW_PASS_HZ: f64 = 11.8;
+const WEIGHTING_REFERENCE_HZ: f64 = 4.0;
+
+/// Quantile used for the "2 sigma" peak values
+const PEAK_QUANTILE: f64 = 0.95;
+
+/// Result of a wow and flutter measurement
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WowFlutter {
+    /// Nominal tone frequency in Hz
+    pub nominal_frequency: f64,
+    /// Mean measured tone frequency in Hz
+    pub mean_frequency: f64,
+    /// Weighted peak (2 sigma) deviation in percent
+    pub weighted_peak: f64,
+    /// Weighted RMS deviation in percent
+    pub weighted_rms: f64,
+    /// Unweighted peak (2 sigma) deviation in percent
+    pub unweighted_peak: f64,
+    /// Length of the measured part in seconds
+    pub duration: f64,
+}
+
+impl WowFlutter {
+    /// Deviation of the mean frequency from the nominal one in percent
+    pub fn speed_error(&self) -> f64 {
+        (self.mean_frequency / self.nominal_frequency - 1.0) * 100.0
+    }
+}
+
+/// First-order section from the bilinear transform
+fn first_order(corner: f64, sample_rate: u32, high_pass: bool) -> Biquad {
+    let k = (std::f64::consts::PI * corner / sample_rate as f64).tan();
+    if high_pass {
+        Biquad::new(1.0, -1.0, 0.0, 1.0 + k, k - 1.0, 0.0)
+    } else {
+        Biquad::new(k, k, 0.0, 1.0 + k, k - 1.0, 0.0)
+    }
+}
+
+/// DIN/IEC weighting filter for a deviation signal at `sample_rate`,
+/// with unity gain at 4 Hz
+pub struct WeightingFilter {
+    sections: Vec<Biquad>,
+    gain: f64,
+    sample_rate: u32,
+}
+
+impl WeightingFilter {
+    pub fn new(sample_rate: u32) -> Self {
+        let mut sections: Vec<Biquad> = (0..3)
+            .map(|_| first_order(WEIGHTING_HIGH_PASS_HZ, sample_rate, true))
+            .collect();
+        sections.push(first_order(WEIGHTING_LOW_PASS_HZ, sample_rate, false));
+        let mut filter = WeightingFilter { sections, gain: 1.0, sample_rate };
+        filter.gain = 1.0 / filter.magnitude(WEIGHTING_REFERENCE_HZ);
+        filter
+    }
+
+    /// Magnitude of the response at `frequency` Hz
+    pub fn magnitude(&self, frequency: f64) -> f64 {
+        self.sections
+            .iter()
+            .map(|s| s.magnitude(frequency, self.sample_rate))
+            .product::<f64>()
+            * self.gain
+    }
+
+    pub fn process(&mut self, samples: &mut [f64]) {
+        for sample in samples.iter_mut() {
+            let mut y = *sample * self.gain;
+            for section in &mut self.sections {
+                y = section.process_sample(y);
+            }
+            *sample = y;
+        }
+    }
+}
+
+/// Band-pass around the test tone (RBJ cookbook, constant peak gain)
+fn tone_band_pass(frequency: f64, sample_rate: u32) -> Biquad {
+    let w0 = 2.0 * std::f64::consts::PI * frequency / sample_rate as f64;
+    let alpha = w0.sin() / (2.0 * 2.0);
+    Biquad::new(alpha, 0.0, -alpha, 1.0 + alpha, -2.0 * w0.cos(), 1.0 - alpha)
+}
+
+/// Times (in samples) of the interpolated positive-going zero crossings
+fn zero_crossings(samples: &[f64]) -> Vec<f64> {
+    samples
+        .windows(2)
+        .enumerate()
+        .filter(|(_, w)| w[0] < 0.0 && w[1] >= 0.0)
+        .map(|(i, w)| i as f64 + w[0] / (w[0] - w[1]))
+        .collect()
+}
+
+fn quantile(values: &[f64], q: f64) -> f64 {
+    let mut sorted: Vec<f64> = values.iter().map(|v| v.abs()).collect();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    sorted[((sorted.len() - 1) as f64 * q).round() as usize]
+}
+
+/// Measure wow and flutter of a mono test tone recording
+pub fn analyze(samples: &[f64], sample_rate: u32, nominal_frequency: f64) -> Result<WowFlutter> {
+    let rate = sample_rate as f64;
+    if nominal_frequency <= 0.0 || nominal_frequency >= rate / 4.0 {
+        return Err(Error::Config(format!(
+            "Test tone of {} Hz cannot be measured at {} Hz",
+            nominal_frequency, sample_rate
+        )));
+    }
+
+    let mut band_pass = tone_band_pass(nominal_frequency, sample_rate);
+    let filtered: Vec<f64> = samples.iter().map(|&s| band_pass.process_sample(s)).collect();
+
+    // Frequency of each cycle, at the middle of the cycle
+    let crossings = zero_crossings(&filtered);
+    let cycles: Vec<(f64, f64)> = crossings
+        .windows(2)
+        .map(|c| ((c[0] + c[1]) / 2.0 / rate, rate / (c[1] - c[0])))
+        .collect();
+    let measured_seconds = cycles.last().map_or(0.0, |c| c.0) - SETTLE_SECONDS;
+    if measured_seconds < MIN_MEASURE_SECONDS {
+        return Err(Error::Decode(format!(
+            "Too few cycles of the {} Hz test tone (need at least {:.0} s of tone)",
+            nominal_frequency,
+            SETTLE_SECONDS + MIN_MEASURE_SECONDS
+        )));
+    }
+    let mean_frequency = cycles.iter().map(|c| c.1).sum::<f64>() / cycles.len() as f64;
+    if ((mean_frequency - nominal_frequency) / nominal_frequency).abs() > 0.1 {
+        return Err(Error::Decode(format!(
+            "Recording does not contain a {} Hz test tone (measured {:.0} Hz)",
+            nominal_frequency, mean_frequency
+        )));
+    }
+
+    // Relative deviation, linearly interpolated onto a uniform grid
+    let start = cycles[0].0;
+    let end = cycles[cycles.len() - 1].0;
+    let step = 1.0 / DEMOD_RATE as f64;
+    let mut deviation = Vec::with_capacity(((end - start) / step) as usize + 1);
+    let mut index = 0;
+    let mut t = start;
+    while t <= end {
+        while cycles[index + 1].0 < t {
+            index += 1;
+        }
+        let (t0, f0) = cycles[index];
+        let (t1, f1) = cycles[index + 1];
+        let f = f0 + (f1 - f0) * (t - t0) / (t1 - t0);
+        deviation.push(f / mean_frequency - 1.0);
+        t += step;
+    }
+
+    let mut weighted = deviation.clone();
+    WeightingFilter::new(DEMOD_RATE).process(&mut weighted);
+    let settle = ((SETTLE_SECONDS - start).max(0.0) / step) as usize;
+    let weighted = &weighted[settle..];
+    let unweighted = &deviation[settle..];
+
+    let rms = (weighted.iter().map(|d| d * d).sum::<f64>() / weighted.len() as f64).sqrt();
+    Ok(WowFlutter {
+        nominal_frequency,
+        mean_frequency,
+        weighted_peak: quantile(weighted, PEAK_QUANTILE) * 100.0,
+        weighted_rms: rms * 100.0,
+        unweighted_peak: quantile(unweighted, PEAK_QUANTILE) * 100.0,
+        duration: weighted.len() as f64 * step,
+    })
+}
+
+/// Measure wow and flutter of a test tone recording; channels are mixed to mono
+pub fn analyze_wav(path: &str, nominal_frequency: f64) -> Result<WowFlutter> {
+    let (header, audio) = read_wav_segment(path, 0.0, f64::INFINITY).map_err(Error::Decode)?;
+    let format = header
+        .sample_format()
+        .ok_or_else(|| Error::Decode(format!("Unsupported bit depth: {}", header.bits_per_sample)))?;
+    let scale = format.max_value() * audio.len().max(1) as f64;
+    let frames = audio.iter().map(|ch| ch.len()).min().unwrap_or(0);
+    let mono: Vec<f64> = (0..frames)
+        .map(|i| audio.iter().map(|ch| ch[i] as f64).sum::<f64>() / scale)
+        .collect();
+    analyze(&mono, header.sample_rate, nominal_frequency)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test tone with sinusoidal frequency modulation of `depth` (relative)
+    fn modulated_tone(rate: u32, carrier: f64, depth: f64, modulation: f64, seconds: f64) -> Vec<f64> {
+        let mut phase = 0.0;
+        (0..(seconds * rate as f64) as usize)
+            .map(|n| {
+                let t = n as f64 / rate as f64;
+                let f = carrier * (1.0 + depth * (2.0 * std::f64::consts::PI * modulation * t).sin());
+                phase += 2.0 * std::f64::consts::PI * f / rate as f64;
+                0.5 * phase.sin()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_weighting_curve() {
+        let filter = WeightingFilter::new(DEMOD_RATE);
+        // IEC 60386 table
+        for (frequency, expected_db) in [(0.315, -19.7), (1.0, -4.2), (4.0, 0.0), (10.0, -2.1), (40.0, -10.4), (100.0, -17.3)] {
+            let db = 20.0 * filter.magnitude(frequency).log10();
+            assert!((db - expected_db).abs() < 1.0, "{} Hz: {:.2} dB", frequency, db);
+        }
+    }
+
+    #[test]
+    fn test_measure_flutter() {
+        let rate = 48000;
+        // 0.1% flutter at 4 Hz, where the weighting has unity gain
+        let tone = modulated_tone(rate, DIN_TONE_HZ, 0.001, 4.0, 8.0);
+        let result = analyze(&tone, rate, DIN_TONE_HZ).unwrap();
+        assert!(result.speed_error().abs() < 0.01, "{:?}", result);
+        assert!((result.weighted_peak - 0.1).abs() < 0.005, "{:?}", result);
+        assert!((result.weighted_rms - 0.0707).abs() < 0.004, "{:?}", result);
+        assert!(result.duration > 5.5);
+
+        // 0.5% wow at 0.5 Hz is mostly weighted away, but shows unweighted
+        let tone = modulated_tone(rate, DIN_TONE_HZ * 1.01, 0.005, 0.5, 8.0);
+        let result = analyze(&tone, rate, DIN_TONE_HZ).unwrap();
+        assert!((result.speed_error() - 1.0).abs() < 0.02, "{:?}", result);
+        assert!(result.weighted_peak < 0.3, "{:?}", result);
+        assert!((result.unweighted_peak - 0.5).abs() < 0.03, "{:?}", result);
+
+        assert!(analyze(&tone[..48000], rate, DIN_TONE_HZ).is_err());
+        assert!(analyze(&tone, rate, 1000.0).is_err());
+    }
+}
@@ -0,0 +1,338 @@
+// Web UI of autorecd: follows the event stream of the daemon (polling the
+// control API while it is not available) and drives the boundary review.
+
+"use strict";
+
+const METER_RANGE_DB = 60;
+const HOT_DB = -6;
+const LEVEL_INTERVAL_MS = 100;
+const STATUS_INTERVAL_MS = 1000;
+const SESSIONS_INTERVAL_MS = 10000;
+const RECONNECT_INTERVAL_MS = 5000;
+// Seconds played before a boundary
+const PRE_ROLL = 3;
+
+const $ = (id) => document.getElementById(id);
+
+function formatTime(seconds) {
+  if (seconds == null || !isFinite(seconds)) {
+    return "";
+  }
+  const minutes = Math.floor(seconds / 60);
+  const rest = (seconds - minutes * 60).toFixed(2).padStart(5, "0");
+  return `${String(minutes).padStart(2, "0")}:${rest}`;
+}
+
+function parseTime(text) {
+  const parts = text.trim().split(":");
+  const seconds = parts.reduce((total, part) => total * 60 + Number(part), 0);
+  return isFinite(seconds) && seconds >= 0 ? seconds : null;
+}
+
+function fileUrl(kind, file) {
+  return `/files/${kind}?file=${encodeURIComponent(file)}`;
+}
+
+function baseName(path) {
+  return path.split("/").pop();
+}
+
+async function api(method, path, body) {
+  const options = { method };
+  if (body !== undefined) {
+    options.body = JSON.stringify(body);
+    options.headers = { "Content-Type": "application/json" };
+  }
+  const response = await fetch(path, options);
+  const data = await response.json();
+  if (!response.ok) {
+    throw new Error(data.error || response.statusText);
+  }
+  return data;
+}
+
+// ---- Recorder state and VU meters ----
+
+function fraction(db) {
+  return Math.min(Math.max((db + METER_RANGE_DB) / METER_RANGE_DB, 0), 1);
+}
+
+function showLevels(levels) {
+  const meters = $("meters");
+  while (meters.children.length > levels.length) {
+    meters.lastChild.remove();
+  }
+  levels.forEach((channel, i) => {
+    let meter = meters.children[i];
+    if (!meter) {
+      meter = document.createElement("div");
+      meter.className = "meter";
+      meter.innerHTML = `<span>${i + 1}</span><div class="bar"><div class="level"></div><div class="peak"></div></div><span class="value"></span>`;
+      meters.appendChild(meter);
+    }
+    const level = meter.querySelector(".level");
+    level.style.width = `${fraction(channel.db) * 100}%`;
+    level.classList.toggle("hot", channel.db > HOT_DB);
+    meter.querySelector(".peak").style.left = `${fraction(channel.max_peak_db) * 100}%`;
+    meter.querySelector(".value").textContent = channel.has_clipped ? "CLIP" : `${channel.db.toFixed(1)} dB`;
+    meter.classList.toggle("clip", channel.has_clipped);
+  });
+}
+
+function showStatus(status) {
+  const state = $("state");
+  state.textContent = status.state;
+  state.className = `state ${status.state}`;
+  $("file").textContent = status.current_file || "";
+  const album = status.album;
+  $("album").textContent = album ? `${album.artist} - ${album.album}${album.year ? ` (${album.year})` : ""}` : "";
+  $("error").textContent = status.error || "";
+}
+
+async function refreshStatus() {
+  try {
+    showStatus(await api("GET", "/status"));
+  } catch (e) {
+    showStatus({ state: "offline", error: `Daemon not reachable: ${e.message}` });
+  }
+}
+
+// Polling is only used while the event stream is closed
+let streaming = false;
+
+async function pollLevels() {
+  if (!streaming) {
+    try {
+      showLevels(await api("GET", "/levels"));
+    } catch (e) {
+      // The daemon is restarting; the status poll reports it
+    }
+  }
+  setTimeout(pollLevels, LEVEL_INTERVAL_MS);
+}
+
+async function pollStatus() {
+  if (!streaming) {
+    await refreshStatus();
+  }
+  setTimeout(pollStatus, STATUS_INTERVAL_MS);
+}
+
+function handleEvent(event) {
+  switch (event.event) {
+    case "levels":
+      showLevels(event.channels);
+      break;
+    case "recording_stopped":
+    case "identified":
+      refreshStatus();
+      loadSessions();
+      break;
+    case "recording_started":
+      $("song").textContent = "";
+      refreshStatus();
+      break;
+    case "song_boundary":
+      $("song").textContent = `Song ${event.song}`;
+      break;
+    default:
+      // state, source_lost, source_back
+      refreshStatus();
+  }
+}
+
+function openEvents() {
+  const protocol = location.protocol === "https:" ? "wss:" : "ws:";
+  const socket = new WebSocket(`${protocol}//${location.host}/events`);
+  socket.onopen = () => {
+    streaming = true;
+  };
+  socket.onmessage = (message) => handleEvent(JSON.parse(message.data));
+  socket.onclose = () => {
+    streaming = false;
+    setTimeout(openEvents, RECONNECT_INTERVAL_MS);
+  };
+}
+
+document.querySelectorAll("button[data-command]").forEach((button) => {
+  button.addEventListener("click", async () => {
+    try {
+      await api("POST", `/${button.dataset.command}`);
+      await refreshStatus();
+    } catch (e) {
+      $("error").textContent = e.message;
+    }
+  });
+});
+
+// ---- Recordings ----
+
+function link(href, text) {
+  const a = document.createElement("a");
+  a.href = href;
+  a.target = "_blank";
+  a.textContent = text;
+  return a;
+}
+
+function button(text, action) {
+  const b = document.createElement("button");
+  b.textContent = text;
+  b.addEventListener("click", action);
+  return b;
+}
+
+function cell(row, text, className) {
+  const td = row.insertCell();
+  td.textContent = text;
+  if (className) {
+    td.className = className;
+  }
+  return td;
+}
+
+async function loadSessions() {
+  let sessions;
+  try {
+    sessions = await api("GET", "/sessions");
+  } catch (e) {
+    $("session-list").innerHTML = "";
+    const row = $("session-list").insertRow();
+    cell(row, `Catalog not available: ${e.message}`).colSpan = 5;
+    return;
+  }
+  const list = $("session-list");
+  list.innerHTML = "";
+  for (const session of sessions) {
+    const row = list.insertRow();
+    if (!session.exists) {
+      row.className = "missing";
+    }
+    cell(row, session.started || "");
+    const name = session.artist && session.album
+      ? `${session.artist} - ${session.album}${session.side ? ` (Side ${session.side})` : ""}`
+      : baseName(session.file);
+    cell(row, name).title = session.file;
+    cell(row, formatTime(session.duration), "n");
+    cell(row, session.tracks || "", "n");
+    const actions = cell(row, "", "actions");
+    if (!session.exists) {
+      continue;
+    }
+    if (session.report) {
+      actions.appendChild(link(fileUrl("report", session.file), "Report"));
+    }
+    if (session.spectrogram) {
+      actions.appendChild(link(fileUrl("spectrogram", session.file), "Spectrogram"));
+    }
+    if (session.cue) {
+      actions.appendChild(button("Review", () => openReview(session)));
+    }
+  }
+}
+
+// ---- Boundary review ----
+
+const review = { file: null, duration: null, boundaries: [], titles: [] };
+
+function renderBoundaries() {
+  const body = $("boundaries");
+  body.innerHTML = "";
+  review.boundaries.sort((a, b) => a - b);
+  review.boundaries.forEach((position, i) => {
+    const row = body.insertRow();
+    cell(row, i + 2, "n");
+    cell(row, review.titles[i + 1] || "");
+    const input = document.createElement("input");
+    input.className = "time";
+    input.value = formatTime(position);
+    input.addEventListener("change", () => {
+      const seconds = parseTime(input.value);
+      if (seconds != null) {
+        review.boundaries[i] = seconds;
+      }
+      renderBoundaries();
+    });
+    row.insertCell().appendChild(input);
+    const actions = cell(row, "", "actions");
+    actions.appendChild(button("Play", () => play(review.boundaries[i] - PRE_ROLL)));
+    actions.appendChild(button("-0.5 s", () => nudge(i, -0.5)));
+    actions.appendChild(button("+0.5 s", () => nudge(i, 0.5)));
+    actions.appendChild(button("Delete", () => {
+      review.boundaries.splice(i, 1);
+      renderBoundaries();
+    }));
+  });
+}
+
+function nudge(i, seconds) {
+  review.boundaries[i] = Math.max(0, review.boundaries[i] + seconds);
+  renderBoundaries();
+  play(review.boundaries[i] - PRE_ROLL);
+}
+
+function play(position) {
+  const player = $("player");
+  player.currentTime = Math.max(0, position);
+  player.play();
+}
+
+async function openReview(session) {
+  let data;
+  try {
+    data = await api("GET", `/boundaries?file=${encodeURIComponent(session.file)}`);
+  } catch (e) {
+    $("error").textContent = e.message;
+    return;
+  }
+  review.file = data.file;
+  review.duration = data.duration;
+  review.titles = data.tracks.map((t) => t.title || "");
+  review.boundaries = data.tracks.slice(1).map((t) => t.start);
+  $("review-title").textContent = baseName(data.file);
+  $("player").src = fileUrl("audio", data.file);
+  const image = $("spectrogram");
+  image.hidden = !session.spectrogram;
+  if (session.spectrogram) {
+    image.src = fileUrl("spectrogram", data.file);
+  }
+  $("review-status").textContent = data.reviewed ? "The boundaries were reviewed before." : "";
+  $("review").hidden = false;
+  renderBoundaries();
+  $("review").scrollIntoView();
+}
+
+// Click on the spectrogram to play from that position
+$("spectrogram").addEventListener("click", (event) => {
+  const player = $("player");
+  const duration = review.duration || player.duration;
+  if (duration) {
+    const rect = event.target.getBoundingClientRect();
+    play((event.clientX - rect.left) / rect.width * duration);
+  }
+});
+
+$("add-boundary").addEventListener("click", () => {
+  review.boundaries.push($("player").currentTime);
+  renderBoundaries();
+});
+
+$("save-boundaries").addEventListener("click", async () => {
+  try {
+    await api("POST", "/boundaries", { file: review.file, boundaries: review.boundaries });
+    $("review-status").textContent = "Saved, creating the CUE file...";
+  } catch (e) {
+    $("review-status").textContent = e.message;
+  }
+});
+
+$("close-review").addEventListener("click", () => {
+  $("player").pause();
+  $("review").hidden = true;
+});
+
+openEvents();
+pollLevels();
+pollStatus();
+loadSessions();
+setInterval(loadSessions, SESSIONS_INTERVAL_MS);